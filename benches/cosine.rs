@@ -1,6 +1,6 @@
 use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
 use rec_rsys::benchmarks::{config, testing_tools::create_vector};
-use rec_rsys::utils::{dot, euclidean_norm, squared_diff_sum};
+use rec_rsys::utils::dot;
 
 use ndarray::prelude::*;
 
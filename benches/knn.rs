@@ -1,7 +1,8 @@
-use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
 use rec_rsys::algorithms::knn::KNN;
 use rec_rsys::benchmarks::{config, testing_tools::create_vector};
 use rec_rsys::models::Item;
+use rec_rsys::scratch::Scratch;
 
 fn knn_bench(c: &mut Criterion) {
     let mut bench = c.benchmark_group("knn");
@@ -25,6 +26,17 @@ fn knn_bench(c: &mut Criterion) {
             ),
             |b| b.iter(|| result.result()),
         );
+        let mut scratch = Scratch::new();
+        bench.bench_function(
+            BenchmarkId::new(
+                "search_with_scratch",
+                format!(
+                    "vector_size{}-neighbors_pool{}-num_neighbors{}",
+                    vector_size, neighbors_pool, num_neighbors
+                ),
+            ),
+            |b| b.iter(|| result.search_with(&mut scratch)),
+        );
     }
     bench.finish();
 }
@@ -0,0 +1,46 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use rec_rsys::algorithms::item_knn::ItemKNN;
+use rec_rsys::algorithms::knn::KNNConfig;
+use rec_rsys::algorithms::mf::{MFConfig, MatrixFactorization};
+use rec_rsys::benchmarks::{config, testing_tools::create_ratings};
+use rec_rsys::recommender::Recommender;
+
+fn recommenders_bench(c: &mut Criterion) {
+    let mut bench = c.benchmark_group("recommenders");
+    config::set_default_benchmark_configs(&mut bench);
+    for (num_users, num_items, ratings_per_user) in [(100, 100, 10), (1_000, 500, 20)] {
+        let dataset = create_ratings(num_users, num_items, ratings_per_user, 42);
+        let parameter = format!("users{}-items{}", num_users, num_items);
+        bench.bench_function(BenchmarkId::new("item_knn-fit", &parameter), |b| {
+            b.iter(|| {
+                ItemKNN::new(KNNConfig::default().set_num_neighbors(20))
+                    .fit(&dataset)
+                    .unwrap()
+            })
+        });
+        bench.bench_function(BenchmarkId::new("mf-fit", &parameter), |b| {
+            b.iter(|| {
+                MatrixFactorization::new(MFConfig::default().set_num_epochs(5))
+                    .fit(&dataset)
+                    .unwrap()
+            })
+        });
+        let mut model = MatrixFactorization::new(MFConfig::default());
+        model.fit(&dataset).unwrap();
+        bench.bench_function(BenchmarkId::new("mf-recommend", &parameter), |b| {
+            b.iter(|| model.recommend(0, 10))
+        });
+    }
+    bench.finish();
+}
+
+#[cfg(not(target_os = "windows"))]
+criterion_group! {
+    name = benches;
+    config = config::get_default_profiling_configs();
+    targets = recommenders_bench
+}
+#[cfg(target_os = "windows")]
+criterion_group!(benches, recommenders_bench,);
+
+criterion_main!(benches);
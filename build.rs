@@ -1,3 +1,18 @@
+use std::process::Command;
+
 fn main() {
-    println!("cargo:rustc-link-lib=openblas"); // Link to OpenBLAS
+    println!("cargo:rustc-env=REC_RSYS_GIT_DESCRIBE={}", git_describe());
+}
+
+/// Version of the sources being built, empty when they are not in a git checkout
+/// (e.g. when building from crates.io).
+fn git_describe() -> String {
+    Command::new("git")
+        .args(["describe", "--tags", "--always", "--dirty"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|describe| describe.trim().to_string())
+        .unwrap_or_default()
 }
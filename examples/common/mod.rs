@@ -0,0 +1,34 @@
+//! Loads the small bundled dataset the examples run on.
+// Not every example uses every loader.
+#![allow(dead_code)]
+use rec_rsys::catalog::{AttributeType, AttributeValue, Attributes, ItemCatalog, Schema};
+use rec_rsys::dataset::{CsvOptions, Dataset};
+use rec_rsys::errors::Result;
+
+const MOVIES: &str = include_str!("../data/movies.csv");
+const RATINGS: &str = include_str!("../data/ratings.tsv");
+
+/// Reads the genres of the `movie_id,genres,year` lines into a catalog. The year
+/// is left out: as a raw number it would dominate the distances.
+pub fn load_catalog() -> Result<ItemCatalog> {
+    let mut catalog =
+        ItemCatalog::new(Schema::new().attribute("genres", AttributeType::Tags, true));
+    for line in MOVIES.lines().skip(1) {
+        let columns: Vec<&str> = line.split(',').collect();
+        let genres = columns[1].split('|').map(str::to_string).collect();
+        let attributes =
+            Attributes::from([("genres".to_string(), AttributeValue::Tags(genres))]);
+        catalog.insert(columns[0].parse().unwrap_or_default(), attributes)?;
+    }
+    Ok(catalog)
+}
+
+/// Reads the `user_id\titem_id\trating\ttimestamp` lines, the MovieLens 100K
+/// layout.
+pub fn load_ratings() -> Result<Dataset> {
+    let options = CsvOptions {
+        delimiter: "\t".to_string(),
+        has_header: false,
+    };
+    Dataset::parse_csv(RATINGS, &options)
+}
@@ -0,0 +1,37 @@
+//! Recommends movies from their genres only: the movies are described in an
+//! [`ItemCatalog`], encoded as multi-hot vectors, and every user is represented by
+//! the mean of the movies they liked.
+//!
+//! Run it with `cargo run --example content_based --features serde`.
+mod common;
+
+use rec_rsys::algorithms::content_based::ContentBased;
+use rec_rsys::algorithms::knn::KNNConfig;
+use rec_rsys::errors::Result;
+use rec_rsys::profiles::Aggregation;
+use rec_rsys::recommender::{Recommendation, Recommender};
+
+/// The top 5 movies for a user, built from the genres of the movies they rated 4
+/// or more. The movies they rated lower are not recommended either.
+pub fn run(user_id: u32) -> Result<Vec<Recommendation>> {
+    let items = common::load_catalog()?.to_items();
+    let mut model = ContentBased::new(items, KNNConfig::default(), Aggregation::Mean)
+        .set_min_rating(4.0);
+    model.fit(&common::load_ratings()?)?;
+    Ok(model.recommend(user_id, 5))
+}
+
+fn main() -> Result<()> {
+    let catalog = common::load_catalog()?;
+    for recommendation in run(1)? {
+        let genres = catalog
+            .attribute(recommendation.item_id, "genres")
+            .map(|genres| genres.labels().join(", "))
+            .unwrap_or_default();
+        println!(
+            "movie {:>2} ({}) score {:.3}",
+            recommendation.item_id, genres, recommendation.score
+        );
+    }
+    Ok(())
+}
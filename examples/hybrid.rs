@@ -0,0 +1,88 @@
+//! Blends a collaborative and a content-based recommender: the scores of each one
+//! are scaled to `[0, 1]` and summed with a weight, so movies liked by similar users
+//! and movies of the genres the user likes both come up.
+//!
+//! Run it with `cargo run --example hybrid --features serde`.
+mod common;
+
+use std::collections::HashMap;
+
+use rec_rsys::algorithms::content_based::ContentBased;
+use rec_rsys::algorithms::knn::KNNConfig;
+use rec_rsys::algorithms::mf::{MFConfig, MatrixFactorization};
+use rec_rsys::dataset::Dataset;
+use rec_rsys::errors::Result;
+use rec_rsys::profiles::Aggregation;
+use rec_rsys::recommender::{Recommendation, Recommender};
+
+/// Weighted sum of the min-max scaled scores of two recommenders.
+pub struct Hybrid<A, B> {
+    pub collaborative: A,
+    pub content: B,
+    /// Weight of the collaborative scores, the content ones get `1 - weight`.
+    pub weight: f32,
+    /// How many candidates are asked to each recommender.
+    pub candidates: usize,
+}
+
+impl<A: Recommender, B: Recommender> Recommender for Hybrid<A, B> {
+    fn fit(&mut self, dataset: &Dataset) -> Result<()> {
+        self.collaborative.fit(dataset)?;
+        self.content.fit(dataset)
+    }
+
+    fn recommend(&self, user_id: u32, num_items: usize) -> Vec<Recommendation> {
+        let mut scores: HashMap<u32, f32> = HashMap::new();
+        let sources: [(&dyn Recommender, f32); 2] = [
+            (&self.collaborative, self.weight),
+            (&self.content, 1.0 - self.weight),
+        ];
+        for (recommender, weight) in sources {
+            let recommendations = recommender.recommend(user_id, self.candidates);
+            let max = recommendations.first().map_or(0.0, |r| r.score);
+            let min = recommendations.last().map_or(0.0, |r| r.score);
+            for r in recommendations {
+                let scaled = if max > min { (r.score - min) / (max - min) } else { 1.0 };
+                *scores.entry(r.item_id).or_default() += weight * scaled;
+            }
+        }
+        let mut blended: Vec<Recommendation> = scores
+            .into_iter()
+            .map(|(item_id, score)| Recommendation { item_id, score })
+            .collect();
+        blended
+            .sort_by(|a, b| b.score.total_cmp(&a.score).then(a.item_id.cmp(&b.item_id)));
+        blended.truncate(num_items);
+        blended
+    }
+}
+
+pub fn run(user_id: u32, weight: f32) -> Result<Vec<Recommendation>> {
+    let catalog = common::load_catalog()?;
+    let ratings = common::load_ratings()?;
+
+    let mut hybrid = Hybrid {
+        collaborative: MatrixFactorization::new(MFConfig::default().set_num_factors(8)),
+        // The genres of the movies rated 4 or more make the profiles.
+        content: ContentBased::new(
+            catalog.to_items(),
+            KNNConfig::default(),
+            Aggregation::Mean,
+        )
+        .set_min_rating(4.0),
+        weight,
+        candidates: 20,
+    };
+    hybrid.fit(&ratings)?;
+    Ok(hybrid.recommend(user_id, 5))
+}
+
+fn main() -> Result<()> {
+    for recommendation in run(1, 0.5)? {
+        println!(
+            "movie {:>2} score {:.3}",
+            recommendation.item_id, recommendation.score
+        );
+    }
+    Ok(())
+}
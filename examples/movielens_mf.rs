@@ -0,0 +1,79 @@
+//! Trains a matrix factorization on ratings in the MovieLens 100K layout and
+//! evaluates it on held-out ratings, both the rating errors and the top-N lists.
+//!
+//! Run it with `cargo run --example movielens_mf --features serde`, point it to the
+//! real `ml-100k/u.data` by passing its path as the first argument.
+mod common;
+
+use std::collections::HashMap;
+
+use rec_rsys::accuracy::{mae, rmse};
+use rec_rsys::algorithms::mf::{MFConfig, MatrixFactorization};
+use rec_rsys::algorithms::regularization::Regularization;
+use rec_rsys::dataset::{CsvOptions, Dataset};
+use rec_rsys::errors::Result;
+use rec_rsys::evaluation::{TopNEvaluator, TopNReport};
+use rec_rsys::recommender::Recommender;
+
+/// Rating errors and top-N metrics on the test ratings.
+pub struct Summary {
+    pub rmse: f32,
+    pub mae: f32,
+    pub report: TopNReport,
+    /// Training loss of every epoch.
+    pub train_losses: Vec<f64>,
+}
+
+pub fn run(dataset: &Dataset) -> Result<Summary> {
+    let (train, test) = dataset.split_random(0.2, 42);
+    let config = MFConfig::default()
+        .set_num_factors(8)
+        .set_num_epochs(30)
+        .set_learning_rate(0.005)
+        .set_regularization(Regularization::L2 { lambda: 0.05 });
+    let mut model = MatrixFactorization::new(config);
+    model.fit_with_validation(&train, Some(&test))?;
+
+    let (predicted, actual): (Vec<f32>, Vec<f32>) = test
+        .ratings
+        .iter()
+        .filter_map(|r| Some((model.predict(r.user_id, r.item_id)?, r.rating)))
+        .unzip();
+    let relevant: HashMap<u32, Vec<u32>> = test.filter(|r| r.rating >= 4.0).user_items();
+    let report = TopNEvaluator::new(10).evaluate(&relevant, |user_id, n| {
+        model
+            .recommend(user_id, n)
+            .iter()
+            .map(|r| r.item_id)
+            .collect()
+    });
+    Ok(Summary {
+        rmse: rmse(&predicted, &actual),
+        mae: mae(&predicted, &actual),
+        report,
+        train_losses: model.history().train_losses(),
+    })
+}
+
+fn main() -> Result<()> {
+    let dataset = match std::env::args().nth(1) {
+        Some(path) => Dataset::from_csv(
+            path,
+            &CsvOptions {
+                delimiter: "\t".to_string(),
+                has_header: false,
+            },
+        )?,
+        None => common::load_ratings()?,
+    };
+    let summary = run(&dataset)?;
+    println!("rmse {:.4} mae {:.4}", summary.rmse, summary.mae);
+    println!(
+        "hit rate@10 {:.3} precision@10 {:.3} recall@10 {:.3} ndcg@10 {:.3}",
+        summary.report.hit_rate,
+        summary.report.precision,
+        summary.report.recall,
+        summary.report.ndcg
+    );
+    Ok(())
+}
@@ -0,0 +1,262 @@
+//! Derive macros of [rec_rsys](https://docs.rs/rec_rsys), re-exported by its
+//! `derive` feature: use them through `rec_rsys::models`.
+//!
+//! ```
+//! use rec_rsys::models::{ItemAdapter, OneHot};
+//!
+//! #[derive(OneHot)]
+//! enum Exchange {
+//!     Nasdaq,
+//!     Nyse,
+//! }
+//!
+//! #[derive(ItemAdapter)]
+//! struct Company {
+//!     id: u32,
+//!     growth: f32,
+//!     employees: u32,
+//!     #[item(one_hot)]
+//!     exchange: Exchange,
+//!     #[item(one_hot = ["Technology", "Energy", "Utilities"])]
+//!     sector: String,
+//!     #[item(skip)]
+//!     ticker: String,
+//! }
+//!
+//! let company = Company {
+//!     id: 7,
+//!     growth: 0.5,
+//!     employees: 120,
+//!     exchange: Exchange::Nyse,
+//!     sector: "Energy".to_string(),
+//!     ticker: "ACME".to_string(),
+//! };
+//! let item = company.to_item();
+//! assert_eq!(item.id, 7);
+//! assert_eq!(item.values, [0.5, 120.0, 0.0, 1.0, 0.0, 1.0, 0.0]);
+//! ```
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    parse_macro_input, Data, DeriveInput, Expr, ExprArray, Fields, Ident, Lit, LitStr,
+};
+
+/// # Item adapter
+/// Implements `ItemAdapter` from the fields of a struct, in their order:
+/// * The field named `id`, or marked `#[item(id)]`, is the id of the item. It must
+///   convert into a `u32`.
+/// * Numeric fields are passed through as `f32`.
+/// * `#[item(one_hot)]` fields are encoded with their `OneHot` implementation,
+///   e.g. a derived one for an enum.
+/// * `#[item(one_hot = ["a", "b"])]` fields, strings, are one-hot encoded over the
+///   listed categories, an unknown category giving only zeros.
+/// * `#[item(skip)]` fields are left out.
+///
+/// `get_references` returns no item unless the struct names a method returning
+/// them, `#[item(references = "method")]`.
+#[proc_macro_derive(ItemAdapter, attributes(item))]
+pub fn derive_item_adapter(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    item_adapter(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// # One-hot
+/// Implements `OneHot` for an enum without fields, every variant being a category
+/// in declaration order.
+#[proc_macro_derive(OneHot)]
+pub fn derive_one_hot(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    one_hot(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// How a field of the struct is turned into values.
+enum Encoding {
+    Id,
+    Skip,
+    Value,
+    OneHot,
+    Categories(Vec<LitStr>),
+}
+
+fn item_adapter(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    &input.ident,
+                    "ItemAdapter can only be derived for structs with named fields",
+                ))
+            },
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "ItemAdapter can only be derived for structs",
+            ))
+        },
+    };
+    let mut id = None;
+    let mut values = Vec::new();
+    for field in fields {
+        let name = field.ident.as_ref().expect("named field");
+        let encoding = match field_encoding(&field.attrs)? {
+            Encoding::Value if name == "id" => Encoding::Id,
+            encoding => encoding,
+        };
+        match encoding {
+            Encoding::Id if id.is_some() => {
+                return Err(syn::Error::new_spanned(name, "the item has two ids"))
+            },
+            Encoding::Id => id = Some(name),
+            Encoding::Skip => {},
+            Encoding::Value => values.push(quote! {
+                values.push(self.#name as f32);
+            }),
+            Encoding::OneHot => values.push(quote! {
+                values.extend(::rec_rsys::models::OneHot::one_hot(&self.#name));
+            }),
+            Encoding::Categories(categories) => values.push(quote! {
+                let category: &str = ::core::convert::AsRef::as_ref(&self.#name);
+                values.extend(
+                    [#(#categories),*]
+                        .iter()
+                        .map(|label| if *label == category { 1.0 } else { 0.0 }),
+                );
+            }),
+        }
+    }
+    let id = id.ok_or_else(|| {
+        syn::Error::new_spanned(
+            &input.ident,
+            "the item needs an `id` field or a field marked `#[item(id)]`",
+        )
+    })?;
+    let references = match struct_references(&input.attrs)? {
+        Some(method) => quote! { self.#method() },
+        None => quote! { ::std::vec::Vec::new() },
+    };
+    let ident = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::rec_rsys::models::ItemAdapter for #ident #type_generics
+        #where_clause
+        {
+            fn to_item(&self) -> ::rec_rsys::models::Item {
+                ::rec_rsys::models::Item::new(
+                    ::core::convert::From::from(self.#id),
+                    self.create_values(),
+                    ::core::option::Option::None,
+                )
+            }
+
+            fn create_values(&self) -> ::std::vec::Vec<f32> {
+                let mut values: ::std::vec::Vec<f32> = ::std::vec::Vec::new();
+                #(#values)*
+                values
+            }
+
+            fn get_references(&self) -> ::std::vec::Vec<::rec_rsys::models::Item> {
+                #references
+            }
+        }
+    })
+}
+
+/// Reads the `#[item(...)]` attributes of a field.
+fn field_encoding(attrs: &[syn::Attribute]) -> syn::Result<Encoding> {
+    let mut encoding = Encoding::Value;
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("item")) {
+        attr.parse_nested_meta(|meta| {
+            encoding = if meta.path.is_ident("id") {
+                Encoding::Id
+            } else if meta.path.is_ident("skip") {
+                Encoding::Skip
+            } else if meta.path.is_ident("one_hot") && meta.input.is_empty() {
+                Encoding::OneHot
+            } else if meta.path.is_ident("one_hot") {
+                let array: ExprArray = meta.value()?.parse()?;
+                Encoding::Categories(categories(&array)?)
+            } else {
+                return Err(meta.error("expected `id`, `skip` or `one_hot`"));
+            };
+            Ok(())
+        })?;
+    }
+    Ok(encoding)
+}
+
+/// The string literals of `["a", "b"]`.
+fn categories(array: &ExprArray) -> syn::Result<Vec<LitStr>> {
+    array
+        .elems
+        .iter()
+        .map(|element| match element {
+            Expr::Lit(expr) => match &expr.lit {
+                Lit::Str(category) => Ok(category.clone()),
+                _ => Err(syn::Error::new_spanned(element, "expected a string")),
+            },
+            _ => Err(syn::Error::new_spanned(element, "expected a string")),
+        })
+        .collect()
+}
+
+/// Reads the `#[item(references = "method")]` attribute of the struct.
+fn struct_references(attrs: &[syn::Attribute]) -> syn::Result<Option<Ident>> {
+    let mut references = None;
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("item")) {
+        attr.parse_nested_meta(|meta| {
+            if !meta.path.is_ident("references") {
+                return Err(meta.error("expected `references`"));
+            }
+            let method: LitStr = meta.value()?.parse()?;
+            references = Some(method.parse()?);
+            Ok(())
+        })?;
+    }
+    Ok(references)
+}
+
+fn one_hot(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let variants = match &input.data {
+        Data::Enum(data) => &data.variants,
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "OneHot can only be derived for enums",
+            ))
+        },
+    };
+    if let Some(variant) = variants
+        .iter()
+        .find(|variant| !matches!(variant.fields, Fields::Unit))
+    {
+        return Err(syn::Error::new_spanned(
+            variant,
+            "OneHot can only be derived for enums without fields",
+        ));
+    }
+    let ident = &input.ident;
+    let names = variants.iter().map(|variant| &variant.ident);
+    let indices = 0..variants.len();
+    let num_categories = variants.len();
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::rec_rsys::models::OneHot for #ident #type_generics
+        #where_clause
+        {
+            const NUM_CATEGORIES: usize = #num_categories;
+
+            fn category(&self) -> usize {
+                match self {
+                    #(Self::#names => #indices,)*
+                }
+            }
+        }
+    })
+}
@@ -4,6 +4,10 @@
 //! $x_i =$ predicted rating
 //! <br>
 //! $n =$ number of ratings
+use std::collections::BTreeMap;
+
+use crate::errors::{Error, Result};
+use crate::statistics::{mean, variance, Running};
 
 /// # RMSE (Root Mean Squared Error).
 ///
@@ -14,8 +18,14 @@
 /// ## Returns:
 /// * The Root Mean Squared Error.
 ///
+/// ## Examples:
+/// ```
+/// use rec_rsys::accuracy::rmse;
+/// assert_eq!(rmse(&[3.0, 4.0, 5.0], &[3.0, 2.0, 5.0]), 1.154_700_5);
+/// ```
+///
 #[doc = include_str!("../docs/accuracy/rmse.md")]
-pub fn rmse(predicted: &[f32], actual: &Vec<f32>) -> f32 {
+pub fn rmse(predicted: &[f32], actual: &[f32]) -> f32 {
     mse(predicted, actual).sqrt()
 }
 
@@ -29,13 +39,8 @@ pub fn rmse(predicted: &[f32], actual: &Vec<f32>) -> f32 {
 /// * The Mean Squared Error.
 ///
 #[doc = include_str!("../docs/accuracy/mse.md")]
-pub fn mse(predicted: &[f32], actual: &Vec<f32>) -> f32 {
-    actual
-        .iter()
-        .zip(predicted.iter())
-        .map(|(a, p)| (a - p).powi(2))
-        .sum::<f32>()
-        / actual.len() as f32
+pub fn mse(predicted: &[f32], actual: &[f32]) -> f32 {
+    mean_error(pairs(predicted, actual), |a, p| (a - p).powi(2))
 }
 
 /// # Compute MAE (Mean Absolute Error).
@@ -48,13 +53,216 @@ pub fn mse(predicted: &[f32], actual: &Vec<f32>) -> f32 {
 /// * The Mean Absolute Error.
 ///
 #[doc = include_str!("../docs/accuracy/mae.md")]
-pub fn mae(predicted: &Vec<f32>, actual: &Vec<f32>) -> f32 {
+pub fn mae(predicted: &[f32], actual: &[f32]) -> f32 {
+    mean_error(pairs(predicted, actual), |a, p| (a - p).abs())
+}
+
+/// # Masked RMSE
+/// Root Mean Squared Error between a full matrix of predictions and a sparse
+/// matrix of true ratings, where missing ratings are marked with `NaN`.
+///
+/// ## Parameters:
+/// * `predicted`: The estimated ratings, one row per user.
+/// * `actual`: The true ratings with the same shape, `NaN` where unknown.
+///
+/// ## Returns:
+/// * The Root Mean Squared Error over the known ratings.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::accuracy::masked_rmse;
+/// let predicted = vec![vec![4.0, 3.0], vec![2.0, 5.0]];
+/// let actual = vec![vec![5.0, f32::NAN], vec![f32::NAN, 5.0]];
+/// assert_eq!(masked_rmse(&predicted, &actual), 0.707_106_77);
+/// ```
+///
+#[doc = include_str!("../docs/accuracy/masked_errors.md")]
+pub fn masked_rmse(predicted: &[Vec<f32>], actual: &[Vec<f32>]) -> f32 {
+    masked_mse(predicted, actual).sqrt()
+}
+
+/// # Masked MSE
+/// Mean Squared Error between a full matrix of predictions and a sparse
+/// matrix of true ratings, where missing ratings are marked with `NaN`.
+///
+/// ## Parameters:
+/// * `predicted`: The estimated ratings, one row per user.
+/// * `actual`: The true ratings with the same shape, `NaN` where unknown.
+///
+/// ## Returns:
+/// * The Mean Squared Error over the known ratings.
+///
+#[doc = include_str!("../docs/accuracy/masked_errors.md")]
+pub fn masked_mse(predicted: &[Vec<f32>], actual: &[Vec<f32>]) -> f32 {
+    mean_error(masked_pairs(predicted, actual), |a, p| (a - p).powi(2))
+}
+
+/// # Masked MAE
+/// Mean Absolute Error between a full matrix of predictions and a sparse
+/// matrix of true ratings, where missing ratings are marked with `NaN`.
+///
+/// ## Parameters:
+/// * `predicted`: The estimated ratings, one row per user.
+/// * `actual`: The true ratings with the same shape, `NaN` where unknown.
+///
+/// ## Returns:
+/// * The Mean Absolute Error over the known ratings.
+///
+#[doc = include_str!("../docs/accuracy/masked_errors.md")]
+pub fn masked_mae(predicted: &[Vec<f32>], actual: &[Vec<f32>]) -> f32 {
+    mean_error(masked_pairs(predicted, actual), |a, p| (a - p).abs())
+}
+
+/// # Coefficient of determination (R²)
+/// Proportion of the variance of the true ratings that is explained by the predictions.
+///
+/// ## Parameters:
+/// * `predicted`: The estimated rating.
+/// * `actual`: The true rating.
+///
+/// ## Returns:
+/// * The R² score. `1.0` is a perfect prediction, `0.0` is as good as always
+///   predicting the mean rating and it can be negative for worse predictions.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::accuracy::r2_score;
+/// assert_eq!(r2_score(&[2.5, 0.0, 2.0, 8.0], &[3.0, -0.5, 2.0, 7.0]), 0.948_608_16);
+/// ```
+///
+#[doc = include_str!("../docs/accuracy/r2_score.md")]
+pub fn r2_score(predicted: &[f32], actual: &[f32]) -> f32 {
+    let actual_mean = mean(actual);
+    let residual_sum: f32 = pairs(predicted, actual).map(|(a, p)| (a - p).powi(2)).sum();
+    let total_sum: f32 = actual.iter().map(|a| (a - actual_mean).powi(2)).sum();
+    1.0 - residual_sum / total_sum
+}
+
+/// # Explained variance
+/// Like the R² score but insensitive to a constant bias of the predictions.
+///
+/// ## Parameters:
+/// * `predicted`: The estimated rating.
+/// * `actual`: The true rating.
+///
+/// ## Returns:
+/// * The explained variance score, `1.0` being the best possible value.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::accuracy::explained_variance;
+/// assert_eq!(explained_variance(&[4.0, 5.0, 6.0], &[3.0, 4.0, 5.0]), 1.0);
+/// ```
+///
+#[doc = include_str!("../docs/accuracy/explained_variance.md")]
+pub fn explained_variance(predicted: &[f32], actual: &[f32]) -> f32 {
+    let residuals: Vec<f32> = pairs(predicted, actual).map(|(a, p)| a - p).collect();
+    1.0 - variance(&residuals) / variance(actual)
+}
+
+/// # Per-user RMSE
+/// Computes the RMSE of every user separately and averages them, so every user
+/// weighs the same no matter how many ratings they have.
+///
+/// ## Parameters:
+/// * `predicted`: The estimated rating.
+/// * `actual`: The true rating.
+/// * `users`: The user that gave each rating.
+///
+/// ## Returns:
+/// * The mean of the users' RMSE.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::accuracy::{rmse, rmse_per_user};
+/// let predicted = [3.0, 3.0, 3.0, 3.0, 1.0];
+/// let actual = [3.0, 3.0, 3.0, 3.0, 5.0];
+/// let users = [1, 1, 1, 1, 2];
+/// assert_eq!(rmse(&predicted, &actual), 1.788_854_4);
+/// assert_eq!(rmse_per_user(&predicted, &actual, &users), 2.0);
+/// ```
+///
+#[doc = include_str!("../docs/accuracy/per_user_errors.md")]
+pub fn rmse_per_user(predicted: &[f32], actual: &[f32], users: &[u32]) -> f32 {
+    per_user_error(predicted, actual, users, |a, p| (a - p).powi(2), f32::sqrt)
+}
+
+/// # Per-user MAE
+/// Computes the MAE of every user separately and averages them, so every user
+/// weighs the same no matter how many ratings they have.
+///
+/// ## Parameters:
+/// * `predicted`: The estimated rating.
+/// * `actual`: The true rating.
+/// * `users`: The user that gave each rating.
+///
+/// ## Returns:
+/// * The mean of the users' MAE.
+///
+#[doc = include_str!("../docs/accuracy/per_user_errors.md")]
+pub fn mae_per_user(predicted: &[f32], actual: &[f32], users: &[u32]) -> f32 {
+    per_user_error(predicted, actual, users, |a, p| (a - p).abs(), |e| e)
+}
+
+/// Groups the errors by user, averages them with `error`, maps every user's mean
+/// through `finish` and averages the result over the users.
+fn per_user_error<E, F>(
+    predicted: &[f32],
+    actual: &[f32],
+    users: &[u32],
+    error: E,
+    finish: F,
+) -> f32
+where
+    E: Fn(f32, f32) -> f32,
+    F: Fn(f32) -> f32,
+{
+    let mut by_user: BTreeMap<u32, (f32, usize)> = BTreeMap::new();
+    pairs(predicted, actual)
+        .zip(users.iter())
+        .for_each(|((a, p), user)| {
+            let entry = by_user.entry(*user).or_insert((0.0, 0));
+            entry.0 += error(a, p);
+            entry.1 += 1;
+        });
+    by_user
+        .values()
+        .map(|(sum, count)| finish(sum / *count as f32))
+        .sum::<f32>()
+        / by_user.len() as f32
+}
+
+/// Pairs each true rating with its prediction.
+fn pairs<'a>(
+    predicted: &'a [f32],
+    actual: &'a [f32],
+) -> impl Iterator<Item = (f32, f32)> + 'a {
+    actual.iter().copied().zip(predicted.iter().copied())
+}
+
+/// Pairs each known true rating with its prediction, skipping the `NaN` entries
+/// of `actual`.
+fn masked_pairs<'a>(
+    predicted: &'a [Vec<f32>],
+    actual: &'a [Vec<f32>],
+) -> impl Iterator<Item = (f32, f32)> + 'a {
     actual
         .iter()
-        .zip(predicted)
-        .map(|(a, p)| (a - p).abs())
-        .sum::<f32>()
-        / actual.len() as f32
+        .zip(predicted.iter())
+        .flat_map(|(a, p)| pairs(p, a))
+        .filter(|(a, _)| !a.is_nan())
+}
+
+/// Averages `error(actual, predicted)` over the given pairs.
+fn mean_error<I, F>(pairs: I, error: F) -> f32
+where
+    I: Iterator<Item = (f32, f32)>,
+    F: Fn(f32, f32) -> f32,
+{
+    let (sum, count) = pairs.fold((0.0, 0_usize), |(sum, count), (a, p)| {
+        (sum + error(a, p), count + 1)
+    });
+    sum / count as f32
 }
 
 /// # Compute ARHR (Average reciprocal hit rate)
@@ -69,8 +277,13 @@ pub fn mae(predicted: &Vec<f32>, actual: &Vec<f32>) -> f32 {
 /// * Average reciprocal hit rate.
 ///
 #[doc = include_str!("../docs/accuracy/arhr.md")]
+#[deprecated(
+    since = "1.1.0",
+    note = "the integer division truncates every reciprocal rank, use `HitCounter::arhr`"
+)]
 pub fn arhr(hits_ranks: Vec<u32>, number_users: u32) -> u32 {
-    hits_ranks.iter().map(|rank: &u32| 1 / rank).sum::<u32>() / number_users
+    let sum: u64 = hits_ranks.iter().map(|rank| 1 / *rank as u64).sum();
+    (sum / number_users as u64) as u32
 }
 
 /// # Compute Hit Rate
@@ -83,6 +296,10 @@ pub fn arhr(hits_ranks: Vec<u32>, number_users: u32) -> u32 {
 /// * Hit Rate.
 ///
 #[doc = include_str!("../docs/accuracy/hit_rate.md")]
+#[deprecated(
+    since = "1.1.0",
+    note = "the integer division truncates the rate, use `HitCounter::hit_rate`"
+)]
 pub fn hit_rate(number_hits: u32, number_users: u32) -> u32 {
     number_hits / number_users
 }
@@ -97,8 +314,120 @@ pub fn hit_rate(number_hits: u32, number_users: u32) -> u32 {
 /// * Hit Rate.
 ///
 #[doc = include_str!("../docs/accuracy/vec_hit_rate.md")]
+#[deprecated(
+    since = "1.1.0",
+    note = "the integer division truncates the rate, use `HitCounter::hit_rate`"
+)]
 pub fn vec_hit_rate(hits: Vec<u32>, users: Vec<u32>) -> u32 {
-    hits.iter().sum::<u32>() / users.len() as u32
+    (hits.iter().map(|hits| *hits as u64).sum::<u64>() / users.len() as u64) as u32
+}
+
+/// What [`HitCounter`] does when a count exceeds `u64::MAX`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Overflow {
+    /// The count stays at `u64::MAX`.
+    #[default]
+    Saturate,
+    /// The update fails and the counter is left unchanged.
+    Error,
+}
+
+/// # Hit counter
+/// Streaming hit rate and average reciprocal hit rate (ARHR): users are recorded
+/// one at a time with the rank of their first hit, and counters of different
+/// shards can be merged. Counts are `u64` and averages `f64`, see [`Running`].
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::accuracy::HitCounter;
+/// let mut counter = HitCounter::default();
+/// counter.record(Some(1)).unwrap();
+/// counter.record(Some(4)).unwrap();
+/// counter.record(None).unwrap();
+/// counter.record(None).unwrap();
+/// assert_eq!(counter.hits(), 2);
+/// assert_eq!(counter.hit_rate(), 0.5);
+/// assert_eq!(counter.arhr(), 0.3125);
+/// ```
+#[doc = include_str!("../docs/accuracy/arhr.md")]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct HitCounter {
+    overflow: Overflow,
+    hits: u64,
+    reciprocal_ranks: Running,
+}
+
+impl HitCounter {
+    pub fn new(overflow: Overflow) -> Self {
+        HitCounter {
+            overflow,
+            ..HitCounter::default()
+        }
+    }
+
+    fn add(&self, count: u64, more: u64) -> Result<u64> {
+        match (count.checked_add(more), self.overflow) {
+            (Some(total), _) => Ok(total),
+            (None, Overflow::Saturate) => Ok(u64::MAX),
+            (None, Overflow::Error) => {
+                Err(Error::InvalidData("hit counts overflowed u64".to_string()))
+            },
+        }
+    }
+
+    /// Records a user, with the rank, starting at 1, of the first relevant item of
+    /// their list or `None` without any.
+    ///
+    /// ## Errors:
+    /// * A count overflows with [`Overflow::Error`], or the rank is 0.
+    pub fn record(&mut self, first_hit_rank: Option<u64>) -> Result<()> {
+        self.add(self.reciprocal_ranks.count(), 1)?;
+        let reciprocal_rank = match first_hit_rank {
+            Some(0) => {
+                return Err(Error::InvalidData("ranks start at 1".to_string()));
+            },
+            Some(rank) => {
+                self.hits = self.add(self.hits, 1)?;
+                1.0 / rank as f64
+            },
+            None => 0.0,
+        };
+        self.reciprocal_ranks.push(reciprocal_rank);
+        Ok(())
+    }
+
+    /// Adds the users recorded by `other`.
+    pub fn merge(&mut self, other: &HitCounter) -> Result<()> {
+        self.add(self.reciprocal_ranks.count(), other.users())?;
+        self.hits = self.add(self.hits, other.hits)?;
+        self.reciprocal_ranks.merge(&other.reciprocal_ranks);
+        Ok(())
+    }
+
+    pub fn users(&self) -> u64 {
+        self.reciprocal_ranks.count()
+    }
+
+    /// Number of users with at least one hit.
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// Fraction of the users with at least one hit, 0 without users.
+    pub fn hit_rate(&self) -> f64 {
+        match self.users() {
+            0 => 0.0,
+            users => self.hits as f64 / users as f64,
+        }
+    }
+
+    /// Average of the reciprocal rank of the first hit, 0 for the users without
+    /// hit.
+    pub fn arhr(&self) -> f64 {
+        self.reciprocal_ranks.mean().unwrap_or(0.0)
+    }
 }
 
 /// # Cumulative Hit Rate
@@ -112,7 +441,7 @@ pub fn vec_hit_rate(hits: Vec<u32>, users: Vec<u32>) -> u32 {
 /// * The Cumulative Hit Rate (CHR).
 ///
 #[doc = include_str!("../docs/accuracy/cumulative_hit_rate.md")]
-fn cumulative_hit_rate(predicted_items: &[u32], true_items: &[u32]) -> f32 {
+pub fn cumulative_hit_rate(predicted_items: &[u32], true_items: &[u32]) -> f32 {
     predicted_items
         .iter()
         .filter(|&item| true_items.contains(item))
@@ -120,6 +449,189 @@ fn cumulative_hit_rate(predicted_items: &[u32], true_items: &[u32]) -> f32 {
         / true_items.len() as f32
 }
 
+/// Points of a ROC or precision-recall curve, one per distinct score threshold,
+/// ordered by decreasing threshold.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Curve {
+    /// Score from which a prediction is considered positive.
+    pub thresholds: Vec<f32>,
+    /// False positive rate for a ROC curve, recall for a precision-recall curve.
+    pub x: Vec<f32>,
+    /// True positive rate for a ROC curve, precision for a precision-recall curve.
+    pub y: Vec<f32>,
+}
+
+impl Curve {
+    /// Area under the curve, see [`auc`].
+    pub fn auc(&self) -> f32 {
+        auc(&self.x, &self.y)
+    }
+}
+
+/// # ROC curve
+/// Computes the Receiver Operating Characteristic curve of a set of scores.
+///
+/// ## Parameters:
+/// * `scores`: The predicted score of each item, higher meaning more relevant.
+/// * `relevant`: Whether each item is actually relevant.
+///
+/// ## Returns:
+/// * The curve, going from `(0, 0)` to `(1, 1)`, with the false positive rate as
+///   `x` and the true positive rate as `y`.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::accuracy::roc_curve;
+/// let curve = roc_curve(&[0.1, 0.4, 0.35, 0.8], &[false, false, true, true]);
+/// assert_eq!(curve.x, vec![0.0, 0.0, 0.5, 0.5, 1.0]);
+/// assert_eq!(curve.y, vec![0.0, 0.5, 0.5, 1.0, 1.0]);
+/// assert_eq!(curve.auc(), 0.75);
+/// ```
+///
+#[doc = include_str!("../docs/accuracy/roc_curve.md")]
+pub fn roc_curve(scores: &[f32], relevant: &[bool]) -> Curve {
+    let counts = cumulative_counts(scores, relevant);
+    let (positives, negatives) = counts.last().map_or((0, 0), |&(_, tp, fp)| (tp, fp));
+    let mut curve = Curve {
+        thresholds: vec![f32::INFINITY],
+        x: vec![0.0],
+        y: vec![0.0],
+    };
+    counts.iter().for_each(|&(threshold, tp, fp)| {
+        curve.thresholds.push(threshold);
+        curve.x.push(fp as f32 / negatives as f32);
+        curve.y.push(tp as f32 / positives as f32);
+    });
+    curve
+}
+
+/// # Precision-recall curve
+/// Computes the precision and recall obtained at every threshold of a set of scores.
+///
+/// ## Parameters:
+/// * `scores`: The predicted score of each item, higher meaning more relevant.
+/// * `relevant`: Whether each item is actually relevant.
+///
+/// ## Returns:
+/// * The curve, starting at a recall of 0 and a precision of 1, with the recall
+///   as `x` and the precision as `y`.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::accuracy::precision_recall_curve;
+/// let curve = precision_recall_curve(&[0.1, 0.4, 0.35, 0.8], &[false, false, true, true]);
+/// assert_eq!(curve.x, vec![0.0, 0.5, 0.5, 1.0, 1.0]);
+/// assert_eq!(curve.y, vec![1.0, 1.0, 0.5, 0.666_666_7, 0.5]);
+/// ```
+///
+#[doc = include_str!("../docs/accuracy/precision_recall_curve.md")]
+pub fn precision_recall_curve(scores: &[f32], relevant: &[bool]) -> Curve {
+    let counts = cumulative_counts(scores, relevant);
+    let positives = counts.last().map_or(0, |&(_, tp, _)| tp);
+    let mut curve = Curve {
+        thresholds: vec![f32::INFINITY],
+        x: vec![0.0],
+        y: vec![1.0],
+    };
+    counts.iter().for_each(|&(threshold, tp, fp)| {
+        curve.thresholds.push(threshold);
+        curve.x.push(tp as f32 / positives as f32);
+        curve.y.push(tp as f32 / (tp + fp) as f32);
+    });
+    curve
+}
+
+/// # Area under the curve
+/// Computes the area under a curve with the trapezoidal rule.
+///
+/// ## Parameters:
+/// * `x`: The x coordinates, in increasing order.
+/// * `y`: The y coordinates.
+///
+/// ## Returns:
+/// * The area under the curve.
+///
+pub fn auc(x: &[f32], y: &[f32]) -> f32 {
+    x.windows(2)
+        .zip(y.windows(2))
+        .map(|(x, y)| (x[1] - x[0]) * (y[0] + y[1]) / 2.0)
+        .sum()
+}
+
+/// # ROC AUC
+/// Area under the ROC curve, i.e. the probability that a relevant item is scored
+/// higher than a non relevant one.
+///
+/// ## Parameters:
+/// * `scores`: The predicted score of each item, higher meaning more relevant.
+/// * `relevant`: Whether each item is actually relevant.
+///
+/// ## Returns:
+/// * The area under the ROC curve.
+///
+#[doc = include_str!("../docs/accuracy/roc_curve.md")]
+pub fn roc_auc(scores: &[f32], relevant: &[bool]) -> f32 {
+    roc_curve(scores, relevant).auc()
+}
+
+/// # Average precision
+/// Summarises the precision-recall curve as the mean of the precisions achieved at
+/// each threshold, weighted by the increase in recall.
+///
+/// ## Parameters:
+/// * `scores`: The predicted score of each item, higher meaning more relevant.
+/// * `relevant`: Whether each item is actually relevant.
+///
+/// ## Returns:
+/// * The average precision.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::accuracy::average_precision;
+/// let ap = average_precision(&[0.1, 0.4, 0.35, 0.8], &[false, false, true, true]);
+/// assert_eq!(ap, 0.833_333_4);
+/// ```
+///
+#[doc = include_str!("../docs/accuracy/precision_recall_curve.md")]
+pub fn average_precision(scores: &[f32], relevant: &[bool]) -> f32 {
+    let curve = precision_recall_curve(scores, relevant);
+    curve
+        .x
+        .windows(2)
+        .zip(curve.y.iter().skip(1))
+        .map(|(recall, precision)| (recall[1] - recall[0]) * precision)
+        .sum()
+}
+
+/// Sorts the scores in decreasing order and returns, for every distinct score, the
+/// number of true and false positives obtained when using it as threshold.
+fn cumulative_counts(scores: &[f32], relevant: &[bool]) -> Vec<(f32, usize, usize)> {
+    let mut ranked: Vec<(f32, bool)> = scores
+        .iter()
+        .copied()
+        .zip(relevant.iter().copied())
+        .collect();
+    ranked.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+
+    let mut counts: Vec<(f32, usize, usize)> = Vec::new();
+    let (mut tp, mut fp) = (0, 0);
+    for (index, (score, is_relevant)) in ranked.iter().enumerate() {
+        if *is_relevant {
+            tp += 1;
+        } else {
+            fp += 1;
+        }
+        let is_last_of_tie = match ranked.get(index + 1) {
+            Some((next_score, _)) => next_score != score,
+            None => true,
+        };
+        if is_last_of_tie {
+            counts.push((*score, tp, fp));
+        }
+    }
+    counts
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -129,20 +641,44 @@ mod tests {
     //     assert_eq!(cumulative_hit_rate(), 1.0)
     // }
 
-    // #[test]
-    // fn test_rmse() {
-    //     assert_eq!(rmse(), 1.0)
-    // }
+    #[test]
+    fn test_rmse() {
+        assert_eq!(rmse(&[3.0, 4.0, 5.0], &[3.0, 2.0, 5.0]), 1.154_700_5);
+    }
 
-    // #[test]
-    // fn test_mse() {
-    //     assert_eq!(mse(), 1.0)
-    // }
+    #[test]
+    fn test_mse() {
+        assert_eq!(mse(&[3.0, 4.0, 5.0], &[3.0, 2.0, 5.0]), 1.333_333_4);
+    }
 
-    // #[test]
-    // fn test_mae() {
-    //     assert_eq!(mae(), 1.0)
-    // }
+    #[test]
+    fn test_mae() {
+        assert_eq!(mae(&[3.0, 4.0, 5.0], &[3.0, 2.0, 6.0]), 1.0);
+    }
+
+    #[test]
+    fn test_masked_mse() {
+        let predicted = vec![vec![4.0, 3.0, 1.0], vec![2.0, 5.0, 3.0]];
+        let actual = vec![vec![5.0, f32::NAN, 1.0], vec![f32::NAN, 3.0, f32::NAN]];
+        assert_eq!(masked_mse(&predicted, &actual), 1.666_666_6);
+    }
+
+    #[test]
+    fn test_masked_mae() {
+        let predicted = vec![vec![4.0, 3.0, 1.0], vec![2.0, 5.0, 3.0]];
+        let actual = vec![vec![5.0, f32::NAN, 1.0], vec![f32::NAN, 3.0, f32::NAN]];
+        assert_eq!(masked_mae(&predicted, &actual), 1.0);
+    }
+
+    #[test]
+    fn test_masked_errors_match_dense_errors_without_missing_entries() {
+        let predicted = vec![vec![4.0, 3.0], vec![2.0, 5.0]];
+        let actual = vec![vec![5.0, 3.0], vec![1.0, 4.0]];
+        assert_eq!(
+            masked_rmse(&predicted, &actual),
+            rmse(&predicted.concat(), &actual.concat())
+        );
+    }
 
     // #[test]
     // fn test_arhr() {
@@ -150,12 +686,123 @@ mod tests {
     // }
 
     #[test]
+    fn test_r2_score() {
+        assert_eq!(
+            r2_score(&[2.5, 0.0, 2.0, 8.0], &[3.0, -0.5, 2.0, 7.0]),
+            0.948_608_16
+        );
+        assert_eq!(r2_score(&[3.0, 3.0, 3.0], &[2.0, 3.0, 4.0]), 0.0);
+    }
+
+    #[test]
+    fn test_explained_variance() {
+        assert_eq!(
+            explained_variance(&[2.5, 0.0, 2.0, 8.0], &[3.0, -0.5, 2.0, 7.0]),
+            0.957_173_45
+        );
+    }
+
+    #[test]
+    fn test_rmse_per_user() {
+        let predicted = [3.0, 3.0, 3.0, 3.0, 1.0];
+        let actual = [3.0, 3.0, 3.0, 3.0, 5.0];
+        let users = [1, 1, 1, 1, 2];
+        assert_eq!(rmse(&predicted, &actual), 1.788_854_4);
+        assert_eq!(rmse_per_user(&predicted, &actual, &users), 2.0);
+    }
+
+    #[test]
+    fn test_mae_per_user() {
+        let predicted = [3.0, 3.0, 2.0, 1.0];
+        let actual = [4.0, 3.0, 2.0, 5.0];
+        let users = [1, 1, 2, 2];
+        assert_eq!(mae_per_user(&predicted, &actual, &users), 1.25);
+    }
+
+    #[test]
+    fn test_roc_curve() {
+        let curve = roc_curve(
+            &[0.9, 0.8, 0.8, 0.3, 0.1],
+            &[true, true, false, false, true],
+        );
+        assert_eq!(curve.thresholds, vec![f32::INFINITY, 0.9, 0.8, 0.3, 0.1]);
+        assert_eq!(curve.x, vec![0.0, 0.0, 0.5, 1.0, 1.0]);
+        assert_eq!(
+            curve.y,
+            vec![0.0, 0.333_333_34, 0.666_666_7, 0.666_666_7, 1.0]
+        );
+    }
+
+    #[test]
+    fn test_roc_auc() {
+        assert_eq!(
+            roc_auc(&[0.9, 0.8, 0.2, 0.1], &[true, true, false, false]),
+            1.0
+        );
+        assert_eq!(
+            roc_auc(&[0.9, 0.8, 0.2, 0.1], &[false, false, true, true]),
+            0.0
+        );
+        assert_eq!(
+            roc_auc(&[0.5, 0.5, 0.5, 0.5], &[true, false, true, false]),
+            0.5
+        );
+    }
+
+    #[test]
+    fn test_precision_recall_curve() {
+        let curve =
+            precision_recall_curve(&[0.9, 0.8, 0.8, 0.3], &[true, true, false, true]);
+        assert_eq!(curve.x, vec![0.0, 0.333_333_34, 0.666_666_7, 1.0]);
+        assert_eq!(curve.y, vec![1.0, 1.0, 0.666_666_7, 0.75]);
+    }
+
+    #[test]
+    fn test_average_precision() {
+        assert_eq!(
+            average_precision(&[0.9, 0.8, 0.2, 0.1], &[true, true, false, false]),
+            1.0
+        );
+    }
+
+    #[test]
+    fn test_auc() {
+        assert_eq!(auc(&[0.0, 0.5, 1.0], &[0.0, 1.0, 1.0]), 0.75);
+    }
+
+    #[test]
+    fn test_hit_counter() {
+        let mut counter = HitCounter::default();
+        assert_eq!((counter.hit_rate(), counter.arhr()), (0.0, 0.0));
+        counter.record(Some(2)).unwrap();
+        let mut other = HitCounter::default();
+        other.record(None).unwrap();
+        other.record(Some(1)).unwrap();
+        counter.merge(&other).unwrap();
+        assert_eq!(counter.users(), 3);
+        assert_eq!(counter.hits(), 2);
+        assert!((counter.arhr() - 0.5).abs() < 1e-12);
+        assert!(counter.record(Some(0)).is_err());
+
+        let mut full = HitCounter::new(Overflow::Error);
+        full.hits = u64::MAX;
+        assert!(full.record(Some(1)).is_err());
+        assert_eq!(full.users(), 0);
+        full.overflow = Overflow::Saturate;
+        full.record(Some(1)).unwrap();
+        assert_eq!(full.hits(), u64::MAX);
+    }
+
+    #[test]
+    #[allow(deprecated)]
     fn test_hit_rate() {
         assert_eq!(hit_rate(8, 4), 2);
     }
 
     #[test]
+    #[allow(deprecated)]
     fn test_vec_hit_rate() {
         assert_eq!(vec_hit_rate(vec![9, 7, 8], vec![0, 0, 0]), 8);
+        assert_eq!(vec_hit_rate(vec![u32::MAX, u32::MAX], vec![0, 0]), u32::MAX);
     }
 }
@@ -0,0 +1,500 @@
+//! Alternating least squares for implicit feedback
+use std::collections::{BTreeMap, HashMap};
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use rand_distr::{Distribution, Normal};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::algorithms::config::{ensure, AlgorithmConfig};
+use crate::algorithms::history::TrainingHistory;
+use crate::dataset::Dataset;
+use crate::envelope::{fields, PersistedModel};
+use crate::errors::{Error, Result};
+use crate::factors::{FactorMatrix, Precision};
+use crate::matrix::solve;
+use crate::memory::MemoryFootprint;
+use crate::parallelism::default_parallelism;
+use crate::parallelism::prelude::*;
+use crate::recommender::{RankedItems, Recommendation, Recommender};
+use crate::sets::IdSet;
+
+/// # Implicit ALS configuration
+/// Hyperparameters of [`ImplicitALS`].
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::algorithms::als::ImplicitALSConfig;
+/// use rec_rsys::algorithms::config::AlgorithmConfig;
+/// let config = ImplicitALSConfig::from_toml("num_factors = 16\nalpha = 10.0").unwrap();
+/// assert_eq!(config.alpha, 10.0);
+/// assert!(ImplicitALSConfig::default().set_alpha(-1.0).validate().is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImplicitALSConfig {
+    /// Number of latent dimensions.
+    pub num_factors: usize,
+    /// Number of alternations, each solving the users then the items.
+    pub num_iterations: usize,
+    /// Penalty on the squared norm of the factors.
+    pub regularization: f32,
+    /// How fast the confidence grows with the number of interactions.
+    pub alpha: f32,
+    /// Standard deviation of the normal distribution the factors start from.
+    pub init_std: f32,
+    /// Seed of the initialization.
+    pub seed: u64,
+}
+
+impl Default for ImplicitALSConfig {
+    fn default() -> Self {
+        ImplicitALSConfig {
+            num_factors: 20,
+            num_iterations: 15,
+            regularization: 0.01,
+            alpha: 40.0,
+            init_std: 0.01,
+            seed: 42,
+        }
+    }
+}
+
+impl ImplicitALSConfig {
+    pub fn set_num_factors(mut self, num_factors: usize) -> Self {
+        self.num_factors = num_factors;
+        self
+    }
+    pub fn set_num_iterations(mut self, num_iterations: usize) -> Self {
+        self.num_iterations = num_iterations;
+        self
+    }
+    pub fn set_regularization(mut self, regularization: f32) -> Self {
+        self.regularization = regularization;
+        self
+    }
+    pub fn set_alpha(mut self, alpha: f32) -> Self {
+        self.alpha = alpha;
+        self
+    }
+    pub fn set_init_std(mut self, init_std: f32) -> Self {
+        self.init_std = init_std;
+        self
+    }
+    pub fn set_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+}
+
+impl AlgorithmConfig for ImplicitALSConfig {
+    fn validate(&self) -> Result<()> {
+        ensure(self.num_factors > 0, "num_factors", "greater than 0")?;
+        ensure(self.num_iterations > 0, "num_iterations", "greater than 0")?;
+        ensure(
+            self.regularization >= 0.0 && self.regularization.is_finite(),
+            "regularization",
+            "a non-negative number",
+        )?;
+        ensure(
+            self.alpha >= 0.0 && self.alpha.is_finite(),
+            "alpha",
+            "a non-negative number",
+        )?;
+        ensure(
+            self.init_std > 0.0 && self.init_std.is_finite(),
+            "init_std",
+            "a positive number",
+        )
+    }
+}
+
+/// # Implicit ALS
+/// Matrix factorization of implicit feedback, such as clicks or views, after Hu,
+/// Koren and Volinsky. The ratings of the dataset are interaction counts, summed
+/// per user and item: every pair is a preference, 1 if the user interacted with
+/// the item and 0 otherwise, weighted by a confidence growing with the count. The
+/// user and item factors are solved in turn, exactly, by least squares.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::algorithms::als::{ImplicitALS, ImplicitALSConfig};
+/// use rec_rsys::dataset::{Dataset, Rating};
+/// use rec_rsys::recommender::Recommender;
+/// // Clicks: users 1 and 2 share their tastes, user 3 has others.
+/// let dataset = Dataset::new(vec![
+///     Rating::new(1, 10, 3.0), Rating::new(1, 11, 1.0),
+///     Rating::new(2, 10, 2.0), Rating::new(2, 11, 1.0), Rating::new(2, 12, 4.0),
+///     Rating::new(3, 13, 5.0), Rating::new(3, 14, 1.0),
+/// ]);
+/// let mut model = ImplicitALS::new(ImplicitALSConfig::default().set_num_factors(4));
+/// model.fit(&dataset).unwrap();
+/// assert_eq!(model.recommend(1, 1)[0].item_id, 12);
+/// assert!(model.predict(3, 13).unwrap() > model.predict(3, 10).unwrap());
+/// ```
+#[doc = include_str!("../../docs/algorithms/implicit_als.md")]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImplicitALS {
+    config: ImplicitALSConfig,
+    users: HashMap<u32, usize>,
+    items: HashMap<u32, usize>,
+    item_ids: Vec<u32>,
+    user_factors: FactorMatrix,
+    item_factors: FactorMatrix,
+    /// The precision the factors are stored with once trained.
+    #[serde(default)]
+    precision: Precision,
+    seen: HashMap<u32, IdSet>,
+    history: TrainingHistory,
+}
+
+/// The interactions of every row of a side, as `(row of the other side, count)`.
+type Interactions = Vec<Vec<(usize, f32)>>;
+
+impl ImplicitALS {
+    pub fn new(config: ImplicitALSConfig) -> Self {
+        ImplicitALS {
+            config,
+            users: HashMap::new(),
+            items: HashMap::new(),
+            item_ids: Vec::new(),
+            user_factors: FactorMatrix::new(0, 0),
+            item_factors: FactorMatrix::new(0, 0),
+            precision: Precision::F32,
+            seen: HashMap::new(),
+            history: TrainingHistory::default(),
+        }
+    }
+
+    pub fn config(&self) -> &ImplicitALSConfig {
+        &self.config
+    }
+
+    /// The ids of the items, in the order of the rows of [`Self::item_factors`].
+    pub fn item_ids(&self) -> &[u32] {
+        &self.item_ids
+    }
+
+    /// The learned user factors, one row per user in increasing id order.
+    pub fn user_factors(&self) -> &FactorMatrix {
+        &self.user_factors
+    }
+
+    /// The learned item factors, one row per item in increasing id order.
+    pub fn item_factors(&self) -> &FactorMatrix {
+        &self.item_factors
+    }
+
+    /// # Factor precision
+    /// Stores the learned factors with another precision, e.g. [`Precision::F16`]
+    /// to halve their memory. Predictions are computed from the stored values, the
+    /// error they introduce is measured by [`FactorMatrix::precision_diff`].
+    ///
+    /// Training always runs in `f64` and stores the factors of the next fits with
+    /// this precision too. Going back to `f32` does not restore the rounded values.
+    pub fn set_factor_precision(&mut self, precision: Precision) {
+        self.precision = precision;
+        self.user_factors = self.user_factors.to_precision(precision);
+        self.item_factors = self.item_factors.to_precision(precision);
+    }
+
+    /// The losses of the last training, one per iteration.
+    pub fn history(&self) -> &TrainingHistory {
+        &self.history
+    }
+
+    /// The counts of the positive interactions, by user then by item.
+    fn interactions(&self, dataset: &Dataset) -> (Interactions, Interactions) {
+        let mut counts: HashMap<(usize, usize), f32> = HashMap::new();
+        for rating in dataset.ratings.iter().filter(|r| r.rating > 0.0) {
+            let user = self.users[&rating.user_id];
+            let item = self.items[&rating.item_id];
+            *counts.entry((user, item)).or_default() += rating.rating;
+        }
+        let mut by_user = vec![Vec::new(); self.users.len()];
+        let mut by_item = vec![Vec::new(); self.items.len()];
+        for ((user, item), count) in counts {
+            by_user[user].push((item, count));
+            by_item[item].push((user, count));
+        }
+        // Sorted so the sums, and the factors, do not depend on the hash order.
+        by_user
+            .iter_mut()
+            .for_each(|row| row.sort_unstable_by_key(|(item, _)| *item));
+        by_item
+            .iter_mut()
+            .for_each(|row| row.sort_unstable_by_key(|(user, _)| *user));
+        (by_user, by_item)
+    }
+
+    /// The loss minimized, averaged over every pair of user and item.
+    fn loss(
+        &self,
+        users: &[Vec<f64>],
+        items: &[Vec<f64>],
+        by_user: &Interactions,
+    ) -> f64 {
+        // The pairs without interaction only add their squared score, summed over
+        // every pair from the Gram matrices, then corrected for the observed ones.
+        let (user_gram, item_gram) = (gram(users), gram(items));
+        let mut loss: f64 = user_gram
+            .iter()
+            .flatten()
+            .zip(item_gram.iter().flatten())
+            .map(|(a, b)| a * b)
+            .sum();
+        for (user, interactions) in by_user.iter().enumerate() {
+            for &(item, count) in interactions {
+                let score = dot(&users[user], &items[item]);
+                let confidence = 1.0 + self.config.alpha as f64 * count as f64;
+                loss += confidence * (1.0 - score).powi(2) - score * score;
+            }
+        }
+        let norms: f64 = users.iter().chain(items).flatten().map(|x| x * x).sum();
+        loss += self.config.regularization as f64 * norms;
+        loss / (users.len() * items.len()).max(1) as f64
+    }
+
+    /// Solves the factors of every row of a side given the factors of the other.
+    fn solve_side(
+        &self,
+        fixed: &[Vec<f64>],
+        interactions: &Interactions,
+    ) -> Vec<Vec<f64>> {
+        let (alpha, lambda) =
+            (self.config.alpha as f64, self.config.regularization as f64);
+        let gram = gram(fixed);
+        default_parallelism().install(|| {
+            interactions
+                .par_iter()
+                .map(|interactions| {
+                    let mut a = gram.clone();
+                    let mut b = vec![0.0; gram.len()];
+                    for &(other, count) in interactions {
+                        let (y, confidence) = (&fixed[other], 1.0 + alpha * count as f64);
+                        for (i, row) in a.iter_mut().enumerate() {
+                            row.iter_mut().zip(y).for_each(|(a, y_j)| {
+                                *a += (confidence - 1.0) * y[i] * y_j
+                            });
+                            b[i] += confidence * y[i];
+                        }
+                    }
+                    a.iter_mut()
+                        .enumerate()
+                        .for_each(|(i, row)| row[i] += lambda);
+                    solve(a, b)
+                })
+                .collect()
+        })
+    }
+}
+
+/// The sum of the outer products of the rows with themselves.
+fn gram(rows: &[Vec<f64>]) -> Vec<Vec<f64>> {
+    let dims = rows.first().map_or(0, Vec::len);
+    let mut gram = vec![vec![0.0; dims]; dims];
+    for row in rows {
+        for (i, gram_row) in gram.iter_mut().enumerate() {
+            gram_row
+                .iter_mut()
+                .zip(row)
+                .for_each(|(value, x)| *value += row[i] * x);
+        }
+    }
+    gram
+}
+
+fn dot(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(a, b)| a * b).sum()
+}
+
+fn to_factors(rows: &[Vec<f64>]) -> Result<FactorMatrix> {
+    let rows: Vec<Vec<f32>> = rows
+        .iter()
+        .map(|row| row.iter().map(|x| *x as f32).collect())
+        .collect();
+    FactorMatrix::from_rows(&rows)
+}
+
+impl Recommender for ImplicitALS {
+    fn fit(&mut self, dataset: &Dataset) -> Result<()> {
+        self.config.validate()?;
+        let positive = dataset.filter(|r| r.rating > 0.0);
+        if positive.is_empty() {
+            return Err(Error::InvalidData(
+                "cannot fit without positive interactions".to_string(),
+            ));
+        }
+        self.users = index(positive.users().into_iter());
+        self.item_ids = positive.items().into_iter().collect();
+        self.items = index(self.item_ids.iter().copied());
+        self.seen = positive.user_item_sets();
+        self.history = TrainingHistory::default();
+        let (by_user, by_item) = self.interactions(&positive);
+
+        let mut rng = StdRng::seed_from_u64(self.config.seed);
+        let normal = Normal::new(0.0, self.config.init_std as f64)
+            .expect("validated standard deviation");
+        let mut random = |rows: usize| -> Vec<Vec<f64>> {
+            (0..rows)
+                .map(|_| {
+                    (0..self.config.num_factors)
+                        .map(|_| normal.sample(&mut rng))
+                        .collect()
+                })
+                .collect()
+        };
+        let mut users = random(self.users.len());
+        let mut items = random(self.items.len());
+        for _ in 0..self.config.num_iterations {
+            users = self.solve_side(&items, &by_user);
+            items = self.solve_side(&users, &by_item);
+            let loss = self.loss(&users, &items, &by_user);
+            self.history.record(loss, None)?;
+        }
+        self.user_factors = to_factors(&users)?;
+        self.item_factors = to_factors(&items)?;
+        self.set_factor_precision(self.precision);
+        Ok(())
+    }
+
+    fn recommend(&self, user_id: u32, num_items: usize) -> Vec<Recommendation> {
+        self.recommend_iter(user_id).take(num_items).collect()
+    }
+
+    fn recommend_iter(&self, user_id: u32) -> RankedItems {
+        let user = match self.users.get(&user_id) {
+            Some(user) => self.user_factors.row(*user),
+            None => return RankedItems::default(),
+        };
+        let scores: HashMap<u32, f32> = self
+            .item_ids
+            .iter()
+            .enumerate()
+            .map(|(item, item_id)| (*item_id, self.item_factors.dot(item, &user)))
+            .collect();
+        RankedItems::new(scores, self.seen.get(&user_id))
+    }
+
+    /// The estimated preference, close to 1 for the items the user would interact
+    /// with and to 0 for the other ones.
+    fn predict(&self, user_id: u32, item_id: u32) -> Option<f32> {
+        let user = self.users.get(&user_id)?;
+        let item = self.items.get(&item_id)?;
+        Some(self.item_factors.dot(*item, &self.user_factors.row(*user)))
+    }
+}
+
+/// Assigns a row to every id, in increasing order.
+fn index(ids: impl Iterator<Item = u32>) -> HashMap<u32, usize> {
+    ids.enumerate().map(|(row, id)| (id, row)).collect()
+}
+
+impl PersistedModel for ImplicitALS {
+    const MODEL_TYPE: &'static str = "implicit_als";
+
+    fn hyperparameters(&self) -> BTreeMap<String, Value> {
+        fields(&self.config)
+    }
+}
+
+impl MemoryFootprint for ImplicitALS {
+    fn heap_size(&self) -> usize {
+        self.users.heap_size()
+            + self.items.heap_size()
+            + self.item_ids.heap_size()
+            + self.user_factors.heap_size()
+            + self.item_factors.heap_size()
+            + self.seen.heap_size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dataset::Rating;
+    use crate::factors::score_diff;
+
+    /// Two groups of users, each clicking the items of its group, item 15 of the
+    /// first group being left for user 1.
+    fn dataset() -> Dataset {
+        let mut ratings = Vec::new();
+        for user in 1..=8 {
+            let items = if user <= 4 { 10..16 } else { 20..26 };
+            for item in items.filter(|item| !(user == 1 && *item == 15)) {
+                ratings.push(Rating::new(user, item, ((user + item) % 3 + 1) as f32));
+            }
+        }
+        Dataset::new(ratings)
+    }
+
+    #[test]
+    fn test_fit() {
+        let mut model = ImplicitALS::new(ImplicitALSConfig::default().set_num_factors(4));
+        model.fit(&dataset()).unwrap();
+        assert_eq!(model.recommend(1, 1)[0].item_id, 15);
+        let recommended: Vec<u32> =
+            model.recommend(5, 3).iter().map(|r| r.item_id).collect();
+        assert!(recommended.iter().all(|item| !(20..26).contains(item)));
+        assert!(model.predict(1, 10).unwrap() > 0.5);
+        assert!(model.predict(1, 20).unwrap().abs() < 0.5);
+        assert!(model.predict(99, 10).is_none());
+        assert_eq!(model.item_factors().rows(), 12);
+
+        let losses = model.history().train_losses();
+        assert_eq!(losses.len(), 15);
+        assert!(losses.windows(2).all(|pair| pair[1] <= pair[0] + 1e-9));
+    }
+
+    #[test]
+    fn test_factor_precision() {
+        let mut model = ImplicitALS::new(ImplicitALSConfig::default().set_num_factors(4));
+        model.fit(&dataset()).unwrap();
+        let (users, items) = (model.user_factors().clone(), model.item_factors().clone());
+        let size = model.heap_size();
+        model.set_factor_precision(Precision::BF16);
+        assert!(model.heap_size() < size);
+        let bound = score_diff(&users, &items, Precision::BF16)
+            .unwrap()
+            .max_abs_error;
+        dataset().ratings.iter().for_each(|r| {
+            let (user, item) = (model.users[&r.user_id], model.items[&r.item_id]);
+            let prediction = items.dot(item, &users.row(user));
+            let compressed = model.predict(r.user_id, r.item_id).unwrap();
+            assert!((compressed - prediction).abs() <= bound + 1e-5);
+        });
+        assert_eq!(model.recommend(1, 1)[0].item_id, 15);
+    }
+
+    #[test]
+    fn test_counts_are_summed() {
+        let mut once = ImplicitALS::new(ImplicitALSConfig::default());
+        once.fit(&Dataset::new(vec![
+            Rating::new(1, 10, 2.0),
+            Rating::new(2, 10, 1.0),
+        ]))
+        .unwrap();
+        let mut twice = ImplicitALS::new(ImplicitALSConfig::default());
+        twice
+            .fit(&Dataset::new(vec![
+                Rating::new(1, 10, 1.0),
+                Rating::new(1, 10, 1.0),
+                Rating::new(2, 10, 1.0),
+                Rating::new(3, 11, 0.0),
+            ]))
+            .unwrap();
+        assert_eq!(once.user_factors(), twice.user_factors());
+        assert!(twice.recommend(3, 1).is_empty());
+    }
+
+    #[test]
+    fn test_fit_rejects_invalid_input() {
+        let mut model = ImplicitALS::new(ImplicitALSConfig::default().set_num_factors(0));
+        assert!(model.fit(&dataset()).is_err());
+        let mut model = ImplicitALS::new(ImplicitALSConfig::default());
+        assert!(model
+            .fit(&Dataset::new(vec![Rating::new(1, 10, 0.0)]))
+            .is_err());
+    }
+}
@@ -0,0 +1,439 @@
+//! Baseline estimates from learned user and item biases
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::algorithms::config::{ensure, AlgorithmConfig};
+use crate::algorithms::history::TrainingHistory;
+use crate::algorithms::mf::index;
+use crate::dataset::{Dataset, Rating, RatingScale};
+use crate::envelope::{fields, PersistedModel};
+use crate::errors::{Error, Result};
+use crate::memory::MemoryFootprint;
+use crate::recommender::{RankedItems, Recommendation, Recommender};
+use crate::sets::IdSet;
+
+/// # Baseline method
+/// How the biases of [`BaselineOnly`] are learned.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum BaselineMethod {
+    /// Alternates the closed form solutions of the item biases and the user
+    /// biases, each one shrunk towards 0 by its regularization.
+    Als {
+        user_regularization: f32,
+        item_regularization: f32,
+    },
+    /// Stochastic gradient descent on the regularized squared error.
+    Sgd {
+        learning_rate: f32,
+        regularization: f32,
+    },
+}
+
+impl Default for BaselineMethod {
+    fn default() -> Self {
+        BaselineMethod::Als {
+            user_regularization: 15.0,
+            item_regularization: 10.0,
+        }
+    }
+}
+
+/// # Baseline configuration
+/// Hyperparameters of [`BaselineOnly`].
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::algorithms::baseline::{BaselineConfig, BaselineMethod};
+/// use rec_rsys::algorithms::config::AlgorithmConfig;
+/// let config = BaselineConfig::from_toml(
+///     "num_epochs = 20\n[method]\nkind = \"sgd\"\nlearning_rate = 0.005\nregularization = 0.02",
+/// )
+/// .unwrap();
+/// assert_eq!(
+///     config.method,
+///     BaselineMethod::Sgd { learning_rate: 0.005, regularization: 0.02 }
+/// );
+/// assert!(config.set_num_epochs(0).validate().is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BaselineConfig {
+    pub method: BaselineMethod,
+    /// Number of passes over the ratings.
+    pub num_epochs: usize,
+    /// Training stops with an error when the loss exceeds the one of the first
+    /// epoch this many times.
+    pub max_loss_growth: f64,
+}
+
+impl Default for BaselineConfig {
+    fn default() -> Self {
+        BaselineConfig {
+            method: BaselineMethod::default(),
+            num_epochs: 10,
+            max_loss_growth: 100.0,
+        }
+    }
+}
+
+impl BaselineConfig {
+    pub fn set_method(mut self, method: BaselineMethod) -> Self {
+        self.method = method;
+        self
+    }
+    pub fn set_num_epochs(mut self, num_epochs: usize) -> Self {
+        self.num_epochs = num_epochs;
+        self
+    }
+    pub fn set_max_loss_growth(mut self, max_loss_growth: f64) -> Self {
+        self.max_loss_growth = max_loss_growth;
+        self
+    }
+}
+
+impl AlgorithmConfig for BaselineConfig {
+    fn validate(&self) -> Result<()> {
+        ensure(self.num_epochs > 0, "num_epochs", "greater than 0")?;
+        let non_negative = |value: f32| value >= 0.0 && value.is_finite();
+        match self.method {
+            BaselineMethod::Als {
+                user_regularization,
+                item_regularization,
+            } => {
+                ensure(
+                    non_negative(user_regularization),
+                    "user_regularization",
+                    "a non-negative number",
+                )?;
+                ensure(
+                    non_negative(item_regularization),
+                    "item_regularization",
+                    "a non-negative number",
+                )
+            },
+            BaselineMethod::Sgd {
+                learning_rate,
+                regularization,
+            } => {
+                ensure(
+                    learning_rate > 0.0 && learning_rate.is_finite(),
+                    "learning_rate",
+                    "a positive number",
+                )?;
+                ensure(
+                    non_negative(regularization),
+                    "regularization",
+                    "a non-negative number",
+                )
+            },
+        }
+    }
+}
+
+/// # Baseline only
+/// Predicts a rating as the global mean plus a bias of the user and a bias of the
+/// item, `mu + b_u + b_i`. Besides being a baseline to beat, its estimates remove
+/// the effects of the users who rate everything high and the items everyone
+/// likes, so other algorithms work on what is left: see [`BaselineOnly::residuals`],
+/// the `baseline` of [`KNNConfig`](crate::algorithms::knn::KNNConfig) and of
+/// [`MFConfig`](crate::algorithms::mf::MFConfig).
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::algorithms::baseline::{BaselineConfig, BaselineOnly};
+/// use rec_rsys::dataset::{Dataset, Rating};
+/// use rec_rsys::recommender::Recommender;
+/// let dataset = Dataset::new(vec![
+///     Rating::new(1, 10, 5.0), Rating::new(1, 11, 4.0),
+///     Rating::new(2, 10, 3.0), Rating::new(2, 11, 2.0), Rating::new(2, 12, 1.0),
+/// ]);
+/// let mut model = BaselineOnly::new(BaselineConfig::default());
+/// model.fit(&dataset).unwrap();
+/// assert_eq!(model.global_mean(), 3.0);
+/// assert!(model.user_bias(1) > 0.0 && model.user_bias(2) < 0.0);
+/// assert!(model.item_bias(10) > model.item_bias(11));
+/// assert_eq!(model.predict(1, 12), Some(model.estimate(1, 12)));
+/// ```
+#[doc = include_str!("../../docs/algorithms/baseline.md")]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BaselineOnly {
+    config: BaselineConfig,
+    global_mean: f32,
+    user_biases: HashMap<u32, f32>,
+    item_biases: HashMap<u32, f32>,
+    seen: HashMap<u32, IdSet>,
+    /// The scale of the training ratings, the predictions are clipped to it.
+    #[serde(default)]
+    scale: Option<RatingScale>,
+    history: TrainingHistory,
+}
+
+impl BaselineOnly {
+    pub fn new(config: BaselineConfig) -> Self {
+        BaselineOnly {
+            config,
+            ..BaselineOnly::default()
+        }
+    }
+
+    pub fn config(&self) -> &BaselineConfig {
+        &self.config
+    }
+
+    /// The mean of the training ratings.
+    pub fn global_mean(&self) -> f32 {
+        self.global_mean
+    }
+
+    /// The learned bias of a user, 0 for unknown users.
+    pub fn user_bias(&self, user_id: u32) -> f32 {
+        self.user_biases.get(&user_id).copied().unwrap_or(0.0)
+    }
+
+    /// The learned bias of an item, 0 for unknown items.
+    pub fn item_bias(&self, item_id: u32) -> f32 {
+        self.item_biases.get(&item_id).copied().unwrap_or(0.0)
+    }
+
+    /// The losses of the last training.
+    pub fn history(&self) -> &TrainingHistory {
+        &self.history
+    }
+
+    /// The baseline estimate `mu + b_u + b_i`, defined for unknown users and items
+    /// too, whose bias is 0.
+    pub fn estimate(&self, user_id: u32, item_id: u32) -> f32 {
+        self.global_mean + self.user_bias(user_id) + self.item_bias(item_id)
+    }
+
+    /// The ratings of a dataset minus their baseline estimate, what is left for
+    /// the neighborhood or the factors to explain.
+    pub fn residuals(&self, dataset: &Dataset) -> Dataset {
+        Dataset::new(
+            dataset
+                .ratings
+                .iter()
+                .map(|r| Rating {
+                    rating: r.rating - self.estimate(r.user_id, r.item_id),
+                    ..*r
+                })
+                .collect(),
+        )
+    }
+}
+
+impl Recommender for BaselineOnly {
+    fn fit(&mut self, dataset: &Dataset) -> Result<()> {
+        self.config.validate()?;
+        if dataset.is_empty() {
+            return Err(Error::InvalidData(
+                "cannot fit on an empty dataset".to_string(),
+            ));
+        }
+        let users = index(dataset.users().into_iter());
+        let item_ids: Vec<u32> = dataset.items().into_iter().collect();
+        let items = index(item_ids.iter().copied());
+        let ratings: Vec<(usize, usize, f32)> = dataset
+            .ratings
+            .iter()
+            .map(|r| (users[&r.user_id], items[&r.item_id], r.rating))
+            .collect();
+        let mean =
+            dataset.ratings.iter().map(|r| r.rating).sum::<f32>() / dataset.len() as f32;
+        let mut user_biases = vec![0.0; users.len()];
+        let mut item_biases = vec![0.0; items.len()];
+        self.history = TrainingHistory::new(self.config.max_loss_growth);
+        for _ in 0..self.config.num_epochs {
+            match self.config.method {
+                BaselineMethod::Als {
+                    user_regularization,
+                    item_regularization,
+                } => {
+                    item_biases = solve(
+                        ratings
+                            .iter()
+                            .map(|(u, i, r)| (*i, r - mean - user_biases[*u])),
+                        items.len(),
+                        item_regularization,
+                    );
+                    user_biases = solve(
+                        ratings
+                            .iter()
+                            .map(|(u, i, r)| (*u, r - mean - item_biases[*i])),
+                        users.len(),
+                        user_regularization,
+                    );
+                },
+                BaselineMethod::Sgd {
+                    learning_rate,
+                    regularization,
+                } => ratings.iter().for_each(|(u, i, r)| {
+                    let error = r - (mean + user_biases[*u] + item_biases[*i]);
+                    user_biases[*u] +=
+                        learning_rate * (error - regularization * user_biases[*u]);
+                    item_biases[*i] +=
+                        learning_rate * (error - regularization * item_biases[*i]);
+                }),
+            }
+            let loss = ratings
+                .iter()
+                .map(|(u, i, r)| (r - (mean + user_biases[*u] + item_biases[*i])) as f64)
+                .map(|error| error * error)
+                .sum::<f64>()
+                / ratings.len() as f64;
+            self.history.record(loss, None)?;
+        }
+        self.global_mean = mean;
+        self.user_biases = users.iter().map(|(id, u)| (*id, user_biases[*u])).collect();
+        self.item_biases = items.iter().map(|(id, i)| (*id, item_biases[*i])).collect();
+        self.seen = dataset.user_item_sets();
+        self.scale = dataset.scale;
+        Ok(())
+    }
+
+    fn recommend(&self, user_id: u32, num_items: usize) -> Vec<Recommendation> {
+        self.recommend_iter(user_id).take(num_items).collect()
+    }
+
+    /// Ranks the items by their bias, the user bias being the same for all of them.
+    fn recommend_iter(&self, user_id: u32) -> RankedItems {
+        let seen = match self.seen.get(&user_id) {
+            Some(seen) => seen,
+            None => return RankedItems::default(),
+        };
+        let scores: HashMap<u32, f32> = self
+            .item_biases
+            .keys()
+            .map(|item_id| (*item_id, self.estimate(user_id, *item_id)))
+            .collect();
+        RankedItems::new(scores, Some(seen))
+    }
+
+    fn predict(&self, user_id: u32, item_id: u32) -> Option<f32> {
+        let known = self.user_biases.contains_key(&user_id)
+            && self.item_biases.contains_key(&item_id);
+        let estimate = self.estimate(user_id, item_id);
+        known.then(|| self.scale.map_or(estimate, |scale| scale.clip(estimate)))
+    }
+}
+
+/// The regularized mean of the residuals of each of the `len` users or items,
+/// `sum / (regularization + count)`.
+fn solve(
+    residuals: impl Iterator<Item = (usize, f32)>,
+    len: usize,
+    regularization: f32,
+) -> Vec<f32> {
+    let mut sums = vec![(0.0, 0); len];
+    residuals.for_each(|(index, residual)| {
+        sums[index].0 += residual;
+        sums[index].1 += 1;
+    });
+    sums.into_iter()
+        .map(|(sum, count)| sum / (regularization + count as f32))
+        .collect()
+}
+
+impl PersistedModel for BaselineOnly {
+    const MODEL_TYPE: &'static str = "baseline_only";
+
+    fn hyperparameters(&self) -> BTreeMap<String, Value> {
+        fields(&self.config)
+    }
+}
+
+impl MemoryFootprint for BaselineOnly {
+    fn heap_size(&self) -> usize {
+        self.user_biases.heap_size()
+            + self.item_biases.heap_size()
+            + self.seen.heap_size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dataset() -> Dataset {
+        Dataset::new(vec![
+            Rating::new(1, 10, 5.0),
+            Rating::new(1, 11, 4.0),
+            Rating::new(2, 10, 3.0),
+            Rating::new(2, 11, 2.0),
+            Rating::new(2, 12, 1.0),
+            Rating::new(3, 12, 4.0),
+        ])
+    }
+
+    #[test]
+    fn test_als_without_regularization() {
+        // Unregularized, one epoch gives the mean residual of every item then of
+        // every user.
+        let config =
+            BaselineConfig::default()
+                .set_num_epochs(1)
+                .set_method(BaselineMethod::Als {
+                    user_regularization: 0.0,
+                    item_regularization: 0.0,
+                });
+        let mut model = BaselineOnly::new(config);
+        model.fit(&dataset()).unwrap();
+        assert_eq!(model.global_mean(), 19.0 / 6.0);
+        let mean = model.global_mean();
+        assert!((model.item_bias(10) - (4.0 - mean)).abs() < 1e-6);
+        assert!((model.item_bias(12) - (2.5 - mean)).abs() < 1e-6);
+        // User 3 only rated item 12, 1.5 above its mean.
+        assert!((model.user_bias(3) - 1.5).abs() < 1e-6);
+        assert_eq!(model.history().len(), 1);
+    }
+
+    #[test]
+    fn test_regularization_shrinks_the_biases() {
+        let mut loose = BaselineOnly::new(BaselineConfig::default().set_method(
+            BaselineMethod::Als {
+                user_regularization: 0.0,
+                item_regularization: 0.0,
+            },
+        ));
+        loose.fit(&dataset()).unwrap();
+        let mut shrunk = BaselineOnly::new(BaselineConfig::default());
+        shrunk.fit(&dataset()).unwrap();
+        assert!(shrunk.user_bias(3).abs() < loose.user_bias(3).abs());
+        assert!(shrunk.item_bias(10).abs() < loose.item_bias(10).abs());
+    }
+
+    #[test]
+    fn test_sgd() {
+        let config = BaselineConfig::default().set_num_epochs(50).set_method(
+            BaselineMethod::Sgd {
+                learning_rate: 0.05,
+                regularization: 0.02,
+            },
+        );
+        let mut model = BaselineOnly::new(config);
+        model.fit(&dataset()).unwrap();
+        let losses = model.history().train_losses();
+        assert!(losses[losses.len() - 1] < losses[0]);
+        assert!(model.user_bias(1) > model.user_bias(2));
+        assert!(model.predict(1, 12).unwrap() > model.predict(2, 12).unwrap());
+        assert_eq!(model.recommend(1, 5)[0].item_id, 12);
+    }
+
+    #[test]
+    fn test_residuals_and_unknown_ids() {
+        let mut model = BaselineOnly::new(BaselineConfig::default());
+        model.fit(&dataset()).unwrap();
+        let residuals = model.residuals(&dataset());
+        assert_eq!(residuals.len(), 6);
+        let r = residuals.ratings[0];
+        assert!((r.rating - (5.0 - model.estimate(1, 10))).abs() < 1e-6);
+        assert_eq!(model.estimate(99, 99), model.global_mean());
+        assert_eq!(model.predict(99, 10), None);
+        assert!(model.recommend(99, 5).is_empty());
+        let mut model = BaselineOnly::new(BaselineConfig::default());
+        assert!(model.fit(&Dataset::new(Vec::new())).is_err());
+    }
+}
@@ -0,0 +1,482 @@
+//! Co-clustering collaborative filtering
+use std::collections::{BTreeMap, HashMap};
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::algorithms::config::{ensure, AlgorithmConfig};
+use crate::algorithms::history::TrainingHistory;
+use crate::algorithms::mf::index;
+use crate::dataset::{Dataset, RatingScale};
+use crate::envelope::{fields, PersistedModel};
+use crate::errors::{Error, Result};
+use crate::memory::MemoryFootprint;
+use crate::recommender::{RankedItems, Recommendation, Recommender};
+use crate::sets::IdSet;
+
+/// # Co-clustering configuration
+/// Hyperparameters of [`CoClustering`].
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::algorithms::coclustering::CoClusteringConfig;
+/// use rec_rsys::algorithms::config::AlgorithmConfig;
+/// let config = CoClusteringConfig::from_toml("num_user_clusters = 5").unwrap();
+/// assert_eq!(config, CoClusteringConfig::default().set_num_user_clusters(5));
+/// assert!(config.set_num_item_clusters(0).validate().is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CoClusteringConfig {
+    pub num_user_clusters: usize,
+    pub num_item_clusters: usize,
+    /// Number of passes reassigning the users then the items.
+    pub num_epochs: usize,
+    /// Seed of the initial clusters.
+    pub seed: u64,
+}
+
+impl Default for CoClusteringConfig {
+    fn default() -> Self {
+        CoClusteringConfig {
+            num_user_clusters: 3,
+            num_item_clusters: 3,
+            num_epochs: 20,
+            seed: 42,
+        }
+    }
+}
+
+impl CoClusteringConfig {
+    pub fn set_num_user_clusters(mut self, num_user_clusters: usize) -> Self {
+        self.num_user_clusters = num_user_clusters;
+        self
+    }
+    pub fn set_num_item_clusters(mut self, num_item_clusters: usize) -> Self {
+        self.num_item_clusters = num_item_clusters;
+        self
+    }
+    pub fn set_num_epochs(mut self, num_epochs: usize) -> Self {
+        self.num_epochs = num_epochs;
+        self
+    }
+    pub fn set_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+}
+
+impl AlgorithmConfig for CoClusteringConfig {
+    fn validate(&self) -> Result<()> {
+        ensure(
+            self.num_user_clusters > 0,
+            "num_user_clusters",
+            "greater than 0",
+        )?;
+        ensure(
+            self.num_item_clusters > 0,
+            "num_item_clusters",
+            "greater than 0",
+        )?;
+        ensure(self.num_epochs > 0, "num_epochs", "greater than 0")
+    }
+}
+
+/// # Co-clustering
+/// Clusters the users and the items at the same time and predicts a rating from
+/// the mean rating of the pair of clusters, corrected by how much the user and
+/// the item deviate from the means of their own clusters. The clusters are
+/// refined by moving every user, then every item, to the cluster that best
+/// explains its ratings.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::algorithms::coclustering::{CoClustering, CoClusteringConfig};
+/// use rec_rsys::dataset::{Dataset, Rating};
+/// use rec_rsys::recommender::Recommender;
+/// // Users 1 to 4 love items 10 to 13 and hate 14 to 17, users 5 to 8 the opposite.
+/// let dataset = Dataset::new(
+///     (1..=8)
+///         .flat_map(|user| (10..18).map(move |item| (user, item)))
+///         .filter(|(user, item)| (user + item) % 3 != 0)
+///         .map(|(user, item)| {
+///             let rating = if (user < 5) == (item < 14) { 5.0 } else { 1.0 };
+///             Rating::new(user, item, rating)
+///         })
+///         .collect(),
+/// );
+/// let config = CoClusteringConfig::default()
+///     .set_num_user_clusters(2)
+///     .set_num_item_clusters(2);
+/// let mut model = CoClustering::new(config);
+/// model.fit(&dataset).unwrap();
+/// assert_eq!(model.user_cluster(1), model.user_cluster(2));
+/// assert_ne!(model.item_cluster(11), model.item_cluster(14));
+/// // User 1 did not rate items 11 and 14.
+/// assert!(model.predict(1, 11).unwrap() > model.predict(1, 14).unwrap());
+/// ```
+#[doc = include_str!("../../docs/algorithms/coclustering.md")]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CoClustering {
+    config: CoClusteringConfig,
+    global_mean: f32,
+    users: HashMap<u32, usize>,
+    items: HashMap<u32, usize>,
+    item_ids: Vec<u32>,
+    user_means: Vec<f32>,
+    item_means: Vec<f32>,
+    user_clusters: Vec<usize>,
+    item_clusters: Vec<usize>,
+    user_cluster_means: Vec<f32>,
+    item_cluster_means: Vec<f32>,
+    /// The mean rating of every pair of clusters, by user cluster then item cluster.
+    cocluster_means: Vec<Vec<f32>>,
+    seen: HashMap<u32, IdSet>,
+    /// The scale of the training ratings, the predictions are clipped to it.
+    #[serde(default)]
+    scale: Option<RatingScale>,
+    history: TrainingHistory,
+}
+
+impl CoClustering {
+    pub fn new(config: CoClusteringConfig) -> Self {
+        CoClustering {
+            config,
+            global_mean: 0.0,
+            users: HashMap::new(),
+            items: HashMap::new(),
+            item_ids: Vec::new(),
+            user_means: Vec::new(),
+            item_means: Vec::new(),
+            user_clusters: Vec::new(),
+            item_clusters: Vec::new(),
+            user_cluster_means: Vec::new(),
+            item_cluster_means: Vec::new(),
+            cocluster_means: Vec::new(),
+            seen: HashMap::new(),
+            scale: None,
+            history: TrainingHistory::default(),
+        }
+    }
+
+    pub fn config(&self) -> &CoClusteringConfig {
+        &self.config
+    }
+
+    /// The cluster of a user.
+    pub fn user_cluster(&self, user_id: u32) -> Option<usize> {
+        Some(self.user_clusters[*self.users.get(&user_id)?])
+    }
+
+    /// The cluster of an item.
+    pub fn item_cluster(&self, item_id: u32) -> Option<usize> {
+        Some(self.item_clusters[*self.items.get(&item_id)?])
+    }
+
+    /// The mean rating of a pair of user and item clusters.
+    pub fn cocluster_mean(
+        &self,
+        user_cluster: usize,
+        item_cluster: usize,
+    ) -> Option<f32> {
+        self.cocluster_means
+            .get(user_cluster)?
+            .get(item_cluster)
+            .copied()
+    }
+
+    /// The mean squared errors of the last training, one per epoch.
+    pub fn history(&self) -> &TrainingHistory {
+        &self.history
+    }
+
+    /// Rating predicted from the rows of a user and an item, in the given clusters.
+    fn score_in(
+        &self,
+        user: usize,
+        item: usize,
+        user_cluster: usize,
+        item_cluster: usize,
+    ) -> f32 {
+        self.cocluster_means[user_cluster][item_cluster]
+            + (self.user_means[user] - self.user_cluster_means[user_cluster])
+            + (self.item_means[item] - self.item_cluster_means[item_cluster])
+    }
+
+    fn score(&self, user: usize, item: usize) -> f32 {
+        self.score_in(
+            user,
+            item,
+            self.user_clusters[user],
+            self.item_clusters[item],
+        )
+    }
+
+    /// The means of the clusters and of the pairs of clusters, the global mean for
+    /// the empty ones.
+    fn update_means(&mut self, ratings: &[(usize, usize, f32)]) {
+        let (users, items) =
+            (self.config.num_user_clusters, self.config.num_item_clusters);
+        let mut user_sums = vec![(0.0, 0); users];
+        let mut item_sums = vec![(0.0, 0); items];
+        let mut cocluster_sums = vec![vec![(0.0, 0); items]; users];
+        for &(user, item, rating) in ratings {
+            let (cu, ci) = (self.user_clusters[user], self.item_clusters[item]);
+            for (sum, count) in [
+                &mut user_sums[cu],
+                &mut item_sums[ci],
+                &mut cocluster_sums[cu][ci],
+            ] {
+                *sum += rating as f64;
+                *count += 1;
+            }
+        }
+        let mean = |(sum, count): (f64, usize)| match count {
+            0 => self.global_mean,
+            _ => (sum / count as f64) as f32,
+        };
+        self.user_cluster_means = user_sums.into_iter().map(mean).collect();
+        self.item_cluster_means = item_sums.into_iter().map(mean).collect();
+        self.cocluster_means = cocluster_sums
+            .into_iter()
+            .map(|row| row.into_iter().map(mean).collect())
+            .collect();
+    }
+
+    /// Moves every user to the cluster with the lowest squared error on their
+    /// ratings, given as `(item row, rating)` per user.
+    fn assign_users(&mut self, by_user: &[Vec<(usize, f32)>]) {
+        self.user_clusters = by_user
+            .iter()
+            .enumerate()
+            .map(|(user, ratings)| {
+                best_cluster(self.config.num_user_clusters, |cluster| {
+                    ratings
+                        .iter()
+                        .map(|&(item, rating)| {
+                            let item_cluster = self.item_clusters[item];
+                            rating - self.score_in(user, item, cluster, item_cluster)
+                        })
+                        .map(|error| error * error)
+                        .sum()
+                })
+            })
+            .collect();
+    }
+
+    /// Moves every item to the cluster with the lowest squared error on its
+    /// ratings, given as `(user row, rating)` per item.
+    fn assign_items(&mut self, by_item: &[Vec<(usize, f32)>]) {
+        self.item_clusters = by_item
+            .iter()
+            .enumerate()
+            .map(|(item, ratings)| {
+                best_cluster(self.config.num_item_clusters, |cluster| {
+                    ratings
+                        .iter()
+                        .map(|&(user, rating)| {
+                            let user_cluster = self.user_clusters[user];
+                            rating - self.score_in(user, item, user_cluster, cluster)
+                        })
+                        .map(|error| error * error)
+                        .sum()
+                })
+            })
+            .collect();
+    }
+}
+
+/// The cluster with the lowest error, the first one on ties.
+fn best_cluster(num_clusters: usize, error: impl Fn(usize) -> f32) -> usize {
+    (0..num_clusters)
+        .map(|cluster| (cluster, error(cluster)))
+        .fold((0, f32::INFINITY), |best, (cluster, error)| {
+            match error < best.1 {
+                true => (cluster, error),
+                false => best,
+            }
+        })
+        .0
+}
+
+/// The mean of the ratings of every row.
+fn means(rows: &[Vec<(usize, f32)>]) -> Vec<f32> {
+    rows.iter()
+        .map(|ratings| {
+            ratings.iter().map(|(_, rating)| rating).sum::<f32>() / ratings.len() as f32
+        })
+        .collect()
+}
+
+impl Recommender for CoClustering {
+    fn fit(&mut self, dataset: &Dataset) -> Result<()> {
+        self.config.validate()?;
+        if dataset.is_empty() {
+            return Err(Error::InvalidData(
+                "cannot fit on an empty dataset".to_string(),
+            ));
+        }
+        let mut rng = StdRng::seed_from_u64(self.config.seed);
+        self.users = index(dataset.users().into_iter());
+        self.item_ids = dataset.items().into_iter().collect();
+        self.items = index(self.item_ids.iter().copied());
+        let ratings: Vec<(usize, usize, f32)> = dataset
+            .ratings
+            .iter()
+            .map(|r| (self.users[&r.user_id], self.items[&r.item_id], r.rating))
+            .collect();
+        let mut by_user = vec![Vec::new(); self.users.len()];
+        let mut by_item = vec![Vec::new(); self.items.len()];
+        ratings.iter().for_each(|&(user, item, rating)| {
+            by_user[user].push((item, rating));
+            by_item[item].push((user, rating));
+        });
+        self.global_mean =
+            ratings.iter().map(|r| r.2).sum::<f32>() / ratings.len() as f32;
+        self.user_means = means(&by_user);
+        self.item_means = means(&by_item);
+        self.user_clusters = (0..self.users.len())
+            .map(|_| rng.gen_range(0..self.config.num_user_clusters))
+            .collect();
+        self.item_clusters = (0..self.items.len())
+            .map(|_| rng.gen_range(0..self.config.num_item_clusters))
+            .collect();
+        self.seen = dataset.user_item_sets();
+        self.scale = dataset.scale;
+        self.history = TrainingHistory::default();
+        self.update_means(&ratings);
+        for _ in 0..self.config.num_epochs {
+            self.assign_users(&by_user);
+            self.update_means(&ratings);
+            self.assign_items(&by_item);
+            self.update_means(&ratings);
+            let error: f64 = ratings
+                .iter()
+                .map(|&(user, item, rating)| (rating - self.score(user, item)) as f64)
+                .map(|error| error * error)
+                .sum();
+            self.history.record(error / ratings.len() as f64, None)?;
+        }
+        Ok(())
+    }
+
+    fn recommend(&self, user_id: u32, num_items: usize) -> Vec<Recommendation> {
+        self.recommend_iter(user_id).take(num_items).collect()
+    }
+
+    fn recommend_iter(&self, user_id: u32) -> RankedItems {
+        let user = match self.users.get(&user_id) {
+            Some(user) => *user,
+            None => return RankedItems::default(),
+        };
+        let scores: HashMap<u32, f32> = self
+            .item_ids
+            .iter()
+            .enumerate()
+            .map(|(item, item_id)| (*item_id, self.score(user, item)))
+            .collect();
+        RankedItems::new(scores, self.seen.get(&user_id))
+    }
+
+    fn predict(&self, user_id: u32, item_id: u32) -> Option<f32> {
+        let user = self.users.get(&user_id)?;
+        let item = self.items.get(&item_id)?;
+        let score = self.score(*user, *item);
+        Some(self.scale.map_or(score, |scale| scale.clip(score)))
+    }
+}
+
+impl PersistedModel for CoClustering {
+    const MODEL_TYPE: &'static str = "coclustering";
+
+    fn hyperparameters(&self) -> BTreeMap<String, Value> {
+        fields(&self.config)
+    }
+}
+
+impl MemoryFootprint for CoClustering {
+    fn heap_size(&self) -> usize {
+        self.users.heap_size()
+            + self.items.heap_size()
+            + self.item_ids.heap_size()
+            + self.user_means.heap_size()
+            + self.item_means.heap_size()
+            + self.user_clusters.heap_size()
+            + self.item_clusters.heap_size()
+            + self.user_cluster_means.heap_size()
+            + self.item_cluster_means.heap_size()
+            + self.cocluster_means.heap_size()
+            + self.seen.heap_size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dataset::Rating;
+
+    /// Two groups of users, each liking one of two groups of items.
+    fn dataset() -> Dataset {
+        Dataset::new(
+            (1..=8)
+                .flat_map(|user| {
+                    (10..18).map(move |item| {
+                        let rating = if (user < 5) == (item < 14) { 5.0 } else { 1.0 };
+                        Rating::new(user, item, rating)
+                    })
+                })
+                .filter(|r| (r.user_id + r.item_id) % 3 != 0)
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_recovers_the_blocks() {
+        let config = CoClusteringConfig::default()
+            .set_num_user_clusters(2)
+            .set_num_item_clusters(2);
+        let mut model = CoClustering::new(config);
+        model.fit(&dataset()).unwrap();
+        let users: Vec<usize> = (1..=8).map(|u| model.user_cluster(u).unwrap()).collect();
+        assert!(users[..4].iter().all(|c| *c == users[0]));
+        assert!(users[4..].iter().all(|c| *c == users[4]));
+        assert_ne!(users[0], users[4]);
+        let items: Vec<usize> =
+            (10..18).map(|i| model.item_cluster(i).unwrap()).collect();
+        assert!(items[..4].iter().all(|c| *c == items[0]));
+        assert_ne!(items[0], items[4]);
+        // The ratings left out of the dataset are recovered, up to the biases of the
+        // users and items whose left out ratings were mostly high or low.
+        assert!((model.predict(1, 11).unwrap() - 5.0).abs() < 1.0);
+        assert!((model.predict(1, 14).unwrap() - 1.0).abs() < 1.0);
+        assert!(model.history().last().unwrap().train_loss < 0.5);
+        assert_eq!(model.cocluster_mean(users[0], items[0]), Some(5.0));
+        assert!(model.predict(1, 99).is_none());
+    }
+
+    #[test]
+    fn test_more_clusters_than_rows() {
+        let config = CoClusteringConfig::default()
+            .set_num_user_clusters(20)
+            .set_num_item_clusters(20)
+            .set_num_epochs(3);
+        let mut model = CoClustering::new(config);
+        model.fit(&dataset()).unwrap();
+        assert!(model.predict(2, 12).unwrap().is_finite());
+        assert_eq!(
+            model.recommend(1, 100).len(),
+            8 - dataset().user_items()[&1].len()
+        );
+    }
+
+    #[test]
+    fn test_fit_rejects_invalid_input() {
+        let mut model =
+            CoClustering::new(CoClusteringConfig::default().set_num_epochs(0));
+        assert!(model.fit(&dataset()).is_err());
+        let mut model = CoClustering::new(CoClusteringConfig::default());
+        assert!(model.fit(&Dataset::new(Vec::new())).is_err());
+    }
+}
@@ -0,0 +1,70 @@
+//! # Configuration of the algorithms
+//! Every algorithm exposes its hyperparameters as a config struct with a `Default`
+//! implementation and `set_*` builder methods. With the `serde` feature they can also
+//! be loaded from JSON or TOML files.
+#[cfg(feature = "serde")]
+use std::fs;
+#[cfg(feature = "serde")]
+use std::path::Path;
+
+#[cfg(feature = "serde")]
+use serde::de::DeserializeOwned;
+
+use crate::errors::{Error, Result};
+
+pub trait AlgorithmConfig: Default {
+    /// Checks that every hyperparameter has a usable value.
+    fn validate(&self) -> Result<()>;
+
+    /// Parses and validates a JSON config. Missing fields take their default value.
+    #[cfg(feature = "serde")]
+    fn from_json(content: &str) -> Result<Self>
+    where
+        Self: DeserializeOwned,
+    {
+        let config: Self = serde_json::from_str(content)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Parses and validates a TOML config. Missing fields take their default value.
+    #[cfg(feature = "serde")]
+    fn from_toml(content: &str) -> Result<Self>
+    where
+        Self: DeserializeOwned,
+    {
+        let config: Self = toml::from_str(content)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Loads a config from a `.json` or `.toml` file.
+    #[cfg(feature = "serde")]
+    fn from_file<P: AsRef<Path>>(path: P) -> Result<Self>
+    where
+        Self: DeserializeOwned,
+    {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)?;
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => Self::from_json(&content),
+            Some("toml") => Self::from_toml(&content),
+            _ => Err(Error::InvalidConfig(format!(
+                "unsupported config file {}, expected a .json or .toml file",
+                path.display()
+            ))),
+        }
+    }
+}
+
+/// Returns an [`Error::InvalidConfig`] naming the parameter when `condition` is false.
+pub(crate) fn ensure(condition: bool, parameter: &str, expected: &str) -> Result<()> {
+    if condition {
+        Ok(())
+    } else {
+        Err(Error::InvalidConfig(format!(
+            "`{}` must be {}",
+            parameter, expected
+        )))
+    }
+}
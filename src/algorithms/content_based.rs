@@ -0,0 +1,429 @@
+//! Content based recommender
+#[cfg(feature = "text")]
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+
+use crate::algorithms::config::AlgorithmConfig;
+use crate::algorithms::knn::{knn_scores, to_similarity, KNNConfig};
+use crate::dataset::Dataset;
+use crate::errors::Result;
+use crate::memory::MemoryFootprint;
+use crate::models::Item;
+use crate::profiles::{build_profiles, Aggregation, UserProfile};
+use crate::recommender::{RankedItems, Recommendation, Recommender};
+use crate::sets::IdSet;
+use crate::similarity::{apply_idf, tag_idf, TagSimilarity, TagWeights};
+#[cfg(feature = "text")]
+use crate::text::Bm25;
+
+/// # Content based
+/// Builds the [`UserProfile`] of every user from the vectors of the items they
+/// rated (features of a catalog, embeddings...) and recommends the unseen items
+/// that are the nearest to the profile with [`KNN`](crate::algorithms::knn::KNN).
+///
+/// With [`ContentBased::set_tags`] the items are compared by their tags instead:
+/// the profile of a user is the mean of the IDF weighted tags of the items they
+/// rated and the unseen tagged items are ranked by their [`TagSimilarity`] to it.
+/// With `ContentBased::set_bm25`, under the `text` feature, they are compared by their texts: the terms of
+/// the texts of the items a user rated are the query `Bm25` scores the unseen
+/// items with.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::algorithms::content_based::ContentBased;
+/// use rec_rsys::algorithms::knn::KNNConfig;
+/// use rec_rsys::dataset::{Dataset, Rating};
+/// use rec_rsys::models::Item;
+/// use rec_rsys::profiles::Aggregation;
+/// use rec_rsys::recommender::Recommender;
+/// let items = vec![
+///     Item::new(10, vec![1.0, 0.0], None),
+///     Item::new(11, vec![0.9, 0.1], None),
+///     Item::new(12, vec![0.0, 1.0], None),
+/// ];
+/// let mut model = ContentBased::new(items, KNNConfig::default(), Aggregation::Mean);
+/// model.fit(&Dataset::new(vec![Rating::new(1, 10, 5.0)])).unwrap();
+/// assert_eq!(model.recommend(1, 1)[0].item_id, 11);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ContentBased {
+    items: Vec<Item>,
+    config: KNNConfig,
+    aggregation: Aggregation,
+    /// The lowest rating of the items the profiles are built from.
+    min_rating: Option<f32>,
+    profiles: HashMap<u32, UserProfile>,
+    seen: HashMap<u32, IdSet>,
+    tags: HashMap<u32, TagWeights>,
+    tag_similarity: TagSimilarity,
+    /// Sum of the tags of the items of every user, with their number.
+    tag_profiles: HashMap<u32, (TagWeights, usize)>,
+    #[cfg(feature = "text")]
+    bm25: Option<Bm25>,
+}
+
+impl ContentBased {
+    pub fn new(items: Vec<Item>, config: KNNConfig, aggregation: Aggregation) -> Self {
+        ContentBased {
+            items,
+            config,
+            aggregation,
+            min_rating: None,
+            profiles: HashMap::new(),
+            seen: HashMap::new(),
+            tags: HashMap::new(),
+            tag_similarity: TagSimilarity::WeightedJaccard,
+            tag_profiles: HashMap::new(),
+            #[cfg(feature = "text")]
+            bm25: None,
+        }
+    }
+
+    /// Builds the vector and tag profiles of the users from the items they rated
+    /// `min_rating` or more only, e.g. the ones they liked. Every rated item is still
+    /// seen and never recommended, unlike when the model is fitted on the liked
+    /// ratings only.
+    ///
+    /// ## Examples:
+    /// ```
+    /// use rec_rsys::algorithms::content_based::ContentBased;
+    /// use rec_rsys::algorithms::knn::KNNConfig;
+    /// use rec_rsys::dataset::{Dataset, Rating};
+    /// use rec_rsys::models::Item;
+    /// use rec_rsys::profiles::Aggregation;
+    /// use rec_rsys::recommender::Recommender;
+    /// let items = vec![
+    ///     Item::new(10, vec![1.0, 0.0], None),
+    ///     Item::new(11, vec![0.9, 0.1], None),
+    ///     Item::new(12, vec![0.0, 1.0], None),
+    /// ];
+    /// let mut model = ContentBased::new(items, KNNConfig::default(), Aggregation::Mean)
+    ///     .set_min_rating(4.0);
+    /// model.fit(&Dataset::new(vec![Rating::new(1, 10, 5.0), Rating::new(1, 11, 1.0)])).unwrap();
+    /// let ids: Vec<u32> = model.recommend(1, 2).iter().map(|r| r.item_id).collect();
+    /// assert_eq!(ids, [12]);
+    /// ```
+    pub fn set_min_rating(mut self, min_rating: f32) -> Self {
+        self.min_rating = Some(min_rating);
+        self
+    }
+
+    /// # Set tags
+    /// Compares the items by their tags, e.g. the ones of
+    /// [`ItemCatalog::tag_weights`](crate::catalog::ItemCatalog::tag_weights),
+    /// rather than by their vectors. The weights are multiplied by the IDF of the
+    /// tags over the items given, so shared rare tags count more than shared
+    /// common ones.
+    ///
+    /// ## Examples:
+    /// ```
+    /// use std::collections::HashMap;
+    /// use rec_rsys::algorithms::content_based::ContentBased;
+    /// use rec_rsys::algorithms::knn::KNNConfig;
+    /// use rec_rsys::dataset::{Dataset, Rating};
+    /// use rec_rsys::profiles::Aggregation;
+    /// use rec_rsys::recommender::Recommender;
+    /// use rec_rsys::similarity::{TagSimilarity, TagWeights};
+    /// let tags = |tags: &[&str]| -> TagWeights {
+    ///     tags.iter().map(|tag| (tag.to_string(), 1.0)).collect()
+    /// };
+    /// let items = HashMap::from([
+    ///     (10, tags(&["rock", "grunge"])),
+    ///     (11, tags(&["rock", "pop"])),
+    ///     (12, tags(&["rock", "grunge", "punk"])),
+    /// ]);
+    /// let mut model = ContentBased::new(vec![], KNNConfig::default(), Aggregation::Mean)
+    ///     .set_tags(items, TagSimilarity::WeightedJaccard);
+    /// model.fit(&Dataset::new(vec![Rating::new(1, 10, 5.0)])).unwrap();
+    /// assert_eq!(model.recommend(1, 1)[0].item_id, 12);
+    /// ```
+    pub fn set_tags(
+        mut self,
+        tags: HashMap<u32, TagWeights>,
+        similarity: TagSimilarity,
+    ) -> Self {
+        let idf = tag_idf(tags.values());
+        self.tags = tags
+            .into_iter()
+            .map(|(item_id, tags)| (item_id, apply_idf(&tags, &idf)))
+            .collect();
+        self.tag_similarity = similarity;
+        self
+    }
+
+    /// Compares the items by their texts indexed in `bm25`, e.g. with
+    /// [`Bm25::fit_catalog`]. Takes precedence over [`ContentBased::set_tags`].
+    #[cfg(feature = "text")]
+    pub fn set_bm25(mut self, bm25: Bm25) -> Self {
+        self.bm25 = Some(bm25);
+        self
+    }
+
+    /// The profile of a user, once fitted.
+    pub fn profile(&self, user_id: u32) -> Option<&UserProfile> {
+        self.profiles.get(&user_id)
+    }
+
+    /// Updates the profile of a user with a new interaction, without refitting.
+    /// Items without vector are ignored.
+    pub fn update(&mut self, user_id: u32, item_id: u32, timestamp: u64) {
+        if let Some(item) = self.items.iter().find(|item| item.id == item_id) {
+            self.profiles
+                .entry(user_id)
+                .or_insert_with(|| UserProfile::new(user_id, self.aggregation))
+                .update(&item.values, timestamp);
+        } else if !self.tags.contains_key(&item_id) && !self.has_text(item_id) {
+            return;
+        }
+        self.add_tags(user_id, item_id);
+        self.seen.entry(user_id).or_default().insert(item_id);
+    }
+
+    #[cfg(feature = "text")]
+    fn has_text(&self, item_id: u32) -> bool {
+        self.bm25
+            .as_ref()
+            .is_some_and(|bm25| bm25.terms(item_id).is_some())
+    }
+
+    #[cfg(not(feature = "text"))]
+    fn has_text(&self, _item_id: u32) -> bool {
+        false
+    }
+
+    fn add_tags(&mut self, user_id: u32, item_id: u32) {
+        let tags = match self.tags.get(&item_id) {
+            Some(tags) => tags,
+            None => return,
+        };
+        let (sum, count) = self.tag_profiles.entry(user_id).or_default();
+        for (tag, weight) in tags {
+            *sum.entry(tag.clone()).or_default() += weight;
+        }
+        *count += 1;
+    }
+
+    #[cfg(feature = "text")]
+    fn recommend_by_text(&self, bm25: &Bm25, user_id: u32) -> RankedItems {
+        let seen = match self.seen.get(&user_id) {
+            Some(seen) => seen,
+            None => return RankedItems::default(),
+        };
+        let mut query: BTreeMap<String, f32> = BTreeMap::new();
+        for terms in seen.iter().filter_map(|item_id| bm25.terms(item_id)) {
+            for (term, count) in terms {
+                *query.entry(term.clone()).or_default() += *count as f32;
+            }
+        }
+        let scores: HashMap<u32, f32> = bm25
+            .score_terms(&query)
+            .into_iter()
+            .filter(|(item_id, _)| !seen.contains(*item_id))
+            .collect();
+        RankedItems::new(scores, None)
+    }
+
+    fn recommend_by_tags(&self, user_id: u32) -> RankedItems {
+        let profile: TagWeights = match self.tag_profiles.get(&user_id) {
+            Some((sum, count)) => sum
+                .iter()
+                .map(|(tag, weight)| (tag.clone(), weight / *count as f32))
+                .collect(),
+            None => return RankedItems::default(),
+        };
+        let seen = self.seen.get(&user_id);
+        let scores: HashMap<u32, f32> = self
+            .tags
+            .iter()
+            .filter(|(item_id, _)| seen.is_none_or(|s| !s.contains(**item_id)))
+            .map(|(item_id, tags)| {
+                (*item_id, self.tag_similarity.compute(&profile, tags))
+            })
+            .collect();
+        RankedItems::new(scores, None)
+    }
+}
+
+impl Recommender for ContentBased {
+    fn fit(&mut self, dataset: &Dataset) -> Result<()> {
+        self.config.validate()?;
+        self.aggregation.validate()?;
+        let liked;
+        let profiled = match self.min_rating {
+            Some(min_rating) => {
+                liked = dataset.filter(|r| r.rating >= min_rating);
+                &liked
+            },
+            None => dataset,
+        };
+        self.profiles = build_profiles(profiled, &self.items, self.aggregation);
+        self.seen = dataset.user_item_sets();
+        self.tag_profiles = HashMap::new();
+        for rating in &profiled.ratings {
+            self.add_tags(rating.user_id, rating.item_id);
+        }
+        Ok(())
+    }
+
+    fn recommend(&self, user_id: u32, num_items: usize) -> Vec<Recommendation> {
+        self.recommend_iter(user_id).take(num_items).collect()
+    }
+
+    fn recommend_iter(&self, user_id: u32) -> RankedItems {
+        #[cfg(feature = "text")]
+        if let Some(bm25) = &self.bm25 {
+            return self.recommend_by_text(bm25, user_id);
+        }
+        if !self.tags.is_empty() {
+            return self.recommend_by_tags(user_id);
+        }
+        let profile = match self.profiles.get(&user_id) {
+            Some(profile) => profile,
+            None => return RankedItems::default(),
+        };
+        let seen = self.seen.get(&user_id);
+        let pool: Vec<&Item> = self
+            .items
+            .iter()
+            .filter(|item| seen.is_none_or(|s| !s.contains(item.id)))
+            .collect();
+        let scores: HashMap<u32, f32> =
+            match knn_scores(&profile.vector(), &pool, &self.config) {
+                Ok(scores) => scores
+                    .into_iter()
+                    .map(|(id, value)| (id, to_similarity(self.config.algorithm, value)))
+                    .filter(|(_, similarity)| similarity.is_finite())
+                    .collect(),
+                Err(_) => return RankedItems::default(),
+            };
+        RankedItems::new(scores, None)
+    }
+}
+
+impl MemoryFootprint for ContentBased {
+    fn heap_size(&self) -> usize {
+        let size = self.items.heap_size()
+            + self.profiles.heap_size()
+            + self.seen.heap_size()
+            + self.tags.heap_size()
+            + self.tag_profiles.heap_size();
+        #[cfg(feature = "text")]
+        let size = size + self.bm25.as_ref().map_or(0, |bm25| bm25.heap_size());
+        size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dataset::Rating;
+    use crate::similarity::SimilarityAlgos;
+    #[cfg(feature = "text")]
+    use crate::text::Bm25Config;
+
+    fn items() -> Vec<Item> {
+        vec![
+            Item::new(10, vec![1.0, 0.0], None),
+            Item::new(11, vec![0.8, 0.2], None),
+            Item::new(12, vec![0.0, 1.0], None),
+            Item::new(13, vec![0.1, 0.9], None),
+        ]
+    }
+
+    #[test]
+    fn test_recommend_nearest_unseen() {
+        let mut model = ContentBased::new(
+            items(),
+            KNNConfig::default().set_algorithm(SimilarityAlgos::Euclidean),
+            Aggregation::Mean,
+        );
+        model
+            .fit(&Dataset::new(vec![Rating::new(1, 12, 5.0)]))
+            .unwrap();
+        let recommended: Vec<u32> =
+            model.recommend(1, 3).iter().map(|r| r.item_id).collect();
+        assert_eq!(recommended, vec![13, 11, 10]);
+        assert!(model.recommend(2, 3).is_empty());
+    }
+
+    #[test]
+    fn test_update() {
+        let mut model =
+            ContentBased::new(items(), KNNConfig::default(), Aggregation::Mean);
+        model
+            .fit(&Dataset::new(vec![Rating::new(1, 12, 5.0)]))
+            .unwrap();
+        model.update(1, 10, 0);
+        model.update(2, 10, 0);
+        assert_eq!(model.profile(1).unwrap().vector(), vec![0.5, 0.5]);
+        assert_eq!(model.recommend(2, 1)[0].item_id, 11);
+    }
+
+    #[test]
+    fn test_min_rating() {
+        let ratings =
+            Dataset::new(vec![Rating::new(1, 12, 5.0), Rating::new(1, 10, 1.0)]);
+        let mut model =
+            ContentBased::new(items(), KNNConfig::default(), Aggregation::Mean)
+                .set_min_rating(4.0);
+        model.fit(&ratings).unwrap();
+        // Item 10 was rated too low to shape the profile, but it was seen.
+        assert_eq!(model.profile(1).unwrap().vector(), vec![0.0, 1.0]);
+        let recommended: Vec<u32> =
+            model.recommend(1, 3).iter().map(|r| r.item_id).collect();
+        assert_eq!(recommended, vec![13, 11]);
+    }
+
+    #[test]
+    fn test_recommend_by_tags() {
+        let tags = |tags: &[(&str, f32)]| -> TagWeights {
+            tags.iter().map(|(tag, w)| (tag.to_string(), *w)).collect()
+        };
+        // "rock" is on every item, only the rare tags tell the items apart.
+        let catalog = HashMap::from([
+            (10, tags(&[("rock", 5.0), ("shoegaze", 1.0)])),
+            (11, tags(&[("rock", 5.0), ("pop", 1.0)])),
+            (12, tags(&[("rock", 1.0), ("shoegaze", 1.0)])),
+            (13, tags(&[("rock", 1.0), ("pop", 1.0)])),
+        ]);
+        for similarity in [TagSimilarity::WeightedJaccard, TagSimilarity::Cosine] {
+            let mut model =
+                ContentBased::new(vec![], KNNConfig::default(), Aggregation::Mean)
+                    .set_tags(catalog.clone(), similarity);
+            model
+                .fit(&Dataset::new(vec![Rating::new(1, 12, 5.0)]))
+                .unwrap();
+            assert_eq!(model.recommend(1, 1)[0].item_id, 10);
+            assert!(model.recommend(2, 1).is_empty());
+            model.update(2, 13, 0);
+            model.update(2, 99, 0);
+            let recommended = model.recommend(2, 3);
+            assert_eq!(recommended.len(), 3);
+            assert_eq!(recommended[0].item_id, 11);
+        }
+    }
+
+    #[cfg(feature = "text")]
+    #[test]
+    fn test_recommend_by_text() {
+        let documents = [
+            (10, "A heist in Paris"),
+            (11, "A wedding in Rome"),
+            (12, "The Paris heist goes wrong"),
+            (13, "A Rome wedding"),
+        ];
+        let bm25 = Bm25::fit(&documents, &Bm25Config::default()).unwrap();
+        let mut model =
+            ContentBased::new(vec![], KNNConfig::default(), Aggregation::Mean)
+                .set_bm25(bm25);
+        model
+            .fit(&Dataset::new(vec![Rating::new(1, 10, 5.0)]))
+            .unwrap();
+        let recommended = model.recommend(1, 3);
+        assert_eq!(recommended.len(), 1);
+        assert_eq!(recommended[0].item_id, 12);
+        model.update(2, 11, 0);
+        assert_eq!(model.recommend(2, 1)[0].item_id, 13);
+        assert!(model.recommend(3, 1).is_empty());
+    }
+}
@@ -0,0 +1,378 @@
+//! Session co-occurrence recommender, updated incrementally
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::algorithms::config::{ensure, AlgorithmConfig};
+use crate::dataset::Dataset;
+use crate::envelope::{fields, PersistedModel};
+use crate::errors::Result;
+use crate::memory::MemoryFootprint;
+use crate::recommender::{RankedItems, Recommendation, Recommender};
+use crate::sets::IdSet;
+
+/// # Co-occurrence configuration
+/// Hyperparameters of [`CoOccurrence`].
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::algorithms::config::AlgorithmConfig;
+/// use rec_rsys::algorithms::cooccurrence::CoOccurrenceConfig;
+/// let config =
+///     CoOccurrenceConfig::from_toml("half_life = 86400.0\nsession_gap = 1800").unwrap();
+/// assert_eq!(config.half_life, Some(86400.0));
+/// assert!(config.set_window(0).validate().is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CoOccurrenceConfig {
+    /// Seconds after which a co-occurrence weighs half as much, `None` to never
+    /// forget.
+    pub half_life: Option<f64>,
+    /// Seconds without interaction after which the next one starts a new session,
+    /// `None` for a single session per user.
+    pub session_gap: Option<u64>,
+    /// Number of previous items of the session an interaction co-occurs with.
+    pub window: usize,
+}
+
+impl Default for CoOccurrenceConfig {
+    fn default() -> Self {
+        CoOccurrenceConfig {
+            half_life: None,
+            session_gap: None,
+            window: 10,
+        }
+    }
+}
+
+impl CoOccurrenceConfig {
+    pub fn set_half_life(mut self, half_life: f64) -> Self {
+        self.half_life = Some(half_life);
+        self
+    }
+    pub fn set_session_gap(mut self, session_gap: u64) -> Self {
+        self.session_gap = Some(session_gap);
+        self
+    }
+    pub fn set_window(mut self, window: usize) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// How much a weight recorded `elapsed` seconds ago still counts.
+    fn decay(&self, elapsed: u64) -> f64 {
+        match self.half_life {
+            Some(half_life) => 0.5_f64.powf(elapsed as f64 / half_life),
+            None => 1.0,
+        }
+    }
+}
+
+impl AlgorithmConfig for CoOccurrenceConfig {
+    fn validate(&self) -> Result<()> {
+        ensure(
+            self.half_life
+                .is_none_or(|half_life| half_life > 0.0 && half_life.is_finite()),
+            "half_life",
+            "a positive number",
+        )?;
+        ensure(self.window > 0, "window", "greater than 0")
+    }
+}
+
+/// A count decayed lazily: the value is exact at `timestamp` and decays from there.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+struct DecayedCount {
+    value: f64,
+    timestamp: u64,
+}
+
+impl DecayedCount {
+    /// Adds one occurrence at `timestamp`, which may be older than the last one.
+    fn increment(&mut self, timestamp: u64, config: &CoOccurrenceConfig) {
+        if timestamp >= self.timestamp {
+            self.value = self.value * config.decay(timestamp - self.timestamp) + 1.0;
+            self.timestamp = timestamp;
+        } else {
+            self.value += config.decay(self.timestamp - timestamp);
+        }
+    }
+
+    /// The value at `now`, never above the value at the last update.
+    fn at(&self, now: u64, config: &CoOccurrenceConfig) -> f64 {
+        self.value * config.decay(now.saturating_sub(self.timestamp))
+    }
+}
+
+/// The last items of the current session of a user.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct Session {
+    items: Vec<u32>,
+    last: u64,
+}
+
+/// # Co-occurrence
+/// Counts how often two items are interacted with in the same session, within
+/// `window` interactions of each other, and recommends the items co-occurring the
+/// most with the current session of the user. Interactions are added one at a
+/// time with [`CoOccurrence::update`], so the counts follow a stream of events
+/// without rebuilding, and with a `half_life` the old co-occurrences fade so the
+/// recent behavior dominates.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::algorithms::cooccurrence::{CoOccurrence, CoOccurrenceConfig};
+/// use rec_rsys::dataset::{Dataset, Rating};
+/// use rec_rsys::recommender::Recommender;
+/// let mut model = CoOccurrence::new(CoOccurrenceConfig::default().set_half_life(100.0));
+/// model.fit(&Dataset::new(vec![
+///     Rating::new(1, 10, 1.0).timestamp(0), Rating::new(1, 11, 1.0).timestamp(0),
+/// ])).unwrap();
+/// assert_eq!(model.count(10, 11), 1.0);
+/// // A week later, users pair item 10 with item 12.
+/// model.update(2, 10, 1000);
+/// model.update(2, 12, 1000);
+/// model.update(3, 10, 1000);
+/// assert_eq!(model.recommend(3, 1)[0].item_id, 12);
+/// assert!(model.count(10, 11) < 0.001);
+/// ```
+#[doc = include_str!("../../docs/algorithms/cooccurrence.md")]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CoOccurrence {
+    config: CoOccurrenceConfig,
+    counts: HashMap<u32, HashMap<u32, DecayedCount>>,
+    sessions: HashMap<u32, Session>,
+    seen: HashMap<u32, IdSet>,
+    /// The latest timestamp seen, the time the counts are read at.
+    now: u64,
+}
+
+impl CoOccurrence {
+    pub fn new(config: CoOccurrenceConfig) -> Self {
+        CoOccurrence {
+            config,
+            ..CoOccurrence::default()
+        }
+    }
+
+    pub fn config(&self) -> &CoOccurrenceConfig {
+        &self.config
+    }
+
+    /// The latest timestamp of the interactions.
+    pub fn now(&self) -> u64 {
+        self.now
+    }
+
+    /// # Update
+    /// Adds an interaction: the item co-occurs with the previous items of the
+    /// session of the user, which starts anew after `session_gap` seconds without
+    /// interaction. Interactions may arrive slightly out of order, an older one
+    /// counts with the weight it would have now.
+    ///
+    /// ## Parameters:
+    /// * `user_id`: The user who interacted.
+    /// * `item_id`: The item they interacted with.
+    /// * `timestamp`: When, in seconds.
+    pub fn update(&mut self, user_id: u32, item_id: u32, timestamp: u64) {
+        let config = &self.config;
+        let session = self.sessions.entry(user_id).or_default();
+        let expired = config
+            .session_gap
+            .is_some_and(|gap| timestamp.saturating_sub(session.last) > gap);
+        if expired {
+            session.items.clear();
+        }
+        for &other in session.items.iter().filter(|other| **other != item_id) {
+            for (a, b) in [(item_id, other), (other, item_id)] {
+                self.counts
+                    .entry(a)
+                    .or_default()
+                    .entry(b)
+                    .or_default()
+                    .increment(timestamp, config);
+            }
+        }
+        session.items.retain(|other| *other != item_id);
+        session.items.push(item_id);
+        if session.items.len() > config.window {
+            session.items.remove(0);
+        }
+        session.last = session.last.max(timestamp);
+        self.seen.entry(user_id).or_default().insert(item_id);
+        self.now = self.now.max(timestamp);
+    }
+
+    /// The decayed number of co-occurrences of two items, now.
+    pub fn count(&self, a: u32, b: u32) -> f32 {
+        self.counts
+            .get(&a)
+            .and_then(|counts| counts.get(&b))
+            .map_or(0.0, |count| count.at(self.now, &self.config) as f32)
+    }
+
+    /// The items co-occurring with an item, the most frequent first.
+    pub fn neighbors(&self, item_id: u32) -> Vec<(u32, f32)> {
+        let mut neighbors: Vec<(u32, f32)> = self
+            .counts
+            .get(&item_id)
+            .into_iter()
+            .flatten()
+            .map(|(other, count)| (*other, count.at(self.now, &self.config) as f32))
+            .collect();
+        neighbors.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        neighbors
+    }
+
+    /// Forgets the co-occurrences whose decayed count fell below `min_count`, so
+    /// the memory stays bounded on a long stream.
+    ///
+    /// ## Returns:
+    /// * The number of pairs of items removed.
+    pub fn prune(&mut self, min_count: f64) -> usize {
+        let (now, config) = (self.now, &self.config);
+        let mut removed = 0;
+        self.counts.retain(|_, counts| {
+            let before = counts.len();
+            counts.retain(|_, count| count.at(now, config) >= min_count);
+            removed += before - counts.len();
+            !counts.is_empty()
+        });
+        removed
+    }
+
+    /// The number of pairs of items with a count.
+    pub fn num_pairs(&self) -> usize {
+        self.counts.values().map(HashMap::len).sum()
+    }
+}
+
+impl Recommender for CoOccurrence {
+    /// Replays the interactions of the dataset in timestamp order, those without
+    /// timestamp first.
+    fn fit(&mut self, dataset: &Dataset) -> Result<()> {
+        self.config.validate()?;
+        *self = CoOccurrence::new(self.config.clone());
+        let mut ratings: Vec<_> = dataset.ratings.iter().collect();
+        ratings.sort_by_key(|r| r.timestamp.unwrap_or(0));
+        ratings
+            .iter()
+            .for_each(|r| self.update(r.user_id, r.item_id, r.timestamp.unwrap_or(0)));
+        Ok(())
+    }
+
+    fn recommend(&self, user_id: u32, num_items: usize) -> Vec<Recommendation> {
+        self.recommend_iter(user_id).take(num_items).collect()
+    }
+
+    /// Scores the items by their co-occurrences with the items of the current
+    /// session of the user.
+    fn recommend_iter(&self, user_id: u32) -> RankedItems {
+        let session = match self.sessions.get(&user_id) {
+            Some(session) => session,
+            None => return RankedItems::default(),
+        };
+        let mut scores: HashMap<u32, f32> = HashMap::new();
+        session
+            .items
+            .iter()
+            .filter_map(|item_id| self.counts.get(item_id))
+            .flatten()
+            .for_each(|(other, count)| {
+                *scores.entry(*other).or_default() +=
+                    count.at(self.now, &self.config) as f32
+            });
+        RankedItems::new(scores, self.seen.get(&user_id))
+    }
+}
+
+impl PersistedModel for CoOccurrence {
+    const MODEL_TYPE: &'static str = "cooccurrence";
+
+    fn hyperparameters(&self) -> BTreeMap<String, Value> {
+        fields(&self.config)
+    }
+}
+
+impl MemoryFootprint for DecayedCount {}
+
+impl MemoryFootprint for Session {
+    fn heap_size(&self) -> usize {
+        self.items.heap_size()
+    }
+}
+
+impl MemoryFootprint for CoOccurrence {
+    fn heap_size(&self) -> usize {
+        self.counts.heap_size() + self.sessions.heap_size() + self.seen.heap_size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dataset::Rating;
+
+    #[test]
+    fn test_sessions_and_window() {
+        let config = CoOccurrenceConfig::default()
+            .set_session_gap(60)
+            .set_window(2);
+        let mut model = CoOccurrence::new(config);
+        model.update(1, 10, 0);
+        model.update(1, 11, 10);
+        model.update(1, 12, 20);
+        // Out of the window of item 10.
+        model.update(1, 13, 30);
+        // A new session.
+        model.update(1, 14, 200);
+        assert_eq!(model.count(10, 11), 1.0);
+        assert_eq!(model.count(11, 10), 1.0);
+        assert_eq!(model.count(10, 13), 0.0);
+        assert_eq!(model.count(12, 13), 1.0);
+        assert_eq!(model.count(13, 14), 0.0);
+        assert_eq!(model.num_pairs(), 2 * 5);
+        assert_eq!(model.neighbors(12), [(10, 1.0), (11, 1.0), (13, 1.0)]);
+    }
+
+    #[test]
+    fn test_decay() {
+        let mut model =
+            CoOccurrence::new(CoOccurrenceConfig::default().set_half_life(10.0));
+        model.update(1, 10, 0);
+        model.update(1, 11, 0);
+        model.update(2, 10, 10);
+        model.update(2, 11, 10);
+        assert!((model.count(10, 11) - 1.5).abs() < 1e-6);
+        // An event arriving late counts as if it had been decayed.
+        model.update(3, 10, 0);
+        model.update(3, 11, 0);
+        assert!((model.count(10, 11) - 2.0).abs() < 1e-6);
+        model.update(4, 12, 30);
+        assert!((model.count(10, 11) - 0.5).abs() < 1e-6);
+        assert_eq!(model.prune(1.0), 2);
+        assert_eq!(model.num_pairs(), 0);
+    }
+
+    #[test]
+    fn test_fit_replays_in_order() {
+        let dataset = Dataset::new(vec![
+            Rating::new(1, 12, 1.0).timestamp(20),
+            Rating::new(1, 10, 1.0).timestamp(0),
+            Rating::new(1, 11, 1.0).timestamp(10),
+            Rating::new(2, 10, 1.0).timestamp(30),
+        ]);
+        let config = CoOccurrenceConfig::default().set_window(1);
+        let mut model = CoOccurrence::new(config);
+        model.fit(&dataset).unwrap();
+        assert_eq!(model.count(10, 11), 1.0);
+        assert_eq!(model.count(10, 12), 0.0);
+        assert_eq!(model.now(), 30);
+        assert_eq!(model.recommend(2, 5)[0].item_id, 11);
+        assert!(model.recommend(99, 5).is_empty());
+        let mut model = CoOccurrence::new(CoOccurrenceConfig::default().set_window(0));
+        assert!(model.fit(&dataset).is_err());
+    }
+}
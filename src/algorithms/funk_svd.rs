@@ -0,0 +1,283 @@
+//! FunkSVD, the biased matrix factorization trained with stochastic gradient descent
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::algorithms::config::{ensure, AlgorithmConfig};
+use crate::algorithms::history::{EarlyStopping, TrainingHistory};
+use crate::algorithms::mf::{MFConfig, MatrixFactorization};
+use crate::algorithms::regularization::Regularization;
+use crate::dataset::Dataset;
+use crate::envelope::{fields, PersistedModel};
+use crate::errors::Result;
+use crate::memory::MemoryFootprint;
+use crate::recommender::{RankedItems, Recommendation, Recommender};
+
+/// # FunkSVD configuration
+/// Hyperparameters of [`FunkSVD`], with the defaults of the Netflix prize
+/// implementations.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::algorithms::config::AlgorithmConfig;
+/// use rec_rsys::algorithms::funk_svd::FunkSVDConfig;
+/// let config = FunkSVDConfig::from_toml(
+///     "num_factors = 8\nregularization = 0.05\n[early_stopping]\npatience = 2",
+/// )
+/// .unwrap();
+/// assert_eq!(config.early_stopping.unwrap().patience, 2);
+/// assert!(config.set_learning_rate(0.0).validate().is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FunkSVDConfig {
+    /// Number of latent dimensions.
+    pub num_factors: usize,
+    /// Number of passes over the ratings.
+    pub num_epochs: usize,
+    pub learning_rate: f32,
+    /// L2 penalty on the factors and the biases.
+    pub regularization: f32,
+    /// Standard deviation of the normal distribution the factors start from.
+    pub init_std: f32,
+    /// Seed of the initialization and of the order of the ratings.
+    pub seed: u64,
+    /// Stops the training once the RMSE on the validation ratings stops improving.
+    pub early_stopping: Option<EarlyStopping>,
+}
+
+impl Default for FunkSVDConfig {
+    fn default() -> Self {
+        FunkSVDConfig {
+            num_factors: 100,
+            num_epochs: 20,
+            learning_rate: 0.005,
+            regularization: 0.02,
+            init_std: 0.1,
+            seed: 42,
+            early_stopping: None,
+        }
+    }
+}
+
+impl FunkSVDConfig {
+    pub fn set_num_factors(mut self, num_factors: usize) -> Self {
+        self.num_factors = num_factors;
+        self
+    }
+    pub fn set_num_epochs(mut self, num_epochs: usize) -> Self {
+        self.num_epochs = num_epochs;
+        self
+    }
+    pub fn set_learning_rate(mut self, learning_rate: f32) -> Self {
+        self.learning_rate = learning_rate;
+        self
+    }
+    pub fn set_regularization(mut self, regularization: f32) -> Self {
+        self.regularization = regularization;
+        self
+    }
+    pub fn set_init_std(mut self, init_std: f32) -> Self {
+        self.init_std = init_std;
+        self
+    }
+    pub fn set_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+    pub fn set_early_stopping(mut self, early_stopping: EarlyStopping) -> Self {
+        self.early_stopping = Some(early_stopping);
+        self
+    }
+}
+
+impl AlgorithmConfig for FunkSVDConfig {
+    fn validate(&self) -> Result<()> {
+        ensure(
+            self.regularization >= 0.0 && self.regularization.is_finite(),
+            "regularization",
+            "a non-negative number",
+        )?;
+        MFConfig::from(self.clone()).validate()
+    }
+}
+
+impl From<FunkSVDConfig> for MFConfig {
+    fn from(config: FunkSVDConfig) -> Self {
+        let mf = MFConfig::default()
+            .set_num_factors(config.num_factors)
+            .set_num_epochs(config.num_epochs)
+            .set_learning_rate(config.learning_rate)
+            .set_regularization(Regularization::L2 {
+                lambda: config.regularization,
+            })
+            .set_init_std(config.init_std)
+            .set_seed(config.seed);
+        match config.early_stopping {
+            Some(early_stopping) => mf.set_early_stopping(early_stopping),
+            None => mf,
+        }
+    }
+}
+
+/// # FunkSVD
+/// Predicts a rating as the global mean plus a user bias, an item bias and the dot
+/// product of their latent factors, all learned by stochastic gradient descent on
+/// the squared error. It is the plain form of [`MatrixFactorization`], which trains
+/// it, without dropout nor differential privacy.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::algorithms::funk_svd::{FunkSVD, FunkSVDConfig};
+/// use rec_rsys::algorithms::history::EarlyStopping;
+/// use rec_rsys::dataset::{Dataset, Rating};
+/// use rec_rsys::recommender::Recommender;
+/// let dataset = Dataset::new(vec![
+///     Rating::new(1, 10, 5.0), Rating::new(1, 11, 1.0),
+///     Rating::new(2, 10, 4.0), Rating::new(2, 11, 2.0), Rating::new(2, 12, 5.0),
+/// ]);
+/// let config = FunkSVDConfig::default()
+///     .set_num_factors(4)
+///     .set_num_epochs(200)
+///     .set_learning_rate(0.02)
+///     .set_early_stopping(EarlyStopping::default().set_patience(5));
+/// let mut model = FunkSVD::new(config);
+/// model.fit_with_validation(&dataset, Some(&dataset)).unwrap();
+/// assert!(model.predict(1, 10).unwrap() > model.predict(1, 11).unwrap());
+/// assert_eq!(model.recommend(1, 1)[0].item_id, 12);
+/// ```
+#[doc = include_str!("../../docs/algorithms/funk_svd.md")]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FunkSVD {
+    config: FunkSVDConfig,
+    model: MatrixFactorization,
+}
+
+impl FunkSVD {
+    pub fn new(config: FunkSVDConfig) -> Self {
+        let model = MatrixFactorization::new(config.clone().into());
+        FunkSVD { config, model }
+    }
+
+    pub fn config(&self) -> &FunkSVDConfig {
+        &self.config
+    }
+
+    /// The trained factorization, with its biases and factors.
+    pub fn model(&self) -> &MatrixFactorization {
+        &self.model
+    }
+
+    /// The losses of the last training.
+    pub fn history(&self) -> &TrainingHistory {
+        self.model.history()
+    }
+
+    /// Trains like [`Recommender::fit`], stopping early on the RMSE of the
+    /// validation ratings when [`FunkSVDConfig::early_stopping`] is set.
+    pub fn fit_with_validation(
+        &mut self,
+        dataset: &Dataset,
+        validation: Option<&Dataset>,
+    ) -> Result<()> {
+        self.config.validate()?;
+        self.model = MatrixFactorization::new(self.config.clone().into());
+        self.model.fit_with_validation(dataset, validation)
+    }
+}
+
+impl Recommender for FunkSVD {
+    fn fit(&mut self, dataset: &Dataset) -> Result<()> {
+        self.fit_with_validation(dataset, None)
+    }
+
+    fn recommend(&self, user_id: u32, num_items: usize) -> Vec<Recommendation> {
+        self.model.recommend(user_id, num_items)
+    }
+
+    fn recommend_iter(&self, user_id: u32) -> RankedItems {
+        self.model.recommend_iter(user_id)
+    }
+
+    fn predict(&self, user_id: u32, item_id: u32) -> Option<f32> {
+        self.model.predict(user_id, item_id)
+    }
+}
+
+impl PersistedModel for FunkSVD {
+    const MODEL_TYPE: &'static str = "funk_svd";
+
+    fn hyperparameters(&self) -> BTreeMap<String, Value> {
+        fields(&self.config)
+    }
+}
+
+impl MemoryFootprint for FunkSVD {
+    fn heap_size(&self) -> usize {
+        self.model.heap_size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dataset::Rating;
+
+    fn dataset() -> Dataset {
+        Dataset::new(
+            (1..=8)
+                .flat_map(|user| {
+                    (10..18).map(move |item| {
+                        let rating = 1.0 + ((user * item) % 5) as f32;
+                        Rating::new(user, item, rating)
+                    })
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_fit_trains_a_biased_factorization() {
+        let config = FunkSVDConfig::default()
+            .set_num_factors(4)
+            .set_num_epochs(5)
+            .set_regularization(0.1);
+        let mut model = FunkSVD::new(config);
+        model.fit(&dataset()).unwrap();
+        assert_eq!(model.history().len(), 5);
+        assert!((model.model().global_mean() - 3.0).abs() < 0.5);
+        assert_eq!(model.model().user_biases().len(), 8);
+        assert_eq!(
+            model.model().config().regularization,
+            Regularization::L2 { lambda: 0.1 }
+        );
+        assert!(model.predict(1, 99).is_none());
+    }
+
+    #[test]
+    fn test_early_stopping_on_validation() {
+        let (train, validation) = dataset().split_random(0.25, 3);
+        let config = FunkSVDConfig::default()
+            .set_num_factors(8)
+            .set_num_epochs(500)
+            .set_learning_rate(0.05)
+            .set_regularization(0.0)
+            .set_early_stopping(EarlyStopping::default().set_min_delta(1e-3));
+        let mut model = FunkSVD::new(config);
+        model
+            .fit_with_validation(&train, Some(&validation))
+            .unwrap();
+        assert!(model.history().len() < 500);
+    }
+
+    #[test]
+    fn test_rejects_invalid_config() {
+        let mut model = FunkSVD::new(FunkSVDConfig::default().set_regularization(-1.0));
+        assert!(model.fit(&dataset()).is_err());
+        let stopping = EarlyStopping::default().set_patience(0);
+        let mut model =
+            FunkSVD::new(FunkSVDConfig::default().set_early_stopping(stopping));
+        assert!(model.fit(&dataset()).is_err());
+    }
+}
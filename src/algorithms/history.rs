@@ -0,0 +1,252 @@
+//! # Training history
+//! Losses recorded by the iterative trainers after every epoch.
+use serde::{Deserialize, Serialize};
+
+use crate::algorithms::config::ensure;
+use crate::errors::{Error, Result};
+
+/// Losses of one epoch.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct EpochLoss {
+    /// 0-based index of the epoch.
+    pub epoch: usize,
+    /// Loss minimized on the training data, penalty included.
+    pub train_loss: f64,
+    /// Loss on the validation data, when some was given.
+    pub validation_loss: Option<f64>,
+}
+
+/// # Training history
+/// The per-epoch losses of a training, retrievable from the model after `fit`.
+/// Recording a loss that is not finite, or that grew more than `max_growth` times
+/// above the first one, fails so the training stops instead of wasting the
+/// remaining epochs on a diverged model.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::algorithms::history::TrainingHistory;
+/// let mut history = TrainingHistory::new(100.0);
+/// history.record(2.0, Some(2.5)).unwrap();
+/// history.record(1.0, Some(2.1)).unwrap();
+/// assert_eq!(history.best_epoch(), Some(1));
+/// assert!(history.record(f64::NAN, None).is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrainingHistory {
+    epochs: Vec<EpochLoss>,
+    max_growth: f64,
+}
+
+impl Default for TrainingHistory {
+    fn default() -> Self {
+        TrainingHistory::new(f64::INFINITY)
+    }
+}
+
+impl TrainingHistory {
+    /// ## Parameters:
+    /// * `max_growth`: How many times the loss can exceed the loss of the first
+    ///   epoch before the training is considered diverged.
+    pub fn new(max_growth: f64) -> Self {
+        TrainingHistory {
+            epochs: Vec::new(),
+            max_growth,
+        }
+    }
+
+    /// Records the losses of the next epoch.
+    ///
+    /// ## Returns:
+    /// * [`Error::TrainingDiverged`] when one of the losses is not finite or exploded.
+    pub fn record(
+        &mut self,
+        train_loss: f64,
+        validation_loss: Option<f64>,
+    ) -> Result<()> {
+        let epoch = self.epochs.len();
+        self.epochs.push(EpochLoss {
+            epoch,
+            train_loss,
+            validation_loss,
+        });
+        let first = self.epochs[0];
+        let diverged = |loss: f64, initial: Option<f64>| {
+            !loss.is_finite() || initial.is_some_and(|i| loss > i.abs() * self.max_growth)
+        };
+        if diverged(train_loss, Some(first.train_loss)) {
+            return Err(Error::TrainingDiverged {
+                epoch,
+                loss: train_loss,
+            });
+        }
+        match validation_loss {
+            Some(loss) if diverged(loss, first.validation_loss) => {
+                Err(Error::TrainingDiverged { epoch, loss })
+            },
+            _ => Ok(()),
+        }
+    }
+
+    pub fn epochs(&self) -> &[EpochLoss] {
+        &self.epochs
+    }
+
+    pub fn len(&self) -> usize {
+        self.epochs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.epochs.is_empty()
+    }
+
+    pub fn last(&self) -> Option<&EpochLoss> {
+        self.epochs.last()
+    }
+
+    pub fn train_losses(&self) -> Vec<f64> {
+        self.epochs.iter().map(|e| e.train_loss).collect()
+    }
+
+    /// The validation losses, empty when the training had no validation data.
+    pub fn validation_losses(&self) -> Vec<f64> {
+        self.epochs
+            .iter()
+            .filter_map(|e| e.validation_loss)
+            .collect()
+    }
+
+    /// The epoch with the lowest validation loss, or training loss without
+    /// validation data.
+    pub fn best_epoch(&self) -> Option<usize> {
+        self.epochs
+            .iter()
+            .min_by(|a, b| {
+                let loss = |e: &EpochLoss| e.validation_loss.unwrap_or(e.train_loss);
+                loss(a).total_cmp(&loss(b))
+            })
+            .map(|e| e.epoch)
+    }
+}
+
+/// # Early stopping
+/// Stops a training once its monitored loss, usually the error on validation
+/// data, has not improved for `patience` epochs.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::algorithms::history::EarlyStopping;
+/// let stopping = EarlyStopping::default().set_patience(2).set_min_delta(0.01);
+/// let losses = [1.0, 0.8, 0.795, 0.81];
+/// assert_eq!(stopping.best_epoch(&losses), Some(1));
+/// assert!(stopping.should_stop(&losses));
+/// assert!(!stopping.should_stop(&losses[..3]));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EarlyStopping {
+    /// Number of epochs without improvement before stopping.
+    pub patience: usize,
+    /// How much lower than the best one a loss must be to count as an improvement.
+    pub min_delta: f64,
+    /// Whether the model is restored to its best epoch once stopped.
+    pub restore_best: bool,
+}
+
+impl Default for EarlyStopping {
+    fn default() -> Self {
+        EarlyStopping {
+            patience: 3,
+            min_delta: 0.0,
+            restore_best: true,
+        }
+    }
+}
+
+impl EarlyStopping {
+    pub fn set_patience(mut self, patience: usize) -> Self {
+        self.patience = patience;
+        self
+    }
+    pub fn set_min_delta(mut self, min_delta: f64) -> Self {
+        self.min_delta = min_delta;
+        self
+    }
+    pub fn set_restore_best(mut self, restore_best: bool) -> Self {
+        self.restore_best = restore_best;
+        self
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        ensure(
+            self.patience > 0,
+            "early_stopping.patience",
+            "greater than 0",
+        )?;
+        ensure(
+            self.min_delta >= 0.0 && self.min_delta.is_finite(),
+            "early_stopping.min_delta",
+            "a non-negative number",
+        )
+    }
+
+    /// The last epoch whose loss improved on all the previous ones by at least
+    /// `min_delta`, the first epoch always being an improvement.
+    pub fn best_epoch(&self, losses: &[f64]) -> Option<usize> {
+        let mut best: Option<(usize, f64)> = None;
+        for (epoch, loss) in losses.iter().enumerate() {
+            match best {
+                Some((_, lowest))
+                    if loss.is_nan() || *loss >= lowest - self.min_delta => {},
+                _ => best = Some((epoch, *loss)),
+            }
+        }
+        best.map(|(epoch, _)| epoch)
+    }
+
+    /// Whether the last `patience` losses did not improve on the best one.
+    pub fn should_stop(&self, losses: &[f64]) -> bool {
+        self.best_epoch(losses)
+            .is_some_and(|best| losses.len() - 1 - best >= self.patience)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exploding_loss() {
+        let mut history = TrainingHistory::new(10.0);
+        history.record(1.0, None).unwrap();
+        history.record(9.0, None).unwrap();
+        match history.record(11.0, None) {
+            Err(Error::TrainingDiverged { epoch, loss }) => {
+                assert_eq!((epoch, loss), (2, 11.0))
+            },
+            other => panic!("expected a divergence, got {:?}", other),
+        }
+        assert_eq!(history.len(), 3);
+    }
+
+    #[test]
+    fn test_exploding_validation_loss() {
+        let mut history = TrainingHistory::new(2.0);
+        history.record(1.0, Some(1.0)).unwrap();
+        assert!(history.record(0.5, Some(3.0)).is_err());
+        assert_eq!(history.validation_losses(), vec![1.0, 3.0]);
+        assert_eq!(history.best_epoch(), Some(0));
+    }
+
+    #[test]
+    fn test_early_stopping() {
+        let stopping = EarlyStopping::default().set_patience(2);
+        assert!(!stopping.should_stop(&[]));
+        assert!(!stopping.should_stop(&[3.0, 2.0, 1.0]));
+        assert!(!stopping.should_stop(&[3.0, 2.0, 2.5]));
+        assert!(stopping.should_stop(&[3.0, 2.0, 2.5, 2.0]));
+        // A loss that is not a number never improves.
+        assert_eq!(stopping.best_epoch(&[1.0, f64::NAN]), Some(0));
+        assert!(stopping.set_patience(0).validate().is_err());
+        assert!(stopping.set_min_delta(-1.0).validate().is_err());
+    }
+}
@@ -0,0 +1,482 @@
+//! Item based neighborhood recommender
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::algorithms::baseline::BaselineOnly;
+use crate::algorithms::config::AlgorithmConfig;
+use crate::algorithms::knn::{knn_scores, to_similarity, KNNConfig};
+use crate::dataset::{Dataset, RatingScale};
+use crate::envelope::{fields, PersistedModel};
+use crate::errors::Result;
+use crate::memory::MemoryFootprint;
+use crate::models::Item;
+use crate::pairwise::{
+    co_ratings, damp, shrink, weighted_rating, PopularityNormalization,
+};
+use crate::recommender::{RankedItems, Recommendation, Recommender};
+use crate::sets::IdSet;
+
+/// # Item KNN
+/// Represents every item by the vector of ratings it received, finds its nearest
+/// items with [`KNN`](crate::algorithms::knn::KNN) and recommends to a user the items that are the most similar
+/// to the ones they already rated. The rating of an item is predicted from the
+/// ratings the user gave to its neighbors, see [`weighted_rating`].
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::algorithms::item_knn::ItemKNN;
+/// use rec_rsys::algorithms::knn::KNNConfig;
+/// use rec_rsys::dataset::{Dataset, Rating};
+/// use rec_rsys::recommender::Recommender;
+/// let dataset = Dataset::new(vec![
+///     Rating::new(1, 10, 5.0), Rating::new(1, 11, 5.0),
+///     Rating::new(2, 10, 4.0), Rating::new(2, 11, 4.0), Rating::new(2, 12, 1.0),
+///     Rating::new(3, 10, 5.0),
+/// ]);
+/// let mut model = ItemKNN::new(KNNConfig::default().set_num_neighbors(2));
+/// model.fit(&dataset).unwrap();
+/// assert_eq!(model.recommend(3, 1)[0].item_id, 11);
+/// // User 3 only rated item 10, a neighbor of 11, with 5.
+/// assert!((model.predict(3, 11).unwrap() - 5.0).abs() < 1e-6);
+/// assert_eq!(model.predict_from_history(&[(10, 2.0)], 11), Some(2.0));
+/// ```
+#[doc = include_str!("../../docs/algorithms/item_knn.md")]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ItemKNN {
+    config: KNNConfig,
+    neighbors: HashMap<u32, Vec<(u32, f32)>>,
+    /// The number of users who rated an item and each of its neighbors, in the
+    /// order of the neighbors.
+    #[serde(default)]
+    overlaps: HashMap<u32, Vec<usize>>,
+    seen: HashMap<u32, IdSet>,
+    #[serde(default)]
+    ratings: HashMap<u32, Vec<(u32, f32)>>,
+    /// The baseline the similarities were computed on, predictions are made on
+    /// its residuals.
+    #[serde(default)]
+    baseline: Option<BaselineOnly>,
+    /// The scale of the training ratings, the predictions are clipped to it.
+    #[serde(default)]
+    scale: Option<RatingScale>,
+}
+
+impl ItemKNN {
+    pub fn new(config: KNNConfig) -> Self {
+        ItemKNN {
+            config,
+            ..ItemKNN::default()
+        }
+    }
+
+    /// The most similar items of an item, with their similarity.
+    pub fn neighbors(&self, item_id: u32) -> &[(u32, f32)] {
+        self.neighbors.get(&item_id).map_or(&[], |n| n.as_slice())
+    }
+
+    /// The most similar items of an item, with their similarity and the number of
+    /// users who rated both items, the support of the similarity.
+    pub fn neighbors_with_overlap(&self, item_id: u32) -> Vec<(u32, f32, usize)> {
+        let overlaps = self
+            .overlaps
+            .get(&item_id)
+            .map_or(&[][..], |o| o.as_slice());
+        self.neighbors(item_id)
+            .iter()
+            .zip(overlaps)
+            .map(|((neighbor, similarity), overlap)| (*neighbor, *similarity, *overlap))
+            .collect()
+    }
+
+    /// # Predict from history
+    /// Predicts the rating of an item from a history of ratings instead of the
+    /// training ratings of a user, e.g. for a visitor who rated a few items since.
+    ///
+    /// ## Parameters:
+    /// * `history`: The items rated, with their rating.
+    /// * `item_id`: The item whose rating is predicted.
+    ///
+    /// ## Returns:
+    /// * The similarity weighted average of the ratings of the neighbors of the
+    ///   item in the history, `None` when the history has none of them.
+    pub fn predict_from_history(
+        &self,
+        history: &[(u32, f32)],
+        item_id: u32,
+    ) -> Option<f32> {
+        self.predict_for(None, history, item_id)
+    }
+
+    /// The prediction for a user, known or not. With a baseline the neighbors vote
+    /// for the residuals of their ratings, added back to the estimate of the item.
+    fn predict_for(
+        &self,
+        user_id: Option<u32>,
+        history: &[(u32, f32)],
+        item_id: u32,
+    ) -> Option<f32> {
+        let estimate = |item_id: u32| match &self.baseline {
+            Some(baseline) => {
+                let user_bias =
+                    user_id.map_or(0.0, |user_id| baseline.user_bias(user_id));
+                baseline.global_mean() + user_bias + baseline.item_bias(item_id)
+            },
+            None => 0.0,
+        };
+        let residuals: Vec<(u32, f32)> = history
+            .iter()
+            .map(|(item_id, rating)| (*item_id, rating - estimate(*item_id)))
+            .collect();
+        let prediction =
+            estimate(item_id) + weighted_rating(self.neighbors(item_id), &residuals)?;
+        Some(
+            self.scale
+                .map_or(prediction, |scale| scale.clip(prediction)),
+        )
+    }
+
+    /// The neighbors of every item, e.g. to store them for serving.
+    pub fn neighbor_lists(&self) -> impl Iterator<Item = (u32, &[(u32, f32)])> + '_ {
+        self.neighbors.iter().map(|(id, n)| (*id, n.as_slice()))
+    }
+}
+
+impl Recommender for ItemKNN {
+    fn fit(&mut self, dataset: &Dataset) -> Result<()> {
+        self.config.validate()?;
+        let normalization = self.config.normalization;
+        let ratings = dataset.item_vectors();
+        let rows: HashMap<u32, usize> = ratings
+            .iter()
+            .enumerate()
+            .map(|(row, item)| (item.id, row))
+            .collect();
+        // The item vectors keep the same rows, only the compared values change.
+        self.baseline = match &self.config.baseline {
+            Some(config) => {
+                let mut baseline = BaselineOnly::new(config.clone());
+                baseline.fit(dataset)?;
+                Some(baseline)
+            },
+            None => None,
+        };
+        let residuals = self
+            .baseline
+            .as_ref()
+            .map(|baseline| baseline.residuals(dataset).item_vectors());
+        let weights = normalization.weights(&ratings);
+        let items = normalization.center(residuals.as_deref().unwrap_or(&ratings));
+        // Damping and shrinkage reorder the neighbors and the overlap filter drops
+        // some regardless of their similarity, so with them all the neighbors are
+        // scored before keeping the best ones.
+        let reordered = matches!(normalization, PopularityNormalization::Damping { .. })
+            || self.config.shrinkage > 0.0
+            || self.config.min_overlap > 0;
+        let config = KNNConfig {
+            num_neighbors: self.config.num_neighbors.filter(|_| !reordered),
+            ..self.config.clone()
+        };
+        let lists = items
+            .iter()
+            .enumerate()
+            .map(|(row, item)| {
+                let pool: Vec<&Item> = items.iter().filter(|i| i.id != item.id).collect();
+                // The overlaps are counted on the ratings, centering turns the ones
+                // equal to the mean of the item into 0.
+                let mut neighbors: Vec<(u32, f32, usize)> =
+                    knn_scores(&item.values, &pool, &config)?
+                        .into_iter()
+                        .map(|(id, value)| {
+                            let neighbor = rows[&id];
+                            let overlap = co_ratings(&ratings[row], &ratings[neighbor]);
+                            let similarity = to_similarity(config.algorithm, value);
+                            let similarity = damp(similarity, weights[neighbor]);
+                            (id, shrink(similarity, overlap, config.shrinkage), overlap)
+                        })
+                        .filter(|(_, similarity, overlap)| {
+                            similarity.is_finite()
+                                && similarity.abs() >= config.min_similarity
+                                && *overlap >= config.min_overlap
+                        })
+                        .collect();
+                neighbors.sort_by(|(_, a, _), (_, b, _)| b.total_cmp(a));
+                if let Some(num_neighbors) = self.config.num_neighbors {
+                    neighbors.truncate(num_neighbors);
+                }
+                Ok((item.id, neighbors))
+            })
+            .collect::<Result<Vec<(u32, Vec<(u32, f32, usize)>)>>>()?;
+        self.neighbors = lists
+            .iter()
+            .map(|(id, neighbors)| (*id, neighbors.iter().map(|n| (n.0, n.1)).collect()))
+            .collect();
+        self.overlaps = lists
+            .into_iter()
+            .map(|(id, neighbors)| (id, neighbors.into_iter().map(|n| n.2).collect()))
+            .collect();
+        self.seen = dataset.user_item_sets();
+        self.ratings = dataset.user_ratings();
+        self.scale = dataset.scale;
+        Ok(())
+    }
+
+    fn recommend(&self, user_id: u32, num_items: usize) -> Vec<Recommendation> {
+        self.recommend_iter(user_id).take(num_items).collect()
+    }
+
+    fn recommend_iter(&self, user_id: u32) -> RankedItems {
+        let seen = match self.seen.get(&user_id) {
+            Some(seen) => seen,
+            None => return RankedItems::default(),
+        };
+        let mut scores: HashMap<u32, f32> = HashMap::new();
+        seen.iter()
+            .flat_map(|item_id| self.neighbors(item_id))
+            .for_each(|(neighbor, similarity)| {
+                *scores.entry(*neighbor).or_default() += similarity
+            });
+        RankedItems::new(scores, Some(seen))
+    }
+
+    fn predict(&self, user_id: u32, item_id: u32) -> Option<f32> {
+        self.predict_for(Some(user_id), self.ratings.get(&user_id)?, item_id)
+    }
+}
+
+impl PersistedModel for ItemKNN {
+    const MODEL_TYPE: &'static str = "item_knn";
+
+    fn hyperparameters(&self) -> BTreeMap<String, Value> {
+        fields(&self.config)
+    }
+}
+
+impl MemoryFootprint for ItemKNN {
+    fn heap_size(&self) -> usize {
+        self.neighbors.heap_size()
+            + self.overlaps.heap_size()
+            + self.seen.heap_size()
+            + self.ratings.heap_size()
+            + self.baseline.heap_size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithms::baseline::BaselineConfig;
+    use crate::dataset::Rating;
+    use crate::similarity::{cosine_similarity, pearson_correlation, SimilarityAlgos};
+
+    fn dataset() -> Dataset {
+        Dataset::new(vec![
+            Rating::new(1, 10, 5.0),
+            Rating::new(1, 11, 5.0),
+            Rating::new(2, 10, 4.0),
+            Rating::new(2, 11, 4.0),
+            Rating::new(2, 12, 1.0),
+            Rating::new(3, 10, 5.0),
+            Rating::new(3, 13, 2.0),
+        ])
+    }
+
+    #[test]
+    fn test_neighbors() {
+        let mut model = ItemKNN::new(KNNConfig::default().set_num_neighbors(1));
+        model.fit(&dataset()).unwrap();
+        assert_eq!(model.neighbors(10)[0].0, 11);
+        assert!(model.neighbors(99).is_empty());
+        // Cut early or not, the kept neighbors are the best ones.
+        for config in [
+            KNNConfig::default(),
+            KNNConfig::default().set_shrinkage(1.0),
+            KNNConfig::default()
+                .set_normalization(PopularityNormalization::Damping { alpha: 1.0 }),
+        ] {
+            let mut all = ItemKNN::new(config.clone());
+            all.fit(&dataset()).unwrap();
+            let mut model = ItemKNN::new(config.set_num_neighbors(1));
+            model.fit(&dataset()).unwrap();
+            for item_id in [10, 11, 12, 13] {
+                assert_eq!(model.neighbors(item_id), &all.neighbors(item_id)[..1]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_baseline_residuals() {
+        let baseline = BaselineConfig::default();
+        let mut model = ItemKNN::new(KNNConfig::default().set_baseline(baseline.clone()));
+        model.fit(&dataset()).unwrap();
+        let mut estimates = BaselineOnly::new(baseline);
+        estimates.fit(&dataset()).unwrap();
+        let vectors = estimates.residuals(&dataset()).item_vectors();
+        let expected = cosine_similarity(&vectors[0].values, &vectors[1].values);
+        let (_, similarity, overlap) = model
+            .neighbors_with_overlap(10)
+            .into_iter()
+            .find(|(neighbor, _, _)| *neighbor == 11)
+            .unwrap();
+        assert!((similarity - expected).abs() < 1e-6);
+        assert_eq!(overlap, 2);
+        let invalid = BaselineConfig::default().set_num_epochs(0);
+        let mut model = ItemKNN::new(KNNConfig::default().set_baseline(invalid));
+        assert!(model.fit(&dataset()).is_err());
+    }
+
+    #[test]
+    fn test_pearson_baseline() {
+        // The Pearson correlation of the residuals, shrunk by the number of users
+        // who rated both items rather than by the length of the vectors.
+        let baseline = BaselineConfig::default();
+        let config = KNNConfig::default()
+            .set_algorithm(SimilarityAlgos::PearsonCorrelation)
+            .set_shrinkage(2.0)
+            .set_baseline(baseline.clone());
+        let mut model = ItemKNN::new(config);
+        model.fit(&dataset()).unwrap();
+        let mut estimates = BaselineOnly::new(baseline);
+        estimates.fit(&dataset()).unwrap();
+        let vectors = estimates.residuals(&dataset()).item_vectors();
+        let correlation = pearson_correlation(&vectors[0].values, &vectors[1].values);
+        let (_, similarity, overlap) = model
+            .neighbors_with_overlap(10)
+            .into_iter()
+            .find(|(neighbor, _, _)| *neighbor == 11)
+            .unwrap();
+        assert_eq!(overlap, 2);
+        assert!((similarity - shrink(correlation, 2, 2.0)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_popularity_normalization() {
+        // Item 10 is rated by every user, 11 and 12 by two users each, one of them
+        // in common.
+        let mut ratings: Vec<Rating> =
+            (1..=6).map(|user| Rating::new(user, 10, 5.0)).collect();
+        ratings.extend([
+            Rating::new(1, 11, 4.0),
+            Rating::new(2, 11, 2.0),
+            Rating::new(2, 12, 3.0),
+            Rating::new(3, 12, 5.0),
+        ]);
+        let dataset = Dataset::new(ratings);
+        let config = KNNConfig::default().set_num_neighbors(1);
+        let mut model = ItemKNN::new(config.clone());
+        model.fit(&dataset).unwrap();
+        assert_eq!(model.neighbors(11)[0].0, 10);
+        let damped =
+            config.set_normalization(PopularityNormalization::Damping { alpha: 1.0 });
+        let mut model = ItemKNN::new(damped);
+        model.fit(&dataset).unwrap();
+        assert_eq!(model.neighbors(11)[0].0, 12);
+        let mut model = ItemKNN::new(
+            KNNConfig::default().set_normalization(PopularityNormalization::CenterItems),
+        );
+        model.fit(&dataset).unwrap();
+        assert!(model.neighbors(11).iter().all(|(id, _)| *id != 10));
+        let invalid = KNNConfig::default()
+            .set_normalization(PopularityNormalization::Damping { alpha: f32::NAN });
+        assert!(ItemKNN::new(invalid).fit(&dataset).is_err());
+    }
+
+    #[test]
+    fn test_shrinkage() {
+        // Item 12 was only rated by the first user, whose rating dominates item 10,
+        // item 11 is a bit less similar to item 10 but rated by all its users.
+        let mut ratings: Vec<Rating> = [5.0, 1.0, 1.0, 1.0, 1.0]
+            .into_iter()
+            .zip([4.0, 3.0, 1.0, 2.0, 1.0])
+            .zip(1..)
+            .flat_map(|((first, second), user)| {
+                [Rating::new(user, 10, first), Rating::new(user, 11, second)]
+            })
+            .collect();
+        ratings.push(Rating::new(1, 12, 5.0));
+        let dataset = Dataset::new(ratings);
+        let config = KNNConfig::default().set_num_neighbors(1);
+        let mut model = ItemKNN::new(config.clone());
+        model.fit(&dataset).unwrap();
+        assert_eq!(model.neighbors_with_overlap(10)[0].0, 12);
+        assert_eq!(model.neighbors_with_overlap(10)[0].2, 1);
+
+        let mut model = ItemKNN::new(config.set_shrinkage(10.0));
+        model.fit(&dataset).unwrap();
+        let (neighbor, similarity, overlap) = model.neighbors_with_overlap(10)[0];
+        assert_eq!((neighbor, overlap), (11, 5));
+        assert!(similarity < 1.0);
+        assert!(model.neighbors_with_overlap(99).is_empty());
+
+        let filtered = KNNConfig::default()
+            .set_min_overlap(2)
+            .set_min_similarity(0.5);
+        let mut model = ItemKNN::new(filtered);
+        model.fit(&dataset).unwrap();
+        assert_eq!(model.neighbors(10).len(), 1);
+        assert!(model.neighbors(12).is_empty());
+        let invalid = KNNConfig::default().set_shrinkage(-1.0);
+        assert!(ItemKNN::new(invalid).fit(&dataset).is_err());
+    }
+
+    #[test]
+    fn test_predict() {
+        let mut model = ItemKNN::new(KNNConfig::default());
+        model.fit(&dataset()).unwrap();
+        // Item 12 was only rated by user 2, with items 10 and 11.
+        let (s10, s11) = (4.0 / 66_f32.sqrt(), 4.0 / 41_f32.sqrt());
+        let expected = (s10 * 4.0 + s11 * 2.0) / (s10 + s11);
+        let predicted = model
+            .predict_from_history(&[(10, 4.0), (11, 2.0), (13, 5.0)], 12)
+            .unwrap();
+        assert!((predicted - expected).abs() < 1e-5);
+        assert!((model.predict(1, 12).unwrap() - 5.0).abs() < 1e-5);
+        assert_eq!(model.predict(99, 12), None);
+        // Item 13 shares no user with item 12.
+        assert_eq!(model.predict_from_history(&[(13, 5.0)], 12), None);
+    }
+
+    #[test]
+    fn test_predict_with_baseline() {
+        let config = KNNConfig::default().set_baseline(BaselineConfig::default());
+        let mut model = ItemKNN::new(config);
+        model.fit(&dataset()).unwrap();
+        let mut baseline = BaselineOnly::new(BaselineConfig::default());
+        baseline.fit(&dataset()).unwrap();
+        // Item 10 is the only neighbor of item 11 with a positive similarity on the
+        // residuals, user 3 rated it with 5.
+        assert!(model.neighbors(11)[0].1 > 0.0 && model.neighbors(11)[1].1 <= 0.0);
+        let expected = baseline.estimate(3, 11) + (5.0 - baseline.estimate(3, 10));
+        assert!((model.predict(3, 11).unwrap() - expected).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_recommend_excludes_seen_items() {
+        let mut model = ItemKNN::new(KNNConfig::default());
+        model.fit(&dataset()).unwrap();
+        let recommended: Vec<u32> =
+            model.recommend(1, 10).iter().map(|r| r.item_id).collect();
+        assert!(!recommended.contains(&10) && !recommended.contains(&11));
+        assert!(model.recommend(42, 10).is_empty());
+    }
+
+    #[test]
+    fn test_recommend_with_euclidean() {
+        let mut model = ItemKNN::new(
+            KNNConfig::default()
+                .set_num_neighbors(2)
+                .set_algorithm(SimilarityAlgos::Euclidean),
+        );
+        model.fit(&dataset()).unwrap();
+        assert!(model.recommend(3, 2).iter().all(|r| r.score > 0.0));
+    }
+
+    #[test]
+    fn test_memory_footprint_grows_with_fit() {
+        let mut model = ItemKNN::new(KNNConfig::default());
+        let empty = model.memory_footprint();
+        model.fit(&dataset()).unwrap();
+        assert!(model.memory_footprint() > empty);
+    }
+}
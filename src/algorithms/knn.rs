@@ -1,13 +1,25 @@
 //! KNN
+use std::borrow::Borrow;
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "serde")]
+use crate::algorithms::baseline::BaselineConfig;
+use crate::algorithms::config::{ensure, AlgorithmConfig};
+use crate::errors::Result;
+use crate::memory::MemoryFootprint;
 use crate::models::Item;
+use crate::pairwise::PopularityNormalization;
+use crate::scratch::Scratch;
 use crate::similarity::{
     adjusted_cosine_similarity, cosine_similarity, euclidean_distance, msd_similarity,
-    pearson_baseline_similarity, pearson_correlation, spearman_correlation,
-    SimilarityAlgos,
+    pearson_correlation, spearman_correlation, SimilarityAlgos,
 };
-use crate::utils::{sort_and_trucate, sort_with_direction};
+use crate::utils::sort_with_direction;
+
+/// Number of items compared between two checks of the time budget.
+const BUDGET_CHECK_INTERVAL: usize = 64;
 
-type ParamDistanceFunction = dyn Fn(&[f32], &[f32]) -> f32;
+type ParamDistanceFunction = dyn Fn(&[f32], &[f32]) -> f32 + Sync;
 
 /// # KNN
 /// K-nearest neighbors (KNN) is a machine learning algorithm used for classification and regression. It predicts the class or value of a new data point based on the majority class or average value of its k nearest neighbors in the feature space.
@@ -36,6 +48,147 @@ pub struct KNN {
     neighbors_pool: Vec<Item>,
     algorithm: SimilarityAlgos,
     num_neighbors: usize,
+    time_budget: Option<Duration>,
+}
+
+/// Outcome of [`KNN::search`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Neighbors {
+    /// The best neighbors among the scanned items, best first.
+    pub items: Vec<Item>,
+    /// How many items of the pool were compared to the query.
+    pub scanned: usize,
+    /// Whether the whole pool was scanned before the time budget ran out.
+    pub complete: bool,
+}
+
+/// # KNN configuration
+/// Hyperparameters of [`KNN`].
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::algorithms::config::AlgorithmConfig;
+/// use rec_rsys::algorithms::knn::KNNConfig;
+/// use rec_rsys::similarity::SimilarityAlgos;
+/// let config = KNNConfig::default()
+///     .set_num_neighbors(5)
+///     .set_algorithm(SimilarityAlgos::Euclidean);
+/// assert!(config.validate().is_ok());
+/// # #[cfg(feature = "serde")]
+/// assert_eq!(KNNConfig::from_toml("num_neighbors = 5\nalgorithm = \"euclidean\"").unwrap(), config);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct KNNConfig {
+    /// Number of neighbors to return, the whole pool when `None`.
+    pub num_neighbors: Option<usize>,
+    /// Similarity used to compare the items.
+    pub algorithm: SimilarityAlgos,
+    /// Maximum time spent scanning the pool of a query, in microseconds.
+    pub time_budget_us: Option<u64>,
+    /// How the popularity of the items is removed by
+    /// [`ItemKNN`](crate::algorithms::item_knn::ItemKNN).
+    /// [`UserKNN`](crate::algorithms::user_knn::UserKNN) only accepts `None`.
+    pub normalization: PopularityNormalization,
+    /// Shrinks the similarities of `ItemKNN` and `UserKNN` towards 0 by
+    /// `overlap / (overlap + shrinkage)`, the overlap being the number of users
+    /// who rated both items, or of items rated by both users for
+    /// [`UserKNN`](crate::algorithms::user_knn::UserKNN). 0 keeps the similarities
+    /// as they are.
+    pub shrinkage: f32,
+    /// `ItemKNN` and `UserKNN` drop the neighbors with a smaller overlap.
+    pub min_overlap: usize,
+    /// `ItemKNN` and `UserKNN` drop the neighbors whose absolute similarity is
+    /// lower.
+    pub min_similarity: f32,
+    /// `ItemKNN` compares the residuals of the ratings from the estimates of a
+    /// [`BaselineOnly`](crate::algorithms::baseline::BaselineOnly) trained with
+    /// this config, instead of the raw ratings. `UserKNN` only accepts `None`.
+    #[cfg(feature = "serde")]
+    pub baseline: Option<BaselineConfig>,
+}
+
+impl Default for KNNConfig {
+    fn default() -> Self {
+        KNNConfig {
+            num_neighbors: None,
+            algorithm: SimilarityAlgos::Cosine,
+            time_budget_us: None,
+            normalization: PopularityNormalization::None,
+            shrinkage: 0.0,
+            min_overlap: 0,
+            min_similarity: 0.0,
+            #[cfg(feature = "serde")]
+            baseline: None,
+        }
+    }
+}
+
+impl KNNConfig {
+    pub fn set_num_neighbors(mut self, num_neighbors: usize) -> Self {
+        self.num_neighbors = Some(num_neighbors);
+        self
+    }
+    pub fn set_algorithm(mut self, algorithm: SimilarityAlgos) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+    pub fn set_time_budget_us(mut self, time_budget_us: u64) -> Self {
+        self.time_budget_us = Some(time_budget_us);
+        self
+    }
+    pub fn set_normalization(mut self, normalization: PopularityNormalization) -> Self {
+        self.normalization = normalization;
+        self
+    }
+    pub fn set_shrinkage(mut self, shrinkage: f32) -> Self {
+        self.shrinkage = shrinkage;
+        self
+    }
+    pub fn set_min_overlap(mut self, min_overlap: usize) -> Self {
+        self.min_overlap = min_overlap;
+        self
+    }
+    pub fn set_min_similarity(mut self, min_similarity: f32) -> Self {
+        self.min_similarity = min_similarity;
+        self
+    }
+    #[cfg(feature = "serde")]
+    pub fn set_baseline(mut self, baseline: BaselineConfig) -> Self {
+        self.baseline = Some(baseline);
+        self
+    }
+}
+
+impl AlgorithmConfig for KNNConfig {
+    fn validate(&self) -> Result<()> {
+        ensure(
+            self.num_neighbors != Some(0),
+            "num_neighbors",
+            "greater than 0",
+        )?;
+        ensure(
+            self.time_budget_us != Some(0),
+            "time_budget_us",
+            "greater than 0",
+        )?;
+        ensure(
+            self.shrinkage >= 0.0 && self.shrinkage.is_finite(),
+            "shrinkage",
+            "a non-negative number",
+        )?;
+        ensure(
+            self.min_similarity >= 0.0 && self.min_similarity.is_finite(),
+            "min_similarity",
+            "a non-negative number",
+        )?;
+        #[cfg(feature = "serde")]
+        if let Some(baseline) = &self.baseline {
+            baseline.validate()?;
+        }
+        self.normalization.validate()
+    }
 }
 
 impl KNN {
@@ -46,6 +199,7 @@ impl KNN {
             neighbors_pool,
             algorithm: SimilarityAlgos::Cosine,
             num_neighbors,
+            time_budget: None,
         }
     }
     pub fn set_algorithm(mut self, algorithm: SimilarityAlgos) -> Self {
@@ -56,20 +210,101 @@ impl KNN {
         self.num_neighbors = num_neighbors;
         self
     }
+    /// Stops scanning the pool once the budget is spent, see [`KNN::search`].
+    pub fn set_time_budget(mut self, time_budget: Duration) -> Self {
+        self.time_budget = Some(time_budget);
+        self
+    }
+    /// Creates a KNN with the hyperparameters of a validated config.
+    pub fn from_config(
+        query_item: Item,
+        neighbors_pool: Vec<Item>,
+        config: &KNNConfig,
+    ) -> Result<Self> {
+        config.validate()?;
+        let mut knn =
+            KNN::new(query_item, neighbors_pool).set_algorithm(config.algorithm);
+        if let Some(num_neighbors) = config.num_neighbors {
+            knn = knn.set_num_neighbors(num_neighbors);
+        }
+        if let Some(budget) = config.time_budget_us {
+            knn = knn.set_time_budget(Duration::from_micros(budget));
+        }
+        Ok(knn)
+    }
     /// Performs the KNN prediction based on the specified similarity algorithm.
     ///
     /// ## Returns:
     /// * A vector of items representing the predicted results.
     pub fn result(&self) -> Vec<Item> {
-        let (formula, reverse) = KNN::get_formula(&self.algorithm);
-        let mut best_matches: Vec<Item> = Vec::new();
-        self.neighbors_pool.iter().for_each(|item| {
-            let cloned_item = item.clone();
-            best_matches
-                .push(cloned_item.result(formula(&self.query_item.values, &item.values)))
-        });
+        self.search().items
+    }
 
-        sort_and_trucate(best_matches, reverse, self.num_neighbors)
+    /// Scans the pool for the nearest neighbors. With a time budget, the scan
+    /// stops once it is spent and the best neighbors found so far are returned,
+    /// so a query never blocks a request for longer than allowed on huge pools.
+    ///
+    /// ## Returns:
+    /// * The neighbors and whether the whole pool was scanned.
+    ///
+    /// ## Examples:
+    /// ```
+    /// use std::time::Duration;
+    /// use rec_rsys::{algorithms::knn::KNN, models::Item};
+    /// let pool = (0..1000).map(|id| Item::new(id, vec![id as f32, 1.0], None)).collect();
+    /// let knn = KNN::new(Item::new(0, vec![1.0, 1.0], None), pool)
+    ///     .set_num_neighbors(5)
+    ///     .set_time_budget(Duration::from_secs(10));
+    /// let neighbors = knn.search();
+    /// assert!(neighbors.complete);
+    /// assert_eq!(neighbors.items.len(), 5);
+    /// ```
+    pub fn search(&self) -> Neighbors {
+        self.search_with(&mut Scratch::new())
+    }
+
+    /// [`KNN::search`] keeping its temporary vectors in `scratch`, so the queries
+    /// sharing a scratch only allocate the returned neighbors.
+    pub fn search_with(&self, scratch: &mut Scratch) -> Neighbors {
+        let complete = self.scan(scratch);
+        Neighbors {
+            scanned: scratch.scores.len(),
+            items: scratch
+                .scores
+                .iter()
+                .take(self.num_neighbors)
+                .map(|(index, value)| self.neighbors_pool[*index].clone().result(*value))
+                .collect(),
+            complete,
+        }
+    }
+
+    /// The ids of the neighbors and their [`KNN::result`] values, best first,
+    /// without cloning the neighbors.
+    ///
+    /// ## Examples:
+    /// ```
+    /// use rec_rsys::{algorithms::knn::KNN, models::Item};
+    /// let pool = vec![Item::new(1, vec![0.0, 1.0], None), Item::new(2, vec![1.0, 0.1], None)];
+    /// let knn = KNN::new(Item::new(0, vec![1.0, 0.0], None), pool).set_num_neighbors(1);
+    /// assert_eq!(knn.scores()[0].0, 2);
+    /// ```
+    pub fn scores(&self) -> Vec<(u32, f32)> {
+        let mut scratch = Scratch::new();
+        self.scan(&mut scratch);
+        ids_and_values(&self.neighbors_pool, &scratch, self.num_neighbors)
+    }
+
+    /// Scores the pool into `scratch`, best first, and tells whether the whole
+    /// pool was scanned before the time budget ran out.
+    fn scan(&self, scratch: &mut Scratch) -> bool {
+        scan(
+            &self.query_item.values,
+            &self.neighbors_pool,
+            self.algorithm,
+            self.time_budget,
+            scratch,
+        )
     }
 
     /// Retrieves the distance formula and reverse flag for the specified similarity algorithm.
@@ -79,7 +314,7 @@ impl KNN {
     ///
     /// ## Returns:
     /// * A tuple containing the distance formula function and a flag indicating if the results should be reversed.
-    fn get_formula(
+    pub(crate) fn get_formula(
         algorithm: &SimilarityAlgos,
     ) -> (&'static ParamDistanceFunction, bool) {
         match algorithm {
@@ -92,3 +327,386 @@ impl KNN {
         }
     }
 }
+
+/// Scores the items of `pool` against `query` into `scratch`, sorted best first.
+fn scan<T: Borrow<Item>>(
+    query: &[f32],
+    pool: &[T],
+    algorithm: SimilarityAlgos,
+    time_budget: Option<Duration>,
+    scratch: &mut Scratch,
+) -> bool {
+    let (_, reverse) = KNN::get_formula(&algorithm);
+    let deadline = time_budget.map(|budget| Instant::now() + budget);
+    scratch.prepare(algorithm, query);
+    let mut complete = true;
+    for (index, item) in pool.iter().enumerate() {
+        // Reading the clock for every item would cost more than comparing it.
+        if index % BUDGET_CHECK_INTERVAL == 0
+            && deadline.is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            complete = false;
+            break;
+        }
+        let value = scratch.compare(algorithm, query, &item.borrow().values);
+        scratch.scores.push((index, value));
+    }
+    sort_with_direction(
+        &mut scratch.scores,
+        |(_, a), (_, b)| a.total_cmp(b),
+        reverse,
+    );
+    complete
+}
+
+fn ids_and_values<T: Borrow<Item>>(
+    pool: &[T],
+    scratch: &Scratch,
+    k: usize,
+) -> Vec<(u32, f32)> {
+    scratch
+        .scores
+        .iter()
+        .take(k)
+        .map(|(index, value)| (pool[*index].borrow().id, *value))
+        .collect()
+}
+
+/// The best `k` items of the pool, only cloning them.
+fn nearest_items(
+    query: &Item,
+    pool: &[Item],
+    algorithm: SimilarityAlgos,
+    k: usize,
+) -> Vec<Item> {
+    let mut scratch = Scratch::new();
+    scan(&query.values, pool, algorithm, None, &mut scratch);
+    scratch
+        .scores
+        .iter()
+        .take(k)
+        .map(|(index, value)| pool[*index].clone().result(*value))
+        .collect()
+}
+
+/// # KNN scores
+/// The neighbors of a query among borrowed items, as the ids and the
+/// [`KNN::result`] values of the neighbors: neither the query nor the pool is
+/// cloned, so the pool can be a filtered view of a larger collection.
+///
+/// ## Parameters:
+/// * `query`: The values to find neighbors for.
+/// * `pool`: The candidate neighbors, items or references to items.
+/// * `config`: The metric, the number of neighbors and the time budget.
+///
+/// ## Returns:
+/// * The ids and values of the neighbors, best first, or an error if the config is
+///   invalid.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::algorithms::knn::{knn_scores, KNNConfig};
+/// use rec_rsys::models::Item;
+/// let items = vec![Item::new(1, vec![0.0, 1.0], None), Item::new(2, vec![1.0, 0.1], None)];
+/// let pool: Vec<&Item> = items.iter().filter(|item| item.id != 3).collect();
+/// let scores = knn_scores(&[1.0, 0.0], &pool, &KNNConfig::default()).unwrap();
+/// assert_eq!(scores.iter().map(|(id, _)| *id).collect::<Vec<u32>>(), [2, 1]);
+/// ```
+pub fn knn_scores<T: Borrow<Item>>(
+    query: &[f32],
+    pool: &[T],
+    config: &KNNConfig,
+) -> Result<Vec<(u32, f32)>> {
+    config.validate()?;
+    let mut scratch = Scratch::new();
+    let time_budget = config.time_budget_us.map(Duration::from_micros);
+    scan(query, pool, config.algorithm, time_budget, &mut scratch);
+    let k = config.num_neighbors.unwrap_or(pool.len());
+    Ok(ids_and_values(pool, &scratch, k))
+}
+
+/// # Cosine KNN
+/// Finds the `k` items of the pool the most similar to the query with the cosine
+/// similarity, for one-shot queries that do not need a [`KNN`].
+///
+/// ## Parameters:
+/// * `query`: The item to find neighbors for.
+/// * `pool`: The candidate neighbors.
+/// * `k`: The number of neighbors.
+///
+/// ## Returns:
+/// * The neighbors, most similar first, with their similarity as result.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::algorithms::cosine_knn;
+/// use rec_rsys::models::Item;
+/// let pool = vec![Item::new(1, vec![0.0, 1.0], None), Item::new(2, vec![1.0, 0.1], None)];
+/// let neighbors = cosine_knn(&Item::new(0, vec![1.0, 0.0], None), &pool, 1);
+/// assert_eq!(neighbors[0].id, 2);
+/// ```
+pub fn cosine_knn(query: &Item, pool: &[Item], k: usize) -> Vec<Item> {
+    nearest_items(query, pool, SimilarityAlgos::Cosine, k)
+}
+
+/// # Euclidean KNN
+/// Finds the `k` items of the pool the closest to the query with the euclidean
+/// distance, for one-shot queries that do not need a [`KNN`].
+///
+/// ## Parameters:
+/// * `query`: The item to find neighbors for.
+/// * `pool`: The candidate neighbors.
+/// * `k`: The number of neighbors.
+///
+/// ## Returns:
+/// * The neighbors, closest first, with their distance as result.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::algorithms::euclidean_knn;
+/// use rec_rsys::models::Item;
+/// let pool = vec![Item::new(1, vec![5.0, 5.0], None), Item::new(2, vec![1.0, 1.0], None)];
+/// let neighbors = euclidean_knn(&Item::new(0, vec![0.0, 0.0], None), &pool, 2);
+/// assert_eq!(neighbors.iter().map(|n| n.id).collect::<Vec<u32>>(), vec![2, 1]);
+/// ```
+pub fn euclidean_knn(query: &Item, pool: &[Item], k: usize) -> Vec<Item> {
+    nearest_items(query, pool, SimilarityAlgos::Euclidean, k)
+}
+
+/// # Metric comparison
+/// The neighbors a query gets with every [`SimilarityAlgos`], side by side, to pick
+/// a metric for a new dataset. See [`compare_metrics`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetricComparison {
+    /// The top-k neighbors found with each metric, in [`SimilarityAlgos::ALL`] order.
+    pub neighbors: Vec<(SimilarityAlgos, Vec<Item>)>,
+    /// Spearman correlation between the rankings of the whole pool by each pair of
+    /// metrics, indexed like `neighbors`.
+    pub rank_correlations: Vec<Vec<f32>>,
+}
+
+impl MetricComparison {
+    /// The top-k neighbors found with `metric`.
+    pub fn neighbors(&self, metric: SimilarityAlgos) -> &[Item] {
+        self.neighbors
+            .iter()
+            .find(|(algorithm, _)| *algorithm == metric)
+            .map_or(&[], |(_, neighbors)| neighbors)
+    }
+
+    /// The rank correlation between two metrics, `1.0` when they order the pool
+    /// the same way and `-1.0` when they order it backwards.
+    pub fn rank_correlation(&self, a: SimilarityAlgos, b: SimilarityAlgos) -> f32 {
+        let index = |metric| SimilarityAlgos::ALL.iter().position(|m| *m == metric);
+        match (index(a), index(b)) {
+            (Some(a), Some(b)) => self.rank_correlations[a][b],
+            _ => f32::NAN,
+        }
+    }
+}
+
+/// # Compare metrics
+/// Runs the same query through every [`SimilarityAlgos`] and measures how much
+/// their rankings of the pool agree.
+///
+/// ## Parameters:
+/// * `query`: The item to find neighbors for.
+/// * `pool`: The candidate neighbors.
+/// * `k`: The number of neighbors kept for each metric.
+///
+/// ## Returns:
+/// * The top-k lists of each metric and the rank correlations between them.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::algorithms::knn::compare_metrics;
+/// use rec_rsys::models::Item;
+/// use rec_rsys::similarity::SimilarityAlgos;
+/// let pool = vec![
+///     Item::new(1, vec![1.0, 2.0, 3.0], None),
+///     Item::new(2, vec![3.0, 2.0, 1.0], None),
+///     Item::new(3, vec![10.0, 20.0, 30.0], None),
+/// ];
+/// let comparison = compare_metrics(&Item::new(0, vec![1.0, 2.0, 3.5], None), &pool, 2);
+/// assert_eq!(comparison.neighbors(SimilarityAlgos::Euclidean)[0].id, 1);
+/// assert_eq!(comparison.rank_correlation(SimilarityAlgos::Cosine, SimilarityAlgos::Cosine), 1.0);
+/// ```
+pub fn compare_metrics(query: &Item, pool: &[Item], k: usize) -> MetricComparison {
+    let mut scratch = Scratch::new();
+    let mut neighbors = Vec::with_capacity(SimilarityAlgos::ALL.len());
+    let mut positions: Vec<Vec<f32>> = Vec::with_capacity(SimilarityAlgos::ALL.len());
+    for algorithm in SimilarityAlgos::ALL {
+        scan(&query.values, pool, algorithm, None, &mut scratch);
+        let mut position = vec![0.0; pool.len()];
+        for (rank, (index, _)) in scratch.scores.iter().enumerate() {
+            position[*index] = rank as f32;
+        }
+        positions.push(position);
+        let top = scratch
+            .scores
+            .iter()
+            .take(k)
+            .map(|(index, value)| pool[*index].clone().result(*value))
+            .collect();
+        neighbors.push((algorithm, top));
+    }
+    let rank_correlations = positions
+        .iter()
+        .map(|a| {
+            positions
+                .iter()
+                .map(|b| match a.len() {
+                    0 | 1 => 1.0,
+                    _ => spearman_correlation(a, b),
+                })
+                .collect()
+        })
+        .collect();
+    MetricComparison {
+        neighbors,
+        rank_correlations,
+    }
+}
+
+/// Distances grow as items get further apart, turn them into a similarity.
+pub(crate) fn to_similarity(algorithm: SimilarityAlgos, value: f32) -> f32 {
+    match algorithm {
+        SimilarityAlgos::Euclidean => 1.0 / (1.0 + value),
+        _ => value,
+    }
+}
+
+impl MemoryFootprint for KNNConfig {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_knn_config_validate() {
+        assert!(KNNConfig::default().validate().is_ok());
+        assert!(KNNConfig::default()
+            .set_num_neighbors(0)
+            .validate()
+            .is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_knn_config_from_json() {
+        let config =
+            KNNConfig::from_json(r#"{"algorithm": "pearson_correlation"}"#).unwrap();
+        assert_eq!(config.algorithm, SimilarityAlgos::PearsonCorrelation);
+        assert_eq!(config.num_neighbors, None);
+        assert!(KNNConfig::from_json(r#"{"num_neighbors": 0}"#).is_err());
+    }
+
+    #[test]
+    fn test_from_config() {
+        let pool = vec![
+            Item::new(1, vec![1.0, 0.0], None),
+            Item::new(2, vec![0.0, 1.0], None),
+            Item::new(3, vec![1.0, 0.1], None),
+        ];
+        let config = KNNConfig::default().set_num_neighbors(1);
+        let result = KNN::from_config(Item::new(0, vec![1.0, 0.0], None), pool, &config)
+            .unwrap()
+            .result();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].id, 1);
+    }
+
+    #[test]
+    fn test_search_time_budget() {
+        let pool: Vec<Item> = (0..10_000)
+            .map(|id| Item::new(id, vec![id as f32, 1.0], None))
+            .collect();
+        let query = Item::new(0, vec![1.0, 1.0], None);
+        let exhausted = KNN::new(query.clone(), pool.clone())
+            .set_num_neighbors(3)
+            .set_time_budget(Duration::ZERO)
+            .search();
+        assert!(!exhausted.complete);
+        assert_eq!(exhausted.scanned, 0);
+        assert!(exhausted.items.is_empty());
+        let config = KNNConfig::default()
+            .set_num_neighbors(3)
+            .set_time_budget_us(60_000_000);
+        let full = KNN::from_config(query, pool, &config).unwrap().search();
+        assert!(full.complete);
+        assert_eq!(full.scanned, 10_000);
+        assert_eq!(full.items.len(), 3);
+        assert!(KNNConfig::default()
+            .set_time_budget_us(0)
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn test_one_shot_knn() {
+        let query = Item::new(0, vec![1.0, 1.0], None);
+        let pool = vec![
+            Item::new(1, vec![10.0, 10.0], None),
+            Item::new(2, vec![1.0, 2.0], None),
+            Item::new(3, vec![-1.0, -1.0], None),
+        ];
+        let ids = |items: Vec<Item>| items.iter().map(|i| i.id).collect::<Vec<u32>>();
+        assert_eq!(ids(cosine_knn(&query, &pool, 2)), vec![1, 2]);
+        assert_eq!(ids(euclidean_knn(&query, &pool, 2)), vec![2, 3]);
+        assert!(cosine_knn(&query, &[], 3).is_empty());
+    }
+
+    #[test]
+    fn test_scores_match_result() {
+        let query = Item::new(0, vec![3.0, 45.0, 7.0], None);
+        let pool: Vec<Item> = (1..20)
+            .map(|id| Item::new(id, vec![id as f32, (id * 7 % 5) as f32, 2.0], None))
+            .collect();
+        for algorithm in SimilarityAlgos::ALL {
+            let config = KNNConfig::default()
+                .set_algorithm(algorithm)
+                .set_num_neighbors(5);
+            let knn = KNN::from_config(query.clone(), pool.clone(), &config).unwrap();
+            let expected: Vec<(u32, f32)> =
+                knn.result().iter().map(|n| (n.id, n.result)).collect();
+            assert_eq!(knn.scores(), expected);
+            let borrowed: Vec<&Item> = pool.iter().collect();
+            assert_eq!(
+                knn_scores(&query.values, &borrowed, &config).unwrap(),
+                expected
+            );
+        }
+        let invalid = KNNConfig::default().set_num_neighbors(0);
+        assert!(knn_scores(&query.values, &pool, &invalid).is_err());
+    }
+
+    #[test]
+    fn test_compare_metrics() {
+        let query = Item::new(0, vec![1.0, 2.0, 3.0], None);
+        let pool = vec![
+            Item::new(1, vec![2.0, 4.0, 6.0], None),
+            Item::new(2, vec![1.0, 2.0, 2.5], None),
+            Item::new(3, vec![3.0, 2.0, 1.0], None),
+        ];
+        let comparison = compare_metrics(&query, &pool, 2);
+        assert_eq!(comparison.neighbors.len(), SimilarityAlgos::ALL.len());
+        assert!(comparison.neighbors.iter().all(|(_, n)| n.len() == 2));
+        let ids = |metric| {
+            comparison
+                .neighbors(metric)
+                .iter()
+                .map(|i| i.id)
+                .collect::<Vec<u32>>()
+        };
+        assert_eq!(ids(SimilarityAlgos::Cosine), vec![1, 2]);
+        assert_eq!(ids(SimilarityAlgos::Euclidean), vec![2, 3]);
+        let correlation = comparison
+            .rank_correlation(SimilarityAlgos::Cosine, SimilarityAlgos::Euclidean);
+        assert_eq!(correlation, -0.5);
+        assert_eq!(
+            correlation,
+            comparison
+                .rank_correlation(SimilarityAlgos::Euclidean, SimilarityAlgos::Cosine)
+        );
+    }
+}
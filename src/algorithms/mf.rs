@@ -0,0 +1,841 @@
+//! Matrix factorization trained with stochastic gradient descent
+use std::collections::{BTreeMap, HashMap};
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rand_distr::{Distribution, Normal};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::algorithms::baseline::{BaselineConfig, BaselineOnly};
+use crate::algorithms::config::{ensure, AlgorithmConfig};
+use crate::algorithms::history::{EarlyStopping, TrainingHistory};
+use crate::algorithms::regularization::Regularization;
+use crate::dataset::{Dataset, RatingScale};
+use crate::envelope::{fields, PersistedModel};
+use crate::errors::{Error, Result};
+use crate::factors::{FactorMatrix, Precision};
+use crate::memory::MemoryFootprint;
+use crate::privacy::{GradientPrivacy, PrivacyAccountant};
+use crate::recommender::{RankedItems, Recommendation, Recommender};
+use crate::sets::IdSet;
+use crate::utils::dot;
+
+/// # Matrix factorization configuration
+/// Hyperparameters of [`MatrixFactorization`].
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::algorithms::config::AlgorithmConfig;
+/// use rec_rsys::algorithms::mf::MFConfig;
+/// use rec_rsys::algorithms::regularization::Regularization;
+/// let config = MFConfig::from_toml(
+///     "num_factors = 8\ndropout = 0.1\n[regularization]\nkind = \"elastic_net\"\nl1 = 0.01\nl2 = 0.05",
+/// )
+/// .unwrap();
+/// assert_eq!(config.regularization, Regularization::ElasticNet { l1: 0.01, l2: 0.05 });
+/// assert!(MFConfig::default().set_dropout(1.0).validate().is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MFConfig {
+    /// Number of latent dimensions.
+    pub num_factors: usize,
+    /// Number of passes over the ratings.
+    pub num_epochs: usize,
+    pub learning_rate: f32,
+    /// Standard deviation of the normal distribution the factors start from.
+    pub init_std: f32,
+    /// Penalty on the factors and the biases.
+    pub regularization: Regularization,
+    /// Probability of dropping each latent dimension of an update.
+    pub dropout: f32,
+    /// Seed of the initialization, of the order of the ratings and of the dropout.
+    pub seed: u64,
+    /// Training stops with an error when the loss exceeds the one of the first
+    /// epoch this many times.
+    pub max_loss_growth: f64,
+    /// Perturbs the gradients for differential privacy, when set.
+    pub privacy: Option<GradientPrivacy>,
+    /// Stops the training once the RMSE on the validation ratings, or on the
+    /// training ones without validation ratings, stops improving.
+    pub early_stopping: Option<EarlyStopping>,
+    /// Starts the biases from the ones of a
+    /// [`BaselineOnly`](crate::algorithms::baseline::BaselineOnly) trained with this
+    /// config instead of 0.
+    pub baseline: Option<BaselineConfig>,
+}
+
+impl Default for MFConfig {
+    fn default() -> Self {
+        MFConfig {
+            num_factors: 20,
+            num_epochs: 20,
+            learning_rate: 0.01,
+            init_std: 0.1,
+            regularization: Regularization::L2 { lambda: 0.02 },
+            dropout: 0.0,
+            seed: 42,
+            max_loss_growth: 100.0,
+            privacy: None,
+            early_stopping: None,
+            baseline: None,
+        }
+    }
+}
+
+impl MFConfig {
+    pub fn set_num_factors(mut self, num_factors: usize) -> Self {
+        self.num_factors = num_factors;
+        self
+    }
+    pub fn set_num_epochs(mut self, num_epochs: usize) -> Self {
+        self.num_epochs = num_epochs;
+        self
+    }
+    pub fn set_learning_rate(mut self, learning_rate: f32) -> Self {
+        self.learning_rate = learning_rate;
+        self
+    }
+    pub fn set_init_std(mut self, init_std: f32) -> Self {
+        self.init_std = init_std;
+        self
+    }
+    pub fn set_regularization(mut self, regularization: Regularization) -> Self {
+        self.regularization = regularization;
+        self
+    }
+    pub fn set_dropout(mut self, dropout: f32) -> Self {
+        self.dropout = dropout;
+        self
+    }
+    pub fn set_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+    pub fn set_max_loss_growth(mut self, max_loss_growth: f64) -> Self {
+        self.max_loss_growth = max_loss_growth;
+        self
+    }
+    pub fn set_privacy(mut self, privacy: GradientPrivacy) -> Self {
+        self.privacy = Some(privacy);
+        self
+    }
+    pub fn set_early_stopping(mut self, early_stopping: EarlyStopping) -> Self {
+        self.early_stopping = Some(early_stopping);
+        self
+    }
+    pub fn set_baseline(mut self, baseline: BaselineConfig) -> Self {
+        self.baseline = Some(baseline);
+        self
+    }
+}
+
+impl AlgorithmConfig for MFConfig {
+    fn validate(&self) -> Result<()> {
+        ensure(self.num_factors > 0, "num_factors", "greater than 0")?;
+        ensure(self.num_epochs > 0, "num_epochs", "greater than 0")?;
+        ensure(
+            self.learning_rate > 0.0 && self.learning_rate.is_finite(),
+            "learning_rate",
+            "a positive number",
+        )?;
+        ensure(
+            self.init_std >= 0.0 && self.init_std.is_finite(),
+            "init_std",
+            "a non-negative number",
+        )?;
+        ensure((0.0..1.0).contains(&self.dropout), "dropout", "in [0, 1)")?;
+        if let Some(privacy) = &self.privacy {
+            privacy.validate()?;
+        }
+        if let Some(early_stopping) = &self.early_stopping {
+            early_stopping.validate()?;
+        }
+        if let Some(baseline) = &self.baseline {
+            baseline.validate()?;
+        }
+        self.regularization.validate()
+    }
+}
+
+type Parameters = (Vec<f32>, Vec<f32>, FactorMatrix, FactorMatrix);
+
+/// # Matrix factorization
+/// Learns a bias and a vector of latent factors for every user and item so that
+/// the global mean plus the biases plus the dot product of the factors
+/// approximates the ratings, with stochastic gradient descent.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::algorithms::mf::{MFConfig, MatrixFactorization};
+/// use rec_rsys::dataset::{Dataset, Rating};
+/// use rec_rsys::recommender::Recommender;
+/// let dataset = Dataset::new(vec![
+///     Rating::new(1, 10, 5.0), Rating::new(1, 11, 1.0),
+///     Rating::new(2, 10, 4.0), Rating::new(2, 11, 2.0), Rating::new(2, 12, 5.0),
+/// ]);
+/// let mut model = MatrixFactorization::new(MFConfig::default().set_num_factors(4));
+/// model.fit(&dataset).unwrap();
+/// assert!(model.predict(1, 10).unwrap() > model.predict(1, 11).unwrap());
+/// assert_eq!(model.recommend(1, 1)[0].item_id, 12);
+/// ```
+#[doc = include_str!("../../docs/algorithms/mf.md")]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MatrixFactorization {
+    config: MFConfig,
+    global_mean: f32,
+    users: HashMap<u32, usize>,
+    items: HashMap<u32, usize>,
+    item_ids: Vec<u32>,
+    user_biases: Vec<f32>,
+    item_biases: Vec<f32>,
+    user_factors: FactorMatrix,
+    item_factors: FactorMatrix,
+    /// The precision the factors are stored with once trained.
+    #[serde(default)]
+    precision: Precision,
+    seen: HashMap<u32, IdSet>,
+    /// The scale of the training ratings, the predictions are clipped to it.
+    #[serde(default)]
+    scale: Option<RatingScale>,
+    history: TrainingHistory,
+    accountant: Option<PrivacyAccountant>,
+}
+
+impl MatrixFactorization {
+    pub fn new(config: MFConfig) -> Self {
+        MatrixFactorization {
+            config,
+            global_mean: 0.0,
+            users: HashMap::new(),
+            items: HashMap::new(),
+            item_ids: Vec::new(),
+            user_biases: Vec::new(),
+            item_biases: Vec::new(),
+            user_factors: FactorMatrix::new(0, 0),
+            item_factors: FactorMatrix::new(0, 0),
+            precision: Precision::F32,
+            seen: HashMap::new(),
+            scale: None,
+            history: TrainingHistory::default(),
+            accountant: None,
+        }
+    }
+
+    pub fn config(&self) -> &MFConfig {
+        &self.config
+    }
+
+    /// The ids of the users, in the order of the rows of [`Self::user_factors`].
+    pub fn user_ids(&self) -> Vec<u32> {
+        let mut ids: Vec<u32> = self.users.keys().copied().collect();
+        ids.sort_unstable();
+        ids
+    }
+
+    /// The ids of the items, in the order of the rows of [`Self::item_factors`].
+    pub fn item_ids(&self) -> &[u32] {
+        &self.item_ids
+    }
+
+    /// The mean of the training ratings, the base of every prediction.
+    pub fn global_mean(&self) -> f32 {
+        self.global_mean
+    }
+
+    /// The learned user biases, in the order of [`Self::user_ids`].
+    pub fn user_biases(&self) -> &[f32] {
+        &self.user_biases
+    }
+
+    /// The learned item biases, in the order of [`Self::item_ids`].
+    pub fn item_biases(&self) -> &[f32] {
+        &self.item_biases
+    }
+
+    /// The learned user factors, one row per user in increasing id order.
+    pub fn user_factors(&self) -> &FactorMatrix {
+        &self.user_factors
+    }
+
+    /// The learned item factors, one row per item in increasing id order.
+    pub fn item_factors(&self) -> &FactorMatrix {
+        &self.item_factors
+    }
+
+    /// # Factor precision
+    /// Stores the learned factors with another precision, e.g. [`Precision::F16`]
+    /// to halve their memory. Predictions are computed from the stored values, the
+    /// error they introduce is measured by [`FactorMatrix::precision_diff`].
+    ///
+    /// Training always runs in `f32` and stores the factors of the next fits with
+    /// this precision too. Going back to `f32` does not restore the rounded values.
+    pub fn set_factor_precision(&mut self, precision: Precision) {
+        self.precision = precision;
+        self.user_factors = self.user_factors.to_precision(precision);
+        self.item_factors = self.item_factors.to_precision(precision);
+    }
+
+    /// The losses of the last training.
+    pub fn history(&self) -> &TrainingHistory {
+        &self.history
+    }
+
+    /// The privacy spent by the last training, when its gradients were perturbed.
+    pub fn privacy_spent(&self) -> Option<&PrivacyAccountant> {
+        self.accountant.as_ref()
+    }
+
+    /// Trains like [`Recommender::fit`] and also records the loss on the validation
+    /// ratings after every epoch, which the
+    /// [`early_stopping`](MFConfig::early_stopping) monitors. Validation ratings of
+    /// unknown users or items are ignored.
+    pub fn fit_with_validation(
+        &mut self,
+        dataset: &Dataset,
+        validation: Option<&Dataset>,
+    ) -> Result<()> {
+        self.config.validate()?;
+        if dataset.is_empty() {
+            return Err(Error::InvalidData(
+                "cannot fit on an empty dataset".to_string(),
+            ));
+        }
+        let mut rng = StdRng::seed_from_u64(self.config.seed);
+        self.users = index(dataset.users().into_iter());
+        self.item_ids = dataset.items().into_iter().collect();
+        self.items = index(self.item_ids.iter().copied());
+        self.global_mean =
+            dataset.ratings.iter().map(|r| r.rating).sum::<f32>() / dataset.len() as f32;
+        self.user_biases = vec![0.0; self.users.len()];
+        self.item_biases = vec![0.0; self.items.len()];
+        if let Some(config) = &self.config.baseline {
+            let mut baseline = BaselineOnly::new(config.clone());
+            baseline.fit(dataset)?;
+            self.users
+                .iter()
+                .for_each(|(id, user)| self.user_biases[*user] = baseline.user_bias(*id));
+            self.items
+                .iter()
+                .for_each(|(id, item)| self.item_biases[*item] = baseline.item_bias(*id));
+        }
+        let (factors, std) = (self.config.num_factors, self.config.init_std);
+        self.user_factors = random_factors(self.users.len(), factors, std, &mut rng);
+        self.item_factors = random_factors(self.items.len(), factors, std, &mut rng);
+        self.seen = dataset.user_item_sets();
+        self.scale = dataset.scale;
+        self.history = TrainingHistory::new(self.config.max_loss_growth);
+        self.accountant = self.config.privacy.map(|privacy| {
+            PrivacyAccountant::new(privacy.epsilon, privacy.mechanism.delta())
+        });
+
+        let mut ratings = self.rows(dataset);
+        let validation = validation.map(|validation| self.rows(validation));
+        let mut errors = Vec::new();
+        let mut best = None;
+        for _ in 0..self.config.num_epochs {
+            if let (Some(privacy), Some(accountant)) =
+                (&self.config.privacy, &mut self.accountant)
+            {
+                let (mechanism, epsilon) = privacy.per_epoch(self.config.num_epochs);
+                accountant.spend(epsilon, mechanism.delta())?;
+            }
+            self.epoch(&mut ratings, &mut rng);
+            let train_loss =
+                self.squared_error(&ratings) + self.penalty() / ratings.len() as f64;
+            let validation_loss = validation.as_ref().map(|v| self.squared_error(v));
+            self.history.record(train_loss, validation_loss)?;
+            if let Some(stopping) = self.config.early_stopping {
+                let error =
+                    validation_loss.unwrap_or_else(|| self.squared_error(&ratings));
+                errors.push(error.sqrt());
+                if stopping.restore_best
+                    && stopping.best_epoch(&errors) == Some(errors.len() - 1)
+                {
+                    best = Some(self.parameters());
+                }
+                if stopping.should_stop(&errors) {
+                    break;
+                }
+            }
+        }
+        if let Some(parameters) = best {
+            self.set_parameters(parameters);
+        }
+        self.set_factor_precision(self.precision);
+        Ok(())
+    }
+
+    /// The learned biases and factors, as `(user biases, item biases, user factors,
+    /// item factors)`.
+    fn parameters(&self) -> Parameters {
+        (
+            self.user_biases.clone(),
+            self.item_biases.clone(),
+            self.user_factors.clone(),
+            self.item_factors.clone(),
+        )
+    }
+
+    fn set_parameters(&mut self, parameters: Parameters) {
+        (
+            self.user_biases,
+            self.item_biases,
+            self.user_factors,
+            self.item_factors,
+        ) = parameters;
+    }
+
+    /// The ratings of known users and items, as `(user row, item row, rating)`.
+    fn rows(&self, dataset: &Dataset) -> Vec<(usize, usize, f32)> {
+        dataset
+            .ratings
+            .iter()
+            .filter_map(|r| {
+                let user = self.users.get(&r.user_id)?;
+                let item = self.items.get(&r.item_id)?;
+                Some((*user, *item, r.rating))
+            })
+            .collect()
+    }
+
+    /// Mean squared error of the predictions, 0 without ratings.
+    fn squared_error(&self, ratings: &[(usize, usize, f32)]) -> f64 {
+        let total: f64 = ratings
+            .iter()
+            .map(|&(user, item, rating)| (rating - self.score(user, item)) as f64)
+            .map(|error| error * error)
+            .sum();
+        total / ratings.len().max(1) as f64
+    }
+
+    /// Regularization penalty of every learned parameter.
+    fn penalty(&self) -> f64 {
+        let regularization = self.config.regularization;
+        [
+            &self.user_biases[..],
+            &self.item_biases[..],
+            &self.user_factors.to_rows().concat(),
+            &self.item_factors.to_rows().concat(),
+        ]
+        .iter()
+        .map(|values| regularization.penalty(values) as f64)
+        .sum()
+    }
+
+    /// Rating predicted from the rows of a user and an item.
+    fn score(&self, user: usize, item: usize) -> f32 {
+        self.global_mean
+            + self.user_biases[user]
+            + self.item_biases[item]
+            + self.user_factors.dot(user, &self.item_factors.row(item))
+    }
+
+    /// One pass of SGD over the ratings, given as `(user row, item row, rating)`.
+    fn epoch(&mut self, ratings: &mut [(usize, usize, f32)], rng: &mut StdRng) {
+        let config = self.config.clone();
+        let (rate, regularization) = (config.learning_rate, config.regularization);
+        let scale = 1.0 / (1.0 - config.dropout);
+        let mut kept = vec![true; config.num_factors];
+        ratings.shuffle(rng);
+        for &(user, item, rating) in ratings.iter() {
+            if config.dropout > 0.0 {
+                kept.iter_mut()
+                    .for_each(|keep| *keep = rng.gen::<f32>() >= config.dropout);
+            }
+            let (p, q) = (self.user_factors.row(user), self.item_factors.row(item));
+            let interaction: f32 = (0..config.num_factors)
+                .filter(|k| kept[*k])
+                .map(|k| p[k] * q[k])
+                .sum::<f32>()
+                * scale;
+            let error = rating
+                - (self.global_mean
+                    + self.user_biases[user]
+                    + self.item_biases[item]
+                    + interaction);
+            if let Some(privacy) = &config.privacy {
+                self.private_update(user, item, error * scale, &kept, privacy, rng);
+                continue;
+            }
+
+            self.user_biases[user] =
+                regularization.shrink(self.user_biases[user] + rate * error, rate);
+            self.item_biases[item] =
+                regularization.shrink(self.item_biases[item] + rate * error, rate);
+            let step = rate * error * scale;
+            let user_row = self.user_factors.row_mut(user).expect("trained in f32");
+            (0..config.num_factors).filter(|k| kept[*k]).for_each(|k| {
+                user_row[k] = regularization.shrink(p[k] + step * q[k], rate);
+            });
+            let item_row = self.item_factors.row_mut(item).expect("trained in f32");
+            (0..config.num_factors).filter(|k| kept[*k]).for_each(|k| {
+                item_row[k] = regularization.shrink(q[k] + step * p[k], rate);
+            });
+        }
+    }
+
+    /// Applies the clipped and perturbed update of a rating, see [`GradientPrivacy`].
+    fn private_update(
+        &mut self,
+        user: usize,
+        item: usize,
+        error: f32,
+        kept: &[bool],
+        privacy: &GradientPrivacy,
+        rng: &mut StdRng,
+    ) {
+        let (rate, regularization) =
+            (self.config.learning_rate, self.config.regularization);
+        let (p, q) = (self.user_factors.row(user), self.item_factors.row(item));
+        let mut update: Vec<f32> = [error, error]
+            .into_iter()
+            .chain(
+                q.iter()
+                    .zip(kept)
+                    .map(|(q, keep)| if *keep { error * q } else { 0.0 }),
+            )
+            .chain(
+                p.iter()
+                    .zip(kept)
+                    .map(|(p, keep)| if *keep { error * p } else { 0.0 }),
+            )
+            .collect();
+        privacy.perturb(&mut update, self.config.num_epochs, rng);
+        let (biases, factors) = update.split_at(2);
+        let (user_update, item_update) = factors.split_at(p.len());
+        self.user_biases[user] =
+            regularization.shrink(self.user_biases[user] + rate * biases[0], rate);
+        self.item_biases[item] =
+            regularization.shrink(self.item_biases[item] + rate * biases[1], rate);
+        let user_row = self.user_factors.row_mut(user).expect("trained in f32");
+        (0..p.len()).filter(|k| kept[*k]).for_each(|k| {
+            user_row[k] = regularization.shrink(p[k] + rate * user_update[k], rate);
+        });
+        let item_row = self.item_factors.row_mut(item).expect("trained in f32");
+        (0..q.len()).filter(|k| kept[*k]).for_each(|k| {
+            item_row[k] = regularization.shrink(q[k] + rate * item_update[k], rate);
+        });
+    }
+}
+
+/// # Squared loss gradient
+/// Gradient of the loss of a single rating, half its squared error, that every
+/// step of the training follows before the regularization is applied.
+///
+/// ## Parameters:
+/// * `rating`: The observed rating.
+/// * `global_mean`: The mean of the ratings.
+/// * `user_bias`, `item_bias`: The biases of the user and the item.
+/// * `p`, `q`: The factors of the user and the item.
+///
+/// ## Returns:
+/// * The partial derivatives, laid out as `[user bias, item bias, p.., q..]`.
+pub fn squared_loss_gradient(
+    rating: f32,
+    global_mean: f32,
+    user_bias: f32,
+    item_bias: f32,
+    p: &[f32],
+    q: &[f32],
+) -> Vec<f32> {
+    let error = rating - (global_mean + user_bias + item_bias + dot(p, q));
+    [-error, -error]
+        .into_iter()
+        .chain(q.iter().map(|q| -error * q))
+        .chain(p.iter().map(|p| -error * p))
+        .collect()
+}
+
+/// Assigns a row to every id, in increasing order.
+pub(crate) fn index(ids: impl Iterator<Item = u32>) -> HashMap<u32, usize> {
+    ids.enumerate().map(|(row, id)| (id, row)).collect()
+}
+
+/// A matrix of factors drawn from a centered normal distribution.
+pub(crate) fn random_factors(
+    rows: usize,
+    dims: usize,
+    std: f32,
+    rng: &mut StdRng,
+) -> FactorMatrix {
+    let mut factors = FactorMatrix::new(rows, dims);
+    if std > 0.0 {
+        let normal = Normal::new(0.0, std).expect("validated standard deviation");
+        (0..rows).for_each(|row| {
+            factors
+                .row_mut(row)
+                .expect("created in f32")
+                .iter_mut()
+                .for_each(|value| *value = normal.sample(rng))
+        });
+    }
+    factors
+}
+
+impl Recommender for MatrixFactorization {
+    fn fit(&mut self, dataset: &Dataset) -> Result<()> {
+        self.fit_with_validation(dataset, None)
+    }
+
+    fn recommend(&self, user_id: u32, num_items: usize) -> Vec<Recommendation> {
+        self.recommend_iter(user_id).take(num_items).collect()
+    }
+
+    fn recommend_iter(&self, user_id: u32) -> RankedItems {
+        let user = match self.users.get(&user_id) {
+            Some(user) => *user,
+            None => return RankedItems::default(),
+        };
+        let scores: HashMap<u32, f32> = self
+            .item_ids
+            .iter()
+            .enumerate()
+            .map(|(item, item_id)| (*item_id, self.score(user, item)))
+            .collect();
+        RankedItems::new(scores, self.seen.get(&user_id))
+    }
+
+    fn predict(&self, user_id: u32, item_id: u32) -> Option<f32> {
+        let user = self.users.get(&user_id)?;
+        let item = self.items.get(&item_id)?;
+        let score = self.score(*user, *item);
+        Some(self.scale.map_or(score, |scale| scale.clip(score)))
+    }
+}
+
+impl PersistedModel for MatrixFactorization {
+    const MODEL_TYPE: &'static str = "matrix_factorization";
+
+    fn hyperparameters(&self) -> BTreeMap<String, Value> {
+        fields(&self.config)
+    }
+}
+
+impl MemoryFootprint for MatrixFactorization {
+    fn heap_size(&self) -> usize {
+        self.users.heap_size()
+            + self.items.heap_size()
+            + self.item_ids.heap_size()
+            + self.user_biases.heap_size()
+            + self.item_biases.heap_size()
+            + self.user_factors.heap_size()
+            + self.item_factors.heap_size()
+            + self.seen.heap_size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accuracy::rmse;
+    use crate::dataset::Rating;
+    use crate::factors::score_diff;
+
+    fn dataset() -> Dataset {
+        Dataset::new(
+            (1..=6)
+                .flat_map(|user| {
+                    (10..16).map(move |item| {
+                        let rating = if (user + item) % 2 == 0 { 5.0 } else { 1.0 };
+                        Rating::new(user, item, rating)
+                    })
+                })
+                .collect(),
+        )
+    }
+
+    fn training_rmse(model: &MatrixFactorization, dataset: &Dataset) -> f32 {
+        let predicted: Vec<f32> = dataset
+            .ratings
+            .iter()
+            .map(|r| model.predict(r.user_id, r.item_id).unwrap())
+            .collect();
+        let actual: Vec<f32> = dataset.ratings.iter().map(|r| r.rating).collect();
+        rmse(&predicted, &actual)
+    }
+
+    #[test]
+    fn test_fit_learns_the_ratings() {
+        let config = MFConfig::default()
+            .set_num_factors(4)
+            .set_num_epochs(200)
+            .set_learning_rate(0.05);
+        let mut model = MatrixFactorization::new(config);
+        model.fit(&dataset()).unwrap();
+        assert!(training_rmse(&model, &dataset()) < 0.5);
+        assert!(model.predict(1, 99).is_none());
+        assert_eq!(model.user_ids(), vec![1, 2, 3, 4, 5, 6]);
+        assert_eq!(model.item_ids(), &[10, 11, 12, 13, 14, 15]);
+    }
+
+    #[test]
+    fn test_biases_start_from_the_baseline() {
+        let dataset = Dataset::new(vec![
+            Rating::new(1, 10, 5.0),
+            Rating::new(1, 11, 4.0),
+            Rating::new(2, 10, 3.0),
+            Rating::new(2, 11, 1.0),
+        ]);
+        let config = MFConfig::default()
+            .set_num_epochs(1)
+            .set_learning_rate(1e-6)
+            .set_baseline(BaselineConfig::default());
+        let mut model = MatrixFactorization::new(config);
+        model.fit(&dataset).unwrap();
+        let mut baseline = BaselineOnly::new(BaselineConfig::default());
+        baseline.fit(&dataset).unwrap();
+        assert!((model.user_biases()[0] - baseline.user_bias(1)).abs() < 1e-4);
+        assert!((model.item_biases()[1] - baseline.item_bias(11)).abs() < 1e-4);
+        assert!(model.user_biases()[0] > 0.0);
+    }
+
+    #[test]
+    fn test_fit_is_deterministic() {
+        let config = MFConfig::default().set_dropout(0.2);
+        let mut first = MatrixFactorization::new(config.clone());
+        let mut second = MatrixFactorization::new(config);
+        first.fit(&dataset()).unwrap();
+        second.fit(&dataset()).unwrap();
+        assert_eq!(first.user_factors(), second.user_factors());
+    }
+
+    #[test]
+    fn test_l1_makes_factors_sparse() {
+        let config = MFConfig::default()
+            .set_num_factors(8)
+            .set_regularization(Regularization::L1 { lambda: 1.0 });
+        let mut model = MatrixFactorization::new(config);
+        model.fit(&dataset()).unwrap();
+        let factors = model.item_factors().to_rows().concat();
+        assert!(
+            factors.iter().filter(|value| **value == 0.0).count() > factors.len() / 2
+        );
+    }
+
+    #[test]
+    fn test_history() {
+        let (train, validation) = dataset().split_random(0.2, 1);
+        let mut model = MatrixFactorization::new(MFConfig::default().set_num_epochs(5));
+        model
+            .fit_with_validation(&train, Some(&validation))
+            .unwrap();
+        let history = model.history();
+        assert_eq!(history.len(), 5);
+        assert_eq!(history.validation_losses().len(), 5);
+        let losses = history.train_losses();
+        assert!(losses[4] < losses[0]);
+    }
+
+    #[test]
+    fn test_early_stopping() {
+        let (train, validation) = dataset().split_random(0.2, 1);
+        let stopping = EarlyStopping::default().set_patience(2);
+        let config = MFConfig::default()
+            .set_num_epochs(300)
+            .set_learning_rate(0.05)
+            .set_early_stopping(stopping.set_min_delta(1e-3));
+        let mut model = MatrixFactorization::new(config.clone());
+        model
+            .fit_with_validation(&train, Some(&validation))
+            .unwrap();
+        let history = model.history();
+        assert!(history.len() < 300);
+        // The best epoch is restored, so the validation error is the lowest seen.
+        let best = history.best_epoch().unwrap();
+        let error = model.squared_error(&model.rows(&validation));
+        assert!((error - history.validation_losses()[best]).abs() < 1e-6);
+
+        let config = config.set_early_stopping(stopping.set_restore_best(false));
+        let mut model = MatrixFactorization::new(config);
+        model.fit(&train).unwrap();
+        let losses = model.history().train_losses();
+        assert!(losses.len() < 300);
+        assert!(losses[losses.len() - 1] >= losses[losses.len() - 3] - 1e-3);
+    }
+
+    #[test]
+    fn test_divergence_aborts_training() {
+        let mut model = MatrixFactorization::new(
+            MFConfig::default()
+                .set_learning_rate(50.0)
+                .set_regularization(Regularization::None),
+        );
+        assert!(matches!(
+            model.fit(&dataset()),
+            Err(Error::TrainingDiverged { .. })
+        ));
+        assert!(model.history().len() < 20);
+    }
+
+    #[test]
+    fn test_fit_rejects_invalid_config() {
+        let mut model = MatrixFactorization::new(MFConfig::default().set_num_factors(0));
+        assert!(model.fit(&dataset()).is_err());
+        let mut model = MatrixFactorization::new(MFConfig::default());
+        assert!(model.fit(&Dataset::new(Vec::new())).is_err());
+    }
+
+    #[test]
+    fn test_factor_precision() {
+        let mut model = MatrixFactorization::new(MFConfig::default().set_num_factors(8));
+        model.fit(&dataset()).unwrap();
+        let (users, items) = (model.user_factors().clone(), model.item_factors().clone());
+        let predictions: Vec<f32> = dataset()
+            .ratings
+            .iter()
+            .map(|r| model.predict(r.user_id, r.item_id).unwrap())
+            .collect();
+        let size = model.heap_size();
+        model.set_factor_precision(Precision::F16);
+        assert!(model.heap_size() < size);
+        assert!(
+            model
+                .user_factors()
+                .precision_diff(&users)
+                .unwrap()
+                .max_abs_error
+                < 1e-2
+        );
+        let bound = score_diff(&users, &items, Precision::F16)
+            .unwrap()
+            .max_abs_error;
+        dataset()
+            .ratings
+            .iter()
+            .zip(predictions)
+            .for_each(|(r, prediction)| {
+                let compressed = model.predict(r.user_id, r.item_id).unwrap();
+                assert!((compressed - prediction).abs() <= bound + 1e-5);
+            });
+        model.fit(&dataset()).unwrap();
+        assert_eq!(model.item_factors().precision(), Precision::F16);
+    }
+
+    #[test]
+    fn test_private_training() {
+        use crate::privacy::NoiseMechanism;
+
+        let privacy = GradientPrivacy {
+            mechanism: NoiseMechanism::Gaussian { delta: 1e-5 },
+            epsilon: 100_000.0,
+            clip: 5.0,
+        };
+        let config = MFConfig::default()
+            .set_num_factors(4)
+            .set_num_epochs(100)
+            .set_learning_rate(0.05)
+            .set_privacy(privacy);
+        let mut model = MatrixFactorization::new(config);
+        model.fit(&dataset()).unwrap();
+        assert!(training_rmse(&model, &dataset()) < 1.0);
+        let spent = model.privacy_spent().unwrap();
+        assert!((spent.epsilon_spent() - 100_000.0).abs() < 1e-6);
+        assert!((spent.delta_spent() - 1e-5).abs() < 1e-12);
+        assert!(MatrixFactorization::new(MFConfig::default())
+            .privacy_spent()
+            .is_none());
+    }
+}
@@ -1,3 +1,39 @@
 //! Common algorithms
 
+#[cfg(feature = "serde")]
+pub mod als;
+#[cfg(feature = "serde")]
+pub mod baseline;
+#[cfg(feature = "serde")]
+pub mod coclustering;
+pub mod config;
+#[cfg(feature = "serde")]
+pub mod content_based;
+#[cfg(feature = "serde")]
+pub mod cooccurrence;
+#[cfg(feature = "serde")]
+pub mod funk_svd;
+#[cfg(feature = "serde")]
+pub mod history;
+#[cfg(feature = "serde")]
+pub mod item_knn;
 pub mod knn;
+#[cfg(feature = "serde")]
+pub mod mf;
+#[cfg(feature = "serde")]
+pub mod most_popular;
+#[cfg(feature = "serde")]
+pub mod nmf;
+#[cfg(feature = "serde")]
+pub mod pca;
+#[cfg(feature = "serde")]
+pub mod regularization;
+#[cfg(feature = "serde")]
+pub mod slope_one;
+pub mod svd;
+#[cfg(feature = "serde")]
+pub mod svdpp;
+#[cfg(feature = "serde")]
+pub mod user_knn;
+
+pub use knn::{cosine_knn, euclidean_knn};
@@ -0,0 +1,59 @@
+//! Most popular items baseline
+use std::collections::HashMap;
+
+use crate::dataset::Dataset;
+use crate::errors::Result;
+use crate::memory::MemoryFootprint;
+use crate::recommender::{sort_ranked, unseen, RankedItems, Recommendation, Recommender};
+use crate::sets::IdSet;
+
+/// # Most popular
+/// Recommends the items with the most ratings that the user has not rated yet.
+/// It ignores the user's tastes, which makes it the baseline any personalised
+/// algorithm has to beat.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::algorithms::most_popular::MostPopular;
+/// use rec_rsys::dataset::{Dataset, Rating};
+/// use rec_rsys::recommender::Recommender;
+/// let dataset = Dataset::new(vec![Rating::new(1, 10, 4.0), Rating::new(2, 10, 5.0), Rating::new(2, 11, 3.0)]);
+/// let mut model = MostPopular::default();
+/// model.fit(&dataset).unwrap();
+/// assert_eq!(model.recommend(1, 5)[0].item_id, 11);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MostPopular {
+    /// Every item with its number of ratings, most rated first.
+    ranked: Vec<Recommendation>,
+    seen: HashMap<u32, IdSet>,
+}
+
+impl Recommender for MostPopular {
+    fn fit(&mut self, dataset: &Dataset) -> Result<()> {
+        let mut counts: HashMap<u32, f32> = HashMap::new();
+        self.seen.clear();
+        dataset.ratings.iter().for_each(|r| {
+            *counts.entry(r.item_id).or_default() += 1.0;
+            self.seen.entry(r.user_id).or_default().insert(r.item_id);
+        });
+        self.ranked = sort_ranked(counts);
+        Ok(())
+    }
+
+    fn recommend(&self, user_id: u32, num_items: usize) -> Vec<Recommendation> {
+        unseen(&self.ranked, self.seen.get(&user_id))
+            .take(num_items)
+            .collect()
+    }
+
+    fn recommend_iter(&self, user_id: u32) -> RankedItems {
+        unseen(&self.ranked, self.seen.get(&user_id)).collect()
+    }
+}
+
+impl MemoryFootprint for MostPopular {
+    fn heap_size(&self) -> usize {
+        self.ranked.heap_size() + self.seen.heap_size()
+    }
+}
@@ -0,0 +1,517 @@
+//! Non-negative matrix factorization
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::algorithms::config::{ensure, AlgorithmConfig};
+use crate::algorithms::history::TrainingHistory;
+use crate::envelope::PersistedModel;
+use crate::errors::{Error, Result};
+use crate::matrix::{matmul, transpose};
+use crate::memory::MemoryFootprint;
+
+/// How the factors are updated at each iteration.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NMFSolver {
+    /// The multiplicative rules of Lee and Seung, simple but slow to converge.
+    #[default]
+    MultiplicativeUpdate,
+    /// Gradient steps projected back on the non-negative orthant, which converge
+    /// faster and can set factors exactly to 0.
+    ProjectedGradient,
+}
+
+/// What unobserved entries, marked with `NaN`, stand for.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MissingValuePolicy {
+    /// They are unknown and left out of the loss, as for explicit ratings.
+    #[default]
+    Ignore,
+    /// They are interactions of strength 0, as for implicit feedback.
+    Zero,
+}
+
+/// # NMF configuration
+/// Hyperparameters of [`NMF`].
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::algorithms::config::AlgorithmConfig;
+/// use rec_rsys::algorithms::nmf::{NMFConfig, NMFSolver};
+/// let config = NMFConfig::from_toml("n_components = 4\nsolver = \"projected_gradient\"").unwrap();
+/// assert_eq!(config.solver, NMFSolver::ProjectedGradient);
+/// assert!(NMFConfig::default().set_n_components(0).validate().is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NMFConfig {
+    /// Number of latent components.
+    pub n_components: usize,
+    /// Maximum number of updates of both factors.
+    pub max_iter: usize,
+    /// Training stops when the reconstruction error improves by less than this
+    /// fraction between two iterations.
+    pub tolerance: f32,
+    pub solver: NMFSolver,
+    pub missing: MissingValuePolicy,
+    /// Seed of the random initialization.
+    pub seed: u64,
+    /// Training stops with an error when the reconstruction error exceeds the one
+    /// of the first iteration this many times.
+    pub max_loss_growth: f64,
+}
+
+impl Default for NMFConfig {
+    fn default() -> Self {
+        NMFConfig {
+            n_components: 10,
+            max_iter: 200,
+            tolerance: 1e-4,
+            solver: NMFSolver::default(),
+            missing: MissingValuePolicy::default(),
+            seed: 42,
+            max_loss_growth: 100.0,
+        }
+    }
+}
+
+impl NMFConfig {
+    pub fn set_n_components(mut self, n_components: usize) -> Self {
+        self.n_components = n_components;
+        self
+    }
+    pub fn set_max_iter(mut self, max_iter: usize) -> Self {
+        self.max_iter = max_iter;
+        self
+    }
+    pub fn set_tolerance(mut self, tolerance: f32) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+    pub fn set_solver(mut self, solver: NMFSolver) -> Self {
+        self.solver = solver;
+        self
+    }
+    pub fn set_missing(mut self, missing: MissingValuePolicy) -> Self {
+        self.missing = missing;
+        self
+    }
+    pub fn set_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+    pub fn set_max_loss_growth(mut self, max_loss_growth: f64) -> Self {
+        self.max_loss_growth = max_loss_growth;
+        self
+    }
+}
+
+impl AlgorithmConfig for NMFConfig {
+    fn validate(&self) -> Result<()> {
+        ensure(self.n_components > 0, "n_components", "greater than 0")?;
+        ensure(self.max_iter > 0, "max_iter", "greater than 0")?;
+        ensure(
+            self.tolerance >= 0.0 && self.tolerance.is_finite(),
+            "tolerance",
+            "a non-negative number",
+        )
+    }
+}
+
+/// # NMF
+/// Factorizes a non-negative matrix `V` into two non-negative matrices `W` and `H`
+/// such that `W H` approximates `V`. The components can only add up, which makes
+/// them easier to interpret than the factors of an SVD.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::algorithms::nmf::{NMFConfig, NMFSolver, NMF};
+/// let matrix = vec![vec![1.0, 0.0, 2.0], vec![2.0, 0.0, 4.0], vec![0.0, 3.0, 0.0]];
+/// let config = NMFConfig::default().set_n_components(2).set_solver(NMFSolver::ProjectedGradient);
+/// let nmf = NMF::fit(&matrix, &config).unwrap();
+/// assert!(nmf.reconstruction_error() < 0.1);
+/// assert!(nmf.w().iter().flatten().all(|w| *w >= 0.0));
+/// ```
+#[doc = include_str!("../../docs/algorithms/nmf.md")]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NMF {
+    w: Vec<Vec<f32>>,
+    h: Vec<Vec<f32>>,
+    reconstruction_error: f32,
+    iterations: usize,
+    #[serde(default)]
+    history: TrainingHistory,
+}
+
+impl NMF {
+    /// Factorizes `matrix`, which must not have negative or infinite entries.
+    /// Unobserved entries are marked with `NaN` and handled by
+    /// [`NMFConfig::missing`]. Fails with [`Error::TrainingDiverged`] when the
+    /// reconstruction error stops being finite or grows too much.
+    pub fn fit(matrix: &[Vec<f32>], config: &NMFConfig) -> Result<Self> {
+        config.validate()?;
+        let columns = matrix.first().map_or(0, Vec::len);
+        if columns == 0 || matrix.iter().any(|row| row.len() != columns) {
+            return Err(Error::InvalidData(
+                "NMF needs a non-empty matrix with rows of the same length".to_string(),
+            ));
+        }
+        if matrix
+            .iter()
+            .flatten()
+            .any(|value| *value < 0.0 || value.is_infinite())
+        {
+            return Err(Error::InvalidData(
+                "NMF needs a matrix without negative or infinite entries".to_string(),
+            ));
+        }
+        let observed: Vec<Vec<bool>> = matrix
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|value| {
+                        config.missing == MissingValuePolicy::Zero || !value.is_nan()
+                    })
+                    .collect()
+            })
+            .collect();
+        let values: Vec<Vec<f32>> = matrix
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|v| if v.is_nan() { 0.0 } else { *v })
+                    .collect()
+            })
+            .collect();
+        let num_observed = observed.iter().flatten().filter(|o| **o).count();
+        if num_observed == 0 {
+            return Err(Error::InvalidData(
+                "NMF needs at least one observed entry".to_string(),
+            ));
+        }
+
+        let k = config.n_components;
+        let mean = values.iter().flatten().sum::<f32>() / num_observed as f32;
+        let scale = (mean / k as f32).sqrt();
+        let mut rng = StdRng::seed_from_u64(config.seed);
+        let mut random = |rows: usize, cols: usize| -> Vec<Vec<f32>> {
+            (0..rows)
+                .map(|_| (0..cols).map(|_| rng.gen::<f32>() * scale).collect())
+                .collect()
+        };
+        let mut w = random(matrix.len(), k);
+        let mut h = random(k, columns);
+
+        let mut error = frobenius_norm(&residual(&values, &observed, &w, &h));
+        let mut history = TrainingHistory::new(config.max_loss_growth);
+        let mut iterations = 0;
+        while iterations < config.max_iter {
+            iterations += 1;
+            match config.solver {
+                NMFSolver::MultiplicativeUpdate => {
+                    let wt = transpose(&w);
+                    let approximation = masked_product(&observed, &w, &h);
+                    h = multiplicative_update(
+                        &h,
+                        &matmul(&wt, &values),
+                        &matmul(&wt, &approximation),
+                    );
+                    let ht = transpose(&h);
+                    let approximation = masked_product(&observed, &w, &h);
+                    w = multiplicative_update(
+                        &w,
+                        &matmul(&values, &ht),
+                        &matmul(&approximation, &ht),
+                    );
+                },
+                NMFSolver::ProjectedGradient => {
+                    let wt = transpose(&w);
+                    let gradient = matmul(&wt, &residual(&values, &observed, &w, &h));
+                    h = projected_step(&h, &gradient, frobenius_norm(&matmul(&wt, &w)));
+                    let ht = transpose(&h);
+                    let gradient = matmul(&residual(&values, &observed, &w, &h), &ht);
+                    w = projected_step(&w, &gradient, frobenius_norm(&matmul(&h, &ht)));
+                },
+            }
+            let previous = error;
+            error = frobenius_norm(&residual(&values, &observed, &w, &h));
+            history.record(error as f64, None)?;
+            if previous - error <= config.tolerance * previous {
+                break;
+            }
+        }
+        Ok(NMF {
+            w,
+            h,
+            reconstruction_error: error,
+            iterations,
+            history,
+        })
+    }
+
+    /// The `rows x n_components` factor.
+    pub fn w(&self) -> &[Vec<f32>] {
+        &self.w
+    }
+
+    /// The `n_components x columns` factor.
+    pub fn h(&self) -> &[Vec<f32>] {
+        &self.h
+    }
+
+    /// The Frobenius norm of `V - W H` over the observed entries.
+    pub fn reconstruction_error(&self) -> f32 {
+        self.reconstruction_error
+    }
+
+    /// How many iterations were run before converging or reaching `max_iter`.
+    pub fn iterations(&self) -> usize {
+        self.iterations
+    }
+
+    /// The reconstruction error after every iteration.
+    pub fn history(&self) -> &TrainingHistory {
+        &self.history
+    }
+
+    /// The approximation `W H` of the factorized matrix, which also predicts the
+    /// unobserved entries.
+    pub fn reconstruct(&self) -> Vec<Vec<f32>> {
+        matmul(&self.w, &self.h)
+    }
+}
+
+/// The `(W, H, reconstruction error)` returned by [`nmf`].
+pub type Factorization = (Vec<Vec<f32>>, Vec<Vec<f32>>, f32);
+
+/// # Non-negative matrix factorization
+/// Factorizes a fully observed non-negative matrix with the multiplicative update
+/// rules, for callers that only need the factors. See [`NMF::fit`] for the other
+/// solver and missing entries.
+///
+/// ## Parameters:
+/// * `matrix`: The `m x n` matrix `V`, without negative entries.
+/// * `n_components`: The number of components `k`.
+/// * `max_iter`: The maximum number of updates of both factors.
+/// * `tolerance`: Training stops when the reconstruction error improves by less
+///   than this fraction between two iterations.
+///
+/// ## Returns:
+/// * `(W, H, error)`: the `m x k` and `k x n` factors and the Frobenius norm of
+///   `V - W H`, or an error if the matrix or the parameters are invalid.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::algorithms::nmf::nmf;
+/// let matrix = vec![vec![1.0, 2.0], vec![2.0, 4.0], vec![3.0, 6.0]];
+/// let (w, h, error) = nmf(&matrix, 1, 500, 1e-6).unwrap();
+/// assert_eq!((w.len(), h[0].len()), (3, 2));
+/// assert!(error < 0.01);
+/// ```
+#[doc = include_str!("../../docs/algorithms/nmf.md")]
+pub fn nmf(
+    matrix: &[Vec<f32>],
+    n_components: usize,
+    max_iter: usize,
+    tolerance: f32,
+) -> Result<Factorization> {
+    let config = NMFConfig::default()
+        .set_n_components(n_components)
+        .set_max_iter(max_iter)
+        .set_tolerance(tolerance)
+        .set_solver(NMFSolver::MultiplicativeUpdate)
+        .set_missing(MissingValuePolicy::Zero);
+    let NMF {
+        w,
+        h,
+        reconstruction_error,
+        ..
+    } = NMF::fit(matrix, &config)?;
+    Ok((w, h, reconstruction_error))
+}
+
+/// `W H` on the observed entries, 0 elsewhere.
+fn masked_product(
+    observed: &[Vec<bool>],
+    w: &[Vec<f32>],
+    h: &[Vec<f32>],
+) -> Vec<Vec<f32>> {
+    matmul(w, h)
+        .into_iter()
+        .zip(observed)
+        .map(|(row, mask)| {
+            row.into_iter()
+                .zip(mask)
+                .map(|(value, &observed)| if observed { value } else { 0.0 })
+                .collect()
+        })
+        .collect()
+}
+
+/// `W H - V` on the observed entries, 0 elsewhere.
+fn residual(
+    values: &[Vec<f32>],
+    observed: &[Vec<bool>],
+    w: &[Vec<f32>],
+    h: &[Vec<f32>],
+) -> Vec<Vec<f32>> {
+    masked_product(observed, w, h)
+        .into_iter()
+        .zip(values)
+        .map(|(row, values)| row.iter().zip(values).map(|(a, v)| a - v).collect())
+        .collect()
+}
+
+/// `factor * numerator / denominator`, element-wise.
+fn multiplicative_update(
+    factor: &[Vec<f32>],
+    numerator: &[Vec<f32>],
+    denominator: &[Vec<f32>],
+) -> Vec<Vec<f32>> {
+    factor
+        .iter()
+        .zip(numerator.iter().zip(denominator))
+        .map(|(row, (num, den))| {
+            row.iter()
+                .zip(num.iter().zip(den))
+                .map(|(f, (n, d))| f * n / (d + f32::EPSILON))
+                .collect()
+        })
+        .collect()
+}
+
+/// One projected gradient step on a factor. The step is the inverse of an upper
+/// bound of the Lipschitz constant of the gradient, the norm of the Gram matrix of
+/// the other factor, so the error never increases.
+fn projected_step(
+    x: &[Vec<f32>],
+    gradient: &[Vec<f32>],
+    lipschitz: f32,
+) -> Vec<Vec<f32>> {
+    if lipschitz == 0.0 {
+        return x.to_vec();
+    }
+    x.iter()
+        .zip(gradient)
+        .map(|(row, gradient)| {
+            row.iter()
+                .zip(gradient)
+                .map(|(value, g)| (value - g / lipschitz).max(0.0))
+                .collect()
+        })
+        .collect()
+}
+
+fn frobenius_norm(matrix: &[Vec<f32>]) -> f32 {
+    matrix.iter().flatten().map(|v| v * v).sum::<f32>().sqrt()
+}
+
+impl PersistedModel for NMF {
+    const MODEL_TYPE: &'static str = "nmf";
+}
+
+impl MemoryFootprint for NMFConfig {}
+
+impl MemoryFootprint for NMF {
+    fn heap_size(&self) -> usize {
+        self.w.heap_size() + self.h.heap_size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A rank 2 matrix with a block structure.
+    fn matrix() -> Vec<Vec<f32>> {
+        let w = vec![
+            vec![1.0, 0.0],
+            vec![2.0, 0.0],
+            vec![0.0, 1.0],
+            vec![1.0, 1.0],
+        ];
+        let h = vec![vec![3.0, 1.0, 0.0], vec![0.0, 1.0, 2.0]];
+        matmul(&w, &h)
+    }
+
+    #[test]
+    fn test_solvers_converge() {
+        for solver in [
+            NMFSolver::MultiplicativeUpdate,
+            NMFSolver::ProjectedGradient,
+        ] {
+            let config = NMFConfig::default()
+                .set_n_components(2)
+                .set_max_iter(2000)
+                .set_tolerance(1e-7)
+                .set_solver(solver);
+            let nmf = NMF::fit(&matrix(), &config).unwrap();
+            assert!(nmf.reconstruction_error() < 0.05, "{:?}", solver);
+            assert!(nmf.w().iter().chain(nmf.h()).flatten().all(|v| *v >= 0.0));
+            assert_eq!((nmf.w().len(), nmf.h()[0].len()), (4, 3));
+            let losses = nmf.history().train_losses();
+            assert_eq!(losses.len(), nmf.iterations());
+            assert_eq!(losses[losses.len() - 1], nmf.reconstruction_error() as f64);
+        }
+    }
+
+    #[test]
+    fn test_projected_gradient_error_decreases() {
+        let config = NMFConfig::default()
+            .set_n_components(1)
+            .set_solver(NMFSolver::ProjectedGradient);
+        let short = NMF::fit(&matrix(), &config.clone().set_max_iter(2)).unwrap();
+        let long = NMF::fit(&matrix(), &config.set_max_iter(50)).unwrap();
+        assert!(long.reconstruction_error() <= short.reconstruction_error());
+    }
+
+    #[test]
+    fn test_missing_entries() {
+        let mut matrix = matrix();
+        let hidden = matrix[3][0];
+        matrix[3][0] = f32::NAN;
+        let config = NMFConfig::default()
+            .set_n_components(2)
+            .set_max_iter(3000)
+            .set_tolerance(1e-8)
+            .set_solver(NMFSolver::ProjectedGradient);
+        let ignored = NMF::fit(&matrix, &config).unwrap();
+        assert!(ignored.reconstruction_error() < 0.05);
+        assert!((ignored.reconstruct()[3][0] - hidden).abs() < 0.5);
+        let zero =
+            NMF::fit(&matrix, &config.set_missing(MissingValuePolicy::Zero)).unwrap();
+        assert!(zero.reconstruct()[3][0] < hidden - 1.0);
+        assert!(NMF::fit(&[vec![f32::NAN]], &NMFConfig::default()).is_err());
+    }
+
+    #[test]
+    fn test_nmf() {
+        let (w, h, error) = nmf(&matrix(), 2, 2000, 1e-7).unwrap();
+        assert!(error < 0.05);
+        let reconstructed = matmul(&w, &h);
+        let expected = frobenius_norm(
+            &reconstructed
+                .iter()
+                .zip(matrix())
+                .map(|(a, v)| a.iter().zip(v).map(|(a, v)| a - v).collect())
+                .collect::<Vec<Vec<f32>>>(),
+        );
+        assert!((error - expected).abs() < 1e-5);
+        assert!(nmf(&matrix(), 0, 10, 1e-4).is_err());
+        // A missing entry is a 0 for the plain function.
+        assert!(nmf(&[vec![1.0, f32::NAN]], 1, 10, 1e-4).is_ok());
+    }
+
+    #[test]
+    fn test_fit_invalid() {
+        let config = NMFConfig::default().set_n_components(2);
+        assert!(NMF::fit(&[vec![1.0, -1.0]], &config).is_err());
+        assert!(NMF::fit(&[], &config).is_err());
+        assert!(NMF::fit(&[vec![1.0], vec![1.0, 2.0]], &config).is_err());
+        assert!(NMF::fit(&[vec![1.0, f32::INFINITY]], &config).is_err());
+        let diverged = NMF::fit(&[vec![f32::MAX, 1.0], vec![1.0, f32::MAX]], &config);
+        assert!(matches!(diverged, Err(Error::TrainingDiverged { .. })));
+    }
+}
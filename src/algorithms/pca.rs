@@ -0,0 +1,238 @@
+//! Principal component analysis
+use serde::{Deserialize, Serialize};
+
+use crate::algorithms::config::ensure;
+use crate::envelope::PersistedModel;
+use crate::errors::{Error, Result};
+use crate::matrix::{mean_along_axis, rank_for_fraction, symmetric_eigen};
+use crate::memory::MemoryFootprint;
+use crate::utils::dot;
+
+/// # PCA
+/// Projects vectors on the orthogonal directions along which the data varies the
+/// most, to reduce the dimension of item features before comparing them.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::algorithms::pca::PCA;
+/// let data = vec![vec![1.0, 1.1, 0.0], vec![2.0, 1.9, 0.1], vec![3.0, 3.1, 0.0], vec![4.0, 3.9, 0.1]];
+/// let pca = PCA::fit_variance_threshold(&data, 0.95).unwrap();
+/// assert_eq!(pca.n_components(), 1);
+/// assert_eq!(pca.transform(&data)[0].len(), 1);
+///
+/// let (pca, projected) = PCA::fit_transform(&data, 2).unwrap();
+/// assert!(pca.explained_variance_ratio()[0] > 0.99);
+/// assert_eq!(projected, pca.transform(&data));
+/// ```
+#[doc = include_str!("../../docs/algorithms/pca.md")]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PCA {
+    mean: Vec<f32>,
+    components: Vec<Vec<f32>>,
+    explained_variance: Vec<f32>,
+    total_variance: f32,
+}
+
+impl PCA {
+    /// Keeps the `n_components` directions with the most variance.
+    ///
+    /// ## Parameters:
+    /// * `data`: The observations, one per row.
+    /// * `n_components`: The number of components, at most the number of columns.
+    pub fn fit(data: &[Vec<f32>], n_components: usize) -> Result<Self> {
+        let dimensions = data.first().map_or(0, Vec::len);
+        ensure(
+            n_components > 0 && n_components <= dimensions,
+            "n_components",
+            "between 1 and the number of columns",
+        )?;
+        let mut pca = PCA::fit_all(data)?;
+        pca.components.truncate(n_components);
+        pca.explained_variance.truncate(n_components);
+        Ok(pca)
+    }
+
+    /// Like [`PCA::fit`], also projecting the data it was fitted on, e.g. the item
+    /// features before a [`KNN`](crate::algorithms::knn::KNN) search.
+    ///
+    /// ## Returns:
+    /// * The fitted PCA and the observations projected on its components.
+    pub fn fit_transform(
+        data: &[Vec<f32>],
+        n_components: usize,
+    ) -> Result<(Self, Vec<Vec<f32>>)> {
+        let pca = PCA::fit(data, n_components)?;
+        let projected = pca.transform(data);
+        Ok((pca, projected))
+    }
+
+    /// Keeps the fewest directions that explain at least `threshold` of the
+    /// variance of the data.
+    ///
+    /// ## Parameters:
+    /// * `data`: The observations, one per row.
+    /// * `threshold`: The fraction of the variance to retain, e.g. `0.95`.
+    pub fn fit_variance_threshold(data: &[Vec<f32>], threshold: f32) -> Result<Self> {
+        ensure(
+            threshold > 0.0 && threshold <= 1.0,
+            "threshold",
+            "between 0 and 1",
+        )?;
+        let mut pca = PCA::fit_all(data)?;
+        let n_components = rank_for_fraction(&pca.explained_variance, threshold).max(1);
+        pca.components.truncate(n_components);
+        pca.explained_variance.truncate(n_components);
+        Ok(pca)
+    }
+
+    fn fit_all(data: &[Vec<f32>]) -> Result<Self> {
+        if data.len() < 2 {
+            return Err(Error::InvalidData(
+                "PCA needs at least 2 observations".to_string(),
+            ));
+        }
+        let dimensions = data[0].len();
+        if data.iter().any(|row| row.len() != dimensions) {
+            return Err(Error::InvalidData(
+                "every observation must have the same length".to_string(),
+            ));
+        }
+        let mean = mean_along_axis(data, 0);
+        let centered: Vec<Vec<f32>> = data
+            .iter()
+            .map(|row| row.iter().zip(&mean).map(|(x, m)| x - m).collect())
+            .collect();
+        let mut covariance = vec![vec![0.0; dimensions]; dimensions];
+        for i in 0..dimensions {
+            for j in i..dimensions {
+                let value = centered.iter().map(|row| row[i] * row[j]).sum::<f32>()
+                    / (data.len() - 1) as f32;
+                covariance[i][j] = value;
+                covariance[j][i] = value;
+            }
+        }
+        let (eigenvalues, components) = symmetric_eigen(&covariance);
+        let explained_variance: Vec<f32> = eigenvalues
+            .into_iter()
+            .map(|value| value.max(0.0))
+            .collect();
+        Ok(PCA {
+            mean,
+            total_variance: explained_variance.iter().sum(),
+            components,
+            explained_variance,
+        })
+    }
+
+    pub fn n_components(&self) -> usize {
+        self.components.len()
+    }
+
+    /// The principal axes, one unit vector per row, by decreasing variance.
+    pub fn components(&self) -> &[Vec<f32>] {
+        &self.components
+    }
+
+    /// The variance of the data along each component.
+    pub fn explained_variance(&self) -> &[f32] {
+        &self.explained_variance
+    }
+
+    /// The fraction of the total variance explained by each component.
+    pub fn explained_variance_ratio(&self) -> Vec<f32> {
+        self.explained_variance
+            .iter()
+            .map(|variance| match self.total_variance {
+                0.0 => 0.0,
+                total => variance / total,
+            })
+            .collect()
+    }
+
+    /// Projects the observations on the components.
+    pub fn transform(&self, data: &[Vec<f32>]) -> Vec<Vec<f32>> {
+        data.iter()
+            .map(|row| {
+                let centered: Vec<f32> =
+                    row.iter().zip(&self.mean).map(|(x, m)| x - m).collect();
+                self.components
+                    .iter()
+                    .map(|component| dot(&centered, component))
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+impl PersistedModel for PCA {
+    const MODEL_TYPE: &'static str = "pca";
+}
+
+impl MemoryFootprint for PCA {
+    fn heap_size(&self) -> usize {
+        self.mean.heap_size()
+            + self.components.heap_size()
+            + self.explained_variance.heap_size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data() -> Vec<Vec<f32>> {
+        vec![
+            vec![2.5, 2.4, 0.5],
+            vec![0.5, 0.7, 0.4],
+            vec![2.2, 2.9, 0.6],
+            vec![1.9, 2.2, 0.5],
+            vec![3.1, 3.0, 0.4],
+            vec![2.3, 2.7, 0.6],
+            vec![2.0, 1.6, 0.5],
+            vec![1.0, 1.1, 0.4],
+        ]
+    }
+
+    #[test]
+    fn test_fit() {
+        let pca = PCA::fit(&data(), 2).unwrap();
+        assert_eq!(pca.n_components(), 2);
+        let ratio = pca.explained_variance_ratio();
+        assert!(ratio[0] > 0.9 && ratio[0] > ratio[1]);
+        let projected = pca.transform(&data());
+        assert_eq!(projected.len(), 8);
+        assert!(projected.iter().map(|row| row[0]).sum::<f32>().abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_fit_transform() {
+        let (pca, projected) = PCA::fit_transform(&data(), 1).unwrap();
+        assert_eq!(projected, pca.transform(&data()));
+        assert!(projected.iter().all(|row| row.len() == 1));
+        assert!(PCA::fit_transform(&data(), 0).is_err());
+    }
+
+    #[test]
+    fn test_fit_variance_threshold() {
+        assert_eq!(
+            PCA::fit_variance_threshold(&data(), 0.9)
+                .unwrap()
+                .n_components(),
+            1
+        );
+        assert_eq!(
+            PCA::fit_variance_threshold(&data(), 0.999)
+                .unwrap()
+                .n_components(),
+            3
+        );
+        assert!(PCA::fit_variance_threshold(&data(), 0.0).is_err());
+    }
+
+    #[test]
+    fn test_fit_invalid() {
+        assert!(PCA::fit(&data(), 4).is_err());
+        assert!(PCA::fit(&data()[..1], 1).is_err());
+        assert!(PCA::fit(&[vec![1.0, 2.0], vec![1.0]], 1).is_err());
+    }
+}
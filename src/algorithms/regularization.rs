@@ -0,0 +1,116 @@
+//! # Regularization of the learned parameters
+use serde::{Deserialize, Serialize};
+
+use crate::algorithms::config::ensure;
+use crate::errors::Result;
+
+/// # Regularization
+/// Penalty added to the training loss to keep the learned parameters small.
+/// Iterative trainers apply it after each gradient step with [`Regularization::shrink`],
+/// so L1 sets small parameters exactly to zero instead of making them oscillate
+/// around it.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::algorithms::regularization::Regularization;
+/// let l1 = Regularization::L1 { lambda: 0.5 };
+/// assert_eq!(l1.shrink(0.3, 1.0), 0.0);
+/// assert_eq!(l1.shrink(-2.0, 1.0), -1.5);
+/// assert_eq!(Regularization::L2 { lambda: 1.0 }.shrink(2.0, 1.0), 1.0);
+/// ```
+#[doc = include_str!("../../docs/algorithms/regularization.md")]
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Regularization {
+    #[default]
+    None,
+    /// Lasso, drives the parameters to exactly zero.
+    L1 { lambda: f32 },
+    /// Ridge, keeps every parameter small.
+    L2 { lambda: f32 },
+    /// Both penalties at once.
+    ElasticNet { l1: f32, l2: f32 },
+}
+
+impl Regularization {
+    /// The L1 and L2 strengths.
+    fn strengths(&self) -> (f32, f32) {
+        match *self {
+            Regularization::None => (0.0, 0.0),
+            Regularization::L1 { lambda } => (lambda, 0.0),
+            Regularization::L2 { lambda } => (0.0, lambda),
+            Regularization::ElasticNet { l1, l2 } => (l1, l2),
+        }
+    }
+
+    /// The penalty of a group of parameters.
+    pub fn penalty(&self, values: &[f32]) -> f32 {
+        let (l1, l2) = self.strengths();
+        values
+            .iter()
+            .map(|value| l1 * value.abs() + 0.5 * l2 * value * value)
+            .sum()
+    }
+
+    /// The derivative of the penalty of a parameter, 0 being used as the
+    /// subgradient of L1 at 0.
+    pub fn gradient(&self, value: f32) -> f32 {
+        let (l1, l2) = self.strengths();
+        let sign = if value == 0.0 { 0.0 } else { value.signum() };
+        l1 * sign + l2 * value
+    }
+
+    /// Applies the penalty to a parameter that just took a gradient step.
+    ///
+    /// ## Parameters:
+    /// * `value`: The parameter after the step on the loss.
+    /// * `learning_rate`: The size of the step.
+    ///
+    /// ## Returns:
+    /// * The regularized parameter.
+    pub fn shrink(&self, value: f32, learning_rate: f32) -> f32 {
+        let (l1, l2) = self.strengths();
+        let thresholded = value.signum() * (value.abs() - learning_rate * l1).max(0.0);
+        thresholded / (1.0 + learning_rate * l2)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        let (l1, l2) = self.strengths();
+        ensure(
+            l1 >= 0.0 && l2 >= 0.0 && l1.is_finite() && l2.is_finite(),
+            "regularization",
+            "made of finite non-negative strengths",
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_penalty() {
+        let values = [1.0, -2.0];
+        assert_eq!(Regularization::None.penalty(&values), 0.0);
+        assert_eq!(Regularization::L1 { lambda: 0.1 }.penalty(&values), 0.3);
+        assert_eq!(Regularization::L2 { lambda: 1.0 }.penalty(&values), 2.5);
+        assert_eq!(
+            Regularization::ElasticNet { l1: 1.0, l2: 1.0 }.penalty(&values),
+            5.5
+        );
+    }
+
+    #[test]
+    fn test_shrink() {
+        assert_eq!(Regularization::None.shrink(0.7, 0.1), 0.7);
+        let elastic = Regularization::ElasticNet { l1: 1.0, l2: 1.0 };
+        assert_eq!(elastic.shrink(3.0, 1.0), 1.0);
+        assert_eq!(elastic.shrink(-0.5, 1.0), 0.0);
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(Regularization::L2 { lambda: 0.02 }.validate().is_ok());
+        assert!(Regularization::L1 { lambda: -1.0 }.validate().is_err());
+    }
+}
@@ -0,0 +1,285 @@
+//! Weighted Slope One rating predictor
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::algorithms::config::{ensure, AlgorithmConfig};
+use crate::dataset::{Dataset, RatingScale};
+use crate::envelope::{fields, PersistedModel};
+use crate::errors::Result;
+use crate::memory::MemoryFootprint;
+use crate::recommender::{RankedItems, Recommendation, Recommender};
+use crate::sets::IdSet;
+
+/// # Slope One configuration
+/// Hyperparameters of [`SlopeOne`].
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::algorithms::config::AlgorithmConfig;
+/// use rec_rsys::algorithms::slope_one::SlopeOneConfig;
+/// let config = SlopeOneConfig::from_toml("min_support = 3").unwrap();
+/// assert_eq!(config, SlopeOneConfig::default().set_min_support(3));
+/// assert!(config.set_min_support(0).validate().is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SlopeOneConfig {
+    /// The minimum number of users who rated both items for their deviation to be
+    /// kept.
+    pub min_support: usize,
+}
+
+impl Default for SlopeOneConfig {
+    fn default() -> Self {
+        SlopeOneConfig { min_support: 1 }
+    }
+}
+
+impl SlopeOneConfig {
+    pub fn set_min_support(mut self, min_support: usize) -> Self {
+        self.min_support = min_support;
+        self
+    }
+}
+
+impl AlgorithmConfig for SlopeOneConfig {
+    fn validate(&self) -> Result<()> {
+        ensure(self.min_support > 0, "min_support", "greater than 0")
+    }
+}
+
+/// # Slope One
+/// Precomputes, for every pair of items rated by the same users, how much higher
+/// the first one is rated on average, and predicts the rating of an item from the
+/// ratings the user gave to the other ones shifted by these deviations. Training
+/// and predicting only need averages, which makes it a cheap baseline for
+/// [`ItemKNN`](crate::algorithms::item_knn::ItemKNN) and the factorizations.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::algorithms::slope_one::{SlopeOne, SlopeOneConfig};
+/// use rec_rsys::dataset::{Dataset, Rating};
+/// use rec_rsys::recommender::Recommender;
+/// let dataset = Dataset::new(vec![
+///     Rating::new(1, 10, 5.0), Rating::new(1, 11, 3.0), Rating::new(1, 12, 2.0),
+///     Rating::new(2, 10, 3.0), Rating::new(2, 11, 4.0),
+///     Rating::new(3, 11, 2.0), Rating::new(3, 12, 5.0),
+/// ]);
+/// let mut model = SlopeOne::new(SlopeOneConfig::default());
+/// model.fit(&dataset).unwrap();
+/// assert_eq!(model.deviation(10, 11), Some((0.5, 2)));
+/// // User 3 rated item 11 with 2 and item 12 with 5, weighted by the supports.
+/// assert_eq!(model.predict(3, 10), Some(((2.0 + 0.5) * 2.0 + (5.0 + 3.0)) / 3.0));
+/// assert_eq!(model.recommend(2, 1)[0].item_id, 12);
+/// ```
+#[doc = include_str!("../../docs/algorithms/slope_one.md")]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SlopeOne {
+    config: SlopeOneConfig,
+    /// The mean of `r_j - r_i` over the users who rated both items and their
+    /// number, indexed by `j` then `i`.
+    deviations: HashMap<u32, HashMap<u32, (f32, usize)>>,
+    ratings: HashMap<u32, Vec<(u32, f32)>>,
+    seen: HashMap<u32, IdSet>,
+    /// The scale of the training ratings, the predictions are clipped to it.
+    #[serde(default)]
+    scale: Option<RatingScale>,
+}
+
+impl SlopeOne {
+    pub fn new(config: SlopeOneConfig) -> Self {
+        SlopeOne {
+            config,
+            ..SlopeOne::default()
+        }
+    }
+
+    pub fn config(&self) -> &SlopeOneConfig {
+        &self.config
+    }
+
+    /// How much higher item `j` is rated than item `i` on average, with the number
+    /// of users who rated both.
+    pub fn deviation(&self, j: u32, i: u32) -> Option<(f32, usize)> {
+        self.deviations.get(&j)?.get(&i).copied()
+    }
+
+    /// The number of pairs of items with a deviation.
+    pub fn num_deviations(&self) -> usize {
+        self.deviations.values().map(HashMap::len).sum()
+    }
+
+    /// The weighted Slope One prediction from the ratings of a user, `None` when no
+    /// rated item shares users with the item.
+    fn predict_from(&self, ratings: &[(u32, f32)], item_id: u32) -> Option<f32> {
+        let deviations = self.deviations.get(&item_id)?;
+        let (total, support) = ratings
+            .iter()
+            .filter_map(|(i, rating)| {
+                let (deviation, support) = deviations.get(i)?;
+                Some(((deviation + rating) * *support as f32, *support))
+            })
+            .fold((0.0, 0), |(total, count), (value, support)| {
+                (total + value, count + support)
+            });
+        match support {
+            0 => None,
+            _ => Some(total / support as f32),
+        }
+    }
+}
+
+impl Recommender for SlopeOne {
+    fn fit(&mut self, dataset: &Dataset) -> Result<()> {
+        self.config.validate()?;
+        self.ratings = dataset.user_ratings();
+        self.ratings
+            .values_mut()
+            .for_each(|ratings| ratings.sort_unstable_by_key(|r| r.0));
+        self.seen = dataset.user_item_sets();
+        self.scale = dataset.scale;
+        // Summed user by user in increasing id order, so the floating point sums do
+        // not depend on the order of the map.
+        let mut ids: Vec<u32> = self.ratings.keys().copied().collect();
+        ids.sort_unstable();
+        let mut sums: HashMap<u32, HashMap<u32, (f64, usize)>> = HashMap::new();
+        for ratings in ids.iter().map(|id| &self.ratings[id]) {
+            for (j, rating_j) in ratings {
+                let row = sums.entry(*j).or_default();
+                for (i, rating_i) in ratings.iter().filter(|(i, _)| i != j) {
+                    let (sum, count) = row.entry(*i).or_default();
+                    *sum += (rating_j - rating_i) as f64;
+                    *count += 1;
+                }
+            }
+        }
+        let min_support = self.config.min_support;
+        self.deviations = sums
+            .into_iter()
+            .map(|(j, row)| {
+                let row = row
+                    .into_iter()
+                    .filter(|(_, (_, count))| *count >= min_support)
+                    .map(|(i, (sum, count))| (i, ((sum / count as f64) as f32, count)))
+                    .collect();
+                (j, row)
+            })
+            .collect();
+        Ok(())
+    }
+
+    fn recommend(&self, user_id: u32, num_items: usize) -> Vec<Recommendation> {
+        self.recommend_iter(user_id).take(num_items).collect()
+    }
+
+    fn recommend_iter(&self, user_id: u32) -> RankedItems {
+        let ratings = match self.ratings.get(&user_id) {
+            Some(ratings) => ratings,
+            None => return RankedItems::default(),
+        };
+        let seen = self.seen.get(&user_id);
+        let scores: HashMap<u32, f32> = self
+            .deviations
+            .keys()
+            .filter(|item_id| !seen.is_some_and(|seen| seen.contains(**item_id)))
+            .filter_map(|item_id| Some((*item_id, self.predict_from(ratings, *item_id)?)))
+            .collect();
+        RankedItems::new(scores, seen)
+    }
+
+    fn predict(&self, user_id: u32, item_id: u32) -> Option<f32> {
+        let prediction = self.predict_from(self.ratings.get(&user_id)?, item_id)?;
+        Some(
+            self.scale
+                .map_or(prediction, |scale| scale.clip(prediction)),
+        )
+    }
+}
+
+impl PersistedModel for SlopeOne {
+    const MODEL_TYPE: &'static str = "slope_one";
+
+    fn hyperparameters(&self) -> BTreeMap<String, Value> {
+        fields(&self.config)
+    }
+}
+
+impl MemoryFootprint for SlopeOne {
+    fn heap_size(&self) -> usize {
+        self.deviations.heap_size() + self.ratings.heap_size() + self.seen.heap_size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dataset::Rating;
+
+    fn dataset() -> Dataset {
+        Dataset::new(vec![
+            Rating::new(1, 10, 5.0),
+            Rating::new(1, 11, 3.0),
+            Rating::new(1, 12, 2.0),
+            Rating::new(2, 10, 3.0),
+            Rating::new(2, 11, 4.0),
+            Rating::new(3, 11, 2.0),
+            Rating::new(3, 12, 5.0),
+            Rating::new(4, 13, 1.0),
+        ])
+    }
+
+    #[test]
+    fn test_deviations() {
+        let mut model = SlopeOne::new(SlopeOneConfig::default());
+        model.fit(&dataset()).unwrap();
+        assert_eq!(model.deviation(10, 11), Some((0.5, 2)));
+        assert_eq!(model.deviation(11, 10), Some((-0.5, 2)));
+        assert_eq!(model.deviation(12, 11), Some((1.0, 2)));
+        assert_eq!(model.deviation(13, 10), None);
+        assert_eq!(model.num_deviations(), 6);
+    }
+
+    #[test]
+    fn test_weighted_prediction() {
+        let mut model = SlopeOne::new(SlopeOneConfig::default());
+        model.fit(&dataset()).unwrap();
+        // User 2 rated item 11 with 4 and item 10 with 3, the deviations of item 12
+        // from them are 1 over two users and -3 over one.
+        let expected = ((4.0 + 1.0) * 2.0 + (3.0 - 3.0) * 1.0) / 3.0;
+        assert_eq!(model.predict(2, 12), Some(expected));
+        // Item 13 shares no user with the others.
+        assert_eq!(model.predict(1, 13), None);
+        assert_eq!(model.predict(99, 10), None);
+        assert!(model.recommend(4, 10).is_empty());
+        assert!(model.recommend(1, 10).is_empty());
+    }
+
+    #[test]
+    fn test_predictions_are_clipped_to_the_scale() {
+        let ratings = vec![
+            Rating::new(1, 10, 5.0),
+            Rating::new(1, 11, 1.0),
+            Rating::new(2, 11, 5.0),
+        ];
+        let mut model = SlopeOne::new(SlopeOneConfig::default());
+        model.fit(&Dataset::new(ratings.clone())).unwrap();
+        assert_eq!(model.predict(2, 10), Some(9.0));
+        let dataset = Dataset::new(ratings)
+            .with_scale(RatingScale::default())
+            .unwrap();
+        model.fit(&dataset).unwrap();
+        assert_eq!(model.predict(2, 10), Some(5.0));
+    }
+
+    #[test]
+    fn test_min_support() {
+        let mut model = SlopeOne::new(SlopeOneConfig::default().set_min_support(2));
+        model.fit(&dataset()).unwrap();
+        assert_eq!(model.num_deviations(), 4);
+        assert!(model.deviation(10, 12).is_none());
+        let mut model = SlopeOne::new(SlopeOneConfig::default().set_min_support(0));
+        assert!(model.fit(&dataset()).is_err());
+    }
+}
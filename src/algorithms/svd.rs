@@ -1,22 +1,151 @@
+//! Singular value decomposition
+use crate::errors::{Error, Result};
+use crate::matrix::one_sided_jacobi;
+
+/// The `(U, S, Vt)` factors of a singular value decomposition.
+pub type Decomposition = (Vec<Vec<f32>>, Vec<f32>, Vec<Vec<f32>>);
+
 /// # Singular Value Decomposition
 /// The Singular Value Decomposition (SVD) is a matrix factorization technique that decomposes a matrix into three matrices: U, Σ, and V.
+/// It is computed with the one-sided Jacobi rotations of
+/// [`singular_values`](crate::matrix::singular_values), which also give the
+/// singular vectors.
 ///
 /// ## Parameters:
-/// * `matrix`: The input matrix to be decomposed.
+/// * `matrix`: The input matrix to be decomposed, of `m` rows and `n` columns.
 ///
 /// ## Returns:
-/// * A tuple `(U, S, V)` containing the decomposed matrices.
+/// * A tuple `(U, S, Vt)` of the thin decomposition, with `k = min(m, n)`: `U` has
+///   `m` rows of `k` values, `S` the `k` singular values in decreasing order, the
+///   diagonal of Σ, and `Vt` `k` rows of `n` values. The columns of `U` and the
+///   rows of `Vt` are orthonormal. An error if the rows have different lengths.
 ///
 /// ## Examples:
 /// ```
+/// use rec_rsys::algorithms::svd::svd;
+/// let (u, s, vt) = svd(&[vec![3.0, 0.0], vec![0.0, -2.0], vec![0.0, 0.0]]).unwrap();
+/// assert!((s[0] - 3.0).abs() < 1e-6 && (s[1] - 2.0).abs() < 1e-6);
+/// // A = U Σ Vt
+/// let a_11 = (0..2).map(|k| u[1][k] * s[k] * vt[k][1]).sum::<f32>();
+/// assert!((a_11 + 2.0).abs() < 1e-6);
 /// ```
 ///
-#[doc = include_str!("../docs/algorithms/svd.md")]
-pub fn svd(matrix: Vec<Vec<f32>>) {}
+#[doc = include_str!("../../docs/algorithms/svd.md")]
+pub fn svd(matrix: &[Vec<f32>]) -> Result<Decomposition> {
+    let columns = matrix.first().map_or(0, Vec::len);
+    if matrix.iter().any(|row| row.len() != columns) {
+        return Err(Error::InvalidData(
+            "the rows of the matrix must have the same length".to_string(),
+        ));
+    }
+    if matrix.is_empty() || columns == 0 {
+        return Ok((vec![Vec::new(); matrix.len()], Vec::new(), Vec::new()));
+    }
+    // The rotations orthogonalize the columns, there must be at most as many
+    // columns as rows: a wide matrix is decomposed through its transpose.
+    if matrix.len() < columns {
+        let transposed: Vec<Vec<f32>> = (0..columns)
+            .map(|j| matrix.iter().map(|row| row[j]).collect())
+            .collect();
+        let (v, s, ut) = svd(&transposed)?;
+        let u = (0..ut[0].len())
+            .map(|i| ut.iter().map(|row| row[i]).collect())
+            .collect();
+        let vt = (0..v[0].len())
+            .map(|k| v.iter().map(|row| row[k]).collect())
+            .collect();
+        return Ok((u, s, vt));
+    }
+    let (u, s, v) = one_sided_jacobi(matrix);
+    let order = {
+        let mut order: Vec<usize> = (0..s.len()).collect();
+        order.sort_by(|&a, &b| s[b].total_cmp(&s[a]));
+        order
+    };
+    let u = (0..matrix.len())
+        .map(|i| order.iter().map(|&k| u[k][i] as f32).collect())
+        .collect();
+    let vt = order
+        .iter()
+        .map(|&k| v[k].iter().map(|&value| value as f32).collect())
+        .collect();
+    Ok((u, order.iter().map(|&k| s[k] as f32).collect(), vt))
+}
+
+/// # Low-rank approximation
+/// The closest matrix of rank `rank` to the given one, in the Frobenius norm,
+/// keeping the largest singular values of its SVD (Eckart–Young theorem). On a
+/// rating matrix, it fills the blanks from the few tastes that explain most of it.
+///
+/// ## Parameters:
+/// * `matrix`: The matrix.
+/// * `rank`: The number of singular values kept, at most `min(m, n)` are.
+///
+/// ## Returns:
+/// * The approximation, of the shape of `matrix`.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::algorithms::svd::low_rank_approximation;
+/// let ratings = vec![vec![5.0, 5.0, 0.0], vec![4.0, 4.0, 0.0], vec![0.0, 0.0, 3.0]];
+/// let approximation = low_rank_approximation(&ratings, 1).unwrap();
+/// assert!((approximation[1][0] - 4.0).abs() < 1e-5);
+/// assert!(approximation[2][2].abs() < 1e-5);
+/// ```
+pub fn low_rank_approximation(matrix: &[Vec<f32>], rank: usize) -> Result<Vec<Vec<f32>>> {
+    let (u, s, vt) = svd(matrix)?;
+    let rank = rank.min(s.len());
+    let columns = matrix.first().map_or(0, Vec::len);
+    Ok(u.iter()
+        .map(|u_row| {
+            (0..columns)
+                .map(|j| (0..rank).map(|k| u_row[k] * s[k] * vt[k][j]).sum())
+                .collect()
+        })
+        .collect())
+}
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::matrix::singular_values;
+
+    fn reconstruct(u: &[Vec<f32>], s: &[f32], vt: &[Vec<f32>]) -> Vec<Vec<f32>> {
+        u.iter()
+            .map(|u_row| {
+                (0..vt[0].len())
+                    .map(|j| (0..s.len()).map(|k| u_row[k] * s[k] * vt[k][j]).sum())
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Checks `A = U Σ Vt` and that the columns of U and the rows of Vt are
+    /// orthonormal.
+    fn assert_decomposition(matrix: &[Vec<f32>]) -> Vec<f32> {
+        let (u, s, vt) = svd(matrix).unwrap();
+        let k = matrix.len().min(matrix[0].len());
+        assert_eq!(
+            (u.len(), u[0].len(), s.len(), vt.len()),
+            (matrix.len(), k, k, k)
+        );
+        assert!(s.windows(2).all(|pair| pair[0] >= pair[1]));
+        for (row, expected) in reconstruct(&u, &s, &vt).iter().zip(matrix) {
+            for (value, expected) in row.iter().zip(expected) {
+                assert!((value - expected).abs() < 1e-4, "{} != {}", value, expected);
+            }
+        }
+        for a in 0..k {
+            for b in 0..k {
+                let expected = if a == b { 1.0 } else { 0.0 };
+                let uu: f32 = u.iter().map(|row| row[a] * row[b]).sum();
+                let vv: f32 = vt[a].iter().zip(&vt[b]).map(|(x, y)| x * y).sum();
+                assert!((uu - expected).abs() < 1e-5, "U {} {}: {}", a, b, uu);
+                assert!((vv - expected).abs() < 1e-5, "V {} {}: {}", a, b, vv);
+            }
+        }
+        s
+    }
 
     #[test]
     fn test_svd() {
@@ -25,8 +154,53 @@ mod tests {
             vec![4.0, 5.0, 6.0],
             vec![7.0, 8.0, 9.0],
         ];
-        assert_eq!(
-     svd(matrix),
-    ,);
+        let s = assert_decomposition(&matrix);
+        assert!((s[0] - 16.848_103).abs() < 1e-4);
+        assert!((s[1] - 1.068_369_7).abs() < 1e-5);
+        assert!(s[2].abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_svd_shapes() {
+        let tall = vec![
+            vec![2.0, 0.0],
+            vec![0.0, 1.0],
+            vec![1.0, 1.0],
+            vec![0.0, 3.0],
+        ];
+        assert_eq!(assert_decomposition(&tall), singular_values(&tall));
+        let wide = vec![vec![3.0, 2.0, 2.0], vec![2.0, 3.0, -2.0]];
+        let s = assert_decomposition(&wide);
+        assert!((s[0] - 5.0).abs() < 1e-5 && (s[1] - 3.0).abs() < 1e-5);
+        assert_eq!(s, singular_values(&wide));
+        let rank_one = vec![vec![1.0, 2.0], vec![2.0, 4.0], vec![3.0, 6.0]];
+        let s = assert_decomposition(&rank_one);
+        assert!(s[1].abs() < 1e-6);
+        assert_decomposition(&[vec![0.0, 0.0], vec![0.0, 0.0]]);
+        assert_eq!(svd(&[]).unwrap().1, Vec::<f32>::new());
+        assert!(svd(&[vec![1.0, 2.0], vec![1.0]]).is_err());
+    }
+
+    #[test]
+    fn test_low_rank_approximation() {
+        let matrix = vec![
+            vec![5.0, 4.0, 0.0, 1.0],
+            vec![4.0, 5.0, 1.0, 0.0],
+            vec![0.0, 1.0, 5.0, 4.0],
+        ];
+        let full = low_rank_approximation(&matrix, 10).unwrap();
+        let error = |approximation: &[Vec<f32>]| -> f32 {
+            approximation
+                .iter()
+                .flatten()
+                .zip(matrix.iter().flatten())
+                .map(|(a, b)| (a - b).powi(2))
+                .sum()
+        };
+        assert!(error(&full) < 1e-8);
+        let (_, s, _) = svd(&matrix).unwrap();
+        // The error of the rank 1 approximation is the energy of the dropped values.
+        let rank_one = low_rank_approximation(&matrix, 1).unwrap();
+        assert!((error(&rank_one) - (s[1] * s[1] + s[2] * s[2])).abs() < 1e-3);
     }
 }
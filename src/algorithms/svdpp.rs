@@ -0,0 +1,590 @@
+//! SVD++, matrix factorization with the implicit feedback of the rated items
+use std::collections::{BTreeMap, HashMap};
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::algorithms::config::{ensure, AlgorithmConfig};
+use crate::algorithms::history::TrainingHistory;
+use crate::algorithms::mf::{index, random_factors};
+use crate::dataset::{Dataset, RatingScale};
+use crate::envelope::{fields, PersistedModel};
+use crate::errors::{Error, Result};
+use crate::factors::{FactorMatrix, Precision};
+use crate::memory::MemoryFootprint;
+use crate::models::Item;
+use crate::recommender::{RankedItems, Recommendation, Recommender};
+use crate::sets::IdSet;
+use crate::utils::dot;
+
+/// # SVD++ configuration
+/// Hyperparameters of [`SVDpp`].
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::algorithms::config::AlgorithmConfig;
+/// use rec_rsys::algorithms::svdpp::SVDppConfig;
+/// let config = SVDppConfig::from_toml("num_factors = 8\nlearning_rate = 0.01").unwrap();
+/// assert_eq!(config, SVDppConfig::default().set_num_factors(8).set_learning_rate(0.01));
+/// assert!(config.set_regularization(-0.1).validate().is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SVDppConfig {
+    /// Number of latent dimensions.
+    pub num_factors: usize,
+    /// Number of passes over the ratings.
+    pub num_epochs: usize,
+    pub learning_rate: f32,
+    /// L2 penalty on the biases and on the explicit and implicit factors.
+    pub regularization: f32,
+    /// Standard deviation of the normal distribution the factors start from.
+    pub init_std: f32,
+    /// Seed of the initialization and of the order of the users and ratings.
+    pub seed: u64,
+    /// Training stops with an error when the loss exceeds the one of the first
+    /// epoch this many times.
+    pub max_loss_growth: f64,
+}
+
+impl Default for SVDppConfig {
+    fn default() -> Self {
+        SVDppConfig {
+            num_factors: 20,
+            num_epochs: 20,
+            learning_rate: 0.007,
+            regularization: 0.02,
+            init_std: 0.1,
+            seed: 42,
+            max_loss_growth: 100.0,
+        }
+    }
+}
+
+impl SVDppConfig {
+    pub fn set_num_factors(mut self, num_factors: usize) -> Self {
+        self.num_factors = num_factors;
+        self
+    }
+    pub fn set_num_epochs(mut self, num_epochs: usize) -> Self {
+        self.num_epochs = num_epochs;
+        self
+    }
+    pub fn set_learning_rate(mut self, learning_rate: f32) -> Self {
+        self.learning_rate = learning_rate;
+        self
+    }
+    pub fn set_regularization(mut self, regularization: f32) -> Self {
+        self.regularization = regularization;
+        self
+    }
+    pub fn set_init_std(mut self, init_std: f32) -> Self {
+        self.init_std = init_std;
+        self
+    }
+    pub fn set_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+    pub fn set_max_loss_growth(mut self, max_loss_growth: f64) -> Self {
+        self.max_loss_growth = max_loss_growth;
+        self
+    }
+}
+
+impl AlgorithmConfig for SVDppConfig {
+    fn validate(&self) -> Result<()> {
+        ensure(self.num_factors > 0, "num_factors", "greater than 0")?;
+        ensure(self.num_epochs > 0, "num_epochs", "greater than 0")?;
+        ensure(
+            self.learning_rate > 0.0 && self.learning_rate.is_finite(),
+            "learning_rate",
+            "a positive number",
+        )?;
+        ensure(
+            self.regularization >= 0.0 && self.regularization.is_finite(),
+            "regularization",
+            "a non-negative number",
+        )?;
+        ensure(
+            self.init_std >= 0.0 && self.init_std.is_finite(),
+            "init_std",
+            "a non-negative number",
+        )
+    }
+}
+
+/// # SVD++
+/// Extends the biased [`MatrixFactorization`](crate::algorithms::mf::MatrixFactorization)
+/// with implicit feedback: a user is represented by their own factors plus the
+/// implicit factors of every item they interacted with, so what a user chose to
+/// rate tells about them as much as the ratings they gave.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::algorithms::svdpp::{SVDpp, SVDppConfig};
+/// use rec_rsys::dataset::{Dataset, Rating};
+/// use rec_rsys::recommender::Recommender;
+/// let dataset = Dataset::new(vec![
+///     Rating::new(1, 10, 5.0), Rating::new(1, 11, 1.0),
+///     Rating::new(2, 10, 4.0), Rating::new(2, 11, 2.0), Rating::new(2, 12, 5.0),
+/// ]);
+/// let mut model = SVDpp::new(SVDppConfig::default().set_num_factors(4));
+/// model.fit(&dataset).unwrap();
+/// assert!(model.predict(1, 10).unwrap() > model.predict(1, 11).unwrap());
+/// assert_eq!(model.recommend(1, 1)[0].item_id, 12);
+/// assert_eq!(model.user_vector(1).unwrap().values.len(), 4);
+/// ```
+#[doc = include_str!("../../docs/algorithms/svdpp.md")]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SVDpp {
+    config: SVDppConfig,
+    global_mean: f32,
+    users: HashMap<u32, usize>,
+    items: HashMap<u32, usize>,
+    item_ids: Vec<u32>,
+    user_biases: Vec<f32>,
+    item_biases: Vec<f32>,
+    user_factors: FactorMatrix,
+    item_factors: FactorMatrix,
+    /// The implicit factors `y` of the items the users interacted with.
+    implicit_factors: FactorMatrix,
+    implicit_items: HashMap<u32, usize>,
+    /// The rows of the implicit factors of the items of every user.
+    interactions: Vec<Vec<usize>>,
+    /// The factors of the users with their implicit feedback added, used to predict.
+    user_vectors: FactorMatrix,
+    /// The precision the factors are stored with once trained.
+    #[serde(default)]
+    precision: Precision,
+    seen: HashMap<u32, IdSet>,
+    /// The scale of the training ratings, the predictions are clipped to it.
+    #[serde(default)]
+    scale: Option<RatingScale>,
+    history: TrainingHistory,
+}
+
+impl SVDpp {
+    pub fn new(config: SVDppConfig) -> Self {
+        SVDpp {
+            config,
+            global_mean: 0.0,
+            users: HashMap::new(),
+            items: HashMap::new(),
+            item_ids: Vec::new(),
+            user_biases: Vec::new(),
+            item_biases: Vec::new(),
+            user_factors: FactorMatrix::new(0, 0),
+            item_factors: FactorMatrix::new(0, 0),
+            implicit_factors: FactorMatrix::new(0, 0),
+            implicit_items: HashMap::new(),
+            interactions: Vec::new(),
+            user_vectors: FactorMatrix::new(0, 0),
+            precision: Precision::F32,
+            seen: HashMap::new(),
+            scale: None,
+            history: TrainingHistory::default(),
+        }
+    }
+
+    pub fn config(&self) -> &SVDppConfig {
+        &self.config
+    }
+
+    /// The mean of the training ratings, the base of every prediction.
+    pub fn global_mean(&self) -> f32 {
+        self.global_mean
+    }
+
+    /// # Factor precision
+    /// Stores the learned factors with another precision, e.g. [`Precision::F16`]
+    /// to halve their memory. Predictions are computed from the stored values, the
+    /// error they introduce is measured by [`FactorMatrix::precision_diff`].
+    ///
+    /// Training always runs in `f32` and stores the factors of the next fits with
+    /// this precision too. Going back to `f32` does not restore the rounded values.
+    pub fn set_factor_precision(&mut self, precision: Precision) {
+        self.precision = precision;
+        self.user_factors = self.user_factors.to_precision(precision);
+        self.item_factors = self.item_factors.to_precision(precision);
+        self.implicit_factors = self.implicit_factors.to_precision(precision);
+        self.user_vectors = self.user_vectors.to_precision(precision);
+    }
+
+    /// The losses of the last training.
+    pub fn history(&self) -> &TrainingHistory {
+        &self.history
+    }
+
+    /// The representation of a user, their factors plus the implicit factors of
+    /// the items they interacted with, e.g. to find similar users with
+    /// [`KNN`](crate::algorithms::knn::KNN).
+    pub fn user_vector(&self, user_id: u32) -> Option<Item> {
+        let user = self.users.get(&user_id)?;
+        Some(Item::new(user_id, self.user_vectors.row(*user), None))
+    }
+
+    /// The latent factors of an item.
+    pub fn item_vector(&self, item_id: u32) -> Option<Item> {
+        let item = self.items.get(&item_id)?;
+        Some(Item::new(item_id, self.item_factors.row(*item), None))
+    }
+
+    /// Trains like [`Recommender::fit`], the implicit feedback of every user being
+    /// the items they rated plus the ones they interacted with in `implicit`, e.g.
+    /// clicks or views, whatever their value.
+    pub fn fit_with_implicit(
+        &mut self,
+        dataset: &Dataset,
+        implicit: Option<&Dataset>,
+    ) -> Result<()> {
+        self.config.validate()?;
+        if dataset.is_empty() {
+            return Err(Error::InvalidData(
+                "cannot fit on an empty dataset".to_string(),
+            ));
+        }
+        let mut rng = StdRng::seed_from_u64(self.config.seed);
+        self.users = index(dataset.users().into_iter());
+        self.item_ids = dataset.items().into_iter().collect();
+        self.items = index(self.item_ids.iter().copied());
+        let mut implicit_ids = dataset.items();
+        if let Some(implicit) = implicit {
+            implicit_ids.extend(implicit.items());
+        }
+        self.implicit_items = index(implicit_ids.into_iter());
+        self.global_mean =
+            dataset.ratings.iter().map(|r| r.rating).sum::<f32>() / dataset.len() as f32;
+        self.user_biases = vec![0.0; self.users.len()];
+        self.item_biases = vec![0.0; self.items.len()];
+        let (factors, std) = (self.config.num_factors, self.config.init_std);
+        self.user_factors = random_factors(self.users.len(), factors, std, &mut rng);
+        self.item_factors = random_factors(self.items.len(), factors, std, &mut rng);
+        self.implicit_factors =
+            random_factors(self.implicit_items.len(), factors, std, &mut rng);
+        self.seen = dataset.user_item_sets();
+        self.scale = dataset.scale;
+        self.history = TrainingHistory::new(self.config.max_loss_growth);
+
+        let mut interactions: Vec<IdSet> = vec![IdSet::default(); self.users.len()];
+        let mut ratings: Vec<Vec<(usize, f32)>> = vec![Vec::new(); self.users.len()];
+        dataset.ratings.iter().for_each(|r| {
+            let user = self.users[&r.user_id];
+            ratings[user].push((self.items[&r.item_id], r.rating));
+            interactions[user].insert(r.item_id);
+        });
+        implicit.into_iter().flat_map(|d| &d.ratings).for_each(|r| {
+            if let Some(user) = self.users.get(&r.user_id) {
+                interactions[*user].insert(r.item_id);
+            }
+        });
+        self.interactions = interactions
+            .iter()
+            .map(|items| items.iter().map(|id| self.implicit_items[&id]).collect())
+            .collect();
+
+        let mut users: Vec<usize> = (0..self.users.len()).collect();
+        for _ in 0..self.config.num_epochs {
+            users.shuffle(&mut rng);
+            for &user in &users {
+                ratings[user].shuffle(&mut rng);
+                self.update_user(user, &ratings[user]);
+            }
+            self.user_vectors = self.compute_user_vectors();
+            let loss =
+                self.squared_error(&ratings) + self.penalty() / dataset.len() as f64;
+            self.history.record(loss, None)?;
+        }
+        self.set_factor_precision(self.precision);
+        Ok(())
+    }
+
+    /// The weight of the implicit factors of a user, `|N(u)|^-1/2`.
+    fn implicit_weight(&self, user: usize) -> f32 {
+        match self.interactions[user].len() {
+            0 => 0.0,
+            count => 1.0 / (count as f32).sqrt(),
+        }
+    }
+
+    /// The weighted sum of the implicit factors of the items of a user.
+    fn implicit_sum(&self, user: usize) -> Vec<f32> {
+        let weight = self.implicit_weight(user);
+        let mut sum = vec![0.0; self.config.num_factors];
+        self.interactions[user].iter().for_each(|row| {
+            self.implicit_factors
+                .row(*row)
+                .iter()
+                .zip(sum.iter_mut())
+                .for_each(|(y, total)| *total += weight * y)
+        });
+        sum
+    }
+
+    /// One SGD step per rating of a user. The implicit sum is kept for all the
+    /// ratings of the user and the implicit factors, shared by all of them, are
+    /// updated once with the accumulated gradient.
+    fn update_user(&mut self, user: usize, ratings: &[(usize, f32)]) {
+        let (rate, lambda) = (self.config.learning_rate, self.config.regularization);
+        let weight = self.implicit_weight(user);
+        let implicit = self.implicit_sum(user);
+        let mut implicit_gradient = vec![0.0; self.config.num_factors];
+        for &(item, rating) in ratings {
+            let p = self.user_factors.row(user);
+            let q = self.item_factors.row(item);
+            let z: Vec<f32> = p.iter().zip(&implicit).map(|(p, y)| p + y).collect();
+            let error = rating
+                - (self.global_mean
+                    + self.user_biases[user]
+                    + self.item_biases[item]
+                    + dot(&z, &q));
+            self.user_biases[user] += rate * (error - lambda * self.user_biases[user]);
+            self.item_biases[item] += rate * (error - lambda * self.item_biases[item]);
+            let user_row = self.user_factors.row_mut(user).expect("trained in f32");
+            user_row
+                .iter_mut()
+                .zip(&q)
+                .for_each(|(p, q)| *p += rate * (error * q - lambda * *p));
+            let item_row = self.item_factors.row_mut(item).expect("trained in f32");
+            item_row
+                .iter_mut()
+                .zip(&z)
+                .for_each(|(q, z)| *q += rate * (error * z - lambda * *q));
+            implicit_gradient
+                .iter_mut()
+                .zip(&q)
+                .for_each(|(gradient, q)| *gradient += error * weight * q);
+        }
+        let decay = lambda * ratings.len() as f32;
+        for row in self.interactions[user].clone() {
+            let y = self.implicit_factors.row_mut(row).expect("trained in f32");
+            y.iter_mut()
+                .zip(&implicit_gradient)
+                .for_each(|(y, gradient)| *y += rate * (gradient - decay * *y));
+        }
+    }
+
+    fn compute_user_vectors(&self) -> FactorMatrix {
+        let rows: Vec<Vec<f32>> = (0..self.users.len())
+            .map(|user| {
+                let implicit = self.implicit_sum(user);
+                self.user_factors
+                    .row(user)
+                    .iter()
+                    .zip(implicit)
+                    .map(|(p, y)| p + y)
+                    .collect()
+            })
+            .collect();
+        match rows.is_empty() {
+            true => FactorMatrix::new(0, self.config.num_factors),
+            false => FactorMatrix::from_rows(&rows).expect("rows of the same length"),
+        }
+    }
+
+    /// Mean squared error on the ratings of every user.
+    fn squared_error(&self, ratings: &[Vec<(usize, f32)>]) -> f64 {
+        let (total, count) = ratings
+            .iter()
+            .enumerate()
+            .flat_map(|(user, ratings)| ratings.iter().map(move |r| (user, r)))
+            .fold((0.0, 0), |(total, count), (user, &(item, rating))| {
+                let error = (rating - self.score(user, item)) as f64;
+                (total + error * error, count + 1)
+            });
+        total / count.max(1) as f64
+    }
+
+    /// L2 penalty of every learned parameter.
+    fn penalty(&self) -> f64 {
+        let squares: f32 = [
+            &self.user_biases[..],
+            &self.item_biases[..],
+            &self.user_factors.to_rows().concat(),
+            &self.item_factors.to_rows().concat(),
+            &self.implicit_factors.to_rows().concat(),
+        ]
+        .iter()
+        .flat_map(|values| values.iter())
+        .map(|value| value * value)
+        .sum();
+        0.5 * self.config.regularization as f64 * squares as f64
+    }
+
+    /// Rating predicted from the rows of a user and an item.
+    fn score(&self, user: usize, item: usize) -> f32 {
+        self.global_mean
+            + self.user_biases[user]
+            + self.item_biases[item]
+            + self.user_vectors.dot(user, &self.item_factors.row(item))
+    }
+}
+
+impl Recommender for SVDpp {
+    fn fit(&mut self, dataset: &Dataset) -> Result<()> {
+        self.fit_with_implicit(dataset, None)
+    }
+
+    fn recommend(&self, user_id: u32, num_items: usize) -> Vec<Recommendation> {
+        self.recommend_iter(user_id).take(num_items).collect()
+    }
+
+    fn recommend_iter(&self, user_id: u32) -> RankedItems {
+        let user = match self.users.get(&user_id) {
+            Some(user) => *user,
+            None => return RankedItems::default(),
+        };
+        let scores: HashMap<u32, f32> = self
+            .item_ids
+            .iter()
+            .enumerate()
+            .map(|(item, item_id)| (*item_id, self.score(user, item)))
+            .collect();
+        RankedItems::new(scores, self.seen.get(&user_id))
+    }
+
+    fn predict(&self, user_id: u32, item_id: u32) -> Option<f32> {
+        let user = self.users.get(&user_id)?;
+        let item = self.items.get(&item_id)?;
+        let score = self.score(*user, *item);
+        Some(self.scale.map_or(score, |scale| scale.clip(score)))
+    }
+}
+
+impl PersistedModel for SVDpp {
+    const MODEL_TYPE: &'static str = "svdpp";
+
+    fn hyperparameters(&self) -> BTreeMap<String, Value> {
+        fields(&self.config)
+    }
+}
+
+impl MemoryFootprint for SVDpp {
+    fn heap_size(&self) -> usize {
+        self.users.heap_size()
+            + self.items.heap_size()
+            + self.item_ids.heap_size()
+            + self.user_biases.heap_size()
+            + self.item_biases.heap_size()
+            + self.user_factors.heap_size()
+            + self.item_factors.heap_size()
+            + self.implicit_factors.heap_size()
+            + self.implicit_items.heap_size()
+            + self.interactions.heap_size()
+            + self.user_vectors.heap_size()
+            + self.seen.heap_size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accuracy::rmse;
+    use crate::dataset::Rating;
+    use crate::factors::score_diff;
+
+    /// Every user rates a different subset of the items, so the implicit feedback
+    /// tells the users apart.
+    fn dataset() -> Dataset {
+        Dataset::new(
+            (1..=6)
+                .flat_map(|user| {
+                    (10..19)
+                        .filter(move |item| (user * item) % 4 != 1)
+                        .map(move |item| {
+                            let rating = if (user + item) % 2 == 0 { 5.0 } else { 1.0 };
+                            Rating::new(user, item, rating)
+                        })
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_fit_learns_the_ratings() {
+        let config = SVDppConfig::default()
+            .set_num_factors(4)
+            .set_num_epochs(200)
+            .set_learning_rate(0.05);
+        let mut model = SVDpp::new(config);
+        model.fit(&dataset()).unwrap();
+        let (predicted, actual): (Vec<f32>, Vec<f32>) = dataset()
+            .ratings
+            .iter()
+            .map(|r| (model.predict(r.user_id, r.item_id).unwrap(), r.rating))
+            .unzip();
+        assert!(rmse(&predicted, &actual) < 0.5);
+        let losses = model.history().train_losses();
+        assert!(losses[199] < losses[0]);
+        assert!(model.predict(1, 99).is_none());
+        assert!(model.item_vector(10).is_some());
+        let sizes: Vec<usize> = model.interactions.iter().map(Vec::len).collect();
+        assert!(sizes.iter().any(|size| *size != sizes[0]));
+    }
+
+    #[test]
+    fn test_implicit_feedback_moves_the_user() {
+        let implicit =
+            Dataset::new(vec![Rating::new(1, 20, 1.0), Rating::new(99, 21, 1.0)]);
+        let mut with = SVDpp::new(SVDppConfig::default());
+        with.fit_with_implicit(&dataset(), Some(&implicit)).unwrap();
+        let mut without = SVDpp::new(SVDppConfig::default());
+        without.fit(&dataset()).unwrap();
+        let vector = |model: &SVDpp, user_id| model.user_vector(user_id).unwrap().values;
+        assert_ne!(vector(&with, 1), vector(&without, 1));
+        // Implicit items are not recommended, nor are the users only seen there.
+        assert!(with.recommend(1, 10).iter().all(|r| r.item_id < 20));
+        assert!(with.user_vector(99).is_none());
+    }
+
+    #[test]
+    fn test_factor_precision() {
+        let mut model = SVDpp::new(SVDppConfig::default().set_num_factors(8));
+        model.fit(&dataset()).unwrap();
+        let (users, items) = (model.user_vectors.clone(), model.item_factors.clone());
+        let predictions: Vec<f32> = dataset()
+            .ratings
+            .iter()
+            .map(|r| model.predict(r.user_id, r.item_id).unwrap())
+            .collect();
+        let size = model.heap_size();
+        model.set_factor_precision(Precision::F16);
+        assert!(model.heap_size() < size);
+        let bound = score_diff(&users, &items, Precision::F16)
+            .unwrap()
+            .max_abs_error;
+        dataset()
+            .ratings
+            .iter()
+            .zip(predictions)
+            .for_each(|(r, prediction)| {
+                let compressed = model.predict(r.user_id, r.item_id).unwrap();
+                assert!((compressed - prediction).abs() <= bound + 1e-5);
+            });
+        model.fit(&dataset()).unwrap();
+        assert_eq!(model.implicit_factors.precision(), Precision::F16);
+    }
+
+    #[test]
+    fn test_fit_is_deterministic() {
+        let mut first = SVDpp::new(SVDppConfig::default());
+        let mut second = SVDpp::new(SVDppConfig::default());
+        first.fit(&dataset()).unwrap();
+        second.fit(&dataset()).unwrap();
+        assert_eq!(
+            first.user_vector(3).unwrap().values,
+            second.user_vector(3).unwrap().values
+        );
+    }
+
+    #[test]
+    fn test_fit_rejects_invalid_input() {
+        let mut model = SVDpp::new(SVDppConfig::default().set_num_epochs(0));
+        assert!(model.fit(&dataset()).is_err());
+        let mut model = SVDpp::new(SVDppConfig::default());
+        assert!(model.fit(&Dataset::new(Vec::new())).is_err());
+    }
+}
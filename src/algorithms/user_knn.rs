@@ -0,0 +1,315 @@
+//! User based neighborhood recommender
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::algorithms::config::AlgorithmConfig;
+use crate::algorithms::knn::{knn_scores, to_similarity, KNNConfig};
+use crate::dataset::{Dataset, RatingScale};
+use crate::envelope::{fields, PersistedModel};
+use crate::errors::{Error, Result};
+use crate::memory::MemoryFootprint;
+use crate::models::Item;
+use crate::pairwise::{co_ratings, shrink, weighted_rating, PopularityNormalization};
+use crate::recommender::{RankedItems, Recommendation, Recommender};
+use crate::sets::IdSet;
+
+/// # User KNN
+/// Represents every user by the vector of their ratings and compares them with the
+/// configured metric. The rating of an item is predicted as the mean rating of the
+/// user plus the similarity weighted deviations from their own means of the
+/// nearest users who rated it, which removes how generous every user is.
+///
+/// Of the options of [`KNNConfig`], `num_neighbors`, `algorithm`, `time_budget_us`,
+/// `shrinkage`, `min_overlap` and `min_similarity` apply to the users. The
+/// item-item options, `normalization` and `baseline`, make [`UserKNN::fit`] fail
+/// with [`Error::InvalidConfig`]: the mean of every user plays the role of the
+/// baseline.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::algorithms::knn::KNNConfig;
+/// use rec_rsys::algorithms::user_knn::UserKNN;
+/// use rec_rsys::dataset::{Dataset, Rating};
+/// use rec_rsys::recommender::Recommender;
+/// let dataset = Dataset::new(vec![
+///     Rating::new(1, 10, 5.0), Rating::new(1, 11, 3.0), Rating::new(1, 12, 4.0),
+///     Rating::new(2, 10, 4.0), Rating::new(2, 11, 2.0),
+///     Rating::new(3, 11, 5.0), Rating::new(3, 12, 1.0),
+/// ]);
+/// let mut model = UserKNN::new(KNNConfig::default().set_num_neighbors(1));
+/// model.fit(&dataset).unwrap();
+/// assert_eq!(model.neighbors(2)[0].0, 1);
+/// // User 1 rated item 12 with 4, their mean, user 2 gets their own mean.
+/// assert!((model.predict(2, 12).unwrap() - 3.0).abs() < 1e-6);
+/// assert_eq!(model.recommend(2, 1)[0].item_id, 12);
+/// ```
+#[doc = include_str!("../../docs/algorithms/user_knn.md")]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UserKNN {
+    config: KNNConfig,
+    /// Every other user with a positive similarity, most similar first.
+    similarities: HashMap<u32, Vec<(u32, f32)>>,
+    means: HashMap<u32, f32>,
+    /// The deviation of every rating of an item from the mean of its user, by item
+    /// then user.
+    deviations: HashMap<u32, HashMap<u32, f32>>,
+    seen: HashMap<u32, IdSet>,
+    /// The scale of the training ratings, the predictions are clipped to it.
+    #[serde(default)]
+    scale: Option<RatingScale>,
+}
+
+impl UserKNN {
+    pub fn new(config: KNNConfig) -> Self {
+        UserKNN {
+            config,
+            ..UserKNN::default()
+        }
+    }
+
+    pub fn config(&self) -> &KNNConfig {
+        &self.config
+    }
+
+    /// The most similar users of a user, with their similarity.
+    pub fn neighbors(&self, user_id: u32) -> &[(u32, f32)] {
+        let similarities = self.similarities.get(&user_id).map_or(&[][..], |s| s);
+        let num_neighbors = self.config.num_neighbors.unwrap_or(similarities.len());
+        &similarities[..num_neighbors.min(similarities.len())]
+    }
+
+    /// The mean rating of a user.
+    pub fn user_mean(&self, user_id: u32) -> Option<f32> {
+        self.means.get(&user_id).copied()
+    }
+
+    /// The prediction from the `k` most similar users who rated the item, `None`
+    /// when none of the similar users did.
+    fn predict_for(&self, user_id: u32, item_id: u32) -> Option<f32> {
+        let mean = self.means.get(&user_id)?;
+        let deviations = self.deviations.get(&item_id)?;
+        let neighbors: Vec<(u32, f32)> = self
+            .similarities
+            .get(&user_id)?
+            .iter()
+            .filter(|(neighbor, _)| deviations.contains_key(neighbor))
+            .take(self.config.num_neighbors.unwrap_or(usize::MAX))
+            .copied()
+            .collect();
+        let deviations: Vec<(u32, f32)> = neighbors
+            .iter()
+            .map(|(neighbor, _)| (*neighbor, deviations[neighbor]))
+            .collect();
+        let prediction = mean + weighted_rating(&neighbors, &deviations)?;
+        Some(
+            self.scale
+                .map_or(prediction, |scale| scale.clip(prediction)),
+        )
+    }
+}
+
+impl Recommender for UserKNN {
+    fn fit(&mut self, dataset: &Dataset) -> Result<()> {
+        self.config.validate()?;
+        if self.config.normalization != PopularityNormalization::None {
+            return Err(Error::InvalidConfig(
+                "`normalization` is not supported by UserKNN".to_string(),
+            ));
+        }
+        if self.config.baseline.is_some() {
+            return Err(Error::InvalidConfig(
+                "`baseline` is not supported by UserKNN".to_string(),
+            ));
+        }
+        let users = dataset.user_vectors();
+        let rows: HashMap<u32, usize> = users
+            .iter()
+            .enumerate()
+            .map(|(row, user)| (user.id, row))
+            .collect();
+        // Every user is kept, the number of neighbors applies to the users who
+        // rated the predicted item.
+        let config = KNNConfig {
+            num_neighbors: None,
+            ..self.config.clone()
+        };
+        self.similarities = users
+            .iter()
+            .map(|user| {
+                let pool: Vec<&Item> = users.iter().filter(|u| u.id != user.id).collect();
+                let mut similarities: Vec<(u32, f32)> =
+                    knn_scores(&user.values, &pool, &config)?
+                        .into_iter()
+                        .filter_map(|(id, value)| {
+                            let overlap = co_ratings(user, &users[rows[&id]]);
+                            let similarity = to_similarity(config.algorithm, value);
+                            let similarity =
+                                shrink(similarity, overlap, config.shrinkage);
+                            (similarity.is_finite()
+                                && similarity > 0.0
+                                && similarity >= config.min_similarity
+                                && overlap >= config.min_overlap)
+                                .then_some((id, similarity))
+                        })
+                        .collect();
+                similarities.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+                Ok((user.id, similarities))
+            })
+            .collect::<Result<HashMap<u32, Vec<(u32, f32)>>>>()?;
+        self.means = dataset
+            .user_ratings()
+            .into_iter()
+            .map(|(user_id, ratings)| {
+                let sum: f32 = ratings.iter().map(|(_, rating)| rating).sum();
+                (user_id, sum / ratings.len() as f32)
+            })
+            .collect();
+        self.deviations = HashMap::new();
+        for r in &dataset.ratings {
+            self.deviations
+                .entry(r.item_id)
+                .or_default()
+                .insert(r.user_id, r.rating - self.means[&r.user_id]);
+        }
+        self.seen = dataset.user_item_sets();
+        self.scale = dataset.scale;
+        Ok(())
+    }
+
+    fn recommend(&self, user_id: u32, num_items: usize) -> Vec<Recommendation> {
+        self.recommend_iter(user_id).take(num_items).collect()
+    }
+
+    fn recommend_iter(&self, user_id: u32) -> RankedItems {
+        let seen = match self.seen.get(&user_id) {
+            Some(seen) => seen,
+            None => return RankedItems::default(),
+        };
+        let scores: HashMap<u32, f32> = self
+            .deviations
+            .keys()
+            .filter(|item_id| !seen.contains(**item_id))
+            .filter_map(|item_id| Some((*item_id, self.predict_for(user_id, *item_id)?)))
+            .collect();
+        RankedItems::new(scores, Some(seen))
+    }
+
+    fn predict(&self, user_id: u32, item_id: u32) -> Option<f32> {
+        self.predict_for(user_id, item_id)
+    }
+}
+
+impl PersistedModel for UserKNN {
+    const MODEL_TYPE: &'static str = "user_knn";
+
+    fn hyperparameters(&self) -> BTreeMap<String, Value> {
+        fields(&self.config)
+    }
+}
+
+impl MemoryFootprint for UserKNN {
+    fn heap_size(&self) -> usize {
+        self.similarities.heap_size()
+            + self.means.heap_size()
+            + self.deviations.heap_size()
+            + self.seen.heap_size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithms::baseline::BaselineConfig;
+    use crate::dataset::Rating;
+    use crate::similarity::SimilarityAlgos;
+
+    fn dataset() -> Dataset {
+        Dataset::new(vec![
+            Rating::new(1, 10, 5.0),
+            Rating::new(1, 11, 3.0),
+            Rating::new(1, 12, 4.0),
+            Rating::new(2, 10, 4.0),
+            Rating::new(2, 11, 2.0),
+            Rating::new(3, 11, 5.0),
+            Rating::new(3, 12, 1.0),
+            Rating::new(4, 13, 2.0),
+        ])
+    }
+
+    #[test]
+    fn test_neighbors() {
+        let mut model = UserKNN::new(KNNConfig::default().set_num_neighbors(1));
+        model.fit(&dataset()).unwrap();
+        assert_eq!(model.neighbors(2), [(1, model.neighbors(2)[0].1)]);
+        assert!(model.neighbors(4).is_empty());
+        assert!(model.neighbors(99).is_empty());
+        assert_eq!(model.user_mean(2), Some(3.0));
+    }
+
+    #[test]
+    fn test_mean_centered_prediction() {
+        let mut model = UserKNN::new(KNNConfig::default());
+        model.fit(&dataset()).unwrap();
+        // Users 1 and 3 rated item 12, 0 and -2 away from their means.
+        let (s1, s3) = (model.similarities[&2][0], model.similarities[&2][1]);
+        assert_eq!((s1.0, s3.0), (1, 3));
+        let expected = 3.0 + (s1.1 * 0.0 + s3.1 * -2.0) / (s1.1 + s3.1);
+        assert!((model.predict(2, 12).unwrap() - expected).abs() < 1e-6);
+        // Nobody similar to user 4 rated item 10.
+        assert_eq!(model.predict(4, 10), None);
+        assert_eq!(model.predict(99, 10), None);
+        assert!(model.recommend(4, 10).is_empty());
+    }
+
+    #[test]
+    fn test_num_neighbors_among_raters() {
+        // The nearest user of user 2 did not rate item 12, the next one did.
+        let mut ratings = dataset().ratings;
+        ratings.push(Rating::new(5, 10, 4.0));
+        ratings.push(Rating::new(5, 11, 2.0));
+        let mut model = UserKNN::new(KNNConfig::default().set_num_neighbors(1));
+        model.fit(&Dataset::new(ratings)).unwrap();
+        assert_eq!(model.neighbors(2)[0].0, 5);
+        assert!((model.predict(2, 12).unwrap() - 3.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_predictions_are_clipped_to_the_scale() {
+        // User 1 rated item 11 two above their mean, user 2 only gave 5s.
+        let ratings = vec![
+            Rating::new(1, 10, 1.0),
+            Rating::new(1, 11, 5.0),
+            Rating::new(2, 10, 5.0),
+        ];
+        let mut model = UserKNN::new(KNNConfig::default());
+        model.fit(&Dataset::new(ratings.clone())).unwrap();
+        assert_eq!(model.predict(2, 11), Some(7.0));
+        let dataset = Dataset::new(ratings)
+            .with_scale(RatingScale::default())
+            .unwrap();
+        model.fit(&dataset).unwrap();
+        assert_eq!(model.predict(2, 11), Some(5.0));
+    }
+
+    #[test]
+    fn test_fit_rejects_invalid_config() {
+        let config = KNNConfig::default().set_algorithm(SimilarityAlgos::Euclidean);
+        let mut model = UserKNN::new(config.set_shrinkage(-1.0));
+        assert!(model.fit(&dataset()).is_err());
+        let config = KNNConfig::default()
+            .set_normalization(PopularityNormalization::Damping { alpha: 0.5 });
+        let mut model = UserKNN::new(config);
+        assert!(matches!(
+            model.fit(&dataset()),
+            Err(Error::InvalidConfig(_))
+        ));
+        let config = KNNConfig::default().set_baseline(BaselineConfig::default());
+        let mut model = UserKNN::new(config);
+        assert!(matches!(
+            model.fit(&dataset()),
+            Err(Error::InvalidConfig(_))
+        ));
+    }
+}
@@ -0,0 +1,244 @@
+//! # Embedding alignment
+//! Maps the item embeddings of one model into the space of another, so a new
+//! model version, or a model of another domain, can replace the current one while
+//! the neighbor tables and caches built in the current space stay valid.
+use std::collections::HashMap;
+
+use crate::errors::{Error, Result};
+use crate::matrix::symmetric_eigen;
+use crate::memory::MemoryFootprint;
+use crate::models::Item;
+
+/// # Procrustes alignment
+/// The rotation, possibly with a reflection, that best maps the embeddings of
+/// anchor items in a source space onto their embeddings in a target space. Being
+/// orthogonal, it keeps the distances and the angles between the source
+/// embeddings, so neighbors in the source space stay neighbors once mapped.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::alignment::Procrustes;
+/// // The target space is the source one rotated by 90 degrees.
+/// let source = [vec![1.0, 0.0], vec![0.0, 2.0], vec![1.0, 1.0]];
+/// let target = [vec![0.0, 1.0], vec![-2.0, 0.0], vec![-1.0, 1.0]];
+/// let procrustes = Procrustes::fit(&source, &target).unwrap();
+/// let mapped = procrustes.transform(&[3.0, 0.0]);
+/// assert!(mapped[0].abs() < 1e-5 && (mapped[1] - 3.0).abs() < 1e-5);
+/// assert!(procrustes.residual() < 1e-5);
+/// ```
+#[doc = include_str!("../docs/alignment/procrustes.md")]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Procrustes {
+    /// `d x d` orthogonal matrix, a source row vector `x` maps to `x R`.
+    rotation: Vec<Vec<f32>>,
+    residual: f32,
+}
+
+impl Procrustes {
+    /// # Fit
+    /// Learns the rotation from paired embeddings of the anchor items.
+    ///
+    /// ## Parameters:
+    /// * `source`: The embeddings of the anchors in the source space.
+    /// * `target`: The embeddings of the same anchors, in the same order, in the
+    ///   target space.
+    ///
+    /// ## Returns:
+    /// * The alignment, or an error if the embeddings do not have the same number
+    ///   of dimensions or the anchors do not span the space, which needs at least
+    ///   as many anchors as dimensions.
+    pub fn fit(source: &[Vec<f32>], target: &[Vec<f32>]) -> Result<Self> {
+        if source.len() != target.len() || source.is_empty() {
+            return Err(Error::InvalidData(format!(
+                "expected the same non zero number of source and target anchors, got \
+                 {} and {}",
+                source.len(),
+                target.len()
+            )));
+        }
+        let dims = source[0].len();
+        if source.iter().chain(target).any(|row| row.len() != dims) || dims == 0 {
+            return Err(Error::InvalidData(
+                "every anchor embedding must have the same non zero dimension"
+                    .to_string(),
+            ));
+        }
+        // M = Xᵀ Y and R = M (Mᵀ M)^(-1/2), the U Vᵀ of the SVD of M.
+        let mut m = vec![vec![0.0_f64; dims]; dims];
+        for (x, y) in source.iter().zip(target) {
+            for i in 0..dims {
+                for j in 0..dims {
+                    m[i][j] += x[i] as f64 * y[j] as f64;
+                }
+            }
+        }
+        let gram: Vec<Vec<f32>> = (0..dims)
+            .map(|i| {
+                (0..dims)
+                    .map(|j| (0..dims).map(|k| m[k][i] * m[k][j]).sum::<f64>() as f32)
+                    .collect()
+            })
+            .collect();
+        let (values, vectors) = symmetric_eigen(&gram);
+        let largest = values[0].max(0.0);
+        if largest == 0.0 || values[dims - 1] <= largest * 1e-8 {
+            return Err(Error::InvalidData(format!(
+                "the {} anchors do not span the {} dimensions of the embeddings",
+                source.len(),
+                dims
+            )));
+        }
+        let mut inverse_root = vec![vec![0.0_f64; dims]; dims];
+        for (value, vector) in values.iter().zip(&vectors) {
+            let scale = 1.0 / (*value as f64).sqrt();
+            for i in 0..dims {
+                for j in 0..dims {
+                    inverse_root[i][j] += scale * vector[i] as f64 * vector[j] as f64;
+                }
+            }
+        }
+        let rotation: Vec<Vec<f32>> = (0..dims)
+            .map(|i| {
+                (0..dims)
+                    .map(|j| {
+                        (0..dims).map(|k| m[i][k] * inverse_root[k][j]).sum::<f64>()
+                            as f32
+                    })
+                    .collect()
+            })
+            .collect();
+        let mut procrustes = Procrustes {
+            rotation,
+            residual: 0.0,
+        };
+        let squared: f32 = source
+            .iter()
+            .zip(target)
+            .flat_map(|(x, y)| {
+                procrustes
+                    .transform(x)
+                    .into_iter()
+                    .zip(y)
+                    .map(|(mapped, y)| (mapped - y).powi(2))
+            })
+            .sum();
+        procrustes.residual = (squared / (source.len() * dims) as f32).sqrt();
+        Ok(procrustes)
+    }
+
+    /// Learns the rotation from the items found in both spaces, see
+    /// [`Procrustes::fit`].
+    pub fn fit_items(source: &[Item], target: &[Item]) -> Result<Self> {
+        let targets: HashMap<u32, &Vec<f32>> =
+            target.iter().map(|item| (item.id, &item.values)).collect();
+        let (anchors, targets): (Vec<Vec<f32>>, Vec<Vec<f32>>) = source
+            .iter()
+            .filter_map(|item| {
+                Some((item.values.clone(), targets.get(&item.id)?.to_vec()))
+            })
+            .unzip();
+        Procrustes::fit(&anchors, &targets)
+    }
+
+    /// The orthogonal matrix, a row per source dimension.
+    pub fn rotation(&self) -> &[Vec<f32>] {
+        &self.rotation
+    }
+
+    /// The root mean squared error of the mapped anchors: close to 0 when the two
+    /// spaces only differ by a rotation.
+    pub fn residual(&self) -> f32 {
+        self.residual
+    }
+
+    /// Maps an embedding of the source space into the target space.
+    pub fn transform(&self, values: &[f32]) -> Vec<f32> {
+        let dims = self.rotation.len();
+        (0..dims)
+            .map(|j| {
+                values
+                    .iter()
+                    .zip(&self.rotation)
+                    .map(|(x, row)| x * row[j])
+                    .sum()
+            })
+            .collect()
+    }
+
+    /// Maps the embeddings of items into the target space, keeping their ids.
+    pub fn transform_items(&self, items: &[Item]) -> Vec<Item> {
+        items
+            .iter()
+            .map(|item| Item::new(item.id, self.transform(&item.values), None))
+            .collect()
+    }
+}
+
+impl MemoryFootprint for Procrustes {
+    fn heap_size(&self) -> usize {
+        self.rotation.heap_size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use super::*;
+
+    #[test]
+    fn test_recovers_rotation() {
+        // A rotation in the (0, 1) plane and a reflection of the last axis.
+        let (cos, sin) = (0.6_f32, 0.8_f32);
+        let rotate =
+            |x: &[f32]| vec![cos * x[0] - sin * x[1], sin * x[0] + cos * x[1], -x[2]];
+        let mut rng = StdRng::seed_from_u64(7);
+        let source: Vec<Item> = (0..20)
+            .map(|id| {
+                Item::new(id, (0..3).map(|_| rng.gen_range(-1.0..1.0)).collect(), None)
+            })
+            .collect();
+        let target: Vec<Item> = source[..10]
+            .iter()
+            .map(|item| Item::new(item.id, rotate(&item.values), None))
+            .collect();
+        let procrustes = Procrustes::fit_items(&source, &target).unwrap();
+        assert!(procrustes.residual() < 1e-5);
+        for item in procrustes.transform_items(&source[10..]) {
+            let expected = rotate(&source[item.id as usize].values);
+            assert!(item
+                .values
+                .iter()
+                .zip(&expected)
+                .all(|(a, b)| (a - b).abs() < 1e-4));
+        }
+        let rotation = procrustes.rotation();
+        for i in 0..3 {
+            for j in 0..3 {
+                let product: f32 = (0..3).map(|k| rotation[i][k] * rotation[j][k]).sum();
+                assert!((product - if i == j { 1.0 } else { 0.0 }).abs() < 1e-4);
+            }
+        }
+    }
+
+    #[test]
+    fn test_noisy_anchors() {
+        let source = [vec![1.0, 0.0], vec![0.0, 1.0], vec![1.0, 1.0]];
+        let target = [vec![1.1, 0.0], vec![0.0, 0.9], vec![1.0, 1.05]];
+        let procrustes = Procrustes::fit(&source, &target).unwrap();
+        assert!(procrustes.residual() > 0.0 && procrustes.residual() < 0.1);
+        let mapped = procrustes.transform(&[2.0, 0.0]);
+        assert!((mapped.iter().map(|v| v * v).sum::<f32>().sqrt() - 2.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_invalid_anchors() {
+        assert!(Procrustes::fit(&[vec![1.0, 0.0]], &[vec![0.0, 1.0]]).is_err());
+        assert!(Procrustes::fit(&[vec![1.0, 0.0]], &[]).is_err());
+        assert!(Procrustes::fit(&vec![vec![1.0, 0.0]; 2], &vec![vec![1.0]; 2]).is_err());
+        let items = [Item::new(1, vec![1.0], None)];
+        assert!(Procrustes::fit_items(&items, &[Item::new(2, vec![1.0], None)]).is_err());
+    }
+}
@@ -0,0 +1,241 @@
+//! # Dataset anonymization
+//! Replaces the user and item identifiers of a dataset and coarsens its timestamps
+//! before it is shared, so evaluation data can leave the team without exposing the
+//! raw identifiers. The ratings themselves are kept, the dataset gives the same
+//! evaluation results once anonymized.
+use std::collections::HashMap;
+
+use rand::rngs::{OsRng, StdRng};
+use rand::seq::SliceRandom;
+use rand::{RngCore, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::algorithms::config::{ensure, AlgorithmConfig};
+use crate::dataset::{Dataset, Rating};
+use crate::errors::Result;
+use crate::ids::mix;
+
+/// How the identifiers are replaced. Both keep distinct ids distinct. The key and
+/// the seed are secrets: whoever knows them can map the ids back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum IdMapping {
+    /// A keyed permutation of the ids: the same key gives the same ids in every
+    /// export, so several exports can be joined.
+    Keyed { key: u64 },
+    /// Consecutive ids from 0, assigned in a random order. Exports are not
+    /// comparable, but the ids tell nothing about the original ones.
+    Sequential { seed: u64 },
+}
+
+impl IdMapping {
+    /// A [`IdMapping::Sequential`] mapping whose seed is drawn from the operating
+    /// system, so that nobody can guess it. Read the seed back from the mapping to
+    /// reproduce the export.
+    pub fn sequential() -> Self {
+        IdMapping::Sequential {
+            seed: OsRng.next_u64(),
+        }
+    }
+}
+
+/// What is kept of the timestamps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TimestampPolicy {
+    Keep,
+    /// Rounds down to a multiple of `seconds`, e.g. `86400` for the day.
+    Bucket {
+        seconds: u64,
+    },
+    Drop,
+}
+
+/// Parameters of [`anonymize`]. The default maps the ids with
+/// [`IdMapping::sequential`], a secret seed drawn for every config, and buckets the
+/// timestamps by day.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AnonymizationConfig {
+    pub ids: IdMapping,
+    pub timestamps: TimestampPolicy,
+}
+
+impl Default for AnonymizationConfig {
+    fn default() -> Self {
+        AnonymizationConfig {
+            ids: IdMapping::sequential(),
+            timestamps: TimestampPolicy::Bucket { seconds: 86400 },
+        }
+    }
+}
+
+impl AnonymizationConfig {
+    pub fn set_ids(mut self, ids: IdMapping) -> Self {
+        self.ids = ids;
+        self
+    }
+    pub fn set_timestamps(mut self, timestamps: TimestampPolicy) -> Self {
+        self.timestamps = timestamps;
+        self
+    }
+}
+
+impl AlgorithmConfig for AnonymizationConfig {
+    fn validate(&self) -> Result<()> {
+        ensure(
+            !matches!(self.timestamps, TimestampPolicy::Bucket { seconds: 0 }),
+            "timestamps.seconds",
+            "greater than 0",
+        )
+    }
+}
+
+/// # Anonymize
+/// Replaces the identifiers and coarsens the timestamps of a dataset. The ratings
+/// are sorted by their new user and item ids, so the order of the original file
+/// is not leaked either.
+///
+/// ## Parameters:
+/// * `dataset`: The dataset to export.
+/// * `config`: How the identifiers and the timestamps are transformed.
+///
+/// ## Returns:
+/// * The anonymized dataset.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::anonymize::{anonymize, AnonymizationConfig, IdMapping};
+/// use rec_rsys::dataset::{Dataset, Rating};
+/// let dataset = Dataset::new(vec![
+///     Rating::new(1001, 52, 4.0).timestamp(978_300_760),
+///     Rating::new(1002, 52, 3.0),
+/// ]);
+/// let config = AnonymizationConfig::default().set_ids(IdMapping::Sequential { seed: 7 });
+/// let anonymized = anonymize(&dataset, &config).unwrap();
+/// assert!(anonymized.users().iter().all(|id| *id < 2));
+/// assert_eq!(anonymized.items().len(), 1);
+/// assert!(anonymized.ratings.iter().any(|r| r.timestamp == Some(978_220_800)));
+/// ```
+pub fn anonymize(dataset: &Dataset, config: &AnonymizationConfig) -> Result<Dataset> {
+    config.validate()?;
+    let users = mapping(dataset.users().into_iter().collect(), config.ids, 0);
+    let items = mapping(dataset.items().into_iter().collect(), config.ids, 1);
+    let mut ratings: Vec<Rating> = dataset
+        .ratings
+        .iter()
+        .map(|r| Rating {
+            user_id: users[&r.user_id],
+            item_id: items[&r.item_id],
+            rating: r.rating,
+            timestamp: match config.timestamps {
+                TimestampPolicy::Keep => r.timestamp,
+                TimestampPolicy::Bucket { seconds } => {
+                    r.timestamp.map(|t| t - t % seconds)
+                },
+                TimestampPolicy::Drop => None,
+            },
+        })
+        .collect();
+    ratings.sort_by_key(|r| (r.user_id, r.item_id, r.timestamp));
+    Ok(Dataset::new(ratings))
+}
+
+/// The new id of every id, `domain` telling users and items apart.
+fn mapping(ids: Vec<u32>, mapping: IdMapping, domain: u64) -> HashMap<u32, u32> {
+    match mapping {
+        IdMapping::Keyed { key } => {
+            let key = mix(key ^ domain.wrapping_mul(0x9E37_79B9_7F4A_7C15));
+            ids.into_iter().map(|id| (id, permute(id, key))).collect()
+        },
+        IdMapping::Sequential { seed } => {
+            let mut new_ids: Vec<u32> = (0..ids.len() as u32).collect();
+            new_ids.shuffle(&mut StdRng::seed_from_u64(seed ^ domain));
+            ids.into_iter().zip(new_ids).collect()
+        },
+    }
+}
+
+/// A four rounds Feistel network over the two halves of the id, which is a
+/// bijection of the `u32` whatever the round function.
+fn permute(id: u32, key: u64) -> u32 {
+    let (mut left, mut right) = ((id >> 16) as u16, id as u16);
+    for round in 0..4 {
+        let f = mix(key.wrapping_add(round) ^ ((right as u64) << 32)) as u16;
+        (left, right) = (right, left ^ f);
+    }
+    ((left as u32) << 16) | right as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    fn dataset() -> Dataset {
+        Dataset::new(
+            (0..200)
+                .map(|i| {
+                    Rating::new(i % 20, 1000 + i % 13, i as f32)
+                        .timestamp(3600 * i as u64)
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_permute_is_a_bijection() {
+        let ids: HashSet<u32> = (0..100_000).map(|id| permute(id, 42)).collect();
+        assert_eq!(ids.len(), 100_000);
+        assert_ne!(permute(7, 42), permute(7, 43));
+    }
+
+    #[test]
+    fn test_keyed_mapping_is_stable() {
+        let config = AnonymizationConfig::default()
+            .set_ids(IdMapping::Keyed { key: 99 })
+            .set_timestamps(TimestampPolicy::Keep);
+        let first = anonymize(&dataset(), &config).unwrap();
+        let subset = Dataset::new(dataset().ratings[..50].to_vec());
+        let second = anonymize(&subset, &config).unwrap();
+        assert!(second.ratings.iter().all(|r| first.ratings.contains(r)));
+        assert_eq!(first.users().len(), 20);
+        assert_eq!(first.items().len(), 13);
+        assert!(first.users().intersection(&dataset().users()).count() < 20);
+    }
+
+    #[test]
+    fn test_timestamps_and_ratings() {
+        let config = AnonymizationConfig::default();
+        let anonymized = anonymize(&dataset(), &config).unwrap();
+        assert_eq!(anonymized.len(), 200);
+        assert!(anonymized.users().iter().all(|id| *id < 20));
+        assert!(anonymized
+            .ratings
+            .iter()
+            .all(|r| r.timestamp.unwrap() % 86400 == 0));
+        let mut ratings: Vec<f32> = anonymized.ratings.iter().map(|r| r.rating).collect();
+        ratings.sort_by(f32::total_cmp);
+        assert_eq!(ratings, (0..200).map(|i| i as f32).collect::<Vec<f32>>());
+        let dropped = config.set_timestamps(TimestampPolicy::Drop);
+        assert!(anonymize(&dataset(), &dropped)
+            .unwrap()
+            .ratings
+            .iter()
+            .all(|r| r.timestamp.is_none()));
+        let invalid = AnonymizationConfig::default()
+            .set_timestamps(TimestampPolicy::Bucket { seconds: 0 });
+        assert!(anonymize(&dataset(), &invalid).is_err());
+    }
+
+    #[test]
+    fn test_default_seed_is_secret() {
+        let config = AnonymizationConfig::default();
+        assert_ne!(config.ids, AnonymizationConfig::default().ids);
+        // The drawn seed reproduces the export.
+        let first = anonymize(&dataset(), &config).unwrap();
+        let again = AnonymizationConfig::default().set_ids(config.ids);
+        assert_eq!(anonymize(&dataset(), &again).unwrap(), first);
+    }
+}
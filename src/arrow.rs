@@ -0,0 +1,152 @@
+//! # Arrow interchange
+//! Exports learned factors and neighbor tables as Arrow IPC streams, the format
+//! `pyarrow.ipc.open_stream` reads without copying the buffers, so models trained
+//! here can be analysed with pandas or polars directly.
+use std::io::Write;
+use std::sync::Arc;
+
+use arrow_array::{ArrayRef, Float32Array, RecordBatch, UInt32Array};
+use arrow_ipc::writer::StreamWriter;
+use arrow_schema::{ArrowError, DataType, Field, Schema};
+
+use crate::errors::{Error, Result};
+use crate::factors::FactorMatrix;
+
+fn arrow_error(error: ArrowError) -> Error {
+    Error::Serialization(error.to_string())
+}
+
+/// # Factors batch
+/// One row per entity: an `id` column followed by one `f{k}` column per factor.
+///
+/// ## Parameters:
+/// * `ids`: The id of every row of the matrix, e.g.
+///   [`MatrixFactorization::item_ids`](crate::algorithms::mf::MatrixFactorization::item_ids).
+/// * `factors`: The factors.
+pub fn factors_batch(ids: &[u32], factors: &FactorMatrix) -> Result<RecordBatch> {
+    if ids.len() != factors.rows() {
+        return Err(Error::InvalidData(format!(
+            "{} ids for {} rows of factors",
+            ids.len(),
+            factors.rows()
+        )));
+    }
+    let mut fields = vec![Field::new("id", DataType::UInt32, false)];
+    let mut columns: Vec<ArrayRef> = vec![Arc::new(UInt32Array::from(ids.to_vec()))];
+    for dim in 0..factors.dims() {
+        fields.push(Field::new(format!("f{}", dim), DataType::Float32, false));
+        let column: Float32Array = (0..factors.rows())
+            .map(|row| factors.get(row, dim))
+            .collect();
+        columns.push(Arc::new(column));
+    }
+    RecordBatch::try_new(Arc::new(Schema::new(fields)), columns).map_err(arrow_error)
+}
+
+/// # Neighbors batch
+/// The neighbor lists in long format: one row per `(item_id, neighbor_id)` pair
+/// with its `similarity` and its `rank` in the list, starting at 0.
+pub fn neighbors_batch<'a, I>(neighbors: I) -> Result<RecordBatch>
+where
+    I: IntoIterator<Item = (u32, &'a [(u32, f32)])>,
+{
+    let (mut item_ids, mut neighbor_ids) = (Vec::new(), Vec::new());
+    let (mut similarities, mut ranks) = (Vec::new(), Vec::new());
+    for (item_id, list) in neighbors {
+        for (rank, (neighbor_id, similarity)) in list.iter().enumerate() {
+            item_ids.push(item_id);
+            neighbor_ids.push(*neighbor_id);
+            similarities.push(*similarity);
+            ranks.push(rank as u32);
+        }
+    }
+    let schema = Schema::new(vec![
+        Field::new("item_id", DataType::UInt32, false),
+        Field::new("neighbor_id", DataType::UInt32, false),
+        Field::new("similarity", DataType::Float32, false),
+        Field::new("rank", DataType::UInt32, false),
+    ]);
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(UInt32Array::from(item_ids)),
+        Arc::new(UInt32Array::from(neighbor_ids)),
+        Arc::new(Float32Array::from(similarities)),
+        Arc::new(UInt32Array::from(ranks)),
+    ];
+    RecordBatch::try_new(Arc::new(schema), columns).map_err(arrow_error)
+}
+
+/// Writes a batch as a complete Arrow IPC stream.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::arrow::{factors_batch, write_ipc};
+/// use rec_rsys::factors::FactorMatrix;
+/// let factors = FactorMatrix::from_rows(&[vec![0.1, 0.2], vec![0.3, 0.4]]).unwrap();
+/// let mut stream = Vec::new();
+/// write_ipc(&factors_batch(&[10, 11], &factors).unwrap(), &mut stream).unwrap();
+/// assert!(!stream.is_empty());
+/// ```
+pub fn write_ipc<W: Write>(batch: &RecordBatch, writer: W) -> Result<()> {
+    let mut writer =
+        StreamWriter::try_new(writer, &batch.schema()).map_err(arrow_error)?;
+    writer.write(batch).map_err(arrow_error)?;
+    writer.finish().map_err(arrow_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::{Float32Type, UInt32Type};
+    use arrow_ipc::reader::StreamReader;
+
+    use super::*;
+
+    fn read_back(batch: &RecordBatch) -> RecordBatch {
+        let mut stream = Vec::new();
+        write_ipc(batch, &mut stream).unwrap();
+        let mut reader = StreamReader::try_new(stream.as_slice(), None).unwrap();
+        let read = reader.next().unwrap().unwrap();
+        assert!(reader.next().is_none());
+        read
+    }
+
+    #[test]
+    fn test_factors() {
+        let factors =
+            FactorMatrix::from_rows(&[vec![1.0, 2.0, 3.0], vec![4.0, 5.0, 6.0]]).unwrap();
+        let batch = read_back(&factors_batch(&[7, 9], &factors).unwrap());
+        assert_eq!(batch.num_rows(), 2);
+        assert_eq!(batch.schema().field(3).name(), "f2");
+        let ids = batch.column(0).as_primitive::<UInt32Type>();
+        assert_eq!(ids.values().to_vec(), vec![7, 9]);
+        let f1 = batch
+            .column_by_name("f1")
+            .unwrap()
+            .as_primitive::<Float32Type>();
+        assert_eq!(f1.values().to_vec(), vec![2.0, 5.0]);
+        assert!(factors_batch(&[7], &factors).is_err());
+    }
+
+    #[test]
+    fn test_neighbors() {
+        let lists = [
+            (1, vec![(2, 0.9), (3, 0.5)]),
+            (2, vec![]),
+            (3, vec![(1, 0.4)]),
+        ];
+        let batch = neighbors_batch(lists.iter().map(|(id, l)| (*id, l.as_slice())));
+        let batch = read_back(&batch.unwrap());
+        assert_eq!(batch.num_rows(), 3);
+        let column = |name: &str| {
+            batch
+                .column_by_name(name)
+                .unwrap()
+                .as_primitive::<UInt32Type>()
+                .values()
+                .to_vec()
+        };
+        assert_eq!(column("item_id"), vec![1, 1, 3]);
+        assert_eq!(column("neighbor_id"), vec![2, 3, 1]);
+        assert_eq!(column("rank"), vec![0, 1, 0]);
+    }
+}
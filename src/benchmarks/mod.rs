@@ -1,4 +1,12 @@
-#[cfg(feature = "benchmarks")]
+//! # Benchmarking helpers
+//! Shared by the criterion benches under `benches/` and available to downstream
+//! crates that want to benchmark their own recommenders the same way. Only built
+//! with the `benchmarks` feature.
+//!
+//! The names exported here are stable: benches written against them keep compiling
+//! across minor versions.
+
+/// Criterion configuration used by every bench of the crate.
 pub mod config {
     use criterion::{measurement::WallTime, BenchmarkGroup, Criterion};
     #[cfg(not(target_os = "windows"))]
@@ -1,13 +1,33 @@
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::rc::Rc;
 use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use crate::statistics::{mean, median, quartiles, standard_deviation};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::algorithms::config::{ensure, AlgorithmConfig};
+use crate::dataset::{Dataset, Rating};
+use crate::errors::Result;
+use crate::formatting::ReportFormat;
+use crate::models::Item;
+use crate::statistics::{
+    confidence_interval, mean, median, median_abs_dev, quartiles, standard_deviation,
+    standard_error, ConfidenceInterval,
+};
 
 type ParamFunction = Rc<RefCell<dyn Fn()>>;
 type ParamFunctionTuple = (&'static str, ParamFunction);
 
+/// The confidence level of the intervals of [`compare_execution_times`].
+pub const CONFIDENCE_LEVEL: f32 = 0.95;
+/// The largest margin of the confidence interval of a mean, relative to the mean,
+/// for which the number of executions is enough.
+pub const TARGET_RELATIVE_MARGIN: f32 = 0.05;
+/// The fewest executions for which the confidence interval is trusted.
+pub const MIN_SAMPLES: usize = 10;
+
 pub fn timeit<F>(method: F) -> Duration
 where
     F: FnOnce(),
@@ -17,6 +37,169 @@ where
     now.elapsed()
 }
 
+/// How [`measure`] runs a function.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MeasureConfig {
+    /// Executions run and discarded first, so caches, branch predictors and the
+    /// CPU frequency reach their steady state.
+    pub warm_up_iterations: usize,
+    /// The fewest executions measured.
+    pub min_iterations: usize,
+    /// Executions are measured until their total reaches this duration, so fast
+    /// functions get more samples.
+    pub min_duration: Duration,
+    /// The most executions measured, whatever the total duration.
+    pub max_iterations: usize,
+    /// Executions further from the median than this many scaled median absolute
+    /// deviations are outliers, e.g. preempted by the scheduler.
+    pub outlier_threshold: f32,
+}
+
+impl Default for MeasureConfig {
+    fn default() -> Self {
+        MeasureConfig {
+            warm_up_iterations: 10,
+            min_iterations: 10,
+            min_duration: Duration::from_millis(100),
+            max_iterations: 1_000_000,
+            outlier_threshold: 3.5,
+        }
+    }
+}
+
+impl MeasureConfig {
+    pub fn set_warm_up_iterations(mut self, warm_up_iterations: usize) -> Self {
+        self.warm_up_iterations = warm_up_iterations;
+        self
+    }
+    pub fn set_min_iterations(mut self, min_iterations: usize) -> Self {
+        self.min_iterations = min_iterations;
+        self
+    }
+    pub fn set_min_duration(mut self, min_duration: Duration) -> Self {
+        self.min_duration = min_duration;
+        self
+    }
+    pub fn set_max_iterations(mut self, max_iterations: usize) -> Self {
+        self.max_iterations = max_iterations;
+        self
+    }
+    pub fn set_outlier_threshold(mut self, outlier_threshold: f32) -> Self {
+        self.outlier_threshold = outlier_threshold;
+        self
+    }
+}
+
+impl AlgorithmConfig for MeasureConfig {
+    fn validate(&self) -> Result<()> {
+        ensure(self.min_iterations > 0, "min_iterations", "greater than 0")?;
+        ensure(
+            self.max_iterations >= self.min_iterations,
+            "max_iterations",
+            "at least min_iterations",
+        )?;
+        ensure(
+            self.outlier_threshold > 0.0,
+            "outlier_threshold",
+            "greater than 0",
+        )
+    }
+}
+
+/// The steady-state execution times of a function, see [`measure`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Measurement {
+    /// The execution times kept, in the order they were measured.
+    pub samples: Vec<Duration>,
+    /// The execution times rejected as outliers.
+    pub outliers: Vec<Duration>,
+}
+
+impl Measurement {
+    pub fn mean(&self) -> Duration {
+        match self.samples.len() {
+            0 => Duration::ZERO,
+            len => self.samples.iter().sum::<Duration>() / len as u32,
+        }
+    }
+
+    pub fn median(&self) -> Duration {
+        Duration::from_secs_f32(median(&durations_to_f32s(&self.samples)))
+    }
+
+    /// The execution times kept, in seconds.
+    pub fn seconds(&self) -> Vec<f32> {
+        durations_to_f32s(&self.samples)
+    }
+}
+
+/// # Measure
+/// Times a function in its steady state: it runs the warm-up executions, then
+/// measures executions until there are enough of them and they took long enough,
+/// and rejects the outliers with the median absolute deviation.
+///
+/// ## Parameters:
+/// * `config`: The number of executions and the outlier threshold.
+/// * `method`: The function measured.
+///
+/// ## Returns:
+/// * The execution times, or an error if the config is invalid.
+///
+/// ## Examples:
+/// ```
+/// use std::time::Duration;
+/// use rec_rsys::benchmarks::testing_tools::{measure, MeasureConfig};
+/// use rec_rsys::similarity::cosine_similarity;
+/// let (a, b) = (vec![0.5; 64], vec![0.25; 64]);
+/// let config = MeasureConfig::default().set_min_duration(Duration::from_millis(5));
+/// let measurement = measure(&config, || {
+///     std::hint::black_box(cosine_similarity(&a, &b));
+/// })
+/// .unwrap();
+/// assert!(measurement.samples.len() + measurement.outliers.len() >= 10);
+/// assert!(measurement.median() > Duration::ZERO);
+/// ```
+pub fn measure<F: FnMut()>(config: &MeasureConfig, mut method: F) -> Result<Measurement> {
+    config.validate()?;
+    (0..config.warm_up_iterations).for_each(|_| method());
+    let mut samples = Vec::with_capacity(config.min_iterations);
+    let mut total = Duration::ZERO;
+    while samples.len() < config.max_iterations
+        && (samples.len() < config.min_iterations || total < config.min_duration)
+    {
+        let elapsed = timeit(&mut method);
+        total += elapsed;
+        samples.push(elapsed);
+    }
+    Ok(reject_outliers(samples, config.outlier_threshold))
+}
+
+/// Splits the samples further than `threshold` scaled median absolute
+/// deviations from their median. None are when most samples are equal.
+fn reject_outliers(samples: Vec<Duration>, threshold: f32) -> Measurement {
+    let seconds = durations_to_f32s(&samples);
+    let center = median(&seconds);
+    let deviation = median_abs_dev(&seconds);
+    if deviation <= 0.0 {
+        return Measurement {
+            samples,
+            outliers: Vec::new(),
+        };
+    }
+    let (samples, outliers) =
+        samples
+            .into_iter()
+            .zip(seconds)
+            .partition::<Vec<_>, _>(|(_, secs)| {
+                (secs - center).abs() <= threshold * deviation
+            });
+    Measurement {
+        samples: samples.into_iter().map(|(sample, _)| sample).collect(),
+        outliers: outliers.into_iter().map(|(sample, _)| sample).collect(),
+    }
+}
+
 struct CustomRng {
     state: u64,
 }
@@ -32,11 +215,6 @@ impl CustomRng {
         CustomRng::default()
     }
 
-    fn seed(mut self, seed: u64) -> Self {
-        self.state = seed;
-        self
-    }
-
     fn next(&mut self) -> u64 {
         let mut x = self.state;
         let current_time = CustomRng::get_current_time();
@@ -68,19 +246,6 @@ impl CustomRng {
     fn random_f32(&mut self, min: f32, max: f32) -> f32 {
         min + (self.next() as f32 / u32::MAX as f32) * (max - min)
     }
-
-    fn random_number<T>(&mut self, min: T, max: T) -> T
-    where
-        T: std::ops::Sub<Output = T>
-            + std::ops::Mul<Output = T>
-            + std::ops::Add<Output = T>
-            + std::ops::Rem<Output = T>
-            + std::ops::Div<Output = T>
-            + Copy
-            + From<u64>,
-    {
-        min + (max - min * T::from(self.next()) % T::from(u64::MAX)) / T::from(u64::MAX)
-    }
 }
 
 // Function to create a vector with random values within a range
@@ -106,15 +271,89 @@ pub fn create_matrix(
     }
     vec
 }
+/// # Create items
+/// Generates items with random values, the same for a given seed so runs of a
+/// bench compare the same data.
+///
+/// ## Parameters:
+/// * `num_items`: The number of items, with ids from 0.
+/// * `dimensions`: The length of their vectors.
+/// * `seed`: The seed of the generator.
+///
+/// ## Returns:
+/// * The items, with values in `[-1, 1)`.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::benchmarks::testing_tools::create_items;
+/// let items = create_items(10, 4, 7);
+/// assert_eq!(items.len(), 10);
+/// assert_eq!(items, create_items(10, 4, 7));
+/// ```
+pub fn create_items(num_items: u32, dimensions: usize, seed: u64) -> Vec<Item> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    (0..num_items)
+        .map(|id| {
+            let values = (0..dimensions).map(|_| rng.gen_range(-1.0..1.0)).collect();
+            Item::new(id, values, None)
+        })
+        .collect()
+}
+
+/// # Create ratings
+/// Generates a random explicit feedback dataset, deterministic for a given seed.
+/// Popular items are rated more often, following a power law like real datasets.
+///
+/// ## Parameters:
+/// * `num_users`: The number of users, with ids from 0.
+/// * `num_items`: The number of items, with ids from 0.
+/// * `ratings_per_user`: How many distinct items every user rates, at most
+///   `num_items`.
+/// * `seed`: The seed of the generator.
+///
+/// ## Returns:
+/// * A dataset of ratings from 1 to 5.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::benchmarks::testing_tools::create_ratings;
+/// let dataset = create_ratings(100, 50, 10, 1);
+/// assert_eq!(dataset.len(), 1000);
+/// ```
+pub fn create_ratings(
+    num_users: u32,
+    num_items: u32,
+    ratings_per_user: usize,
+    seed: u64,
+) -> Dataset {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let ratings_per_user = ratings_per_user.min(num_items as usize);
+    let ratings = (0..num_users)
+        .flat_map(|user_id| {
+            let mut items: BTreeSet<u32> = BTreeSet::new();
+            while items.len() < ratings_per_user {
+                let popularity: f32 = rng.gen::<f32>().powi(3);
+                items.insert((popularity * num_items as f32) as u32 % num_items);
+            }
+            items
+                .into_iter()
+                .map(|item_id| Rating::new(user_id, item_id, rng.gen_range(1..=5) as f32))
+                .collect::<Vec<Rating>>()
+        })
+        .collect();
+    Dataset::new(ratings)
+}
+
 /// # Compare Execution Times
-/// Compares the execution times of multiple functions and stores the results.
+/// Compares the execution times of multiple functions, prints them and returns
+/// their statistics.
 ///
 /// ## Parameters:
 /// * `n`: The number of times to execute each function.
 /// * `functions`: A vector of tuples containing the name and function to be evaluated.
 ///
 /// ## Returns:
-/// * A hashmap where the key is the function name and the value is a vector of execution times.
+/// * The statistics of every function, the fastest first.
 ///
 /// ## Examples:
 /// ```ignore
@@ -138,7 +377,10 @@ pub fn create_matrix(
 /// let results = compare_execution_times(100, functions);
 /// ```
 ///
-pub fn compare_execution_times(n: u64, functions: Vec<ParamFunctionTuple>) {
+pub fn compare_execution_times(
+    n: u64,
+    functions: Vec<ParamFunctionTuple>,
+) -> Vec<ExecutionStatistics> {
     let mut results: HashMap<String, Vec<Duration>> = HashMap::new();
 
     for (name, function) in functions {
@@ -152,29 +394,56 @@ pub fn compare_execution_times(n: u64, functions: Vec<ParamFunctionTuple>) {
         results.insert(name.to_string(), execution_times);
     }
 
-    analyze_execution_results(results);
+    let statistics = analyze_execution_results(results);
+    print!(
+        "{}",
+        format_execution_report(&statistics, &ReportFormat::default())
+    );
+    statistics
 }
 
-struct FunctionStatistics {
-    name: String,
-    mean: f32,
-    median: f32,
-    std_deviation: f32,
-    percentile_25: f32,
-    percentile_75: f32,
-    speedup: Option<f32>,
+/// The statistics of the execution times of a function, in seconds.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExecutionStatistics {
+    pub name: String,
+    pub num_samples: usize,
+    pub mean: f32,
+    /// The standard error of the mean, `None` with less than two executions.
+    pub standard_error: Option<f32>,
+    /// The interval containing the mean with probability [`CONFIDENCE_LEVEL`].
+    pub confidence_interval: Option<ConfidenceInterval>,
+    /// The number of executions needed to know the mean within
+    /// [`TARGET_RELATIVE_MARGIN`], when more than were run.
+    pub suggested_samples: Option<usize>,
+    /// Whether the confidence interval overlaps the one of the fastest function,
+    /// in which case the difference may be noise. Always false for the fastest.
+    pub overlaps_fastest: bool,
+    pub median: f32,
+    pub std_deviation: f32,
+    pub percentile_25: f32,
+    pub percentile_75: f32,
+    /// The mean of the fastest function divided by the mean of this one.
+    pub speedup: Option<f32>,
 }
 
-fn analyze_execution_results(results: HashMap<String, Vec<Duration>>) {
-    let mut function_stats: Vec<FunctionStatistics> = results
+fn analyze_execution_results(
+    results: HashMap<String, Vec<Duration>>,
+) -> Vec<ExecutionStatistics> {
+    let mut function_stats: Vec<ExecutionStatistics> = results
         .iter()
         .map(|(name, duration_times)| {
             let mut execution_times: Vec<f32> = durations_to_f32s(duration_times);
             let (percentile_25, percentile_75) = quartiles(&mut execution_times);
 
-            FunctionStatistics {
+            let interval = confidence_interval(&execution_times, CONFIDENCE_LEVEL);
+            ExecutionStatistics {
                 name: name.clone(),
+                num_samples: execution_times.len(),
                 mean: mean(&execution_times),
+                standard_error: standard_error(&execution_times),
+                confidence_interval: interval,
+                suggested_samples: suggested_samples(execution_times.len(), interval),
+                overlaps_fastest: false,
                 median: median(&execution_times),
                 std_deviation: standard_deviation(&execution_times),
                 percentile_25,
@@ -197,6 +466,10 @@ fn analyze_execution_results(results: HashMap<String, Vec<Duration>>) {
         .map(|stats| stats.mean)
         .unwrap_or(0.0);
 
+    let reference_interval = function_stats
+        .first()
+        .and_then(|stats| stats.confidence_interval);
+
     // Calculate speedup factor relative to the reference function
     for stats in &mut function_stats {
         if reference_mean != 0.0 {
@@ -204,22 +477,105 @@ fn analyze_execution_results(results: HashMap<String, Vec<Duration>>) {
             stats.speedup = Some(speedup_factor);
         }
     }
+    for stats in function_stats.iter_mut().skip(1) {
+        stats.overlaps_fastest = match (stats.confidence_interval, reference_interval) {
+            (Some(interval), Some(reference)) => interval.overlaps(&reference),
+            _ => true,
+        };
+    }
+    function_stats
+}
 
-    for (rank, stats) in function_stats.iter().enumerate() {
-        let rank = rank + 1;
-        println!("Rank {}: Function: {}", rank, stats.name);
-        println!("Mean: {:.6} ms", stats.mean * 1000.0);
-        println!("Median: {:.6} ms", stats.median * 1000.0);
-        // println!("Standard Deviation: {:.6} ms", stats.std_deviation * 1000.0);
-        println!("25th Percentile: {:.6} ms", stats.percentile_25 * 1000.0);
-        println!("75th Percentile: {:.6} ms", stats.percentile_75 * 1000.0);
-
-        // if let Some(speedup_factor) = stats.speedup {
-        //     println!("Speedup: {:.2}x slower", 1.0 - speedup_factor);
-        // }
+/// The number of executions for which the margin of the interval would be
+/// [`TARGET_RELATIVE_MARGIN`], as it shrinks with the square root of the number
+/// of executions. `None` if the executions were enough.
+fn suggested_samples(
+    num_samples: usize,
+    interval: Option<ConfidenceInterval>,
+) -> Option<usize> {
+    let Some(interval) = interval else {
+        return Some(MIN_SAMPLES);
+    };
+    let ratio = interval.relative_margin() / TARGET_RELATIVE_MARGIN;
+    let needed = match ratio.is_finite() {
+        true => (num_samples as f32 * ratio * ratio).ceil() as usize,
+        false => num_samples.max(1) * 4,
+    };
+    let needed = needed.max(MIN_SAMPLES);
+    (needed > num_samples).then_some(needed)
+}
 
-        println!("---------------------------------");
+/// # Format execution report
+/// Writes the statistics returned by [`compare_execution_times`] as text, ranked
+/// in the given order.
+///
+/// ## Parameters:
+/// * `statistics`: The statistics of the functions.
+/// * `format`: The precision and the time unit of the report.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::benchmarks::testing_tools::{format_execution_report, ExecutionStatistics};
+/// use rec_rsys::formatting::{ReportFormat, TimeUnit};
+/// let statistics = ExecutionStatistics {
+///     name: "dot".to_string(),
+///     num_samples: 5,
+///     mean: 0.000_002,
+///     standard_error: None,
+///     confidence_interval: None,
+///     suggested_samples: Some(10),
+///     overlaps_fastest: false,
+///     median: 0.000_002,
+///     std_deviation: 0.0,
+///     percentile_25: 0.000_001,
+///     percentile_75: 0.000_003,
+///     speedup: Some(1.0),
+/// };
+/// let format = ReportFormat::default()
+///     .set_precision(1)
+///     .set_time_unit(Some(TimeUnit::Microseconds))
+///     .set_ascii(true);
+/// let report = format_execution_report(&[statistics], &format);
+/// assert!(report.contains("Mean: 2.0 us\n"));
+/// assert!(report.contains("run at least 10"));
+/// ```
+pub fn format_execution_report(
+    statistics: &[ExecutionStatistics],
+    format: &ReportFormat,
+) -> String {
+    let mut report = String::new();
+    for (rank, stats) in statistics.iter().enumerate() {
+        let rank = rank + 1;
+        let duration = |secs: f32| format.duration(secs as f64);
+        report += &format!("Rank {}: Function: {}\n", rank, stats.name);
+        report += &format!("Mean: {}\n", duration(stats.mean));
+        report += &format!("Median: {}\n", duration(stats.median));
+        report += &format!("Standard Deviation: {}\n", duration(stats.std_deviation));
+        report += &format!("25th Percentile: {}\n", duration(stats.percentile_25));
+        report += &format!("75th Percentile: {}\n", duration(stats.percentile_75));
+        if let Some(standard_error) = stats.standard_error {
+            report += &format!("Standard Error: {}\n", duration(standard_error));
+        }
+        if let Some(interval) = stats.confidence_interval {
+            report += &format!(
+                "{}% Confidence Interval: [{}, {}]\n",
+                interval.level * 100.0,
+                duration(interval.lower),
+                duration(interval.upper)
+            );
+        }
+        if let Some(needed) = stats.suggested_samples {
+            report += &format!(
+                "Warning: {} executions are not enough, run at least {}\n",
+                stats.num_samples, needed
+            );
+        }
+        if stats.overlaps_fastest {
+            report += "Warning: not significantly slower than the fastest function\n";
+        }
+        report += "---------------------------------\n";
     }
+    report
 }
 
 fn durations_to_f32s(durations: &[Duration]) -> Vec<f32> {
@@ -229,6 +585,7 @@ fn durations_to_f32s(durations: &[Duration]) -> Vec<f32> {
         .collect()
 }
 
+#[cfg(feature = "unstable")]
 pub fn test_implementation() {
     // for v in [5, 6, 7, 8, 9, 10, 20] {
     //     let mut matrix = Vec::new();
@@ -263,10 +620,12 @@ pub fn test_implementation() {
     //     println!("---------------------------------");
     // }
 }
+#[cfg(test)]
 struct NewCustomRng {
     state: u64,
 }
 
+#[cfg(test)]
 impl NewCustomRng {
     fn new() -> NewCustomRng {
         NewCustomRng {
@@ -302,6 +661,83 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_measure() {
+        let config = MeasureConfig::default()
+            .set_warm_up_iterations(3)
+            .set_min_iterations(20)
+            .set_min_duration(Duration::ZERO);
+        let mut calls = 0;
+        let measurement = measure(&config, || calls += 1).unwrap();
+        assert_eq!(calls, 23);
+        assert_eq!(measurement.samples.len() + measurement.outliers.len(), 20);
+
+        let capped = config.clone().set_min_duration(Duration::from_secs(3600));
+        let measurement = measure(&capped.set_max_iterations(50), || ()).unwrap();
+        assert_eq!(measurement.samples.len() + measurement.outliers.len(), 50);
+        assert!(measure(&config.set_min_iterations(0), || ()).is_err());
+    }
+
+    #[test]
+    fn test_reject_outliers() {
+        let mut samples: Vec<Duration> = [10, 11, 9, 10, 12, 10, 11, 9]
+            .iter()
+            .map(|micros| Duration::from_micros(*micros))
+            .collect();
+        samples.push(Duration::from_millis(5));
+        let measurement = reject_outliers(samples, 3.5);
+        assert_eq!(measurement.outliers, [Duration::from_millis(5)]);
+        assert_eq!(measurement.samples.len(), 8);
+        assert_eq!(measurement.median(), Duration::from_micros(10));
+        let constant = reject_outliers(vec![Duration::from_micros(1); 5], 3.5);
+        assert!(constant.outliers.is_empty());
+    }
+
+    #[test]
+    fn test_suggested_samples() {
+        let interval = |mean: f32, margin: f32| ConfidenceInterval {
+            mean,
+            lower: mean - margin,
+            upper: mean + margin,
+            level: CONFIDENCE_LEVEL,
+        };
+        assert_eq!(suggested_samples(100, Some(interval(1.0, 0.01))), None);
+        // Halving the margin takes four times the executions.
+        let needed = suggested_samples(100, Some(interval(1.0, 0.1))).unwrap();
+        assert!((400..=401).contains(&needed), "{}", needed);
+        assert_eq!(suggested_samples(4, Some(interval(1.0, 0.01))), Some(10));
+        assert_eq!(suggested_samples(1, None), Some(MIN_SAMPLES));
+    }
+
+    #[test]
+    fn test_analyze_execution_results() {
+        let durations = |micros: &[u64]| -> Vec<Duration> {
+            micros.iter().map(|m| Duration::from_micros(*m)).collect()
+        };
+        let results = HashMap::from([
+            ("fast".to_string(), durations(&[10; 20])),
+            (
+                "noisy".to_string(),
+                durations(&[5, 30, 10, 50, 12, 3, 40, 8]),
+            ),
+            (
+                "slow".to_string(),
+                durations(&[100, 101, 99, 100, 100, 101, 99, 100, 100, 100]),
+            ),
+        ]);
+        let statistics = analyze_execution_results(results);
+        let names: Vec<&str> = statistics.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["fast", "noisy", "slow"]);
+        assert_eq!(statistics[0].suggested_samples, None);
+        assert!(!statistics[0].overlaps_fastest);
+        assert!(statistics[1].suggested_samples.unwrap() > 8);
+        assert!(statistics[1].overlaps_fastest);
+        assert!(!statistics[2].overlaps_fastest);
+        assert_eq!(statistics[2].suggested_samples, None);
+        let report = format_execution_report(&statistics, &ReportFormat::default());
+        assert!(report.contains("95% Confidence Interval: [0.010000 ms, 0.010000 ms]"));
+    }
+
     #[test]
     fn random_f() {
         let mut rng = NewCustomRng::new();
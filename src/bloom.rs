@@ -0,0 +1,218 @@
+//! # Counting Bloom filters
+//! Probabilistic sets of ids whose size depends on the number of ids and the
+//! accepted error rate only, for membership checks on sets too large to keep
+//! exactly, such as the items seen by heavy users.
+
+use crate::errors::{Error, Result};
+use crate::ids::stable_hash;
+use crate::memory::MemoryFootprint;
+
+/// Largest value of a 4-bit counter, once reached the counter never changes.
+const MAX_COUNT: u8 = 15;
+
+/// # Counting Bloom filter
+/// A set of ids answering "maybe present" or "definitely absent": an id inserted
+/// is always found, an id never inserted is found with a small probability, the
+/// false positive rate. Every id increments `num_hashes` of the 4-bit counters, so
+/// unlike a plain Bloom filter ids can be removed again.
+///
+/// Sized for 1% of false positives, it takes about 4.8 bytes per id whatever the
+/// ids, while a bitset [`IdSet`](crate::sets::IdSet) takes one bit per id up to the
+/// largest one.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::bloom::CountingBloomFilter;
+/// let mut seen = CountingBloomFilter::with_capacity(1000, 0.01).unwrap();
+/// seen.insert(4_000_000_000);
+/// seen.insert(7);
+/// assert!(seen.contains(7));
+/// assert!(seen.remove(7));
+/// assert!(!seen.contains(7));
+/// assert!(seen.contains(4_000_000_000));
+/// assert_eq!(seen.len(), 1);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CountingBloomFilter {
+    /// Two counters per byte, the low nibble first.
+    counters: Vec<u8>,
+    num_counters: usize,
+    num_hashes: u32,
+    len: usize,
+}
+
+impl CountingBloomFilter {
+    /// # With capacity
+    /// An empty filter with the number of counters and hashes minimizing the
+    /// memory for the expected number of ids and false positive rate.
+    ///
+    /// ## Parameters:
+    /// * `capacity`: The number of ids expected. Inserting more raises the false
+    ///   positive rate above the target.
+    /// * `false_positive_rate`: The target probability of finding an id never
+    ///   inserted, between 0 and 1 exclusive.
+    pub fn with_capacity(capacity: usize, false_positive_rate: f64) -> Result<Self> {
+        if !(false_positive_rate > 0.0 && false_positive_rate < 1.0) {
+            return Err(Error::InvalidConfig(format!(
+                "false_positive_rate must be between 0 and 1 exclusive, got {}",
+                false_positive_rate
+            )));
+        }
+        let capacity = capacity.max(1) as f64;
+        let ln2 = std::f64::consts::LN_2;
+        let num_counters = (-capacity * false_positive_rate.ln() / (ln2 * ln2)).ceil();
+        let num_hashes = (num_counters / capacity * ln2).round().max(1.0);
+        Ok(CountingBloomFilter::new(
+            num_counters as usize,
+            num_hashes as u32,
+        ))
+    }
+
+    /// An empty filter with the given number of counters and hashes per id.
+    pub fn new(num_counters: usize, num_hashes: u32) -> Self {
+        let num_counters = num_counters.max(1);
+        CountingBloomFilter {
+            counters: vec![0; num_counters.div_ceil(2)],
+            num_counters,
+            num_hashes: num_hashes.max(1),
+            len: 0,
+        }
+    }
+
+    /// The counters of an id, by double hashing: `h1 + i * h2` for every hash `i`.
+    fn positions(&self, id: u32) -> impl Iterator<Item = usize> {
+        let hash = stable_hash(&id);
+        let (h1, h2) = (hash as u32 as u64, (hash >> 32) | 1);
+        let num_counters = self.num_counters as u64;
+        (0..self.num_hashes as u64)
+            .map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % num_counters) as usize)
+    }
+
+    fn counter(&self, position: usize) -> u8 {
+        (self.counters[position / 2] >> (4 * (position % 2))) & MAX_COUNT
+    }
+
+    fn set_counter(&mut self, position: usize, count: u8) {
+        let shift = 4 * (position % 2);
+        let byte = &mut self.counters[position / 2];
+        *byte = (*byte & !(MAX_COUNT << shift)) | (count << shift);
+    }
+
+    /// Adds an id. Inserting an id twice counts it twice, it then takes two
+    /// removals to forget it.
+    pub fn insert(&mut self, id: u32) {
+        let positions: Vec<usize> = self.positions(id).collect();
+        for position in positions {
+            let count = self.counter(position);
+            if count < MAX_COUNT {
+                self.set_counter(position, count + 1);
+            }
+        }
+        self.len += 1;
+    }
+
+    /// Whether the id may have been inserted: false means it was not.
+    pub fn contains(&self, id: u32) -> bool {
+        self.positions(id)
+            .all(|position| self.counter(position) > 0)
+    }
+
+    /// Removes an id if it may be present, returns whether it was. Removing an id
+    /// that was never inserted but is a false positive removes other ids with it,
+    /// so only ids known to be inserted should be removed.
+    pub fn remove(&mut self, id: u32) -> bool {
+        if !self.contains(id) {
+            return false;
+        }
+        let positions: Vec<usize> = self.positions(id).collect();
+        for position in positions {
+            let count = self.counter(position);
+            // A saturated counter lost track of its count, decrementing it could
+            // forget other ids.
+            if count < MAX_COUNT {
+                self.set_counter(position, count - 1);
+            }
+        }
+        self.len = self.len.saturating_sub(1);
+        true
+    }
+
+    /// The number of ids inserted and not removed.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.counters.iter_mut().for_each(|byte| *byte = 0);
+        self.len = 0;
+    }
+
+    /// The expected false positive rate with the current number of ids.
+    pub fn false_positive_rate(&self) -> f64 {
+        let k = self.num_hashes as f64;
+        let filled = 1.0 - (-k * self.len as f64 / self.num_counters as f64).exp();
+        filled.powf(k)
+    }
+}
+
+impl Extend<u32> for CountingBloomFilter {
+    fn extend<I: IntoIterator<Item = u32>>(&mut self, ids: I) {
+        ids.into_iter().for_each(|id| self.insert(id));
+    }
+}
+
+impl MemoryFootprint for CountingBloomFilter {
+    fn heap_size(&self) -> usize {
+        self.counters.heap_size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_false_positive_rate() {
+        let mut filter = CountingBloomFilter::with_capacity(10_000, 0.01).unwrap();
+        filter.extend(0..10_000);
+        assert!((0..10_000).all(|id| filter.contains(id)));
+        let false_positives = (1_000_000..1_100_000)
+            .filter(|id| filter.contains(*id))
+            .count();
+        assert!(false_positives < 1_500, "{}", false_positives);
+        assert!((filter.false_positive_rate() - 0.01).abs() < 0.002);
+        assert!(filter.memory_footprint() < 10_000 * 5 + 100);
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut filter = CountingBloomFilter::with_capacity(100, 0.01).unwrap();
+        filter.extend([1, 2, 3, 2]);
+        assert!(filter.remove(2));
+        assert!(filter.contains(2));
+        assert!(filter.remove(2));
+        assert!(!filter.contains(2));
+        assert!(!filter.remove(2));
+        assert!(filter.contains(1) && filter.contains(3));
+        assert_eq!(filter.len(), 2);
+        filter.clear();
+        assert!(filter.is_empty() && !filter.contains(1));
+    }
+
+    #[test]
+    fn test_saturated_counters_are_kept() {
+        let mut filter = CountingBloomFilter::new(1, 1);
+        (0..20).for_each(|_| filter.insert(5));
+        (0..20).for_each(|_| {
+            filter.remove(5);
+        });
+        assert!(filter.contains(5));
+        assert!(CountingBloomFilter::with_capacity(10, 0.0).is_err());
+        assert!(CountingBloomFilter::with_capacity(10, f64::NAN).is_err());
+    }
+}
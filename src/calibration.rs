@@ -0,0 +1,254 @@
+//! # Score calibration
+//! Ranking models such as BPR or implicit ALS output scores whose scale means
+//! nothing on its own. The calibrators here map them to pseudo-ratings on the scale
+//! users know, so the same "predicted rating" can be served whatever the backend.
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::dataset::Dataset;
+use crate::envelope::PersistedModel;
+use crate::errors::{Error, Result};
+use crate::memory::MemoryFootprint;
+use crate::recommender::{Recommendation, Recommender};
+
+pub use crate::dataset::RatingScale;
+
+/// How scores are mapped to ratings.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CalibrationMethod {
+    /// Linearly from the lowest and highest scores to the ends of the scale,
+    /// without looking at the ratings.
+    MinMax,
+    /// With the non-decreasing function that best fits the known ratings.
+    #[default]
+    Isotonic,
+}
+
+/// # Score calibrator
+/// A non-decreasing, piecewise linear mapping from scores to ratings.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::calibration::{CalibrationMethod, RatingScale, ScoreCalibrator};
+/// let scores = [0.1, 0.2, 0.4, 0.7, 0.9];
+/// let ratings = [1.0, 2.0, 3.0, 4.0, 5.0];
+/// let calibrator = ScoreCalibrator::fit(&scores, &ratings, CalibrationMethod::Isotonic, RatingScale::default()).unwrap();
+/// assert_eq!(calibrator.calibrate(0.7), 4.0);
+/// assert_eq!(calibrator.calibrate(0.55), 3.5);
+/// assert_eq!(calibrator.calibrate(10.0), 5.0);
+/// ```
+#[doc = include_str!("../docs/calibration/isotonic.md")]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScoreCalibrator {
+    scores: Vec<f32>,
+    ratings: Vec<f32>,
+    scale: RatingScale,
+}
+
+impl ScoreCalibrator {
+    /// Learns the mapping from scores to the ratings they were given.
+    ///
+    /// ## Parameters:
+    /// * `scores`: The scores of the model.
+    /// * `ratings`: The actual rating of each scored item.
+    /// * `method`: How the scores are mapped.
+    /// * `scale`: The scale of the pseudo-ratings.
+    pub fn fit(
+        scores: &[f32],
+        ratings: &[f32],
+        method: CalibrationMethod,
+        scale: RatingScale,
+    ) -> Result<Self> {
+        scale.validate()?;
+        if scores.is_empty() || scores.len() != ratings.len() {
+            return Err(Error::InvalidData(
+                "calibration needs as many ratings as scores, at least one".to_string(),
+            ));
+        }
+        let mut pairs: Vec<(f32, f32)> = scores
+            .iter()
+            .copied()
+            .zip(ratings.iter().copied())
+            .filter(|(score, rating)| score.is_finite() && rating.is_finite())
+            .collect();
+        pairs.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+        let (lowest, highest) = match (pairs.first(), pairs.last()) {
+            (Some(first), Some(last)) => (first.0, last.0),
+            _ => return Err(Error::InvalidData("every score is NaN".to_string())),
+        };
+        let (scores, ratings) = match method {
+            CalibrationMethod::MinMax => {
+                (vec![lowest, highest], vec![scale.min, scale.max])
+            },
+            CalibrationMethod::Isotonic => pool_adjacent_violators(&pairs),
+        };
+        Ok(ScoreCalibrator {
+            scores,
+            ratings,
+            scale,
+        })
+    }
+
+    /// Learns the mapping from the scores a recommender gives to the items of a
+    /// held-out dataset, for the items it recommends among the first `num_items`.
+    pub fn fit_recommender<R: Recommender + ?Sized>(
+        recommender: &R,
+        validation: &Dataset,
+        num_items: usize,
+        method: CalibrationMethod,
+        scale: RatingScale,
+    ) -> Result<Self> {
+        let mut scores = Vec::new();
+        let mut ratings = Vec::new();
+        for (user_id, rated) in validation.user_ratings() {
+            let rated: HashMap<u32, f32> = rated.into_iter().collect();
+            for recommendation in recommender.recommend(user_id, num_items) {
+                if let Some(rating) = rated.get(&recommendation.item_id) {
+                    scores.push(recommendation.score);
+                    ratings.push(*rating);
+                }
+            }
+        }
+        ScoreCalibrator::fit(&scores, &ratings, method, scale)
+    }
+
+    /// Maps a score to a pseudo-rating, interpolating between the known scores.
+    pub fn calibrate(&self, score: f32) -> f32 {
+        let upper = self.scores.partition_point(|s| *s < score);
+        let rating = if upper == 0 {
+            self.ratings[0]
+        } else if upper == self.scores.len() {
+            self.ratings[upper - 1]
+        } else {
+            let (x0, x1) = (self.scores[upper - 1], self.scores[upper]);
+            let (y0, y1) = (self.ratings[upper - 1], self.ratings[upper]);
+            y0 + (y1 - y0) * (score - x0) / (x1 - x0)
+        };
+        self.scale.apply(rating)
+    }
+
+    /// Replaces the scores of the recommendations by pseudo-ratings. The order is
+    /// kept since the mapping never decreases.
+    pub fn calibrate_recommendations(
+        &self,
+        recommendations: &[Recommendation],
+    ) -> Vec<Recommendation> {
+        recommendations
+            .iter()
+            .map(|r| Recommendation {
+                item_id: r.item_id,
+                score: self.calibrate(r.score),
+            })
+            .collect()
+    }
+}
+
+/// Fits the non-decreasing step function closest to the ratings, returning the
+/// mean score and rating of every block. Equal scores always share a block.
+fn pool_adjacent_violators(pairs: &[(f32, f32)]) -> (Vec<f32>, Vec<f32>) {
+    // (sum of scores, sum of ratings, count, highest score) of each block.
+    let mut blocks: Vec<(f32, f32, f32, f32)> = Vec::with_capacity(pairs.len());
+    for &(score, rating) in pairs {
+        blocks.push((score, rating, 1.0, score));
+        while blocks.len() > 1 {
+            let (s, r, n, last) = blocks[blocks.len() - 1];
+            let (ps, pr, pn, previous_last) = blocks[blocks.len() - 2];
+            if pr / pn < r / n && previous_last < s / n {
+                break;
+            }
+            blocks.pop();
+            *blocks.last_mut().unwrap() = (ps + s, pr + r, pn + n, last);
+        }
+    }
+    blocks.iter().map(|(s, r, n, _)| (s / n, r / n)).unzip()
+}
+
+impl PersistedModel for ScoreCalibrator {
+    const MODEL_TYPE: &'static str = "score_calibrator";
+}
+
+impl MemoryFootprint for ScoreCalibrator {
+    fn heap_size(&self) -> usize {
+        self.scores.heap_size() + self.ratings.heap_size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_isotonic() {
+        let scores = [0.1, 0.2, 0.3, 0.4, 0.5];
+        let ratings = [1.0, 3.0, 2.0, 4.0, 5.0];
+        let calibrator = ScoreCalibrator::fit(
+            &scores,
+            &ratings,
+            CalibrationMethod::Isotonic,
+            RatingScale::default(),
+        )
+        .unwrap();
+        assert_eq!(calibrator.scores, vec![0.1, 0.25, 0.4, 0.5]);
+        assert_eq!(calibrator.ratings, vec![1.0, 2.5, 4.0, 5.0]);
+        let calibrated: Vec<f32> =
+            scores.iter().map(|s| calibrator.calibrate(*s)).collect();
+        assert!(calibrated.windows(2).all(|w| w[0] <= w[1]));
+    }
+
+    #[test]
+    fn test_min_max() {
+        let calibrator = ScoreCalibrator::fit(
+            &[-2.0, 0.0, 2.0],
+            &[0.0; 3],
+            CalibrationMethod::MinMax,
+            RatingScale::new(0.0, 10.0).set_step(1.0),
+        )
+        .unwrap();
+        assert_eq!(calibrator.calibrate(0.0), 5.0);
+        assert_eq!(calibrator.calibrate(1.1), 8.0);
+        assert!(ScoreCalibrator::fit(
+            &[1.0],
+            &[],
+            CalibrationMethod::MinMax,
+            RatingScale::default()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_fit_recommender() {
+        use crate::algorithms::most_popular::MostPopular;
+        use crate::dataset::Rating;
+
+        let mut model = MostPopular::default();
+        model
+            .fit(&Dataset::new(vec![
+                Rating::new(1, 10, 1.0),
+                Rating::new(2, 10, 1.0),
+                Rating::new(3, 10, 1.0),
+                Rating::new(1, 11, 1.0),
+                Rating::new(4, 12, 1.0),
+            ]))
+            .unwrap();
+        let validation = Dataset::new(vec![
+            Rating::new(5, 10, 5.0),
+            Rating::new(5, 11, 3.0),
+            Rating::new(5, 12, 2.0),
+        ]);
+        let calibrator = ScoreCalibrator::fit_recommender(
+            &model,
+            &validation,
+            10,
+            CalibrationMethod::Isotonic,
+            RatingScale::default(),
+        )
+        .unwrap();
+        let calibrated = calibrator.calibrate_recommendations(&model.recommend(5, 3));
+        assert_eq!(
+            calibrated.iter().map(|r| r.score).collect::<Vec<f32>>(),
+            vec![5.0, 2.5, 2.5]
+        );
+    }
+}
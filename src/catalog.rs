@@ -0,0 +1,760 @@
+//! # Item catalog
+//! Typed metadata of the items, validated against a schema, shared by everything
+//! that needs more than the item id (encoders, filters, explanations...), and the
+//! period each item may be recommended in.
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::dataset::Dataset;
+use crate::errors::{Error, Result};
+use crate::memory::MemoryFootprint;
+use crate::models::{one_hot_encode, sum_encoding_vectors, Item};
+use crate::recommender::{RankedItems, Recommendation, Recommender};
+use crate::sets::IdSet;
+use crate::similarity::TagWeights;
+
+/// Kind of value an attribute holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AttributeType {
+    /// One label out of a closed set, e.g. a country.
+    Categorical,
+    /// A number, e.g. a price.
+    Numeric,
+    /// Free text, e.g. a description.
+    Text,
+    /// Any number of labels, e.g. genres.
+    Tags,
+}
+
+/// Value of an attribute of an item.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AttributeValue {
+    Categorical(String),
+    Numeric(f32),
+    Text(String),
+    Tags(Vec<String>),
+}
+
+impl AttributeValue {
+    pub fn attribute_type(&self) -> AttributeType {
+        match self {
+            AttributeValue::Categorical(_) => AttributeType::Categorical,
+            AttributeValue::Numeric(_) => AttributeType::Numeric,
+            AttributeValue::Text(_) => AttributeType::Text,
+            AttributeValue::Tags(_) => AttributeType::Tags,
+        }
+    }
+
+    pub fn as_numeric(&self) -> Option<f32> {
+        match self {
+            AttributeValue::Numeric(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// The label of a categorical value or the content of a text value.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            AttributeValue::Categorical(value) | AttributeValue::Text(value) => {
+                Some(value)
+            },
+            _ => None,
+        }
+    }
+
+    /// The labels of a categorical or a tags value.
+    pub fn labels(&self) -> Vec<&str> {
+        match self {
+            AttributeValue::Categorical(value) => vec![value.as_str()],
+            AttributeValue::Tags(tags) => tags.iter().map(String::as_str).collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// The attributes of an item by name.
+pub type Attributes = BTreeMap<String, AttributeValue>;
+
+/// Declaration of an attribute of the schema.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AttributeSpec {
+    pub attribute_type: AttributeType,
+    /// Whether every item must have the attribute.
+    pub required: bool,
+}
+
+/// # Schema
+/// The attributes the items of a catalog may have.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Schema {
+    pub attributes: BTreeMap<String, AttributeSpec>,
+}
+
+impl Schema {
+    pub fn new() -> Self {
+        Schema::default()
+    }
+
+    /// Declares an attribute.
+    pub fn attribute(
+        mut self,
+        name: &str,
+        attribute_type: AttributeType,
+        required: bool,
+    ) -> Self {
+        self.attributes.insert(
+            name.to_string(),
+            AttributeSpec {
+                attribute_type,
+                required,
+            },
+        );
+        self
+    }
+
+    /// Checks that the attributes are declared, have the declared type and that
+    /// none of the required ones is missing.
+    pub fn validate(&self, attributes: &Attributes) -> Result<()> {
+        for (name, value) in attributes {
+            match self.attributes.get(name) {
+                None => return Err(invalid(format!("unknown attribute `{}`", name))),
+                Some(spec) if spec.attribute_type != value.attribute_type() => {
+                    return Err(invalid(format!(
+                        "attribute `{}` must be {:?}, got {:?}",
+                        name,
+                        spec.attribute_type,
+                        value.attribute_type()
+                    )))
+                },
+                Some(_) => {},
+            }
+        }
+        match self
+            .attributes
+            .iter()
+            .find(|(name, spec)| spec.required && !attributes.contains_key(*name))
+        {
+            Some((name, _)) => {
+                Err(invalid(format!("missing required attribute `{}`", name)))
+            },
+            None => Ok(()),
+        }
+    }
+}
+
+fn invalid(message: String) -> Error {
+    Error::InvalidData(message)
+}
+
+/// # Validity
+/// The period an item may be recommended in, e.g. a limited offer or an event,
+/// bounded by timestamps in seconds. The item is valid from `valid_from` included
+/// until `valid_until` excluded, an unset bound does not limit the period.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::catalog::Validity;
+/// let offer = Validity::with_ttl(1_000, 60);
+/// assert!(offer.is_valid_at(1_000));
+/// assert!(offer.has_expired(1_060));
+/// assert!(!Validity::between(Some(2_000), None).is_valid_at(1_000));
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Validity {
+    pub valid_from: Option<u64>,
+    pub valid_until: Option<u64>,
+}
+
+impl Validity {
+    pub fn between(valid_from: Option<u64>, valid_until: Option<u64>) -> Self {
+        Validity {
+            valid_from,
+            valid_until,
+        }
+    }
+
+    /// Valid from `now` for `ttl` seconds.
+    pub fn with_ttl(now: u64, ttl: u64) -> Self {
+        Validity::between(Some(now), Some(now.saturating_add(ttl)))
+    }
+
+    /// Rejects the periods ending before they start.
+    pub fn validate(&self) -> Result<()> {
+        match (self.valid_from, self.valid_until) {
+            (Some(from), Some(until)) if until < from => Err(invalid(format!(
+                "the validity ends at {} before it starts at {}",
+                until, from
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn is_valid_at(&self, now: u64) -> bool {
+        self.valid_from.is_none_or(|from| from <= now) && !self.has_expired(now)
+    }
+
+    /// Whether the period is over, the item will never be valid again.
+    pub fn has_expired(&self, now: u64) -> bool {
+        self.valid_until.is_some_and(|until| until <= now)
+    }
+}
+
+/// # Item catalog
+/// Maps the item ids to their attributes, every insertion being validated against
+/// the schema.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::catalog::{AttributeType, AttributeValue, Attributes, ItemCatalog, Schema};
+/// let schema = Schema::new()
+///     .attribute("price", AttributeType::Numeric, true)
+///     .attribute("genres", AttributeType::Tags, false);
+/// let mut catalog = ItemCatalog::new(schema);
+/// let attributes = Attributes::from([
+///     ("price".to_string(), AttributeValue::Numeric(9.99)),
+///     ("genres".to_string(), AttributeValue::Tags(vec!["rock".to_string()])),
+/// ]);
+/// catalog.insert(1, attributes).unwrap();
+/// assert!(catalog.insert(2, Attributes::new()).is_err());
+/// assert_eq!(catalog.attribute(1, "price").and_then(|v| v.as_numeric()), Some(9.99));
+/// ```
+///
+/// The items may be given a [`Validity`], the ones outside of it are never
+/// recommended by an [`AvailableRecommender`] and the expired ones are removed by
+/// [`ItemCatalog::sweep`], which remembers them as unavailable:
+/// ```
+/// use rec_rsys::catalog::{Attributes, ItemCatalog, Schema, Validity};
+/// let mut catalog = ItemCatalog::new(Schema::new());
+/// catalog.insert(1, Attributes::new()).unwrap();
+/// catalog.insert(2, Attributes::new()).unwrap();
+/// catalog.set_validity(2, Validity::with_ttl(0, 3_600)).unwrap();
+/// assert!(!catalog.is_available(2, 7_200));
+/// assert_eq!(catalog.sweep(7_200), [2]);
+/// assert!(!catalog.contains(2));
+/// assert!(!catalog.is_available(2, 7_200));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ItemCatalog {
+    schema: Schema,
+    items: BTreeMap<u32, Attributes>,
+    #[serde(default)]
+    validity: BTreeMap<u32, Validity>,
+    /// The expired items removed by a sweep, never available again unless they are
+    /// inserted back.
+    #[serde(default)]
+    swept: IdSet,
+}
+
+impl ItemCatalog {
+    pub fn new(schema: Schema) -> Self {
+        ItemCatalog {
+            schema,
+            items: BTreeMap::new(),
+            validity: BTreeMap::new(),
+            swept: IdSet::new(),
+        }
+    }
+
+    pub fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    /// Adds or replaces an item, after validating its attributes. A swept item
+    /// inserted back is available again.
+    pub fn insert(&mut self, item_id: u32, attributes: Attributes) -> Result<()> {
+        self.schema
+            .validate(&attributes)
+            .map_err(|error| invalid(format!("item {}: {}", item_id, error)))?;
+        self.swept.remove(item_id);
+        self.items.insert(item_id, attributes);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, item_id: u32) -> Option<Attributes> {
+        self.validity.remove(&item_id);
+        self.items.remove(&item_id)
+    }
+
+    /// Limits the period an item of the catalog may be recommended in, replacing
+    /// its previous validity.
+    pub fn set_validity(&mut self, item_id: u32, validity: Validity) -> Result<()> {
+        if !self.contains(item_id) {
+            return Err(invalid(format!("item {} is not in the catalog", item_id)));
+        }
+        validity
+            .validate()
+            .map_err(|error| invalid(format!("item {}: {}", item_id, error)))?;
+        self.validity.insert(item_id, validity);
+        Ok(())
+    }
+
+    /// The validity of an item, `None` if it is always valid.
+    pub fn validity(&self, item_id: u32) -> Option<&Validity> {
+        self.validity.get(&item_id)
+    }
+
+    /// Whether the item may be recommended at `now`. The catalog only limits the
+    /// items it gave a validity to and the ones it swept, the other ones, known or
+    /// not, are available.
+    pub fn is_available(&self, item_id: u32, now: u64) -> bool {
+        !self.swept.contains(item_id)
+            && self
+                .validity
+                .get(&item_id)
+                .is_none_or(|validity| validity.is_valid_at(now))
+    }
+
+    /// The items whose validity is over at `now`, sorted.
+    pub fn expired(&self, now: u64) -> Vec<u32> {
+        self.validity
+            .iter()
+            .filter(|(_, validity)| validity.has_expired(now))
+            .map(|(item_id, _)| *item_id)
+            .collect()
+    }
+
+    /// # Sweep
+    /// Removes the expired items from the catalog, which keeps their ids to never
+    /// make them available again. The returned ids are to be removed from the
+    /// indexes built over the catalog as well, e.g. with
+    /// [`RetrievalIndex::retain`](crate::retrieval::RetrievalIndex::retain).
+    ///
+    /// ## Parameters:
+    /// * `now`: The current timestamp, in seconds.
+    ///
+    /// ## Returns:
+    /// * The ids of the removed items, sorted.
+    pub fn sweep(&mut self, now: u64) -> Vec<u32> {
+        let expired = self.expired(now);
+        for item_id in &expired {
+            self.remove(*item_id);
+            self.swept.insert(*item_id);
+        }
+        expired
+    }
+
+    pub fn get(&self, item_id: u32) -> Option<&Attributes> {
+        self.items.get(&item_id)
+    }
+
+    pub fn attribute(&self, item_id: u32, name: &str) -> Option<&AttributeValue> {
+        self.items.get(&item_id)?.get(name)
+    }
+
+    pub fn contains(&self, item_id: u32) -> bool {
+        self.items.contains_key(&item_id)
+    }
+
+    /// The item ids, sorted.
+    pub fn ids(&self) -> impl Iterator<Item = u32> + '_ {
+        self.items.keys().copied()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (u32, &Attributes)> + '_ {
+        self.items.iter().map(|(id, attributes)| (*id, attributes))
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// The distinct labels of a categorical or tags attribute, sorted.
+    pub fn labels(&self, name: &str) -> BTreeSet<String> {
+        self.items
+            .values()
+            .filter_map(|attributes| attributes.get(name))
+            .flat_map(|value| value.labels())
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// The tags of a categorical or tags attribute of every item that has it, each
+    /// weighing the number of times it appears, for
+    /// [`ContentBased::set_tags`](crate::algorithms::content_based::ContentBased::set_tags).
+    pub fn tag_weights(&self, name: &str) -> HashMap<u32, TagWeights> {
+        self.iter()
+            .filter_map(|(item_id, attributes)| {
+                let mut tags = TagWeights::new();
+                for label in attributes.get(name)?.labels() {
+                    *tags.entry(label.to_string()).or_default() += 1.0;
+                }
+                Some((item_id, tags))
+            })
+            .collect()
+    }
+
+    /// Encodes every item as an [`Item`]: the numeric attributes are passed through
+    /// (0 when missing) and the categorical and tags attributes are one-hot encoded
+    /// over their labels. Text attributes are ignored.
+    pub fn to_items(&self) -> Vec<Item> {
+        let encodings: Vec<(&String, Option<_>)> = self
+            .schema
+            .attributes
+            .iter()
+            .filter(|(_, spec)| spec.attribute_type != AttributeType::Text)
+            .map(|(name, spec)| match spec.attribute_type {
+                AttributeType::Numeric => (name, None),
+                _ => {
+                    let labels = self.labels(name);
+                    let labels: Vec<&str> = labels.iter().map(String::as_str).collect();
+                    (name, Some(one_hot_encode(&labels)))
+                },
+            })
+            .collect();
+
+        self.iter()
+            .map(|(item_id, attributes)| {
+                let mut values: Vec<f32> = Vec::new();
+                for (name, encoding) in &encodings {
+                    let value = attributes.get(*name);
+                    match encoding {
+                        None => {
+                            values.push(value.and_then(|v| v.as_numeric()).unwrap_or(0.0))
+                        },
+                        Some(encoding) => {
+                            let labels: Vec<String> = value
+                                .map(|v| {
+                                    v.labels().into_iter().map(str::to_string).collect()
+                                })
+                                .unwrap_or_default();
+                            values.extend(sum_encoding_vectors(encoding, &labels));
+                        },
+                    }
+                }
+                Item::new(item_id, values, None)
+            })
+            .collect()
+    }
+}
+
+/// The current unix timestamp, in seconds.
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+/// # Available recommender
+/// Wraps a recommender so that its candidates are checked against the validity of
+/// the items in a catalog: the items not available at the time of the request, not
+/// valid yet, expired or swept, are skipped as the ranking is consumed and the
+/// following ones take their place.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::algorithms::most_popular::MostPopular;
+/// use rec_rsys::catalog::{Attributes, AvailableRecommender, ItemCatalog, Schema, Validity};
+/// use rec_rsys::dataset::{Dataset, Rating};
+/// use rec_rsys::recommender::Recommender;
+/// let mut catalog = ItemCatalog::new(Schema::new());
+/// for item_id in [10, 11, 12] {
+///     catalog.insert(item_id, Attributes::new()).unwrap();
+/// }
+/// catalog.set_validity(10, Validity::with_ttl(0, 3_600)).unwrap();
+/// let mut model = AvailableRecommender::new(MostPopular::default(), catalog)
+///     .set_clock(|| 7_200);
+/// model.fit(&Dataset::new(vec![
+///     Rating::new(1, 10, 1.0), Rating::new(2, 10, 1.0), Rating::new(2, 11, 1.0),
+///     Rating::new(3, 12, 1.0),
+/// ])).unwrap();
+/// // Item 10, the most popular, expired.
+/// let ids: Vec<u32> = model.recommend(3, 2).iter().map(|r| r.item_id).collect();
+/// assert_eq!(ids, [11]);
+/// assert_eq!(model.catalog_mut().sweep(7_200), [10]);
+/// assert_eq!(model.recommend(3, 2).len(), 1);
+/// ```
+#[derive(Debug, Clone)]
+pub struct AvailableRecommender<R> {
+    inner: R,
+    catalog: ItemCatalog,
+    clock: fn() -> u64,
+}
+
+impl<R> AvailableRecommender<R> {
+    /// Checks the recommendations of `inner` against `catalog` at the current unix
+    /// time, in seconds.
+    pub fn new(inner: R, catalog: ItemCatalog) -> Self {
+        AvailableRecommender {
+            inner,
+            catalog,
+            clock: unix_now,
+        }
+    }
+
+    /// Replaces the clock giving the time of the requests, in the unit of the
+    /// validities of the catalog.
+    pub fn set_clock(mut self, clock: fn() -> u64) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    pub fn inner(&self) -> &R {
+        &self.inner
+    }
+
+    pub fn catalog(&self) -> &ItemCatalog {
+        &self.catalog
+    }
+
+    /// The catalog, to update the items and their validity or to sweep it.
+    pub fn catalog_mut(&mut self) -> &mut ItemCatalog {
+        &mut self.catalog
+    }
+}
+
+impl<R: Recommender> Recommender for AvailableRecommender<R> {
+    fn fit(&mut self, dataset: &Dataset) -> Result<()> {
+        self.inner.fit(dataset)
+    }
+
+    fn recommend(&self, user_id: u32, num_items: usize) -> Vec<Recommendation> {
+        let now = (self.clock)();
+        self.inner
+            .recommend_iter(user_id)
+            .filter(|r| self.catalog.is_available(r.item_id, now))
+            .take(num_items)
+            .collect()
+    }
+
+    fn recommend_iter(&self, user_id: u32) -> RankedItems {
+        let now = (self.clock)();
+        self.inner
+            .recommend_iter(user_id)
+            .filter(|r| self.catalog.is_available(r.item_id, now))
+            .collect()
+    }
+
+    fn predict(&self, user_id: u32, item_id: u32) -> Option<f32> {
+        self.inner.predict(user_id, item_id)
+    }
+}
+
+impl MemoryFootprint for AttributeType {}
+
+impl MemoryFootprint for AttributeValue {
+    fn heap_size(&self) -> usize {
+        match self {
+            AttributeValue::Categorical(value) | AttributeValue::Text(value) => {
+                value.heap_size()
+            },
+            AttributeValue::Numeric(_) => 0,
+            AttributeValue::Tags(tags) => tags.heap_size(),
+        }
+    }
+}
+
+impl MemoryFootprint for AttributeSpec {}
+
+impl MemoryFootprint for Schema {
+    fn heap_size(&self) -> usize {
+        self.attributes.heap_size()
+    }
+}
+
+impl MemoryFootprint for Validity {}
+
+impl MemoryFootprint for ItemCatalog {
+    fn heap_size(&self) -> usize {
+        self.schema.heap_size()
+            + self.items.heap_size()
+            + self.validity.heap_size()
+            + self.swept.heap_size()
+    }
+}
+
+impl<R: MemoryFootprint> MemoryFootprint for AvailableRecommender<R> {
+    fn heap_size(&self) -> usize {
+        self.inner.heap_size() + self.catalog.heap_size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schema() -> Schema {
+        Schema::new()
+            .attribute("country", AttributeType::Categorical, true)
+            .attribute("growth", AttributeType::Numeric, false)
+            .attribute("tags", AttributeType::Tags, false)
+            .attribute("description", AttributeType::Text, false)
+    }
+
+    fn attributes(country: &str, growth: f32, tags: &[&str]) -> Attributes {
+        Attributes::from([
+            (
+                "country".to_string(),
+                AttributeValue::Categorical(country.to_string()),
+            ),
+            ("growth".to_string(), AttributeValue::Numeric(growth)),
+            (
+                "tags".to_string(),
+                AttributeValue::Tags(tags.iter().map(|t| t.to_string()).collect()),
+            ),
+        ])
+    }
+
+    #[test]
+    fn test_schema_validate() {
+        let schema = schema();
+        assert!(schema.validate(&attributes("FR", 1.0, &[])).is_ok());
+        let wrong_type =
+            Attributes::from([("country".to_string(), AttributeValue::Numeric(1.0))]);
+        assert!(schema.validate(&wrong_type).is_err());
+        let unknown = Attributes::from([
+            (
+                "country".to_string(),
+                AttributeValue::Categorical("FR".to_string()),
+            ),
+            (
+                "color".to_string(),
+                AttributeValue::Categorical("red".to_string()),
+            ),
+        ]);
+        assert!(schema.validate(&unknown).is_err());
+        let missing =
+            Attributes::from([("growth".to_string(), AttributeValue::Numeric(1.0))]);
+        let error = schema.validate(&missing).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("missing required attribute `country`"));
+    }
+
+    #[test]
+    fn test_labels() {
+        let mut catalog = ItemCatalog::new(schema());
+        catalog
+            .insert(1, attributes("FR", 1.0, &["b", "a"]))
+            .unwrap();
+        catalog.insert(2, attributes("ES", 1.0, &["c"])).unwrap();
+        assert_eq!(
+            catalog.labels("country"),
+            BTreeSet::from(["ES".to_string(), "FR".to_string()])
+        );
+        assert_eq!(catalog.labels("tags").len(), 3);
+    }
+
+    #[test]
+    fn test_tag_weights() {
+        let mut catalog = ItemCatalog::new(schema());
+        catalog
+            .insert(1, attributes("FR", 1.0, &["a", "b", "a"]))
+            .unwrap();
+        catalog
+            .insert(
+                2,
+                Attributes::from([(
+                    "country".to_string(),
+                    AttributeValue::Categorical("ES".to_string()),
+                )]),
+            )
+            .unwrap();
+        let tags = catalog.tag_weights("tags");
+        assert_eq!(tags.len(), 1);
+        assert_eq!(tags[&1]["a"], 2.0);
+        assert_eq!(catalog.tag_weights("country")[&2]["ES"], 1.0);
+    }
+
+    #[test]
+    fn test_validity() {
+        let mut catalog = ItemCatalog::new(schema());
+        for item_id in 1..=4 {
+            catalog.insert(item_id, attributes("FR", 1.0, &[])).unwrap();
+        }
+        catalog
+            .set_validity(1, Validity::with_ttl(100, 50))
+            .unwrap();
+        catalog
+            .set_validity(2, Validity::between(Some(120), None))
+            .unwrap();
+        catalog
+            .set_validity(3, Validity::between(None, Some(110)))
+            .unwrap();
+        assert!(catalog.set_validity(5, Validity::default()).is_err());
+        assert!(catalog
+            .set_validity(4, Validity::between(Some(10), Some(5)))
+            .is_err());
+
+        let available = |now| -> Vec<u32> {
+            catalog
+                .ids()
+                .filter(|id| catalog.is_available(*id, now))
+                .collect()
+        };
+        assert_eq!(available(50), [3, 4]);
+        assert_eq!(available(100), [1, 3, 4]);
+        assert_eq!(available(130), [1, 2, 4]);
+        assert_eq!(available(150), [2, 4]);
+        assert!(catalog.is_available(99, 150));
+
+        assert_eq!(catalog.expired(120), [3]);
+        assert_eq!(catalog.sweep(150), [1, 3]);
+        assert_eq!(catalog.ids().collect::<Vec<u32>>(), [2, 4]);
+        assert_eq!(catalog.validity(1), None);
+        assert!(catalog.sweep(150).is_empty());
+        // The swept items stay unavailable until they are inserted back.
+        assert!(!catalog.is_available(1, 150) && !catalog.is_available(3, 0));
+        catalog.insert(3, attributes("FR", 1.0, &[])).unwrap();
+        assert!(catalog.is_available(3, 150));
+
+        let json = serde_json::to_string(&catalog).unwrap();
+        let restored: ItemCatalog = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored, catalog);
+    }
+
+    #[test]
+    fn test_available_recommender() {
+        use crate::algorithms::most_popular::MostPopular;
+        use crate::dataset::Rating;
+
+        let mut catalog = ItemCatalog::new(Schema::new());
+        for item_id in 1..=4 {
+            catalog.insert(item_id, Attributes::new()).unwrap();
+        }
+        catalog.set_validity(1, Validity::with_ttl(0, 100)).unwrap();
+        catalog
+            .set_validity(2, Validity::between(Some(200), None))
+            .unwrap();
+        let mut model =
+            AvailableRecommender::new(MostPopular::default(), catalog).set_clock(|| 150);
+        // Item 1 is the most popular, then 2, 3 and 4.
+        let ratings = (1..=4)
+            .flat_map(|item_id| {
+                (item_id..=4).map(move |user| Rating::new(user, item_id, 1.0))
+            })
+            .chain([Rating::new(9, 5, 1.0)])
+            .collect();
+        model.fit(&Dataset::new(ratings)).unwrap();
+        let ids = |model: &AvailableRecommender<MostPopular>| -> Vec<u32> {
+            model.recommend(9, 2).iter().map(|r| r.item_id).collect()
+        };
+        assert_eq!(ids(&model), [3, 4]);
+        assert_eq!(model.recommend_iter(9).count(), 2);
+        assert_eq!(model.catalog_mut().sweep(150), [1]);
+        assert!(!model.catalog().contains(1));
+        assert_eq!(ids(&model), [3, 4]);
+        assert_eq!(model.inner().recommend(9, 1)[0].item_id, 1);
+    }
+
+    #[test]
+    fn test_to_items() {
+        let mut catalog = ItemCatalog::new(schema());
+        catalog
+            .insert(1, attributes("FR", 0.5, &["a", "b"]))
+            .unwrap();
+        catalog.insert(2, attributes("ES", 2.0, &["b"])).unwrap();
+        let items = catalog.to_items();
+        // country (ES, FR), growth, tags (a, b)
+        assert_eq!(items[0].values, vec![0.0, 1.0, 0.5, 1.0, 1.0]);
+        assert_eq!(items[1].values, vec![1.0, 0.0, 2.0, 0.0, 1.0]);
+    }
+}
@@ -0,0 +1,125 @@
+//! # Compressed inputs
+//! Opens data files whether they are compressed or not, so the loaders read the
+//! `.gz` and `.zst` archives benchmark datasets are distributed as without
+//! unpacking them first. Each decoder is behind its own feature, `gzip` and `zstd`.
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use crate::errors::{Error, Result};
+
+/// The compression of a file, guessed from its extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    /// ## Examples:
+    /// ```
+    /// use rec_rsys::compression::Compression;
+    /// assert_eq!(Compression::from_path("ratings.csv.gz"), Compression::Gzip);
+    /// assert_eq!(Compression::from_path("ratings.csv"), Compression::None);
+    /// ```
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Self {
+        match path.as_ref().extension().and_then(|e| e.to_str()) {
+            Some("gz") => Compression::Gzip,
+            Some("zst") => Compression::Zstd,
+            _ => Compression::None,
+        }
+    }
+}
+
+/// Opens a file for buffered reading, decompressing it on the fly according to its
+/// extension.
+///
+/// ## Errors:
+/// * The file cannot be opened.
+/// * The file is compressed and the feature of its decoder is disabled.
+pub fn open<P: AsRef<Path>>(path: P) -> Result<Box<dyn BufRead>> {
+    let path = path.as_ref();
+    let compression = Compression::from_path(path);
+    let file = File::open(path)?;
+    match compression {
+        Compression::None => Ok(Box::new(BufReader::new(file))),
+        #[cfg(feature = "gzip")]
+        Compression::Gzip => Ok(Box::new(BufReader::new(
+            flate2::read::MultiGzDecoder::new(file),
+        ))),
+        #[cfg(feature = "zstd")]
+        Compression::Zstd => Ok(Box::new(BufReader::new(zstd::Decoder::new(file)?))),
+        #[allow(unreachable_patterns)]
+        _ => Err(Error::InvalidData(format!(
+            "{} is compressed, enable the `{}` feature to read it",
+            path.display(),
+            match compression {
+                Compression::Zstd => "zstd",
+                _ => "gzip",
+            }
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::*;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("rec_rsys_{}_{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn test_plain_file() {
+        let path = temp_path("plain.csv");
+        std::fs::write(&path, "1,10,4.0\n").unwrap();
+        let mut content = String::new();
+        open(&path).unwrap().read_to_string(&mut content).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(content, "1,10,4.0\n");
+        assert!(open(temp_path("missing.csv")).is_err());
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_gzip() {
+        use std::io::Write;
+        let path = temp_path("ratings.csv.gz");
+        let mut encoder = flate2::write::GzEncoder::new(
+            File::create(&path).unwrap(),
+            Default::default(),
+        );
+        encoder.write_all(b"1,10,4.0\n2,11,3.0\n").unwrap();
+        encoder.finish().unwrap();
+        let dataset =
+            crate::dataset::Dataset::from_csv(&path, &Default::default()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(dataset.len(), 2);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_zstd() {
+        use std::io::Write;
+        let path = temp_path("ratings.csv.zst");
+        let compressed = zstd::encode_all(&b"1,10,4.0\n"[..], 3).unwrap();
+        File::create(&path).unwrap().write_all(&compressed).unwrap();
+        let dataset =
+            crate::dataset::Dataset::from_csv(&path, &Default::default()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(dataset.len(), 1);
+    }
+
+    #[cfg(not(feature = "zstd"))]
+    #[test]
+    fn test_missing_decoder() {
+        let path = temp_path("ratings.csv.zst");
+        File::create(&path).unwrap();
+        let error = open(&path).err().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(error.to_string().contains("enable the `zstd` feature"));
+    }
+}
@@ -0,0 +1,522 @@
+//! # Experiments described in configuration files
+//! An experiment names a ratings file, the preprocessing applied to it, the algorithm
+//! to train and how to evaluate it, so it can be versioned and re-run as is.
+//!
+//! ```toml
+//! name = "item-knn-movielens"
+//!
+//! [dataset]
+//! path = "ml-100k/u.data"
+//! delimiter = "\t"
+//!
+//! [[preprocessing]]
+//! step = "min_ratings"
+//! users = 5
+//! items = 5
+//!
+//! [algorithm]
+//! name = "item_knn"
+//! num_neighbors = 20
+//! algorithm = "cosine"
+//!
+//! [evaluation]
+//! test_ratio = 0.2
+//! num_recommendations = 10
+//! relevance_threshold = 4.0
+//! ```
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::accuracy::{mae, rmse};
+use crate::algorithms::als::{ImplicitALS, ImplicitALSConfig};
+use crate::algorithms::baseline::{BaselineConfig, BaselineOnly};
+use crate::algorithms::coclustering::{CoClustering, CoClusteringConfig};
+use crate::algorithms::config::{ensure, AlgorithmConfig};
+use crate::algorithms::cooccurrence::{CoOccurrence, CoOccurrenceConfig};
+use crate::algorithms::funk_svd::{FunkSVD, FunkSVDConfig};
+use crate::algorithms::item_knn::ItemKNN;
+use crate::algorithms::knn::KNNConfig;
+use crate::algorithms::mf::{MFConfig, MatrixFactorization};
+use crate::algorithms::most_popular::MostPopular;
+use crate::algorithms::slope_one::{SlopeOne, SlopeOneConfig};
+use crate::algorithms::svdpp::{SVDpp, SVDppConfig};
+use crate::algorithms::user_knn::UserKNN;
+use crate::dataset::{CsvOptions, Dataset, RatingScale};
+use crate::errors::{Error, Result};
+use crate::evaluation::runs::{dataset_hash, EvaluationRun};
+use crate::evaluation::{TopNEvaluator, TopNReport};
+use crate::recommender::Recommender;
+
+/// Where the ratings of the experiment are read from.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DatasetConfig {
+    /// Path of the delimited ratings file, see [`Dataset::from_csv`].
+    pub path: PathBuf,
+    #[serde(flatten)]
+    pub csv: CsvOptions,
+    /// The scale the ratings are checked against, see [`Dataset::with_scale`].
+    #[serde(default)]
+    pub scale: Option<RatingScale>,
+}
+
+/// A transformation applied to the whole dataset before splitting it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "step", rename_all = "snake_case")]
+pub enum Preprocessing {
+    /// See [`Dataset::filter_min_ratings`].
+    MinRatings { users: usize, items: usize },
+    /// See [`Dataset::binarize`].
+    Binarize { threshold: f32 },
+}
+
+impl Preprocessing {
+    pub fn apply(&self, dataset: &Dataset) -> Dataset {
+        match self {
+            Preprocessing::MinRatings { users, items } => {
+                dataset.filter_min_ratings(*users, *items)
+            },
+            Preprocessing::Binarize { threshold } => dataset.binarize(*threshold),
+        }
+    }
+}
+
+/// The algorithm to train, with its hyperparameters.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "name", rename_all = "snake_case")]
+pub enum AlgorithmSpec {
+    MostPopular,
+    BaselineOnly(BaselineConfig),
+    ItemKnn(KNNConfig),
+    UserKnn(KNNConfig),
+    Mf(MFConfig),
+    FunkSvd(FunkSVDConfig),
+    Svdpp(SVDppConfig),
+    SlopeOne(SlopeOneConfig),
+    Coclustering(CoClusteringConfig),
+    Cooccurrence(CoOccurrenceConfig),
+    ImplicitAls(ImplicitALSConfig),
+}
+
+impl AlgorithmSpec {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AlgorithmSpec::MostPopular => "most_popular",
+            AlgorithmSpec::BaselineOnly(_) => "baseline_only",
+            AlgorithmSpec::ItemKnn(_) => "item_knn",
+            AlgorithmSpec::UserKnn(_) => "user_knn",
+            AlgorithmSpec::Mf(_) => "mf",
+            AlgorithmSpec::FunkSvd(_) => "funk_svd",
+            AlgorithmSpec::Svdpp(_) => "svdpp",
+            AlgorithmSpec::SlopeOne(_) => "slope_one",
+            AlgorithmSpec::Coclustering(_) => "coclustering",
+            AlgorithmSpec::Cooccurrence(_) => "cooccurrence",
+            AlgorithmSpec::ImplicitAls(_) => "implicit_als",
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            AlgorithmSpec::MostPopular => Ok(()),
+            AlgorithmSpec::BaselineOnly(config) => config.validate(),
+            AlgorithmSpec::ItemKnn(config) => config.validate(),
+            AlgorithmSpec::UserKnn(config) => config.validate(),
+            AlgorithmSpec::Mf(config) => config.validate(),
+            AlgorithmSpec::FunkSvd(config) => config.validate(),
+            AlgorithmSpec::Svdpp(config) => config.validate(),
+            AlgorithmSpec::SlopeOne(config) => config.validate(),
+            AlgorithmSpec::Coclustering(config) => config.validate(),
+            AlgorithmSpec::Cooccurrence(config) => config.validate(),
+            AlgorithmSpec::ImplicitAls(config) => config.validate(),
+        }
+    }
+
+    /// Creates the untrained recommender.
+    pub fn build(&self) -> Box<dyn Recommender + Sync> {
+        match self {
+            AlgorithmSpec::MostPopular => Box::<MostPopular>::default(),
+            AlgorithmSpec::BaselineOnly(config) => {
+                Box::new(BaselineOnly::new(config.clone()))
+            },
+            AlgorithmSpec::ItemKnn(config) => Box::new(ItemKNN::new(config.clone())),
+            AlgorithmSpec::UserKnn(config) => Box::new(UserKNN::new(config.clone())),
+            AlgorithmSpec::Mf(config) => {
+                Box::new(MatrixFactorization::new(config.clone()))
+            },
+            AlgorithmSpec::FunkSvd(config) => Box::new(FunkSVD::new(config.clone())),
+            AlgorithmSpec::Svdpp(config) => Box::new(SVDpp::new(config.clone())),
+            AlgorithmSpec::SlopeOne(config) => Box::new(SlopeOne::new(config.clone())),
+            AlgorithmSpec::Coclustering(config) => {
+                Box::new(CoClustering::new(config.clone()))
+            },
+            AlgorithmSpec::Cooccurrence(config) => {
+                Box::new(CoOccurrence::new(config.clone()))
+            },
+            AlgorithmSpec::ImplicitAls(config) => {
+                Box::new(ImplicitALS::new(config.clone()))
+            },
+        }
+    }
+
+    /// The hyperparameters, as recorded in the [`EvaluationRun`].
+    fn hyperparameters(&self) -> Vec<(String, Value)> {
+        match serde_json::to_value(self) {
+            Ok(Value::Object(fields)) => fields
+                .into_iter()
+                .filter(|(name, _)| name != "name")
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// How the trained algorithm is evaluated.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EvaluationProtocol {
+    /// Fraction of the ratings held out for testing.
+    pub test_ratio: f32,
+    /// Seed of the random train/test split.
+    pub seed: u64,
+    /// Length of the recommended lists.
+    pub num_recommendations: usize,
+    /// Minimum rating for a test item to count as relevant, all of them when `None`.
+    pub relevance_threshold: Option<f32>,
+}
+
+impl Default for EvaluationProtocol {
+    fn default() -> Self {
+        EvaluationProtocol {
+            test_ratio: 0.2,
+            seed: 42,
+            num_recommendations: 10,
+            relevance_threshold: None,
+        }
+    }
+}
+
+/// # Experiment configuration
+/// A whole experiment, from the ratings file to the evaluation.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExperimentConfig {
+    pub name: String,
+    pub dataset: DatasetConfig,
+    #[serde(default)]
+    pub preprocessing: Vec<Preprocessing>,
+    pub algorithm: AlgorithmSpec,
+    #[serde(default)]
+    pub evaluation: EvaluationProtocol,
+}
+
+/// Outcome of [`ExperimentConfig::run`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExperimentResult {
+    /// The run, ready to be saved and compared with other runs.
+    pub run: EvaluationRun,
+    /// The detailed top-N evaluation.
+    pub report: TopNReport,
+}
+
+impl ExperimentConfig {
+    pub fn from_json(content: &str) -> Result<Self> {
+        let config: Self = serde_json::from_str(content)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn from_toml(content: &str) -> Result<Self> {
+        let config: Self = toml::from_str(content)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    #[cfg(feature = "yaml")]
+    pub fn from_yaml(content: &str) -> Result<Self> {
+        let config: Self = serde_yaml::from_str(content)
+            .map_err(|error| Error::Serialization(error.to_string()))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Loads an experiment from a `.json`, `.toml` or, with the `yaml` feature,
+    /// `.yaml` file. A relative dataset path is resolved from the file's directory.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)?;
+        let mut config = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => Self::from_json(&content)?,
+            Some("toml") => Self::from_toml(&content)?,
+            #[cfg(feature = "yaml")]
+            Some("yaml") | Some("yml") => Self::from_yaml(&content)?,
+            _ => {
+                return Err(Error::InvalidConfig(format!(
+                    "unsupported experiment file {}",
+                    path.display()
+                )))
+            },
+        };
+        if let Some(directory) = path.parent() {
+            config.dataset.path = directory.join(&config.dataset.path);
+        }
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        let protocol = &self.evaluation;
+        ensure(
+            protocol.test_ratio > 0.0 && protocol.test_ratio < 1.0,
+            "evaluation.test_ratio",
+            "between 0 and 1",
+        )?;
+        ensure(
+            protocol.num_recommendations > 0,
+            "evaluation.num_recommendations",
+            "greater than 0",
+        )?;
+        self.algorithm.validate()
+    }
+
+    /// Loads the dataset, preprocesses and splits it, trains the algorithm on the
+    /// train split and evaluates it on the test split.
+    pub fn run(&self) -> Result<ExperimentResult> {
+        let raw = fs::read(&self.dataset.path)?;
+        let dataset =
+            Dataset::parse_csv(&String::from_utf8_lossy(&raw), &self.dataset.csv)?;
+        self.run_on(&dataset, &dataset_hash(&raw))
+    }
+
+    /// Same as [`ExperimentConfig::run`] over an already loaded dataset.
+    pub fn run_on(
+        &self,
+        dataset: &Dataset,
+        dataset_hash: &str,
+    ) -> Result<ExperimentResult> {
+        self.validate()?;
+        let dataset = match self.dataset.scale {
+            Some(scale) => dataset.clone().with_scale(scale)?,
+            None => dataset.clone(),
+        };
+        let dataset = self
+            .preprocessing
+            .iter()
+            .fold(dataset, |dataset, step| step.apply(&dataset));
+        let protocol = &self.evaluation;
+        let (train, test) = dataset.split_random(protocol.test_ratio, protocol.seed);
+
+        let mut recommender = self.algorithm.build();
+        recommender.fit(&train)?;
+
+        let mut test_set: HashMap<u32, Vec<u32>> = HashMap::new();
+        test.ratings
+            .iter()
+            .filter(|r| protocol.relevance_threshold.is_none_or(|t| r.rating >= t))
+            .for_each(|r| test_set.entry(r.user_id).or_default().push(r.item_id));
+        let report = TopNEvaluator::new(protocol.num_recommendations).evaluate(
+            &test_set,
+            |user_id, num_items| {
+                recommender
+                    .recommend(user_id, num_items)
+                    .iter()
+                    .map(|r| r.item_id)
+                    .collect()
+            },
+        );
+
+        let mut run = EvaluationRun::new(self.algorithm.name())
+            .set_dataset_hash(dataset_hash)
+            .set_top_n_report(&report);
+        for (name, value) in self.algorithm.hyperparameters() {
+            run = run.set_hyperparameter(&name, value);
+        }
+        let (predicted, actual): (Vec<f32>, Vec<f32>) = test
+            .ratings
+            .iter()
+            .filter_map(|r| Some((recommender.predict(r.user_id, r.item_id)?, r.rating)))
+            .unzip();
+        // Implicit ALS predicts preferences, comparing them to the ratings would be
+        // meaningless.
+        let predicts_ratings = !matches!(self.algorithm, AlgorithmSpec::ImplicitAls(_));
+        if !predicted.is_empty() && predicts_ratings {
+            run = run
+                .set_metric("rmse", rmse(&predicted, &actual) as f64)
+                .set_metric("mae", mae(&predicted, &actual) as f64);
+        }
+        Ok(ExperimentResult { run, report })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dataset::Rating;
+
+    const EXPERIMENT: &str = r#"
+        name = "test"
+
+        [dataset]
+        path = "ratings.csv"
+
+        [[preprocessing]]
+        step = "min_ratings"
+        users = 2
+        items = 1
+
+        [algorithm]
+        name = "item_knn"
+        num_neighbors = 2
+
+        [evaluation]
+        test_ratio = 0.25
+        num_recommendations = 2
+    "#;
+
+    fn dataset() -> Dataset {
+        Dataset::new(
+            (1..=8)
+                .flat_map(|user_id| {
+                    (0..4).map(move |item| {
+                        Rating::new(user_id, 10 + item + user_id % 2, 4.0)
+                    })
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_from_toml() {
+        let config = ExperimentConfig::from_toml(EXPERIMENT).unwrap();
+        assert_eq!(config.dataset.csv, CsvOptions::default());
+        assert_eq!(
+            config.preprocessing,
+            vec![Preprocessing::MinRatings { users: 2, items: 1 }]
+        );
+        assert_eq!(
+            config.algorithm,
+            AlgorithmSpec::ItemKnn(KNNConfig::default().set_num_neighbors(2))
+        );
+        assert_eq!(config.evaluation.seed, 42);
+    }
+
+    #[test]
+    fn test_from_toml_invalid() {
+        let invalid = EXPERIMENT.replace("test_ratio = 0.25", "test_ratio = 1.5");
+        assert!(ExperimentConfig::from_toml(&invalid).is_err());
+    }
+
+    #[test]
+    fn test_run_on() {
+        let config = ExperimentConfig::from_toml(EXPERIMENT).unwrap();
+        let result = config.run_on(&dataset(), "hash").unwrap();
+        assert_eq!(result.run.algorithm, "item_knn");
+        assert_eq!(result.run.dataset_hash, "hash");
+        assert_eq!(result.run.hyperparameters["num_neighbors"], Value::from(2));
+        assert!(result.run.metrics.contains_key("hit_rate"));
+        assert!(result.report.users > 0);
+    }
+
+    #[test]
+    fn test_run_mf_records_errors() {
+        let mf = EXPERIMENT.replace(
+            "name = \"item_knn\"\n        num_neighbors = 2",
+            "name = \"mf\"\n        num_factors = 4\n        [algorithm.regularization]\n        kind = \"l1\"\n        lambda = 0.01",
+        );
+        let config = ExperimentConfig::from_toml(&mf).unwrap();
+        let result = config.run_on(&dataset(), "hash").unwrap();
+        assert_eq!(result.run.algorithm, "mf");
+        assert!(result.run.metrics.contains_key("rmse"));
+    }
+
+    #[test]
+    fn test_run_funk_svd_and_svdpp() {
+        let funk_svd = EXPERIMENT.replace(
+            "name = \"item_knn\"\n        num_neighbors = 2",
+            "name = \"funk_svd\"\n        num_factors = 4\n        [algorithm.early_stopping]\n        patience = 2",
+        );
+        let config = ExperimentConfig::from_toml(&funk_svd).unwrap();
+        let result = config.run_on(&dataset(), "hash").unwrap();
+        assert_eq!(result.run.algorithm, "funk_svd");
+        assert!(result.run.metrics.contains_key("rmse"));
+
+        let svdpp = EXPERIMENT.replace(
+            "name = \"item_knn\"\n        num_neighbors = 2",
+            "name = \"svdpp\"\n        num_factors = 4",
+        );
+        let result = ExperimentConfig::from_toml(&svdpp)
+            .unwrap()
+            .run_on(&dataset(), "hash")
+            .unwrap();
+        assert_eq!(result.run.algorithm, "svdpp");
+        assert_eq!(result.run.hyperparameters["num_factors"], Value::from(4));
+    }
+
+    #[test]
+    fn test_dataset_scale() {
+        let scaled = EXPERIMENT.replace(
+            "path = \"ratings.csv\"",
+            "path = \"ratings.csv\"\n        scale = { min = 1.0, max = 3.0 }",
+        );
+        let config = ExperimentConfig::from_toml(&scaled).unwrap();
+        assert_eq!(config.dataset.scale, Some(RatingScale::new(1.0, 3.0)));
+        assert!(config.run_on(&dataset(), "hash").is_err());
+    }
+
+    #[test]
+    fn test_run_implicit_als() {
+        let als = EXPERIMENT.replace(
+            "name = \"item_knn\"\n        num_neighbors = 2",
+            "name = \"implicit_als\"\n        num_factors = 4\n        alpha = 10.0",
+        );
+        let config = ExperimentConfig::from_toml(&als).unwrap();
+        assert_eq!(
+            config.algorithm,
+            AlgorithmSpec::ImplicitAls(
+                ImplicitALSConfig::default()
+                    .set_num_factors(4)
+                    .set_alpha(10.0)
+            )
+        );
+        let result = config.run_on(&dataset(), "hash").unwrap();
+        assert_eq!(result.run.algorithm, "implicit_als");
+        assert_eq!(result.run.hyperparameters["alpha"], Value::from(10.0));
+        assert!(!result.run.metrics.contains_key("rmse"));
+    }
+
+    #[test]
+    fn test_run_user_knn() {
+        let user_knn = EXPERIMENT.replace("item_knn", "user_knn");
+        let config = ExperimentConfig::from_toml(&user_knn).unwrap();
+        assert_eq!(
+            config.algorithm,
+            AlgorithmSpec::UserKnn(KNNConfig::default().set_num_neighbors(2))
+        );
+        let result = config.run_on(&dataset(), "hash").unwrap();
+        assert_eq!(result.run.algorithm, "user_knn");
+        assert!(result.run.metrics.contains_key("rmse"));
+    }
+
+    #[test]
+    fn test_run_from_file() {
+        let directory = std::env::temp_dir().join("rec_rsys_test_experiment");
+        fs::create_dir_all(&directory).unwrap();
+        let content: String = dataset()
+            .ratings
+            .iter()
+            .map(|r| format!("{},{},{}\n", r.user_id, r.item_id, r.rating))
+            .collect();
+        fs::write(directory.join("ratings.csv"), content).unwrap();
+        fs::write(
+            directory.join("experiment.toml"),
+            EXPERIMENT.replace("item_knn", "most_popular"),
+        )
+        .unwrap();
+        let result = ExperimentConfig::from_file(directory.join("experiment.toml"))
+            .unwrap()
+            .run()
+            .unwrap();
+        assert_eq!(result.run.algorithm, "most_popular");
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,1020 @@
+//! # Ratings datasets
+//! Loading, preprocessing and splitting of `(user, item, rating)` interactions.
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::io::BufRead;
+use std::path::Path;
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+
+use crate::algorithms::config::ensure;
+use crate::compression;
+use crate::errors::{Error, Result};
+use crate::ids::{stable_hash, IdMap};
+use crate::memory::MemoryFootprint;
+use crate::models::Item;
+use crate::parallelism::default_parallelism;
+use crate::parallelism::prelude::*;
+use crate::sampling::reservoir;
+use crate::sets::IdSet;
+
+/// Number of lines of a file parsed together by [`Dataset::from_reader`].
+const CSV_CHUNK_LINES: usize = 1 << 16;
+
+/// A single interaction between a user and an item.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Rating {
+    pub user_id: u32,
+    pub item_id: u32,
+    /// The explicit rating, or `1.0` for implicit feedback.
+    pub rating: f32,
+    /// Seconds since the unix epoch, when known.
+    pub timestamp: Option<u64>,
+}
+
+impl Rating {
+    pub fn new(user_id: u32, item_id: u32, rating: f32) -> Self {
+        Rating {
+            user_id,
+            item_id,
+            rating,
+            timestamp: None,
+        }
+    }
+
+    pub fn timestamp(mut self, timestamp: u64) -> Self {
+        self.timestamp = Some(timestamp);
+        self
+    }
+}
+
+/// # Rating scale
+/// The range of the ratings, optionally in steps such as half stars, or the
+/// strengths of implicit feedback. Datasets carry it to validate their ratings and
+/// the models trained on them clip their predictions to it.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::dataset::RatingScale;
+/// let stars = RatingScale::new(1.0, 5.0).set_step(0.5);
+/// assert!(stars.contains(4.5) && !stars.contains(4.2) && !stars.contains(6.0));
+/// assert_eq!(stars.clip(5.3), 5.0);
+/// assert_eq!(stars.normalize(3.0), 0.5);
+/// assert_eq!(stars.denormalize(0.5), 3.0);
+/// let clicks = RatingScale::implicit();
+/// assert!(clicks.contains(12.0) && !clicks.contains(-1.0));
+/// assert_eq!(clicks.clip(12.0), 12.0);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RatingScale {
+    pub min: f32,
+    pub max: f32,
+    /// The ratings are rounded to a multiple of the step above `min`, when set.
+    pub step: Option<f32>,
+    /// The ratings are strengths of implicit feedback, such as counts, only bounded
+    /// by `min`, and the predictions are preferences that are never clipped.
+    #[serde(default)]
+    pub implicit: bool,
+}
+
+impl Default for RatingScale {
+    fn default() -> Self {
+        RatingScale {
+            min: 1.0,
+            max: 5.0,
+            step: None,
+            implicit: false,
+        }
+    }
+}
+
+impl RatingScale {
+    pub fn new(min: f32, max: f32) -> Self {
+        RatingScale {
+            min,
+            max,
+            step: None,
+            implicit: false,
+        }
+    }
+
+    /// Implicit feedback: non-negative strengths, `1.0` once binarized.
+    pub fn implicit() -> Self {
+        RatingScale {
+            min: 0.0,
+            max: 1.0,
+            step: None,
+            implicit: true,
+        }
+    }
+
+    pub fn set_step(mut self, step: f32) -> Self {
+        self.step = Some(step);
+        self
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        ensure(self.min < self.max, "scale.min", "lower than scale.max")?;
+        ensure(
+            self.step.is_none_or(|step| step > 0.0),
+            "scale.step",
+            "greater than 0",
+        )
+    }
+
+    /// Whether a rating belongs to the scale, on one of its steps when it has some.
+    pub fn contains(&self, value: f32) -> bool {
+        if self.implicit {
+            return value.is_finite() && value >= self.min;
+        }
+        let on_step = self.step.is_none_or(|step| {
+            let steps = (value - self.min) / step;
+            (steps - steps.round()).abs() < 1e-4
+        });
+        (self.min..=self.max).contains(&value) && on_step
+    }
+
+    /// Clamps a prediction to the scale, implicit scales leave it as it is.
+    pub fn clip(&self, value: f32) -> f32 {
+        match self.implicit {
+            true => value,
+            false => value.clamp(self.min, self.max),
+        }
+    }
+
+    /// Clamps the value to the scale and rounds it to the step.
+    pub fn apply(&self, value: f32) -> f32 {
+        let value = value.clamp(self.min, self.max);
+        match self.step {
+            Some(step) => {
+                (self.min + ((value - self.min) / step).round() * step).min(self.max)
+            },
+            None => value,
+        }
+    }
+
+    /// Maps `min` to 0 and `max` to 1.
+    pub fn normalize(&self, value: f32) -> f32 {
+        (value - self.min) / (self.max - self.min)
+    }
+
+    /// The inverse of [`RatingScale::normalize`].
+    pub fn denormalize(&self, value: f32) -> f32 {
+        self.min + value * (self.max - self.min)
+    }
+}
+
+/// How a delimited ratings file is laid out. The columns must be
+/// `user, item, rating` optionally followed by a `timestamp`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CsvOptions {
+    /// Separator of the columns, e.g. `"\t"` for MovieLens 100K or `"::"` for 1M.
+    pub delimiter: String,
+    /// Whether the first line holds the column names.
+    pub has_header: bool,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        CsvOptions {
+            delimiter: ",".to_string(),
+            has_header: false,
+        }
+    }
+}
+
+/// How the events of a JSON lines log map to interactions. Every line is an
+/// object; fields are looked up by name, nested ones with a dotted path such as
+/// `"context.user"`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EventLogOptions {
+    /// Field of the user id, an integer or a string holding one unless
+    /// `intern_ids` is set.
+    pub user_field: String,
+    /// Field of the item id, an integer or a string holding one unless
+    /// `intern_ids` is set.
+    pub item_field: String,
+    /// Field of the type of event, e.g. `"click"` or `"purchase"`.
+    pub event_field: Option<String>,
+    /// Field of the timestamp, in seconds since the unix epoch.
+    pub timestamp_field: Option<String>,
+    /// Strength of the interaction of each type of event. Events of other types
+    /// are skipped; when empty every event is an interaction of strength `1.0`.
+    pub event_weights: HashMap<String, f32>,
+    /// Gives dense ids to the user and item ids, any string such as `"u123"` or
+    /// integer, in the order they come; the mapping is returned with the dataset,
+    /// see [`EventIds`].
+    pub intern_ids: bool,
+}
+
+/// The ids given to the users and items of an events log read with
+/// [`EventLogOptions::intern_ids`], empty otherwise.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EventIds {
+    pub users: IdMap,
+    pub items: IdMap,
+}
+
+impl Default for EventLogOptions {
+    fn default() -> Self {
+        EventLogOptions {
+            user_field: "user_id".to_string(),
+            item_field: "item_id".to_string(),
+            event_field: None,
+            timestamp_field: None,
+            event_weights: HashMap::new(),
+            intern_ids: false,
+        }
+    }
+}
+
+/// # Dataset
+/// A collection of ratings.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::dataset::{CsvOptions, Dataset};
+/// let dataset = Dataset::parse_csv("1,10,4.0\n1,11,2.0\n2,10,5.0\n", &CsvOptions::default()).unwrap();
+/// assert_eq!(dataset.len(), 3);
+/// let (train, test) = dataset.split_random(0.34, 42);
+/// assert_eq!(train.len() + test.len(), 3);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Dataset {
+    pub ratings: Vec<Rating>,
+    /// The scale the ratings were checked against, see [`Dataset::with_scale`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scale: Option<RatingScale>,
+}
+
+impl Dataset {
+    pub fn new(ratings: Vec<Rating>) -> Self {
+        Dataset {
+            ratings,
+            scale: None,
+        }
+    }
+
+    /// Attaches the scale of the ratings, which the datasets derived from this one
+    /// keep and the models trained on it clip their predictions to.
+    ///
+    /// ## Returns:
+    /// * The dataset, or an error naming the first rating out of the scale.
+    ///
+    /// ## Examples:
+    /// ```
+    /// use rec_rsys::dataset::{Dataset, Rating, RatingScale};
+    /// let ratings = vec![Rating::new(1, 10, 4.5), Rating::new(1, 11, 2.0)];
+    /// let stars = RatingScale::new(1.0, 5.0).set_step(0.5);
+    /// let dataset = Dataset::new(ratings.clone()).with_scale(stars).unwrap();
+    /// assert_eq!(dataset.split_random(0.5, 42).0.scale, Some(stars));
+    /// let whole_stars = RatingScale::new(1.0, 5.0).set_step(1.0);
+    /// assert!(Dataset::new(ratings).with_scale(whole_stars).is_err());
+    /// ```
+    pub fn with_scale(mut self, scale: RatingScale) -> Result<Self> {
+        scale.validate()?;
+        if let Some(r) = self.ratings.iter().find(|r| !scale.contains(r.rating)) {
+            return Err(Error::InvalidData(format!(
+                "rating {} of user {} on item {} is out of the scale [{}, {}]",
+                r.rating, r.user_id, r.item_id, scale.min, scale.max
+            )));
+        }
+        self.scale = Some(scale);
+        Ok(self)
+    }
+
+    /// Reads a delimited ratings file, see [`Dataset::from_reader`]. Files ending
+    /// in `.gz` or `.zst` are decompressed, see [`compression::open`].
+    pub fn from_csv<P: AsRef<Path>>(path: P, options: &CsvOptions) -> Result<Self> {
+        Dataset::from_reader(compression::open(path)?, options)
+    }
+
+    /// Reads delimited ratings, skipping the empty lines. The lines are read by
+    /// chunks parsed in parallel, so only one chunk of text is held in memory at a
+    /// time next to the ratings, whatever the size of the input.
+    pub fn from_reader<R: BufRead>(reader: R, options: &CsvOptions) -> Result<Self> {
+        let mut ratings = Vec::new();
+        let mut lines = reader
+            .lines()
+            .enumerate()
+            .skip(usize::from(options.has_header));
+        let mut chunk: Vec<(usize, String)> = Vec::with_capacity(CSV_CHUNK_LINES);
+        loop {
+            chunk.clear();
+            for (index, line) in lines.by_ref().take(CSV_CHUNK_LINES) {
+                chunk.push((index + 1, line?));
+            }
+            if chunk.is_empty() {
+                break;
+            }
+            let parsed: Vec<Result<Rating>> = default_parallelism().install(|| {
+                chunk
+                    .par_iter()
+                    .filter(|(_, line)| !line.trim().is_empty())
+                    .map(|(number, line)| parse_rating(line, &options.delimiter, *number))
+                    .collect()
+            });
+            // Reports the first invalid line of the chunk, not any of them.
+            for rating in parsed {
+                ratings.push(rating?);
+            }
+        }
+        Ok(Dataset::new(ratings))
+    }
+
+    /// Writes the ratings to a delimited file, see [`Dataset::to_csv`].
+    pub fn write_csv<P: AsRef<Path>>(&self, path: P, options: &CsvOptions) -> Result<()> {
+        Ok(fs::write(path, self.to_csv(options))?)
+    }
+
+    /// Formats the ratings in the layout [`Dataset::parse_csv`] reads. The timestamp
+    /// column is only written when a rating has one.
+    pub fn to_csv(&self, options: &CsvOptions) -> String {
+        let delimiter = &options.delimiter;
+        let has_timestamps = self.ratings.iter().any(|r| r.timestamp.is_some());
+        let mut lines: Vec<String> = Vec::with_capacity(self.len() + 1);
+        if options.has_header {
+            let columns: &[&str] = match has_timestamps {
+                true => &["user", "item", "rating", "timestamp"],
+                false => &["user", "item", "rating"],
+            };
+            lines.push(columns.join(delimiter));
+        }
+        lines.extend(self.ratings.iter().map(|r| {
+            let line = format!(
+                "{}{d}{}{d}{}",
+                r.user_id,
+                r.item_id,
+                r.rating,
+                d = delimiter
+            );
+            match (has_timestamps, r.timestamp) {
+                (true, Some(timestamp)) => format!("{}{}{}", line, delimiter, timestamp),
+                _ => line,
+            }
+        }));
+        lines.iter().map(|line| format!("{}\n", line)).collect()
+    }
+
+    /// Reads a JSON lines events log, see [`Dataset::from_events_reader`]. Files
+    /// ending in `.gz` or `.zst` are decompressed, see [`compression::open`].
+    pub fn from_events<P: AsRef<Path>>(
+        path: P,
+        options: &EventLogOptions,
+    ) -> Result<(Self, EventIds)> {
+        Dataset::from_events_reader(compression::open(path)?, options)
+    }
+
+    /// # Events log
+    /// Reads interaction events, one JSON object per line, as implicit feedback.
+    /// Empty lines and events of types without weight are skipped.
+    ///
+    /// ## Parameters:
+    /// * `reader`: The log.
+    /// * `options`: The fields holding the user, the item, the type of event and
+    ///   the timestamp, and the weight of each type of event.
+    ///
+    /// ## Returns:
+    /// * The interactions, with the weight of their event as rating, and the ids
+    ///   given to the users and items when `options.intern_ids` is set.
+    ///
+    /// ## Examples:
+    /// ```
+    /// use std::collections::HashMap;
+    /// use rec_rsys::dataset::{Dataset, EventLogOptions, Rating};
+    /// let log = r#"{"user": "1", "item": 10, "type": "view", "ts": 100}
+    /// {"user": "1", "item": 11, "type": "purchase", "ts": 120}
+    /// {"user": "2", "item": 10, "type": "scroll", "ts": 130}
+    /// "#;
+    /// let options = EventLogOptions {
+    ///     user_field: "user".to_string(),
+    ///     item_field: "item".to_string(),
+    ///     event_field: Some("type".to_string()),
+    ///     timestamp_field: Some("ts".to_string()),
+    ///     event_weights: HashMap::from([("view".to_string(), 1.0), ("purchase".to_string(), 5.0)]),
+    ///     ..Default::default()
+    /// };
+    /// let (dataset, _) = Dataset::from_events_reader(log.as_bytes(), &options).unwrap();
+    /// assert_eq!(dataset.ratings, vec![
+    ///     Rating::new(1, 10, 1.0).timestamp(100),
+    ///     Rating::new(1, 11, 5.0).timestamp(120),
+    /// ]);
+    ///
+    /// let log = r#"{"user": "u123", "item": "sku-9"}"#;
+    /// let options = EventLogOptions {
+    ///     user_field: "user".to_string(),
+    ///     item_field: "item".to_string(),
+    ///     intern_ids: true,
+    ///     ..Default::default()
+    /// };
+    /// let (dataset, ids) = Dataset::from_events_reader(log.as_bytes(), &options).unwrap();
+    /// assert_eq!(dataset.ratings, vec![Rating::new(0, 0, 1.0)]);
+    /// assert_eq!(ids.users.key(0), Some("u123"));
+    /// assert_eq!(ids.items.id("sku-9"), Some(0));
+    /// ```
+    pub fn from_events_reader<R: BufRead>(
+        reader: R,
+        options: &EventLogOptions,
+    ) -> Result<(Self, EventIds)> {
+        let mut ratings = Vec::new();
+        let mut ids = EventIds::default();
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            if let Some(rating) = parse_event(&line, options, &mut ids, index + 1)? {
+                ratings.push(rating);
+            }
+        }
+        Ok((Dataset::new(ratings), ids))
+    }
+
+    /// Parses delimited ratings, skipping the empty lines.
+    pub fn parse_csv(content: &str, options: &CsvOptions) -> Result<Self> {
+        Dataset::from_reader(content.as_bytes(), options)
+    }
+
+    pub fn len(&self) -> usize {
+        self.ratings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ratings.is_empty()
+    }
+
+    /// The distinct users, sorted.
+    pub fn users(&self) -> BTreeSet<u32> {
+        self.ratings.iter().map(|r| r.user_id).collect()
+    }
+
+    /// The distinct items, sorted.
+    pub fn items(&self) -> BTreeSet<u32> {
+        self.ratings.iter().map(|r| r.item_id).collect()
+    }
+
+    /// The items rated by each user, with their rating.
+    pub fn user_ratings(&self) -> HashMap<u32, Vec<(u32, f32)>> {
+        let mut by_user: HashMap<u32, Vec<(u32, f32)>> = HashMap::new();
+        self.ratings.iter().for_each(|r| {
+            by_user
+                .entry(r.user_id)
+                .or_default()
+                .push((r.item_id, r.rating))
+        });
+        by_user
+    }
+
+    /// The items rated by each user.
+    pub fn user_items(&self) -> HashMap<u32, Vec<u32>> {
+        let mut by_user: HashMap<u32, Vec<u32>> = HashMap::new();
+        self.ratings
+            .iter()
+            .for_each(|r| by_user.entry(r.user_id).or_default().push(r.item_id));
+        by_user
+    }
+
+    /// The set of items each user interacted with.
+    pub fn user_item_sets(&self) -> HashMap<u32, IdSet> {
+        let mut by_user: HashMap<u32, IdSet> = HashMap::new();
+        self.ratings.iter().for_each(|r| {
+            by_user.entry(r.user_id).or_default().insert(r.item_id);
+        });
+        by_user
+    }
+
+    /// One item per rated item, whose values are the ratings of every user of the
+    /// dataset (0 when the user did not rate it).
+    pub(crate) fn item_vectors(&self) -> Vec<Item> {
+        self.rating_vectors(|r| (r.item_id, r.user_id))
+    }
+
+    /// One item per user, whose values are their ratings of every item of the
+    /// dataset (0 when the user did not rate it).
+    pub(crate) fn user_vectors(&self) -> Vec<Item> {
+        self.rating_vectors(|r| (r.user_id, r.item_id))
+    }
+
+    /// One item per row of the rating matrix, `ids` giving the row and the column
+    /// of a rating, sorted by id.
+    fn rating_vectors(&self, ids: fn(&Rating) -> (u32, u32)) -> Vec<Item> {
+        let rows: BTreeSet<u32> = self.ratings.iter().map(|r| ids(r).0).collect();
+        let columns: HashMap<u32, usize> = self
+            .ratings
+            .iter()
+            .map(|r| ids(r).1)
+            .collect::<BTreeSet<u32>>()
+            .into_iter()
+            .enumerate()
+            .map(|(index, id)| (id, index))
+            .collect();
+        let mut vectors: HashMap<u32, Vec<f32>> = rows
+            .iter()
+            .map(|id| (*id, vec![0.0; columns.len()]))
+            .collect();
+        self.ratings.iter().for_each(|r| {
+            let (row, column) = ids(r);
+            vectors.get_mut(&row).unwrap()[columns[&column]] = r.rating;
+        });
+        rows.into_iter()
+            .map(|id| Item::new(id, vectors.remove(&id).unwrap(), None))
+            .collect()
+    }
+
+    /// The set of users that interacted with each item.
+    pub fn item_user_sets(&self) -> HashMap<u32, IdSet> {
+        let mut by_item: HashMap<u32, IdSet> = HashMap::new();
+        self.ratings.iter().for_each(|r| {
+            by_item.entry(r.item_id).or_default().insert(r.user_id);
+        });
+        by_item
+    }
+
+    /// Keeps the ratings of the users and items with at least the given number of
+    /// ratings. A single pass is done, so removing items can leave some users below
+    /// the threshold.
+    pub fn filter_min_ratings(
+        &self,
+        min_user_ratings: usize,
+        min_item_ratings: usize,
+    ) -> Self {
+        let mut user_counts: HashMap<u32, usize> = HashMap::new();
+        let mut item_counts: HashMap<u32, usize> = HashMap::new();
+        self.ratings.iter().for_each(|r| {
+            *user_counts.entry(r.user_id).or_default() += 1;
+            *item_counts.entry(r.item_id).or_default() += 1;
+        });
+        self.filter(|r| {
+            user_counts[&r.user_id] >= min_user_ratings
+                && item_counts[&r.item_id] >= min_item_ratings
+        })
+    }
+
+    /// Turns explicit ratings into implicit feedback, keeping the ratings of at
+    /// least `threshold` as `1.0` and dropping the others. The dataset gets the
+    /// [`RatingScale::implicit`] scale.
+    pub fn binarize(&self, threshold: f32) -> Self {
+        Dataset {
+            ratings: self
+                .ratings
+                .iter()
+                .filter(|r| r.rating >= threshold)
+                .map(|r| Rating { rating: 1.0, ..*r })
+                .collect(),
+            scale: Some(RatingScale::implicit()),
+        }
+    }
+
+    /// Keeps the ratings for which `predicate` returns `true`.
+    pub fn filter<F: Fn(&Rating) -> bool>(&self, predicate: F) -> Self {
+        Dataset {
+            ratings: self
+                .ratings
+                .iter()
+                .filter(|r| predicate(r))
+                .copied()
+                .collect(),
+            scale: self.scale,
+        }
+    }
+
+    /// Randomly splits the ratings into a train and a test dataset.
+    ///
+    /// ## Parameters:
+    /// * `test_ratio`: The fraction of the ratings that go to the test dataset.
+    /// * `seed`: Seed of the shuffle, the same seed always gives the same split.
+    ///
+    /// ## Returns:
+    /// * A tuple `(train, test)`.
+    pub fn split_random(&self, test_ratio: f32, seed: u64) -> (Self, Self) {
+        let mut ratings = self.ratings.clone();
+        ratings.shuffle(&mut StdRng::seed_from_u64(seed));
+        let test_len = (ratings.len() as f32 * test_ratio).round() as usize;
+        let train = ratings.split_off(test_len.min(ratings.len()));
+        (self.with_ratings(train), self.with_ratings(ratings))
+    }
+
+    /// Draws `num_ratings` ratings uniformly, e.g. to run quick experiments on a
+    /// large dataset. The ratings keep their order, the same seed always gives the
+    /// same sample.
+    ///
+    /// ## Examples:
+    /// ```
+    /// use rec_rsys::dataset::{Dataset, Rating};
+    /// let dataset = Dataset::new((0..100).map(|i| Rating::new(i, i, 1.0)).collect());
+    /// let sample = dataset.subsample(10, 42);
+    /// assert_eq!(sample.len(), 10);
+    /// assert!(sample.ratings.windows(2).all(|w| w[0].user_id < w[1].user_id));
+    /// assert_eq!(sample, dataset.subsample(10, 42));
+    /// ```
+    pub fn subsample(&self, num_ratings: usize, seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut indices = reservoir(0..self.ratings.len(), num_ratings, &mut rng);
+        indices.sort_unstable();
+        self.with_ratings(indices.into_iter().map(|i| self.ratings[i]).collect())
+    }
+
+    /// Splits the ratings with [`is_test`]: unlike [`Dataset::split_random`], the
+    /// side of a rating does not depend on the other ratings nor on their order, so
+    /// the split survives new data and reshuffled files.
+    ///
+    /// ## Parameters:
+    /// * `test_ratio`: The expected fraction of the ratings in the test dataset.
+    /// * `salt`: Changes the split, e.g. the name of the experiment.
+    ///
+    /// ## Returns:
+    /// * A tuple `(train, test)`.
+    pub fn split_hash(&self, test_ratio: f32, salt: &str) -> (Self, Self) {
+        let (test, train) = self
+            .ratings
+            .iter()
+            .partition(|r| is_test(r.user_id, r.item_id, salt, test_ratio));
+        (self.with_ratings(train), self.with_ratings(test))
+    }
+
+    /// A dataset of other ratings with the same scale.
+    fn with_ratings(&self, ratings: Vec<Rating>) -> Self {
+        Dataset {
+            ratings,
+            scale: self.scale,
+        }
+    }
+}
+
+/// # Is test
+/// Decides from a hash of the pair whether an interaction belongs to the test
+/// set, so the membership is the same across runs and machines without storing
+/// the split.
+///
+/// ## Parameters:
+/// * `user_id`: The user.
+/// * `item_id`: The item.
+/// * `salt`: Changes the split, e.g. the name of the experiment.
+/// * `test_ratio`: The probability of a pair to be in the test set.
+///
+/// ## Returns:
+/// * Whether the pair is in the test set. A pair in the test set for a ratio is
+///   also in it for any larger ratio.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::dataset::is_test;
+/// let test = is_test(7, 42, "offline_eval", 0.2);
+/// assert_eq!(test, is_test(7, 42, "offline_eval", 0.2));
+/// assert!(!is_test(7, 42, "offline_eval", 0.0) && is_test(7, 42, "offline_eval", 1.0));
+/// ```
+pub fn is_test(user_id: u32, item_id: u32, salt: &str, test_ratio: f32) -> bool {
+    // The 53 high bits, the precision of an f64, as a number in [0, 1).
+    let position =
+        (stable_hash(&(salt, user_id, item_id)) >> 11) as f64 / (1_u64 << 53) as f64;
+    position < test_ratio as f64
+}
+
+fn parse_rating(line: &str, delimiter: &str, line_number: usize) -> Result<Rating> {
+    let invalid = |message: &str| {
+        Error::InvalidData(format!("line {}: {}: {:?}", line_number, message, line))
+    };
+    let columns: Vec<&str> = line.split(delimiter).map(str::trim).collect();
+    if columns.len() < 3 {
+        return Err(invalid("expected at least 3 columns"));
+    }
+    Ok(Rating {
+        user_id: columns[0].parse().map_err(|_| invalid("invalid user id"))?,
+        item_id: columns[1].parse().map_err(|_| invalid("invalid item id"))?,
+        rating: columns[2].parse().map_err(|_| invalid("invalid rating"))?,
+        timestamp: match columns.get(3) {
+            Some(timestamp) => Some(
+                timestamp
+                    .parse()
+                    .map_err(|_| invalid("invalid timestamp"))?,
+            ),
+            None => None,
+        },
+    })
+}
+
+fn parse_event(
+    line: &str,
+    options: &EventLogOptions,
+    ids: &mut EventIds,
+    line_number: usize,
+) -> Result<Option<Rating>> {
+    let invalid = |message: String| {
+        Error::InvalidData(format!("line {}: {}: {:?}", line_number, message, line))
+    };
+    let event: serde_json::Value =
+        serde_json::from_str(line).map_err(|e| invalid(e.to_string()))?;
+    let field = |name: &str| -> Result<&serde_json::Value> {
+        name.split('.')
+            .try_fold(&event, |value, key| value.get(key))
+            .ok_or_else(|| invalid(format!("missing field {:?}", name)))
+    };
+    let rating = match &options.event_field {
+        Some(name) if !options.event_weights.is_empty() => {
+            let kind = field(name)?
+                .as_str()
+                .ok_or_else(|| invalid(format!("{:?} is not a string", name)))?;
+            match options.event_weights.get(kind) {
+                Some(weight) => *weight,
+                None => return Ok(None),
+            }
+        },
+        _ => 1.0,
+    };
+    let integer = |name: &str| -> Result<u64> {
+        let value = field(name)?;
+        value
+            .as_u64()
+            .or_else(|| value.as_str().and_then(|s| s.trim().parse().ok()))
+            .ok_or_else(|| invalid(format!("{:?} is not an integer", name)))
+    };
+    let id = |name: &str, map: &mut IdMap| -> Result<u32> {
+        if !options.intern_ids {
+            return u32::try_from(integer(name)?)
+                .map_err(|_| invalid(format!("{:?} does not fit an id", name)));
+        }
+        match field(name)? {
+            serde_json::Value::String(key) => Ok(map.intern(key)),
+            serde_json::Value::Number(key) => Ok(map.intern(&key.to_string())),
+            _ => Err(invalid(format!("{:?} is not a string or a number", name))),
+        }
+    };
+    Ok(Some(Rating {
+        user_id: id(&options.user_field, &mut ids.users)?,
+        item_id: id(&options.item_field, &mut ids.items)?,
+        rating,
+        timestamp: match &options.timestamp_field {
+            Some(name) => Some(integer(name)?),
+            None => None,
+        },
+    }))
+}
+
+impl MemoryFootprint for Rating {}
+
+impl MemoryFootprint for Dataset {
+    fn heap_size(&self) -> usize {
+        self.ratings.heap_size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_item_sets_of_hashed_ids() {
+        let dataset = Dataset::new(
+            (1..=100)
+                .flat_map(|user| {
+                    [Rating::new(user, 3, 1.0), Rating::new(user, u32::MAX, 1.0)]
+                })
+                .collect(),
+        );
+        let sets = dataset.user_item_sets();
+        assert!(sets[&7].contains(u32::MAX) && sets[&7].contains(3));
+        assert!(sets.heap_size() < 1024 * 1024);
+    }
+
+    #[test]
+    fn test_rating_vectors() {
+        let dataset = Dataset::new(vec![
+            Rating::new(2, 10, 4.0),
+            Rating::new(1, 11, 3.0),
+            Rating::new(1, 10, 5.0),
+        ]);
+        let items = dataset.item_vectors();
+        assert_eq!(items.iter().map(|i| i.id).collect::<Vec<_>>(), vec![10, 11]);
+        assert_eq!(items[0].values, vec![5.0, 4.0]);
+        assert_eq!(items[1].values, vec![3.0, 0.0]);
+        let users = dataset.user_vectors();
+        assert_eq!(users.iter().map(|u| u.id).collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(users[0].values, vec![5.0, 3.0]);
+        assert_eq!(users[1].values, vec![4.0, 0.0]);
+    }
+
+    #[test]
+    fn test_rating_scale() {
+        let scale = RatingScale::new(1.0, 5.0).set_step(0.5);
+        assert_eq!(scale.apply(3.7), 3.5);
+        assert_eq!(scale.apply(3.8), 4.0);
+        assert_eq!(scale.apply(-2.0), 1.0);
+        assert!(RatingScale::new(5.0, 1.0).validate().is_err());
+        assert!(scale.contains(1.0) && scale.contains(5.0) && !scale.contains(f32::NAN));
+        assert_eq!(scale.clip(-2.0), 1.0);
+        assert_eq!(RatingScale::implicit().clip(-2.0), -2.0);
+    }
+
+    #[test]
+    fn test_scale_is_kept_by_derived_datasets() {
+        let dataset = Dataset::new(vec![
+            Rating::new(1, 10, 4.0),
+            Rating::new(2, 10, 2.0),
+            Rating::new(2, 11, 5.0),
+        ])
+        .with_scale(RatingScale::default())
+        .unwrap();
+        let scale = Some(RatingScale::default());
+        assert_eq!(dataset.filter(|r| r.user_id == 2).scale, scale);
+        assert_eq!(dataset.subsample(2, 42).scale, scale);
+        assert_eq!(dataset.split_hash(0.5, "salt").1.scale, scale);
+        assert_eq!(dataset.binarize(4.0).scale, Some(RatingScale::implicit()));
+        let error = Dataset::new(vec![Rating::new(1, 10, 0.0)])
+            .with_scale(RatingScale::default())
+            .unwrap_err();
+        assert!(error.to_string().contains("user 1 on item 10"));
+    }
+
+    fn dataset() -> Dataset {
+        Dataset::new(vec![
+            Rating::new(1, 10, 5.0),
+            Rating::new(1, 11, 3.0),
+            Rating::new(1, 12, 4.0),
+            Rating::new(2, 10, 4.0),
+            Rating::new(2, 11, 1.0),
+            Rating::new(3, 10, 2.0),
+        ])
+    }
+
+    #[test]
+    fn test_events() {
+        let log = concat!(
+            "{\"context\": {\"user\": 1}, \"item\": \"10\"}\n",
+            "\n",
+            "{\"context\": {\"user\": 2}, \"item\": 11, \"extra\": [1, 2]}\n",
+        );
+        let options = EventLogOptions {
+            user_field: "context.user".to_string(),
+            item_field: "item".to_string(),
+            ..Default::default()
+        };
+        let (dataset, ids) =
+            Dataset::from_events_reader(log.as_bytes(), &options).unwrap();
+        assert_eq!(
+            dataset.ratings,
+            vec![Rating::new(1, 10, 1.0), Rating::new(2, 11, 1.0)]
+        );
+        assert_eq!(ids, EventIds::default());
+        let error = Dataset::from_events_reader("{\"item\": 1}".as_bytes(), &options)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("line 1: missing field \"context.user\""));
+        let error = Dataset::from_events_reader(
+            "{\"context\": {\"user\": -1}, \"item\": 1}".as_bytes(),
+            &options,
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(error.contains("is not an integer"));
+        assert!(Dataset::from_events_reader("not json".as_bytes(), &options).is_err());
+        let error = Dataset::from_events_reader(
+            "{\"context\": {\"user\": \"u1\"}, \"item\": 1}".as_bytes(),
+            &options,
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(error.contains("is not an integer"));
+    }
+
+    #[test]
+    fn test_events_with_string_ids() {
+        let log = concat!(
+            "{\"user\": \"u123\", \"item\": \"sku-9\"}\n",
+            "{\"user\": \"u7\", \"item\": 42}\n",
+            "{\"user\": \"u123\", \"item\": \"42\"}\n",
+        );
+        let options = EventLogOptions {
+            user_field: "user".to_string(),
+            item_field: "item".to_string(),
+            intern_ids: true,
+            ..Default::default()
+        };
+        let (dataset, ids) =
+            Dataset::from_events_reader(log.as_bytes(), &options).unwrap();
+        assert_eq!(
+            dataset.ratings,
+            vec![
+                Rating::new(0, 0, 1.0),
+                Rating::new(1, 1, 1.0),
+                Rating::new(0, 1, 1.0)
+            ]
+        );
+        assert_eq!(ids.users.key(1), Some("u7"));
+        assert_eq!(ids.items.key(1), Some("42"));
+        assert_eq!(ids.items.len(), 2);
+        let error = Dataset::from_events_reader(
+            "{\"user\": [1], \"item\": 1}".as_bytes(),
+            &options,
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(error.contains("is not a string or a number"));
+    }
+
+    #[test]
+    fn test_parse_csv() {
+        let options = CsvOptions {
+            delimiter: "::".to_string(),
+            has_header: true,
+        };
+        let dataset = Dataset::parse_csv(
+            "user::item::rating::ts\n1::10::4.5::978300760\n\n",
+            &options,
+        )
+        .unwrap();
+        assert_eq!(
+            dataset.ratings,
+            vec![Rating::new(1, 10, 4.5).timestamp(978300760)]
+        );
+    }
+
+    #[test]
+    fn test_to_csv_round_trip() {
+        let options = CsvOptions {
+            delimiter: "\t".to_string(),
+            has_header: true,
+        };
+        let dataset = Dataset::new(vec![
+            Rating::new(1, 10, 4.5).timestamp(978300760),
+            Rating::new(2, 11, 3.0),
+        ]);
+        let csv = dataset.to_csv(&options);
+        assert!(csv.starts_with("user\titem\trating\ttimestamp\n1\t10\t4.5\t978300760\n"));
+        assert_eq!(Dataset::parse_csv(&csv, &options).unwrap(), dataset);
+    }
+
+    #[test]
+    fn test_from_reader_spans_chunks() {
+        let content: String = (0..CSV_CHUNK_LINES + 10)
+            .map(|i| format!("{},{},1.0\n\n", i % 7, i))
+            .collect();
+        let dataset =
+            Dataset::from_reader(content.as_bytes(), &CsvOptions::default()).unwrap();
+        assert_eq!(dataset.len(), CSV_CHUNK_LINES + 10);
+        assert!(dataset
+            .ratings
+            .iter()
+            .enumerate()
+            .all(|(i, r)| r.item_id == i as u32));
+        let invalid = format!("{}1,x,1\n1,y,1\n", content);
+        let error = Dataset::parse_csv(&invalid, &CsvOptions::default()).unwrap_err();
+        let line = 2 * (CSV_CHUNK_LINES + 10) + 1;
+        assert!(error
+            .to_string()
+            .contains(&format!("line {}: invalid item id", line)));
+    }
+
+    #[test]
+    fn test_parse_csv_invalid_line() {
+        let error =
+            Dataset::parse_csv("1,10,4\n1,a,3\n", &CsvOptions::default()).unwrap_err();
+        assert!(error.to_string().contains("line 2: invalid item id"));
+    }
+
+    #[test]
+    fn test_filter_min_ratings() {
+        let filtered = dataset().filter_min_ratings(2, 2);
+        assert_eq!(filtered.users(), BTreeSet::from([1, 2]));
+        assert_eq!(filtered.items(), BTreeSet::from([10, 11]));
+    }
+
+    #[test]
+    fn test_binarize() {
+        let binarized = dataset().binarize(4.0);
+        assert_eq!(binarized.len(), 3);
+        assert!(binarized.ratings.iter().all(|r| r.rating == 1.0));
+    }
+
+    #[test]
+    fn test_split_random() {
+        let (train, test) = dataset().split_random(0.5, 7);
+        assert_eq!((train.len(), test.len()), (3, 3));
+        assert_eq!(dataset().split_random(0.5, 7), (train, test));
+    }
+
+    #[test]
+    fn test_split_hash() {
+        let ratings: Vec<Rating> = (0..100)
+            .flat_map(|user| (0..20).map(move |item| Rating::new(user, item, 1.0)))
+            .collect();
+        let dataset = Dataset::new(ratings.clone());
+        let (train, test) = dataset.split_hash(0.25, "a");
+        assert_eq!(train.len() + test.len(), 2000);
+        assert!((test.len() as f32 / 2000.0 - 0.25).abs() < 0.05);
+        let mut reversed = ratings;
+        reversed.reverse();
+        let (_, reversed_test) = Dataset::new(reversed).split_hash(0.25, "a");
+        let ids = |d: &Dataset| -> BTreeSet<(u32, u32)> {
+            d.ratings.iter().map(|r| (r.user_id, r.item_id)).collect()
+        };
+        assert_eq!(ids(&reversed_test), ids(&test));
+        let (_, larger) = dataset.split_hash(0.5, "a");
+        assert!(ids(&test).is_subset(&ids(&larger)));
+        assert_ne!(ids(&dataset.split_hash(0.25, "b").1), ids(&test));
+    }
+}
@@ -0,0 +1,269 @@
+//! # Standard datasets
+//! Downloads the datasets commonly used to benchmark recommenders into a local
+//! cache and loads them as a [`Dataset`], so an example or an evaluation run is a
+//! single call. Requires the `fetch` feature.
+//!
+//! ```no_run
+//! use rec_rsys::datasets::{fetch, DatasetSource};
+//! let dataset = fetch(&DatasetSource::movielens_100k(), None).unwrap();
+//! assert_eq!(dataset.len(), 100_000);
+//! ```
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::dataset::{CsvOptions, Dataset, RatingScale};
+use crate::errors::{Error, Result};
+
+/// Where a dataset is downloaded from and how to read it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DatasetSource {
+    /// Short name, also used as the name of the cached archive.
+    pub name: String,
+    /// URL of the zip archive.
+    pub url: String,
+    /// Expected SHA-256 of the archive, in hexadecimal. When `None` the hash of the
+    /// first download is recorded next to it and enforced on the next loads.
+    pub sha256: Option<String>,
+    /// Path of the ratings file inside the archive.
+    pub member: String,
+    pub csv: CsvOptions,
+    /// Turns the ratings into implicit feedback, keeping the ones at or above the
+    /// threshold, see [`Dataset::binarize`].
+    pub implicit_threshold: Option<f32>,
+    /// The scale the ratings are checked against, see [`Dataset::with_scale`].
+    #[serde(default)]
+    pub scale: Option<RatingScale>,
+}
+
+const GROUPLENS: &str = "https://files.grouplens.org/datasets";
+
+impl DatasetSource {
+    /// 100,000 ratings from 1 to 5 of 943 users on 1682 movies.
+    pub fn movielens_100k() -> Self {
+        DatasetSource {
+            name: "ml-100k".to_string(),
+            url: format!("{}/movielens/ml-100k.zip", GROUPLENS),
+            sha256: None,
+            member: "ml-100k/u.data".to_string(),
+            csv: CsvOptions {
+                delimiter: "\t".to_string(),
+                has_header: false,
+            },
+            implicit_threshold: None,
+            scale: Some(RatingScale::new(1.0, 5.0).set_step(1.0)),
+        }
+    }
+
+    /// 1,000,209 ratings from 1 to 5 of 6040 users on 3706 movies.
+    pub fn movielens_1m() -> Self {
+        DatasetSource {
+            name: "ml-1m".to_string(),
+            url: format!("{}/movielens/ml-1m.zip", GROUPLENS),
+            sha256: None,
+            member: "ml-1m/ratings.dat".to_string(),
+            csv: CsvOptions {
+                delimiter: "::".to_string(),
+                has_header: false,
+            },
+            implicit_threshold: None,
+            scale: Some(RatingScale::new(1.0, 5.0).set_step(1.0)),
+        }
+    }
+
+    /// MovieLens 100K as implicit feedback: the movies rated 4 or more.
+    pub fn movielens_100k_implicit() -> Self {
+        DatasetSource {
+            implicit_threshold: Some(4.0),
+            ..DatasetSource::movielens_100k()
+        }
+    }
+
+    /// The artists 1892 Last.fm users listened to, as implicit feedback.
+    pub fn lastfm_2k() -> Self {
+        DatasetSource {
+            name: "hetrec2011-lastfm-2k".to_string(),
+            url: format!("{}/hetrec2011/hetrec2011-lastfm-2k.zip", GROUPLENS),
+            sha256: None,
+            member: "user_artists.dat".to_string(),
+            csv: CsvOptions {
+                delimiter: "\t".to_string(),
+                has_header: true,
+            },
+            implicit_threshold: Some(f32::MIN),
+            scale: Some(RatingScale::implicit()),
+        }
+    }
+
+    /// Pins the expected SHA-256 of the archive.
+    pub fn set_sha256(mut self, sha256: &str) -> Self {
+        self.sha256 = Some(sha256.to_lowercase());
+        self
+    }
+
+    fn archive_path(&self, cache_dir: &Path) -> PathBuf {
+        cache_dir.join(format!("{}.zip", self.name))
+    }
+}
+
+/// The cache directory: `$REC_RSYS_DATA` when set, `~/.cache/rec_rsys` otherwise.
+pub fn default_cache_dir() -> PathBuf {
+    match std::env::var_os("REC_RSYS_DATA") {
+        Some(directory) => PathBuf::from(directory),
+        None => std::env::var_os("HOME")
+            .map_or_else(std::env::temp_dir, PathBuf::from)
+            .join(".cache")
+            .join("rec_rsys"),
+    }
+}
+
+/// # Fetch
+/// Loads a dataset, downloading it first when it is not in the cache.
+///
+/// ## Parameters:
+/// * `source`: The dataset, e.g. [`DatasetSource::movielens_100k`].
+/// * `cache_dir`: Where the archives are kept, [`default_cache_dir`] when `None`.
+///
+/// ## Returns:
+/// * The ratings, or an error when the download fails or the archive does not
+///   match its checksum.
+pub fn fetch(source: &DatasetSource, cache_dir: Option<&Path>) -> Result<Dataset> {
+    let cache_dir = cache_dir.map_or_else(default_cache_dir, Path::to_path_buf);
+    let archive = source.archive_path(&cache_dir);
+    if !archive.exists() {
+        fs::create_dir_all(&cache_dir)?;
+        download(&source.url, &archive)?;
+    }
+    load_cached(source, &cache_dir)
+}
+
+/// # Load cached
+/// Loads a dataset already in the cache, after checking its checksum, without
+/// network access.
+pub fn load_cached(source: &DatasetSource, cache_dir: &Path) -> Result<Dataset> {
+    let archive = source.archive_path(cache_dir);
+    let bytes = fs::read(&archive)?;
+    verify(source, &archive, &sha256_hex(&bytes))?;
+
+    let mut zip = zip::ZipArchive::new(io::Cursor::new(bytes)).map_err(|error| {
+        Error::InvalidData(format!("{}: {}", archive.display(), error))
+    })?;
+    let mut content = String::new();
+    zip.by_name(&source.member)
+        .map_err(|error| Error::InvalidData(format!("{}: {}", source.member, error)))?
+        .read_to_string(&mut content)?;
+    let dataset = Dataset::parse_csv(&content, &source.csv)?;
+    let dataset = match source.scale {
+        Some(scale) => dataset.with_scale(scale)?,
+        None => dataset,
+    };
+    Ok(match source.implicit_threshold {
+        Some(threshold) => dataset.binarize(threshold),
+        None => dataset,
+    })
+}
+
+/// Compares the hash of the archive with the pinned one, or with the one recorded
+/// at the first download, recording it if there is none yet.
+fn verify(source: &DatasetSource, archive: &Path, actual: &str) -> Result<()> {
+    let recorded = archive.with_extension("zip.sha256");
+    let expected = match &source.sha256 {
+        Some(sha256) => Some(sha256.clone()),
+        None => fs::read_to_string(&recorded)
+            .ok()
+            .map(|sha256| sha256.trim().to_string()),
+    };
+    match expected {
+        Some(expected) if expected != actual => Err(Error::InvalidData(format!(
+            "checksum mismatch for {}: expected {}, got {}, delete it to download it again",
+            archive.display(),
+            expected,
+            actual
+        ))),
+        Some(_) => Ok(()),
+        None => Ok(fs::write(recorded, actual)?),
+    }
+}
+
+fn download(url: &str, destination: &Path) -> Result<()> {
+    let response = ureq::get(url)
+        .call()
+        .map_err(|error| io::Error::other(format!("downloading {}: {}", url, error)))?;
+    let partial = destination.with_extension("part");
+    let mut file = fs::File::create(&partial)?;
+    io::copy(&mut response.into_reader(), &mut file)?;
+    fs::rename(partial, destination)?;
+    Ok(())
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+
+    fn cache(name: &str, member: &str, content: &str) -> (DatasetSource, PathBuf) {
+        let directory = std::env::temp_dir().join(format!("rec_rsys_datasets_{}", name));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        let mut writer = zip::ZipWriter::new(io::Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Stored);
+        writer.start_file(member, options).unwrap();
+        writer.write_all(content.as_bytes()).unwrap();
+        let bytes = writer.finish().unwrap().into_inner();
+        let source = DatasetSource {
+            name: name.to_string(),
+            ..DatasetSource::movielens_100k()
+        };
+        fs::write(source.archive_path(&directory), bytes).unwrap();
+        (source, directory)
+    }
+
+    #[test]
+    fn test_load_cached_records_checksum() {
+        let (source, directory) =
+            cache("record", "ml-100k/u.data", "1\t10\t4\t100\n2\t10\t3\t101\n");
+        assert_eq!(load_cached(&source, &directory).unwrap().len(), 2);
+        let recorded = directory.join("record.zip.sha256");
+        assert_eq!(fs::read_to_string(&recorded).unwrap().len(), 64);
+        fs::write(&recorded, "0".repeat(64)).unwrap();
+        assert!(load_cached(&source, &directory).is_err());
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_load_cached_pinned_and_implicit() {
+        let (source, directory) =
+            cache("pinned", "ml-100k/u.data", "1\t10\t4\n1\t11\t2\n");
+        let bytes = fs::read(source.archive_path(&directory)).unwrap();
+        let source = DatasetSource {
+            implicit_threshold: Some(4.0),
+            ..source.set_sha256(&sha256_hex(&bytes).to_uppercase())
+        };
+        let dataset = load_cached(&source, &directory).unwrap();
+        assert_eq!(dataset.len(), 1);
+        assert_eq!(dataset.ratings[0].rating, 1.0);
+        assert_eq!(dataset.scale, Some(RatingScale::implicit()));
+        let wrong = source.set_sha256(&"f".repeat(64));
+        assert!(load_cached(&wrong, &directory).is_err());
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_missing_member() {
+        let (source, directory) = cache("member", "other.csv", "1\t10\t4\n");
+        assert!(load_cached(&source, &directory).is_err());
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,365 @@
+//! # Diagnostics
+//! Tools to verify the implementation of the trainers, starting with checking
+//! analytic gradients against finite differences, and to measure how much a
+//! retrained model differs from the deployed one.
+use std::collections::{HashMap, HashSet};
+
+use crate::errors::{Error, Result};
+use crate::models::Item;
+use crate::pairwise::SimilarityTable;
+use crate::similarity::{cosine_similarity, SimilarityAlgos};
+use crate::statistics::percentile_of_sorted;
+
+/// Outcome of [`check_gradient`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GradientCheck {
+    /// Largest absolute difference between the two gradients.
+    pub max_abs_error: f64,
+    /// Largest difference relative to the magnitude of the gradients.
+    pub max_relative_error: f64,
+    /// The parameter with the largest relative error.
+    pub worst_parameter: usize,
+}
+
+impl GradientCheck {
+    /// Whether the relative error stays under the tolerance, `1e-6` is a usual
+    /// value for a correct gradient computed in `f64`.
+    pub fn passed(&self, tolerance: f64) -> bool {
+        self.max_relative_error <= tolerance
+    }
+}
+
+/// # Numerical gradient
+/// Approximates the gradient of a function with central finite differences.
+///
+/// ## Parameters:
+/// * `loss`: The function to differentiate.
+/// * `parameters`: Where to evaluate the gradient.
+/// * `epsilon`: The step, around `1e-5` for `f64`.
+///
+/// ## Returns:
+/// * The partial derivative for every parameter.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::diagnostics::numerical_gradient;
+/// let gradient = numerical_gradient(|x| x[0] * x[0] + 3.0 * x[1], &[2.0, 1.0], 1e-5);
+/// assert!((gradient[0] - 4.0).abs() < 1e-6 && (gradient[1] - 3.0).abs() < 1e-6);
+/// ```
+#[doc = include_str!("../docs/diagnostics/gradient_check.md")]
+pub fn numerical_gradient<F>(loss: F, parameters: &[f64], epsilon: f64) -> Vec<f64>
+where
+    F: Fn(&[f64]) -> f64,
+{
+    let mut shifted = parameters.to_vec();
+    (0..parameters.len())
+        .map(|index| {
+            shifted[index] = parameters[index] + epsilon;
+            let above = loss(&shifted);
+            shifted[index] = parameters[index] - epsilon;
+            let below = loss(&shifted);
+            shifted[index] = parameters[index];
+            (above - below) / (2.0 * epsilon)
+        })
+        .collect()
+}
+
+/// # Check gradient
+/// Compares an analytic gradient with the numerical one. Use it on tiny problems
+/// when implementing a new loss or trainer: a wrong sign or a missing factor shows
+/// up as a relative error close to 1.
+///
+/// ## Parameters:
+/// * `loss`: The function to differentiate.
+/// * `gradient`: Its analytic gradient.
+/// * `parameters`: Where to compare them.
+/// * `epsilon`: The step of the finite differences.
+///
+/// ## Returns:
+/// * The largest differences, see [`GradientCheck::passed`].
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::diagnostics::check_gradient;
+/// let loss = |x: &[f64]| x[0] * x[1];
+/// let check = check_gradient(loss, |x: &[f64]| vec![x[1], x[0]], &[0.5, -3.0], 1e-5);
+/// assert!(check.passed(1e-6));
+/// let wrong = check_gradient(loss, |x: &[f64]| vec![x[1], -x[0]], &[0.5, -3.0], 1e-5);
+/// assert!(!wrong.passed(1e-6));
+/// assert_eq!(wrong.worst_parameter, 1);
+/// ```
+pub fn check_gradient<F, G>(
+    loss: F,
+    gradient: G,
+    parameters: &[f64],
+    epsilon: f64,
+) -> GradientCheck
+where
+    F: Fn(&[f64]) -> f64,
+    G: Fn(&[f64]) -> Vec<f64>,
+{
+    let analytic = gradient(parameters);
+    let numerical = numerical_gradient(loss, parameters, epsilon);
+    assert_eq!(
+        analytic.len(),
+        numerical.len(),
+        "the gradient must have one value per parameter"
+    );
+    let mut check = GradientCheck {
+        max_abs_error: 0.0,
+        max_relative_error: 0.0,
+        worst_parameter: 0,
+    };
+    analytic
+        .iter()
+        .zip(numerical.iter())
+        .enumerate()
+        .for_each(|(index, (a, n))| {
+            let error = (a - n).abs();
+            let relative = error / a.abs().max(n.abs()).max(1e-12);
+            let relative = if error < 1e-12 { 0.0 } else { relative };
+            check.max_abs_error = check.max_abs_error.max(error);
+            if relative > check.max_relative_error {
+                check.max_relative_error = relative;
+                check.worst_parameter = index;
+            }
+        });
+    check
+}
+
+/// Outcome of [`embedding_drift`].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EmbeddingDrift {
+    /// Items embedded by both versions, the ones the other fields describe.
+    pub common_items: usize,
+    /// Items only embedded by the new version.
+    pub added_items: usize,
+    /// Items only embedded by the old version.
+    pub removed_items: usize,
+    /// `1 - cosine` between the two embeddings of every common item, from the
+    /// largest shift.
+    pub shifts: Vec<(u32, f32)>,
+    pub mean_shift: f32,
+    pub median_shift: f32,
+    pub p90_shift: f32,
+    /// The number of neighbors compared per item.
+    pub k: usize,
+    /// Mean fraction of the `k` nearest neighbors of an item kept by the new
+    /// version, 1 when no neighborhood changed.
+    pub neighbor_overlap: f32,
+}
+
+impl EmbeddingDrift {
+    /// The items whose embedding moved the most.
+    pub fn most_shifted(&self, n: usize) -> &[(u32, f32)] {
+        &self.shifts[..n.min(self.shifts.len())]
+    }
+}
+
+/// # Embedding drift
+/// Compares the item embeddings of two versions of a model, to decide whether a
+/// retrain changes the recommendations too much to be deployed as is.
+///
+/// The shift of an item only makes sense when both versions share the same
+/// space: align them first with [`Procrustes`](crate::alignment::Procrustes), as
+/// retraining usually rotates the factors. The overlap of the neighborhoods does
+/// not depend on the space.
+///
+/// ## Parameters:
+/// * `before`: The embeddings of the deployed version.
+/// * `after`: The embeddings of the new version, in any order.
+/// * `k`: The number of nearest neighbors, by cosine, compared for every item.
+///
+/// ## Returns:
+/// * The drift over the items embedded by both versions, or an error if there is
+///   none.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::diagnostics::embedding_drift;
+/// use rec_rsys::models::Item;
+/// let before = vec![
+///     Item::new(1, vec![1.0, 0.0], None),
+///     Item::new(2, vec![0.9, 0.1], None),
+///     Item::new(3, vec![0.0, 1.0], None),
+/// ];
+/// let mut after = before.clone();
+/// after[2] = Item::new(3, vec![1.0, 0.05], None);
+/// let drift = embedding_drift(&before, &after, 1).unwrap();
+/// assert_eq!(drift.most_shifted(1)[0].0, 3);
+/// assert!(drift.neighbor_overlap < 1.0);
+/// ```
+pub fn embedding_drift(
+    before: &[Item],
+    after: &[Item],
+    k: usize,
+) -> Result<EmbeddingDrift> {
+    let before_ids: HashSet<u32> = before.iter().map(|item| item.id).collect();
+    let after_items: HashMap<u32, &Item> =
+        after.iter().map(|item| (item.id, item)).collect();
+    let common: Vec<&Item> = before
+        .iter()
+        .filter(|item| after_items.contains_key(&item.id))
+        .collect();
+    if common.is_empty() {
+        return Err(Error::InvalidData(
+            "the two versions have no item in common".to_string(),
+        ));
+    }
+    let mut shifts: Vec<(u32, f32)> = common
+        .iter()
+        .map(|item| {
+            let similarity =
+                cosine_similarity(&item.values, &after_items[&item.id].values);
+            (
+                item.id,
+                if similarity.is_finite() { 1.0 - similarity } else { 1.0 },
+            )
+        })
+        .collect();
+    shifts.sort_by(|(a, x), (b, y)| y.total_cmp(x).then_with(|| a.cmp(b)));
+    let mut sorted: Vec<f32> = shifts.iter().map(|(_, shift)| *shift).collect();
+    sorted.reverse();
+
+    let old: Vec<Item> = common.iter().map(|item| (*item).clone()).collect();
+    let new: Vec<Item> = common
+        .iter()
+        .map(|item| after_items[&item.id].clone())
+        .collect();
+    let old = SimilarityTable::exact(&old, SimilarityAlgos::Cosine, k);
+    let new = SimilarityTable::exact(&new, SimilarityAlgos::Cosine, k);
+    let overlap: f32 = common
+        .iter()
+        .map(|item| {
+            let kept: HashSet<u32> = new.neighbors(item.id).iter().map(|n| n.0).collect();
+            let neighbors = old.neighbors(item.id);
+            match neighbors.len() {
+                0 => 1.0,
+                len => {
+                    neighbors.iter().filter(|n| kept.contains(&n.0)).count() as f32
+                        / len as f32
+                },
+            }
+        })
+        .sum();
+    Ok(EmbeddingDrift {
+        common_items: common.len(),
+        added_items: after_items
+            .keys()
+            .filter(|id| !before_ids.contains(id))
+            .count(),
+        removed_items: before.len() - common.len(),
+        mean_shift: sorted.iter().sum::<f32>() / sorted.len() as f32,
+        median_shift: percentile_of_sorted(&sorted, 50.0),
+        p90_shift: percentile_of_sorted(&sorted, 90.0),
+        shifts,
+        k,
+        neighbor_overlap: overlap / common.len() as f32,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "serde")]
+    use crate::algorithms::mf::squared_loss_gradient;
+    #[cfg(feature = "serde")]
+    use crate::algorithms::regularization::Regularization;
+
+    /// Parameters laid out as `[user bias, item bias, p.., q..]`.
+    #[cfg(feature = "serde")]
+    fn mf_loss(parameters: &[f64]) -> f64 {
+        let k = (parameters.len() - 2) / 2;
+        let (p, q) = parameters[2..].split_at(k);
+        let prediction = 3.5
+            + parameters[0]
+            + parameters[1]
+            + p.iter().zip(q).map(|(a, b)| a * b).sum::<f64>();
+        0.5 * (4.0 - prediction).powi(2)
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_mf_gradient() {
+        let parameters = [0.1, -0.2, 0.3, -0.5, 0.7, 0.2, 0.4, -0.1];
+        let check = check_gradient(
+            mf_loss,
+            |x| {
+                let as_f32: Vec<f32> = x.iter().map(|v| *v as f32).collect();
+                let (p, q) = as_f32[2..].split_at(3);
+                squared_loss_gradient(4.0, 3.5, as_f32[0], as_f32[1], p, q)
+                    .iter()
+                    .map(|g| *g as f64)
+                    .collect()
+            },
+            &parameters,
+            1e-5,
+        );
+        assert!(check.passed(1e-4), "{:?}", check);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_regularization_gradient() {
+        let regularization = Regularization::ElasticNet { l1: 0.3, l2: 0.1 };
+        let check = check_gradient(
+            |x| {
+                let values: Vec<f32> = x.iter().map(|v| *v as f32).collect();
+                regularization.penalty(&values) as f64
+            },
+            |x| {
+                x.iter()
+                    .map(|v| regularization.gradient(*v as f32) as f64)
+                    .collect()
+            },
+            &[0.5, -1.5, 2.0],
+            1e-3,
+        );
+        assert!(check.passed(1e-3), "{:?}", check);
+    }
+
+    #[test]
+    fn test_embedding_drift() {
+        let before: Vec<Item> = (0..6)
+            .map(|id| {
+                let angle = id as f32 * 0.3;
+                Item::new(id, vec![angle.cos(), angle.sin()], None)
+            })
+            .collect();
+        // The same embeddings, scaled: nothing drifted.
+        let scaled: Vec<Item> = before
+            .iter()
+            .rev()
+            .map(|item| {
+                Item::new(item.id, item.values.iter().map(|v| 2.0 * v).collect(), None)
+            })
+            .collect();
+        let drift = embedding_drift(&before, &scaled, 2).unwrap();
+        assert_eq!(
+            (drift.common_items, drift.added_items, drift.removed_items),
+            (6, 0, 0)
+        );
+        assert!(drift.p90_shift < 1e-6);
+        assert_eq!(drift.neighbor_overlap, 1.0);
+
+        let mut moved = before[1..].to_vec();
+        moved[4] = Item::new(5, vec![-1.0, 0.0], None);
+        moved.push(Item::new(9, vec![0.0, 1.0], None));
+        let drift = embedding_drift(&before, &moved, 2).unwrap();
+        assert_eq!(
+            (drift.common_items, drift.added_items, drift.removed_items),
+            (5, 1, 1)
+        );
+        assert_eq!(drift.most_shifted(1)[0].0, 5);
+        assert!(drift.most_shifted(2)[1].1 < 1e-6);
+        assert!(drift.mean_shift > drift.median_shift);
+        assert!(drift.neighbor_overlap < 1.0);
+        assert_eq!(drift.most_shifted(10).len(), 5);
+
+        assert!(
+            embedding_drift(&before, &[Item::new(42, vec![1.0, 0.0], None)], 2).is_err()
+        );
+    }
+}
@@ -0,0 +1,244 @@
+//! # Model envelopes
+//! Trained models are persisted inside an envelope recording what the model is and
+//! how it was obtained: its type, the version of the crate that trained it, when,
+//! with which hyperparameters, on which data and with which metrics. Loading checks
+//! the envelope before the model, so a file written for another model or by an
+//! incompatible version of the crate fails with an explicit error instead of a
+//! deserialization error deep in the weights.
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::errors::{Error, Result};
+use crate::evaluation::runs::build_version;
+
+/// Version of the layout of the envelope and of the models it holds. It is bumped
+/// whenever a persisted model changes in a way older versions cannot read.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// A model that can be persisted in a [`ModelEnvelope`].
+pub trait PersistedModel: Serialize + DeserializeOwned {
+    /// Name of the type of model, checked when loading.
+    const MODEL_TYPE: &'static str;
+
+    /// The hyperparameters the model was trained with, recorded in the envelope.
+    fn hyperparameters(&self) -> BTreeMap<String, Value> {
+        BTreeMap::new()
+    }
+}
+
+/// The fields of an object serialized as JSON, e.g. the configuration of a model.
+pub(crate) fn fields<T: Serialize>(value: &T) -> BTreeMap<String, Value> {
+    match serde_json::to_value(value) {
+        Ok(Value::Object(fields)) => fields.into_iter().collect(),
+        _ => BTreeMap::new(),
+    }
+}
+
+/// # Model envelope
+/// A trained model with its metadata, saved as JSON. Unknown fields are ignored
+/// when loading, so files written by newer versions of the same format are read
+/// as long as the model itself did not change.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::algorithms::pca::PCA;
+/// use rec_rsys::envelope::ModelEnvelope;
+/// let pca = PCA::fit(&[vec![1.0, 2.0], vec![2.0, 4.1], vec![3.0, 6.2]], 1).unwrap();
+/// let envelope = ModelEnvelope::new(pca)
+///     .set_dataset_hash("0123456789abcdef")
+///     .set_metric("explained_variance_ratio", 0.99);
+/// let json = envelope.to_json().unwrap();
+/// let loaded = ModelEnvelope::<PCA>::from_json(&json).unwrap();
+/// assert_eq!(loaded.model_type, "pca");
+/// assert_eq!(loaded.model(), envelope.model());
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelEnvelope<T> {
+    /// See [`FORMAT_VERSION`].
+    pub format_version: u32,
+    /// See [`PersistedModel::MODEL_TYPE`].
+    pub model_type: String,
+    /// Version of the crate that trained the model, see [`build_version`].
+    #[serde(default)]
+    pub crate_version: String,
+    /// Seconds since the unix epoch when the model was trained.
+    #[serde(default)]
+    pub trained_at: u64,
+    #[serde(default)]
+    pub hyperparameters: BTreeMap<String, Value>,
+    /// Fingerprint of the training data, see
+    /// [`dataset_hash`](crate::evaluation::runs::dataset_hash).
+    #[serde(default)]
+    pub dataset_hash: String,
+    /// Metrics of the model when it was saved, by name.
+    #[serde(default)]
+    pub metrics: BTreeMap<String, f64>,
+    model: T,
+}
+
+impl<T: PersistedModel> ModelEnvelope<T> {
+    /// Wraps a model trained now.
+    pub fn new(model: T) -> Self {
+        ModelEnvelope {
+            format_version: FORMAT_VERSION,
+            model_type: T::MODEL_TYPE.to_string(),
+            crate_version: build_version(),
+            trained_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs()),
+            hyperparameters: model.hyperparameters(),
+            dataset_hash: String::new(),
+            metrics: BTreeMap::new(),
+            model,
+        }
+    }
+
+    pub fn set_trained_at(mut self, trained_at: u64) -> Self {
+        self.trained_at = trained_at;
+        self
+    }
+
+    pub fn set_dataset_hash(mut self, dataset_hash: &str) -> Self {
+        self.dataset_hash = dataset_hash.to_string();
+        self
+    }
+
+    pub fn set_metric(mut self, name: &str, value: f64) -> Self {
+        self.metrics.insert(name.to_string(), value);
+        self
+    }
+
+    pub fn model(&self) -> &T {
+        &self.model
+    }
+
+    pub fn into_model(self) -> T {
+        self.model
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    /// Reads an envelope, checking its format version and its model type before
+    /// reading the model.
+    pub fn from_json(content: &str) -> Result<Self> {
+        let value: Value = serde_json::from_str(content)?;
+        let format_version = value.get("format_version").and_then(Value::as_u64);
+        match format_version {
+            None => {
+                return Err(Error::InvalidData(
+                    "not a model envelope: no format_version".to_string(),
+                ))
+            },
+            Some(version) if version > FORMAT_VERSION as u64 => {
+                return Err(Error::InvalidData(format!(
+                    "model written with format version {} by rec_rsys {}, this version \
+                     reads up to format version {}",
+                    version,
+                    value["crate_version"].as_str().unwrap_or("unknown"),
+                    FORMAT_VERSION
+                )))
+            },
+            Some(_) => {},
+        }
+        let model_type = value.get("model_type").and_then(Value::as_str);
+        if model_type != Some(T::MODEL_TYPE) {
+            return Err(Error::InvalidData(format!(
+                "expected model type {}, found {}",
+                T::MODEL_TYPE,
+                model_type.unwrap_or("no model type")
+            )));
+        }
+        Ok(serde_json::from_value(value)?)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        Ok(fs::write(path, self.to_json()?)?)
+    }
+
+    /// Reads an envelope written with [`ModelEnvelope::save`], see
+    /// [`ModelEnvelope::from_json`].
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        ModelEnvelope::from_json(&fs::read_to_string(path)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithms::item_knn::ItemKNN;
+    use crate::algorithms::knn::KNNConfig;
+    use crate::algorithms::mf::{MFConfig, MatrixFactorization};
+    use crate::algorithms::nmf::NMF;
+    use crate::dataset::{Dataset, Rating};
+    use crate::recommender::Recommender;
+
+    fn dataset() -> Dataset {
+        Dataset::new(vec![
+            Rating::new(1, 10, 5.0),
+            Rating::new(1, 11, 3.0),
+            Rating::new(2, 10, 4.0),
+            Rating::new(2, 12, 1.0),
+            Rating::new(3, 11, 2.0),
+        ])
+    }
+
+    #[test]
+    fn test_mf_round_trip() {
+        let mut model = MatrixFactorization::new(MFConfig::default().set_num_factors(3));
+        model.fit(&dataset()).unwrap();
+        let envelope = ModelEnvelope::new(model).set_trained_at(1_700_000_000);
+        assert_eq!(envelope.hyperparameters["num_factors"], 3);
+        let loaded =
+            ModelEnvelope::<MatrixFactorization>::from_json(&envelope.to_json().unwrap())
+                .unwrap();
+        assert_eq!(loaded.trained_at, 1_700_000_000);
+        assert_eq!(
+            loaded.model().predict(1, 12),
+            envelope.model().predict(1, 12)
+        );
+        assert_eq!(
+            loaded.into_model().recommend(3, 2),
+            envelope.model().recommend(3, 2)
+        );
+    }
+
+    #[test]
+    fn test_item_knn_round_trip() {
+        let mut model = ItemKNN::new(KNNConfig::default().set_num_neighbors(2));
+        model.fit(&dataset()).unwrap();
+        let json = ModelEnvelope::new(model.clone()).to_json().unwrap();
+        let loaded = ModelEnvelope::<ItemKNN>::from_json(&json).unwrap();
+        assert_eq!(loaded.model().neighbors(10), model.neighbors(10));
+        assert_eq!(loaded.model().recommend(3, 2), model.recommend(3, 2));
+    }
+
+    #[test]
+    fn test_compatibility_checks() {
+        let nmf =
+            NMF::fit(&[vec![1.0, 0.0], vec![0.0, 1.0]], &Default::default()).unwrap();
+        let mut value = serde_json::to_value(ModelEnvelope::new(nmf)).unwrap();
+        value["unknown_field"] = Value::from("added in a later release");
+        assert!(ModelEnvelope::<NMF>::from_json(&value.to_string()).is_ok());
+
+        let error = ModelEnvelope::<ItemKNN>::from_json(&value.to_string())
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("expected model type item_knn, found nmf"));
+
+        value["format_version"] = Value::from(FORMAT_VERSION + 1);
+        let error = ModelEnvelope::<NMF>::from_json(&value.to_string())
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("format version 2"));
+
+        assert!(ModelEnvelope::<NMF>::from_json("{\"w\": []}").is_err());
+    }
+}
@@ -0,0 +1,78 @@
+//! # Errors returned by the fallible operations of the crate
+//!
+use std::fmt;
+
+/// Error type of the crate.
+#[derive(Debug)]
+pub enum Error {
+    /// Reading or writing a file failed.
+    Io(std::io::Error),
+    /// A value could not be serialized or deserialized.
+    Serialization(String),
+    /// A configuration has an invalid value.
+    InvalidConfig(String),
+    /// Input data is malformed or inconsistent.
+    InvalidData(String),
+    /// The loss of an iterative training became too large or not a number.
+    TrainingDiverged { epoch: usize, loss: f64 },
+    /// A database or a key-value store failed, `context` saying what was being
+    /// done.
+    Backend {
+        context: String,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+}
+
+/// Result type of the crate.
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(error) => write!(f, "io error: {}", error),
+            Error::Serialization(message) => {
+                write!(f, "serialization error: {}", message)
+            },
+            Error::InvalidConfig(message) => {
+                write!(f, "invalid configuration: {}", message)
+            },
+            Error::InvalidData(message) => write!(f, "invalid data: {}", message),
+            Error::TrainingDiverged { epoch, loss } => write!(
+                f,
+                "training diverged at epoch {} with a loss of {}, try a lower learning rate",
+                epoch, loss
+            ),
+            Error::Backend { context, source } => write!(f, "{}: {}", context, source),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(error) => Some(error),
+            Error::Backend { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<serde_json::Error> for Error {
+    fn from(error: serde_json::Error) -> Self {
+        Error::Serialization(error.to_string())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<toml::de::Error> for Error {
+    fn from(error: toml::de::Error) -> Self {
+        Error::Serialization(error.to_string())
+    }
+}
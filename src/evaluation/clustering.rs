@@ -0,0 +1,218 @@
+//! # Internal clustering validation
+//! Scores a clustering from the items alone, without ground truth labels, so the
+//! number of clusters can be tuned by comparing the scores of several runs.
+use std::collections::BTreeMap;
+
+use crate::models::Item;
+use crate::similarity::euclidean_distance;
+use crate::utils::centroid;
+
+/// # Silhouette samples
+/// Calculates the silhouette of every item: how much closer it is to the items of
+/// its own cluster than to the items of the nearest other cluster.
+///
+/// ## Parameters:
+/// * `items`: The clustered items.
+/// * `labels`: The cluster of each item.
+///
+/// ## Returns:
+/// * The silhouette of each item, between -1 and 1. It is 0 for the items alone in
+///   their cluster and for every item when there is a single cluster.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::evaluation::clustering::silhouette_samples;
+/// use rec_rsys::models::Item;
+/// let items = vec![
+///     Item::new(1, vec![0.0], None), Item::new(2, vec![1.0], None),
+///     Item::new(3, vec![9.0], None), Item::new(4, vec![10.0], None),
+/// ];
+/// let silhouettes = silhouette_samples(&items, &[0, 0, 1, 1]);
+/// assert!(silhouettes.iter().all(|s| *s > 0.8));
+/// ```
+///
+#[doc = include_str!("../../docs/evaluation/silhouette.md")]
+pub fn silhouette_samples(items: &[Item], labels: &[usize]) -> Vec<f32> {
+    let clusters = clusters(items, labels);
+    if clusters.len() < 2 {
+        return vec![0.0; items.len()];
+    }
+    items
+        .iter()
+        .zip(labels)
+        .map(|(item, label)| {
+            let mean_distance = |members: &[&Item]| {
+                let total: f32 = members
+                    .iter()
+                    .map(|other| euclidean_distance(&item.values, &other.values))
+                    .sum();
+                total / members.len() as f32
+            };
+            let own = &clusters[label];
+            if own.len() < 2 {
+                return 0.0;
+            }
+            let cohesion = mean_distance(own) * own.len() as f32 / (own.len() - 1) as f32;
+            let separation = clusters
+                .iter()
+                .filter(|(other, _)| *other != label)
+                .map(|(_, members)| mean_distance(members))
+                .fold(f32::INFINITY, f32::min);
+            match cohesion.max(separation) {
+                0.0 => 0.0,
+                max => (separation - cohesion) / max,
+            }
+        })
+        .collect()
+}
+
+/// # Silhouette score
+/// Calculates the mean silhouette of the items. Higher is better.
+///
+/// ## Parameters:
+/// * `items`: The clustered items.
+/// * `labels`: The cluster of each item.
+///
+/// ## Returns:
+/// * The mean of [`silhouette_samples`], `NaN` when there are no items.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::evaluation::clustering::silhouette_score;
+/// use rec_rsys::models::Item;
+/// let items = vec![
+///     Item::new(1, vec![0.0], None), Item::new(2, vec![1.0], None),
+///     Item::new(3, vec![9.0], None), Item::new(4, vec![10.0], None),
+/// ];
+/// assert!(silhouette_score(&items, &[0, 0, 1, 1]) > silhouette_score(&items, &[0, 1, 0, 1]));
+/// ```
+///
+#[doc = include_str!("../../docs/evaluation/silhouette.md")]
+pub fn silhouette_score(items: &[Item], labels: &[usize]) -> f32 {
+    let samples = silhouette_samples(items, labels);
+    samples.iter().sum::<f32>() / samples.len() as f32
+}
+
+/// # Davies-Bouldin index
+/// Calculates the average similarity of every cluster with the cluster it is the
+/// most similar to, comparing the spread of the clusters to the distance between
+/// their centroids. Lower is better.
+///
+/// ## Parameters:
+/// * `items`: The clustered items.
+/// * `labels`: The cluster of each item.
+///
+/// ## Returns:
+/// * The index, 0 or more, `NaN` when there are fewer than 2 clusters.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::evaluation::clustering::davies_bouldin_index;
+/// use rec_rsys::models::Item;
+/// let items = vec![
+///     Item::new(1, vec![0.0], None), Item::new(2, vec![1.0], None),
+///     Item::new(3, vec![9.0], None), Item::new(4, vec![10.0], None),
+/// ];
+/// assert!(davies_bouldin_index(&items, &[0, 0, 1, 1]) < davies_bouldin_index(&items, &[0, 1, 0, 1]));
+/// ```
+///
+#[doc = include_str!("../../docs/evaluation/davies_bouldin.md")]
+pub fn davies_bouldin_index(items: &[Item], labels: &[usize]) -> f32 {
+    let clusters = clusters(items, labels);
+    if clusters.len() < 2 {
+        return f32::NAN;
+    }
+    let summaries: Vec<(Vec<f32>, f32)> = clusters
+        .values()
+        .map(|members| {
+            let owned: Vec<Item> = members.iter().map(|&item| item.clone()).collect();
+            let center = centroid(&owned);
+            let scatter = members
+                .iter()
+                .map(|item| euclidean_distance(&item.values, &center))
+                .sum::<f32>()
+                / members.len() as f32;
+            (center, scatter)
+        })
+        .collect();
+    let total: f32 = summaries
+        .iter()
+        .enumerate()
+        .map(|(i, (center_i, scatter_i))| {
+            summaries
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| *j != i)
+                .map(|(_, (center_j, scatter_j))| {
+                    (scatter_i + scatter_j) / euclidean_distance(center_i, center_j)
+                })
+                .fold(0.0, f32::max)
+        })
+        .sum();
+    total / summaries.len() as f32
+}
+
+/// The items of each cluster.
+fn clusters<'a>(items: &'a [Item], labels: &[usize]) -> BTreeMap<usize, Vec<&'a Item>> {
+    let mut clusters: BTreeMap<usize, Vec<&Item>> = BTreeMap::new();
+    items
+        .iter()
+        .zip(labels)
+        .for_each(|(item, label)| clusters.entry(*label).or_default().push(item));
+    clusters
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn items() -> Vec<Item> {
+        [
+            [0.0, 0.0],
+            [0.0, 1.0],
+            [1.0, 0.0],
+            [10.0, 10.0],
+            [10.0, 11.0],
+            [11.0, 10.0],
+        ]
+        .iter()
+        .enumerate()
+        .map(|(id, values)| Item::new(id as u32, values.to_vec(), None))
+        .collect()
+    }
+
+    #[test]
+    fn test_silhouette() {
+        let good = silhouette_score(&items(), &[0, 0, 0, 1, 1, 1]);
+        let bad = silhouette_score(&items(), &[0, 1, 0, 1, 0, 1]);
+        assert!(good > 0.9);
+        assert!(bad < 0.0);
+        assert_eq!(silhouette_samples(&items(), &[0; 6]), vec![0.0; 6]);
+        assert_eq!(silhouette_samples(&items(), &[0, 0, 0, 1, 1, 2])[5], 0.0);
+        assert!(silhouette_score(&[], &[]).is_nan());
+    }
+
+    #[test]
+    fn test_silhouette_sample() {
+        let items = vec![
+            Item::new(1, vec![0.0], None),
+            Item::new(2, vec![2.0], None),
+            Item::new(3, vec![5.0], None),
+        ];
+        // a = 2, b = 3 for the second item.
+        assert_eq!(silhouette_samples(&items, &[0, 0, 1])[1], 1.0 / 3.0);
+    }
+
+    #[test]
+    fn test_davies_bouldin_index() {
+        let items = vec![
+            Item::new(1, vec![0.0], None),
+            Item::new(2, vec![2.0], None),
+            Item::new(3, vec![10.0], None),
+            Item::new(4, vec![14.0], None),
+        ];
+        // Scatters 1 and 2, centroids 1 and 12.
+        assert_eq!(davies_bouldin_index(&items, &[0, 0, 1, 1]), 3.0 / 11.0);
+        assert!(davies_bouldin_index(&items, &[0; 4]).is_nan());
+    }
+}
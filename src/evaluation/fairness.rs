@@ -0,0 +1,168 @@
+//! # Group fairness
+//! Compares how well a recommender serves groups of users, such as demographics,
+//! and how much exposure it gives to groups of items, such as their providers.
+use std::collections::{BTreeMap, HashMap};
+
+use crate::evaluation::UserEvaluation;
+
+/// A metric averaged over the users of every group.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroupParity {
+    /// The mean of the metric and the number of users of each group.
+    pub groups: BTreeMap<String, (f32, usize)>,
+}
+
+impl GroupParity {
+    /// The difference between the best and the worst served groups, 0 when they
+    /// are served equally.
+    pub fn max_gap(&self) -> f32 {
+        let (min, max) = self.bounds();
+        max - min
+    }
+
+    /// The mean of the worst served group divided by the one of the best, 1 when
+    /// they are served equally.
+    pub fn min_ratio(&self) -> f32 {
+        match self.bounds() {
+            (_, 0.0) => 1.0,
+            (min, max) => min / max,
+        }
+    }
+
+    fn bounds(&self) -> (f32, f32) {
+        match self.groups.is_empty() {
+            true => (0.0, 0.0),
+            false => self.groups.values().fold(
+                (f32::INFINITY, f32::NEG_INFINITY),
+                |(min, max), (mean, _)| (min.min(*mean), max.max(*mean)),
+            ),
+        }
+    }
+}
+
+/// # Metric parity
+/// Averages a per-user metric within each group of users.
+///
+/// ## Parameters:
+/// * `per_user`: The evaluation of every user, e.g. [`TopNReport::per_user`](crate::evaluation::TopNReport::per_user).
+/// * `user_groups`: The group of each user, users without group are skipped.
+/// * `metric`: The metric compared, e.g. `|e| e.ndcg`.
+///
+/// ## Returns:
+/// * The mean of the metric in every group.
+///
+/// ## Examples:
+/// ```
+/// use std::collections::HashMap;
+/// use rec_rsys::evaluation::evaluate_user;
+/// use rec_rsys::evaluation::fairness::metric_parity;
+/// let per_user = vec![
+///     evaluate_user(1, &[10, 11], &[10]),
+///     evaluate_user(2, &[10, 11], &[12]),
+/// ];
+/// let groups = HashMap::from([(1, "a".to_string()), (2, "b".to_string())]);
+/// let parity = metric_parity(&per_user, &groups, |e| e.precision);
+/// assert_eq!(parity.max_gap(), 0.5);
+/// assert_eq!(parity.min_ratio(), 0.0);
+/// ```
+pub fn metric_parity<M>(
+    per_user: &[UserEvaluation],
+    user_groups: &HashMap<u32, String>,
+    metric: M,
+) -> GroupParity
+where
+    M: Fn(&UserEvaluation) -> f32,
+{
+    let mut sums: BTreeMap<String, (f32, usize)> = BTreeMap::new();
+    for evaluation in per_user {
+        if let Some(group) = user_groups.get(&evaluation.user_id) {
+            let (sum, count) = sums.entry(group.clone()).or_default();
+            *sum += metric(evaluation);
+            *count += 1;
+        }
+    }
+    GroupParity {
+        groups: sums
+            .into_iter()
+            .map(|(group, (sum, count))| (group, (sum / count as f32, count)))
+            .collect(),
+    }
+}
+
+/// # Exposure share
+/// The share of the attention given to each group of items over recommended
+/// lists, the items at the top of a list being seen more than those at the bottom.
+///
+/// ## Parameters:
+/// * `lists`: The recommended lists, best first.
+/// * `item_groups`: The group of each item. Items without group take their part
+///   of the exposure, which is not reported.
+///
+/// ## Returns:
+/// * The fraction of the total exposure of each group.
+///
+/// ## Examples:
+/// ```
+/// use std::collections::HashMap;
+/// use rec_rsys::evaluation::fairness::exposure_share;
+/// let groups = HashMap::from([(10, "big".to_string()), (11, "small".to_string())]);
+/// let shares = exposure_share(&[vec![10, 11], vec![11, 10]], &groups);
+/// assert_eq!(shares["big"], 0.5);
+/// ```
+#[doc = include_str!("../../docs/evaluation/exposure.md")]
+pub fn exposure_share(
+    lists: &[Vec<u32>],
+    item_groups: &HashMap<u32, String>,
+) -> BTreeMap<String, f32> {
+    let mut exposure: BTreeMap<String, f32> = BTreeMap::new();
+    let mut total = 0.0;
+    for list in lists {
+        for (index, item_id) in list.iter().enumerate() {
+            let attention = 1.0 / (index as f32 + 2.0).log2();
+            total += attention;
+            if let Some(group) = item_groups.get(item_id) {
+                *exposure.entry(group.clone()).or_default() += attention;
+            }
+        }
+    }
+    exposure.values_mut().for_each(|value| *value /= total);
+    exposure
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluation::evaluate_user;
+
+    #[test]
+    fn test_metric_parity() {
+        let per_user = vec![
+            evaluate_user(1, &[10, 11], &[10, 11]),
+            evaluate_user(2, &[10, 11], &[10]),
+            evaluate_user(3, &[10, 11], &[11]),
+            evaluate_user(4, &[10, 11], &[12]),
+        ];
+        let groups = HashMap::from([
+            (1, "young".to_string()),
+            (2, "young".to_string()),
+            (3, "old".to_string()),
+        ]);
+        let parity = metric_parity(&per_user, &groups, |e| e.recall);
+        assert_eq!(parity.groups["young"], (1.0, 2));
+        assert_eq!(parity.groups["old"], (1.0, 1));
+        assert_eq!(parity.max_gap(), 0.0);
+        assert_eq!(parity.min_ratio(), 1.0);
+        let empty = metric_parity(&per_user, &HashMap::new(), |e| e.recall);
+        assert_eq!(empty.max_gap(), 0.0);
+    }
+
+    #[test]
+    fn test_exposure_share() {
+        let groups = HashMap::from([(1, "a".to_string()), (2, "b".to_string())]);
+        let shares = exposure_share(&[vec![1, 2, 3]], &groups);
+        let total = 1.0 + 1.0 / 3_f32.log2() + 0.5;
+        assert!((shares["a"] - 1.0 / total).abs() < 1e-6);
+        assert!((shares["b"] - 1.0 / 3_f32.log2() / total).abs() < 1e-6);
+        assert!(exposure_share(&[], &groups).is_empty());
+    }
+}
@@ -0,0 +1,302 @@
+//! # Tools to evaluate recommenders over a whole test set
+//!
+pub mod clustering;
+pub mod fairness;
+#[cfg(feature = "serde")]
+pub mod runs;
+#[cfg(feature = "serde")]
+pub mod sampled;
+
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::parallelism::prelude::*;
+use crate::parallelism::{default_parallelism, Parallelism};
+use crate::statistics::Running;
+
+type ProgressCallback = dyn Fn(Progress) + Send + Sync;
+
+/// State of an evaluation, reported after each user is evaluated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    /// Number of users already evaluated.
+    pub done: usize,
+    /// Number of users to evaluate.
+    pub total: usize,
+}
+
+/// Metrics of the top-N list recommended to a single user.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UserEvaluation {
+    /// Identifier of the user.
+    pub user_id: u32,
+    /// Number of relevant items in the recommended list.
+    pub hits: usize,
+    /// 1-based position of the first relevant item, if any.
+    pub first_hit_rank: Option<usize>,
+    /// Fraction of the recommended list that is relevant.
+    pub precision: f32,
+    /// Fraction of the relevant items that were recommended.
+    pub recall: f32,
+    /// Normalized discounted cumulative gain of the list.
+    pub ndcg: f32,
+}
+
+/// Top-N metrics averaged over every evaluated user.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TopNReport {
+    /// Number of evaluated users.
+    pub users: usize,
+    /// Fraction of users with at least one relevant recommended item.
+    pub hit_rate: f32,
+    /// Average reciprocal hit rank.
+    pub arhr: f32,
+    /// Mean precision at N.
+    pub precision: f32,
+    /// Mean recall at N.
+    pub recall: f32,
+    /// Mean NDCG at N.
+    pub ndcg: f32,
+    /// The metrics of every user, sorted by user id.
+    pub per_user: Vec<UserEvaluation>,
+}
+
+/// # Top-N evaluator
+/// Generates the recommendations of every test user in parallel and scores them
+/// against the items each user actually interacted with.
+///
+/// Users are evaluated in any order but the results are always aggregated sorted by
+/// user id, so two runs over the same data give exactly the same report.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::evaluation::TopNEvaluator;
+/// use std::collections::HashMap;
+/// let test_set = HashMap::from([(1, vec![10, 11]), (2, vec![12])]);
+/// let report = TopNEvaluator::new(2)
+///     .set_progress(|progress| println!("{}/{}", progress.done, progress.total))
+///     .evaluate(&test_set, |_user, k| vec![10, 12, 13][..k].to_vec());
+/// assert_eq!(report.hit_rate, 1.0);
+/// assert_eq!(report.precision, 0.5);
+/// ```
+///
+#[doc = include_str!("../../docs/evaluation/top_n.md")]
+pub struct TopNEvaluator {
+    num_recommendations: usize,
+    progress: Option<Box<ProgressCallback>>,
+    parallelism: Option<Parallelism>,
+}
+
+impl TopNEvaluator {
+    pub fn new(num_recommendations: usize) -> Self {
+        TopNEvaluator {
+            num_recommendations,
+            progress: None,
+            parallelism: None,
+        }
+    }
+
+    /// Sets a callback invoked after each user is evaluated. It is called from the
+    /// worker threads, so it has to be cheap and thread-safe.
+    pub fn set_progress<P>(mut self, progress: P) -> Self
+    where
+        P: Fn(Progress) + Send + Sync + 'static,
+    {
+        self.progress = Some(Box::new(progress));
+        self
+    }
+
+    /// Sets where the users are evaluated, [`default_parallelism`] otherwise.
+    pub fn set_parallelism(mut self, parallelism: Parallelism) -> Self {
+        self.parallelism = Some(parallelism);
+        self
+    }
+
+    /// Evaluates the recommendations of every user of the test set.
+    ///
+    /// ## Parameters:
+    /// * `test_set`: The relevant items of each user.
+    /// * `recommend`: Returns the ordered recommendations for a user, given the
+    ///   user id and the number of items wanted.
+    ///
+    /// ## Returns:
+    /// * The aggregated report.
+    pub fn evaluate<R>(
+        &self,
+        test_set: &HashMap<u32, Vec<u32>>,
+        recommend: R,
+    ) -> TopNReport
+    where
+        R: Fn(u32, usize) -> Vec<u32> + Sync,
+    {
+        let mut users: Vec<(&u32, &Vec<u32>)> = test_set.iter().collect();
+        users.sort_by_key(|(user_id, _)| **user_id);
+
+        let done = AtomicUsize::new(0);
+        let total = users.len();
+        let parallelism = self.parallelism.clone().unwrap_or_else(default_parallelism);
+        let per_user: Vec<UserEvaluation> = parallelism.install(|| {
+            users
+                .par_iter()
+                .map(|(&user_id, relevant)| {
+                    let recommended = recommend(user_id, self.num_recommendations);
+                    let evaluation = evaluate_user(user_id, &recommended, relevant);
+                    if let Some(progress) = &self.progress {
+                        let done = done.fetch_add(1, Ordering::Relaxed) + 1;
+                        progress(Progress { done, total });
+                    }
+                    evaluation
+                })
+                .collect()
+        });
+
+        aggregate(per_user)
+    }
+}
+
+/// Scores the top-N list recommended to a single user.
+///
+/// ## Parameters:
+/// * `user_id`: The user the list was recommended to.
+/// * `recommended`: The recommended items, best first.
+/// * `relevant`: The items the user actually interacted with.
+///
+/// ## Returns:
+/// * The metrics of the list.
+pub fn evaluate_user(
+    user_id: u32,
+    recommended: &[u32],
+    relevant: &[u32],
+) -> UserEvaluation {
+    let relevant: HashSet<&u32> = relevant.iter().collect();
+    let hit_ranks: Vec<usize> = recommended
+        .iter()
+        .enumerate()
+        .filter(|(_, item)| relevant.contains(item))
+        .map(|(index, _)| index + 1)
+        .collect();
+
+    let dcg: f32 = hit_ranks
+        .iter()
+        .map(|&rank| 1.0 / (rank as f32 + 1.0).log2())
+        .sum();
+    let ideal_dcg: f32 = (1..=relevant.len().min(recommended.len()))
+        .map(|rank| 1.0 / (rank as f32 + 1.0).log2())
+        .sum();
+
+    UserEvaluation {
+        user_id,
+        hits: hit_ranks.len(),
+        first_hit_rank: hit_ranks.first().copied(),
+        precision: ratio(hit_ranks.len(), recommended.len()),
+        recall: ratio(hit_ranks.len(), relevant.len()),
+        ndcg: if ideal_dcg > 0.0 { dcg / ideal_dcg } else { 0.0 },
+    }
+}
+
+fn ratio(numerator: usize, denominator: usize) -> f32 {
+    if denominator == 0 {
+        0.0
+    } else {
+        numerator as f32 / denominator as f32
+    }
+}
+
+fn aggregate(per_user: Vec<UserEvaluation>) -> TopNReport {
+    let users = per_user.len();
+    let average = |metric: &dyn Fn(&UserEvaluation) -> f32| -> f32 {
+        let mut running = Running::default();
+        per_user.iter().for_each(|e| running.push(metric(e) as f64));
+        running.mean().unwrap_or(0.0) as f32
+    };
+    TopNReport {
+        users,
+        hit_rate: average(&|e| if e.hits > 0 { 1.0 } else { 0.0 }),
+        arhr: average(&|e| e.first_hit_rank.map_or(0.0, |rank| 1.0 / rank as f32)),
+        precision: average(&|e| e.precision),
+        recall: average(&|e| e.recall),
+        ndcg: average(&|e| e.ndcg),
+        per_user,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    fn test_set() -> HashMap<u32, Vec<u32>> {
+        HashMap::from([
+            (1, vec![1, 2]),
+            (2, vec![3]),
+            (3, vec![9]),
+            (4, vec![2, 5, 6]),
+        ])
+    }
+
+    fn recommend(_user_id: u32, k: usize) -> Vec<u32> {
+        [2, 5, 3, 1][..k].to_vec()
+    }
+
+    #[test]
+    fn test_evaluate_user() {
+        let evaluation = evaluate_user(7, &[4, 2, 8, 1], &[1, 2]);
+        assert_eq!(evaluation.hits, 2);
+        assert_eq!(evaluation.first_hit_rank, Some(2));
+        assert_eq!(evaluation.precision, 0.5);
+        assert_eq!(evaluation.recall, 1.0);
+        assert_eq!(evaluation.ndcg, 0.650_921);
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let report = TopNEvaluator::new(3).evaluate(&test_set(), recommend);
+        assert_eq!(report.users, 4);
+        assert_eq!(report.hit_rate, 0.75);
+        assert_eq!(report.arhr, 0.583_333_3);
+        assert_eq!(
+            report
+                .per_user
+                .iter()
+                .map(|e| e.user_id)
+                .collect::<Vec<u32>>(),
+            vec![1, 2, 3, 4]
+        );
+    }
+
+    #[test]
+    fn test_evaluate_is_deterministic() {
+        let evaluator = TopNEvaluator::new(2);
+        let first = evaluator.evaluate(&test_set(), recommend);
+        (0..10)
+            .for_each(|_| assert_eq!(evaluator.evaluate(&test_set(), recommend), first));
+    }
+
+    #[test]
+    fn test_evaluate_reports_progress() {
+        let seen: Arc<Mutex<Vec<Progress>>> = Arc::new(Mutex::new(Vec::new()));
+        let recorder = Arc::clone(&seen);
+        TopNEvaluator::new(2)
+            .set_progress(move |progress| recorder.lock().unwrap().push(progress))
+            .evaluate(&test_set(), recommend);
+        let mut done: Vec<usize> = seen.lock().unwrap().iter().map(|p| p.done).collect();
+        done.sort();
+        assert_eq!(done, vec![1, 2, 3, 4]);
+        assert!(seen.lock().unwrap().iter().all(|p| p.total == 4));
+    }
+
+    #[test]
+    fn test_evaluate_sequentially() {
+        let seen: Arc<Mutex<Vec<usize>>> = Arc::new(Mutex::new(Vec::new()));
+        let recorder = Arc::clone(&seen);
+        let report = TopNEvaluator::new(2)
+            .set_parallelism(Parallelism::Sequential)
+            .set_progress(move |progress| recorder.lock().unwrap().push(progress.done))
+            .evaluate(&test_set(), recommend);
+        assert_eq!(*seen.lock().unwrap(), vec![1, 2, 3, 4]);
+        assert_eq!(
+            report,
+            TopNEvaluator::new(2).evaluate(&test_set(), recommend)
+        );
+    }
+}
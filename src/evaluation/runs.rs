@@ -0,0 +1,316 @@
+//! # Persisted evaluation runs
+//! Stores what was evaluated (algorithm, hyperparameters, dataset and crate version)
+//! together with the obtained metrics, so experiments can be compared afterwards.
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::TopNReport;
+use crate::errors::Result;
+use crate::formatting::ReportFormat;
+use crate::ids::{fnv1a, FNV_OFFSET};
+
+/// # Evaluation run
+/// The result of evaluating an algorithm on a dataset.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::evaluation::runs::{compare_runs, dataset_hash, EvaluationRun};
+/// let hash = dataset_hash(b"1,10,4.0\n2,10,3.5\n");
+/// let baseline = EvaluationRun::new("knn")
+///     .set_hyperparameter("k", 10)
+///     .set_dataset_hash(&hash)
+///     .set_metric("rmse", 0.95);
+/// let candidate = EvaluationRun::new("knn")
+///     .set_hyperparameter("k", 20)
+///     .set_dataset_hash(&hash)
+///     .set_metric("rmse", 0.91);
+/// let comparison = compare_runs(&baseline, &candidate);
+/// assert!(comparison.same_dataset);
+/// println!("{}", comparison);
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EvaluationRun {
+    /// Name of the evaluated algorithm.
+    pub algorithm: String,
+    /// Hyperparameters the algorithm was configured with.
+    pub hyperparameters: BTreeMap<String, Value>,
+    /// Fingerprint of the dataset, see [`dataset_hash`].
+    pub dataset_hash: String,
+    /// Version of the crate that produced the run, see [`build_version`].
+    pub version: String,
+    /// Seconds since the unix epoch when the run was created.
+    pub timestamp: u64,
+    /// Obtained metrics by name.
+    pub metrics: BTreeMap<String, f64>,
+}
+
+impl EvaluationRun {
+    pub fn new(algorithm: &str) -> Self {
+        EvaluationRun {
+            algorithm: algorithm.to_string(),
+            hyperparameters: BTreeMap::new(),
+            dataset_hash: String::new(),
+            version: build_version(),
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs()),
+            metrics: BTreeMap::new(),
+        }
+    }
+
+    pub fn set_hyperparameter<V: Into<Value>>(mut self, name: &str, value: V) -> Self {
+        self.hyperparameters.insert(name.to_string(), value.into());
+        self
+    }
+
+    pub fn set_dataset_hash(mut self, dataset_hash: &str) -> Self {
+        self.dataset_hash = dataset_hash.to_string();
+        self
+    }
+
+    pub fn set_metric(mut self, name: &str, value: f64) -> Self {
+        self.metrics.insert(name.to_string(), value);
+        self
+    }
+
+    /// Records the averaged metrics of a top-N evaluation.
+    pub fn set_top_n_report(self, report: &TopNReport) -> Self {
+        self.set_metric("hit_rate", report.hit_rate as f64)
+            .set_metric("arhr", report.arhr as f64)
+            .set_metric("precision", report.precision as f64)
+            .set_metric("recall", report.recall as f64)
+            .set_metric("ndcg", report.ndcg as f64)
+    }
+
+    /// Writes the run as pretty printed JSON.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Reads a run previously written with [`EvaluationRun::save`].
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+}
+
+/// Version of the crate, followed by the `git describe` of the sources when they
+/// were built from a git checkout, e.g. `1.0.0+v1.0.0-3-g2e1ab9c-dirty`.
+pub fn build_version() -> String {
+    let describe = env!("REC_RSYS_GIT_DESCRIBE");
+    if describe.is_empty() {
+        env!("CARGO_PKG_VERSION").to_string()
+    } else {
+        format!("{}+{}", env!("CARGO_PKG_VERSION"), describe)
+    }
+}
+
+/// # Dataset hash
+/// Fingerprints the raw content of a dataset with the 64 bits FNV-1a hash, so two
+/// runs can tell whether they were evaluated on the same data.
+///
+/// ## Parameters:
+/// * `data`: The bytes of the dataset.
+///
+/// ## Returns:
+/// * The hash as 16 hexadecimal characters.
+pub fn dataset_hash(data: &[u8]) -> String {
+    format!("{:016x}", fnv1a(FNV_OFFSET, data))
+}
+
+/// Values of a metric in the two compared runs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetricDiff {
+    pub name: String,
+    pub a: Option<f64>,
+    pub b: Option<f64>,
+}
+
+impl MetricDiff {
+    /// Difference `b - a`, when both runs have the metric.
+    pub fn delta(&self) -> Option<f64> {
+        Some(self.b? - self.a?)
+    }
+}
+
+/// Values of a hyperparameter that differs between the two compared runs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HyperparameterDiff {
+    pub name: String,
+    pub a: Option<Value>,
+    pub b: Option<Value>,
+}
+
+/// Differences between two evaluation runs, see [`compare_runs`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunComparison {
+    pub algorithms: (String, String),
+    pub same_dataset: bool,
+    pub same_version: bool,
+    /// Every metric present in at least one of the runs.
+    pub metrics: Vec<MetricDiff>,
+    /// Only the hyperparameters whose value changed.
+    pub hyperparameters: Vec<HyperparameterDiff>,
+}
+
+/// # Compare runs
+/// Diffs two evaluation runs, `a` being the reference.
+///
+/// ## Parameters:
+/// * `a`: The reference run.
+/// * `b`: The run compared against the reference.
+///
+/// ## Returns:
+/// * The comparison, whose `Display` implementation renders a readable report.
+pub fn compare_runs(a: &EvaluationRun, b: &EvaluationRun) -> RunComparison {
+    let metric_names: BTreeSet<&String> =
+        a.metrics.keys().chain(b.metrics.keys()).collect();
+    let parameter_names: BTreeSet<&String> = a
+        .hyperparameters
+        .keys()
+        .chain(b.hyperparameters.keys())
+        .collect();
+
+    RunComparison {
+        algorithms: (a.algorithm.clone(), b.algorithm.clone()),
+        same_dataset: a.dataset_hash == b.dataset_hash,
+        same_version: a.version == b.version,
+        metrics: metric_names
+            .into_iter()
+            .map(|name| MetricDiff {
+                name: name.clone(),
+                a: a.metrics.get(name).copied(),
+                b: b.metrics.get(name).copied(),
+            })
+            .collect(),
+        hyperparameters: parameter_names
+            .into_iter()
+            .map(|name| HyperparameterDiff {
+                name: name.clone(),
+                a: a.hyperparameters.get(name).cloned(),
+                b: b.hyperparameters.get(name).cloned(),
+            })
+            .filter(|diff| diff.a != diff.b)
+            .collect(),
+    }
+}
+
+impl RunComparison {
+    /// # Report
+    /// The comparison as text, with the metrics written in the given format.
+    ///
+    /// ## Examples:
+    /// ```
+    /// use rec_rsys::evaluation::runs::{compare_runs, EvaluationRun};
+    /// use rec_rsys::formatting::ReportFormat;
+    /// let a = EvaluationRun::new("knn").set_metric("ndcg", 0.41);
+    /// let b = EvaluationRun::new("knn").set_metric("ndcg", 0.4375);
+    /// let report = compare_runs(&a, &b).report(&ReportFormat::default().set_precision(3));
+    /// assert!(report.contains("ndcg: 0.410 -> 0.438 (+0.028)"));
+    /// ```
+    pub fn report(&self, format: &ReportFormat) -> String {
+        let mut report = String::new();
+        // Writing to a string does not fail.
+        let _ = self.write_report(&mut report, format);
+        report
+    }
+
+    fn write_report<W: fmt::Write>(
+        &self,
+        f: &mut W,
+        format: &ReportFormat,
+    ) -> fmt::Result {
+        let display = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+        writeln!(
+            f,
+            "Comparing {} -> {}",
+            self.algorithms.0, self.algorithms.1
+        )?;
+        if !self.same_dataset {
+            writeln!(f, "Warning: the runs were evaluated on different datasets")?;
+        }
+        if !self.same_version {
+            writeln!(f, "Warning: the runs were produced by different versions")?;
+        }
+        for diff in &self.hyperparameters {
+            writeln!(
+                f,
+                "{}: {} -> {}",
+                diff.name,
+                display(diff.a.as_ref().map(Value::to_string)),
+                display(diff.b.as_ref().map(Value::to_string)),
+            )?;
+        }
+        for diff in &self.metrics {
+            writeln!(
+                f,
+                "{}: {} -> {} ({})",
+                diff.name,
+                display(diff.a.map(|value| format.number(value))),
+                display(diff.b.map(|value| format.number(value))),
+                display(diff.delta().map(|delta| format.delta(delta))),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for RunComparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_report(f, &ReportFormat::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dataset_hash() {
+        assert_eq!(dataset_hash(b""), "cbf29ce484222325");
+        assert_eq!(dataset_hash(b"a"), "af63dc4c8601ec8c");
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let path = std::env::temp_dir().join("rec_rsys_test_evaluation_run.json");
+        let run = EvaluationRun::new("knn")
+            .set_hyperparameter("k", 10)
+            .set_hyperparameter("algorithm", "cosine")
+            .set_metric("rmse", 0.93);
+        run.save(&path).unwrap();
+        assert_eq!(EvaluationRun::load(&path).unwrap(), run);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_compare_runs() {
+        let a = EvaluationRun::new("knn")
+            .set_hyperparameter("k", 10)
+            .set_hyperparameter("algorithm", "cosine")
+            .set_dataset_hash("aa")
+            .set_metric("rmse", 1.0)
+            .set_metric("mae", 0.5);
+        let b = EvaluationRun::new("knn")
+            .set_hyperparameter("k", 20)
+            .set_hyperparameter("algorithm", "cosine")
+            .set_dataset_hash("bb")
+            .set_metric("rmse", 0.75);
+        let comparison = compare_runs(&a, &b);
+        assert!(!comparison.same_dataset);
+        assert_eq!(comparison.hyperparameters.len(), 1);
+        assert_eq!(comparison.hyperparameters[0].name, "k");
+        assert_eq!(comparison.metrics[0].name, "mae");
+        assert_eq!(comparison.metrics[0].delta(), None);
+        assert_eq!(comparison.metrics[1].delta(), Some(-0.25));
+        assert!(comparison
+            .to_string()
+            .contains("rmse: 1.000000 -> 0.750000 (-0.250000)"));
+    }
+}
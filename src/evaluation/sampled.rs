@@ -0,0 +1,517 @@
+//! # Sampled evaluation
+//! Ranks every test item among a few sampled negatives instead of the whole
+//! catalog, which makes evaluating large catalogs affordable at the price of
+//! metrics that are not the full-ranking ones, see [`SampledReport::caveats`].
+use std::collections::HashMap;
+use std::fmt;
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+
+use crate::algorithms::config::{ensure, AlgorithmConfig};
+use crate::dataset::Dataset;
+use crate::errors::Result;
+use crate::formatting::ReportFormat;
+use crate::memory::MemoryFootprint;
+use crate::parallelism::default_parallelism;
+use crate::parallelism::prelude::*;
+use crate::recommender::Recommender;
+use crate::sampling::NegativeSampler;
+use crate::sets::IdSet;
+use crate::statistics::Running;
+
+/// How the negatives are drawn from the items of the training data.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum NegativeSampling {
+    /// Every item is equally likely.
+    #[default]
+    Uniform,
+    /// Items are drawn proportionally to their number of ratings raised to the
+    /// exponent, see [`NegativeSampler`].
+    Popularity { exponent: f32 },
+}
+
+impl NegativeSampling {
+    fn exponent(&self) -> f32 {
+        match self {
+            NegativeSampling::Uniform => 0.0,
+            NegativeSampling::Popularity { exponent } => *exponent,
+        }
+    }
+}
+
+/// # Sampled evaluation configuration
+/// How a [`SampledEvaluator`] builds and scores the candidates.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SampledConfig {
+    /// The negatives ranked with each test item.
+    pub num_negatives: usize,
+    /// The cutoff of the hit rate and the NDCG.
+    pub cutoff: usize,
+    pub sampling: NegativeSampling,
+    /// Scales the sampled rank to the expected rank among every item the user did
+    /// not interact with before computing the metrics.
+    pub bias_correction: bool,
+    /// Seed of the draws, each test case getting its own stream.
+    pub seed: u64,
+}
+
+impl Default for SampledConfig {
+    fn default() -> Self {
+        SampledConfig {
+            num_negatives: 100,
+            cutoff: 10,
+            sampling: NegativeSampling::Uniform,
+            bias_correction: false,
+            seed: 42,
+        }
+    }
+}
+
+impl SampledConfig {
+    pub fn set_num_negatives(mut self, num_negatives: usize) -> Self {
+        self.num_negatives = num_negatives;
+        self
+    }
+    pub fn set_cutoff(mut self, cutoff: usize) -> Self {
+        self.cutoff = cutoff;
+        self
+    }
+    pub fn set_sampling(mut self, sampling: NegativeSampling) -> Self {
+        self.sampling = sampling;
+        self
+    }
+    pub fn set_bias_correction(mut self, bias_correction: bool) -> Self {
+        self.bias_correction = bias_correction;
+        self
+    }
+    pub fn set_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+}
+
+impl AlgorithmConfig for SampledConfig {
+    fn validate(&self) -> Result<()> {
+        ensure(self.num_negatives > 0, "num_negatives", "greater than 0")?;
+        ensure(self.cutoff > 0, "cutoff", "greater than 0")?;
+        let exponent = self.sampling.exponent();
+        ensure(
+            exponent >= 0.0 && exponent.is_finite(),
+            "sampling.exponent",
+            "a non-negative number",
+        )
+    }
+}
+
+/// The rank of a test item among its sampled candidates.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SampledRank {
+    pub user_id: u32,
+    pub item_id: u32,
+    /// 1-based rank among the item and its negatives, the ties counting against
+    /// the item.
+    pub rank: usize,
+    /// The number of negatives actually drawn, fewer than configured for users who
+    /// interacted with nearly every item.
+    pub num_negatives: usize,
+    /// The rank the metrics are computed from, the sampled rank scaled to the items
+    /// the user did not interact with when the bias is corrected.
+    pub effective_rank: f64,
+}
+
+/// Metrics of a [`SampledEvaluator`], averaged over the test cases.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SampledReport {
+    /// The number of test cases, pairs of user and item.
+    pub cases: usize,
+    pub num_negatives: usize,
+    pub cutoff: usize,
+    pub sampling: NegativeSampling,
+    pub bias_correction: bool,
+    /// Fraction of the test items ranked within the cutoff.
+    pub hit_rate: f64,
+    /// NDCG at the cutoff, with a single relevant item per case.
+    pub ndcg: f64,
+    /// Mean reciprocal rank.
+    pub mrr: f64,
+    /// Fraction of the negatives ranked below the test item, from the sampled rank.
+    pub auc: f64,
+    pub per_case: Vec<SampledRank>,
+}
+
+impl SampledReport {
+    /// # Caveats
+    /// What the sampled metrics do not tell, written in every report.
+    pub fn caveats(&self) -> Vec<String> {
+        let mut caveats = vec![
+            format!(
+                "metrics rank each test item among {} sampled negatives, not the whole \
+                 catalog, and are higher than the full-ranking ones",
+                self.num_negatives
+            ),
+            "sampled metrics may order models differently than full-ranking ones, \
+             compare models on the full ranking before concluding"
+                .to_string(),
+        ];
+        match self.sampling {
+            NegativeSampling::Uniform => caveats.push(
+                "uniform negatives are mostly unpopular items, easy to rank below \
+                 the test item, which favors popularity-biased models"
+                    .to_string(),
+            ),
+            NegativeSampling::Popularity { .. } => caveats.push(
+                "popularity-sampled negatives are harder than the average item, the \
+                 corrected ranks over-estimate the full ranks"
+                    .to_string(),
+            ),
+        }
+        if self.bias_correction {
+            caveats.push(
+                "the bias correction only holds in expectation, single ranks are \
+                 noisy and the metrics at small cutoffs stay approximate"
+                    .to_string(),
+            );
+        }
+        caveats
+    }
+
+    /// # Report
+    /// The metrics and their caveats as text, written in the given format.
+    ///
+    /// ## Examples:
+    /// ```
+    /// use rec_rsys::evaluation::sampled::{SampledConfig, SampledEvaluator};
+    /// use rec_rsys::dataset::{Dataset, Rating};
+    /// use rec_rsys::formatting::ReportFormat;
+    /// let train = (10..30).map(|item| Rating::new(1, item, 1.0)).collect();
+    /// let test = Dataset::new(vec![Rating::new(2, 10, 1.0)]);
+    /// let config = SampledConfig::default();
+    /// let evaluator = SampledEvaluator::new(config, &Dataset::new(train)).unwrap();
+    /// // Scores the lowest ids first.
+    /// let report = evaluator.evaluate(&test, |_, items| {
+    ///     items.iter().map(|item| -(*item as f32)).collect()
+    /// });
+    /// assert_eq!(report.hit_rate, 1.0);
+    /// let text = report.report(&ReportFormat::default().set_precision(2));
+    /// assert!(text.contains("hit_rate@10: 1.00"));
+    /// assert!(text.contains("Caveat:"));
+    /// ```
+    pub fn report(&self, format: &ReportFormat) -> String {
+        let mut report = String::new();
+        // Writing to a string does not fail.
+        let _ = self.write_report(&mut report, format);
+        report
+    }
+
+    fn write_report<W: fmt::Write>(
+        &self,
+        f: &mut W,
+        format: &ReportFormat,
+    ) -> fmt::Result {
+        writeln!(
+            f,
+            "Sampled evaluation: {} cases, {} negatives each ({:?}){}",
+            self.cases,
+            self.num_negatives,
+            self.sampling,
+            match self.bias_correction {
+                true => ", bias corrected",
+                false => "",
+            }
+        )?;
+        writeln!(
+            f,
+            "hit_rate@{}: {}",
+            self.cutoff,
+            format.number(self.hit_rate)
+        )?;
+        writeln!(f, "ndcg@{}: {}", self.cutoff, format.number(self.ndcg))?;
+        writeln!(f, "mrr: {}", format.number(self.mrr))?;
+        writeln!(f, "auc: {}", format.number(self.auc))?;
+        for caveat in self.caveats() {
+            writeln!(f, "Caveat: {}", caveat)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for SampledReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_report(f, &ReportFormat::default())
+    }
+}
+
+/// # Sampled evaluator
+/// Ranks every test item among negatives sampled from the items of the training
+/// data, the items the user interacted with in the training or the test data
+/// never being drawn. Only the candidates are scored, `1 + N` per test item
+/// whatever the size of the catalog.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::dataset::{Dataset, Rating};
+/// use rec_rsys::evaluation::sampled::{NegativeSampling, SampledConfig, SampledEvaluator};
+/// // Item 10 is rated by every user, the other ones by a single user.
+/// let ratings = (1..=20).flat_map(|user| [(user, 10), (user, 10 + user)]);
+/// let train = Dataset::new(ratings.map(|(u, i)| Rating::new(u, i, 1.0)).collect());
+/// let test = Dataset::new(vec![Rating::new(21, 10, 1.0)]);
+/// let config = SampledConfig::default()
+///     .set_num_negatives(5)
+///     .set_sampling(NegativeSampling::Popularity { exponent: 0.5 });
+/// let evaluator = SampledEvaluator::new(config, &train).unwrap();
+/// // Scores the items by popularity.
+/// let popularity = train.item_user_sets();
+/// let report = evaluator.evaluate(&test, |_, items| {
+///     items.iter().map(|item| popularity[item].len() as f32).collect()
+/// });
+/// assert_eq!(report.per_case[0].rank, 1);
+/// assert_eq!(report.per_case[0].num_negatives, 5);
+/// ```
+#[doc = include_str!("../../docs/evaluation/sampled.md")]
+#[derive(Debug, Clone)]
+pub struct SampledEvaluator {
+    config: SampledConfig,
+    sampler: NegativeSampler,
+    seen: HashMap<u32, IdSet>,
+}
+
+impl SampledEvaluator {
+    /// Samples the negatives from the items of `train`, never drawing the items the
+    /// users rated in it.
+    pub fn new(config: SampledConfig, train: &Dataset) -> Result<Self> {
+        config.validate()?;
+        Ok(SampledEvaluator {
+            sampler: NegativeSampler::from_dataset(train, config.sampling.exponent())?,
+            seen: train.user_item_sets(),
+            config,
+        })
+    }
+
+    pub fn config(&self) -> &SampledConfig {
+        &self.config
+    }
+
+    /// # Evaluate
+    /// Ranks every rating of the test set among its sampled negatives.
+    ///
+    /// ## Parameters:
+    /// * `test`: The test interactions, each one a test case.
+    /// * `score`: Scores items for a user, given the user id and the candidates,
+    ///   in the same order, higher is better.
+    ///
+    /// ## Returns:
+    /// * The report, its cases sorted by user then item.
+    pub fn evaluate<S>(&self, test: &Dataset, score: S) -> SampledReport
+    where
+        S: Fn(u32, &[u32]) -> Vec<f32> + Sync,
+    {
+        let test_items = test.user_item_sets();
+        let mut cases: Vec<(u32, u32)> = test
+            .ratings
+            .iter()
+            .map(|r| (r.user_id, r.item_id))
+            .collect();
+        cases.sort_unstable();
+        cases.dedup();
+        let empty = IdSet::new();
+        let per_case: Vec<SampledRank> = default_parallelism().install(|| {
+            cases
+                .par_iter()
+                .enumerate()
+                .map(|(index, &(user_id, item_id))| {
+                    let mut positives = self.seen.get(&user_id).unwrap_or(&empty).clone();
+                    positives.extend(test_items.get(&user_id).unwrap_or(&empty).iter());
+                    let mut rng = StdRng::seed_from_u64(
+                        self.config.seed.wrapping_add(index as u64),
+                    );
+                    let mut candidates = vec![item_id];
+                    candidates.extend(self.sampler.sample_distinct(
+                        &positives,
+                        self.config.num_negatives,
+                        &mut rng,
+                    ));
+                    let scores = score(user_id, &candidates);
+                    self.rank(user_id, &candidates, &scores, positives.len())
+                })
+                .collect()
+        });
+        self.aggregate(per_case)
+    }
+
+    /// Evaluates the scores [`Recommender::predict`] gives to the candidates, the
+    /// items it can not score ranking last. The recommenders that do not predict
+    /// scores are evaluated with [`SampledEvaluator::evaluate`] and a scorer.
+    pub fn evaluate_recommender<R: Recommender + Sync + ?Sized>(
+        &self,
+        test: &Dataset,
+        recommender: &R,
+    ) -> SampledReport {
+        self.evaluate(test, |user_id, items| {
+            items
+                .iter()
+                .map(|item_id| {
+                    recommender
+                        .predict(user_id, *item_id)
+                        .unwrap_or(f32::NEG_INFINITY)
+                })
+                .collect()
+        })
+    }
+
+    fn rank(
+        &self,
+        user_id: u32,
+        candidates: &[u32],
+        scores: &[f32],
+        num_positives: usize,
+    ) -> SampledRank {
+        let num_negatives = candidates.len() - 1;
+        let rank = match scores.first() {
+            Some(score) if !score.is_nan() => {
+                1 + scores[1..]
+                    .iter()
+                    .filter(|other| other.is_nan() || *other >= score)
+                    .count()
+            },
+            // A missing or invalid score ranks last.
+            _ => candidates.len(),
+        };
+        let effective_rank = match self.config.bias_correction && num_negatives > 0 {
+            true => {
+                let unseen = self.sampler.num_items().saturating_sub(num_positives);
+                1.0 + (rank - 1) as f64 * unseen.max(num_negatives) as f64
+                    / num_negatives as f64
+            },
+            false => rank as f64,
+        };
+        SampledRank {
+            user_id,
+            item_id: candidates[0],
+            rank,
+            num_negatives,
+            effective_rank,
+        }
+    }
+
+    fn aggregate(&self, per_case: Vec<SampledRank>) -> SampledReport {
+        let cutoff = self.config.cutoff as f64;
+        let average = |metric: &dyn Fn(&SampledRank) -> f64| -> f64 {
+            let mut running = Running::default();
+            per_case.iter().for_each(|case| running.push(metric(case)));
+            running.mean().unwrap_or(0.0)
+        };
+        SampledReport {
+            cases: per_case.len(),
+            num_negatives: self.config.num_negatives,
+            cutoff: self.config.cutoff,
+            sampling: self.config.sampling,
+            bias_correction: self.config.bias_correction,
+            hit_rate: average(&|case| (case.effective_rank <= cutoff) as u8 as f64),
+            ndcg: average(&|case| match case.effective_rank <= cutoff {
+                true => 1.0 / (case.effective_rank + 1.0).log2(),
+                false => 0.0,
+            }),
+            mrr: average(&|case| 1.0 / case.effective_rank),
+            auc: average(&|case| match case.num_negatives {
+                0 => 1.0,
+                n => 1.0 - (case.rank - 1) as f64 / n as f64,
+            }),
+            per_case,
+        }
+    }
+}
+
+impl MemoryFootprint for NegativeSampling {}
+
+impl MemoryFootprint for SampledConfig {}
+
+impl MemoryFootprint for SampledRank {}
+
+impl MemoryFootprint for SampledReport {
+    fn heap_size(&self) -> usize {
+        self.per_case.heap_size()
+    }
+}
+
+impl MemoryFootprint for SampledEvaluator {
+    fn heap_size(&self) -> usize {
+        self.sampler.heap_size() + self.seen.heap_size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithms::most_popular::MostPopular;
+    use crate::dataset::Rating;
+
+    /// 100 items rated once by user 1, user 2 being tested on items 0 and 50.
+    fn datasets() -> (Dataset, Dataset) {
+        let train =
+            Dataset::new((0..100).map(|item| Rating::new(1, item, 1.0)).collect());
+        let test = Dataset::new(vec![Rating::new(2, 50, 1.0), Rating::new(2, 0, 1.0)]);
+        (train, test)
+    }
+
+    /// Scores the items by decreasing id.
+    fn by_id(_user_id: u32, items: &[u32]) -> Vec<f32> {
+        items.iter().map(|item| *item as f32).collect()
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let (train, test) = datasets();
+        let config = SampledConfig::default().set_num_negatives(20).set_cutoff(5);
+        let evaluator = SampledEvaluator::new(config, &train).unwrap();
+        let report = evaluator.evaluate(&test, by_id);
+        assert_eq!(report.cases, 2);
+        let (first, second) = (report.per_case[0], report.per_case[1]);
+        assert_eq!((first.item_id, second.item_id), (0, 50));
+        // Item 0 ranks below every negative, neither 0 nor 50 is drawn.
+        assert_eq!(first.num_negatives, 20);
+        assert_eq!(first.rank, 21);
+        assert_eq!(report.per_case[0].effective_rank, 21.0);
+        assert!(report.auc > 0.0 && report.auc < 0.75);
+        assert_eq!(report, evaluator.evaluate(&test, by_id));
+
+        let constant = evaluator.evaluate(&test, |_, items| vec![1.0; items.len()]);
+        assert_eq!(constant.hit_rate, 0.0);
+        assert_eq!(constant.auc, 0.0);
+        let unscored = evaluator.evaluate_recommender(&test, &MostPopular::default());
+        assert_eq!(unscored.mrr, 1.0 / 21.0);
+    }
+
+    #[test]
+    fn test_bias_correction() {
+        let (train, test) = datasets();
+        let config = SampledConfig::default()
+            .set_num_negatives(10)
+            .set_bias_correction(true);
+        let evaluator = SampledEvaluator::new(config, &train).unwrap();
+        let report = evaluator.evaluate(&test, by_id);
+        // 98 items were not interacted with by user 2, 10 of them sampled.
+        let case = report.per_case[0];
+        assert_eq!(case.rank, 11);
+        assert!((case.effective_rank - 99.0).abs() < 1e-9);
+        assert!(report.caveats().len() == 4);
+        assert!(report.to_string().contains("bias corrected"));
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        let (train, _) = datasets();
+        let invalid = [
+            SampledConfig::default().set_num_negatives(0),
+            SampledConfig::default().set_cutoff(0),
+            SampledConfig::default()
+                .set_sampling(NegativeSampling::Popularity { exponent: -1.0 }),
+        ];
+        for config in invalid {
+            assert!(SampledEvaluator::new(config, &train).is_err());
+        }
+    }
+}
@@ -0,0 +1,388 @@
+//! # Exclusions
+//! Items that must not be recommended anymore to a user, kept between calls so
+//! callers do not have to filter the recommendations themselves.
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::bloom::CountingBloomFilter;
+use crate::errors::Result;
+use crate::memory::MemoryFootprint;
+use crate::sets::IdSet;
+
+/// Why an item is excluded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExclusionReason {
+    /// The user already consumed the item.
+    Seen,
+    /// The user gave a negative feedback.
+    Disliked,
+    /// The user returned the item.
+    Returned,
+}
+
+/// # Exclusion store
+/// The excluded items of every user, grouped by reason. Passed to
+/// [`crate::recommender::Recommender::recommend_excluding`] so any recommender skips
+/// them.
+///
+/// The items of a user are kept in an [`IdSet`], whose memory follows the number
+/// of items and not the largest id, so hashed item ids are fine.
+///
+/// The items seen by heavy users can be moved to a [`CountingBloomFilter`] with
+/// [`ExclusionStore::compact_seen`], trading a small rate of wrongly excluded
+/// items for a memory bounded by their number.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::exclusions::{ExclusionReason, ExclusionStore};
+/// let mut store = ExclusionStore::new();
+/// store.exclude(1, 10, ExclusionReason::Disliked);
+/// store.exclude(1, 11, ExclusionReason::Returned);
+/// assert!(store.is_excluded(1, 10));
+/// assert!(!store.is_excluded(2, 10));
+/// assert_eq!(store.excluded(1).collect::<Vec<u32>>(), vec![10, 11]);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ExclusionStore {
+    users: HashMap<u32, HashMap<ExclusionReason, IdSet>>,
+    /// The seen items of the compacted users.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    seen_filters: HashMap<u32, CountingBloomFilter>,
+    /// The items of the compacted users' filters that were included again, a
+    /// filter cannot forget an item without the risk of forgetting others.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    reincluded: HashMap<u32, IdSet>,
+}
+
+impl ExclusionStore {
+    pub fn new() -> Self {
+        ExclusionStore::default()
+    }
+
+    pub fn exclude(&mut self, user_id: u32, item_id: u32, reason: ExclusionReason) {
+        if self.exclude_compacted(user_id, item_id, reason) {
+            return;
+        }
+        self.users
+            .entry(user_id)
+            .or_default()
+            .entry(reason)
+            .or_default()
+            .insert(item_id);
+    }
+
+    pub fn exclude_all<I: IntoIterator<Item = u32>>(
+        &mut self,
+        user_id: u32,
+        item_ids: I,
+        reason: ExclusionReason,
+    ) {
+        if reason == ExclusionReason::Seen && self.is_compacted(user_id) {
+            for item_id in item_ids {
+                self.exclude_compacted(user_id, item_id, reason);
+            }
+            return;
+        }
+        self.users
+            .entry(user_id)
+            .or_default()
+            .entry(reason)
+            .or_default()
+            .extend(item_ids);
+    }
+
+    /// Adds a seen item to the filter of a compacted user, returns false if the
+    /// user is not compacted or the reason is not seen.
+    fn exclude_compacted(
+        &mut self,
+        user_id: u32,
+        item_id: u32,
+        reason: ExclusionReason,
+    ) -> bool {
+        let filter = match reason {
+            ExclusionReason::Seen => self.seen_filters.get_mut(&user_id),
+            _ => None,
+        };
+        let Some(filter) = filter else {
+            return false;
+        };
+        // A re-included item is still in the filter.
+        let reincluded = self
+            .reincluded
+            .get_mut(&user_id)
+            .is_some_and(|items| items.remove(item_id));
+        if !reincluded {
+            filter.insert(item_id);
+        }
+        true
+    }
+
+    /// Whether the filter of a compacted user excludes the item.
+    fn filtered(&self, user_id: u32, item_id: u32) -> bool {
+        self.seen_filters
+            .get(&user_id)
+            .is_some_and(|filter| filter.contains(item_id))
+            && !self
+                .reincluded
+                .get(&user_id)
+                .is_some_and(|items| items.contains(item_id))
+    }
+
+    /// # Compact seen
+    /// Moves the seen items of a user to a counting Bloom filter, for users whose
+    /// history is too large to keep exactly. The items seen afterwards go to the
+    /// filter too. Does nothing if the user is already compacted.
+    ///
+    /// Items of the filter are not listed by [`ExclusionStore::excluded`] and an
+    /// item never seen is excluded with the false positive rate of the filter.
+    ///
+    /// ## Parameters:
+    /// * `user_id`: The user.
+    /// * `capacity`: The number of seen items the filter is sized for, at least
+    ///   the current ones.
+    /// * `false_positive_rate`: The target rate of items wrongly excluded.
+    ///
+    /// ## Examples:
+    /// ```
+    /// use rec_rsys::exclusions::{ExclusionReason, ExclusionStore};
+    /// let mut store = ExclusionStore::new();
+    /// store.exclude_all(1, 0..10_000, ExclusionReason::Seen);
+    /// store.compact_seen(1, 100_000, 0.01).unwrap();
+    /// store.exclude(1, 20_000, ExclusionReason::Seen);
+    /// assert!(store.is_excluded(1, 42) && store.is_excluded(1, 20_000));
+    /// assert_eq!(store.num_excluded(1), 10_001);
+    /// assert!(store.excluded_for(1, ExclusionReason::Seen).is_none());
+    /// ```
+    pub fn compact_seen(
+        &mut self,
+        user_id: u32,
+        capacity: usize,
+        false_positive_rate: f64,
+    ) -> Result<()> {
+        if self.is_compacted(user_id) {
+            return Ok(());
+        }
+        let num_seen = self
+            .excluded_for(user_id, ExclusionReason::Seen)
+            .map_or(0, IdSet::len);
+        let mut filter = CountingBloomFilter::with_capacity(
+            capacity.max(num_seen),
+            false_positive_rate,
+        )?;
+        let seen = self
+            .users
+            .get_mut(&user_id)
+            .and_then(|reasons| reasons.remove(&ExclusionReason::Seen));
+        if let Some(seen) = seen {
+            filter.extend(seen.iter());
+        }
+        self.seen_filters.insert(user_id, filter);
+        Ok(())
+    }
+
+    /// Whether the seen items of the user are kept in a filter.
+    pub fn is_compacted(&self, user_id: u32) -> bool {
+        self.seen_filters.contains_key(&user_id)
+    }
+
+    /// Allows an item to be recommended again, whatever the reason it was excluded.
+    /// The item is not removed from the filter of a compacted user, as it may only
+    /// collide with the items seen, but kept apart as included.
+    pub fn include(&mut self, user_id: u32, item_id: u32) {
+        if let Some(reasons) = self.users.get_mut(&user_id) {
+            reasons.values_mut().for_each(|items| {
+                items.remove(item_id);
+            });
+        }
+        if self.filtered(user_id, item_id) {
+            self.reincluded.entry(user_id).or_default().insert(item_id);
+        }
+    }
+
+    /// Forgets the exclusions of a user for a reason.
+    pub fn clear(&mut self, user_id: u32, reason: ExclusionReason) {
+        if let Some(reasons) = self.users.get_mut(&user_id) {
+            reasons.remove(&reason);
+        }
+        if reason == ExclusionReason::Seen {
+            self.seen_filters.remove(&user_id);
+            self.reincluded.remove(&user_id);
+        }
+    }
+
+    pub fn is_excluded(&self, user_id: u32, item_id: u32) -> bool {
+        self.users
+            .get(&user_id)
+            .is_some_and(|reasons| reasons.values().any(|items| items.contains(item_id)))
+            || self.filtered(user_id, item_id)
+    }
+
+    /// Why an item is excluded for a user, the first of seen, disliked and returned
+    /// if there are several. The items of a compacted user's filter are seen.
+    pub fn reason(&self, user_id: u32, item_id: u32) -> Option<ExclusionReason> {
+        let exact = self.users.get(&user_id).and_then(|reasons| {
+            [
+                ExclusionReason::Seen,
+                ExclusionReason::Disliked,
+                ExclusionReason::Returned,
+            ]
+            .into_iter()
+            .find(|reason| {
+                reasons
+                    .get(reason)
+                    .is_some_and(|items| items.contains(item_id))
+            })
+        });
+        exact.or_else(|| {
+            self.filtered(user_id, item_id)
+                .then_some(ExclusionReason::Seen)
+        })
+    }
+
+    /// The excluded items of a user, in increasing order and without duplicates.
+    /// The seen items of a compacted user are not listed.
+    pub fn excluded(&self, user_id: u32) -> impl Iterator<Item = u32> + '_ {
+        let merged: IdSet = self
+            .users
+            .get(&user_id)
+            .into_iter()
+            .flat_map(|reasons| reasons.values())
+            .flat_map(|items| items.iter())
+            .collect();
+        merged.into_iter()
+    }
+
+    /// The items excluded for a given reason.
+    pub fn excluded_for(&self, user_id: u32, reason: ExclusionReason) -> Option<&IdSet> {
+        self.users
+            .get(&user_id)
+            .and_then(|reasons| reasons.get(&reason))
+    }
+
+    /// Upper bound of the number of items excluded for a user.
+    pub fn num_excluded(&self, user_id: u32) -> usize {
+        let exact = self
+            .users
+            .get(&user_id)
+            .map_or(0, |reasons| reasons.values().map(|items| items.len()).sum());
+        exact
+            + self
+                .seen_filters
+                .get(&user_id)
+                .map_or(0, |filter| filter.len())
+    }
+
+    /// Writes the store as JSON.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        fs::write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// Reads a store previously written with [`ExclusionStore::save`].
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+}
+
+impl MemoryFootprint for ExclusionReason {}
+
+impl MemoryFootprint for ExclusionStore {
+    fn heap_size(&self) -> usize {
+        self.users.heap_size()
+            + self.seen_filters.heap_size()
+            + self.reincluded.heap_size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_include_and_clear() {
+        let mut store = ExclusionStore::new();
+        store.exclude_all(1, [10, 11, 12], ExclusionReason::Seen);
+        store.exclude(1, 11, ExclusionReason::Disliked);
+        assert_eq!(store.num_excluded(1), 4);
+        store.include(1, 11);
+        assert!(!store.is_excluded(1, 11));
+        store.clear(1, ExclusionReason::Seen);
+        assert_eq!(store.excluded(1).count(), 0);
+    }
+
+    #[test]
+    fn test_large_item_ids() {
+        let mut store = ExclusionStore::new();
+        for user_id in 0..100 {
+            store.exclude(user_id, u32::MAX, ExclusionReason::Disliked);
+            store.exclude_all(user_id, [3, 1 << 31], ExclusionReason::Seen);
+        }
+        assert!(store.is_excluded(42, u32::MAX) && store.is_excluded(42, 1 << 31));
+        assert!(!store.is_excluded(42, 4));
+        assert!(store.heap_size() < 1024 * 1024);
+    }
+
+    #[test]
+    fn test_compact_seen() {
+        let mut store = ExclusionStore::new();
+        store.exclude_all(1, [10, 11], ExclusionReason::Seen);
+        store.exclude(1, 12, ExclusionReason::Disliked);
+        assert!(store.compact_seen(1, 100, 2.0).is_err());
+        assert_eq!(store.num_excluded(1), 3);
+        assert!(!store.is_compacted(1));
+
+        store.compact_seen(1, 100, 0.01).unwrap();
+        assert!(store.is_compacted(1));
+        assert_eq!(store.excluded(1).collect::<Vec<u32>>(), vec![12]);
+        assert!(store.is_excluded(1, 10) && store.is_excluded(1, 12));
+        assert_eq!(store.reason(1, 10), Some(ExclusionReason::Seen));
+        assert_eq!(store.reason(1, 12), Some(ExclusionReason::Disliked));
+        assert_eq!(store.reason(2, 12), None);
+        store.include(1, 10);
+        assert!(!store.is_excluded(1, 10));
+        store.exclude(1, 13, ExclusionReason::Seen);
+        store.compact_seen(1, 100, 0.01).unwrap();
+        assert!(store.is_excluded(1, 11) && store.is_excluded(1, 13));
+        store.clear(1, ExclusionReason::Seen);
+        assert!(!store.is_excluded(1, 11) && !store.is_compacted(1));
+    }
+
+    #[test]
+    fn test_include_filter_collision() {
+        let mut store = ExclusionStore::new();
+        let seen: Vec<u32> = (0..50).collect();
+        store.exclude_all(1, seen.iter().copied(), ExclusionReason::Seen);
+        store.compact_seen(1, 50, 0.2).unwrap();
+        let collision = (1000..)
+            .find(|&item_id| store.is_excluded(1, item_id))
+            .unwrap();
+        store.include(1, collision);
+        store.include(1, collision);
+        store.include(1, 3);
+        assert!(!store.is_excluded(1, collision) && !store.is_excluded(1, 3));
+        assert!(seen[4..]
+            .iter()
+            .all(|&item_id| store.is_excluded(1, item_id)));
+        assert!(seen[..3]
+            .iter()
+            .all(|&item_id| store.is_excluded(1, item_id)));
+        store.exclude(1, 3, ExclusionReason::Seen);
+        assert_eq!(store.reason(1, 3), Some(ExclusionReason::Seen));
+    }
+
+    #[test]
+    fn test_save_load() {
+        let mut store = ExclusionStore::new();
+        store.exclude(3, 7, ExclusionReason::Returned);
+        store.exclude(4, 8, ExclusionReason::Seen);
+        store.compact_seen(4, 10, 0.01).unwrap();
+        let path = std::env::temp_dir().join("rec_rsys_exclusions_test.json");
+        store.save(&path).unwrap();
+        assert_eq!(ExclusionStore::load(&path).unwrap(), store);
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,498 @@
+//! # Exploration
+//! A recommender only learns about the items it shows. Serving a small fraction of
+//! exploratory items in the lists gathers feedback on new or overlooked items, and
+//! logging the exploratory slots lets that feedback be attributed afterwards.
+use std::collections::{HashMap, HashSet};
+use std::sync::{Mutex, MutexGuard};
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::algorithms::config::{ensure, AlgorithmConfig};
+use crate::dataset::Dataset;
+use crate::errors::Result;
+use crate::exclusions::ExclusionStore;
+use crate::memory::MemoryFootprint;
+use crate::recommender::{Recommendation, Recommender};
+use crate::sampling::{reservoir, shuffled_reservoir};
+
+/// How the exploratory items are picked from the pool.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "name", rename_all = "snake_case")]
+pub enum ExplorationStrategy {
+    /// Uniformly at random.
+    Random,
+    /// The items of the highest upper confidence bound (UCB1): the mean reward of
+    /// an item plus `c` times the uncertainty of that mean. Items never shown come
+    /// first.
+    Ucb { c: f32 },
+}
+
+/// # Exploration configuration
+/// The slots of a list an [`ExplorationRecommender`] may replace.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::algorithms::config::AlgorithmConfig;
+/// use rec_rsys::exploration::{ExplorationConfig, ExplorationStrategy};
+/// let config = ExplorationConfig::default()
+///     .set_rate(0.2)
+///     .set_min_position(3)
+///     .set_strategy(ExplorationStrategy::Ucb { c: 1.0 });
+/// assert!(config.validate().is_ok());
+/// assert!(ExplorationConfig::default().set_rate(1.5).validate().is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExplorationConfig {
+    /// Probability of each eligible slot to be exploratory.
+    pub rate: f32,
+    /// The slots before this position, 0 being the first, are never replaced so
+    /// the head of the list stays exploitative.
+    pub min_position: usize,
+    /// Maximum number of exploratory slots in a list, unbounded when `None`.
+    pub max_explored: Option<usize>,
+    pub strategy: ExplorationStrategy,
+    pub seed: u64,
+}
+
+impl Default for ExplorationConfig {
+    fn default() -> Self {
+        ExplorationConfig {
+            rate: 0.1,
+            min_position: 0,
+            max_explored: None,
+            strategy: ExplorationStrategy::Random,
+            seed: 42,
+        }
+    }
+}
+
+impl ExplorationConfig {
+    pub fn set_rate(mut self, rate: f32) -> Self {
+        self.rate = rate;
+        self
+    }
+    pub fn set_min_position(mut self, min_position: usize) -> Self {
+        self.min_position = min_position;
+        self
+    }
+    pub fn set_max_explored(mut self, max_explored: usize) -> Self {
+        self.max_explored = Some(max_explored);
+        self
+    }
+    pub fn set_strategy(mut self, strategy: ExplorationStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+    pub fn set_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+}
+
+impl AlgorithmConfig for ExplorationConfig {
+    fn validate(&self) -> Result<()> {
+        ensure((0.0..=1.0).contains(&self.rate), "rate", "between 0 and 1")?;
+        match self.strategy {
+            ExplorationStrategy::Ucb { c } => {
+                ensure(c.is_finite() && c >= 0.0, "c", "a non-negative number")
+            },
+            ExplorationStrategy::Random => Ok(()),
+        }
+    }
+}
+
+/// An exploratory slot of a served list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExploratorySlot {
+    /// The position in the list, 0 being the first.
+    pub position: usize,
+    pub item_id: u32,
+    /// The item the model recommended at that position.
+    pub replaced_item_id: u32,
+}
+
+/// # Explored list
+/// A served list and its exploratory slots, to log along with the impression so
+/// the rewards can be attributed to exploration or exploitation.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ExploredList {
+    /// The list, best first. An exploratory slot keeps the score of the item it
+    /// replaced so the list stays sorted, that score says nothing about the
+    /// injected item.
+    pub recommendations: Vec<Recommendation>,
+    /// Sorted by position.
+    pub exploratory: Vec<ExploratorySlot>,
+}
+
+impl ExploredList {
+    /// Whether the item at the position was injected by exploration.
+    pub fn is_exploratory(&self, position: usize) -> bool {
+        self.exploratory
+            .binary_search_by_key(&position, |slot| slot.position)
+            .is_ok()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Arm {
+    impressions: u64,
+    rewards: f64,
+}
+
+#[derive(Debug)]
+struct State {
+    rng: StdRng,
+    arms: HashMap<u32, Arm>,
+}
+
+/// # Exploration recommender
+/// Wraps a recommender and replaces random slots of its lists with items of an
+/// exploration pool, e.g. the newest items. An injected item takes the score of the
+/// item it replaces, so the list stays sorted: the scores of the exploratory slots
+/// are not scores of their items and should not be compared with other lists.
+///
+/// With [`ExplorationStrategy::Ucb`], every exploratory impression counts as a
+/// pull of the item and [`ExplorationRecommender::record_reward`] feeds back its
+/// rewards, so the items that perform get explored more.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::algorithms::most_popular::MostPopular;
+/// use rec_rsys::dataset::{Dataset, Rating};
+/// use rec_rsys::exploration::{ExplorationConfig, ExplorationRecommender};
+/// use rec_rsys::recommender::Recommender;
+/// let config = ExplorationConfig::default().set_rate(1.0).set_min_position(1);
+/// let mut model = ExplorationRecommender::new(MostPopular::default(), vec![99], config).unwrap();
+/// model.fit(&Dataset::new(vec![
+///     Rating::new(1, 10, 1.0), Rating::new(2, 10, 1.0), Rating::new(2, 11, 1.0),
+///     Rating::new(3, 12, 1.0),
+/// ])).unwrap();
+/// let list = model.recommend_explored(4, 3);
+/// assert_eq!(list.recommendations[0].item_id, 10);
+/// assert_eq!(list.exploratory.len(), 1);
+/// assert_eq!(list.exploratory[0].item_id, 99);
+/// assert!(list.is_exploratory(list.exploratory[0].position));
+/// ```
+#[derive(Debug)]
+pub struct ExplorationRecommender<R> {
+    inner: R,
+    pool: Vec<u32>,
+    config: ExplorationConfig,
+    state: Mutex<State>,
+}
+
+impl<R: Recommender> ExplorationRecommender<R> {
+    /// # New
+    /// ## Parameters:
+    /// * `inner`: The recommender whose lists are explored.
+    /// * `pool`: The items to explore.
+    /// * `config`: Which slots are explored and how the items are picked.
+    pub fn new(inner: R, pool: Vec<u32>, config: ExplorationConfig) -> Result<Self> {
+        config.validate()?;
+        let state = State {
+            rng: StdRng::seed_from_u64(config.seed),
+            arms: HashMap::new(),
+        };
+        Ok(ExplorationRecommender {
+            inner,
+            pool,
+            config,
+            state: Mutex::new(state),
+        })
+    }
+
+    pub fn inner(&self) -> &R {
+        &self.inner
+    }
+
+    pub fn config(&self) -> &ExplorationConfig {
+        &self.config
+    }
+
+    pub fn pool(&self) -> &[u32] {
+        &self.pool
+    }
+
+    /// Replaces the items to explore, keeping the rewards gathered so far.
+    pub fn set_pool(&mut self, pool: Vec<u32>) {
+        self.pool = pool;
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        // A panic cannot leave the generator or the counters half updated.
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Credits an exploratory item with a reward, e.g. 1 for a click and 0
+    /// otherwise. Only used by [`ExplorationStrategy::Ucb`].
+    pub fn record_reward(&self, item_id: u32, reward: f32) {
+        self.state().arms.entry(item_id).or_default().rewards += reward as f64;
+    }
+
+    /// # Recommend explored
+    /// Recommends like the wrapped recommender, with some slots replaced by
+    /// exploratory items.
+    ///
+    /// ## Parameters:
+    /// * `user_id`: The user to recommend items to.
+    /// * `num_items`: The maximum number of items to recommend.
+    ///
+    /// ## Returns:
+    /// * The list and its exploratory slots. Pool items already in the list are
+    ///   never injected, so fewer slots are explored when the pool runs out.
+    pub fn recommend_explored(&self, user_id: u32, num_items: usize) -> ExploredList {
+        self.explore(self.inner.recommend(user_id, num_items), |_| false)
+    }
+
+    /// Recommends like [`ExplorationRecommender::recommend_explored`], the items
+    /// excluded for the user in the store being neither recommended nor explored.
+    pub fn recommend_explored_excluding(
+        &self,
+        user_id: u32,
+        num_items: usize,
+        exclusions: &ExclusionStore,
+    ) -> ExploredList {
+        let recommendations = self
+            .inner
+            .recommend_excluding(user_id, num_items, exclusions);
+        self.explore(recommendations, |item_id| {
+            exclusions.is_excluded(user_id, item_id)
+        })
+    }
+
+    /// Replaces some slots of the list with the items of the pool that are neither
+    /// listed nor excluded.
+    fn explore(
+        &self,
+        mut recommendations: Vec<Recommendation>,
+        excluded: impl Fn(u32) -> bool,
+    ) -> ExploredList {
+        let mut state = self.state();
+        let State { rng, arms } = &mut *state;
+        let mut positions: Vec<usize> = (self.config.min_position..recommendations.len())
+            .filter(|_| rng.gen_bool(self.config.rate as f64))
+            .collect();
+        if let Some(max_explored) = self.config.max_explored {
+            // Keeps a uniform subset of the positions, in order.
+            positions = reservoir(positions, max_explored, rng);
+            positions.sort_unstable();
+        }
+        if positions.is_empty() {
+            return ExploredList {
+                recommendations,
+                exploratory: Vec::new(),
+            };
+        }
+        let listed: HashSet<u32> = recommendations.iter().map(|r| r.item_id).collect();
+        let unseen = self
+            .pool
+            .iter()
+            .copied()
+            .filter(|id| !listed.contains(id) && !excluded(*id));
+        let items: Vec<u32> = match self.config.strategy {
+            ExplorationStrategy::Random => {
+                shuffled_reservoir(unseen, positions.len(), rng)
+            },
+            ExplorationStrategy::Ucb { c } => {
+                let pulls: u64 = arms.values().map(|arm| arm.impressions).sum();
+                let bound = |item_id: &u32| match arms.get(item_id) {
+                    Some(arm) if arm.impressions > 0 => {
+                        let n = arm.impressions as f64;
+                        arm.rewards / n
+                            + c as f64 * (2.0 * (pulls.max(1) as f64).ln() / n).sqrt()
+                    },
+                    _ => f64::INFINITY,
+                };
+                let mut bounds: Vec<(f64, u32)> =
+                    unseen.map(|item_id| (bound(&item_id), item_id)).collect();
+                bounds.sort_by(|a, b| b.0.total_cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+                bounds.truncate(positions.len());
+                bounds.into_iter().map(|(_, item_id)| item_id).collect()
+            },
+        };
+        let exploratory = positions
+            .into_iter()
+            .zip(items)
+            .map(|(position, item_id)| {
+                arms.entry(item_id).or_default().impressions += 1;
+                let replaced_item_id = recommendations[position].item_id;
+                recommendations[position].item_id = item_id;
+                ExploratorySlot {
+                    position,
+                    item_id,
+                    replaced_item_id,
+                }
+            })
+            .collect();
+        ExploredList {
+            recommendations,
+            exploratory,
+        }
+    }
+}
+
+impl<R: Recommender> Recommender for ExplorationRecommender<R> {
+    fn fit(&mut self, dataset: &Dataset) -> Result<()> {
+        self.inner.fit(dataset)
+    }
+
+    /// The list of [`ExplorationRecommender::recommend_explored`], without the log
+    /// of its exploratory slots.
+    fn recommend(&self, user_id: u32, num_items: usize) -> Vec<Recommendation> {
+        self.recommend_explored(user_id, num_items).recommendations
+    }
+
+    fn recommend_excluding(
+        &self,
+        user_id: u32,
+        num_items: usize,
+        exclusions: &ExclusionStore,
+    ) -> Vec<Recommendation> {
+        self.recommend_explored_excluding(user_id, num_items, exclusions)
+            .recommendations
+    }
+}
+
+impl MemoryFootprint for ExplorationConfig {}
+
+impl MemoryFootprint for ExploratorySlot {}
+
+impl MemoryFootprint for ExploredList {
+    fn heap_size(&self) -> usize {
+        self.recommendations.capacity() * size_of::<Recommendation>()
+            + self.exploratory.heap_size()
+    }
+}
+
+impl<R: MemoryFootprint> MemoryFootprint for ExplorationRecommender<R> {
+    fn heap_size(&self) -> usize {
+        let state = self
+            .state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let arms = state.arms.capacity() * (size_of::<(u32, Arm)>() + 1);
+        self.inner.heap_size() + self.pool.heap_size() + arms
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Recommends the items 1 to `num_items`, of a catalog of 1000, to everyone.
+    struct Ranked;
+
+    impl Recommender for Ranked {
+        fn fit(&mut self, _dataset: &Dataset) -> Result<()> {
+            Ok(())
+        }
+
+        fn recommend(&self, _user_id: u32, num_items: usize) -> Vec<Recommendation> {
+            (1..=num_items.min(1000) as u32)
+                .map(|item_id| Recommendation {
+                    item_id,
+                    score: 1.0 / item_id as f32,
+                })
+                .collect()
+        }
+    }
+
+    #[test]
+    fn test_position_constraints() {
+        let config = ExplorationConfig::default()
+            .set_rate(1.0)
+            .set_min_position(2)
+            .set_max_explored(3);
+        let model =
+            ExplorationRecommender::new(Ranked, (100..200).collect(), config).unwrap();
+        for user_id in 0..20 {
+            let list = model.recommend_explored(user_id, 10);
+            assert_eq!(list.exploratory.len(), 3);
+            assert!(list.exploratory.iter().all(|slot| slot.position >= 2));
+            assert!(list
+                .exploratory
+                .windows(2)
+                .all(|w| w[0].position < w[1].position));
+            for slot in &list.exploratory {
+                assert_eq!(list.recommendations[slot.position].item_id, slot.item_id);
+                assert_eq!(slot.replaced_item_id, slot.position as u32 + 1);
+            }
+            assert!(list
+                .recommendations
+                .windows(2)
+                .all(|w| w[0].score >= w[1].score));
+        }
+        let never = ExplorationConfig::default().set_rate(0.0);
+        let model = ExplorationRecommender::new(Ranked, vec![100], never).unwrap();
+        assert!(model.recommend_explored(1, 10).exploratory.is_empty());
+    }
+
+    #[test]
+    fn test_ucb_explores_rewarded_items() {
+        let config = ExplorationConfig::default()
+            .set_rate(1.0)
+            .set_min_position(4)
+            .set_strategy(ExplorationStrategy::Ucb { c: 0.1 });
+        let model =
+            ExplorationRecommender::new(Ranked, vec![100, 101, 102], config).unwrap();
+        // Every item is shown once before any is shown twice.
+        let first: HashSet<u32> = (0..3)
+            .map(|user_id| model.recommend_explored(user_id, 5).exploratory[0].item_id)
+            .collect();
+        assert_eq!(first.len(), 3);
+        model.record_reward(101, 1.0);
+        let next = model.recommend_explored(0, 5);
+        assert_eq!(next.exploratory[0].item_id, 101);
+        assert!(next.is_exploratory(4) && !next.is_exploratory(3));
+    }
+
+    #[test]
+    fn test_random_exploration_of_long_lists() {
+        let config = ExplorationConfig::default().set_rate(1.0);
+        let model =
+            ExplorationRecommender::new(Ranked, (100..1100).collect(), config).unwrap();
+        let list = model.recommend_explored(1, 25);
+        assert_eq!(list.exploratory.len(), 25);
+        let items: HashSet<u32> =
+            list.recommendations.iter().map(|r| r.item_id).collect();
+        assert_eq!(items.len(), 25);
+        assert!(items.iter().all(|item_id| *item_id >= 100));
+    }
+
+    #[test]
+    fn test_excluded_items_are_not_explored() {
+        use crate::exclusions::ExclusionReason;
+
+        let config = ExplorationConfig::default()
+            .set_rate(1.0)
+            .set_min_position(1);
+        let model =
+            ExplorationRecommender::new(Ranked, vec![100, 101, 102], config).unwrap();
+        let mut exclusions = ExclusionStore::new();
+        exclusions.exclude_all(1, [1, 100, 101], ExclusionReason::Seen);
+        let list = model.recommend_explored_excluding(1, 3, &exclusions);
+        let items: Vec<u32> = list.recommendations.iter().map(|r| r.item_id).collect();
+        assert_eq!(items[0], 2);
+        assert!(items
+            .iter()
+            .all(|item_id| !exclusions.is_excluded(1, *item_id)));
+        assert_eq!(list.exploratory.len(), 1);
+        assert_eq!(list.exploratory[0].item_id, 102);
+        let excluded = model.recommend_excluding(1, 3, &exclusions);
+        assert!(excluded.iter().all(|r| ![1, 100, 101].contains(&r.item_id)));
+    }
+
+    #[test]
+    fn test_pool_already_listed() {
+        let config = ExplorationConfig::default().set_rate(1.0);
+        let model = ExplorationRecommender::new(Ranked, vec![1, 2], config).unwrap();
+        let list = model.recommend_explored(1, 5);
+        assert!(list.exploratory.is_empty());
+        assert_eq!(list.recommendations, Ranked.recommend(1, 5));
+    }
+}
@@ -0,0 +1,385 @@
+//! # Factor matrices
+//! Storage of the latent factors learned by the matrix factorization algorithms,
+//! optionally in half precision to halve the memory of large models.
+use half::{bf16, f16};
+use serde::{Deserialize, Serialize};
+
+use crate::errors::{Error, Result};
+use crate::memory::MemoryFootprint;
+use crate::utils::dot;
+
+/// How the values of a [`FactorMatrix`] are stored. Computations are always done
+/// in `f32`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Precision {
+    #[default]
+    F32,
+    /// IEEE half precision: 10 bits of mantissa, values up to 65504.
+    F16,
+    /// Brain float: the range of `f32` with 7 bits of mantissa.
+    BF16,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Storage {
+    F32(Vec<f32>),
+    F16(Vec<f16>),
+    BF16(Vec<bf16>),
+}
+
+impl Storage {
+    fn len(&self) -> usize {
+        match self {
+            Storage::F32(values) => values.len(),
+            Storage::F16(values) => values.len(),
+            Storage::BF16(values) => values.len(),
+        }
+    }
+}
+
+/// # Factor matrix
+/// A dense row-major matrix with one row of latent factors per user or item.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::factors::{FactorMatrix, Precision};
+/// let factors = FactorMatrix::from_rows(&[vec![0.5, 1.25], vec![-2.0, 0.1]]).unwrap();
+/// let compressed = factors.to_precision(Precision::F16);
+/// assert_eq!(compressed.get(0, 1), 1.25);
+/// assert!(compressed.precision_diff(&factors).unwrap().max_abs_error < 1e-3);
+/// ```
+#[doc = include_str!("../docs/factors/half_precision.md")]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "RawFactorMatrix")]
+pub struct FactorMatrix {
+    rows: usize,
+    dims: usize,
+    storage: Storage,
+}
+
+/// A deserialized [`FactorMatrix`] whose shape is not checked yet.
+#[derive(Deserialize)]
+struct RawFactorMatrix {
+    rows: usize,
+    dims: usize,
+    storage: Storage,
+}
+
+impl TryFrom<RawFactorMatrix> for FactorMatrix {
+    type Error = Error;
+
+    fn try_from(raw: RawFactorMatrix) -> Result<Self> {
+        if raw.rows.checked_mul(raw.dims) != Some(raw.storage.len()) {
+            return Err(Error::InvalidData(format!(
+                "{} values for {} rows of {} factors",
+                raw.storage.len(),
+                raw.rows,
+                raw.dims
+            )));
+        }
+        Ok(FactorMatrix {
+            rows: raw.rows,
+            dims: raw.dims,
+            storage: raw.storage,
+        })
+    }
+}
+
+impl FactorMatrix {
+    /// A matrix of zeros stored in `f32`.
+    pub fn new(rows: usize, dims: usize) -> Self {
+        FactorMatrix {
+            rows,
+            dims,
+            storage: Storage::F32(vec![0.0; rows * dims]),
+        }
+    }
+
+    /// A matrix stored in `f32` from its rows, which must all have the same length.
+    pub fn from_rows(rows: &[Vec<f32>]) -> Result<Self> {
+        let dims = rows.first().map_or(0, |row| row.len());
+        if let Some(index) = rows.iter().position(|row| row.len() != dims) {
+            return Err(Error::InvalidData(format!(
+                "row {} has {} factors, expected {}",
+                index,
+                rows[index].len(),
+                dims
+            )));
+        }
+        Ok(FactorMatrix {
+            rows: rows.len(),
+            dims,
+            storage: Storage::F32(rows.concat()),
+        })
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn dims(&self) -> usize {
+        self.dims
+    }
+
+    pub fn precision(&self) -> Precision {
+        match self.storage {
+            Storage::F32(_) => Precision::F32,
+            Storage::F16(_) => Precision::F16,
+            Storage::BF16(_) => Precision::BF16,
+        }
+    }
+
+    pub fn get(&self, row: usize, dim: usize) -> f32 {
+        let index = row * self.dims + dim;
+        match &self.storage {
+            Storage::F32(values) => values[index],
+            Storage::F16(values) => values[index].to_f32(),
+            Storage::BF16(values) => values[index].to_f32(),
+        }
+    }
+
+    /// Sets a value, rounded to the precision of the storage.
+    pub fn set(&mut self, row: usize, dim: usize, value: f32) {
+        let index = row * self.dims + dim;
+        match &mut self.storage {
+            Storage::F32(values) => values[index] = value,
+            Storage::F16(values) => values[index] = f16::from_f32(value),
+            Storage::BF16(values) => values[index] = bf16::from_f32(value),
+        }
+    }
+
+    /// The factors of a row converted to `f32`.
+    pub fn row(&self, row: usize) -> Vec<f32> {
+        let range = row * self.dims..(row + 1) * self.dims;
+        match &self.storage {
+            Storage::F32(values) => values[range].to_vec(),
+            Storage::F16(values) => values[range].iter().map(|v| v.to_f32()).collect(),
+            Storage::BF16(values) => values[range].iter().map(|v| v.to_f32()).collect(),
+        }
+    }
+
+    /// The factors of a row to update them in place, only available in `f32` so
+    /// training never accumulates rounding errors.
+    pub fn row_mut(&mut self, row: usize) -> Option<&mut [f32]> {
+        let range = row * self.dims..(row + 1) * self.dims;
+        match &mut self.storage {
+            Storage::F32(values) => Some(&mut values[range]),
+            _ => None,
+        }
+    }
+
+    /// All the rows converted to `f32`.
+    pub fn to_rows(&self) -> Vec<Vec<f32>> {
+        (0..self.rows).map(|row| self.row(row)).collect()
+    }
+
+    /// Dot product of a row with a vector, computed in `f32`.
+    pub fn dot(&self, row: usize, vector: &[f32]) -> f32 {
+        match &self.storage {
+            Storage::F32(values) => {
+                dot(&values[row * self.dims..(row + 1) * self.dims], vector)
+            },
+            _ => dot(&self.row(row), vector),
+        }
+    }
+
+    /// A copy of the matrix stored with another precision.
+    pub fn to_precision(&self, precision: Precision) -> Self {
+        let values: Vec<f32> = (0..self.rows).flat_map(|row| self.row(row)).collect();
+        let storage = match precision {
+            Precision::F32 => Storage::F32(values),
+            Precision::F16 => {
+                Storage::F16(values.iter().map(|v| f16::from_f32(*v)).collect())
+            },
+            Precision::BF16 => {
+                Storage::BF16(values.iter().map(|v| bf16::from_f32(*v)).collect())
+            },
+        };
+        FactorMatrix {
+            rows: self.rows,
+            dims: self.dims,
+            storage,
+        }
+    }
+
+    /// Compares the values of two matrices of the same shape, typically a
+    /// compressed copy against the original.
+    pub fn precision_diff(&self, reference: &FactorMatrix) -> Result<PrecisionDiff> {
+        if (self.rows, self.dims) != (reference.rows, reference.dims) {
+            return Err(Error::InvalidData(format!(
+                "cannot compare a {}x{} matrix with a {}x{} one",
+                self.rows, self.dims, reference.rows, reference.dims
+            )));
+        }
+        Ok(PrecisionDiff::from_errors((0..self.rows).flat_map(|row| {
+            (0..self.dims).map(move |dim| self.get(row, dim) - reference.get(row, dim))
+        })))
+    }
+}
+
+impl MemoryFootprint for Precision {}
+
+impl MemoryFootprint for FactorMatrix {
+    fn heap_size(&self) -> usize {
+        match &self.storage {
+            Storage::F32(values) => values.heap_size(),
+            Storage::F16(values) => values.capacity() * 2,
+            Storage::BF16(values) => values.capacity() * 2,
+        }
+    }
+}
+
+/// Errors introduced by storing values with a lower precision.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PrecisionDiff {
+    pub max_abs_error: f32,
+    pub mean_abs_error: f32,
+    pub rmse: f32,
+}
+
+impl PrecisionDiff {
+    fn from_errors<I: Iterator<Item = f32>>(errors: I) -> Self {
+        let (mut max, mut sum, mut squares, mut count) = (0.0_f32, 0.0_f64, 0.0_f64, 0);
+        errors.for_each(|error| {
+            max = max.max(error.abs());
+            sum += error.abs() as f64;
+            squares += (error * error) as f64;
+            count += 1;
+        });
+        let count = count.max(1) as f64;
+        PrecisionDiff {
+            max_abs_error: max,
+            mean_abs_error: (sum / count) as f32,
+            rmse: (squares / count).sqrt() as f32,
+        }
+    }
+}
+
+/// # Score diff
+/// Measures how much the predicted scores, the dot products between every user and
+/// every item, move when the factors are stored with a lower precision.
+///
+/// ## Parameters:
+/// * `users`: The user factors, in `f32`.
+/// * `items`: The item factors, in `f32`.
+/// * `precision`: The precision to evaluate.
+///
+/// ## Returns:
+/// * The errors of the scores computed from the converted factors.
+pub fn score_diff(
+    users: &FactorMatrix,
+    items: &FactorMatrix,
+    precision: Precision,
+) -> Result<PrecisionDiff> {
+    if users.dims != items.dims {
+        return Err(Error::InvalidData(format!(
+            "users have {} factors but items have {}",
+            users.dims, items.dims
+        )));
+    }
+    let (converted_users, converted_items) =
+        (users.to_precision(precision), items.to_precision(precision));
+    let items_rows = items.to_rows();
+    let converted_items_rows = converted_items.to_rows();
+    Ok(PrecisionDiff::from_errors((0..users.rows).flat_map(
+        |user| {
+            let original = users.row(user);
+            let converted = converted_users.row(user);
+            items_rows
+                .iter()
+                .zip(converted_items_rows.iter())
+                .map(move |(item, converted_item)| {
+                    dot(&converted, converted_item) - dot(&original, item)
+                })
+                .collect::<Vec<f32>>()
+        },
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn factors() -> FactorMatrix {
+        FactorMatrix::from_rows(&[vec![0.1, -0.7, 1.3], vec![2.5, 0.01, -0.333]]).unwrap()
+    }
+
+    #[test]
+    fn test_from_rows() {
+        let factors = factors();
+        assert_eq!((factors.rows(), factors.dims()), (2, 3));
+        assert_eq!(factors.row(1), vec![2.5, 0.01, -0.333]);
+        assert!(FactorMatrix::from_rows(&[vec![1.0], vec![1.0, 2.0]]).is_err());
+    }
+
+    #[test]
+    fn test_deserialize_checks_shape() {
+        let json = serde_json::to_string(&factors()).unwrap();
+        let restored: FactorMatrix = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored, factors());
+        let truncated = r#"{"rows": 2, "dims": 3, "storage": {"f32": [0.1, -0.7]}}"#;
+        let error = serde_json::from_str::<FactorMatrix>(truncated).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("2 values for 2 rows of 3 factors"));
+        let overflowing = format!(
+            r#"{{"rows": {}, "dims": 2, "storage": {{"f32": []}}}}"#,
+            usize::MAX
+        );
+        assert!(serde_json::from_str::<FactorMatrix>(&overflowing).is_err());
+    }
+
+    #[test]
+    fn test_half_precision_halves_memory() {
+        let factors = FactorMatrix::new(100, 32);
+        let compressed = factors.to_precision(Precision::BF16);
+        assert_eq!(compressed.precision(), Precision::BF16);
+        assert_eq!(compressed.heap_size() * 2, factors.heap_size());
+    }
+
+    #[test]
+    fn test_precision_diff() {
+        let factors = factors();
+        let f16 = factors
+            .to_precision(Precision::F16)
+            .precision_diff(&factors)
+            .unwrap();
+        let bf16 = factors
+            .to_precision(Precision::BF16)
+            .precision_diff(&factors)
+            .unwrap();
+        assert!(f16.max_abs_error > 0.0 && f16.max_abs_error < 1e-3);
+        assert!(bf16.max_abs_error < 1e-2);
+        assert!(bf16.rmse > f16.rmse);
+        assert!(factors.precision_diff(&FactorMatrix::new(1, 3)).is_err());
+    }
+
+    #[test]
+    fn test_set_and_row_mut() {
+        let mut factors = factors().to_precision(Precision::F16);
+        assert!(factors.row_mut(0).is_none());
+        factors.set(0, 0, 0.5);
+        assert_eq!(factors.get(0, 0), 0.5);
+        let mut factors = factors.to_precision(Precision::F32);
+        factors.row_mut(1).unwrap()[2] = 4.0;
+        assert_eq!(factors.dot(1, &[0.0, 0.0, 0.5]), 2.0);
+    }
+
+    #[test]
+    fn test_score_diff() {
+        let users = factors();
+        let items = FactorMatrix::from_rows(&[vec![0.3, 0.3, 0.3]]).unwrap();
+        assert_eq!(
+            score_diff(&users, &items, Precision::F32).unwrap().rmse,
+            0.0
+        );
+        assert!(
+            score_diff(&users, &items, Precision::F16)
+                .unwrap()
+                .max_abs_error
+                < 1e-3
+        );
+    }
+}
@@ -0,0 +1,205 @@
+//! # Report formatting
+//! How the numbers of the metric and benchmark reports are written: their
+//! precision and the unit of the durations. The output does not depend on the
+//! locale of the machine, the decimal separator is always a dot and the digits are
+//! never grouped, so reports can be parsed back or compared as text.
+use serde::{Deserialize, Serialize};
+
+use crate::errors::{Error, Result};
+use crate::memory::MemoryFootprint;
+
+/// The unit durations are written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeUnit {
+    Seconds,
+    Milliseconds,
+    Microseconds,
+    Nanoseconds,
+}
+
+impl TimeUnit {
+    /// How many of the unit make a second.
+    pub fn per_second(&self) -> f64 {
+        match self {
+            TimeUnit::Seconds => 1.0,
+            TimeUnit::Milliseconds => 1e3,
+            TimeUnit::Microseconds => 1e6,
+            TimeUnit::Nanoseconds => 1e9,
+        }
+    }
+
+    /// The symbol of the unit, `us` rather than `µs` if only ASCII is allowed.
+    pub fn symbol(&self, ascii: bool) -> &'static str {
+        match self {
+            TimeUnit::Seconds => "s",
+            TimeUnit::Milliseconds => "ms",
+            TimeUnit::Microseconds if ascii => "us",
+            TimeUnit::Microseconds => "µs",
+            TimeUnit::Nanoseconds => "ns",
+        }
+    }
+
+    /// The largest unit in which the duration is at least 1, nanoseconds for
+    /// shorter durations.
+    pub fn fitting(secs: f64) -> Self {
+        [
+            TimeUnit::Seconds,
+            TimeUnit::Milliseconds,
+            TimeUnit::Microseconds,
+        ]
+        .into_iter()
+        .find(|unit| secs.abs() * unit.per_second() >= 1.0)
+        .unwrap_or(TimeUnit::Nanoseconds)
+    }
+}
+
+/// # Report format
+/// The precision and units of a report.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::formatting::{ReportFormat, TimeUnit};
+/// let format = ReportFormat::default().set_precision(3);
+/// assert_eq!(format.number(0.12345), "0.123");
+/// assert_eq!(format.delta(-0.0001), "0.000");
+/// assert_eq!(format.duration(0.0015), "1.500 ms");
+///
+/// let format = format.set_time_unit(None).set_ascii(true);
+/// assert_eq!(format.duration(0.0000042), "4.200 us");
+/// assert_eq!(format.round(2.71828), 2.718);
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReportFormat {
+    /// The number of digits after the decimal point.
+    pub precision: usize,
+    /// The unit of the durations, `None` to pick the [`TimeUnit::fitting`] one for
+    /// every duration.
+    pub time_unit: Option<TimeUnit>,
+    /// Only write ASCII characters.
+    pub ascii: bool,
+}
+
+impl Default for ReportFormat {
+    fn default() -> Self {
+        ReportFormat {
+            precision: 6,
+            time_unit: Some(TimeUnit::Milliseconds),
+            ascii: false,
+        }
+    }
+}
+
+impl ReportFormat {
+    pub fn set_precision(mut self, precision: usize) -> Self {
+        self.precision = precision;
+        self
+    }
+    pub fn set_time_unit(mut self, time_unit: Option<TimeUnit>) -> Self {
+        self.time_unit = time_unit;
+        self
+    }
+    pub fn set_ascii(mut self, ascii: bool) -> Self {
+        self.ascii = ascii;
+        self
+    }
+
+    /// Rejects precisions `f64` can not hold.
+    pub fn validate(&self) -> Result<()> {
+        if self.precision > 17 {
+            return Err(Error::InvalidConfig(format!(
+                "precision must be at most 17, got {}",
+                self.precision
+            )));
+        }
+        Ok(())
+    }
+
+    /// A number with the precision of the format. Values rounding to zero are
+    /// written without sign and the non-finite ones as `NaN`, `inf` and `-inf`.
+    pub fn number(&self, value: f64) -> String {
+        let written = format!("{:.*}", self.precision, value);
+        match written.strip_prefix('-') {
+            Some(unsigned) if unsigned.bytes().all(|b| b == b'0' || b == b'.') => {
+                unsigned.to_string()
+            },
+            _ => written,
+        }
+    }
+
+    /// A difference, with a `+` before the positive ones.
+    pub fn delta(&self, value: f64) -> String {
+        let written = self.number(value);
+        let zero = written.bytes().all(|b| b == b'0' || b == b'.');
+        match value > 0.0 && !zero {
+            true => format!("+{}", written),
+            false => written,
+        }
+    }
+
+    /// A duration given in seconds, followed by its unit.
+    pub fn duration(&self, secs: f64) -> String {
+        let unit = self.time_unit.unwrap_or_else(|| TimeUnit::fitting(secs));
+        format!(
+            "{} {}",
+            self.number(secs * unit.per_second()),
+            unit.symbol(self.ascii)
+        )
+    }
+
+    /// The value rounded to the precision, for the reports consumed as numbers.
+    pub fn round(&self, value: f64) -> f64 {
+        let factor = 10_f64.powi(self.precision.min(17) as i32);
+        match value.is_finite() {
+            true => (value * factor).round() / factor,
+            false => value,
+        }
+    }
+}
+
+impl MemoryFootprint for TimeUnit {}
+
+impl MemoryFootprint for ReportFormat {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_number() {
+        let format = ReportFormat::default();
+        assert_eq!(format.number(1.0 / 3.0), "0.333333");
+        assert_eq!(format.number(1234567.0), "1234567.000000");
+        assert_eq!(format.number(-0.0), "0.000000");
+        assert_eq!(format.number(-0.25), "-0.250000");
+        assert_eq!(format.number(f64::NAN), "NaN");
+        assert_eq!(format.number(f64::NEG_INFINITY), "-inf");
+        assert_eq!(format.set_precision(0).number(2.5), "2");
+        assert!(ReportFormat::default()
+            .set_precision(30)
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn test_delta() {
+        let format = ReportFormat::default().set_precision(2);
+        assert_eq!(format.delta(0.5), "+0.50");
+        assert_eq!(format.delta(-0.5), "-0.50");
+        assert_eq!(format.delta(0.001), "0.00");
+        assert_eq!(format.delta(f64::INFINITY), "+inf");
+    }
+
+    #[test]
+    fn test_duration() {
+        let format = ReportFormat::default().set_precision(1).set_time_unit(None);
+        assert_eq!(format.duration(2.0), "2.0 s");
+        assert_eq!(format.duration(0.25), "250.0 ms");
+        assert_eq!(format.duration(0.000_25), "250.0 µs");
+        assert_eq!(format.duration(0.000_000_25), "250.0 ns");
+        assert_eq!(format.duration(0.0), "0.0 ns");
+        let fixed = format.set_time_unit(Some(TimeUnit::Microseconds));
+        assert_eq!(fixed.duration(0.5), "500000.0 µs");
+    }
+}
@@ -0,0 +1,292 @@
+//! # Health checks
+//! Canary queries run against a model before it serves traffic, e.g. from the
+//! readiness probe of a server: the first queries warm the caches and the next
+//! ones check the recommendations are usable and fast enough.
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+
+use crate::algorithms::config::{ensure, AlgorithmConfig};
+use crate::errors::Result;
+use crate::memory::MemoryFootprint;
+use crate::recommender::Recommender;
+
+/// # Self-test configuration
+/// The canary queries of [`self_test`].
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::algorithms::config::AlgorithmConfig;
+/// use rec_rsys::health::SelfTestConfig;
+/// let config = SelfTestConfig::default()
+///     .set_canary_users(vec![1, 2, 3])
+///     .set_max_latency_us(5_000);
+/// assert!(config.validate().is_ok());
+/// assert!(SelfTestConfig::default().validate().is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SelfTestConfig {
+    /// Users queried, whose recommendations are expected to be non empty.
+    pub canary_users: Vec<u32>,
+    /// Length of the recommended lists.
+    pub num_items: usize,
+    /// Untimed queries run for every canary before the timed one.
+    pub warmup_rounds: usize,
+    /// Maximum latency of a query, in microseconds, unchecked when `None`.
+    pub max_latency_us: Option<u64>,
+}
+
+impl Default for SelfTestConfig {
+    fn default() -> Self {
+        SelfTestConfig {
+            canary_users: Vec::new(),
+            num_items: 10,
+            warmup_rounds: 1,
+            max_latency_us: None,
+        }
+    }
+}
+
+impl SelfTestConfig {
+    pub fn set_canary_users(mut self, canary_users: Vec<u32>) -> Self {
+        self.canary_users = canary_users;
+        self
+    }
+    pub fn set_num_items(mut self, num_items: usize) -> Self {
+        self.num_items = num_items;
+        self
+    }
+    pub fn set_warmup_rounds(mut self, warmup_rounds: usize) -> Self {
+        self.warmup_rounds = warmup_rounds;
+        self
+    }
+    pub fn set_max_latency_us(mut self, max_latency_us: u64) -> Self {
+        self.max_latency_us = Some(max_latency_us);
+        self
+    }
+}
+
+impl AlgorithmConfig for SelfTestConfig {
+    fn validate(&self) -> Result<()> {
+        ensure(!self.canary_users.is_empty(), "canary_users", "not empty")?;
+        ensure(self.num_items > 0, "num_items", "greater than 0")?;
+        ensure(
+            self.max_latency_us != Some(0),
+            "max_latency_us",
+            "greater than 0",
+        )
+    }
+}
+
+/// Something wrong with the recommendations of a canary.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Problem {
+    /// No item was recommended.
+    Empty,
+    /// A score is NaN or infinite.
+    NonFiniteScore,
+    /// The recommendations are not sorted from the best score.
+    Unsorted,
+    /// The query took longer than the maximum latency.
+    TooSlow,
+}
+
+/// The outcome of the timed query of a canary.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CanaryResult {
+    pub user_id: u32,
+    pub num_results: usize,
+    pub latency_us: u64,
+    pub problems: Vec<Problem>,
+}
+
+/// # Health report
+/// The outcome of a [`self_test`], serializable as the body of a readiness probe.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct HealthReport {
+    pub canaries: Vec<CanaryResult>,
+    /// Why the test could not run, e.g. the model could not be loaded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl HealthReport {
+    /// A report of a test that could not run.
+    pub fn failed(error: String) -> Self {
+        HealthReport {
+            canaries: Vec::new(),
+            error: Some(error),
+        }
+    }
+
+    /// Whether the test ran and no canary had a problem.
+    pub fn is_healthy(&self) -> bool {
+        self.error.is_none()
+            && !self.canaries.is_empty()
+            && self
+                .canaries
+                .iter()
+                .all(|canary| canary.problems.is_empty())
+    }
+
+    /// The slowest canary query, in microseconds.
+    pub fn max_latency_us(&self) -> u64 {
+        self.canaries
+            .iter()
+            .map(|canary| canary.latency_us)
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+/// # Self-test
+/// Warms a recommender up with the canary queries then checks their
+/// recommendations: not empty, finite scores, best first and within the latency.
+///
+/// ## Parameters:
+/// * `recommender`: The trained model.
+/// * `config`: The canaries and the thresholds.
+///
+/// ## Returns:
+/// * The report, failed if the config is invalid.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::algorithms::most_popular::MostPopular;
+/// use rec_rsys::dataset::{Dataset, Rating};
+/// use rec_rsys::health::{self_test, Problem, SelfTestConfig};
+/// use rec_rsys::recommender::Recommender;
+/// let mut model = MostPopular::default();
+/// model.fit(&Dataset::new(vec![Rating::new(1, 10, 1.0), Rating::new(2, 11, 1.0)])).unwrap();
+/// let report = self_test(&model, &SelfTestConfig::default().set_canary_users(vec![1, 3]));
+/// assert!(report.is_healthy());
+/// let report = self_test(&MostPopular::default(), &SelfTestConfig::default().set_canary_users(vec![1]));
+/// assert_eq!(report.canaries[0].problems, [Problem::Empty]);
+/// ```
+pub fn self_test<R: Recommender + ?Sized>(
+    recommender: &R,
+    config: &SelfTestConfig,
+) -> HealthReport {
+    if let Err(error) = config.validate() {
+        return HealthReport::failed(error.to_string());
+    }
+    for _ in 0..config.warmup_rounds {
+        for user_id in &config.canary_users {
+            recommender.recommend(*user_id, config.num_items);
+        }
+    }
+    let canaries = config
+        .canary_users
+        .iter()
+        .map(|&user_id| {
+            let start = Instant::now();
+            let recommendations = recommender.recommend(user_id, config.num_items);
+            let latency_us = start.elapsed().as_micros() as u64;
+            let mut problems = Vec::new();
+            if recommendations.is_empty() {
+                problems.push(Problem::Empty);
+            }
+            if recommendations.iter().any(|r| !r.score.is_finite()) {
+                problems.push(Problem::NonFiniteScore);
+            }
+            if recommendations.windows(2).any(|w| w[0].score < w[1].score) {
+                problems.push(Problem::Unsorted);
+            }
+            if config.max_latency_us.is_some_and(|max| latency_us > max) {
+                problems.push(Problem::TooSlow);
+            }
+            CanaryResult {
+                user_id,
+                num_results: recommendations.len(),
+                latency_us,
+                problems,
+            }
+        })
+        .collect();
+    HealthReport {
+        canaries,
+        error: None,
+    }
+}
+
+impl MemoryFootprint for SelfTestConfig {
+    fn heap_size(&self) -> usize {
+        self.canary_users.heap_size()
+    }
+}
+
+impl MemoryFootprint for Problem {}
+
+impl MemoryFootprint for CanaryResult {
+    fn heap_size(&self) -> usize {
+        self.problems.heap_size()
+    }
+}
+
+impl MemoryFootprint for HealthReport {
+    fn heap_size(&self) -> usize {
+        self.canaries.heap_size() + self.error.heap_size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::dataset::Dataset;
+    use crate::recommender::Recommendation;
+
+    /// Returns fixed recommendations for every user.
+    struct Fixed(Vec<Recommendation>);
+
+    impl Recommender for Fixed {
+        fn fit(&mut self, _dataset: &Dataset) -> Result<()> {
+            Ok(())
+        }
+
+        fn recommend(&self, _user_id: u32, num_items: usize) -> Vec<Recommendation> {
+            self.0.iter().take(num_items).copied().collect()
+        }
+    }
+
+    fn recommendation(item_id: u32, score: f32) -> Recommendation {
+        Recommendation { item_id, score }
+    }
+
+    #[test]
+    fn test_problems() {
+        let config = SelfTestConfig::default().set_canary_users(vec![1]);
+        let broken = Fixed(vec![recommendation(1, 0.5), recommendation(2, f32::NAN)]);
+        let report = self_test(&broken, &config);
+        assert_eq!(report.canaries[0].problems, [Problem::NonFiniteScore]);
+        let unsorted = Fixed(vec![recommendation(1, 0.5), recommendation(2, 0.9)]);
+        assert_eq!(
+            self_test(&unsorted, &config).canaries[0].problems,
+            [Problem::Unsorted]
+        );
+        assert!(!self_test(&unsorted, &config).is_healthy());
+    }
+
+    #[test]
+    fn test_report() {
+        let model = Fixed(vec![recommendation(1, 0.9)]);
+        let config = SelfTestConfig::default()
+            .set_canary_users(vec![1, 2])
+            .set_warmup_rounds(3)
+            .set_max_latency_us(10_000_000);
+        let report = self_test(&model, &config);
+        assert!(report.is_healthy());
+        assert_eq!(report.canaries.len(), 2);
+        let json: HashMap<String, serde_json::Value> =
+            serde_json::from_str(&serde_json::to_string(&report).unwrap()).unwrap();
+        assert!(!json.contains_key("error"));
+
+        let invalid = self_test(&model, &SelfTestConfig::default());
+        assert!(!invalid.is_healthy());
+        assert!(invalid.error.unwrap().contains("canary_users"));
+        assert!(!HealthReport::default().is_healthy());
+    }
+}
@@ -0,0 +1,286 @@
+//! # Stable identifiers
+//! A 64 bits hash of keys made of several parts, such as a user and a context or an
+//! item and its variant. Unlike [`std::hash::Hash`], whose algorithm can change
+//! between Rust releases, the value of [`stable_hash`] is part of the public API:
+//! it never changes across versions of the crate, so it can be stored, used to
+//! assign users to experiment buckets or to intern ids in persisted models.
+//! [`IdMap`] instead gives dense ids to string keys, in the order they come.
+use std::collections::HashMap;
+
+pub(crate) const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+/// The 64 bits FNV-1a hash of the bytes.
+pub(crate) fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(FNV_PRIME)
+    })
+}
+
+/// The finalizer of SplitMix64, which spreads every input bit over the output.
+pub(crate) fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// Accumulates the parts of a key, see [`StableHash`].
+#[derive(Debug, Clone)]
+pub struct StableHasher {
+    hash: u64,
+}
+
+impl Default for StableHasher {
+    fn default() -> Self {
+        StableHasher { hash: FNV_OFFSET }
+    }
+}
+
+impl StableHasher {
+    pub fn write(&mut self, bytes: &[u8]) {
+        self.hash = fnv1a(self.hash, bytes);
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.write(&value.to_le_bytes());
+    }
+
+    pub fn finish(&self) -> u64 {
+        mix(self.hash)
+    }
+}
+
+/// Types that can be part of a key of [`stable_hash`]. Integers are hashed as
+/// little-endian `u64`, so `3_u32` and `3_u64` give the same hash, and variable
+/// length values are prefixed with their length, so `("ab", "c")` and `("a", "bc")`
+/// do not.
+pub trait StableHash {
+    fn stable_hash(&self, hasher: &mut StableHasher);
+}
+
+macro_rules! impl_unsigned {
+    ($($t:ty),*) => {
+        $(impl StableHash for $t {
+            fn stable_hash(&self, hasher: &mut StableHasher) {
+                hasher.write_u64(*self as u64);
+            }
+        })*
+    };
+}
+
+macro_rules! impl_signed {
+    ($($t:ty),*) => {
+        $(impl StableHash for $t {
+            fn stable_hash(&self, hasher: &mut StableHasher) {
+                hasher.write_u64(*self as i64 as u64);
+            }
+        })*
+    };
+}
+
+impl_unsigned!(u8, u16, u32, u64, usize);
+impl_signed!(i8, i16, i32, i64, isize);
+
+impl StableHash for bool {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.write_u64(*self as u64);
+    }
+}
+
+impl StableHash for str {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.write_u64(self.len() as u64);
+        hasher.write(self.as_bytes());
+    }
+}
+
+impl StableHash for String {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        self.as_str().stable_hash(hasher);
+    }
+}
+
+impl<T: StableHash> StableHash for [T] {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.write_u64(self.len() as u64);
+        self.iter().for_each(|value| value.stable_hash(hasher));
+    }
+}
+
+impl<T: StableHash> StableHash for Vec<T> {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        self.as_slice().stable_hash(hasher);
+    }
+}
+
+impl<T: StableHash + ?Sized> StableHash for &T {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        (**self).stable_hash(hasher);
+    }
+}
+
+macro_rules! impl_tuple {
+    ($($name:ident),+) => {
+        impl<$($name: StableHash),+> StableHash for ($($name,)+) {
+            #[allow(non_snake_case)]
+            fn stable_hash(&self, hasher: &mut StableHasher) {
+                let ($($name,)+) = self;
+                $($name.stable_hash(hasher);)+
+            }
+        }
+    };
+}
+
+impl_tuple!(A);
+impl_tuple!(A, B);
+impl_tuple!(A, B, C);
+impl_tuple!(A, B, C, D);
+
+/// # Stable hash
+/// Hashes a key, possibly made of several parts, to 64 bits with FNV-1a followed
+/// by the SplitMix64 finalizer, so that the low bits are as good as the high ones.
+///
+/// ## Parameters:
+/// * `key`: The key, e.g. `(user_id, "mobile")`.
+///
+/// ## Returns:
+/// * A hash that is the same on every platform and in every version of the crate.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::ids::stable_hash;
+/// assert_eq!(stable_hash(&(42_u32, "mobile")), stable_hash(&(42_u64, "mobile".to_string())));
+/// assert_ne!(stable_hash(&("ab", "c")), stable_hash(&("a", "bc")));
+/// ```
+pub fn stable_hash<K: StableHash + ?Sized>(key: &K) -> u64 {
+    let mut hasher = StableHasher::default();
+    key.stable_hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Assigns a key to one of `num_buckets` buckets, e.g. the variant of an
+/// experiment a user sees. Salting the key with the name of the experiment keeps
+/// the buckets of different experiments independent.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::ids::bucket;
+/// let variant = bucket(&("homepage_test", 7_u32), 2);
+/// assert!(variant < 2);
+/// assert_eq!(variant, bucket(&("homepage_test", 7_u32), 2));
+/// ```
+pub fn bucket<K: StableHash + ?Sized>(key: &K, num_buckets: u64) -> u64 {
+    stable_hash(key) % num_buckets.max(1)
+}
+
+/// # Id map
+/// Interns external keys, such as the `"u123"` of an events log, as dense ids in
+/// the order they are first seen, and maps the ids back to their keys.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::ids::IdMap;
+/// let mut users = IdMap::default();
+/// assert_eq!(users.intern("u123"), 0);
+/// assert_eq!(users.intern("u7"), 1);
+/// assert_eq!(users.intern("u123"), 0);
+/// assert_eq!(users.id("u7"), Some(1));
+/// assert_eq!(users.key(0), Some("u123"));
+/// assert_eq!(users.len(), 2);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(from = "Vec<String>", into = "Vec<String>"))]
+pub struct IdMap {
+    /// The key of every id, by id.
+    keys: Vec<String>,
+    ids: HashMap<String, u32>,
+}
+
+impl IdMap {
+    /// The id of the key, a new one when the key was not seen yet.
+    pub fn intern(&mut self, key: &str) -> u32 {
+        if let Some(id) = self.ids.get(key) {
+            return *id;
+        }
+        let id = self.keys.len() as u32;
+        self.keys.push(key.to_string());
+        self.ids.insert(key.to_string(), id);
+        id
+    }
+
+    pub fn id(&self, key: &str) -> Option<u32> {
+        self.ids.get(key).copied()
+    }
+
+    pub fn key(&self, id: u32) -> Option<&str> {
+        self.keys.get(id as usize).map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+}
+
+impl From<Vec<String>> for IdMap {
+    fn from(keys: Vec<String>) -> Self {
+        let mut map = IdMap::default();
+        keys.iter().for_each(|key| {
+            map.intern(key);
+        });
+        map
+    }
+}
+
+impl From<IdMap> for Vec<String> {
+    fn from(map: IdMap) -> Self {
+        map.keys
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The values must never change, they may be stored by users of the crate.
+    #[test]
+    fn test_stable_values() {
+        assert_eq!(stable_hash(&0_u32), 0x813f_0174_a236_7c13);
+        assert_eq!(stable_hash("user"), 0xddcd_9370_8825_1d45);
+        assert_eq!(stable_hash(&(1_u32, 2_u32)), 0x35cc_bd7b_cc8d_bf8a);
+    }
+
+    #[test]
+    fn test_composite_keys() {
+        assert_eq!(stable_hash(&(1_u8, -1_i32)), stable_hash(&(1_u64, -1_i64)));
+        assert_ne!(stable_hash(&(1_u32, 2_u32)), stable_hash(&(2_u32, 1_u32)));
+        assert_ne!(stable_hash(&vec![1_u32, 2]), stable_hash(&(1_u32, 2_u32)));
+        assert_eq!(stable_hash(&vec!["a", "b"]), stable_hash(&["a", "b"][..]));
+    }
+
+    #[test]
+    fn test_buckets_are_balanced() {
+        let mut counts = [0; 4];
+        (0..40_000_u32).for_each(|user| counts[bucket(&("exp", user), 4) as usize] += 1);
+        assert!(counts.iter().all(|count| (9_500..10_500).contains(count)));
+        assert_eq!(bucket(&1_u32, 0), 0);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_id_map_round_trip() {
+        let mut map = IdMap::default();
+        ["b", "a", "b"].iter().for_each(|key| {
+            map.intern(key);
+        });
+        let json = serde_json::to_string(&map).unwrap();
+        assert_eq!(json, r#"["b","a"]"#);
+        let restored: IdMap = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored, map);
+        assert_eq!(restored.id("a"), Some(1));
+    }
+}
@@ -1,4 +1,3 @@
-#![allow(dead_code, unused)]
 #![deny(nonstandard_style, unused_variables, unused_mut, unused_parens)]
 //! [![github]](https://github.com/lucas-montes/rec_rsys)&ensp;[![crates-io]](https://crates.io/crates/rec_rsys)&ensp;[![docs-rs]](crate)
 //!
@@ -8,11 +7,132 @@
 //!
 //! <br>
 //! An awesome library (to learn currently) about recommender systems, maths and some other theory about ML.
+//!
+//! ## Stability
+//! The crate follows semantic versioning for everything that is public without the
+//! `unstable` feature: a minor release never removes or changes the signature of
+//! an exported item. Renamed items keep a `#[deprecated]` alias until the next
+//! major release.
+//!
+//! Unfinished work, such as the non-symmetric eigen solver in [`matrix`], is only exported
+//! with the `unstable` feature and can change or disappear in any release.
+//!
+//! ## Features
+//! No feature is enabled by default: the crate then only builds the maths, e.g. the
+//! similarities, the statistics, the matrices, [`algorithms::knn`] and the
+//! similarity tables of [`pairwise`].
+//! * `serde`: the datasets, the recommenders and everything built on them, which
+//!   persist their models and load their configs from JSON or TOML files. Without
+//!   it the remaining types derive no serde trait.
+//! * `ann`: approximate neighbor search with random hyperplane hashing, the
+//!   `pairwise::LshBlocking` of the similarity tables and the LSH search of the
+//!   `retrieval` index.
+//! * `parallel`: parallel computations with rayon, see [`parallelism`].
+//! * `async`: the `models::AsyncItemAdapter` trait.
+//! * `text`: tokenizing, TF-IDF and BM25 of item texts, see `text`.
+//! * `benchmarks`: the `benchmarks` helpers used by the criterion benches.
+//! * `yaml`: loading experiments from YAML files.
+//! * `roaring`: roaring bitmaps behind [`sets::IdSet`].
+//! * `fetch`: downloading standard datasets, see `datasets`.
+//! * `metrics`: serving metrics exported in the Prometheus format, see `metrics`.
+//! * `gzip`, `zstd`: reading compressed data files, see [`compression`].
+//! * `sql`: loading ratings and items from Postgres, MySQL or SQLite, see `sql`.
+//! * `redis`: a Redis backend for the serving state, see [`store`].
+//! * `arrow`: exporting factors and neighbor tables as Arrow IPC streams, see `arrow`.
+//! * `languages`: Snowball stemmers, stopword lists and Unicode word segmentation
+//!   for the `text` tokenizer.
+//! * `derive`: `#[derive(ItemAdapter)]` and `#[derive(OneHot)]`, see [`models`].
+//! * `full`: every stable feature above.
+//! * `unstable`: unfinished items, without any guarantee.
 pub mod accuracy;
 pub mod algorithms;
+pub mod alignment;
+#[cfg(feature = "serde")]
+pub mod anonymize;
+#[cfg(feature = "arrow")]
+pub mod arrow;
+#[cfg(feature = "benchmarks")]
 pub mod benchmarks;
+pub mod bloom;
+#[cfg(feature = "serde")]
+pub mod calibration;
+#[cfg(feature = "serde")]
+pub mod catalog;
+#[cfg(feature = "serde")]
+pub mod compression;
+#[cfg(feature = "serde")]
+pub mod config;
+#[cfg(feature = "serde")]
+pub mod dataset;
+#[cfg(feature = "fetch")]
+pub mod datasets;
+pub mod diagnostics;
+#[cfg(feature = "serde")]
+pub mod envelope;
+pub mod errors;
+pub mod evaluation;
+#[cfg(feature = "serde")]
+pub mod exclusions;
+#[cfg(feature = "serde")]
+pub mod exploration;
+#[cfg(feature = "serde")]
+pub mod factors;
+#[cfg(feature = "serde")]
+pub mod formatting;
+#[cfg(feature = "serde")]
+pub mod health;
+pub mod ids;
 pub mod matrix;
+pub mod memory;
+#[cfg(feature = "metrics")]
+pub mod metrics;
 pub mod models;
+#[cfg(feature = "serde")]
+pub mod monitoring;
+#[cfg(feature = "serde")]
+pub mod onnx;
+pub mod pairwise;
+pub mod parallelism;
+#[cfg(feature = "serde")]
+pub mod partitions;
+#[cfg(feature = "serde")]
+pub mod pipeline;
+#[cfg(feature = "serde")]
+pub mod popularity;
+#[cfg(feature = "serde")]
+pub mod privacy;
+#[cfg(feature = "serde")]
+pub mod profiles;
+#[cfg(feature = "serde")]
+pub mod propensity;
+#[cfg(feature = "serde")]
+pub mod quickstart;
+#[cfg(feature = "serde")]
+pub mod recommender;
+#[cfg(feature = "serde")]
+pub mod rerank;
+#[cfg(feature = "serde")]
+pub mod retrain;
+#[cfg(feature = "serde")]
+pub mod retrieval;
+#[cfg(feature = "serde")]
+pub mod rfm;
+#[cfg(feature = "serde")]
+pub mod sampling;
+pub mod scratch;
+pub mod sets;
 pub mod similarity;
+#[cfg(feature = "serde")]
+pub mod simulation;
+#[cfg(feature = "sql")]
+pub mod sql;
 pub mod statistics;
+#[cfg(feature = "serde")]
+pub mod store;
+#[cfg(feature = "serde")]
+pub mod tenant;
+#[cfg(feature = "text")]
+pub mod text;
+#[cfg(feature = "serde")]
+pub mod trace;
 pub mod utils;
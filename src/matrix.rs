@@ -1,8 +1,7 @@
 //! A collection of funcitons to apply to matrices
-use std::collections::HashMap;
-
+use crate::parallelism::default_parallelism;
+use crate::parallelism::prelude::*;
 use crate::statistics::mean as vec_mean;
-use rayon::prelude::*;
 
 /// Transpose a matrix
 pub fn transpose<T: Clone + Send + Sync>(matrix: &[Vec<T>]) -> Vec<Vec<T>> {
@@ -13,10 +12,12 @@ pub fn transpose<T: Clone + Send + Sync>(matrix: &[Vec<T>]) -> Vec<Vec<T>> {
             .map(|j: usize| matrix.iter().map(|row: &Vec<T>| row[j].clone()).collect())
             .collect();
     }
-    (0..cols)
-        .into_par_iter()
-        .map(|j: usize| matrix.iter().map(|row: &Vec<T>| row[j].clone()).collect())
-        .collect()
+    default_parallelism().install(|| {
+        (0..cols)
+            .into_par_iter()
+            .map(|j: usize| matrix.iter().map(|row: &Vec<T>| row[j].clone()).collect())
+            .collect()
+    })
 }
 
 /// Calculate the mean of a matrix using f32 values
@@ -143,6 +144,7 @@ pub fn subtract_vector_from_matrix(matrix: &[Vec<f32>], vector: &[f32]) -> Vec<V
 /// let matrix = X;
 /// (matrix)
 ///
+#[cfg(feature = "unstable")]
 pub fn get_eigenvalues(matrix: &[Vec<f32>]) -> Vec<i32> {
     let _indentity_matrix: Vec<Vec<f32>> = vec![vec![0.0; matrix.len()]; matrix.len()];
     for (index, row) in matrix.iter().enumerate() {
@@ -189,12 +191,12 @@ pub fn get_eigenvalues(matrix: &[Vec<f32>]) -> Vec<i32> {
 /// ### Where:
 /// * `x`: Is the eigenvector
 /// * `$A$`: Is the matrix
+#[cfg(feature = "unstable")]
 pub fn get_eigenvectors(_matrix: &[Vec<f32>]) -> Vec<Vec<f32>> {
     todo!()
 }
 
 pub fn get_determinant(matrix: &[Vec<f64>]) -> f64 {
-    println!("matrix {:?}", matrix);
     match matrix.len() {
         1 => matrix[0][0],
         2 => matrix[0][0] * matrix[1][1] - matrix[0][1] * matrix[1][0],
@@ -210,7 +212,7 @@ fn laplace_extension(matrix: &[Vec<f64>]) -> f64 {
     let mut det = 0.0;
     for col in 1..matrix.len() {
         let submatrix = create_submatrix(matrix, col);
-        let sign = (-1.0 as f64).powi(col as i32);
+        let sign = (-1.0_f64).powi(col as i32);
         let submatrix_det = get_determinant(&submatrix);
         det += matrix[0][col] * submatrix_det * sign;
     }
@@ -218,17 +220,435 @@ fn laplace_extension(matrix: &[Vec<f64>]) -> f64 {
 }
 
 fn create_submatrix(matrix: &[Vec<f64>], j: usize) -> Vec<Vec<f64>> {
-    let mut submatrix: Vec<Vec<f64>> = Vec::new();
-    for row in 1..matrix.len() {
-        let submatrix_row = matrix[row][j..matrix.len()].to_vec();
-        submatrix.push(submatrix_row);
+    matrix
+        .iter()
+        .skip(1)
+        .map(|row| row[j..matrix.len()].to_vec())
+        .collect()
+}
+
+/// # Matrix product
+/// Multiplies two matrices.
+///
+/// ## Parameters:
+/// * `a`: A `n x m` matrix.
+/// * `b`: A `m x p` matrix.
+///
+/// ## Returns:
+/// * The `n x p` product `a b`.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::matrix::matmul;
+/// let product = matmul(&[vec![1.0, 2.0], vec![3.0, 4.0]], &[vec![5.0], vec![6.0]]);
+/// assert_eq!(product, vec![vec![17.0], vec![39.0]]);
+/// ```
+pub fn matmul(a: &[Vec<f32>], b: &[Vec<f32>]) -> Vec<Vec<f32>> {
+    let columns = b.first().map_or(0, Vec::len);
+    a.iter()
+        .map(|row| {
+            row.iter()
+                .zip(b)
+                .fold(vec![0.0; columns], |mut product, (&x, b_row)| {
+                    product
+                        .iter_mut()
+                        .zip(b_row)
+                        .for_each(|(p, &y)| *p += x * y);
+                    product
+                })
+        })
+        .collect()
+}
+
+/// # Non-negative least squares
+/// Solves `min ||a x - b||` subject to `x >= 0` with the active set method of
+/// Lawson and Hanson.
+///
+/// ## Parameters:
+/// * `a`: The `n x m` matrix of the system.
+/// * `b`: The `n` targets.
+///
+/// ## Returns:
+/// * The `m` non-negative coefficients.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::matrix::nnls;
+/// let a = vec![vec![1.0, 0.0], vec![0.0, 1.0], vec![1.0, 1.0]];
+/// let x = nnls(&a, &[2.0, -1.0, 1.0]);
+/// assert!((x[0] - 1.5).abs() < 1e-5 && x[1] == 0.0);
+/// ```
+///
+#[doc = include_str!("../docs/matrix/nnls.md")]
+pub fn nnls(a: &[Vec<f32>], b: &[f32]) -> Vec<f32> {
+    let m = a.first().map_or(0, Vec::len);
+    let column = |j: usize| a.iter().map(move |row| row[j] as f64);
+    let gram: Vec<Vec<f64>> = (0..m)
+        .map(|i| {
+            (0..m)
+                .map(|j| column(i).zip(column(j)).map(|(x, y)| x * y).sum())
+                .collect()
+        })
+        .collect();
+    let targets: Vec<f64> = (0..m)
+        .map(|j| column(j).zip(b).map(|(x, &y)| x * y as f64).sum())
+        .collect();
+    let gradient = |x: &[f64]| -> Vec<f64> {
+        (0..m)
+            .map(|i| targets[i] - (0..m).map(|j| gram[i][j] * x[j]).sum::<f64>())
+            .collect()
+    };
+    let scale = gram
+        .iter()
+        .flatten()
+        .fold(1.0_f64, |max, g| max.max(g.abs()));
+    let tolerance = 1e-10 * scale * m.max(1) as f64;
+
+    let mut x = vec![0.0; m];
+    let mut passive = vec![false; m];
+    for _ in 0..3 * m.max(1) {
+        let w = gradient(&x);
+        let next = (0..m)
+            .filter(|&j| !passive[j] && w[j] > tolerance)
+            .max_by(|&i, &j| w[i].total_cmp(&w[j]));
+        let Some(next) = next else { break };
+        passive[next] = true;
+        loop {
+            let s = passive_solution(&gram, &targets, &passive);
+            if (0..m).filter(|&j| passive[j]).all(|j| s[j] > 0.0) {
+                x = s;
+                break;
+            }
+            let alpha = (0..m)
+                .filter(|&j| passive[j] && s[j] <= 0.0)
+                .map(|j| x[j] / (x[j] - s[j]))
+                .fold(f64::INFINITY, f64::min);
+            for j in 0..m {
+                x[j] += alpha * (s[j] - x[j]);
+                if passive[j] && x[j] <= tolerance {
+                    passive[j] = false;
+                    x[j] = 0.0;
+                }
+            }
+        }
+    }
+    x.into_iter().map(|value| value as f32).collect()
+}
+
+/// Unconstrained least squares restricted to the passive variables, the others
+/// being 0.
+fn passive_solution(gram: &[Vec<f64>], targets: &[f64], passive: &[bool]) -> Vec<f64> {
+    let indices: Vec<usize> = (0..passive.len()).filter(|&j| passive[j]).collect();
+    let system: Vec<Vec<f64>> = indices
+        .iter()
+        .map(|&i| indices.iter().map(|&j| gram[i][j]).collect())
+        .collect();
+    let rhs: Vec<f64> = indices.iter().map(|&i| targets[i]).collect();
+    let mut solution = vec![0.0; passive.len()];
+    for (&j, value) in indices.iter().zip(solve(system, rhs)) {
+        solution[j] = value;
+    }
+    solution
+}
+
+/// Solves the square system `a x = b` with Gaussian elimination and partial
+/// pivoting. Singular directions are given a 0 coefficient.
+pub(crate) fn solve(mut a: Vec<Vec<f64>>, mut b: Vec<f64>) -> Vec<f64> {
+    let n = b.len();
+    for col in 0..n {
+        let pivot = (col..n)
+            .max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))
+            .unwrap_or(col);
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+        if a[col][col].abs() < 1e-12 {
+            continue;
+        }
+        let (upper, lower) = a.split_at_mut(col + 1);
+        let pivot_row = &upper[col];
+        for (offset, row) in lower.iter_mut().enumerate() {
+            let factor = row[col] / pivot_row[col];
+            row.iter_mut()
+                .zip(pivot_row)
+                .skip(col)
+                .for_each(|(value, pivot)| *value -= factor * pivot);
+            b[col + 1 + offset] -= factor * b[col];
+        }
+    }
+    let mut x = vec![0.0; n];
+    for row in (0..n).rev() {
+        if a[row][row].abs() < 1e-12 {
+            continue;
+        }
+        let rest: f64 = (row + 1..n).map(|k| a[row][k] * x[k]).sum();
+        x[row] = (b[row] - rest) / a[row][row];
+    }
+    x
+}
+
+/// # Symmetric eigendecomposition
+/// Calculates the eigenvalues and eigenvectors of a symmetric matrix, such as a
+/// covariance or a Gram matrix, with the cyclic Jacobi method.
+///
+/// ## Parameters:
+/// * `matrix`: A square symmetric matrix.
+///
+/// ## Returns:
+/// * A tuple `(eigenvalues, eigenvectors)` sorted by decreasing eigenvalue, the
+///   eigenvectors being the rows of the second element and of unit norm.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::matrix::symmetric_eigen;
+/// let (values, vectors) = symmetric_eigen(&[vec![2.0, 1.0], vec![1.0, 2.0]]);
+/// assert!((values[0] - 3.0).abs() < 1e-6 && (values[1] - 1.0).abs() < 1e-6);
+/// assert!((vectors[0][0] - vectors[0][1]).abs() < 1e-6);
+/// ```
+///
+#[doc = include_str!("../docs/matrix/symmetric_eigen.md")]
+pub fn symmetric_eigen(matrix: &[Vec<f32>]) -> (Vec<f32>, Vec<Vec<f32>>) {
+    let n = matrix.len();
+    let mut a: Vec<Vec<f64>> = matrix
+        .iter()
+        .map(|row| row.iter().map(|&value| value as f64).collect())
+        .collect();
+    let mut v: Vec<Vec<f64>> = (0..n)
+        .map(|i| (0..n).map(|j| if i == j { 1.0 } else { 0.0 }).collect())
+        .collect();
+    let norm: f64 = a.iter().flatten().map(|value| value * value).sum();
+    for _ in 0..100 {
+        let off_diagonal: f64 = (0..n)
+            .flat_map(|i| (0..n).filter(move |&j| j != i).map(move |j| (i, j)))
+            .map(|(i, j)| a[i][j] * a[i][j])
+            .sum();
+        if off_diagonal <= f64::EPSILON * f64::EPSILON * norm {
+            break;
+        }
+        for p in 0..n {
+            for q in p + 1..n {
+                if a[p][q] == 0.0 {
+                    continue;
+                }
+                let theta = (a[q][q] - a[p][p]) / (2.0 * a[p][q]);
+                let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+                let c = 1.0 / (t * t + 1.0).sqrt();
+                let s = t * c;
+                for row in a.iter_mut() {
+                    let (kp, kq) = (row[p], row[q]);
+                    row[p] = c * kp - s * kq;
+                    row[q] = s * kp + c * kq;
+                }
+                for k in 0..n {
+                    let (pk, qk) = (a[p][k], a[q][k]);
+                    a[p][k] = c * pk - s * qk;
+                    a[q][k] = s * pk + c * qk;
+                    let (kp, kq) = (v[k][p], v[k][q]);
+                    v[k][p] = c * kp - s * kq;
+                    v[k][q] = s * kp + c * kq;
+                }
+            }
+        }
+    }
+    let mut order: Vec<usize> = (0..n).collect();
+    order.sort_by(|&i, &j| a[j][j].total_cmp(&a[i][i]));
+    let values = order.iter().map(|&i| a[i][i] as f32).collect();
+    let vectors = order
+        .iter()
+        .map(|&i| v.iter().map(|row| row[i] as f32).collect())
+        .collect();
+    (values, vectors)
+}
+
+/// # Singular values
+/// Calculates the singular values of a matrix with one-sided Jacobi rotations in
+/// double precision, which find the small singular values as accurately as the
+/// large ones. [`svd`](crate::algorithms::svd::svd) also returns the singular
+/// vectors.
+///
+/// ## Parameters:
+/// * `matrix`: The matrix, of any shape.
+///
+/// ## Returns:
+/// * The `min(rows, columns)` singular values, in decreasing order.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::matrix::singular_values;
+/// let values = singular_values(&[vec![3.0, 0.0], vec![0.0, -2.0], vec![0.0, 0.0]]);
+/// assert!((values[0] - 3.0).abs() < 1e-6 && (values[1] - 2.0).abs() < 1e-6);
+/// ```
+pub fn singular_values(matrix: &[Vec<f32>]) -> Vec<f32> {
+    if matrix.is_empty() || matrix[0].is_empty() {
+        return Vec::new();
+    }
+    let (_, mut values, _) = if matrix.len() < matrix[0].len() {
+        one_sided_jacobi(&transpose(matrix))
+    } else {
+        one_sided_jacobi(matrix)
+    };
+    values.sort_by(|a, b| b.total_cmp(a));
+    values.into_iter().map(|value| value as f32).collect()
+}
+
+/// Sweeps of rotations after which the decomposition is returned even if the
+/// columns are not orthogonal to the working precision yet.
+const MAX_SWEEPS: usize = 60;
+
+/// Rotates pairs of columns of a tall matrix, with at least as many rows as
+/// columns, until they are orthogonal. Returns
+/// the columns of U, the singular values and the columns of V, unsorted.
+pub(crate) fn one_sided_jacobi(
+    matrix: &[Vec<f32>],
+) -> (Vec<Vec<f64>>, Vec<f64>, Vec<Vec<f64>>) {
+    let (m, n) = (matrix.len(), matrix[0].len());
+    let mut u: Vec<Vec<f64>> = (0..n)
+        .map(|j| matrix.iter().map(|row| row[j] as f64).collect())
+        .collect();
+    let mut v: Vec<Vec<f64>> = (0..n)
+        .map(|j| (0..n).map(|i| if i == j { 1.0 } else { 0.0 }).collect())
+        .collect();
+    let dot = |a: &[f64], b: &[f64]| a.iter().zip(b).map(|(x, y)| x * y).sum::<f64>();
+    for _ in 0..MAX_SWEEPS {
+        let mut rotated = false;
+        for p in 0..n {
+            for q in p + 1..n {
+                let alpha = dot(&u[p], &u[p]);
+                let beta = dot(&u[q], &u[q]);
+                let gamma = dot(&u[p], &u[q]);
+                if gamma.abs() <= f64::EPSILON * (alpha * beta).sqrt() {
+                    continue;
+                }
+                rotated = true;
+                let zeta = (beta - alpha) / (2.0 * gamma);
+                let t = zeta.signum() / (zeta.abs() + (zeta * zeta + 1.0).sqrt());
+                let c = 1.0 / (t * t + 1.0).sqrt();
+                let s = c * t;
+                for columns in [&mut u, &mut v] {
+                    let (left, right) = columns.split_at_mut(q);
+                    for (x, y) in left[p].iter_mut().zip(right[0].iter_mut()) {
+                        let (xp, xq) = (*x, *y);
+                        *x = c * xp - s * xq;
+                        *y = s * xp + c * xq;
+                    }
+                }
+            }
+        }
+        if !rotated {
+            break;
+        }
+    }
+    let singular_values: Vec<f64> =
+        u.iter().map(|column| dot(column, column).sqrt()).collect();
+    let largest = singular_values.iter().copied().fold(0.0, f64::max);
+    let tolerance = largest * f64::EPSILON * m.max(n) as f64;
+    for (column, &sigma) in u.iter_mut().zip(&singular_values) {
+        if sigma > tolerance {
+            column.iter_mut().for_each(|value| *value /= sigma);
+        }
     }
-    submatrix
+    complete_basis(&mut u, &singular_values, tolerance);
+    let singular_values = singular_values
+        .into_iter()
+        .map(|sigma| if sigma > tolerance { sigma } else { 0.0 })
+        .collect();
+    (u, singular_values, v)
+}
+
+/// Replaces the columns of the null singular values, which the rotations leave
+/// about zero, with unit vectors orthogonal to the other columns.
+fn complete_basis(u: &mut [Vec<f64>], singular_values: &[f64], tolerance: f64) {
+    let m = u.first().map_or(0, Vec::len);
+    let mut candidates = 0..m;
+    for k in 0..u.len() {
+        if singular_values[k] > tolerance {
+            continue;
+        }
+        for i in candidates.by_ref() {
+            let mut column: Vec<f64> =
+                (0..m).map(|r| if r == i { 1.0 } else { 0.0 }).collect();
+            // Orthogonalized twice, a single Gram-Schmidt pass loses orthogonality.
+            for _ in 0..2 {
+                for (j, other) in u.iter().enumerate() {
+                    let basis = j < k || singular_values[j] > tolerance;
+                    if j == k || !basis {
+                        continue;
+                    }
+                    let projection: f64 =
+                        column.iter().zip(other).map(|(a, b)| a * b).sum();
+                    column
+                        .iter_mut()
+                        .zip(other)
+                        .for_each(|(a, b)| *a -= projection * b);
+                }
+            }
+            let norm = column.iter().map(|a| a * a).sum::<f64>().sqrt();
+            if norm > 1e-6 {
+                u[k] = column.into_iter().map(|a| a / norm).collect();
+                break;
+            }
+        }
+    }
+}
+
+/// # Rank for a fraction
+/// Finds how many of the leading values are needed to reach a fraction of their
+/// total, e.g. the number of principal components that explain 95% of the variance.
+///
+/// ## Parameters:
+/// * `values`: Non-negative values in decreasing order, such as eigenvalues.
+/// * `fraction`: The fraction of the total to retain, between 0 and 1.
+///
+/// ## Returns:
+/// * The number of values to keep, at least 1 unless `values` is empty.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::matrix::rank_for_fraction;
+/// assert_eq!(rank_for_fraction(&[6.0, 3.0, 1.0], 0.9), 2);
+/// assert_eq!(rank_for_fraction(&[6.0, 3.0, 1.0], 0.95), 3);
+/// ```
+pub fn rank_for_fraction(values: &[f32], fraction: f32) -> usize {
+    let total: f32 = values.iter().sum();
+    let mut retained = 0.0;
+    for (rank, value) in values.iter().enumerate() {
+        retained += value;
+        if retained >= fraction * total * (1.0 - f32::EPSILON) {
+            return rank + 1;
+        }
+    }
+    values.len()
+}
+
+/// # Energy rank
+/// Finds the rank of the truncated SVD that keeps a fraction of the energy of a
+/// matrix, the sum of its squared singular values.
+///
+/// ## Parameters:
+/// * `matrix`: The matrix, of any shape.
+/// * `energy`: The fraction of the energy to retain, between 0 and 1.
+///
+/// ## Returns:
+/// * The number of singular values to keep.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::matrix::energy_rank;
+/// let ratings = vec![vec![5.0, 5.0, 0.0], vec![4.0, 4.0, 0.0], vec![0.0, 0.1, 3.0]];
+/// assert_eq!(energy_rank(&ratings, 0.9), 1);
+/// assert_eq!(energy_rank(&ratings, 0.99), 2);
+/// ```
+pub fn energy_rank(matrix: &[Vec<f32>], energy: f32) -> usize {
+    let energies: Vec<f32> = singular_values(matrix)
+        .iter()
+        .map(|value| value * value)
+        .collect();
+    rank_for_fraction(&energies, energy)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::utils::dot;
 
     #[test]
     fn test_get_determinant_1x1() {
@@ -288,6 +708,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "unstable")]
     fn test_get_eigenvalues() {
         assert_eq!(get_eigenvalues(&[vec![2., -1.], vec![4., 3.]]), vec![5, -1]);
     }
@@ -364,4 +785,62 @@ mod tests {
             vec![2.0, 5.0, 8.0],
         );
     }
+
+    #[test]
+    fn test_symmetric_eigen() {
+        let matrix = vec![
+            vec![4.0, 1.0, 2.0],
+            vec![1.0, 3.0, 0.0],
+            vec![2.0, 0.0, 5.0],
+        ];
+        let (values, vectors) = symmetric_eigen(&matrix);
+        assert!(values.windows(2).all(|w| w[0] >= w[1]));
+        assert!((values.iter().sum::<f32>() - 12.0).abs() < 1e-4);
+        for (value, vector) in values.iter().zip(&vectors) {
+            assert!((dot(vector, vector) - 1.0).abs() < 1e-5);
+            for (row, x) in matrix.iter().zip(vector) {
+                assert!((dot(row, vector) - value * x).abs() < 1e-4);
+            }
+        }
+        assert!(dot(&vectors[0], &vectors[1]).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_singular_values() {
+        let values = singular_values(&[vec![1.0, 2.0], vec![2.0, 4.0], vec![3.0, 6.0]]);
+        assert!((values[0] - 70.0_f32.sqrt()).abs() < 1e-4);
+        assert!(values[1].abs() < 1e-3);
+        assert!(singular_values(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_rank_for_fraction() {
+        assert_eq!(rank_for_fraction(&[5.0, 3.0, 2.0], 0.5), 1);
+        assert_eq!(rank_for_fraction(&[5.0, 3.0, 2.0], 0.8), 2);
+        assert_eq!(rank_for_fraction(&[5.0, 3.0, 2.0], 1.0), 3);
+        assert_eq!(rank_for_fraction(&[], 0.9), 0);
+    }
+
+    #[test]
+    fn test_matmul() {
+        let a = vec![vec![1.0, 2.0, 3.0], vec![4.0, 5.0, 6.0]];
+        assert_eq!(
+            matmul(&a, &transpose(&a)),
+            vec![vec![14.0, 32.0], vec![32.0, 77.0]]
+        );
+        assert!(matmul(&[], &a).is_empty());
+    }
+
+    #[test]
+    fn test_nnls() {
+        // Unconstrained solution is already non-negative.
+        let a = vec![vec![1.0, 1.0], vec![1.0, 2.0], vec![1.0, 3.0]];
+        let x = nnls(&a, &[6.0, 8.0, 10.0]);
+        assert!((x[0] - 4.0).abs() < 1e-4 && (x[1] - 2.0).abs() < 1e-4);
+        // The negative slope is clipped and the intercept refitted.
+        let x = nnls(&a, &[10.0, 8.0, 6.0]);
+        assert_eq!(x[1], 0.0);
+        assert!((x[0] - 8.0).abs() < 1e-4);
+        assert_eq!(nnls(&a, &[-1.0, -2.0, -3.0]), vec![0.0, 0.0]);
+    }
 }
@@ -0,0 +1,136 @@
+//! # Memory usage estimation
+//! Estimates how many bytes models, item stores and indexes hold, so the capacity
+//! needed to serve them can be planned programmatically.
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::mem::size_of;
+
+/// # Memory footprint
+/// Estimated number of bytes used by a value. Collections count their allocated
+/// capacity, the bookkeeping of the allocator and of the hash tables is
+/// approximated, so the result is an order of magnitude rather than an exact count.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::memory::MemoryFootprint;
+/// use rec_rsys::models::Item;
+/// let item = Item::new(1, vec![0.0; 100], None);
+/// assert!(item.memory_footprint() >= 400);
+/// ```
+pub trait MemoryFootprint {
+    /// Bytes allocated on the heap and owned by the value.
+    fn heap_size(&self) -> usize {
+        0
+    }
+
+    /// Bytes of the value itself plus the ones it owns on the heap.
+    fn memory_footprint(&self) -> usize
+    where
+        Self: Sized,
+    {
+        size_of::<Self>() + self.heap_size()
+    }
+}
+
+macro_rules! impl_inline {
+    ($($t:ty),*) => {
+        $(impl MemoryFootprint for $t {})*
+    };
+}
+
+impl_inline!(
+    u8,
+    u16,
+    u32,
+    u64,
+    usize,
+    i8,
+    i16,
+    i32,
+    i64,
+    isize,
+    f32,
+    f64,
+    bool,
+    char,
+    ()
+);
+
+impl MemoryFootprint for String {
+    fn heap_size(&self) -> usize {
+        self.capacity()
+    }
+}
+
+impl<T: MemoryFootprint> MemoryFootprint for Vec<T> {
+    fn heap_size(&self) -> usize {
+        self.capacity() * size_of::<T>() + self.iter().map(T::heap_size).sum::<usize>()
+    }
+}
+
+impl<T: MemoryFootprint> MemoryFootprint for Option<T> {
+    fn heap_size(&self) -> usize {
+        self.as_ref().map_or(0, T::heap_size)
+    }
+}
+
+impl<A: MemoryFootprint, B: MemoryFootprint> MemoryFootprint for (A, B) {
+    fn heap_size(&self) -> usize {
+        self.0.heap_size() + self.1.heap_size()
+    }
+}
+
+/// Hash tables allocate one control byte per bucket next to the entries.
+impl<K: MemoryFootprint, V: MemoryFootprint, S> MemoryFootprint for HashMap<K, V, S> {
+    fn heap_size(&self) -> usize {
+        self.capacity() * (size_of::<(K, V)>() + 1)
+            + self
+                .iter()
+                .map(|(k, v)| k.heap_size() + v.heap_size())
+                .sum::<usize>()
+    }
+}
+
+impl<T: MemoryFootprint, S> MemoryFootprint for HashSet<T, S> {
+    fn heap_size(&self) -> usize {
+        self.capacity() * (size_of::<T>() + 1)
+            + self.iter().map(T::heap_size).sum::<usize>()
+    }
+}
+
+/// B-tree nodes are about two thirds full on average.
+impl<K: MemoryFootprint, V: MemoryFootprint> MemoryFootprint for BTreeMap<K, V> {
+    fn heap_size(&self) -> usize {
+        self.len() * size_of::<(K, V)>() * 3 / 2
+            + self
+                .iter()
+                .map(|(k, v)| k.heap_size() + v.heap_size())
+                .sum::<usize>()
+    }
+}
+
+impl<T: MemoryFootprint> MemoryFootprint for BTreeSet<T> {
+    fn heap_size(&self) -> usize {
+        self.len() * size_of::<T>() * 3 / 2 + self.iter().map(T::heap_size).sum::<usize>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vec() {
+        let values: Vec<f32> = Vec::with_capacity(10);
+        assert_eq!(values.heap_size(), 40);
+        let nested = vec![vec![0_u8; 3], vec![0_u8; 5]];
+        assert_eq!(nested.heap_size(), 2 * size_of::<Vec<u8>>() + 8);
+    }
+
+    #[test]
+    fn test_strings_and_maps() {
+        assert_eq!(String::from("abc").heap_size(), 3);
+        let map: HashMap<u32, String> = HashMap::from([(1, "ab".to_string())]);
+        assert!(map.heap_size() >= size_of::<(u32, String)>() + 2);
+        assert_eq!(BTreeSet::<u32>::new().heap_size(), 0);
+    }
+}
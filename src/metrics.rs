@@ -0,0 +1,272 @@
+//! # Serving metrics
+//! Counters a serving wrapper updates while answering requests: queries and their
+//! latency per model, cache hits and the age of the models. The registry is shared
+//! between threads and exported in the Prometheus text format, so it can be
+//! returned as is by a `/metrics` endpoint.
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::statistics::Histogram;
+
+/// Upper bounds of the latency buckets, in seconds.
+pub const DEFAULT_LATENCY_BUCKETS: [f32; 12] = [
+    0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5,
+];
+
+#[derive(Debug, Default)]
+struct State {
+    latencies: BTreeMap<String, Histogram>,
+    cache_hits: u64,
+    cache_misses: u64,
+    trained_at: BTreeMap<String, SystemTime>,
+}
+
+/// # Metrics registry
+/// Thread-safe metrics of the models being served, labelled by model name.
+///
+/// ## Examples:
+/// ```
+/// use std::time::Duration;
+/// use rec_rsys::metrics::MetricsRegistry;
+/// let metrics = MetricsRegistry::default();
+/// metrics.record_query("item_knn", Duration::from_millis(3));
+/// metrics.record_cache_lookup(true);
+/// metrics.record_cache_lookup(false);
+/// assert_eq!(metrics.query_count("item_knn"), 1);
+/// assert_eq!(metrics.cache_hit_ratio(), Some(0.5));
+/// assert!(metrics
+///     .to_prometheus()
+///     .contains("rec_rsys_queries_total{model=\"item_knn\"} 1"));
+/// ```
+#[derive(Debug)]
+pub struct MetricsRegistry {
+    latency_buckets: Vec<f32>,
+    state: Mutex<State>,
+}
+
+impl Default for MetricsRegistry {
+    fn default() -> Self {
+        MetricsRegistry::new(DEFAULT_LATENCY_BUCKETS.to_vec())
+    }
+}
+
+impl MetricsRegistry {
+    /// Creates a registry whose latency histograms use the given bucket bounds, in
+    /// seconds.
+    pub fn new(latency_buckets: Vec<f32>) -> Self {
+        MetricsRegistry {
+            latency_buckets,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        // A panic while holding the lock cannot leave the counters inconsistent.
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Records a query answered by a model.
+    pub fn record_query(&self, model: &str, latency: Duration) {
+        self.state()
+            .latencies
+            .entry(model.to_string())
+            .or_insert_with(|| Histogram::new(self.latency_buckets.clone()))
+            .observe(latency.as_secs_f32());
+    }
+
+    /// Runs a query and records how long it took.
+    pub fn time_query<T>(&self, model: &str, query: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = query();
+        self.record_query(model, start.elapsed());
+        result
+    }
+
+    /// Records whether a lookup in the recommendation cache was a hit.
+    pub fn record_cache_lookup(&self, hit: bool) {
+        let mut state = self.state();
+        match hit {
+            true => state.cache_hits += 1,
+            false => state.cache_misses += 1,
+        }
+    }
+
+    /// Records when a model was trained, to export its age.
+    pub fn set_model_trained_at(&self, model: &str, trained_at: SystemTime) {
+        self.state()
+            .trained_at
+            .insert(model.to_string(), trained_at);
+    }
+
+    pub fn query_count(&self, model: &str) -> u64 {
+        self.state()
+            .latencies
+            .get(model)
+            .map_or(0, Histogram::count)
+    }
+
+    /// Estimates a quantile of the latency of a model, in seconds.
+    pub fn latency_quantile(&self, model: &str, q: f32) -> Option<f32> {
+        self.state().latencies.get(model)?.quantile(q)
+    }
+
+    /// The fraction of cache lookups that were hits, `None` before any lookup.
+    pub fn cache_hit_ratio(&self) -> Option<f32> {
+        let state = self.state();
+        let lookups = state.cache_hits + state.cache_misses;
+        (lookups > 0).then(|| state.cache_hits as f32 / lookups as f32)
+    }
+
+    /// Time since the model was trained, `None` when it was never recorded.
+    pub fn model_age(&self, model: &str) -> Option<Duration> {
+        let trained_at = *self.state().trained_at.get(model)?;
+        Some(
+            SystemTime::now()
+                .duration_since(trained_at)
+                .unwrap_or_default(),
+        )
+    }
+
+    /// Exports every metric in the Prometheus text exposition format.
+    pub fn to_prometheus(&self) -> String {
+        let state = self.state();
+        let now = SystemTime::now();
+        let mut out = String::new();
+        header(
+            &mut out,
+            "rec_rsys_queries_total",
+            "counter",
+            "Recommendation queries answered.",
+        );
+        for (model, histogram) in &state.latencies {
+            let model = escape(model);
+            let _ = writeln!(
+                out,
+                "rec_rsys_queries_total{{model=\"{model}\"}} {}",
+                histogram.count()
+            );
+        }
+        header(
+            &mut out,
+            "rec_rsys_query_duration_seconds",
+            "histogram",
+            "Time taken to answer a recommendation query.",
+        );
+        for (model, histogram) in &state.latencies {
+            let model = escape(model);
+            let bounds = histogram.bounds().iter().map(|bound| bound.to_string());
+            let cumulative = histogram.cumulative_counts();
+            for (le, count) in bounds.chain(["+Inf".to_string()]).zip(&cumulative) {
+                let _ = writeln!(
+                    out,
+                    "rec_rsys_query_duration_seconds_bucket{{model=\"{model}\",le=\"{le}\"}} {count}"
+                );
+            }
+            let _ = writeln!(
+                out,
+                "rec_rsys_query_duration_seconds_sum{{model=\"{model}\"}} {}",
+                histogram.sum()
+            );
+            let _ = writeln!(
+                out,
+                "rec_rsys_query_duration_seconds_count{{model=\"{model}\"}} {}",
+                histogram.count()
+            );
+        }
+        header(
+            &mut out,
+            "rec_rsys_cache_lookups_total",
+            "counter",
+            "Lookups in the recommendation cache.",
+        );
+        let _ = writeln!(
+            out,
+            "rec_rsys_cache_lookups_total{{result=\"hit\"}} {}",
+            state.cache_hits
+        );
+        let _ = writeln!(
+            out,
+            "rec_rsys_cache_lookups_total{{result=\"miss\"}} {}",
+            state.cache_misses
+        );
+        header(
+            &mut out,
+            "rec_rsys_model_age_seconds",
+            "gauge",
+            "Time since the model was trained.",
+        );
+        for (model, trained_at) in &state.trained_at {
+            let age = now.duration_since(*trained_at).unwrap_or_default();
+            let _ = writeln!(
+                out,
+                "rec_rsys_model_age_seconds{{model=\"{}\"}} {}",
+                escape(model),
+                age.as_secs_f64()
+            );
+        }
+        out
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+/// Escapes a label value as required by the text format.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_queries() {
+        let metrics = MetricsRegistry::new(vec![0.01, 0.1]);
+        metrics.record_query("knn", Duration::from_millis(5));
+        metrics.record_query("knn", Duration::from_millis(50));
+        assert_eq!(metrics.time_query("mf", || 3), 3);
+        assert_eq!(metrics.query_count("knn"), 2);
+        assert_eq!(metrics.query_count("mf"), 1);
+        assert_eq!(metrics.query_count("svd"), 0);
+        assert!(metrics.latency_quantile("knn", 0.5).unwrap() <= 0.01);
+        assert_eq!(metrics.cache_hit_ratio(), None);
+    }
+
+    #[test]
+    fn test_to_prometheus() {
+        let metrics = MetricsRegistry::new(vec![0.01, 0.1]);
+        metrics.record_query("knn", Duration::from_millis(5));
+        metrics.record_query("knn", Duration::from_millis(50));
+        metrics.record_cache_lookup(true);
+        metrics.set_model_trained_at(
+            "a \"quoted\" name",
+            SystemTime::now() - Duration::from_secs(60),
+        );
+        let text = metrics.to_prometheus();
+        for line in [
+            "# TYPE rec_rsys_query_duration_seconds histogram",
+            "rec_rsys_queries_total{model=\"knn\"} 2",
+            "rec_rsys_query_duration_seconds_bucket{model=\"knn\",le=\"0.01\"} 1",
+            "rec_rsys_query_duration_seconds_bucket{model=\"knn\",le=\"0.1\"} 2",
+            "rec_rsys_query_duration_seconds_bucket{model=\"knn\",le=\"+Inf\"} 2",
+            "rec_rsys_query_duration_seconds_count{model=\"knn\"} 2",
+            "rec_rsys_cache_lookups_total{result=\"hit\"} 1",
+            "rec_rsys_cache_lookups_total{result=\"miss\"} 0",
+        ] {
+            assert!(text.lines().any(|l| l == line), "missing {line}");
+        }
+        assert!(text
+            .contains("rec_rsys_model_age_seconds{model=\"a \\\"quoted\\\" name\"} 6"));
+        assert!(metrics.model_age("a \"quoted\" name").unwrap().as_secs() >= 60);
+    }
+}
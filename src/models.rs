@@ -1,6 +1,8 @@
 //! Place to store all the models used to calculate
+use crate::errors::{Error, Result};
+use crate::memory::MemoryFootprint;
+#[cfg(feature = "async")]
 use async_trait::async_trait;
-use serde::{Deserialize, Serialize};
 /// Generic model to save the results
 // Similarity struct: used to store the result of the similarities calculation
 // struct Result {
@@ -14,7 +16,8 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
 /// Generic model to perform calculations
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Item {
     /// Identifier
     pub id: u32,
@@ -27,14 +30,6 @@ pub struct Item {
 }
 
 impl Item {
-    fn default() -> Self {
-        Item {
-            id: 0,
-            values: vec![0.0],
-            result: f32::NAN,
-        }
-    }
-
     /// New
     pub fn new(id: u32, values: Vec<f32>, result: Option<f32>) -> Self {
         Item {
@@ -56,7 +51,7 @@ impl PartialEq for Item {
     }
 }
 
-impl<'a> PartialEq<Item> for &'a Item {
+impl PartialEq<Item> for &Item {
     fn eq(&self, other: &Item) -> bool {
         *self == other
     }
@@ -68,12 +63,103 @@ impl<'a> PartialEq<&'a Item> for Item {
     }
 }
 
+/// # Fixed dimension item
+/// An [`Item`] whose number of values is known at compile time: it lives on the
+/// stack and is `Copy`, for tiny models, e.g. 8 dimension profiles scored on edge
+/// devices, with the kernels of [`crate::similarity::fixed`].
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::models::{FixedItem, Item};
+/// let item = Item::new(1, vec![0.5, 1.0], None);
+/// let fixed: FixedItem<2> = FixedItem::try_from(&item).unwrap();
+/// assert_eq!(fixed.values, [0.5, 1.0]);
+/// assert!(FixedItem::<3>::try_from(&item).is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FixedItem<const D: usize> {
+    pub id: u32,
+    pub values: [f32; D],
+}
+
+impl<const D: usize> FixedItem<D> {
+    pub fn new(id: u32, values: [f32; D]) -> Self {
+        FixedItem { id, values }
+    }
+}
+
+impl<const D: usize> TryFrom<&Item> for FixedItem<D> {
+    type Error = Error;
+
+    fn try_from(item: &Item) -> Result<Self> {
+        let values = item.values.as_slice().try_into().map_err(|_| {
+            Error::InvalidData(format!(
+                "item {} has {} values, expected {}",
+                item.id,
+                item.values.len(),
+                D
+            ))
+        })?;
+        Ok(FixedItem::new(item.id, values))
+    }
+}
+
+impl<const D: usize> From<FixedItem<D>> for Item {
+    fn from(item: FixedItem<D>) -> Self {
+        Item::new(item.id, item.values.to_vec(), None)
+    }
+}
+
 pub trait ItemAdapter {
     fn to_item(&self) -> Item;
     fn create_values(&self) -> Vec<f32>;
     fn get_references(&self) -> Vec<Item>;
 }
 
+/// # One-hot
+/// A value among a fixed number of categories, e.g. a fieldless enum, encoded as a
+/// vector with a single 1 at its category. With the `derive` feature,
+/// `#[derive(OneHot)]` implements it for enums, and `#[derive(ItemAdapter)]`
+/// encodes the fields marked `#[item(one_hot)]` with it.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::models::OneHot;
+/// enum Color {
+///     Red,
+///     Blue,
+/// }
+/// impl OneHot for Color {
+///     const NUM_CATEGORIES: usize = 2;
+///     fn category(&self) -> usize {
+///         match self {
+///             Color::Red => 0,
+///             Color::Blue => 1,
+///         }
+///     }
+/// }
+/// assert_eq!(Color::Blue.one_hot(), [0.0, 1.0]);
+/// ```
+pub trait OneHot {
+    /// The number of categories, the length of the encoding.
+    const NUM_CATEGORIES: usize;
+
+    /// The index of the category of the value, below [`OneHot::NUM_CATEGORIES`].
+    fn category(&self) -> usize;
+
+    fn one_hot(&self) -> Vec<f32> {
+        let mut encoding = vec![0.0; Self::NUM_CATEGORIES];
+        encoding[self.category()] = 1.0;
+        encoding
+    }
+}
+
+/// Derives [`ItemAdapter`] from the fields of a struct and [`OneHot`] for
+/// fieldless enums, see the `rec_rsys_derive` crate.
+#[cfg(feature = "derive")]
+pub use rec_rsys_derive::{ItemAdapter, OneHot};
+
+#[cfg(feature = "async")]
 #[async_trait]
 pub trait AsyncItemAdapter {
     async fn to_item(&self) -> Item;
@@ -113,6 +199,14 @@ pub fn sum_encoding_vectors(
     sum_vector
 }
 
+impl MemoryFootprint for Item {
+    fn heap_size(&self) -> usize {
+        self.values.heap_size()
+    }
+}
+
+impl<const D: usize> MemoryFootprint for FixedItem<D> {}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,389 @@
+//! # Score monitoring
+//! Tracks the distribution of the scores served, window after window, and compares
+//! it with a reference distribution, e.g. the scores of the first hours after a
+//! deployment. A model trained on broken data or fed broken features usually keeps
+//! answering, only with differently distributed scores.
+use std::collections::VecDeque;
+use std::sync::{Mutex, MutexGuard};
+
+use serde::{Deserialize, Serialize};
+
+use crate::algorithms::config::{ensure, AlgorithmConfig};
+use crate::errors::{Error, Result};
+use crate::memory::MemoryFootprint;
+use crate::recommender::Recommendation;
+use crate::statistics::Histogram;
+
+/// # Drift configuration
+/// The buckets of the score histograms, the rolling windows and the alert
+/// thresholds of a [`ScoreMonitor`].
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::algorithms::config::AlgorithmConfig;
+/// use rec_rsys::monitoring::DriftConfig;
+/// let config = DriftConfig::default()
+///     .set_bounds((1..=10).map(|i| i as f32 * 0.5).collect())
+///     .set_window_secs(600);
+/// assert!(config.validate().is_ok());
+/// assert!(DriftConfig::default().set_bounds(vec![]).validate().is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DriftConfig {
+    /// Upper bounds of the buckets of the scores.
+    pub bounds: Vec<f32>,
+    /// Length of a window, in seconds.
+    pub window_secs: u64,
+    /// Number of windows the current distribution is made of.
+    pub num_windows: usize,
+    /// Population stability index above which the scores drifted.
+    pub psi_threshold: f64,
+    /// Kolmogorov-Smirnov distance above which the scores drifted.
+    pub ks_threshold: f64,
+    /// Minimum number of scores in the current windows to compare them.
+    pub min_count: u64,
+}
+
+impl Default for DriftConfig {
+    fn default() -> Self {
+        DriftConfig {
+            bounds: (-9..=10).map(|i| i as f32 / 10.0).collect(),
+            window_secs: 3600,
+            num_windows: 24,
+            psi_threshold: 0.25,
+            ks_threshold: 0.2,
+            min_count: 100,
+        }
+    }
+}
+
+impl DriftConfig {
+    pub fn set_bounds(mut self, bounds: Vec<f32>) -> Self {
+        self.bounds = bounds;
+        self
+    }
+    pub fn set_window_secs(mut self, window_secs: u64) -> Self {
+        self.window_secs = window_secs;
+        self
+    }
+    pub fn set_num_windows(mut self, num_windows: usize) -> Self {
+        self.num_windows = num_windows;
+        self
+    }
+    pub fn set_psi_threshold(mut self, psi_threshold: f64) -> Self {
+        self.psi_threshold = psi_threshold;
+        self
+    }
+    pub fn set_ks_threshold(mut self, ks_threshold: f64) -> Self {
+        self.ks_threshold = ks_threshold;
+        self
+    }
+    pub fn set_min_count(mut self, min_count: u64) -> Self {
+        self.min_count = min_count;
+        self
+    }
+}
+
+impl AlgorithmConfig for DriftConfig {
+    fn validate(&self) -> Result<()> {
+        ensure(
+            !self.bounds.is_empty() && self.bounds.iter().all(|b| b.is_finite()),
+            "bounds",
+            "finite and not empty",
+        )?;
+        ensure(self.window_secs > 0, "window_secs", "greater than 0")?;
+        ensure(self.num_windows > 0, "num_windows", "greater than 0")?;
+        ensure(self.psi_threshold > 0.0, "psi_threshold", "greater than 0")?;
+        ensure(
+            self.ks_threshold > 0.0 && self.ks_threshold <= 1.0,
+            "ks_threshold",
+            "between 0 and 1",
+        )
+    }
+}
+
+/// Why the scores are flagged.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DriftAlert {
+    /// The population stability index is above its threshold.
+    Psi { value: f64 },
+    /// The Kolmogorov-Smirnov distance is above its threshold.
+    Ks { value: f64 },
+    /// Scores were NaN or infinite, whatever the reference.
+    NonFinite { count: u64 },
+}
+
+/// # Drift report
+/// The comparison of the current windows with the reference, serializable to be
+/// logged or returned by a monitoring endpoint.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DriftReport {
+    pub current_count: u64,
+    pub reference_count: u64,
+    /// `None` without reference or with too few current scores.
+    pub psi: Option<f64>,
+    pub ks: Option<f64>,
+    pub non_finite: u64,
+    pub alerts: Vec<DriftAlert>,
+}
+
+impl DriftReport {
+    pub fn is_drifting(&self) -> bool {
+        !self.alerts.is_empty()
+    }
+}
+
+#[derive(Debug)]
+struct Window {
+    start: u64,
+    histogram: Histogram,
+    non_finite: u64,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    windows: VecDeque<Window>,
+    reference: Option<Histogram>,
+}
+
+/// # Score monitor
+/// Rolling histograms of the served scores, shared between the serving threads,
+/// compared with a reference histogram by [`ScoreMonitor::check`].
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::monitoring::{DriftAlert, DriftConfig, ScoreMonitor};
+/// let monitor = ScoreMonitor::new(DriftConfig::default().set_min_count(10)).unwrap();
+/// (0..100).for_each(|i| monitor.observe(i as f32 / 100.0, 0));
+/// monitor.freeze_reference();
+/// // An hour later the model only returns low scores.
+/// (0..100).for_each(|i| monitor.observe(i as f32 / 1000.0, 3_600));
+/// monitor.observe(f32::NAN, 3_600);
+/// let report = monitor.check();
+/// assert!(report.is_drifting());
+/// assert!(report.alerts.contains(&DriftAlert::NonFinite { count: 1 }));
+/// ```
+#[derive(Debug)]
+pub struct ScoreMonitor {
+    config: DriftConfig,
+    state: Mutex<State>,
+}
+
+impl ScoreMonitor {
+    pub fn new(config: DriftConfig) -> Result<Self> {
+        config.validate()?;
+        Ok(ScoreMonitor {
+            config,
+            state: Mutex::new(State::default()),
+        })
+    }
+
+    pub fn config(&self) -> &DriftConfig {
+        &self.config
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        // Observations are single increments, a panic cannot leave them half done.
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Records a served score.
+    ///
+    /// ## Parameters:
+    /// * `score`: The score.
+    /// * `timestamp`: When it was served, in seconds. Scores older than the
+    ///   windows kept are ignored.
+    pub fn observe(&self, score: f32, timestamp: u64) {
+        self.observe_all([score], timestamp);
+    }
+
+    /// Records scores served at the same time.
+    pub fn observe_all<I: IntoIterator<Item = f32>>(&self, scores: I, timestamp: u64) {
+        let mut state = self.state();
+        let start = timestamp - timestamp % self.config.window_secs;
+        if state.windows.back().is_none_or(|last| last.start < start) {
+            state.windows.push_back(Window {
+                start,
+                histogram: Histogram::new(self.config.bounds.clone()),
+                non_finite: 0,
+            });
+            while state.windows.len() > self.config.num_windows {
+                state.windows.pop_front();
+            }
+        }
+        let Some(window) = state.windows.iter_mut().rev().find(|w| w.start == start)
+        else {
+            return;
+        };
+        for score in scores {
+            match score.is_finite() {
+                true => window.histogram.observe(score),
+                false => window.non_finite += 1,
+            }
+        }
+    }
+
+    /// Records the scores of a served list.
+    pub fn observe_recommendations(
+        &self,
+        recommendations: &[Recommendation],
+        timestamp: u64,
+    ) {
+        self.observe_all(recommendations.iter().map(|r| r.score), timestamp);
+    }
+
+    /// The distribution of the scores of the windows kept.
+    pub fn current(&self) -> Histogram {
+        let state = self.state();
+        let mut current = Histogram::new(self.config.bounds.clone());
+        for window in &state.windows {
+            // Every window has the bounds of the config.
+            let _ = current.merge(&window.histogram);
+        }
+        current
+    }
+
+    /// Uses the windows kept as the reference and starts over from empty windows,
+    /// e.g. once a new model served long enough to be trusted.
+    pub fn freeze_reference(&self) {
+        let current = self.current();
+        let mut state = self.state();
+        state.reference = Some(current);
+        state.windows.clear();
+    }
+
+    /// Uses a histogram as the reference, e.g. the scores of the offline
+    /// evaluation. Its bounds must be the ones of the config.
+    pub fn set_reference(&self, reference: Histogram) -> Result<()> {
+        if reference.bounds() != Histogram::new(self.config.bounds.clone()).bounds() {
+            return Err(Error::InvalidData(
+                "the reference must have the bounds of the config".to_string(),
+            ));
+        }
+        self.state().reference = Some(reference);
+        Ok(())
+    }
+
+    /// # Check
+    /// Compares the current windows with the reference.
+    ///
+    /// ## Returns:
+    /// * The report, whose alerts are the thresholds exceeded. The distributions
+    ///   are only compared with a reference and at least `min_count` current scores.
+    pub fn check(&self) -> DriftReport {
+        let current = self.current();
+        let state = self.state();
+        let mut report = DriftReport {
+            current_count: current.count(),
+            reference_count: state.reference.as_ref().map_or(0, Histogram::count),
+            non_finite: state.windows.iter().map(|w| w.non_finite).sum(),
+            ..DriftReport::default()
+        };
+        if let Some(reference) = &state.reference {
+            if current.count() >= self.config.min_count {
+                report.psi = current.population_stability_index(reference);
+                report.ks = current.ks_distance(reference);
+            }
+        }
+        if let Some(value) = report.psi.filter(|psi| *psi > self.config.psi_threshold) {
+            report.alerts.push(DriftAlert::Psi { value });
+        }
+        if let Some(value) = report.ks.filter(|ks| *ks > self.config.ks_threshold) {
+            report.alerts.push(DriftAlert::Ks { value });
+        }
+        if report.non_finite > 0 {
+            report.alerts.push(DriftAlert::NonFinite {
+                count: report.non_finite,
+            });
+        }
+        report
+    }
+}
+
+impl MemoryFootprint for DriftConfig {
+    fn heap_size(&self) -> usize {
+        self.bounds.heap_size()
+    }
+}
+
+impl MemoryFootprint for DriftAlert {}
+
+impl MemoryFootprint for DriftReport {
+    fn heap_size(&self) -> usize {
+        self.alerts.heap_size()
+    }
+}
+
+impl MemoryFootprint for ScoreMonitor {
+    fn heap_size(&self) -> usize {
+        let histogram = size_of::<f32>() * self.config.bounds.len()
+            + size_of::<u64>() * (self.config.bounds.len() + 1);
+        let state = self.state();
+        let windows = state.windows.capacity() * size_of::<Window>()
+            + state.windows.len() * histogram;
+        self.config.heap_size()
+            + windows
+            + state.reference.as_ref().map_or(0, |_| histogram)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn monitor() -> ScoreMonitor {
+        let config = DriftConfig::default()
+            .set_window_secs(10)
+            .set_num_windows(2)
+            .set_min_count(5);
+        ScoreMonitor::new(config).unwrap()
+    }
+
+    #[test]
+    fn test_rolling_windows() {
+        let monitor = monitor();
+        monitor.observe_all([0.1, 0.2], 5);
+        monitor.observe_all([0.3], 15);
+        monitor.observe(0.4, 9);
+        assert_eq!(monitor.current().count(), 4);
+        monitor.observe(0.5, 25);
+        // The first window was dropped, as are the scores older than the windows.
+        monitor.observe(0.6, 1);
+        assert_eq!(monitor.current().count(), 2);
+    }
+
+    #[test]
+    fn test_stable_scores() {
+        let monitor = monitor();
+        let scores: Vec<f32> = (0..50).map(|i| (i % 10) as f32 / 10.0).collect();
+        monitor.observe_all(scores.iter().copied(), 0);
+        assert_eq!(monitor.check().psi, None);
+        monitor.freeze_reference();
+        assert_eq!(monitor.check().current_count, 0);
+        monitor.observe_all(scores.iter().copied(), 100);
+        let report = monitor.check();
+        assert_eq!(report.reference_count, 50);
+        assert_eq!(report.ks, Some(0.0));
+        assert!(!report.is_drifting());
+    }
+
+    #[test]
+    fn test_set_reference() {
+        let monitor = monitor();
+        assert!(monitor.set_reference(Histogram::new(vec![1.0])).is_err());
+        let mut reference = Histogram::new(DriftConfig::default().bounds);
+        (0..10).for_each(|_| reference.observe(0.9));
+        monitor.set_reference(reference).unwrap();
+        (0..10).for_each(|_| monitor.observe(-0.9, 0));
+        let report = monitor.check();
+        assert_eq!(report.ks, Some(1.0));
+        assert!(matches!(
+            report.alerts[..],
+            [DriftAlert::Psi { .. }, DriftAlert::Ks { .. }]
+        ));
+    }
+}
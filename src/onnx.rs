@@ -0,0 +1,303 @@
+//! # ONNX export
+//! Writes the scoring function of a factorization model as an ONNX graph, so the
+//! model can be served by any ONNX runtime, outside of Rust. The graph embeds the
+//! factors and the biases and computes, for batches of `(user, item)` rows,
+//!
+//! `score = global_mean + user_bias[user] + item_bias[item] + user_factors[user] · item_factors[item]`
+//!
+//! The inputs are the rows of the factor matrices, not the ids: map the ids with
+//! [`MatrixFactorization::user_ids`] and [`MatrixFactorization::item_ids`].
+//! The protobuf messages are encoded by hand, only the few fields of the ONNX
+//! schema the graph needs are written.
+use std::fs;
+use std::path::Path;
+
+use crate::algorithms::mf::MatrixFactorization;
+use crate::errors::{Error, Result};
+use crate::factors::FactorMatrix;
+
+/// Version of the ONNX file format written.
+pub const IR_VERSION: u64 = 8;
+/// Version of the default operator set the graph uses.
+pub const OPSET_VERSION: u64 = 13;
+
+const FLOAT: u64 = 1;
+const INT64: u64 = 7;
+const ATTRIBUTE_INT: u64 = 2;
+
+/// A protobuf message being encoded.
+#[derive(Debug, Default)]
+struct Message(Vec<u8>);
+
+impl Message {
+    fn varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.0.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        self.0.push(value as u8);
+    }
+
+    fn key(&mut self, field: u64, wire_type: u64) {
+        self.varint(field << 3 | wire_type);
+    }
+
+    fn int(mut self, field: u64, value: u64) -> Self {
+        self.key(field, 0);
+        self.varint(value);
+        self
+    }
+
+    fn bytes(mut self, field: u64, bytes: &[u8]) -> Self {
+        self.key(field, 2);
+        self.varint(bytes.len() as u64);
+        self.0.extend_from_slice(bytes);
+        self
+    }
+
+    fn string(self, field: u64, value: &str) -> Self {
+        self.bytes(field, value.as_bytes())
+    }
+
+    fn message(self, field: u64, message: Message) -> Self {
+        self.bytes(field, &message.0)
+    }
+}
+
+/// A `TensorProto` holding its values as little-endian raw data.
+fn tensor(name: &str, data_type: u64, dims: &[usize], raw: Vec<u8>) -> Message {
+    dims.iter()
+        .fold(Message::default(), |m, dim| m.int(1, *dim as u64))
+        .int(2, data_type)
+        .string(8, name)
+        .bytes(9, &raw)
+}
+
+fn float_tensor(name: &str, dims: &[usize], values: &[f32]) -> Message {
+    let raw = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+    tensor(name, FLOAT, dims, raw)
+}
+
+/// A `ValueInfoProto` of a one dimensional tensor of dynamic length.
+fn batch_value(name: &str, elem_type: u64) -> Message {
+    let dimension = Message::default().string(2, "batch");
+    let shape = Message::default().message(1, dimension);
+    let tensor_type = Message::default().int(1, elem_type).message(2, shape);
+    let value_type = Message::default().message(1, tensor_type);
+    Message::default().string(1, name).message(2, value_type)
+}
+
+fn node(op_type: &str, inputs: &[&str], output: &str) -> Message {
+    inputs
+        .iter()
+        .fold(Message::default(), |m, input| m.string(1, input))
+        .string(2, output)
+        .string(3, output)
+        .string(4, op_type)
+}
+
+fn int_attribute(name: &str, value: u64) -> Message {
+    Message::default()
+        .string(1, name)
+        .int(3, value)
+        .int(20, ATTRIBUTE_INT)
+}
+
+fn matrix_values(matrix: &FactorMatrix) -> Vec<f32> {
+    (0..matrix.rows()).flat_map(|row| matrix.row(row)).collect()
+}
+
+/// # Export MF
+/// Encodes the scoring function of a trained [`MatrixFactorization`] as an ONNX
+/// model with the `int64` inputs `user` and `item`, of the same length, and the
+/// `float` output `score`.
+///
+/// ## Parameters:
+/// * `model`: The trained model.
+///
+/// ## Returns:
+/// * The serialized `ModelProto`, or an error if the model is not trained.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::algorithms::mf::{MFConfig, MatrixFactorization};
+/// use rec_rsys::dataset::{Dataset, Rating};
+/// use rec_rsys::onnx::export_mf;
+/// use rec_rsys::recommender::Recommender;
+/// let dataset = Dataset::new(vec![Rating::new(1, 10, 5.0), Rating::new(2, 11, 1.0)]);
+/// let mut model = MatrixFactorization::new(MFConfig::default().set_num_factors(2));
+/// assert!(export_mf(&model).is_err());
+/// model.fit(&dataset).unwrap();
+/// let onnx = export_mf(&model).unwrap();
+/// assert!(!onnx.is_empty());
+/// ```
+pub fn export_mf(model: &MatrixFactorization) -> Result<Vec<u8>> {
+    let (users, items) = (model.user_factors(), model.item_factors());
+    if users.rows() == 0 || items.rows() == 0 {
+        return Err(Error::InvalidData(
+            "cannot export an untrained model".to_string(),
+        ));
+    }
+    let dims = users.dims();
+    let initializers = [
+        float_tensor("user_factors", &[users.rows(), dims], &matrix_values(users)),
+        float_tensor("item_factors", &[items.rows(), dims], &matrix_values(items)),
+        float_tensor("user_biases", &[users.rows()], model.user_biases()),
+        float_tensor("item_biases", &[items.rows()], model.item_biases()),
+        float_tensor("global_mean", &[], &[model.global_mean()]),
+        tensor("axes", INT64, &[1], 1_i64.to_le_bytes().to_vec()),
+    ];
+    let nodes = [
+        node("Gather", &["user_factors", "user"], "p"),
+        node("Gather", &["item_factors", "item"], "q"),
+        node("Mul", &["p", "q"], "pq"),
+        node("ReduceSum", &["pq", "axes"], "dot")
+            .message(5, int_attribute("keepdims", 0)),
+        node("Gather", &["user_biases", "user"], "user_bias"),
+        node("Gather", &["item_biases", "item"], "item_bias"),
+        node("Add", &["dot", "user_bias"], "with_user_bias"),
+        node("Add", &["with_user_bias", "item_bias"], "with_biases"),
+        node("Add", &["with_biases", "global_mean"], "score"),
+    ];
+    let graph = nodes
+        .into_iter()
+        .fold(Message::default(), |graph, node| graph.message(1, node))
+        .string(2, "matrix_factorization");
+    let graph = initializers
+        .into_iter()
+        .fold(graph, |graph, tensor| graph.message(5, tensor))
+        .message(11, batch_value("user", INT64))
+        .message(11, batch_value("item", INT64))
+        .message(12, batch_value("score", FLOAT));
+    let opset = Message::default().string(1, "").int(2, OPSET_VERSION);
+    let model = Message::default()
+        .int(1, IR_VERSION)
+        .string(2, "rec_rsys")
+        .string(3, env!("CARGO_PKG_VERSION"))
+        .message(7, graph)
+        .message(8, opset);
+    Ok(model.0)
+}
+
+/// Writes the ONNX model of [`export_mf`] to a file, usually `*.onnx`.
+pub fn write_mf<P: AsRef<Path>>(model: &MatrixFactorization, path: P) -> Result<()> {
+    Ok(fs::write(path, export_mf(model)?)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::algorithms::mf::MFConfig;
+    use crate::dataset::{Dataset, Rating};
+    use crate::recommender::Recommender;
+
+    /// Decodes the fields of a message, the values of varints as little-endian
+    /// bytes so every field is a byte string.
+    fn fields(mut bytes: &[u8]) -> Vec<(u64, Vec<u8>)> {
+        fn varint(bytes: &mut &[u8]) -> u64 {
+            let mut value = 0;
+            for shift in (0..).step_by(7) {
+                let byte = bytes[0];
+                *bytes = &bytes[1..];
+                value |= ((byte & 0x7f) as u64) << shift;
+                if byte < 0x80 {
+                    break;
+                }
+            }
+            value
+        }
+        let mut fields = Vec::new();
+        while !bytes.is_empty() {
+            let key = varint(&mut bytes);
+            let value = match key & 7 {
+                0 => varint(&mut bytes).to_le_bytes().to_vec(),
+                2 => {
+                    let length = varint(&mut bytes) as usize;
+                    let (value, rest) = bytes.split_at(length);
+                    bytes = rest;
+                    value.to_vec()
+                },
+                wire_type => panic!("unexpected wire type {}", wire_type),
+            };
+            fields.push((key >> 3, value));
+        }
+        fields
+    }
+
+    fn field(message: &[(u64, Vec<u8>)], number: u64) -> Vec<Vec<u8>> {
+        message
+            .iter()
+            .filter(|(n, _)| *n == number)
+            .map(|(_, value)| value.clone())
+            .collect()
+    }
+
+    fn text(bytes: &[u8]) -> String {
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[test]
+    fn test_varint() {
+        let mut message = Message::default();
+        message.varint(300);
+        assert_eq!(message.0, vec![0xac, 0x02]);
+    }
+
+    /// Evaluates the exported graph by hand and compares it with the model.
+    #[test]
+    fn test_export_mf() {
+        let dataset = Dataset::new(
+            (1..=4)
+                .flat_map(|user| (10..13).map(move |item| (user, item)))
+                .map(|(user, item)| Rating::new(user, item, ((user + item) % 5) as f32))
+                .collect(),
+        );
+        let mut model = MatrixFactorization::new(MFConfig::default().set_num_factors(3));
+        model.fit(&dataset).unwrap();
+        let onnx = fields(&export_mf(&model).unwrap());
+        assert_eq!(field(&onnx, 1)[0][0], IR_VERSION as u8);
+        let graph = fields(&field(&onnx, 7)[0]);
+        let op_types: Vec<String> = field(&graph, 1)
+            .iter()
+            .map(|node| text(&field(&fields(node), 4)[0]))
+            .collect();
+        assert_eq!(op_types[..4], ["Gather", "Gather", "Mul", "ReduceSum"]);
+        let tensors: HashMap<String, Vec<f32>> = field(&graph, 5)
+            .iter()
+            .map(|tensor| {
+                let tensor = fields(tensor);
+                let raw = &field(&tensor, 9)[0];
+                let values = raw
+                    .chunks(4)
+                    .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                    .collect();
+                (text(&field(&tensor, 8)[0]), values)
+            })
+            .collect();
+        let inputs: Vec<String> = field(&graph, 11)
+            .iter()
+            .map(|input| text(&field(&fields(input), 1)[0]))
+            .collect();
+        assert_eq!(inputs, ["user", "item"]);
+
+        let users = model.user_ids();
+        for (item, item_id) in model.item_ids().iter().enumerate() {
+            for (user, user_id) in users.iter().enumerate() {
+                let dot: f32 = (0..3)
+                    .map(|k| {
+                        tensors["user_factors"][user * 3 + k]
+                            * tensors["item_factors"][item * 3 + k]
+                    })
+                    .sum();
+                let score = tensors["global_mean"][0]
+                    + tensors["user_biases"][user]
+                    + tensors["item_biases"][item]
+                    + dot;
+                let expected = model.predict(*user_id, *item_id).unwrap();
+                assert!((score - expected).abs() < 1e-5);
+            }
+        }
+    }
+}
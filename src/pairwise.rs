@@ -0,0 +1,782 @@
+//! # Pairwise similarity tables
+//! The most similar items of every item of a catalog, computed once and reused by
+//! neighborhood recommenders. Comparing every pair is quadratic, so large catalogs
+//! can first be split into candidate buckets with locality-sensitive hashing, with
+//! the `ann` feature, and only the items sharing a bucket are compared. On skewed
+//! data the popular items end up the neighbors of everything,
+//! [`PopularityNormalization`] removes their advantage.
+use std::borrow::Cow;
+use std::collections::HashMap;
+#[cfg(feature = "ann")]
+use std::collections::HashSet;
+
+#[cfg(feature = "ann")]
+use rand::rngs::StdRng;
+#[cfg(feature = "ann")]
+use rand::SeedableRng;
+#[cfg(feature = "ann")]
+use rand_distr::{Distribution, StandardNormal};
+
+use crate::algorithms::config::{ensure, AlgorithmConfig};
+use crate::algorithms::knn::to_similarity;
+use crate::errors::Result;
+use crate::memory::MemoryFootprint;
+use crate::models::Item;
+use crate::parallelism::default_parallelism;
+use crate::parallelism::prelude::*;
+use crate::scratch::Scratch;
+use crate::similarity::SimilarityAlgos;
+
+/// # LSH blocking
+/// Random hyperplane hashing: every table draws `num_hyperplanes` random
+/// hyperplanes and the signature of an item is the side of each hyperplane it lies
+/// on. Two items are candidates when they share a signature in at least one table.
+///
+/// The hyperplanes approximate the angle between vectors, so the blocking suits
+/// the cosine and the Pearson correlation best. More hyperplanes give smaller
+/// buckets, more tables give back the pairs a single table misses.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::algorithms::config::AlgorithmConfig;
+/// use rec_rsys::pairwise::LshBlocking;
+/// let blocking = LshBlocking::default().set_num_tables(4).set_num_hyperplanes(8);
+/// assert!(blocking.validate().is_ok());
+/// assert!(LshBlocking::default().set_num_hyperplanes(65).validate().is_err());
+/// ```
+#[cfg(feature = "ann")]
+#[doc = include_str!("../docs/pairwise/lsh_blocking.md")]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct LshBlocking {
+    pub num_tables: usize,
+    pub num_hyperplanes: usize,
+    pub seed: u64,
+}
+
+#[cfg(feature = "ann")]
+impl Default for LshBlocking {
+    fn default() -> Self {
+        LshBlocking {
+            num_tables: 8,
+            num_hyperplanes: 12,
+            seed: 42,
+        }
+    }
+}
+
+#[cfg(feature = "ann")]
+impl LshBlocking {
+    pub fn set_num_tables(mut self, num_tables: usize) -> Self {
+        self.num_tables = num_tables;
+        self
+    }
+
+    pub fn set_num_hyperplanes(mut self, num_hyperplanes: usize) -> Self {
+        self.num_hyperplanes = num_hyperplanes;
+        self
+    }
+
+    pub fn set_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// The random hyperplanes of every table, for vectors of `dimensions` values.
+    pub(crate) fn hyperplanes(&self, dimensions: usize) -> Vec<Vec<Vec<f32>>> {
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        (0..self.num_tables)
+            .map(|_| {
+                (0..self.num_hyperplanes)
+                    .map(|_| {
+                        (0..dimensions)
+                            .map(|_| StandardNormal.sample(&mut rng))
+                            .collect()
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// The indices of the items sharing a bucket with each item, itself excluded.
+    pub fn candidates(&self, items: &[Item]) -> Vec<Vec<usize>> {
+        let dimensions = items.first().map_or(0, |item| item.values.len());
+        let mut candidates: Vec<HashSet<usize>> = vec![HashSet::new(); items.len()];
+        for hyperplanes in self.hyperplanes(dimensions) {
+            let mut buckets: HashMap<u64, Vec<usize>> = HashMap::new();
+            items.iter().enumerate().for_each(|(index, item)| {
+                let signature = signature(&item.values, &hyperplanes);
+                buckets.entry(signature).or_default().push(index);
+            });
+            for bucket in buckets.values() {
+                for &index in bucket {
+                    candidates[index].extend(bucket.iter().filter(|&&i| i != index));
+                }
+            }
+        }
+        candidates
+            .into_iter()
+            .map(|set| {
+                let mut indices: Vec<usize> = set.into_iter().collect();
+                indices.sort_unstable();
+                indices
+            })
+            .collect()
+    }
+}
+
+#[cfg(feature = "ann")]
+impl AlgorithmConfig for LshBlocking {
+    fn validate(&self) -> Result<()> {
+        ensure(self.num_tables > 0, "num_tables", "greater than 0")?;
+        ensure(
+            self.num_hyperplanes > 0 && self.num_hyperplanes <= 64,
+            "num_hyperplanes",
+            "between 1 and 64",
+        )
+    }
+}
+
+/// One bit per hyperplane, set when the vector is on its positive side.
+#[cfg(feature = "ann")]
+pub(crate) fn signature(values: &[f32], hyperplanes: &[Vec<f32>]) -> u64 {
+    hyperplanes
+        .iter()
+        .enumerate()
+        .filter(|(_, hyperplane)| {
+            hyperplane
+                .iter()
+                .zip(values)
+                .map(|(h, v)| h * v)
+                .sum::<f32>()
+                >= 0.0
+        })
+        .fold(0, |signature, (bit, _)| signature | (1 << bit))
+}
+
+/// # Popularity normalization
+/// Removes the effect of item popularity from the similarities of items described
+/// by the ratings they received, 0 standing for a missing rating.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::models::Item;
+/// use rec_rsys::pairwise::{damp, PopularityNormalization};
+/// let items = vec![
+///     Item::new(1, vec![4.0, 0.0, 2.0], None),
+///     Item::new(2, vec![0.0, 1.0, 0.0], None),
+/// ];
+/// let centered = PopularityNormalization::CenterItems.center(&items);
+/// assert_eq!(centered[0].values, [1.0, 0.0, -1.0]);
+/// let damping = PopularityNormalization::Damping { alpha: 1.0 };
+/// assert_eq!(damping.weights(&items), [2.0, 1.0]);
+/// assert_eq!(damp(0.8, 2.0), 0.4);
+/// ```
+#[doc = include_str!("../docs/pairwise/popularity_normalization.md")]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind", rename_all = "snake_case"))]
+pub enum PopularityNormalization {
+    /// The similarities of the raw ratings.
+    #[default]
+    None,
+    /// Subtracts from the ratings of every item their mean, so items rated well by
+    /// everyone do not look alike only because of it.
+    CenterItems,
+    /// Divides the positive similarity of a neighbor by its number of ratings
+    /// raised to `alpha`, 0 leaving the similarities as they are and 1 cancelling
+    /// most of the popularity.
+    Damping { alpha: f32 },
+}
+
+impl PopularityNormalization {
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            PopularityNormalization::Damping { alpha } => ensure(
+                *alpha >= 0.0 && alpha.is_finite(),
+                "normalization.alpha",
+                "a non-negative number",
+            ),
+            _ => Ok(()),
+        }
+    }
+
+    /// The items to compare: centered ones for [`PopularityNormalization::CenterItems`],
+    /// the given ones otherwise.
+    pub fn center<'a>(&self, items: &'a [Item]) -> Cow<'a, [Item]> {
+        match self {
+            PopularityNormalization::CenterItems => Cow::Owned(center_items(items)),
+            _ => Cow::Borrowed(items),
+        }
+    }
+
+    /// What the similarity of each item as a neighbor is divided by, 1 unless the
+    /// similarities are damped.
+    pub fn weights(&self, items: &[Item]) -> Vec<f32> {
+        match self {
+            PopularityNormalization::Damping { alpha } => items
+                .iter()
+                .map(|item| (popularity(item).max(1) as f32).powf(*alpha))
+                .collect(),
+            _ => vec![1.0; items.len()],
+        }
+    }
+}
+
+/// Damps a similarity by the weight of the neighbor. The negative similarities,
+/// already at the bottom of the lists, are left as they are.
+pub fn damp(similarity: f32, weight: f32) -> f32 {
+    match similarity > 0.0 {
+        true => similarity / weight,
+        false => similarity,
+    }
+}
+
+/// # Shrink
+/// Shrinks a similarity towards 0 when it is supported by few users, so pairs of
+/// items rated together once or twice do not outrank well supported ones.
+///
+/// ## Parameters:
+/// * `similarity`: The similarity of two items.
+/// * `overlap`: The number of users who rated both, see [`co_ratings`].
+/// * `shrinkage`: The overlap at which the similarity is halved, 0 to keep it.
+///
+/// ## Returns:
+/// * The similarity multiplied by `overlap / (overlap + shrinkage)`, unchanged
+///   when `shrinkage` is 0.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::pairwise::shrink;
+/// assert_eq!(shrink(0.9, 10, 10.0), 0.45);
+/// assert_eq!(shrink(0.9, 10, 0.0), 0.9);
+/// assert_eq!(shrink(0.9, 0, 0.0), 0.9);
+/// ```
+pub fn shrink(similarity: f32, overlap: usize, shrinkage: f32) -> f32 {
+    match shrinkage > 0.0 {
+        true => similarity * overlap as f32 / (overlap as f32 + shrinkage),
+        false => similarity,
+    }
+}
+
+/// # Weighted rating
+/// Predicts the rating of an item as the average of the ratings the user gave to
+/// its neighbors, weighted by their similarity with the item.
+///
+/// ## Parameters:
+/// * `neighbors`: The neighbors of the item, with their similarity.
+/// * `history`: The items rated by the user, with their rating.
+///
+/// ## Returns:
+/// * The weighted average over the neighbors the user rated with a positive
+///   similarity, `None` when there is none.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::pairwise::weighted_rating;
+/// let neighbors = [(2, 0.9), (3, 0.3), (4, -0.5)];
+/// let history = [(3, 1.0), (2, 5.0), (4, 5.0)];
+/// assert_eq!(weighted_rating(&neighbors, &history), Some((0.9 * 5.0 + 0.3 * 1.0) / 1.2));
+/// assert_eq!(weighted_rating(&neighbors, &[(5, 4.0)]), None);
+/// ```
+#[doc = include_str!("../docs/pairwise/weighted_rating.md")]
+pub fn weighted_rating(neighbors: &[(u32, f32)], history: &[(u32, f32)]) -> Option<f32> {
+    let ratings: HashMap<u32, f32> = history.iter().copied().collect();
+    let (total, weights) = neighbors
+        .iter()
+        .filter(|(_, similarity)| *similarity > 0.0)
+        .filter_map(|(neighbor, similarity)| Some((ratings.get(neighbor)?, similarity)))
+        .fold((0.0, 0.0), |(total, weights), (rating, similarity)| {
+            (total + similarity * rating, weights + similarity)
+        });
+    (weights > 0.0).then(|| total / weights)
+}
+
+/// The number of users who rated both items, the positions where neither of their
+/// values is 0.
+pub fn co_ratings(a: &Item, b: &Item) -> usize {
+    a.values
+        .iter()
+        .zip(&b.values)
+        .filter(|(a, b)| **a != 0.0 && **b != 0.0)
+        .count()
+}
+
+/// The number of ratings of an item, its values that are not 0.
+fn popularity(item: &Item) -> usize {
+    item.values.iter().filter(|value| **value != 0.0).count()
+}
+
+/// Subtracts from the ratings of every item, its values that are not 0, their mean.
+pub fn center_items(items: &[Item]) -> Vec<Item> {
+    items
+        .iter()
+        .map(|item| {
+            let mean = item.values.iter().sum::<f32>() / popularity(item).max(1) as f32;
+            let values = item
+                .values
+                .iter()
+                .map(|value| match *value != 0.0 {
+                    true => value - mean,
+                    false => 0.0,
+                })
+                .collect();
+            Item::new(item.id, values, None)
+        })
+        .collect()
+}
+
+/// # Table options
+/// How [`SimilarityTable::build`] compares the items and which neighbors it keeps.
+/// The filters bound the size of the table to `num_neighbors` per item and drop the
+/// similarities that are too weak or supported by too few users to be trusted.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::algorithms::config::AlgorithmConfig;
+/// use rec_rsys::pairwise::{PopularityNormalization, TableOptions};
+/// let options = TableOptions::default()
+///     .set_num_neighbors(10)
+///     .set_min_overlap(3)
+///     .set_normalization(PopularityNormalization::Damping { alpha: 0.5 });
+/// # #[cfg(feature = "serde")]
+/// assert_eq!(TableOptions::from_toml(
+///     "num_neighbors = 10\nmin_overlap = 3\n[normalization]\nkind = \"damping\"\nalpha = 0.5",
+/// ).unwrap(), options);
+/// assert!(options.set_min_similarity(-1.0).validate().is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct TableOptions {
+    /// The maximum number of neighbors of every item.
+    pub num_neighbors: usize,
+    /// How the popularity of the items is removed.
+    pub normalization: PopularityNormalization,
+    /// Only compares the items sharing a bucket, when set.
+    #[cfg(feature = "ann")]
+    pub blocking: Option<LshBlocking>,
+    /// The minimum number of users who rated both items, see [`co_ratings`].
+    pub min_overlap: usize,
+    /// The minimum absolute similarity of a neighbor.
+    pub min_similarity: f32,
+}
+
+impl Default for TableOptions {
+    fn default() -> Self {
+        TableOptions {
+            num_neighbors: 20,
+            normalization: PopularityNormalization::None,
+            #[cfg(feature = "ann")]
+            blocking: None,
+            min_overlap: 0,
+            min_similarity: 0.0,
+        }
+    }
+}
+
+impl TableOptions {
+    pub fn set_num_neighbors(mut self, num_neighbors: usize) -> Self {
+        self.num_neighbors = num_neighbors;
+        self
+    }
+    pub fn set_normalization(mut self, normalization: PopularityNormalization) -> Self {
+        self.normalization = normalization;
+        self
+    }
+    #[cfg(feature = "ann")]
+    pub fn set_blocking(mut self, blocking: LshBlocking) -> Self {
+        self.blocking = Some(blocking);
+        self
+    }
+    pub fn set_min_overlap(mut self, min_overlap: usize) -> Self {
+        self.min_overlap = min_overlap;
+        self
+    }
+    pub fn set_min_similarity(mut self, min_similarity: f32) -> Self {
+        self.min_similarity = min_similarity;
+        self
+    }
+
+    /// Whether a neighbor passes the filters.
+    fn keeps(&self, similarity: f32, overlap: impl FnOnce() -> usize) -> bool {
+        similarity.is_finite()
+            && similarity.abs() >= self.min_similarity
+            && (self.min_overlap == 0 || overlap() >= self.min_overlap)
+    }
+}
+
+impl AlgorithmConfig for TableOptions {
+    fn validate(&self) -> Result<()> {
+        ensure(self.num_neighbors > 0, "num_neighbors", "greater than 0")?;
+        ensure(
+            self.min_similarity >= 0.0 && self.min_similarity.is_finite(),
+            "min_similarity",
+            "a non-negative number",
+        )?;
+        #[cfg(feature = "ann")]
+        if let Some(blocking) = &self.blocking {
+            blocking.validate()?;
+        }
+        self.normalization.validate()
+    }
+}
+
+/// # Similarity table
+/// The `k` most similar items of every item, with their similarity. Distances are
+/// turned into similarities so the neighbors are always sorted from the most
+/// similar.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::models::Item;
+/// use rec_rsys::pairwise::SimilarityTable;
+/// use rec_rsys::similarity::SimilarityAlgos;
+/// let items = vec![
+///     Item::new(1, vec![1.0, 0.0], None),
+///     Item::new(2, vec![0.9, 0.1], None),
+///     Item::new(3, vec![-1.0, 0.2], None),
+/// ];
+/// let exact = SimilarityTable::exact(&items, SimilarityAlgos::Cosine, 1);
+/// assert_eq!(exact.neighbors(1)[0].0, 2);
+/// # #[cfg(feature = "ann")]
+/// # {
+/// use rec_rsys::pairwise::LshBlocking;
+/// let blocked = SimilarityTable::with_blocking(&items, SimilarityAlgos::Cosine, 1, &LshBlocking::default()).unwrap();
+/// assert_eq!(blocked.neighbors(1)[0].0, 2);
+/// assert!(blocked.num_comparisons() <= exact.num_comparisons());
+/// # }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SimilarityTable {
+    neighbors: HashMap<u32, Vec<(u32, f32)>>,
+    num_comparisons: usize,
+}
+
+impl SimilarityTable {
+    /// Compares every pair of items.
+    pub fn exact(items: &[Item], metric: SimilarityAlgos, k: usize) -> Self {
+        let options = TableOptions::default().set_num_neighbors(k);
+        SimilarityTable::from_candidates(items, items, None, metric, &[], &options)
+    }
+
+    /// # Build
+    /// Like [`SimilarityTable::exact`] or [`SimilarityTable::with_blocking`], with
+    /// the popularity of the items removed first and the neighbors filtered.
+    ///
+    /// ## Parameters:
+    /// * `items`: The items, described by the ratings they received.
+    /// * `metric`: The similarity of the items.
+    /// * `options`: The normalization, the blocking and the filters of the table.
+    ///
+    /// ## Returns:
+    /// * The table, with at most `num_neighbors` neighbors per item, or an error if
+    ///   the options are invalid.
+    ///
+    /// ## Examples:
+    /// ```
+    /// use rec_rsys::models::Item;
+    /// use rec_rsys::pairwise::{SimilarityTable, TableOptions};
+    /// use rec_rsys::similarity::SimilarityAlgos;
+    /// let items = vec![
+    ///     Item::new(1, vec![5.0, 4.0, 0.0], None),
+    ///     Item::new(2, vec![4.0, 5.0, 1.0], None),
+    ///     Item::new(3, vec![5.0, 0.0, 0.0], None),
+    /// ];
+    /// let options = TableOptions::default().set_min_overlap(2);
+    /// let table = SimilarityTable::build(&items, SimilarityAlgos::Cosine, &options).unwrap();
+    /// assert_eq!(table.neighbors(1), [(2, table.similarity(1, 2).unwrap())]);
+    /// assert!(table.neighbors(3).is_empty());
+    /// assert_eq!(table.num_entries(), 2);
+    /// ```
+    pub fn build(
+        items: &[Item],
+        metric: SimilarityAlgos,
+        options: &TableOptions,
+    ) -> Result<Self> {
+        options.validate()?;
+        let weights = options.normalization.weights(items);
+        let centered = options.normalization.center(items);
+        // Only the blocking lists the candidates, every pair is compared without.
+        #[cfg(feature = "ann")]
+        let candidates = options
+            .blocking
+            .as_ref()
+            .map(|blocking| blocking.candidates(&centered));
+        #[cfg(not(feature = "ann"))]
+        let candidates: Option<Vec<Vec<usize>>> = None;
+        Ok(SimilarityTable::from_candidates(
+            &centered,
+            items,
+            candidates.as_deref(),
+            metric,
+            &weights,
+            options,
+        ))
+    }
+
+    /// Only compares the items [`LshBlocking`] puts in the same bucket, so some
+    /// neighbors can be missed.
+    #[cfg(feature = "ann")]
+    pub fn with_blocking(
+        items: &[Item],
+        metric: SimilarityAlgos,
+        k: usize,
+        blocking: &LshBlocking,
+    ) -> Result<Self> {
+        blocking.validate()?;
+        let candidates = blocking.candidates(items);
+        let options = TableOptions::default().set_num_neighbors(k);
+        Ok(SimilarityTable::from_candidates(
+            items,
+            items,
+            Some(&candidates),
+            metric,
+            &[],
+            &options,
+        ))
+    }
+
+    /// Compares every item with its candidates, every other item when `None`, the
+    /// similarity of a candidate being damped by its weight, when given. The
+    /// overlaps are counted on the `ratings`, the items before they were centered.
+    fn from_candidates(
+        items: &[Item],
+        ratings: &[Item],
+        candidates: Option<&[Vec<usize>]>,
+        metric: SimilarityAlgos,
+        weights: &[f32],
+        options: &TableOptions,
+    ) -> Self {
+        let neighbors = default_parallelism().install(|| {
+            items
+                .par_iter()
+                .enumerate()
+                .map_init(Scratch::new, |scratch, (row, item)| {
+                    scratch.prepare(metric, &item.values);
+                    let others: Box<dyn Iterator<Item = usize>> = match candidates {
+                        Some(candidates) => Box::new(candidates[row].iter().copied()),
+                        None => Box::new((0..items.len()).filter(move |&i| i != row)),
+                    };
+                    for index in others {
+                        let value =
+                            scratch.compare(metric, &item.values, &items[index].values);
+                        let mut similarity = to_similarity(metric, value);
+                        if let Some(weight) = weights.get(index) {
+                            similarity = damp(similarity, *weight);
+                        }
+                        let overlap = || co_ratings(&ratings[row], &ratings[index]);
+                        if options.keeps(similarity, overlap) {
+                            scratch.scores.push((index, similarity));
+                        }
+                    }
+                    scratch.scores.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+                    let neighbors = scratch
+                        .scores
+                        .iter()
+                        .take(options.num_neighbors)
+                        .map(|(index, similarity)| (items[*index].id, *similarity))
+                        .collect();
+                    (item.id, neighbors)
+                })
+                .collect()
+        });
+        SimilarityTable {
+            neighbors,
+            num_comparisons: candidates
+                .map_or(items.len() * items.len().saturating_sub(1), |candidates| {
+                    candidates.iter().map(Vec::len).sum()
+                }),
+        }
+    }
+
+    /// The most similar items of an item, with their similarity.
+    pub fn neighbors(&self, item_id: u32) -> &[(u32, f32)] {
+        self.neighbors.get(&item_id).map_or(&[], |n| n.as_slice())
+    }
+
+    /// The similarity of two items, when `b` is one of the neighbors of `a`.
+    pub fn similarity(&self, a: u32, b: u32) -> Option<f32> {
+        self.neighbors(a)
+            .iter()
+            .find(|(id, _)| *id == b)
+            .map(|(_, similarity)| *similarity)
+    }
+
+    /// The rating a user would give to an item, from their ratings of its
+    /// neighbors, see [`weighted_rating`].
+    pub fn predict(&self, history: &[(u32, f32)], item_id: u32) -> Option<f32> {
+        weighted_rating(self.neighbors(item_id), history)
+    }
+
+    /// How many similarities were computed to build the table.
+    pub fn num_comparisons(&self) -> usize {
+        self.num_comparisons
+    }
+
+    /// The number of neighbors of all the items, the size of the table.
+    pub fn num_entries(&self) -> usize {
+        self.neighbors.values().map(Vec::len).sum()
+    }
+
+    /// The number of items in the table.
+    pub fn len(&self) -> usize {
+        self.neighbors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.neighbors.is_empty()
+    }
+}
+
+#[cfg(feature = "ann")]
+impl MemoryFootprint for LshBlocking {}
+
+impl MemoryFootprint for PopularityNormalization {}
+
+impl MemoryFootprint for TableOptions {}
+
+impl MemoryFootprint for SimilarityTable {
+    fn heap_size(&self) -> usize {
+        self.neighbors.heap_size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two groups of items pointing in opposite directions.
+    fn items() -> Vec<Item> {
+        (0..40)
+            .map(|id| {
+                let sign = if id % 2 == 0 { 1.0 } else { -1.0 };
+                let noise = id as f32 / 100.0;
+                Item::new(id, vec![sign, sign * 0.5 + noise, sign - noise], None)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_exact() {
+        let table = SimilarityTable::exact(&items(), SimilarityAlgos::Euclidean, 3);
+        assert_eq!(table.len(), 40);
+        assert_eq!(table.num_comparisons(), 40 * 39);
+        assert!(table
+            .neighbors(0)
+            .iter()
+            .all(|(id, similarity)| id % 2 == 0 && *similarity > 0.0));
+        assert!(table.neighbors(0).windows(2).all(|w| w[0].1 >= w[1].1));
+        assert_eq!(table.similarity(0, 1), None);
+        assert!(table.neighbors(100).is_empty());
+    }
+
+    #[cfg(feature = "ann")]
+    #[test]
+    fn test_blocking_never_mixes_opposite_items() {
+        let blocking = LshBlocking::default().set_num_tables(4);
+        let candidates = blocking.candidates(&items());
+        assert!(candidates
+            .iter()
+            .enumerate()
+            .all(|(index, c)| c.iter().all(|i| i % 2 == index % 2 && *i != index)));
+        let table = SimilarityTable::with_blocking(
+            &items(),
+            SimilarityAlgos::Cosine,
+            3,
+            &blocking,
+        )
+        .unwrap();
+        assert!(table.num_comparisons() < 40 * 39);
+        assert!(table.neighbors(1).iter().all(|(id, _)| id % 2 == 1));
+    }
+
+    #[cfg(feature = "ann")]
+    #[test]
+    fn test_blocking_finds_exact_neighbors() {
+        let items = items();
+        let exact = SimilarityTable::exact(&items, SimilarityAlgos::Cosine, 1);
+        let blocking = LshBlocking::default()
+            .set_num_tables(16)
+            .set_num_hyperplanes(2);
+        let blocked =
+            SimilarityTable::with_blocking(&items, SimilarityAlgos::Cosine, 1, &blocking)
+                .unwrap();
+        assert_eq!(exact.neighbors(4), blocked.neighbors(4));
+    }
+
+    #[test]
+    fn test_popularity_normalization() {
+        // Item 0 is rated by every user, items 1 and 2 by the same two users.
+        let items = vec![
+            Item::new(0, vec![5.0, 5.0, 5.0, 5.0], None),
+            Item::new(1, vec![4.0, 2.0, 0.0, 0.0], None),
+            Item::new(2, vec![4.0, 1.0, 0.0, 0.0], None),
+            Item::new(3, vec![0.0, 0.0, 3.0, 0.0], None),
+        ];
+        let build = |normalization| {
+            let options = TableOptions::default()
+                .set_num_neighbors(1)
+                .set_normalization(normalization);
+            SimilarityTable::build(&items, SimilarityAlgos::Cosine, &options).unwrap()
+        };
+        assert_eq!(build(PopularityNormalization::None).neighbors(3)[0].0, 0);
+        let damped = build(PopularityNormalization::Damping { alpha: 1.0 });
+        assert_eq!(damped.neighbors(1)[0].0, 2);
+        assert_eq!(damped.neighbors(3)[0].0, 0);
+        assert!(
+            damped.neighbors(3)[0].1
+                < build(PopularityNormalization::None).neighbors(3)[0].1
+        );
+        // Centered, item 0 has no variation left to be similar with.
+        let centered = build(PopularityNormalization::CenterItems);
+        assert!(centered.neighbors(1).iter().all(|(id, _)| *id != 0));
+        assert_eq!(center_items(&items)[1].values, [1.0, -1.0, 0.0, 0.0]);
+        let invalid = TableOptions::default()
+            .set_normalization(PopularityNormalization::Damping { alpha: -1.0 });
+        assert!(
+            SimilarityTable::build(&items, SimilarityAlgos::Cosine, &invalid).is_err()
+        );
+    }
+
+    #[test]
+    fn test_filters() {
+        let items = items();
+        let options = TableOptions::default().set_num_neighbors(5);
+        let table =
+            SimilarityTable::build(&items, SimilarityAlgos::Cosine, &options).unwrap();
+        assert_eq!(table.num_entries(), 40 * 5);
+        let strict = options.clone().set_min_similarity(0.999);
+        let filtered =
+            SimilarityTable::build(&items, SimilarityAlgos::Cosine, &strict).unwrap();
+        assert!(filtered.num_entries() < table.num_entries());
+        assert!(items.iter().all(|item| filtered
+            .neighbors(item.id)
+            .iter()
+            .all(|(_, similarity)| similarity.abs() >= 0.999)));
+        // Every value of the items is set, so the overlap is their length.
+        let overlapping = options.clone().set_min_overlap(items[0].values.len());
+        let table = SimilarityTable::build(&items, SimilarityAlgos::Cosine, &overlapping)
+            .unwrap();
+        assert_eq!(table.num_entries(), 40 * 5);
+        let disjoint = options.set_min_overlap(items[0].values.len() + 1);
+        let table =
+            SimilarityTable::build(&items, SimilarityAlgos::Cosine, &disjoint).unwrap();
+        assert_eq!(table.num_entries(), 0);
+    }
+
+    #[cfg(feature = "ann")]
+    #[test]
+    fn test_invalid_blocking() {
+        let blocking = LshBlocking::default().set_num_tables(0);
+        assert!(SimilarityTable::with_blocking(
+            &items(),
+            SimilarityAlgos::Cosine,
+            1,
+            &blocking
+        )
+        .is_err());
+    }
+}
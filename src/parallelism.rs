@@ -0,0 +1,192 @@
+//! # Parallelism
+//! Controls on which threads the parallel parts of the crate run, so an application
+//! embedding it can keep some cores for itself, e.g. while retraining in the
+//! background of a server.
+//!
+//! Without the `parallel` feature the crate does not depend on rayon and every
+//! parallelism runs the work sequentially, on the calling thread.
+use std::sync::RwLock;
+#[cfg(feature = "parallel")]
+use std::sync::{Arc, OnceLock};
+
+#[cfg(feature = "parallel")]
+use rayon::{ThreadPool, ThreadPoolBuilder};
+
+use crate::errors::{Error, Result};
+
+/// The parallel iterators used by the crate: the ones of rayon, or the sequential
+/// iterators of the standard library under the same names.
+#[cfg(feature = "parallel")]
+pub(crate) use rayon::prelude;
+
+#[cfg(not(feature = "parallel"))]
+pub(crate) mod prelude {
+    pub trait IntoParallelIterator: IntoIterator + Sized {
+        fn into_par_iter(self) -> Self::IntoIter {
+            self.into_iter()
+        }
+    }
+
+    impl<I: IntoIterator> IntoParallelIterator for I {}
+
+    pub trait IntoParallelRefIterator<'a> {
+        type Iter: Iterator;
+
+        fn par_iter(&'a self) -> Self::Iter;
+    }
+
+    impl<'a, I: 'a + ?Sized> IntoParallelRefIterator<'a> for I
+    where
+        &'a I: IntoIterator,
+    {
+        type Iter = <&'a I as IntoIterator>::IntoIter;
+
+        fn par_iter(&'a self) -> Self::Iter {
+            self.into_iter()
+        }
+    }
+
+    pub trait ParallelIterator: Iterator + Sized {
+        /// A single state, initialized once, as there is a single thread.
+        fn map_init<T, R>(
+            self,
+            init: impl Fn() -> T,
+            map_op: impl Fn(&mut T, Self::Item) -> R,
+        ) -> impl Iterator<Item = R> {
+            let mut state = init();
+            self.map(move |item| map_op(&mut state, item))
+        }
+    }
+
+    impl<I: Iterator> ParallelIterator for I {}
+}
+
+/// Where the parallel work is executed.
+#[derive(Debug, Clone, Default)]
+pub enum Parallelism {
+    /// The global rayon pool, one thread per core unless configured otherwise.
+    #[default]
+    Global,
+    /// A single thread, the work is done sequentially.
+    Sequential,
+    /// A pool owned by the caller.
+    #[cfg(feature = "parallel")]
+    Pool(Arc<ThreadPool>),
+}
+
+static DEFAULT: RwLock<Option<Parallelism>> = RwLock::new(None);
+
+impl Parallelism {
+    /// A dedicated pool with a bounded number of threads, [`Parallelism::Sequential`]
+    /// without the `parallel` feature.
+    pub fn threads(num_threads: usize) -> Result<Self> {
+        if num_threads == 0 {
+            return Err(Error::InvalidConfig(
+                "num_threads must be greater than 0".to_string(),
+            ));
+        }
+        #[cfg(not(feature = "parallel"))]
+        return Ok(Parallelism::Sequential);
+        #[cfg(feature = "parallel")]
+        ThreadPoolBuilder::new()
+            .num_threads(num_threads)
+            .thread_name(|index| format!("rec_rsys-{}", index))
+            .build()
+            .map(|pool| Parallelism::Pool(Arc::new(pool)))
+            .map_err(|error| Error::InvalidConfig(error.to_string()))
+    }
+
+    /// Number of threads the work is spread on.
+    pub fn num_threads(&self) -> usize {
+        match self {
+            #[cfg(feature = "parallel")]
+            Parallelism::Global => rayon::current_num_threads(),
+            #[cfg(not(feature = "parallel"))]
+            Parallelism::Global => 1,
+            Parallelism::Sequential => 1,
+            #[cfg(feature = "parallel")]
+            Parallelism::Pool(pool) => pool.current_num_threads(),
+        }
+    }
+
+    /// Runs `op` so that the parallel iterators it uses execute on this pool.
+    pub fn install<OP, R>(&self, op: OP) -> R
+    where
+        OP: FnOnce() -> R + Send,
+        R: Send,
+    {
+        match self {
+            Parallelism::Global => op(),
+            #[cfg(feature = "parallel")]
+            Parallelism::Sequential => sequential_pool().install(op),
+            #[cfg(not(feature = "parallel"))]
+            Parallelism::Sequential => op(),
+            #[cfg(feature = "parallel")]
+            Parallelism::Pool(pool) => pool.install(op),
+        }
+    }
+}
+
+/// The pool of [`Parallelism::Sequential`], shared by the whole process.
+#[cfg(feature = "parallel")]
+fn sequential_pool() -> &'static ThreadPool {
+    static POOL: OnceLock<ThreadPool> = OnceLock::new();
+    POOL.get_or_init(|| {
+        ThreadPoolBuilder::new()
+            .num_threads(1)
+            .thread_name(|_| "rec_rsys-sequential".to_string())
+            .build()
+            .expect("failed to spawn the sequential thread")
+    })
+}
+
+/// # Set default parallelism
+/// Sets the parallelism used by every operation of the crate that is not given one
+/// explicitly.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::parallelism::{default_parallelism, set_default_parallelism, Parallelism};
+/// set_default_parallelism(Parallelism::threads(2).unwrap());
+/// // A single thread without the `parallel` feature.
+/// assert!(default_parallelism().num_threads() <= 2);
+/// set_default_parallelism(Parallelism::Global);
+/// ```
+pub fn set_default_parallelism(parallelism: Parallelism) {
+    *DEFAULT.write().unwrap_or_else(|e| e.into_inner()) = Some(parallelism);
+}
+
+/// The parallelism set with [`set_default_parallelism`], the global rayon pool if
+/// none was.
+pub fn default_parallelism() -> Parallelism {
+    DEFAULT
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+        .unwrap_or_default()
+}
+
+#[cfg(all(test, feature = "parallel"))]
+mod tests {
+    use super::*;
+    use rayon::prelude::*;
+
+    #[test]
+    fn test_threads() {
+        let parallelism = Parallelism::threads(3).unwrap();
+        assert_eq!(parallelism.num_threads(), 3);
+        assert_eq!(parallelism.install(rayon::current_num_threads), 3);
+        assert!(Parallelism::threads(0).is_err());
+    }
+
+    #[test]
+    fn test_sequential() {
+        let sum: u32 = Parallelism::Sequential.install(|| {
+            (1..=100)
+                .into_par_iter()
+                .map(|_| rayon::current_num_threads() as u32)
+                .sum()
+        });
+        assert_eq!(sum, 100);
+    }
+}
@@ -0,0 +1,393 @@
+//! # Catalog partitions
+//! Serves a catalog split by market or locale with a single model: every item
+//! belongs to the partitions it may be shown in, every user to the partition their
+//! requests are routed to, and the recommendations of a user only hold the items
+//! of their partition, completed if needed by the items of fallback partitions.
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+
+use crate::dataset::Dataset;
+use crate::errors::{Error, Result};
+use crate::memory::MemoryFootprint;
+use crate::recommender::{Recommendation, Recommender};
+use crate::sets::IdSet;
+
+/// The items of a partition and where to look when they are not enough.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Partition {
+    pub items: IdSet,
+    /// The partitions whose items complete the recommendations, in order, e.g. the
+    /// other countries sharing the language.
+    pub fallbacks: Vec<String>,
+}
+
+/// # Item partitions
+/// The items of every partition and the partition of every user.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::partitions::ItemPartitions;
+/// let mut partitions = ItemPartitions::new();
+/// partitions.insert_items("fr-FR", [1, 2, 3]);
+/// partitions.insert_items("fr-BE", [3, 4]);
+/// partitions.set_fallbacks("fr-BE", &["fr-FR"]).unwrap();
+/// partitions.assign_user(7, "fr-BE").unwrap();
+/// assert_eq!(partitions.partition_of(7), Some("fr-BE"));
+/// assert!(partitions.contains("fr-FR", 3));
+/// assert!(!partitions.contains("fr-BE", 1));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ItemPartitions {
+    partitions: BTreeMap<String, Partition>,
+    users: HashMap<u32, String>,
+    /// The partition of the users without one.
+    default_partition: Option<String>,
+}
+
+impl ItemPartitions {
+    pub fn new() -> Self {
+        ItemPartitions::default()
+    }
+
+    /// Adds items to a partition, creating it if needed. An item may belong to
+    /// several partitions.
+    pub fn insert_items<I: IntoIterator<Item = u32>>(
+        &mut self,
+        partition: &str,
+        items: I,
+    ) {
+        self.partitions
+            .entry(partition.to_string())
+            .or_default()
+            .items
+            .extend(items);
+    }
+
+    /// Removes an item from every partition, returns whether it was in one.
+    pub fn remove_item(&mut self, item_id: u32) -> bool {
+        let mut removed = false;
+        for partition in self.partitions.values_mut() {
+            removed |= partition.items.remove(item_id);
+        }
+        removed
+    }
+
+    /// Sets the partitions completing the recommendations of a partition, in order.
+    /// Every partition must exist.
+    pub fn set_fallbacks(&mut self, partition: &str, fallbacks: &[&str]) -> Result<()> {
+        if let Some(unknown) = fallbacks
+            .iter()
+            .find(|f| !self.partitions.contains_key(**f))
+        {
+            return Err(unknown_partition(unknown));
+        }
+        self.partition_mut(partition)?.fallbacks =
+            fallbacks.iter().map(|f| f.to_string()).collect();
+        Ok(())
+    }
+
+    /// Routes the requests of a user to an existing partition.
+    pub fn assign_user(&mut self, user_id: u32, partition: &str) -> Result<()> {
+        self.partition(partition)?;
+        self.users.insert(user_id, partition.to_string());
+        Ok(())
+    }
+
+    /// Routes the requests of the users assigned to no partition, `None` to serve
+    /// them the whole catalog.
+    pub fn set_default_partition(&mut self, partition: Option<&str>) -> Result<()> {
+        if let Some(partition) = partition {
+            self.partition(partition)?;
+        }
+        self.default_partition = partition.map(str::to_string);
+        Ok(())
+    }
+
+    pub fn get(&self, partition: &str) -> Option<&Partition> {
+        self.partitions.get(partition)
+    }
+
+    fn partition(&self, partition: &str) -> Result<&Partition> {
+        self.partitions
+            .get(partition)
+            .ok_or_else(|| unknown_partition(partition))
+    }
+
+    fn partition_mut(&mut self, partition: &str) -> Result<&mut Partition> {
+        self.partitions
+            .get_mut(partition)
+            .ok_or_else(|| unknown_partition(partition))
+    }
+
+    /// The partition the requests of the user are routed to.
+    pub fn partition_of(&self, user_id: u32) -> Option<&str> {
+        self.users
+            .get(&user_id)
+            .or(self.default_partition.as_ref())
+            .map(String::as_str)
+    }
+
+    pub fn contains(&self, partition: &str, item_id: u32) -> bool {
+        self.partitions
+            .get(partition)
+            .is_some_and(|p| p.items.contains(item_id))
+    }
+
+    /// The partition names, sorted.
+    pub fn names(&self) -> impl Iterator<Item = &str> + '_ {
+        self.partitions.keys().map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.partitions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.partitions.is_empty()
+    }
+
+    /// # Select
+    /// Picks the recommendations of a partition out of a ranking.
+    ///
+    /// ## Parameters:
+    /// * `partition`: The partition of the request.
+    /// * `ranked`: The items, best first.
+    /// * `num_items`: The maximum number of items returned.
+    /// * `fallback`: Whether to complete the recommendations with the items of the
+    ///   fallback partitions when the partition does not have enough.
+    ///
+    /// ## Returns:
+    /// * The items of the partition, best first, followed by the items of the
+    ///   fallback partitions, by fallback then best first. An error if the
+    ///   partition does not exist.
+    pub fn select<I: IntoIterator<Item = Recommendation>>(
+        &self,
+        partition: &str,
+        ranked: I,
+        num_items: usize,
+        fallback: bool,
+    ) -> Result<Vec<Recommendation>> {
+        let primary = self.partition(partition)?;
+        let fallbacks: Vec<&Partition> = match fallback {
+            true => primary
+                .fallbacks
+                .iter()
+                .filter_map(|name| self.partitions.get(name))
+                .collect(),
+            false => Vec::new(),
+        };
+        let mut selected = Vec::new();
+        let mut completions: Vec<Vec<Recommendation>> = vec![Vec::new(); fallbacks.len()];
+        for recommendation in ranked {
+            if selected.len() == num_items {
+                break;
+            }
+            if primary.items.contains(recommendation.item_id) {
+                selected.push(recommendation);
+            } else if let Some(index) = fallbacks
+                .iter()
+                .position(|p| p.items.contains(recommendation.item_id))
+            {
+                if completions[index].len() < num_items {
+                    completions[index].push(recommendation);
+                }
+            }
+        }
+        let missing = num_items - selected.len();
+        selected.extend(completions.into_iter().flatten().take(missing));
+        Ok(selected)
+    }
+}
+
+fn unknown_partition(partition: &str) -> Error {
+    Error::InvalidData(format!("unknown partition {}", partition))
+}
+
+/// # Partitioned recommender
+/// A model trained on every partition whose recommendations are routed to the
+/// partition of the user, see [`ItemPartitions::partition_of`]. The users routed
+/// nowhere are served the recommendations of the model as they are.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::algorithms::most_popular::MostPopular;
+/// use rec_rsys::dataset::{Dataset, Rating};
+/// use rec_rsys::partitions::{ItemPartitions, Partitioned};
+/// use rec_rsys::recommender::Recommender;
+/// let mut partitions = ItemPartitions::new();
+/// partitions.insert_items("es", [10, 11]);
+/// partitions.insert_items("pt", [12]);
+/// partitions.set_fallbacks("pt", &["es"]).unwrap();
+/// partitions.assign_user(1, "pt").unwrap();
+///
+/// let mut model = Partitioned::new(MostPopular::default(), partitions);
+/// model.fit(&Dataset::new(vec![
+///     Rating::new(2, 10, 1.0), Rating::new(3, 10, 1.0), Rating::new(3, 11, 1.0),
+///     Rating::new(4, 12, 1.0),
+/// ])).unwrap();
+/// let ids: Vec<u32> = model.recommend(1, 2).iter().map(|r| r.item_id).collect();
+/// assert_eq!(ids, [12, 10]);
+/// let model = model.set_fallback(false);
+/// assert_eq!(model.recommend(1, 2).len(), 1);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Partitioned<R> {
+    model: R,
+    partitions: ItemPartitions,
+    fallback: bool,
+}
+
+impl<R: Recommender> Partitioned<R> {
+    /// Routes the recommendations of the model, completed by the fallback
+    /// partitions.
+    pub fn new(model: R, partitions: ItemPartitions) -> Self {
+        Partitioned {
+            model,
+            partitions,
+            fallback: true,
+        }
+    }
+
+    pub fn set_fallback(mut self, fallback: bool) -> Self {
+        self.fallback = fallback;
+        self
+    }
+
+    pub fn model(&self) -> &R {
+        &self.model
+    }
+
+    pub fn partitions(&self) -> &ItemPartitions {
+        &self.partitions
+    }
+
+    /// The partitions, to add items or users while serving.
+    pub fn partitions_mut(&mut self) -> &mut ItemPartitions {
+        &mut self.partitions
+    }
+
+    /// Recommends the items of a given partition, e.g. the market of the site a
+    /// request comes from, instead of the partition of the user.
+    pub fn recommend_in(
+        &self,
+        partition: &str,
+        user_id: u32,
+        num_items: usize,
+    ) -> Result<Vec<Recommendation>> {
+        self.partitions.select(
+            partition,
+            self.model.recommend_iter(user_id),
+            num_items,
+            self.fallback,
+        )
+    }
+}
+
+impl<R: Recommender> Recommender for Partitioned<R> {
+    fn fit(&mut self, dataset: &Dataset) -> Result<()> {
+        self.model.fit(dataset)
+    }
+
+    fn recommend(&self, user_id: u32, num_items: usize) -> Vec<Recommendation> {
+        match self.partitions.partition_of(user_id) {
+            Some(partition) => self
+                .recommend_in(partition, user_id, num_items)
+                .unwrap_or_default(),
+            None => self.model.recommend(user_id, num_items),
+        }
+    }
+
+    /// Only the items of the partition of the user are estimated.
+    fn predict(&self, user_id: u32, item_id: u32) -> Option<f32> {
+        match self.partitions.partition_of(user_id) {
+            Some(partition) if !self.partitions.contains(partition, item_id) => None,
+            _ => self.model.predict(user_id, item_id),
+        }
+    }
+}
+
+impl MemoryFootprint for Partition {
+    fn heap_size(&self) -> usize {
+        self.items.heap_size() + self.fallbacks.heap_size()
+    }
+}
+
+impl MemoryFootprint for ItemPartitions {
+    fn heap_size(&self) -> usize {
+        self.partitions.heap_size()
+            + self.users.heap_size()
+            + self.default_partition.heap_size()
+    }
+}
+
+impl<R: MemoryFootprint> MemoryFootprint for Partitioned<R> {
+    fn heap_size(&self) -> usize {
+        self.model.heap_size() + self.partitions.heap_size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ranked(ids: &[u32]) -> Vec<Recommendation> {
+        ids.iter()
+            .enumerate()
+            .map(|(rank, &item_id)| Recommendation {
+                item_id,
+                score: -(rank as f32),
+            })
+            .collect()
+    }
+
+    fn partitions() -> ItemPartitions {
+        let mut partitions = ItemPartitions::new();
+        partitions.insert_items("a", [1, 2]);
+        partitions.insert_items("b", [3, 4, 2]);
+        partitions.insert_items("c", [5, 6]);
+        partitions.set_fallbacks("a", &["c", "b"]).unwrap();
+        partitions
+    }
+
+    #[test]
+    fn test_select() {
+        let partitions = partitions();
+        let ids = |selected: Vec<Recommendation>| -> Vec<u32> {
+            selected.iter().map(|r| r.item_id).collect()
+        };
+        let ranking = ranked(&[4, 6, 2, 3, 5, 1]);
+        let select = |partition, num_items, fallback| {
+            ids(partitions
+                .select(partition, ranking.clone(), num_items, fallback)
+                .unwrap())
+        };
+        assert_eq!(select("a", 1, true), [2]);
+        assert_eq!(select("a", 5, true), [2, 1, 6, 5, 4]);
+        assert_eq!(select("a", 5, false), [2, 1]);
+        assert_eq!(select("b", 5, true), [4, 2, 3]);
+        assert!(partitions.select("d", ranking, 5, true).is_err());
+    }
+
+    #[test]
+    fn test_routing() {
+        let mut partitions = partitions();
+        assert!(partitions.set_fallbacks("b", &["d"]).is_err());
+        assert!(partitions.assign_user(1, "d").is_err());
+        partitions.assign_user(1, "b").unwrap();
+        assert_eq!(partitions.partition_of(1), Some("b"));
+        assert_eq!(partitions.partition_of(2), None);
+        partitions.set_default_partition(Some("c")).unwrap();
+        assert_eq!(partitions.partition_of(2), Some("c"));
+
+        assert!(partitions.remove_item(2));
+        assert!(!partitions.contains("a", 2) && !partitions.contains("b", 2));
+        assert!(!partitions.remove_item(2));
+        assert_eq!(partitions.names().collect::<Vec<&str>>(), ["a", "b", "c"]);
+
+        let json = serde_json::to_string(&partitions).unwrap();
+        let restored: ItemPartitions = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored, partitions);
+    }
+}
@@ -0,0 +1,418 @@
+//! # Pipeline snapshots
+//! The whole state a recommendation service needs, its models, their
+//! configuration, the id maps and the metadata of its caches, saved as a single
+//! archive. A new state is written next to the live one and swapped in at once,
+//! and every entry carries a hash checked when restoring, so a server only ever
+//! loads a complete and intact state: blue/green deployments of the state are a
+//! [`Pipeline::snapshot`] followed by a [`Pipeline::restore`].
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::envelope::{ModelEnvelope, PersistedModel, FORMAT_VERSION};
+use crate::errors::{Error, Result};
+use crate::evaluation::runs::{build_version, dataset_hash};
+use crate::health::{self, HealthReport, SelfTestConfig};
+use crate::memory::MemoryFootprint;
+use crate::recommender::Recommender;
+
+/// What an entry of a [`Pipeline`] holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntryKind {
+    /// A [`ModelEnvelope`].
+    Model,
+    /// Any other serializable value: a configuration, an id map, cache metadata...
+    Data,
+}
+
+/// An entry of a [`Pipeline`], serialized as JSON.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PipelineEntry {
+    pub kind: EntryKind,
+    /// See [`PersistedModel::MODEL_TYPE`], for the models.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model_type: Option<String>,
+    /// The [`dataset_hash`] of the content.
+    pub hash: String,
+    content: String,
+}
+
+impl PipelineEntry {
+    fn new(kind: EntryKind, model_type: Option<&str>, content: String) -> Self {
+        PipelineEntry {
+            kind,
+            model_type: model_type.map(str::to_string),
+            hash: dataset_hash(content.as_bytes()),
+            content,
+        }
+    }
+
+    /// The JSON of the value, as it was inserted.
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+}
+
+/// # Pipeline
+/// Named entries, models or plain data, saved together with
+/// [`Pipeline::snapshot`].
+///
+/// ## Examples:
+/// ```
+/// use std::collections::HashMap;
+/// use rec_rsys::algorithms::knn::KNNConfig;
+/// use rec_rsys::algorithms::pca::PCA;
+/// use rec_rsys::envelope::ModelEnvelope;
+/// use rec_rsys::pipeline::Pipeline;
+/// let pca = PCA::fit(&[vec![1.0, 2.0], vec![2.0, 4.1], vec![3.0, 6.2]], 1).unwrap();
+/// let mut pipeline = Pipeline::new();
+/// pipeline.insert_model("embeddings", &ModelEnvelope::new(pca)).unwrap();
+/// pipeline.insert("knn", &KNNConfig::default().set_num_neighbors(20)).unwrap();
+/// pipeline.insert("item_ids", &HashMap::from([("sku-1".to_string(), 0_u32)])).unwrap();
+///
+/// let path = std::env::temp_dir().join("rec_rsys_doc_pipeline.json");
+/// pipeline.snapshot(&path).unwrap();
+/// let restored = Pipeline::restore(&path).unwrap();
+/// let config: KNNConfig = restored.get("knn").unwrap();
+/// assert_eq!(config.num_neighbors, Some(20));
+/// assert_eq!(restored.model::<PCA>("embeddings").unwrap().model_type, "pca");
+/// # std::fs::remove_file(path).unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Pipeline {
+    /// See [`FORMAT_VERSION`].
+    pub format_version: u32,
+    /// Version of the crate that saved the pipeline.
+    #[serde(default)]
+    pub crate_version: String,
+    /// Seconds since the unix epoch when the pipeline was created.
+    #[serde(default)]
+    pub created_at: u64,
+    entries: BTreeMap<String, PipelineEntry>,
+    /// Hash of the names and the hashes of every entry, so removed or added entries
+    /// are detected as well as altered ones.
+    checksum: String,
+}
+
+impl Default for Pipeline {
+    fn default() -> Self {
+        Pipeline::new()
+    }
+}
+
+impl Pipeline {
+    pub fn new() -> Self {
+        let mut pipeline = Pipeline {
+            format_version: FORMAT_VERSION,
+            crate_version: build_version(),
+            created_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs()),
+            entries: BTreeMap::new(),
+            checksum: String::new(),
+        };
+        pipeline.checksum = pipeline.compute_checksum();
+        pipeline
+    }
+
+    fn compute_checksum(&self) -> String {
+        let manifest: String = self
+            .entries
+            .iter()
+            .map(|(name, entry)| format!("{}:{}\n", name, entry.hash))
+            .collect();
+        dataset_hash(manifest.as_bytes())
+    }
+
+    fn insert_entry(&mut self, name: &str, entry: PipelineEntry) {
+        self.entries.insert(name.to_string(), entry);
+        self.checksum = self.compute_checksum();
+    }
+
+    /// Adds or replaces a model.
+    pub fn insert_model<T: PersistedModel>(
+        &mut self,
+        name: &str,
+        envelope: &ModelEnvelope<T>,
+    ) -> Result<()> {
+        let entry = PipelineEntry::new(
+            EntryKind::Model,
+            Some(T::MODEL_TYPE),
+            envelope.to_json()?,
+        );
+        self.insert_entry(name, entry);
+        Ok(())
+    }
+
+    /// Adds or replaces any other value, e.g. a configuration or an id map.
+    pub fn insert<T: Serialize>(&mut self, name: &str, value: &T) -> Result<()> {
+        let entry =
+            PipelineEntry::new(EntryKind::Data, None, serde_json::to_string(value)?);
+        self.insert_entry(name, entry);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<PipelineEntry> {
+        let entry = self.entries.remove(name);
+        self.checksum = self.compute_checksum();
+        entry
+    }
+
+    pub fn entry(&self, name: &str) -> Option<&PipelineEntry> {
+        self.entries.get(name)
+    }
+
+    /// The names of the entries, sorted.
+    pub fn names(&self) -> impl Iterator<Item = &str> + '_ {
+        self.entries.keys().map(String::as_str)
+    }
+
+    fn content(&self, name: &str, kind: EntryKind) -> Result<&str> {
+        match self.entries.get(name) {
+            Some(entry) if entry.kind == kind => Ok(&entry.content),
+            Some(entry) => Err(Error::InvalidData(format!(
+                "pipeline entry {} is a {:?} entry, not a {:?} one",
+                name, entry.kind, kind
+            ))),
+            None => Err(Error::InvalidData(format!("no pipeline entry {}", name))),
+        }
+    }
+
+    /// Reads a model, checking its type, see [`ModelEnvelope::from_json`].
+    pub fn model<T: PersistedModel>(&self, name: &str) -> Result<ModelEnvelope<T>> {
+        ModelEnvelope::from_json(self.content(name, EntryKind::Model)?)
+    }
+
+    /// Reads a value inserted with [`Pipeline::insert`].
+    pub fn get<T: DeserializeOwned>(&self, name: &str) -> Result<T> {
+        Ok(serde_json::from_str(self.content(name, EntryKind::Data)?)?)
+    }
+
+    /// Checks the hash of every entry and the checksum of the whole pipeline.
+    pub fn verify(&self) -> Result<()> {
+        for (name, entry) in &self.entries {
+            if dataset_hash(entry.content.as_bytes()) != entry.hash {
+                return Err(Error::InvalidData(format!(
+                    "pipeline entry {} does not match its hash {}",
+                    name, entry.hash
+                )));
+            }
+        }
+        if self.compute_checksum() != self.checksum {
+            return Err(Error::InvalidData(
+                "the pipeline entries do not match its checksum".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// # Self-test
+    /// Checks the integrity of the pipeline, loads one of its models and runs the
+    /// canary queries of [`health::self_test`] against it, e.g. before swapping a
+    /// restored pipeline in.
+    ///
+    /// ## Parameters:
+    /// * `name`: The entry of the model.
+    /// * `config`: The canaries and the thresholds.
+    ///
+    /// ## Returns:
+    /// * The report, failed if the pipeline is corrupted or the model can not be
+    ///   loaded.
+    ///
+    /// ## Examples:
+    /// ```
+    /// use rec_rsys::algorithms::item_knn::ItemKNN;
+    /// use rec_rsys::algorithms::knn::KNNConfig;
+    /// use rec_rsys::dataset::{Dataset, Rating};
+    /// use rec_rsys::envelope::ModelEnvelope;
+    /// use rec_rsys::health::SelfTestConfig;
+    /// use rec_rsys::pipeline::Pipeline;
+    /// use rec_rsys::recommender::Recommender;
+    /// let mut model = ItemKNN::new(KNNConfig::default());
+    /// model.fit(&Dataset::new(vec![
+    ///     Rating::new(1, 10, 5.0), Rating::new(1, 11, 4.0), Rating::new(2, 10, 5.0),
+    /// ])).unwrap();
+    /// let mut pipeline = Pipeline::new();
+    /// pipeline.insert_model("item_knn", &ModelEnvelope::new(model)).unwrap();
+    /// let config = SelfTestConfig::default().set_canary_users(vec![2]);
+    /// assert!(pipeline.self_test::<ItemKNN>("item_knn", &config).is_healthy());
+    /// assert!(!pipeline.self_test::<ItemKNN>("missing", &config).is_healthy());
+    /// ```
+    pub fn self_test<T: PersistedModel + Recommender>(
+        &self,
+        name: &str,
+        config: &SelfTestConfig,
+    ) -> HealthReport {
+        let model = self.verify().and_then(|_| self.model::<T>(name));
+        match model {
+            Ok(envelope) => health::self_test(envelope.model(), config),
+            Err(error) => HealthReport::failed(error.to_string()),
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    /// Reads a pipeline, checking its format version and its integrity.
+    pub fn from_json(content: &str) -> Result<Self> {
+        let pipeline: Pipeline = serde_json::from_str(content)?;
+        if pipeline.format_version > FORMAT_VERSION {
+            return Err(Error::InvalidData(format!(
+                "pipeline written with format version {} by rec_rsys {}, this version \
+                 reads up to format version {}",
+                pipeline.format_version, pipeline.crate_version, FORMAT_VERSION
+            )));
+        }
+        pipeline.verify()?;
+        Ok(pipeline)
+    }
+
+    /// Saves the pipeline to a file. It is first written next to the file then
+    /// renamed over it, so readers see the previous snapshot or the new one but
+    /// never a partly written file, even after a crash, see [`write_atomically`].
+    pub fn snapshot<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        Ok(write_atomically(path.as_ref(), self.to_json()?.as_bytes())?)
+    }
+
+    /// Loads a pipeline saved with [`Pipeline::snapshot`], see
+    /// [`Pipeline::from_json`].
+    pub fn restore<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Pipeline::from_json(&fs::read_to_string(path)?)
+    }
+}
+
+/// Writes a file next to `path` then renames it over `path`. The new file is
+/// flushed to disk before the rename, and on unix the directory after it, so a
+/// crash leaves either the previous content or the new one, never an empty or
+/// partial file.
+pub(crate) fn write_atomically(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut staging = path.as_os_str().to_owned();
+    staging.push(".tmp");
+    let mut file = File::create(&staging)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    drop(file);
+    fs::rename(&staging, path)?;
+    #[cfg(unix)]
+    {
+        let directory = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        File::open(directory)?.sync_all()?;
+    }
+    Ok(())
+}
+
+impl MemoryFootprint for EntryKind {}
+
+impl MemoryFootprint for PipelineEntry {
+    fn heap_size(&self) -> usize {
+        self.model_type.heap_size() + self.hash.heap_size() + self.content.heap_size()
+    }
+}
+
+impl MemoryFootprint for Pipeline {
+    fn heap_size(&self) -> usize {
+        self.crate_version.heap_size()
+            + self.entries.heap_size()
+            + self.checksum.heap_size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::Value;
+
+    use super::*;
+    use crate::algorithms::item_knn::ItemKNN;
+    use crate::algorithms::knn::KNNConfig;
+    use crate::algorithms::pca::PCA;
+    use crate::dataset::{Dataset, Rating};
+    use crate::recommender::Recommender;
+
+    fn pipeline() -> Pipeline {
+        let mut model = ItemKNN::new(KNNConfig::default().set_num_neighbors(2));
+        model
+            .fit(&Dataset::new(vec![
+                Rating::new(1, 10, 5.0),
+                Rating::new(1, 11, 3.0),
+                Rating::new(2, 10, 4.0),
+            ]))
+            .unwrap();
+        let mut pipeline = Pipeline::new();
+        pipeline
+            .insert_model("item_knn", &ModelEnvelope::new(model))
+            .unwrap();
+        pipeline
+            .insert("user_ids", &HashMap::from([(7_u32, 1_u32), (8, 2)]))
+            .unwrap();
+        pipeline
+    }
+
+    #[test]
+    fn test_snapshot_restore() {
+        let pipeline = pipeline();
+        let path = std::env::temp_dir().join("rec_rsys_test_pipeline.json");
+        pipeline.snapshot(&path).unwrap();
+        let restored = Pipeline::restore(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(restored, pipeline);
+        assert_eq!(
+            restored.names().collect::<Vec<_>>(),
+            ["item_knn", "user_ids"]
+        );
+        let model = restored.model::<ItemKNN>("item_knn").unwrap().into_model();
+        assert_eq!(model.recommend(2, 1)[0].item_id, 11);
+        let ids: HashMap<u32, u32> = restored.get("user_ids").unwrap();
+        assert_eq!(ids[&8], 2);
+
+        assert!(restored.model::<PCA>("item_knn").is_err());
+        assert!(restored.get::<HashMap<u32, u32>>("item_knn").is_err());
+        assert!(restored.get::<u32>("missing").is_err());
+    }
+
+    #[test]
+    fn test_write_atomically() {
+        let path = std::env::temp_dir().join("rec_rsys_test_atomic.json");
+        write_atomically(&path, b"first").unwrap();
+        write_atomically(&path, b"second").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"second");
+        assert!(!path.with_extension("json.tmp").exists());
+        fs::remove_file(&path).unwrap();
+        let missing = std::env::temp_dir().join("rec_rsys_missing_dir/state.json");
+        assert!(write_atomically(&missing, b"state").is_err());
+    }
+
+    #[test]
+    fn test_integrity() {
+        let mut value = serde_json::to_value(pipeline()).unwrap();
+        let mut altered = value.clone();
+        altered["entries"]["user_ids"]["content"] = Value::from("{\"7\":3,\"8\":2}");
+        let error = Pipeline::from_json(&altered.to_string()).unwrap_err();
+        assert!(error.to_string().contains("user_ids"));
+
+        let mut removed = value.clone();
+        removed["entries"]
+            .as_object_mut()
+            .unwrap()
+            .remove("user_ids");
+        assert!(Pipeline::from_json(&removed.to_string()).is_err());
+
+        value["format_version"] = Value::from(FORMAT_VERSION + 1);
+        assert!(Pipeline::from_json(&value.to_string()).is_err());
+
+        let mut pipeline = pipeline();
+        assert!(pipeline.remove("user_ids").is_some());
+        assert!(pipeline.verify().is_ok());
+    }
+}
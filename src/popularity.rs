@@ -0,0 +1,277 @@
+//! # Item popularity
+//! Per-item statistics of the interactions: how often an item was rated in a time
+//! window, how fast it is trending and its average rating shrunk towards the global
+//! mean. They can rank items on their own, with [`PopularityRecommender`], or be
+//! used as features of hybrid models.
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::dataset::Dataset;
+use crate::errors::{Error, Result};
+use crate::memory::MemoryFootprint;
+use crate::recommender::{sort_ranked, unseen, RankedItems, Recommendation, Recommender};
+use crate::sets::IdSet;
+
+/// # Windowed counts
+/// Counts the interactions of every item with a timestamp in `[start, end)`.
+/// Interactions without timestamp are skipped.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::dataset::{Dataset, Rating};
+/// use rec_rsys::popularity::windowed_counts;
+/// let dataset = Dataset::new(vec![
+///     Rating::new(1, 10, 1.0).timestamp(5),
+///     Rating::new(2, 10, 1.0).timestamp(15),
+///     Rating::new(3, 11, 1.0).timestamp(12),
+/// ]);
+/// let counts = windowed_counts(&dataset, 10, 20);
+/// assert_eq!(counts[&10], 1);
+/// assert_eq!(counts[&11], 1);
+/// ```
+pub fn windowed_counts(dataset: &Dataset, start: u64, end: u64) -> HashMap<u32, usize> {
+    let mut counts = HashMap::new();
+    dataset
+        .ratings
+        .iter()
+        .filter(|r| r.timestamp.is_some_and(|t| (start..end).contains(&t)))
+        .for_each(|r| *counts.entry(r.item_id).or_default() += 1);
+    counts
+}
+
+/// # Trending scores
+/// Compares the interactions of every item in the last `window` seconds before
+/// `now` with the ones of the window before. The score is the relative growth,
+/// smoothed by one interaction so items appearing from nothing do not dominate.
+///
+/// ## Parameters:
+/// * `dataset`: The interactions, with timestamps.
+/// * `now`: The end of the recent window.
+/// * `window`: The length of each window, in seconds.
+///
+/// ## Returns:
+/// * The growth of every item seen in one of the windows, `0` being stable.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::dataset::{Dataset, Rating};
+/// use rec_rsys::popularity::trending_scores;
+/// let dataset = Dataset::new(vec![
+///     Rating::new(1, 10, 1.0).timestamp(1),
+///     Rating::new(2, 11, 1.0).timestamp(11),
+///     Rating::new(3, 11, 1.0).timestamp(12),
+/// ]);
+/// let trending = trending_scores(&dataset, 20, 10);
+/// assert_eq!(trending[&11], 2.0);
+/// assert_eq!(trending[&10], -0.5);
+/// ```
+#[doc = include_str!("../docs/popularity/trending.md")]
+pub fn trending_scores(dataset: &Dataset, now: u64, window: u64) -> HashMap<u32, f32> {
+    let recent = windowed_counts(dataset, now.saturating_sub(window), now);
+    let previous = windowed_counts(
+        dataset,
+        now.saturating_sub(2 * window),
+        now.saturating_sub(window),
+    );
+    recent
+        .keys()
+        .chain(previous.keys())
+        .map(|item_id| {
+            let recent = *recent.get(item_id).unwrap_or(&0) as f32;
+            let previous = *previous.get(item_id).unwrap_or(&0) as f32;
+            (*item_id, (recent - previous) / (previous + 1.0))
+        })
+        .collect()
+}
+
+/// # Bayesian average
+/// The average rating of every item, shrunk towards the global mean as if every
+/// item had `prior_weight` extra ratings equal to it. Items with few ratings are
+/// not ranked first by a single enthusiastic user.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::dataset::{Dataset, Rating};
+/// use rec_rsys::popularity::bayesian_average;
+/// let dataset = Dataset::new(vec![
+///     Rating::new(1, 10, 5.0),
+///     Rating::new(1, 11, 5.0), Rating::new(2, 11, 5.0), Rating::new(3, 11, 5.0),
+///     Rating::new(2, 12, 1.0),
+/// ]);
+/// let averages = bayesian_average(&dataset, 2.0);
+/// assert!(averages[&11] > averages[&10]);
+/// ```
+#[doc = include_str!("../docs/popularity/bayesian_average.md")]
+pub fn bayesian_average(dataset: &Dataset, prior_weight: f32) -> HashMap<u32, f32> {
+    if dataset.is_empty() {
+        return HashMap::new();
+    }
+    let global_mean =
+        dataset.ratings.iter().map(|r| r.rating).sum::<f32>() / dataset.len() as f32;
+    let mut sums: HashMap<u32, (f32, f32)> = HashMap::new();
+    dataset.ratings.iter().for_each(|r| {
+        let (sum, count) = sums.entry(r.item_id).or_default();
+        *sum += r.rating;
+        *count += 1.0;
+    });
+    sums.into_iter()
+        .map(|(item_id, (sum, count))| {
+            let average = (prior_weight * global_mean + sum) / (prior_weight + count);
+            (item_id, average)
+        })
+        .collect()
+}
+
+/// How [`PopularityRecommender`] scores the items.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PopularityScore {
+    /// Interactions in the last `window` seconds of the dataset.
+    Recent { window: u64 },
+    /// Growth over the last `window` seconds of the dataset, see [`trending_scores`].
+    Trending { window: u64 },
+    /// Average rating shrunk towards the global mean, see [`bayesian_average`].
+    BayesianAverage { prior_weight: f32 },
+}
+
+/// # Popularity recommender
+/// Recommends the items with the best popularity score the user has not rated yet.
+/// The time windows end at the latest timestamp of the training dataset.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::dataset::{Dataset, Rating};
+/// use rec_rsys::popularity::{PopularityRecommender, PopularityScore};
+/// use rec_rsys::recommender::Recommender;
+/// let dataset = Dataset::new(vec![
+///     Rating::new(1, 10, 1.0).timestamp(1),
+///     Rating::new(2, 10, 1.0).timestamp(2),
+///     Rating::new(2, 11, 1.0).timestamp(99),
+///     Rating::new(3, 11, 1.0).timestamp(100),
+/// ]);
+/// let mut model = PopularityRecommender::new(PopularityScore::Trending { window: 50 });
+/// model.fit(&dataset).unwrap();
+/// assert_eq!(model.recommend(1, 1)[0].item_id, 11);
+/// ```
+#[derive(Debug, Clone)]
+pub struct PopularityRecommender {
+    score: PopularityScore,
+    scores: HashMap<u32, f32>,
+    /// The scored items, best first.
+    ranked: Vec<Recommendation>,
+    seen: HashMap<u32, IdSet>,
+}
+
+impl PopularityRecommender {
+    pub fn new(score: PopularityScore) -> Self {
+        PopularityRecommender {
+            score,
+            scores: HashMap::new(),
+            ranked: Vec::new(),
+            seen: HashMap::new(),
+        }
+    }
+
+    /// The score of every item of the training dataset.
+    pub fn scores(&self) -> &HashMap<u32, f32> {
+        &self.scores
+    }
+}
+
+impl Recommender for PopularityRecommender {
+    fn fit(&mut self, dataset: &Dataset) -> Result<()> {
+        let now = dataset.ratings.iter().filter_map(|r| r.timestamp).max();
+        self.scores = match (self.score, now) {
+            (PopularityScore::BayesianAverage { prior_weight }, _) => {
+                bayesian_average(dataset, prior_weight)
+            },
+            (PopularityScore::Recent { window }, Some(now)) => {
+                windowed_counts(dataset, now.saturating_sub(window), now + 1)
+                    .into_iter()
+                    .map(|(item_id, count)| (item_id, count as f32))
+                    .collect()
+            },
+            (PopularityScore::Trending { window }, Some(now)) => {
+                trending_scores(dataset, now + 1, window)
+            },
+            (_, None) => {
+                return Err(Error::InvalidData(
+                    "time windows need ratings with timestamps".to_string(),
+                ))
+            },
+        };
+        self.ranked = sort_ranked(self.scores.clone());
+        self.seen = dataset.user_item_sets();
+        Ok(())
+    }
+
+    fn recommend(&self, user_id: u32, num_items: usize) -> Vec<Recommendation> {
+        unseen(&self.ranked, self.seen.get(&user_id))
+            .take(num_items)
+            .collect()
+    }
+
+    fn recommend_iter(&self, user_id: u32) -> RankedItems {
+        unseen(&self.ranked, self.seen.get(&user_id)).collect()
+    }
+}
+
+impl MemoryFootprint for PopularityRecommender {
+    fn heap_size(&self) -> usize {
+        self.scores.heap_size() + self.ranked.heap_size() + self.seen.heap_size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dataset::Rating;
+
+    fn dataset() -> Dataset {
+        Dataset::new(vec![
+            Rating::new(1, 10, 5.0).timestamp(0),
+            Rating::new(2, 10, 4.0).timestamp(5),
+            Rating::new(3, 10, 3.0).timestamp(8),
+            Rating::new(1, 11, 2.0).timestamp(12),
+            Rating::new(2, 11, 2.0).timestamp(15),
+            Rating::new(3, 11, 2.0).timestamp(19),
+            Rating::new(4, 12, 5.0),
+        ])
+    }
+
+    #[test]
+    fn test_windowed_counts() {
+        let counts = windowed_counts(&dataset(), 5, 15);
+        assert_eq!(counts, HashMap::from([(10, 2), (11, 1)]));
+    }
+
+    #[test]
+    fn test_trending_scores() {
+        let trending = trending_scores(&dataset(), 20, 10);
+        assert_eq!(trending[&11], 3.0);
+        assert_eq!(trending[&10], -0.75);
+        assert!(!trending.contains_key(&12));
+    }
+
+    #[test]
+    fn test_bayesian_average() {
+        let averages = bayesian_average(&dataset(), 0.0);
+        assert_eq!(averages[&10], 4.0);
+        assert_eq!(averages[&12], 5.0);
+        let shrunk = bayesian_average(&dataset(), 20.0);
+        assert!(shrunk[&12] < shrunk[&10]);
+        assert!(bayesian_average(&Dataset::default(), 1.0).is_empty());
+    }
+
+    #[test]
+    fn test_recommender() {
+        let mut model =
+            PopularityRecommender::new(PopularityScore::Recent { window: 10 });
+        model.fit(&dataset()).unwrap();
+        assert_eq!(model.scores()[&11], 3.0);
+        assert_eq!(model.recommend(4, 1)[0].item_id, 11);
+        let without_timestamps = Dataset::new(vec![Rating::new(1, 10, 1.0)]);
+        assert!(model.fit(&without_timestamps).is_err());
+    }
+}
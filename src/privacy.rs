@@ -0,0 +1,362 @@
+//! # Differential privacy
+//! Noise mechanisms that make the outputs of a model differentially private, and
+//! an accountant tracking how much of the privacy budget every release spends.
+//! The noise is calibrated to the sensitivity of the released values: how much a
+//! single rating can change them.
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::errors::{Error, Result};
+use crate::recommender::Recommendation;
+
+/// How the noise is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum NoiseMechanism {
+    /// Pure ε-differential privacy, for a sensitivity measured with the L1 norm.
+    Laplace,
+    /// (ε, δ)-differential privacy, for a sensitivity measured with the L2 norm.
+    Gaussian { delta: f64 },
+}
+
+impl NoiseMechanism {
+    /// The δ spent by each release, 0 for the Laplace mechanism.
+    pub fn delta(&self) -> f64 {
+        match self {
+            NoiseMechanism::Laplace => 0.0,
+            NoiseMechanism::Gaussian { delta } => *delta,
+        }
+    }
+
+    /// The scale of the Laplace noise or the standard deviation of the Gaussian
+    /// noise needed to release values of the given sensitivity with `epsilon`.
+    ///
+    /// ## Examples:
+    /// ```
+    /// use rec_rsys::privacy::NoiseMechanism;
+    /// assert_eq!(NoiseMechanism::Laplace.scale(1.0, 0.5), 2.0);
+    /// ```
+    pub fn scale(&self, sensitivity: f32, epsilon: f64) -> f32 {
+        match self {
+            NoiseMechanism::Laplace => (sensitivity as f64 / epsilon) as f32,
+            NoiseMechanism::Gaussian { delta } => {
+                (sensitivity as f64 * (2.0 * (1.25 / delta).ln()).sqrt() / epsilon) as f32
+            },
+        }
+    }
+
+    /// The norm the sensitivity is measured with.
+    pub fn norm(&self, values: &[f32]) -> f32 {
+        match self {
+            NoiseMechanism::Laplace => values.iter().map(|v| v.abs()).sum(),
+            NoiseMechanism::Gaussian { .. } => {
+                values.iter().map(|v| v * v).sum::<f32>().sqrt()
+            },
+        }
+    }
+
+    /// Draws one noise value of the given scale.
+    pub fn sample<R: Rng + ?Sized>(&self, scale: f32, rng: &mut R) -> f32 {
+        match self {
+            NoiseMechanism::Laplace => {
+                // Inverse of the cumulative distribution function.
+                let u: f32 = rng.gen_range(-0.5..0.5);
+                -scale * u.signum() * (1.0 - 2.0 * u.abs()).max(f32::MIN_POSITIVE).ln()
+            },
+            NoiseMechanism::Gaussian { .. } => {
+                let normal: f32 = rng.sample(rand_distr::StandardNormal);
+                scale * normal
+            },
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            NoiseMechanism::Laplace => Ok(()),
+            NoiseMechanism::Gaussian { delta } if *delta > 0.0 && *delta < 1.0 => Ok(()),
+            NoiseMechanism::Gaussian { .. } => Err(Error::InvalidConfig(
+                "delta must be between 0 and 1".to_string(),
+            )),
+        }
+    }
+}
+
+/// # Privacy accountant
+/// The (ε, δ) budget allowed for a dataset and what was already spent on it.
+/// Releases compose sequentially: their ε and δ add up.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::privacy::PrivacyAccountant;
+/// let mut accountant = PrivacyAccountant::new(1.0, 0.0);
+/// assert!(accountant.spend(0.6, 0.0).is_ok());
+/// assert!(accountant.spend(0.6, 0.0).is_err());
+/// assert!((accountant.remaining_epsilon() - 0.4).abs() < 1e-9);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PrivacyAccountant {
+    epsilon_budget: f64,
+    delta_budget: f64,
+    epsilon_spent: f64,
+    delta_spent: f64,
+}
+
+impl PrivacyAccountant {
+    pub fn new(epsilon_budget: f64, delta_budget: f64) -> Self {
+        PrivacyAccountant {
+            epsilon_budget,
+            delta_budget,
+            epsilon_spent: 0.0,
+            delta_spent: 0.0,
+        }
+    }
+
+    /// Records a release, unless it would exceed the budget. Nothing is spent on
+    /// error, so the release must not happen.
+    pub fn spend(&mut self, epsilon: f64, delta: f64) -> Result<()> {
+        if epsilon <= 0.0 || delta < 0.0 {
+            return Err(Error::InvalidConfig(
+                "epsilon must be positive and delta non-negative".to_string(),
+            ));
+        }
+        // Tolerates the rounding of budgets split in equal parts.
+        let tolerance = 1e-9;
+        if self.epsilon_spent + epsilon > self.epsilon_budget + tolerance
+            || self.delta_spent + delta > self.delta_budget + tolerance
+        {
+            return Err(Error::InvalidConfig(format!(
+                "privacy budget exceeded: spending (ε={}, δ={}) with (ε={}, δ={}) left",
+                epsilon,
+                delta,
+                self.remaining_epsilon(),
+                self.remaining_delta()
+            )));
+        }
+        self.epsilon_spent += epsilon;
+        self.delta_spent += delta;
+        Ok(())
+    }
+
+    pub fn epsilon_spent(&self) -> f64 {
+        self.epsilon_spent
+    }
+
+    pub fn delta_spent(&self) -> f64 {
+        self.delta_spent
+    }
+
+    pub fn remaining_epsilon(&self) -> f64 {
+        (self.epsilon_budget - self.epsilon_spent).max(0.0)
+    }
+
+    pub fn remaining_delta(&self) -> f64 {
+        (self.delta_budget - self.delta_spent).max(0.0)
+    }
+}
+
+/// # Add noise
+/// Perturbs values released together, such as similarity scores, and charges the
+/// release to the accountant.
+///
+/// ## Parameters:
+/// * `values`: The values, perturbed in place.
+/// * `sensitivity`: How much a single rating can change the values, in the norm of
+///   the mechanism.
+/// * `epsilon`: The ε spent by the release.
+/// * `mechanism`: How the noise is drawn.
+/// * `accountant`: The budget the release is charged to.
+/// * `rng`: The source of the noise.
+///
+/// ## Examples:
+/// ```
+/// use rand::rngs::StdRng;
+/// use rand::SeedableRng;
+/// use rec_rsys::privacy::{add_noise, NoiseMechanism, PrivacyAccountant};
+/// let mut accountant = PrivacyAccountant::new(1.0, 0.0);
+/// let mut scores = vec![0.9, 0.5, 0.1];
+/// let mut rng = StdRng::seed_from_u64(42);
+/// add_noise(&mut scores, 1.0, 1.0, NoiseMechanism::Laplace, &mut accountant, &mut rng).unwrap();
+/// assert_ne!(scores, vec![0.9, 0.5, 0.1]);
+/// assert!(add_noise(&mut scores, 1.0, 1.0, NoiseMechanism::Laplace, &mut accountant, &mut rng).is_err());
+/// ```
+pub fn add_noise<R: Rng + ?Sized>(
+    values: &mut [f32],
+    sensitivity: f32,
+    epsilon: f64,
+    mechanism: NoiseMechanism,
+    accountant: &mut PrivacyAccountant,
+    rng: &mut R,
+) -> Result<()> {
+    mechanism.validate()?;
+    accountant.spend(epsilon, mechanism.delta())?;
+    let scale = mechanism.scale(sensitivity, epsilon);
+    values
+        .iter_mut()
+        .for_each(|value| *value += mechanism.sample(scale, rng));
+    Ok(())
+}
+
+/// Perturbs the scores of recommendations with [`add_noise`] and sorts them again,
+/// best first.
+pub fn privatize_recommendations<R: Rng + ?Sized>(
+    recommendations: &[Recommendation],
+    sensitivity: f32,
+    epsilon: f64,
+    mechanism: NoiseMechanism,
+    accountant: &mut PrivacyAccountant,
+    rng: &mut R,
+) -> Result<Vec<Recommendation>> {
+    let mut scores: Vec<f32> = recommendations.iter().map(|r| r.score).collect();
+    add_noise(
+        &mut scores,
+        sensitivity,
+        epsilon,
+        mechanism,
+        accountant,
+        rng,
+    )?;
+    let mut noisy: Vec<Recommendation> = recommendations
+        .iter()
+        .zip(scores)
+        .map(|(r, score)| Recommendation {
+            item_id: r.item_id,
+            score,
+        })
+        .collect();
+    noisy.sort_by(|a, b| b.score.total_cmp(&a.score));
+    Ok(noisy)
+}
+
+/// # Gradient privacy
+/// Perturbation of the gradients of a training: the gradient of every rating is
+/// clipped to the norm `clip` of the mechanism, so that a rating has a bounded
+/// influence, and noise of that sensitivity is added to it. The ε and the δ of the
+/// mechanism are the ones of the whole training, split equally between the epochs.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct GradientPrivacy {
+    pub mechanism: NoiseMechanism,
+    /// The ε of the whole training.
+    pub epsilon: f64,
+    /// The maximum norm of the gradient of a rating.
+    pub clip: f32,
+}
+
+impl GradientPrivacy {
+    pub fn validate(&self) -> Result<()> {
+        self.mechanism.validate()?;
+        if self.epsilon > 0.0 && self.clip > 0.0 {
+            Ok(())
+        } else {
+            Err(Error::InvalidConfig(
+                "privacy.epsilon and privacy.clip must be greater than 0".to_string(),
+            ))
+        }
+    }
+
+    /// The mechanism and the ε of a single epoch.
+    pub fn per_epoch(&self, num_epochs: usize) -> (NoiseMechanism, f64) {
+        let epochs = num_epochs.max(1) as f64;
+        let mechanism = match self.mechanism {
+            NoiseMechanism::Laplace => NoiseMechanism::Laplace,
+            NoiseMechanism::Gaussian { delta } => NoiseMechanism::Gaussian {
+                delta: delta / epochs,
+            },
+        };
+        (mechanism, self.epsilon / epochs)
+    }
+
+    /// Clips the gradient of a rating and adds the noise of one epoch to it.
+    pub(crate) fn perturb<R: Rng + ?Sized>(
+        &self,
+        gradient: &mut [f32],
+        num_epochs: usize,
+        rng: &mut R,
+    ) {
+        let norm = self.mechanism.norm(gradient);
+        if norm > self.clip {
+            gradient.iter_mut().for_each(|g| *g *= self.clip / norm);
+        }
+        let (mechanism, epsilon) = self.per_epoch(num_epochs);
+        let scale = mechanism.scale(self.clip, epsilon);
+        gradient
+            .iter_mut()
+            .for_each(|g| *g += mechanism.sample(scale, rng));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::statistics::{mean, standard_deviation};
+
+    #[test]
+    fn test_noise_distribution() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let laplace: Vec<f32> = (0..20_000)
+            .map(|_| NoiseMechanism::Laplace.sample(2.0, &mut rng))
+            .collect();
+        assert!(mean(&laplace).abs() < 0.1);
+        // The standard deviation of a Laplace distribution is its scale times √2.
+        assert!((standard_deviation(&laplace) - 2.0 * 2_f32.sqrt()).abs() < 0.15);
+        let gaussian = NoiseMechanism::Gaussian { delta: 1e-5 };
+        let samples: Vec<f32> = (0..20_000)
+            .map(|_| gaussian.sample(3.0, &mut rng))
+            .collect();
+        assert!((standard_deviation(&samples) - 3.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_accountant() {
+        let mut accountant = PrivacyAccountant::new(1.0, 1e-5);
+        let gaussian = NoiseMechanism::Gaussian { delta: 1e-5 };
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut values = vec![1.0; 3];
+        add_noise(&mut values, 1.0, 0.5, gaussian, &mut accountant, &mut rng).unwrap();
+        assert_eq!(accountant.epsilon_spent(), 0.5);
+        assert!(
+            add_noise(&mut values, 1.0, 0.1, gaussian, &mut accountant, &mut rng)
+                .is_err()
+        );
+        assert_eq!(accountant.epsilon_spent(), 0.5);
+        assert!(accountant.spend(0.0, 0.0).is_err());
+        assert!(NoiseMechanism::Gaussian { delta: 0.0 }.validate().is_err());
+    }
+
+    #[test]
+    fn test_gradient_clipping() {
+        let privacy = GradientPrivacy {
+            mechanism: NoiseMechanism::Laplace,
+            epsilon: 1e9,
+            clip: 1.0,
+        };
+        let mut gradient = vec![3.0, -1.0];
+        privacy.perturb(&mut gradient, 1, &mut StdRng::seed_from_u64(3));
+        assert!((NoiseMechanism::Laplace.norm(&gradient) - 1.0).abs() < 1e-4);
+        assert!((gradient[0] - 0.75).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_privatize_recommendations() {
+        let recommendations: Vec<Recommendation> = (0..5)
+            .map(|item_id| Recommendation {
+                item_id,
+                score: item_id as f32,
+            })
+            .collect();
+        let mut accountant = PrivacyAccountant::new(10.0, 0.0);
+        let noisy = privatize_recommendations(
+            &recommendations,
+            1.0,
+            1.0,
+            NoiseMechanism::Laplace,
+            &mut accountant,
+            &mut StdRng::seed_from_u64(0),
+        )
+        .unwrap();
+        assert_eq!(noisy.len(), 5);
+        assert!(noisy.windows(2).all(|w| w[0].score >= w[1].score));
+    }
+}
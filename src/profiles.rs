@@ -0,0 +1,539 @@
+//! # User profiles
+//! Represents a user in the same space as the items by aggregating the vectors of
+//! the items they interacted with.
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::algorithms::config::{ensure, AlgorithmConfig};
+use crate::dataset::Dataset;
+use crate::errors::Result;
+use crate::memory::MemoryFootprint;
+use crate::models::Item;
+use crate::parallelism::default_parallelism;
+use crate::parallelism::prelude::*;
+
+/// How the vectors of the interacted items are combined.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum Aggregation {
+    /// Plain average of the vectors.
+    Mean,
+    /// Average where an interaction weighs half as much every `half_life` seconds,
+    /// which must be positive.
+    TimeDecayedMean { half_life: f64 },
+    /// Element-wise maximum of the vectors.
+    MaxPool,
+}
+
+impl Aggregation {
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            Aggregation::TimeDecayedMean { half_life } => ensure(
+                *half_life > 0.0 && half_life.is_finite(),
+                "aggregation.half_life",
+                "a positive number",
+            ),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// # User profile
+/// A vector summarising the items a user interacted with, that can be updated
+/// event by event without the whole history.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::profiles::{Aggregation, UserProfile};
+/// let mut profile = UserProfile::new(1, Aggregation::Mean);
+/// profile.update(&[1.0, 0.0], 10);
+/// profile.update(&[0.0, 1.0], 20);
+/// assert_eq!(profile.vector(), vec![0.5, 0.5]);
+/// ```
+#[doc = include_str!("../docs/profiles/user_profile.md")]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UserProfile {
+    pub user_id: u32,
+    pub aggregation: Aggregation,
+    /// Weighted sum of the vectors, or their maximum for [`Aggregation::MaxPool`].
+    accumulator: Vec<f32>,
+    /// Sum of the weights of the events.
+    weight: f64,
+    num_events: usize,
+    last_timestamp: Option<u64>,
+}
+
+impl UserProfile {
+    pub fn new(user_id: u32, aggregation: Aggregation) -> Self {
+        UserProfile {
+            user_id,
+            aggregation,
+            accumulator: Vec::new(),
+            weight: 0.0,
+            num_events: 0,
+            last_timestamp: None,
+        }
+    }
+
+    /// Builds a profile from a whole history of `(item vector, timestamp)`.
+    pub fn from_history<'a, I>(user_id: u32, aggregation: Aggregation, history: I) -> Self
+    where
+        I: IntoIterator<Item = (&'a [f32], u64)>,
+    {
+        let mut profile = UserProfile::new(user_id, aggregation);
+        history
+            .into_iter()
+            .for_each(|(values, timestamp)| profile.update(values, timestamp));
+        profile
+    }
+
+    /// Adds an interaction with an item.
+    ///
+    /// ## Parameters:
+    /// * `values`: The vector of the item.
+    /// * `timestamp`: When the interaction happened, in seconds. Only used by
+    ///   [`Aggregation::TimeDecayedMean`], events may arrive out of order.
+    pub fn update(&mut self, values: &[f32], timestamp: u64) {
+        if self.accumulator.len() < values.len() {
+            let fill = match self.aggregation {
+                Aggregation::MaxPool if self.num_events > 0 => f32::NEG_INFINITY,
+                _ => 0.0,
+            };
+            self.accumulator.resize(values.len(), fill);
+        }
+        match self.aggregation {
+            Aggregation::Mean => self.accumulate(values, 1.0, 1.0),
+            Aggregation::TimeDecayedMean { half_life } => {
+                let last = self.last_timestamp.unwrap_or(timestamp);
+                let decay = |elapsed: u64| 0.5_f64.powf(elapsed as f64 / half_life);
+                if timestamp >= last {
+                    self.accumulate(values, decay(timestamp - last), 1.0);
+                } else {
+                    self.accumulate(values, 1.0, decay(last - timestamp));
+                }
+            },
+            Aggregation::MaxPool => {
+                let first = self.num_events == 0;
+                self.accumulator.iter_mut().zip(values.iter()).for_each(
+                    |(max, &value)| *max = if first { value } else { max.max(value) },
+                );
+                self.weight += 1.0;
+            },
+        }
+        self.num_events += 1;
+        self.last_timestamp = self.last_timestamp.max(Some(timestamp));
+    }
+
+    /// Scales the current state by `decay` and adds the new values with `weight`.
+    fn accumulate(&mut self, values: &[f32], decay: f64, weight: f64) {
+        self.accumulator
+            .iter_mut()
+            .for_each(|sum| *sum = (*sum as f64 * decay) as f32);
+        self.accumulator
+            .iter_mut()
+            .zip(values.iter())
+            .for_each(|(sum, &value)| *sum += (value as f64 * weight) as f32);
+        self.weight = self.weight * decay + weight;
+    }
+
+    /// The aggregated vector, empty until the first update.
+    pub fn vector(&self) -> Vec<f32> {
+        match self.aggregation {
+            Aggregation::MaxPool => self.accumulator.clone(),
+            _ => self
+                .accumulator
+                .iter()
+                .map(|sum| (*sum as f64 / self.weight) as f32)
+                .collect(),
+        }
+    }
+
+    pub fn num_events(&self) -> usize {
+        self.num_events
+    }
+
+    pub fn last_timestamp(&self) -> Option<u64> {
+        self.last_timestamp
+    }
+
+    /// The profile as an [`Item`] identified by the user id, ready to be used as
+    /// the query of a [`crate::algorithms::knn::KNN`].
+    pub fn to_item(&self) -> Item {
+        Item::new(self.user_id, self.vector(), None)
+    }
+}
+
+/// # Build profiles
+/// Builds the profile of every user of a dataset from the vectors of the items
+/// they rated, in chronological order. Ratings of items without vector are skipped.
+///
+/// ## Parameters:
+/// * `dataset`: The interactions.
+/// * `items`: The vectors of the items.
+/// * `aggregation`: How to combine the vectors.
+///
+/// ## Returns:
+/// * The profiles by user id.
+pub fn build_profiles(
+    dataset: &Dataset,
+    items: &[Item],
+    aggregation: Aggregation,
+) -> HashMap<u32, UserProfile> {
+    let vectors: HashMap<u32, &[f32]> = items
+        .iter()
+        .map(|item| (item.id, item.values.as_slice()))
+        .collect();
+    let mut ratings: Vec<_> = dataset.ratings.iter().collect();
+    ratings.sort_by_key(|r| r.timestamp.unwrap_or(0));
+
+    let mut profiles: HashMap<u32, UserProfile> = HashMap::new();
+    ratings.iter().for_each(|r| {
+        if let Some(values) = vectors.get(&r.item_id) {
+            profiles
+                .entry(r.user_id)
+                .or_insert_with(|| UserProfile::new(r.user_id, aggregation))
+                .update(values, r.timestamp.unwrap_or(0));
+        }
+    });
+    profiles
+}
+
+/// # Refresh configuration
+/// Parameters of [`refresh_profiles`].
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::algorithms::config::AlgorithmConfig;
+/// use rec_rsys::profiles::RefreshConfig;
+/// assert!(RefreshConfig::default().set_batch_size(1_000).validate().is_ok());
+/// assert!(RefreshConfig::default().set_batch_size(0).validate().is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RefreshConfig {
+    pub aggregation: Aggregation,
+    /// Number of users whose profiles are built in parallel and handed to the
+    /// sink together, which bounds the profiles held in memory.
+    pub batch_size: usize,
+    /// Euclidean distance between the previous and the new vector of a user above
+    /// which the profile counts as changed.
+    pub change_threshold: f32,
+}
+
+impl Default for RefreshConfig {
+    fn default() -> Self {
+        RefreshConfig {
+            aggregation: Aggregation::Mean,
+            batch_size: 10_000,
+            change_threshold: 1e-4,
+        }
+    }
+}
+
+impl RefreshConfig {
+    pub fn set_aggregation(mut self, aggregation: Aggregation) -> Self {
+        self.aggregation = aggregation;
+        self
+    }
+    pub fn set_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+    pub fn set_change_threshold(mut self, change_threshold: f32) -> Self {
+        self.change_threshold = change_threshold;
+        self
+    }
+}
+
+impl AlgorithmConfig for RefreshConfig {
+    fn validate(&self) -> Result<()> {
+        self.aggregation.validate()?;
+        ensure(self.batch_size > 0, "batch_size", "greater than 0")?;
+        ensure(
+            self.change_threshold.is_finite() && self.change_threshold >= 0.0,
+            "change_threshold",
+            "a non-negative number",
+        )
+    }
+}
+
+/// How the profiles moved during a [`refresh_profiles`] run.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RefreshStats {
+    /// Profiles built, the users without any item with a vector have none.
+    pub num_profiles: usize,
+    /// Profiles of users without a previous vector.
+    pub num_new: usize,
+    /// Profiles that moved further than the change threshold.
+    pub num_changed: usize,
+    pub num_unchanged: usize,
+    /// Batches handed to the sink.
+    pub num_batches: usize,
+    /// Mean distance between the previous and the new vectors, over the users who
+    /// had a previous vector.
+    pub mean_change: f32,
+    pub max_change: f32,
+}
+
+/// # Refresh profiles
+/// Rebuilds the profile of every user from all their interactions, e.g. nightly
+/// between two full retrains, batch after batch so only the ratings and one batch
+/// of profiles are held in memory at once. The profiles of a batch are built in
+/// parallel, with the [default parallelism](crate::parallelism::default_parallelism).
+///
+/// ## Parameters:
+/// * `dataset`: The latest interactions.
+/// * `items`: The vectors of the items, ratings of items without vector are
+///   skipped.
+/// * `config`: The aggregation, the batch size and the change threshold.
+/// * `previous`: The current vector of a user, if any, to measure how the profiles
+///   changed.
+/// * `sink`: Receives every batch of profiles, e.g. to write them to a store. An
+///   error stops the job.
+///
+/// ## Returns:
+/// * The change statistics, or the first error of the config or of the sink.
+///
+/// ## Examples:
+/// ```
+/// use std::collections::HashMap;
+/// use rec_rsys::dataset::{Dataset, Rating};
+/// use rec_rsys::models::Item;
+/// use rec_rsys::profiles::{refresh_profiles, RefreshConfig};
+/// let dataset = Dataset::new(vec![
+///     Rating::new(1, 10, 5.0), Rating::new(1, 11, 5.0), Rating::new(2, 10, 1.0),
+/// ]);
+/// let items = vec![Item::new(10, vec![1.0, 0.0], None), Item::new(11, vec![0.0, 1.0], None)];
+/// let mut stored: HashMap<u32, Vec<f32>> = HashMap::from([(2, vec![1.0, 0.0])]);
+/// let previous = stored.clone();
+/// let stats = refresh_profiles(
+///     &dataset,
+///     &items,
+///     &RefreshConfig::default(),
+///     |user_id| previous.get(&user_id).cloned(),
+///     |batch| {
+///         stored.extend(batch.into_iter().map(|p| (p.user_id, p.vector())));
+///         Ok(())
+///     },
+/// )
+/// .unwrap();
+/// assert_eq!((stats.num_profiles, stats.num_new, stats.num_unchanged), (2, 1, 1));
+/// assert_eq!(stored[&1], vec![0.5, 0.5]);
+/// ```
+pub fn refresh_profiles<P, S>(
+    dataset: &Dataset,
+    items: &[Item],
+    config: &RefreshConfig,
+    previous: P,
+    mut sink: S,
+) -> Result<RefreshStats>
+where
+    P: Fn(u32) -> Option<Vec<f32>> + Sync,
+    S: FnMut(Vec<UserProfile>) -> Result<()>,
+{
+    config.validate()?;
+    let vectors: HashMap<u32, &[f32]> = items
+        .iter()
+        .map(|item| (item.id, item.values.as_slice()))
+        .collect();
+    let ratings = &dataset.ratings;
+    let mut order: Vec<usize> = (0..ratings.len()).collect();
+    order.sort_by_key(|&i| (ratings[i].user_id, ratings[i].timestamp.unwrap_or(0)));
+    let users: Vec<&[usize]> = order
+        .chunk_by(|&a, &b| ratings[a].user_id == ratings[b].user_id)
+        .collect();
+
+    let mut stats = RefreshStats::default();
+    let mut total_change = 0.0;
+    for batch in users.chunks(config.batch_size) {
+        let refreshed: Vec<(UserProfile, Option<f32>)> =
+            default_parallelism().install(|| {
+                batch
+                    .par_iter()
+                    .filter_map(|indices| {
+                        let user_id = ratings[indices[0]].user_id;
+                        let history = indices.iter().filter_map(|&i| {
+                            let rating = &ratings[i];
+                            let values = vectors.get(&rating.item_id)?;
+                            Some((*values, rating.timestamp.unwrap_or(0)))
+                        });
+                        let profile = UserProfile::from_history(
+                            user_id,
+                            config.aggregation,
+                            history,
+                        );
+                        if profile.num_events() == 0 {
+                            return None;
+                        }
+                        let change = previous(user_id)
+                            .map(|old| distance(&old, &profile.vector()));
+                        Some((profile, change))
+                    })
+                    .collect()
+            });
+        let mut profiles = Vec::with_capacity(refreshed.len());
+        for (profile, change) in refreshed {
+            match change {
+                None => stats.num_new += 1,
+                Some(change) => {
+                    total_change += change as f64;
+                    stats.max_change = stats.max_change.max(change);
+                    match change > config.change_threshold {
+                        true => stats.num_changed += 1,
+                        false => stats.num_unchanged += 1,
+                    }
+                },
+            }
+            profiles.push(profile);
+        }
+        stats.num_profiles += profiles.len();
+        stats.num_batches += 1;
+        sink(profiles)?;
+    }
+    let num_previous = stats.num_changed + stats.num_unchanged;
+    if num_previous > 0 {
+        stats.mean_change = (total_change / num_previous as f64) as f32;
+    }
+    Ok(stats)
+}
+
+/// The euclidean distance, the missing dimensions of the shorter vector being 0.
+fn distance(u: &[f32], v: &[f32]) -> f32 {
+    let (short, long) = if u.len() <= v.len() { (u, v) } else { (v, u) };
+    long.iter()
+        .enumerate()
+        .map(|(i, b)| (short.get(i).unwrap_or(&0.0) - b).powi(2))
+        .sum::<f32>()
+        .sqrt()
+}
+
+impl MemoryFootprint for Aggregation {}
+
+impl MemoryFootprint for RefreshConfig {}
+
+impl MemoryFootprint for RefreshStats {}
+
+impl MemoryFootprint for UserProfile {
+    fn heap_size(&self) -> usize {
+        self.accumulator.heap_size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dataset::Rating;
+
+    #[test]
+    fn test_mean() {
+        let profile = UserProfile::from_history(
+            1,
+            Aggregation::Mean,
+            [
+                (&[1.0, 2.0][..], 0),
+                (&[3.0, 4.0][..], 0),
+                (&[2.0, 0.0][..], 0),
+            ],
+        );
+        assert_eq!(profile.vector(), vec![2.0, 2.0]);
+        assert_eq!(profile.num_events(), 3);
+    }
+
+    #[test]
+    fn test_time_decayed_mean() {
+        let aggregation = Aggregation::TimeDecayedMean { half_life: 10.0 };
+        let profile = UserProfile::from_history(
+            1,
+            aggregation,
+            [(&[0.0][..], 0), (&[3.0][..], 10)],
+        );
+        // The first event weighs 0.5, the second 1.0.
+        assert_eq!(profile.vector(), vec![2.0]);
+        let shuffled = UserProfile::from_history(
+            1,
+            aggregation,
+            [(&[3.0][..], 10), (&[0.0][..], 0)],
+        );
+        assert_eq!(shuffled.vector(), profile.vector());
+        for half_life in [0.0, -1.0, f64::NAN] {
+            let invalid = Aggregation::TimeDecayedMean { half_life };
+            assert!(invalid.validate().is_err());
+            let config = RefreshConfig::default().set_aggregation(invalid);
+            assert!(config.validate().is_err());
+        }
+        assert!(aggregation.validate().is_ok());
+    }
+
+    #[test]
+    fn test_max_pool() {
+        let profile = UserProfile::from_history(
+            1,
+            Aggregation::MaxPool,
+            [(&[-1.0, 2.0][..], 0), (&[-3.0, 4.0][..], 0)],
+        );
+        assert_eq!(profile.vector(), vec![-1.0, 4.0]);
+    }
+
+    #[test]
+    fn test_build_profiles() {
+        let dataset = Dataset::new(vec![
+            Rating::new(1, 10, 5.0),
+            Rating::new(1, 11, 5.0),
+            Rating::new(2, 12, 1.0),
+            Rating::new(3, 99, 1.0),
+        ]);
+        let items = vec![
+            Item::new(10, vec![1.0, 0.0], None),
+            Item::new(11, vec![0.0, 1.0], None),
+            Item::new(12, vec![1.0, 1.0], None),
+        ];
+        let profiles = build_profiles(&dataset, &items, Aggregation::Mean);
+        assert_eq!(profiles.len(), 2);
+        assert_eq!(profiles[&1].vector(), vec![0.5, 0.5]);
+        assert_eq!(profiles[&2].to_item().id, 2);
+    }
+
+    #[test]
+    fn test_refresh_profiles() {
+        let dataset = Dataset::new(
+            (0..10)
+                .flat_map(|user_id| {
+                    [Rating::new(user_id, 10, 1.0), Rating::new(user_id, 11, 1.0)]
+                })
+                .chain([Rating::new(20, 99, 1.0)])
+                .collect(),
+        );
+        let items = vec![
+            Item::new(10, vec![1.0, 0.0], None),
+            Item::new(11, vec![0.0, 1.0], None),
+        ];
+        let config = RefreshConfig::default().set_batch_size(3);
+        let previous = |user_id: u32| match user_id {
+            0 => Some(vec![0.5, 0.5]),
+            1 => Some(vec![0.5]),
+            _ => None,
+        };
+        let mut batches = Vec::new();
+        let stats = refresh_profiles(&dataset, &items, &config, previous, |batch| {
+            batches.push(batch.len());
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(batches, vec![3, 3, 3, 1]);
+        assert_eq!(stats.num_profiles, 10);
+        assert_eq!(
+            (stats.num_new, stats.num_changed, stats.num_unchanged),
+            (8, 1, 1)
+        );
+        assert_eq!(stats.max_change, 0.5);
+        assert_eq!(stats.mean_change, 0.25);
+
+        let failing = refresh_profiles(&dataset, &items, &config, previous, |_| {
+            Err(crate::errors::Error::InvalidData(
+                "store is down".to_string(),
+            ))
+        });
+        assert!(failing.is_err());
+    }
+}
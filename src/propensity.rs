@@ -0,0 +1,327 @@
+//! # Position bias
+//! Estimates how much more the top of a list is examined than its bottom, from
+//! the clicks logged at each position, and turns it into weights that remove the
+//! position bias of the clicks when training or evaluating ranking models.
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::dataset::{Dataset, Rating};
+use crate::errors::{Error, Result};
+use crate::memory::MemoryFootprint;
+
+/// An item shown to a user at a position of a list, clicked or not.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Impression {
+    pub user_id: u32,
+    pub item_id: u32,
+    /// The position in the list, 0 being the first.
+    pub position: usize,
+    pub clicked: bool,
+}
+
+impl Impression {
+    pub fn new(user_id: u32, item_id: u32, position: usize, clicked: bool) -> Self {
+        Impression {
+            user_id,
+            item_id,
+            position,
+            clicked,
+        }
+    }
+}
+
+/// # Position propensities
+/// The probability of every position of a list to be examined, relative to the
+/// first one. The positions after the last estimated one share its propensity.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::propensity::{Impression, PositionPropensities};
+/// // Position 0 is clicked 2 times out of 4, position 1 only once.
+/// let impressions: Vec<Impression> = (0..8)
+///     .map(|i| Impression::new(i, i, (i % 2) as usize, [0, 1, 2].contains(&i)))
+///     .collect();
+/// let propensities = PositionPropensities::from_click_rates(&impressions, 2, 0.0).unwrap();
+/// assert_eq!(propensities.propensity(0), 1.0);
+/// assert_eq!(propensities.propensity(1), 0.5);
+/// assert_eq!(propensities.weight(1, 10.0), 2.0);
+/// assert_eq!(propensities.weight(7, 1.5), 1.5);
+/// ```
+#[doc = include_str!("../docs/propensity/ips.md")]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PositionPropensities {
+    propensities: Vec<f32>,
+}
+
+impl PositionPropensities {
+    /// Propensities given for every position, e.g. estimated by a randomized
+    /// experiment. They must be in `(0, 1]`.
+    pub fn new(propensities: Vec<f32>) -> Result<Self> {
+        if propensities.is_empty() {
+            return Err(Error::InvalidData(
+                "at least one propensity is needed".to_string(),
+            ));
+        }
+        if propensities.iter().any(|p| !(*p > 0.0 && *p <= 1.0)) {
+            return Err(Error::InvalidData(
+                "the propensities must be in (0, 1]".to_string(),
+            ));
+        }
+        Ok(PositionPropensities { propensities })
+    }
+
+    /// The propensities `1 / (k + 1)^eta` of the positions `k` of a list, the
+    /// usual parametric model of the position bias: `eta` of 0 means no bias, the
+    /// larger it is the faster the attention decays.
+    pub fn power_law(num_positions: usize, eta: f32) -> Result<Self> {
+        if !eta.is_finite() || eta < 0.0 {
+            return Err(Error::InvalidConfig(format!(
+                "eta must be a non-negative number, got {}",
+                eta
+            )));
+        }
+        PositionPropensities::new(
+            (0..num_positions)
+                .map(|k| (k as f32 + 1.0).powf(-eta))
+                .collect(),
+        )
+    }
+
+    /// # From click rates
+    /// Estimates the propensities as the click rate of every position divided by
+    /// the click rate of the first one. The click rates only reflect the position
+    /// bias when the items were shown at random positions.
+    ///
+    /// ## Parameters:
+    /// * `impressions`: The logged impressions. The ones at positions from
+    ///   `num_positions` on are ignored.
+    /// * `num_positions`: The number of positions estimated.
+    /// * `smoothing`: Pseudo impressions at the overall click rate added to every
+    ///   position, so positions with few impressions do not get extreme estimates.
+    ///
+    /// ## Returns:
+    /// * The propensities, or an error if nothing was clicked at the first position
+    ///   and no smoothing makes up for it. Estimates above the first position's are
+    ///   capped to 1 and the ones of positions without clicks to the smallest
+    ///   positive estimate.
+    pub fn from_click_rates(
+        impressions: &[Impression],
+        num_positions: usize,
+        smoothing: f32,
+    ) -> Result<Self> {
+        if !smoothing.is_finite() || smoothing < 0.0 {
+            return Err(Error::InvalidConfig(format!(
+                "smoothing must be a non-negative number, got {}",
+                smoothing
+            )));
+        }
+        let mut shown = vec![0.0_f64; num_positions];
+        let mut clicks = vec![0.0_f64; num_positions];
+        impressions
+            .iter()
+            .filter(|impression| impression.position < num_positions)
+            .for_each(|impression| {
+                shown[impression.position] += 1.0;
+                clicks[impression.position] += f64::from(u8::from(impression.clicked));
+            });
+        let total_shown: f64 = shown.iter().sum();
+        let overall = match total_shown > 0.0 {
+            true => clicks.iter().sum::<f64>() / total_shown,
+            false => 0.0,
+        };
+        let smoothing = smoothing as f64;
+        let rates: Vec<f64> = shown
+            .iter()
+            .zip(&clicks)
+            .map(|(shown, clicks)| match shown + smoothing > 0.0 {
+                true => (clicks + smoothing * overall) / (shown + smoothing),
+                false => 0.0,
+            })
+            .collect();
+        let first = rates.first().copied().unwrap_or(0.0);
+        if first <= 0.0 {
+            return Err(Error::InvalidData(
+                "the first position must have clicks to estimate the propensities"
+                    .to_string(),
+            ));
+        }
+        let relative: Vec<f32> = rates
+            .iter()
+            .map(|rate| (rate / first).min(1.0) as f32)
+            .collect();
+        let smallest = relative
+            .iter()
+            .copied()
+            .filter(|p| *p > 0.0)
+            .fold(1.0, f32::min);
+        PositionPropensities::new(
+            relative
+                .into_iter()
+                .map(|p| if p > 0.0 { p } else { smallest })
+                .collect(),
+        )
+    }
+
+    /// The propensity of a position.
+    pub fn propensity(&self, position: usize) -> f32 {
+        let last = self.propensities.len() - 1;
+        self.propensities[position.min(last)]
+    }
+
+    pub fn propensities(&self) -> &[f32] {
+        &self.propensities
+    }
+
+    /// The inverse propensity of a position, clipped to `max_weight`.
+    pub fn weight(&self, position: usize, max_weight: f32) -> f32 {
+        (1.0 / self.propensity(position)).min(max_weight)
+    }
+
+    /// # Debiased dataset
+    /// The clicks as ratings weighted by the inverse propensity of their position,
+    /// to train a model on the relevance of the items rather than on where they
+    /// were shown.
+    ///
+    /// ## Parameters:
+    /// * `impressions`: The logged impressions, only the clicked ones are kept.
+    /// * `max_weight`: The maximum weight of a click.
+    ///
+    /// ## Examples:
+    /// ```
+    /// use rec_rsys::propensity::{Impression, PositionPropensities};
+    /// let propensities = PositionPropensities::power_law(10, 1.0).unwrap();
+    /// let impressions = [Impression::new(1, 10, 0, true), Impression::new(1, 11, 3, true)];
+    /// let dataset = propensities.debiased_dataset(&impressions, 10.0);
+    /// assert_eq!(dataset.ratings[1].rating, 4.0);
+    /// ```
+    pub fn debiased_dataset(
+        &self,
+        impressions: &[Impression],
+        max_weight: f32,
+    ) -> Dataset {
+        Dataset::new(
+            impressions
+                .iter()
+                .filter(|impression| impression.clicked)
+                .map(|impression| {
+                    let weight = self.weight(impression.position, max_weight);
+                    Rating::new(impression.user_id, impression.item_id, weight)
+                })
+                .collect(),
+        )
+    }
+
+    /// # Debiased click rates
+    /// The click rate of every item as if it had always been shown at the first
+    /// position: the weighted clicks divided by the impressions, e.g. to evaluate
+    /// which items are relevant from logs of a biased ranking.
+    ///
+    /// ## Parameters:
+    /// * `impressions`: The logged impressions.
+    /// * `max_weight`: The maximum weight of a click.
+    ///
+    /// ## Returns:
+    /// * The debiased click rate of every item shown.
+    pub fn debiased_click_rates(
+        &self,
+        impressions: &[Impression],
+        max_weight: f32,
+    ) -> HashMap<u32, f32> {
+        let mut sums: HashMap<u32, (f32, f32)> = HashMap::new();
+        impressions.iter().for_each(|impression| {
+            let (clicks, shown) = sums.entry(impression.item_id).or_default();
+            if impression.clicked {
+                *clicks += self.weight(impression.position, max_weight);
+            }
+            *shown += 1.0;
+        });
+        sums.into_iter()
+            .map(|(item_id, (clicks, shown))| (item_id, clicks / shown))
+            .collect()
+    }
+}
+
+impl MemoryFootprint for Impression {}
+
+impl MemoryFootprint for PositionPropensities {
+    fn heap_size(&self) -> usize {
+        self.propensities.heap_size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Impressions of items clicked with probability `1 / (position + 1)`, shown
+    /// at every position the same number of times.
+    fn impressions() -> Vec<Impression> {
+        let mut impressions = Vec::new();
+        for position in 0..4 {
+            for i in 0..120 {
+                let clicked = i % (position + 1) == 0;
+                impressions.push(Impression::new(
+                    i as u32,
+                    position as u32,
+                    position,
+                    clicked,
+                ));
+            }
+        }
+        impressions
+    }
+
+    #[test]
+    fn test_from_click_rates() {
+        let propensities =
+            PositionPropensities::from_click_rates(&impressions(), 4, 0.0).unwrap();
+        let expected = PositionPropensities::power_law(4, 1.0).unwrap();
+        for (estimated, expected) in propensities
+            .propensities()
+            .iter()
+            .zip(expected.propensities())
+        {
+            crate::assert_approx_eq!(*estimated, *expected);
+        }
+        assert_eq!(propensities.propensity(100), propensities.propensity(3));
+
+        let smoothed =
+            PositionPropensities::from_click_rates(&impressions(), 6, 10.0).unwrap();
+        assert!(smoothed
+            .propensities()
+            .iter()
+            .all(|p| *p > 0.0 && *p <= 1.0));
+        assert!(smoothed.propensity(1) > propensities.propensity(1));
+    }
+
+    #[test]
+    fn test_invalid_estimates() {
+        let unclicked = [
+            Impression::new(1, 1, 0, false),
+            Impression::new(1, 2, 1, true),
+        ];
+        assert!(PositionPropensities::from_click_rates(&unclicked, 2, 0.0).is_err());
+        assert!(PositionPropensities::from_click_rates(&[], 2, 1.0).is_err());
+        assert!(PositionPropensities::from_click_rates(&unclicked, 2, -1.0).is_err());
+        assert!(PositionPropensities::new(vec![1.0, 0.0]).is_err());
+        assert!(PositionPropensities::power_law(0, 1.0).is_err());
+        assert!(PositionPropensities::power_law(3, f32::NAN).is_err());
+    }
+
+    #[test]
+    fn test_debiased_click_rates() {
+        let propensities = PositionPropensities::power_law(4, 1.0).unwrap();
+        let rates = propensities.debiased_click_rates(&impressions(), 100.0);
+        // Every item is as relevant once the position bias is removed.
+        rates
+            .values()
+            .for_each(|rate| crate::assert_approx_eq!(*rate, 1.0));
+        let clipped = propensities.debiased_click_rates(&impressions(), 2.0);
+        assert_eq!(clipped[&3], 0.5);
+        assert_eq!(
+            propensities.debiased_dataset(&impressions(), 100.0).len(),
+            120 + 60 + 40 + 30
+        );
+    }
+}
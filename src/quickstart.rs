@@ -0,0 +1,228 @@
+//! # Quickstart
+//! From a MovieLens ratings file to a trained and evaluated recommender in a single
+//! call, the shortest path through the [`dataset`](crate::dataset),
+//! [`algorithms`](crate::algorithms) and [`evaluation`](crate::evaluation) modules.
+//! Each step can then be replaced by its own module when the defaults fall short.
+//!
+//! ```
+//! use rec_rsys::quickstart;
+//! use rec_rsys::recommender::Recommender;
+//! let (model, report) = quickstart::movielens("examples/data/ratings.tsv").unwrap();
+//! println!("{}", report);
+//! assert!(report.top_n.hit_rate > 0.0);
+//! assert_eq!(model.recommend(1, 10).len(), 10);
+//! ```
+use std::fmt;
+use std::path::Path;
+use std::time::Instant;
+
+use crate::algorithms::als::{ImplicitALS, ImplicitALSConfig};
+use crate::dataset::{CsvOptions, Dataset};
+use crate::errors::{Error, Result};
+use crate::evaluation::{TopNEvaluator, TopNReport};
+use crate::formatting::ReportFormat;
+use crate::recommender::Recommender;
+
+/// The fraction of the ratings held out to evaluate the model.
+const TEST_RATIO: f32 = 0.2;
+/// The seed of the split.
+const SEED: u64 = 42;
+/// The lowest rating counted as a positive interaction.
+const RELEVANCE_THRESHOLD: f32 = 4.0;
+/// The length of the evaluated recommendation lists.
+const NUM_RECOMMENDATIONS: usize = 10;
+
+/// What [`movielens`] loaded, trained and measured.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuickstartReport {
+    pub num_ratings: usize,
+    pub num_users: usize,
+    pub num_items: usize,
+    /// The number of positive interactions the model was trained on.
+    pub train_interactions: usize,
+    /// The number of positive interactions held out.
+    pub test_interactions: usize,
+    pub train_seconds: f64,
+    /// The loss of the last iteration.
+    pub train_loss: Option<f64>,
+    /// The top-10 metrics on the held-out interactions.
+    pub top_n: TopNReport,
+}
+
+impl QuickstartReport {
+    /// # Report
+    /// The report as text, written in the given format.
+    pub fn report(&self, format: &ReportFormat) -> String {
+        let mut report = String::new();
+        // Writing to a string does not fail.
+        let _ = self.write_report(&mut report, format);
+        report
+    }
+
+    fn write_report<W: fmt::Write>(
+        &self,
+        f: &mut W,
+        format: &ReportFormat,
+    ) -> fmt::Result {
+        writeln!(
+            f,
+            "Dataset: {} ratings of {} users on {} items",
+            self.num_ratings, self.num_users, self.num_items
+        )?;
+        writeln!(
+            f,
+            "Implicit ALS: trained on {} interactions in {}, evaluated on {}",
+            self.train_interactions,
+            format.duration(self.train_seconds),
+            self.test_interactions
+        )?;
+        if let Some(loss) = self.train_loss {
+            writeln!(f, "train_loss: {}", format.number(loss))?;
+        }
+        for (metric, value) in [
+            ("hit_rate", self.top_n.hit_rate),
+            ("precision", self.top_n.precision),
+            ("recall", self.top_n.recall),
+            ("ndcg", self.top_n.ndcg),
+        ] {
+            writeln!(
+                f,
+                "{}@{}: {}",
+                metric,
+                NUM_RECOMMENDATIONS,
+                format.number(value as f64)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for QuickstartReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_report(f, &ReportFormat::default())
+    }
+}
+
+/// # MovieLens quickstart
+/// Loads a MovieLens ratings file, holds out 20% of the ratings, trains an
+/// [`ImplicitALS`] with its default configuration on the ratings of 4 or more and
+/// evaluates its top-10 recommendations against the held-out ones.
+///
+/// The layout is read from the name of the file: `ratings.dat` of MovieLens 1M
+/// separated by `::`, `ratings.csv` of the latest releases with a header, and
+/// otherwise tab separated like `u.data` of MovieLens 100K.
+///
+/// ## Parameters:
+/// * `path`: The ratings file.
+///
+/// ## Returns:
+/// * The trained model and its report, or an error when the file cannot be read
+///   or holds no rating of 4 or more.
+pub fn movielens<P: AsRef<Path>>(path: P) -> Result<(ImplicitALS, QuickstartReport)> {
+    let dataset = Dataset::from_csv(&path, &csv_options(path.as_ref()))?;
+    let (train, test) = dataset.split_random(TEST_RATIO, SEED);
+    let train = train.binarize(RELEVANCE_THRESHOLD);
+    if train.is_empty() {
+        return Err(Error::InvalidData(format!(
+            "no rating of at least {} to train on",
+            RELEVANCE_THRESHOLD
+        )));
+    }
+    let mut model = ImplicitALS::new(ImplicitALSConfig::default());
+    let start = Instant::now();
+    model.fit(&train)?;
+    let train_seconds = start.elapsed().as_secs_f64();
+
+    let relevant = test
+        .filter(|r| r.rating >= RELEVANCE_THRESHOLD)
+        .user_items();
+    let top_n =
+        TopNEvaluator::new(NUM_RECOMMENDATIONS).evaluate(&relevant, |user_id, n| {
+            model
+                .recommend(user_id, n)
+                .iter()
+                .map(|r| r.item_id)
+                .collect()
+        });
+    let report = QuickstartReport {
+        num_ratings: dataset.len(),
+        num_users: dataset.users().len(),
+        num_items: dataset.items().len(),
+        train_interactions: train.len(),
+        test_interactions: relevant.values().map(Vec::len).sum(),
+        train_seconds,
+        train_loss: model.history().last().map(|epoch| epoch.train_loss),
+        top_n,
+    };
+    Ok((model, report))
+}
+
+/// How to read a MovieLens ratings file, from its name.
+fn csv_options(path: &Path) -> CsvOptions {
+    let (delimiter, has_header) = match path.extension().and_then(|e| e.to_str()) {
+        Some("dat") => ("::", false),
+        Some("csv") => (",", true),
+        _ => ("\t", false),
+    };
+    CsvOptions {
+        delimiter: delimiter.to_string(),
+        has_header,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dataset::Rating;
+
+    #[test]
+    fn test_csv_options() {
+        assert_eq!(csv_options(Path::new("ml-1m/ratings.dat")).delimiter, "::");
+        let options = csv_options(Path::new("ml-latest-small/ratings.csv"));
+        assert_eq!(options.delimiter, ",");
+        assert!(options.has_header);
+        assert_eq!(csv_options(Path::new("ml-100k/u.data")).delimiter, "\t");
+    }
+
+    #[test]
+    fn test_movielens_1m_layout() {
+        let ratings: Vec<Rating> = (1..=30)
+            .flat_map(|user| {
+                (1..=12).map(move |item| {
+                    let rating =
+                        if (user % 2 == 0) == (item % 2 == 0) { 5.0 } else { 1.0 };
+                    Rating::new(user, item, rating)
+                })
+            })
+            .collect();
+        let path = std::env::temp_dir().join("rec_rsys_test_quickstart_ratings.dat");
+        let options = CsvOptions {
+            delimiter: "::".to_string(),
+            has_header: false,
+        };
+        Dataset::new(ratings).write_csv(&path, &options).unwrap();
+        let (model, report) = movielens(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(report.num_ratings, 360);
+        assert_eq!((report.num_users, report.num_items), (30, 12));
+        assert_eq!(
+            report.train_interactions + report.test_interactions,
+            report.num_ratings / 2
+        );
+        assert!(report.train_loss.is_some());
+        assert!(report.top_n.hit_rate > 0.5);
+        assert!(report.to_string().contains("hit_rate@10"));
+        // The even users like the even items.
+        assert!(model.predict(2, 4).unwrap() > model.predict(2, 3).unwrap());
+    }
+
+    #[test]
+    fn test_nothing_to_train_on() {
+        let path = std::env::temp_dir().join("rec_rsys_test_quickstart_u.data");
+        std::fs::write(&path, "1\t10\t2\t0\n2\t10\t1\t0\n").unwrap();
+        let result = movielens(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(result.is_err());
+        assert!(movielens("missing/u.data").is_err());
+    }
+}
@@ -0,0 +1,260 @@
+//! # Recommenders
+//! Common interface of the algorithms that learn from a [`Dataset`] and recommend
+//! items to its users.
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+
+use serde::{Deserialize, Serialize};
+
+use crate::dataset::Dataset;
+use crate::errors::Result;
+use crate::exclusions::ExclusionStore;
+use crate::memory::MemoryFootprint;
+use crate::sets::IdSet;
+
+/// An item recommended to a user.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Recommendation {
+    pub item_id: u32,
+    /// How much the item is recommended, higher is better.
+    pub score: f32,
+}
+
+pub trait Recommender {
+    /// Learns from the ratings of the dataset.
+    fn fit(&mut self, dataset: &Dataset) -> Result<()>;
+
+    /// Recommends the best items the user has not rated yet.
+    ///
+    /// ## Parameters:
+    /// * `user_id`: The user to recommend items to.
+    /// * `num_items`: The maximum number of items to recommend.
+    ///
+    /// ## Returns:
+    /// * The recommendations, best first.
+    fn recommend(&self, user_id: u32, num_items: usize) -> Vec<Recommendation>;
+
+    /// Recommends like [`Recommender::recommend`] while skipping the items excluded
+    /// for the user in the store.
+    fn recommend_excluding(
+        &self,
+        user_id: u32,
+        num_items: usize,
+        exclusions: &ExclusionStore,
+    ) -> Vec<Recommendation> {
+        self.recommend_iter(user_id)
+            .filter(|r| !exclusions.is_excluded(user_id, r.item_id))
+            .take(num_items)
+            .collect()
+    }
+
+    /// Recommends the items the user has not rated yet one at a time, best first, so
+    /// a consumer stopping early, e.g. once a business rule is satisfied, does not
+    /// pay for ordering the whole catalog.
+    ///
+    /// The default implementation ranks the whole [`Recommender::recommend`] list,
+    /// the algorithms of the crate only order the items as they are consumed.
+    ///
+    /// ## Examples:
+    /// ```
+    /// use rec_rsys::algorithms::most_popular::MostPopular;
+    /// use rec_rsys::dataset::{Dataset, Rating};
+    /// use rec_rsys::recommender::Recommender;
+    /// let mut model = MostPopular::default();
+    /// model.fit(&Dataset::new(vec![
+    ///     Rating::new(1, 10, 1.0), Rating::new(2, 10, 1.0), Rating::new(2, 11, 1.0),
+    ///     Rating::new(3, 12, 1.0), Rating::new(3, 13, 1.0), Rating::new(4, 13, 1.0),
+    /// ])).unwrap();
+    /// // The first two recommendations of user 1 that are not item 13.
+    /// let picked: Vec<u32> = model
+    ///     .recommend_iter(1)
+    ///     .filter(|r| r.item_id != 13)
+    ///     .take(2)
+    ///     .map(|r| r.item_id)
+    ///     .collect();
+    /// assert_eq!(picked, [11, 12]);
+    /// ```
+    fn recommend_iter(&self, user_id: u32) -> RankedItems {
+        RankedItems::from(self.recommend(user_id, usize::MAX))
+    }
+
+    /// Estimates the rating the user would give to the item, for the algorithms that
+    /// are able to.
+    fn predict(&self, _user_id: u32, _item_id: u32) -> Option<f32> {
+        None
+    }
+}
+
+/// A [`Recommendation`] ordered by score, then by item id with the smallest first
+/// so the order is deterministic.
+#[derive(Debug, Clone, Copy)]
+struct Ranked(Recommendation);
+
+impl Ord for Ranked {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0
+            .score
+            .total_cmp(&other.0.score)
+            .then_with(|| other.0.item_id.cmp(&self.0.item_id))
+    }
+}
+
+impl PartialOrd for Ranked {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Ranked {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Ranked {}
+
+/// # Ranked items
+/// Recommendations yielded best first, see [`Recommender::recommend_iter`]. The
+/// items are kept in a binary heap: building it is linear in the number of items
+/// and each yielded item costs a logarithmic time, instead of sorting them all.
+#[derive(Debug, Clone, Default)]
+pub struct RankedItems {
+    heap: BinaryHeap<Ranked>,
+}
+
+impl RankedItems {
+    /// Ranks the scored items that are not excluded.
+    pub(crate) fn new(scores: HashMap<u32, f32>, excluded: Option<&IdSet>) -> Self {
+        scores
+            .into_iter()
+            .filter(|(item_id, _)| excluded.is_none_or(|e| !e.contains(*item_id)))
+            .map(|(item_id, score)| Recommendation { item_id, score })
+            .collect()
+    }
+}
+
+/// Sorts scored items best first, in the order [`RankedItems`] yields them, for the
+/// recommenders whose scores do not depend on the user.
+pub(crate) fn sort_ranked(scores: HashMap<u32, f32>) -> Vec<Recommendation> {
+    let mut ranked: Vec<Ranked> = scores
+        .into_iter()
+        .map(|(item_id, score)| Ranked(Recommendation { item_id, score }))
+        .collect();
+    ranked.sort_unstable_by(|a, b| b.cmp(a));
+    ranked.into_iter().map(|ranked| ranked.0).collect()
+}
+
+/// The items of a list sorted by [`sort_ranked`] that are not excluded, best first.
+pub(crate) fn unseen<'a>(
+    ranked: &'a [Recommendation],
+    excluded: Option<&'a IdSet>,
+) -> impl Iterator<Item = Recommendation> + 'a {
+    ranked
+        .iter()
+        .filter(move |r| excluded.is_none_or(|e| !e.contains(r.item_id)))
+        .copied()
+}
+
+impl FromIterator<Recommendation> for RankedItems {
+    fn from_iter<I: IntoIterator<Item = Recommendation>>(iter: I) -> Self {
+        RankedItems {
+            heap: iter.into_iter().map(Ranked).collect(),
+        }
+    }
+}
+
+impl From<Vec<Recommendation>> for RankedItems {
+    fn from(recommendations: Vec<Recommendation>) -> Self {
+        recommendations.into_iter().collect()
+    }
+}
+
+impl Iterator for RankedItems {
+    type Item = Recommendation;
+
+    fn next(&mut self) -> Option<Recommendation> {
+        self.heap.pop().map(|ranked| ranked.0)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.heap.len(), Some(self.heap.len()))
+    }
+}
+
+impl ExactSizeIterator for RankedItems {}
+
+impl MemoryFootprint for Recommendation {}
+
+impl MemoryFootprint for RankedItems {
+    fn heap_size(&self) -> usize {
+        self.heap.capacity() * std::mem::size_of::<Ranked>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_top_items() {
+        let scores = HashMap::from([(1, 0.5), (2, 0.9), (3, 0.5), (4, 0.7)]);
+        let excluded: IdSet = [4].into_iter().collect();
+        let top: Vec<Recommendation> =
+            RankedItems::new(scores, Some(&excluded)).take(3).collect();
+        assert_eq!(
+            top.iter().map(|r| r.item_id).collect::<Vec<u32>>(),
+            vec![2, 1, 3]
+        );
+    }
+
+    #[test]
+    fn test_sorted_items() {
+        let scores = HashMap::from([(1, 0.5), (2, 0.9), (3, 0.5), (4, 0.7)]);
+        let ranked = sort_ranked(scores.clone());
+        let expected: Vec<Recommendation> = RankedItems::new(scores, None).collect();
+        assert_eq!(ranked, expected);
+        let excluded: IdSet = [4].into_iter().collect();
+        let top: Vec<u32> = unseen(&ranked, Some(&excluded))
+            .map(|r| r.item_id)
+            .collect();
+        assert_eq!(top, vec![2, 1, 3]);
+    }
+
+    #[test]
+    fn test_ranked_items() {
+        let scores = HashMap::from([(1, 0.5), (2, f32::NAN), (3, 0.5), (4, 0.7)]);
+        let mut ranked = RankedItems::new(scores, None);
+        assert_eq!(ranked.len(), 4);
+        assert_eq!(ranked.next().map(|r| r.item_id), Some(2));
+        let rest: Vec<u32> = ranked.map(|r| r.item_id).collect();
+        assert_eq!(rest, vec![4, 1, 3]);
+        assert_eq!(RankedItems::default().next(), None);
+    }
+
+    #[test]
+    fn test_recommend_excluding() {
+        use crate::algorithms::most_popular::MostPopular;
+        use crate::dataset::Rating;
+        use crate::exclusions::ExclusionReason;
+
+        let mut model = MostPopular::default();
+        model
+            .fit(&Dataset::new(vec![
+                Rating::new(1, 10, 1.0),
+                Rating::new(2, 10, 1.0),
+                Rating::new(2, 11, 1.0),
+                Rating::new(3, 12, 1.0),
+            ]))
+            .unwrap();
+        let mut exclusions = ExclusionStore::new();
+        exclusions.exclude(4, 10, ExclusionReason::Disliked);
+        let recommended: Vec<u32> = model
+            .recommend_excluding(4, 2, &exclusions)
+            .iter()
+            .map(|r| r.item_id)
+            .collect();
+        assert_eq!(recommended, vec![11, 12]);
+        let all = model.recommend_excluding(4, usize::MAX, &exclusions);
+        assert_eq!(all.len(), 2);
+    }
+}
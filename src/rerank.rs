@@ -0,0 +1,229 @@
+//! # Re-ranking
+//! Post-processing of the candidates a recommender scored, to enforce constraints
+//! the scores alone do not, before the final list is served.
+use std::collections::{HashMap, HashSet};
+
+use rand::Rng;
+
+use crate::recommender::Recommendation;
+use crate::sampling::shuffled_reservoir;
+
+/// # Exposure quotas
+/// Builds a list of `num_items` recommendations in which every group of items has
+/// at least its quota of items, when there are enough candidates of the group.
+/// Each position takes the best remaining candidate, unless the remaining positions
+/// are only just enough for the unmet quotas, in which case it takes the best
+/// candidate of a group still below its quota.
+///
+/// ## Parameters:
+/// * `candidates`: The scored candidates, in any order.
+/// * `item_groups`: The group of each item, such as its provider.
+/// * `quotas`: The minimum number of items of each group in the list.
+/// * `num_items`: The length of the list.
+///
+/// ## Returns:
+/// * The list, sorted by score when no quota forced an item in.
+///
+/// ## Examples:
+/// ```
+/// use std::collections::HashMap;
+/// use rec_rsys::recommender::Recommendation;
+/// use rec_rsys::rerank::enforce_exposure_quotas;
+/// let candidates: Vec<Recommendation> = [(1, 0.9), (2, 0.8), (3, 0.7), (4, 0.1)]
+///     .iter()
+///     .map(|&(item_id, score)| Recommendation { item_id, score })
+///     .collect();
+/// let groups = HashMap::from([(4, "small".to_string())]);
+/// let quotas = HashMap::from([("small".to_string(), 1)]);
+/// let list = enforce_exposure_quotas(&candidates, &groups, &quotas, 3);
+/// let ids: Vec<u32> = list.iter().map(|r| r.item_id).collect();
+/// assert_eq!(ids, vec![1, 2, 4]);
+/// ```
+pub fn enforce_exposure_quotas(
+    candidates: &[Recommendation],
+    item_groups: &HashMap<u32, String>,
+    quotas: &HashMap<String, usize>,
+    num_items: usize,
+) -> Vec<Recommendation> {
+    let mut remaining: Vec<Recommendation> = candidates.to_vec();
+    remaining.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| a.item_id.cmp(&b.item_id))
+    });
+    // Quotas that cannot be met are lowered to the number of candidates.
+    let mut unmet: HashMap<&str, usize> = quotas
+        .iter()
+        .map(|(group, quota)| {
+            let available = remaining
+                .iter()
+                .filter(|r| item_groups.get(&r.item_id) == Some(group))
+                .count();
+            (group.as_str(), (*quota).min(available))
+        })
+        .filter(|(_, quota)| *quota > 0)
+        .collect();
+    let group_of = |item_id: u32| item_groups.get(&item_id).map(String::as_str);
+    let mut list = Vec::with_capacity(num_items.min(remaining.len()));
+    while list.len() < num_items && !remaining.is_empty() {
+        let slots = num_items - list.len();
+        let forced = unmet.values().sum::<usize>() >= slots;
+        let position = match forced {
+            true => remaining
+                .iter()
+                .position(|r| group_of(r.item_id).is_some_and(|g| unmet.contains_key(g))),
+            false => Some(0),
+        };
+        let Some(position) = position else {
+            break;
+        };
+        let chosen = remaining.remove(position);
+        if let Some(group) = group_of(chosen.item_id) {
+            if let Some(quota) = unmet.get_mut(group) {
+                *quota -= 1;
+                if *quota == 0 {
+                    unmet.remove(group);
+                }
+            }
+        }
+        list.push(chosen);
+    }
+    list
+}
+
+/// # Exploration injection
+/// Replaces random positions of a list with items drawn from a pool, so the
+/// feedback also covers items the model would not recommend yet (epsilon-greedy
+/// exploration). An injected item takes the position and the score of the item it
+/// replaces, so the list stays sorted.
+///
+/// ## Parameters:
+/// * `recommendations`: The list, best first.
+/// * `pool`: The items to explore, e.g. new or rarely shown ones. Items already in
+///   the list are skipped.
+/// * `rate`: The probability of each position to be replaced, clamped to [0, 1].
+/// * `rng`: The random number generator.
+///
+/// ## Returns:
+/// * The list with at most as many items replaced as the pool has new items.
+///
+/// ## Examples:
+/// ```
+/// use rand::rngs::StdRng;
+/// use rand::SeedableRng;
+/// use rec_rsys::recommender::Recommendation;
+/// use rec_rsys::rerank::inject_exploration;
+/// let list: Vec<Recommendation> = [(1, 0.9), (2, 0.8), (3, 0.7)]
+///     .iter()
+///     .map(|&(item_id, score)| Recommendation { item_id, score })
+///     .collect();
+/// let mut rng = StdRng::seed_from_u64(42);
+/// let explored = inject_exploration(&list, &[2, 40, 41], 1.0, &mut rng);
+/// let ids: Vec<u32> = explored.iter().map(|r| r.item_id).collect();
+/// assert_eq!(ids.iter().filter(|&&id| id >= 40).count(), 2);
+/// assert_eq!(inject_exploration(&list, &[40], 0.0, &mut rng), list);
+/// ```
+pub fn inject_exploration<R: Rng + ?Sized>(
+    recommendations: &[Recommendation],
+    pool: &[u32],
+    rate: f32,
+    rng: &mut R,
+) -> Vec<Recommendation> {
+    let rate = rate.clamp(0.0, 1.0) as f64;
+    let mut explored = recommendations.to_vec();
+    let positions: Vec<usize> =
+        (0..explored.len()).filter(|_| rng.gen_bool(rate)).collect();
+    if positions.is_empty() {
+        return explored;
+    }
+    let listed: HashSet<u32> = recommendations.iter().map(|r| r.item_id).collect();
+    let unseen = pool.iter().filter(|id| !listed.contains(id)).copied();
+    let items = shuffled_reservoir(unseen, positions.len(), rng);
+    for (position, item_id) in positions.into_iter().zip(items) {
+        explored[position].item_id = item_id;
+    }
+    explored
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidates() -> Vec<Recommendation> {
+        (1..=10)
+            .map(|item_id| Recommendation {
+                item_id,
+                score: 1.0 / item_id as f32,
+            })
+            .collect()
+    }
+
+    fn groups() -> HashMap<u32, String> {
+        (1..=10)
+            .map(|item_id| {
+                let group = if item_id <= 7 { "major" } else { "indie" };
+                (item_id, group.to_string())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_quotas_are_met() {
+        let quotas = HashMap::from([("indie".to_string(), 2)]);
+        let list = enforce_exposure_quotas(&candidates(), &groups(), &quotas, 5);
+        let ids: Vec<u32> = list.iter().map(|r| r.item_id).collect();
+        assert_eq!(ids, vec![1, 2, 3, 8, 9]);
+    }
+
+    #[test]
+    fn test_without_quotas() {
+        let list = enforce_exposure_quotas(&candidates(), &groups(), &HashMap::new(), 3);
+        let ids: Vec<u32> = list.iter().map(|r| r.item_id).collect();
+        assert_eq!(ids, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_impossible_quotas_are_lowered() {
+        let quotas = HashMap::from([("indie".to_string(), 5), ("other".to_string(), 1)]);
+        let list = enforce_exposure_quotas(&candidates(), &groups(), &quotas, 4);
+        let ids: Vec<u32> = list.iter().map(|r| r.item_id).collect();
+        assert_eq!(ids, vec![1, 8, 9, 10]);
+        assert_eq!(enforce_exposure_quotas(&[], &groups(), &quotas, 4), vec![]);
+    }
+
+    #[test]
+    fn test_exploration_keeps_the_scores() {
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+
+        let list = candidates();
+        let pool: Vec<u32> = (5..20).collect();
+        let mut rng = StdRng::seed_from_u64(1);
+        let explored = inject_exploration(&list, &pool, 0.5, &mut rng);
+        assert_eq!(explored.len(), list.len());
+        assert!(explored.iter().zip(&list).all(|(e, r)| e.score == r.score));
+        let ids: HashSet<u32> = explored.iter().map(|r| r.item_id).collect();
+        assert_eq!(ids.len(), list.len());
+        assert_ne!(explored, list);
+        assert_eq!(inject_exploration(&list, &[], 1.0, &mut rng), list);
+    }
+
+    #[test]
+    fn test_exploration_of_long_lists() {
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+
+        let list: Vec<Recommendation> = (1..=25)
+            .map(|item_id| Recommendation {
+                item_id,
+                score: 1.0 / item_id as f32,
+            })
+            .collect();
+        let pool: Vec<u32> = (100..1100).collect();
+        let mut rng = StdRng::seed_from_u64(3);
+        let explored = inject_exploration(&list, &pool, 1.0, &mut rng);
+        assert!(explored.iter().all(|r| r.item_id >= 100));
+        let ids: HashSet<u32> = explored.iter().map(|r| r.item_id).collect();
+        assert_eq!(ids.len(), 25);
+    }
+}
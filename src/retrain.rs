@@ -0,0 +1,311 @@
+//! # Retrain scheduling
+//! Guards the retraining of models called from the request path of a service,
+//! e.g. when new ratings arrive: a model is never retrained twice at the same
+//! time, the requests arriving during a retrain are merged into a single rerun,
+//! and callers are told when to back off instead of piling work up.
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::algorithms::config::{ensure, AlgorithmConfig};
+use crate::errors::Result;
+use crate::memory::MemoryFootprint;
+
+/// # Retrain gate configuration
+/// Limits of a [`RetrainGate`].
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::algorithms::config::AlgorithmConfig;
+/// use rec_rsys::retrain::RetrainGateConfig;
+/// let config = RetrainGateConfig::default().set_max_concurrent(2).set_min_interval_ms(60_000);
+/// assert!(config.validate().is_ok());
+/// assert!(RetrainGateConfig::default().set_max_concurrent(0).validate().is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetrainGateConfig {
+    /// Maximum number of models retrained at the same time.
+    pub max_concurrent: usize,
+    /// Minimum time between the starts of two retrains of a model, in
+    /// milliseconds.
+    pub min_interval_ms: u64,
+}
+
+impl Default for RetrainGateConfig {
+    fn default() -> Self {
+        RetrainGateConfig {
+            max_concurrent: 1,
+            min_interval_ms: 0,
+        }
+    }
+}
+
+impl RetrainGateConfig {
+    pub fn set_max_concurrent(mut self, max_concurrent: usize) -> Self {
+        self.max_concurrent = max_concurrent;
+        self
+    }
+    pub fn set_min_interval_ms(mut self, min_interval_ms: u64) -> Self {
+        self.min_interval_ms = min_interval_ms;
+        self
+    }
+}
+
+impl AlgorithmConfig for RetrainGateConfig {
+    fn validate(&self) -> Result<()> {
+        ensure(self.max_concurrent > 0, "max_concurrent", "greater than 0")
+    }
+}
+
+/// What happened to a retrain request, see [`RetrainGate::run`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetrainOutcome {
+    /// The model was retrained, once plus once more if requests were coalesced
+    /// while it was training.
+    Ran { runs: usize },
+    /// The model is being retrained: the request will be served by a rerun once the
+    /// current retrain is done.
+    Coalesced,
+    /// The model was retrained too recently, retry after the given time.
+    Throttled { retry_after: Duration },
+    /// As many models as allowed are being retrained, retry later.
+    Saturated,
+}
+
+/// The load of a [`RetrainGate`], to expose as backpressure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct GateStatus {
+    /// Models being retrained.
+    pub running: usize,
+    /// Models with coalesced requests waiting for a rerun.
+    pub pending: usize,
+    /// Whether no other model can start retraining.
+    pub saturated: bool,
+}
+
+#[derive(Debug, Default)]
+struct ModelState {
+    running: bool,
+    pending: bool,
+    last_start: Option<Instant>,
+}
+
+/// # Retrain gate
+/// Runs the retrains of models, at most one at a time per model and at most
+/// `max_concurrent` overall. Shared between threads, e.g. in an `Arc`.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::retrain::{RetrainGate, RetrainGateConfig, RetrainOutcome};
+/// let gate = RetrainGate::new(RetrainGateConfig::default()).unwrap();
+/// let outcome = gate.run("item_knn", || {
+///     // A retrain requested from another thread meanwhile would be coalesced.
+///     assert!(gate.is_running("item_knn"));
+///     Ok(())
+/// });
+/// assert_eq!(outcome.unwrap(), RetrainOutcome::Ran { runs: 1 });
+/// assert_eq!(gate.status().running, 0);
+/// ```
+#[derive(Debug)]
+pub struct RetrainGate {
+    config: RetrainGateConfig,
+    models: Mutex<HashMap<String, ModelState>>,
+}
+
+/// Marks the retrain of a model as done when dropped, even if it panicked.
+struct Running<'a> {
+    gate: &'a RetrainGate,
+    model: &'a str,
+}
+
+impl Drop for Running<'_> {
+    fn drop(&mut self) {
+        if let Some(state) = self.gate.models().get_mut(self.model) {
+            state.running = false;
+            state.pending = false;
+        }
+    }
+}
+
+impl RetrainGate {
+    pub fn new(config: RetrainGateConfig) -> Result<Self> {
+        config.validate()?;
+        Ok(RetrainGate {
+            config,
+            models: Mutex::new(HashMap::new()),
+        })
+    }
+
+    fn models(&self) -> MutexGuard<'_, HashMap<String, ModelState>> {
+        // The states are updated in single statements, a panic cannot leave them
+        // inconsistent.
+        self.models
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// # Run
+    /// Retrains a model unless it is already being retrained, too recently or the
+    /// gate is saturated. The retrain is rerun as long as requests were coalesced
+    /// during the previous run.
+    ///
+    /// ## Parameters:
+    /// * `model`: The name of the model.
+    /// * `retrain`: Retrains the model, called on the current thread.
+    ///
+    /// ## Returns:
+    /// * What happened to the request, or the error of the retrain, in which case
+    ///   the coalesced requests are dropped with it.
+    pub fn run<F: FnMut() -> Result<()>>(
+        &self,
+        model: &str,
+        mut retrain: F,
+    ) -> Result<RetrainOutcome> {
+        {
+            let mut models = self.models();
+            let running = models.values().filter(|state| state.running).count();
+            let state = models.entry(model.to_string()).or_default();
+            if state.running {
+                state.pending = true;
+                return Ok(RetrainOutcome::Coalesced);
+            }
+            let min_interval = Duration::from_millis(self.config.min_interval_ms);
+            if let Some(elapsed) = state.last_start.map(|last| last.elapsed()) {
+                if elapsed < min_interval {
+                    return Ok(RetrainOutcome::Throttled {
+                        retry_after: min_interval - elapsed,
+                    });
+                }
+            }
+            if running >= self.config.max_concurrent {
+                return Ok(RetrainOutcome::Saturated);
+            }
+            state.running = true;
+            state.last_start = Some(Instant::now());
+        }
+        let _running = Running { gate: self, model };
+        let mut runs = 0;
+        loop {
+            runs += 1;
+            retrain()?;
+            let mut models = self.models();
+            let state = models.entry(model.to_string()).or_default();
+            if !state.pending {
+                return Ok(RetrainOutcome::Ran { runs });
+            }
+            state.pending = false;
+            state.last_start = Some(Instant::now());
+        }
+    }
+
+    /// Whether the model is being retrained.
+    pub fn is_running(&self, model: &str) -> bool {
+        self.models().get(model).is_some_and(|state| state.running)
+    }
+
+    pub fn status(&self) -> GateStatus {
+        let models = self.models();
+        let running = models.values().filter(|state| state.running).count();
+        GateStatus {
+            running,
+            pending: models.values().filter(|state| state.pending).count(),
+            saturated: running >= self.config.max_concurrent,
+        }
+    }
+}
+
+impl MemoryFootprint for RetrainGateConfig {}
+
+impl MemoryFootprint for GateStatus {}
+
+impl MemoryFootprint for RetrainGate {
+    fn heap_size(&self) -> usize {
+        let models = self.models();
+        models.capacity() * (size_of::<(String, ModelState)>() + 1)
+            + models.keys().map(String::heap_size).sum::<usize>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+    use std::thread;
+
+    use super::*;
+    use crate::errors::Error;
+
+    #[test]
+    fn test_coalesces_concurrent_requests() {
+        let gate = RetrainGate::new(RetrainGateConfig::default()).unwrap();
+        let (started, wait_started) = mpsc::channel();
+        let (release, wait_release) = mpsc::channel::<()>();
+        thread::scope(|scope| {
+            let gate = &gate;
+            let worker = scope.spawn(move || {
+                let mut calls = 0;
+                gate.run("mf", || {
+                    calls += 1;
+                    if calls == 1 {
+                        started.send(()).unwrap();
+                        wait_release.recv().unwrap();
+                    }
+                    Ok(())
+                })
+            });
+            wait_started.recv().unwrap();
+            assert_eq!(
+                gate.run("mf", || Ok(())).unwrap(),
+                RetrainOutcome::Coalesced
+            );
+            assert_eq!(
+                gate.run("mf", || Ok(())).unwrap(),
+                RetrainOutcome::Coalesced
+            );
+            assert_eq!(
+                gate.run("knn", || Ok(())).unwrap(),
+                RetrainOutcome::Saturated
+            );
+            assert_eq!(
+                gate.status(),
+                GateStatus {
+                    running: 1,
+                    pending: 1,
+                    saturated: true
+                }
+            );
+            release.send(()).unwrap();
+            assert_eq!(
+                worker.join().unwrap().unwrap(),
+                RetrainOutcome::Ran { runs: 2 }
+            );
+        });
+        assert_eq!(gate.status(), GateStatus::default());
+    }
+
+    #[test]
+    fn test_throttles_and_releases_on_error() {
+        let config = RetrainGateConfig::default().set_min_interval_ms(60_000);
+        let gate = RetrainGate::new(config).unwrap();
+        let failed = gate.run("mf", || {
+            Err(Error::TrainingDiverged {
+                epoch: 3,
+                loss: f64::NAN,
+            })
+        });
+        assert!(failed.is_err());
+        assert!(!gate.is_running("mf"));
+        match gate.run("mf", || Ok(())).unwrap() {
+            RetrainOutcome::Throttled { retry_after } => {
+                assert!(retry_after <= Duration::from_secs(60))
+            },
+            outcome => panic!("unexpected {:?}", outcome),
+        }
+        assert_eq!(
+            gate.run("knn", || Ok(())).unwrap(),
+            RetrainOutcome::Ran { runs: 1 }
+        );
+    }
+}
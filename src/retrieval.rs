@@ -0,0 +1,457 @@
+//! # Two-tower retrieval
+//! Serves models made of two encoders, or towers, trained elsewhere: one maps a
+//! user to a vector, the other maps an item to a vector of the same space, and the
+//! dot product of the two scores the pair. The items are encoded once into an
+//! index, each request only encodes the user and searches the index.
+#[cfg(feature = "ann")]
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "ann")]
+use crate::algorithms::config::AlgorithmConfig;
+use crate::catalog::ItemCatalog;
+use crate::errors::{Error, Result};
+use crate::factors::FactorMatrix;
+use crate::memory::MemoryFootprint;
+#[cfg(feature = "ann")]
+use crate::pairwise::{signature, LshBlocking};
+use crate::parallelism::default_parallelism;
+use crate::parallelism::prelude::*;
+use crate::recommender::{RankedItems, Recommendation};
+
+/// # Tower
+/// Encodes an input, the features of a user or of an item, into a vector. Any
+/// closure `Fn(&T) -> Vec<f32>` is a tower, e.g. one calling an ONNX runtime or
+/// looking the vector up in an exported embedding table.
+pub trait Tower<T: ?Sized> {
+    fn encode(&self, input: &T) -> Vec<f32>;
+}
+
+impl<T: ?Sized, F: Fn(&T) -> Vec<f32>> Tower<T> for F {
+    fn encode(&self, input: &T) -> Vec<f32> {
+        self(input)
+    }
+}
+
+/// How a [`RetrievalIndex`] searches the items.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "name", rename_all = "snake_case")]
+pub enum Search {
+    /// Scores every item, exact.
+    #[default]
+    BruteForce,
+    /// Only scores the items sharing a random hyperplane bucket with the query in
+    /// one of the tables, see [`LshBlocking`]. The buckets follow the angle of the
+    /// vectors, so the search misses items whose dot product is high because of
+    /// their norm rather than their direction. Needs the `ann` feature.
+    #[cfg(feature = "ann")]
+    Lsh(LshBlocking),
+}
+
+/// The buckets of the items in every LSH table.
+#[cfg(feature = "ann")]
+#[derive(Debug, Clone, PartialEq)]
+struct LshTables {
+    hyperplanes: Vec<Vec<Vec<f32>>>,
+    buckets: Vec<HashMap<u64, Vec<usize>>>,
+}
+
+#[cfg(feature = "ann")]
+impl LshTables {
+    fn new(blocking: &LshBlocking, vectors: &[Vec<f32>]) -> Self {
+        let dimensions = vectors.first().map_or(0, Vec::len);
+        let hyperplanes = blocking.hyperplanes(dimensions);
+        let buckets = hyperplanes
+            .iter()
+            .map(|hyperplanes| {
+                let mut buckets: HashMap<u64, Vec<usize>> = HashMap::new();
+                vectors.iter().enumerate().for_each(|(index, vector)| {
+                    buckets
+                        .entry(signature(vector, hyperplanes))
+                        .or_default()
+                        .push(index);
+                });
+                buckets
+            })
+            .collect();
+        LshTables {
+            hyperplanes,
+            buckets,
+        }
+    }
+
+    /// The items sharing a bucket with the query in at least one table.
+    fn candidates(&self, query: &[f32]) -> HashSet<usize> {
+        self.hyperplanes
+            .iter()
+            .zip(&self.buckets)
+            .filter_map(|(hyperplanes, buckets)| {
+                buckets.get(&signature(query, hyperplanes))
+            })
+            .flatten()
+            .copied()
+            .collect()
+    }
+}
+
+/// # Retrieval index
+/// The encoded items, searched for the largest dot products with a query.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::retrieval::{RetrievalIndex, Search};
+/// let vectors = vec![vec![1.0, 0.0], vec![0.0, 1.0], vec![2.0, 0.1]];
+/// let index = RetrievalIndex::new(vec![10, 11, 12], vectors.clone(), Search::BruteForce).unwrap();
+/// let ids: Vec<u32> = index.search(&[1.0, 0.0], 2).iter().map(|r| r.item_id).collect();
+/// assert_eq!(ids, [12, 10]);
+///
+/// # #[cfg(feature = "ann")]
+/// # {
+/// use rec_rsys::pairwise::LshBlocking;
+/// let lsh = Search::Lsh(LshBlocking::default().set_num_tables(4).set_num_hyperplanes(2));
+/// let approximate = RetrievalIndex::new(vec![10, 11, 12], vectors, lsh).unwrap();
+/// assert_eq!(approximate.search(&[1.0, 0.0], 1)[0].item_id, 12);
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetrievalIndex {
+    item_ids: Vec<u32>,
+    vectors: FactorMatrix,
+    search: Search,
+    #[cfg(feature = "ann")]
+    lsh: Option<LshTables>,
+}
+
+impl RetrievalIndex {
+    /// # New
+    /// ## Parameters:
+    /// * `item_ids`: The ids of the items.
+    /// * `vectors`: The encoded items, in the same order, all of the same length.
+    /// * `search`: How the items are searched.
+    ///
+    /// ## Returns:
+    /// * The index, or an error if the ids and vectors do not match or the search
+    ///   is invalid.
+    pub fn new(
+        item_ids: Vec<u32>,
+        vectors: Vec<Vec<f32>>,
+        search: Search,
+    ) -> Result<Self> {
+        if item_ids.len() != vectors.len() {
+            return Err(Error::InvalidData(format!(
+                "{} item ids for {} vectors",
+                item_ids.len(),
+                vectors.len()
+            )));
+        }
+        #[cfg(feature = "ann")]
+        let lsh = match &search {
+            Search::BruteForce => None,
+            Search::Lsh(blocking) => {
+                blocking.validate()?;
+                Some(LshTables::new(blocking, &vectors))
+            },
+        };
+        Ok(RetrievalIndex {
+            item_ids,
+            vectors: FactorMatrix::from_rows(&vectors)?,
+            search,
+            #[cfg(feature = "ann")]
+            lsh,
+        })
+    }
+
+    /// Encodes the items with a tower, in parallel, and indexes them.
+    pub fn encode<T, I>(items: &[(u32, T)], tower: &I, search: Search) -> Result<Self>
+    where
+        T: Sync,
+        I: Tower<T> + Sync,
+    {
+        let vectors: Vec<Vec<f32>> = default_parallelism().install(|| {
+            items
+                .par_iter()
+                .map(|(_, input)| tower.encode(input))
+                .collect()
+        });
+        let item_ids = items.iter().map(|(item_id, _)| *item_id).collect();
+        RetrievalIndex::new(item_ids, vectors, search)
+    }
+
+    pub fn len(&self) -> usize {
+        self.item_ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.item_ids.is_empty()
+    }
+
+    pub fn dimensions(&self) -> usize {
+        self.vectors.dims()
+    }
+
+    pub fn search_method(&self) -> &Search {
+        &self.search
+    }
+
+    /// # Search
+    /// The items with the largest dot product with the query.
+    ///
+    /// ## Parameters:
+    /// * `query`: The encoded user, of the dimensions of the index.
+    /// * `num_items`: The maximum number of items returned.
+    ///
+    /// ## Returns:
+    /// * The items, best first, scored by their dot product. Empty if the query does
+    ///   not have the dimensions of the index.
+    pub fn search(&self, query: &[f32], num_items: usize) -> Vec<Recommendation> {
+        self.ranked(query).take(num_items).collect()
+    }
+
+    /// Like [`RetrievalIndex::search`], skipping the items the catalog does not make
+    /// available at `now`, so the expired items never take the place of valid ones.
+    pub fn search_available(
+        &self,
+        query: &[f32],
+        num_items: usize,
+        catalog: &ItemCatalog,
+        now: u64,
+    ) -> Vec<Recommendation> {
+        self.ranked(query)
+            .filter(|r| catalog.is_available(r.item_id, now))
+            .take(num_items)
+            .collect()
+    }
+
+    /// Removes the items for which the predicate does not hold, e.g. the ones swept
+    /// from the catalog, and rebuilds the search tables.
+    ///
+    /// ## Returns:
+    /// * The number of removed items.
+    pub fn retain<F: FnMut(u32) -> bool>(&mut self, mut keep: F) -> Result<usize> {
+        let (item_ids, vectors): (Vec<u32>, Vec<Vec<f32>>) = self
+            .item_ids
+            .iter()
+            .enumerate()
+            .filter(|(_, item_id)| keep(**item_id))
+            .map(|(index, item_id)| (*item_id, self.vectors.row(index)))
+            .unzip();
+        let removed = self.len() - item_ids.len();
+        if removed > 0 {
+            *self = RetrievalIndex::new(item_ids, vectors, self.search.clone())?;
+        }
+        Ok(removed)
+    }
+
+    /// Like [`RetrievalIndex::search`], ordering the items as they are consumed.
+    pub fn ranked(&self, query: &[f32]) -> RankedItems {
+        if query.len() != self.vectors.dims() {
+            return RankedItems::default();
+        }
+        let score = |index: usize| Recommendation {
+            item_id: self.item_ids[index],
+            score: self.vectors.dot(index, query),
+        };
+        #[cfg(feature = "ann")]
+        if let Some(lsh) = &self.lsh {
+            return lsh.candidates(query).into_iter().map(score).collect();
+        }
+        (0..self.item_ids.len()).map(score).collect()
+    }
+}
+
+/// # Two-tower model
+/// A user tower and the index of the items encoded by the item tower.
+///
+/// ## Examples:
+/// ```
+/// use std::collections::HashMap;
+/// use rec_rsys::retrieval::{Search, TwoTower};
+/// // Embedding tables exported from a model trained elsewhere.
+/// let users = HashMap::from([(1_u32, vec![1.0, 0.0]), (2, vec![0.0, 1.0])]);
+/// let items = vec![(10, vec![0.9, 0.1]), (11, vec![0.1, 0.9])];
+/// let model = TwoTower::new(
+///     move |user_id: &u32| users.get(user_id).cloned().unwrap_or_default(),
+///     &items,
+///     &|values: &Vec<f32>| values.clone(),
+///     Search::BruteForce,
+/// )
+/// .unwrap();
+/// assert_eq!(model.retrieve(&1, 1)[0].item_id, 10);
+/// assert_eq!(model.retrieve(&2, 1)[0].item_id, 11);
+/// assert!(model.retrieve(&3, 1).is_empty());
+/// ```
+pub struct TwoTower<U> {
+    user_tower: U,
+    index: RetrievalIndex,
+}
+
+impl<U> TwoTower<U> {
+    /// # New
+    /// ## Parameters:
+    /// * `user_tower`: Encodes the users at request time.
+    /// * `items`: The items and their features.
+    /// * `item_tower`: Encodes the items, once.
+    /// * `search`: How the encoded items are searched.
+    pub fn new<T, I>(
+        user_tower: U,
+        items: &[(u32, T)],
+        item_tower: &I,
+        search: Search,
+    ) -> Result<Self>
+    where
+        T: Sync,
+        I: Tower<T> + Sync,
+    {
+        Ok(TwoTower {
+            user_tower,
+            index: RetrievalIndex::encode(items, item_tower, search)?,
+        })
+    }
+
+    /// A model over items encoded beforehand.
+    pub fn from_index(user_tower: U, index: RetrievalIndex) -> Self {
+        TwoTower { user_tower, index }
+    }
+
+    pub fn index(&self) -> &RetrievalIndex {
+        &self.index
+    }
+
+    pub fn index_mut(&mut self) -> &mut RetrievalIndex {
+        &mut self.index
+    }
+
+    /// # Retrieve
+    /// Encodes a user and searches the index with the vector.
+    ///
+    /// ## Parameters:
+    /// * `user`: The features of the user.
+    /// * `num_items`: The maximum number of items returned.
+    ///
+    /// ## Returns:
+    /// * The items, best first, empty if the user tower returns a vector of other
+    ///   dimensions, e.g. empty for an unknown user.
+    pub fn retrieve<Q: ?Sized>(&self, user: &Q, num_items: usize) -> Vec<Recommendation>
+    where
+        U: Tower<Q>,
+    {
+        self.index.search(&self.user_tower.encode(user), num_items)
+    }
+}
+
+impl MemoryFootprint for Search {}
+
+impl MemoryFootprint for RetrievalIndex {
+    fn heap_size(&self) -> usize {
+        #[cfg(not(feature = "ann"))]
+        let lsh = 0;
+        #[cfg(feature = "ann")]
+        let lsh = self.lsh.as_ref().map_or(0, |lsh| {
+            let hyperplanes: usize =
+                lsh.hyperplanes.iter().flatten().map(Vec::heap_size).sum();
+            hyperplanes + lsh.buckets.heap_size()
+        });
+        self.item_ids.heap_size() + self.vectors.heap_size() + lsh
+    }
+}
+
+impl<U> MemoryFootprint for TwoTower<U> {
+    /// The user tower is not counted, it is opaque.
+    fn heap_size(&self) -> usize {
+        self.index.heap_size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use super::*;
+    use crate::catalog::{Schema, Validity};
+
+    fn vectors(n: usize, seed: u64) -> Vec<Vec<f32>> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        (0..n)
+            .map(|_| (0..8).map(|_| rng.gen_range(-1.0..1.0)).collect())
+            .collect()
+    }
+
+    #[cfg(feature = "ann")]
+    #[test]
+    fn test_lsh_recall() {
+        let items = vectors(2_000, 1);
+        let ids: Vec<u32> = (0..items.len() as u32).collect();
+        let exact =
+            RetrievalIndex::new(ids.clone(), items.clone(), Search::BruteForce).unwrap();
+        let blocking = LshBlocking::default()
+            .set_num_tables(8)
+            .set_num_hyperplanes(6);
+        let lsh = RetrievalIndex::new(ids, items, Search::Lsh(blocking)).unwrap();
+        let mut found = 0;
+        for query in vectors(50, 2) {
+            let expected: HashSet<u32> =
+                exact.search(&query, 10).iter().map(|r| r.item_id).collect();
+            found += lsh
+                .search(&query, 10)
+                .iter()
+                .filter(|r| expected.contains(&r.item_id))
+                .count();
+        }
+        assert!(found >= 400, "recall of {} out of 500", found);
+    }
+
+    #[test]
+    fn test_expired_items() {
+        let items = vectors(50, 3);
+        let ids: Vec<u32> = (0..50).collect();
+        #[cfg(feature = "ann")]
+        let search = Search::Lsh(LshBlocking::default().set_num_tables(2));
+        #[cfg(not(feature = "ann"))]
+        let search = Search::BruteForce;
+        let mut index = RetrievalIndex::new(ids, items, search).unwrap();
+        let query = vectors(1, 4).remove(0);
+        let best: Vec<u32> = index.search(&query, 3).iter().map(|r| r.item_id).collect();
+
+        let mut catalog = ItemCatalog::new(Schema::new());
+        catalog.insert(best[0], Default::default()).unwrap();
+        catalog
+            .set_validity(best[0], Validity::with_ttl(0, 10))
+            .unwrap();
+        let available = index.search_available(&query, 2, &catalog, 10);
+        let available: Vec<u32> = available.iter().map(|r| r.item_id).collect();
+        assert_eq!(available, best[1..]);
+        assert_eq!(
+            index.search_available(&query, 1, &catalog, 5)[0].item_id,
+            best[0]
+        );
+
+        let swept = catalog.sweep(10);
+        assert_eq!(
+            index.retain(|item_id| !swept.contains(&item_id)).unwrap(),
+            1
+        );
+        assert_eq!(index.len(), 49);
+        assert_eq!(
+            index.search(&query, 2),
+            index.search_available(&query, 2, &catalog, 10)
+        );
+        assert_eq!(index.retain(|_| true).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_invalid_index() {
+        assert!(RetrievalIndex::new(vec![1], vec![], Search::BruteForce).is_err());
+        let ragged = vec![vec![1.0], vec![1.0, 2.0]];
+        assert!(RetrievalIndex::new(vec![1, 2], ragged, Search::BruteForce).is_err());
+        #[cfg(feature = "ann")]
+        {
+            let invalid = Search::Lsh(LshBlocking::default().set_num_tables(0));
+            assert!(RetrievalIndex::new(vec![], vec![], invalid).is_err());
+        }
+        let index =
+            RetrievalIndex::new(vec![1], vec![vec![1.0, 2.0]], Search::BruteForce);
+        assert!(index.unwrap().search(&[1.0], 5).is_empty());
+    }
+}
@@ -0,0 +1,199 @@
+//! # RFM segmentation
+//! Scores users of a transaction log on recency, frequency and monetary value, the
+//! classic way to split customers in segments such as champions or users at risk
+//! of churning. The segments can select a strategy per user or break an
+//! evaluation down by cohort.
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::dataset::Dataset;
+use crate::statistics::{median, quartiles};
+
+/// Segment of a user, from its RFM scores.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Segment {
+    /// Bought recently and often.
+    Champions,
+    /// Buys regularly.
+    Loyal,
+    /// Bought recently for the first times.
+    NewCustomers,
+    /// Used to buy often but not lately.
+    AtRisk,
+    /// Bought rarely, a long time ago.
+    Hibernating,
+    /// Everyone in between.
+    NeedsAttention,
+}
+
+impl Segment {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Segment::Champions => "champions",
+            Segment::Loyal => "loyal",
+            Segment::NewCustomers => "new_customers",
+            Segment::AtRisk => "at_risk",
+            Segment::Hibernating => "hibernating",
+            Segment::NeedsAttention => "needs_attention",
+        }
+    }
+}
+
+/// The raw values and the quartile scores of a user, from 1 (worst) to 4 (best).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RfmScore {
+    pub user_id: u32,
+    /// Seconds since the last transaction.
+    pub recency: u64,
+    /// Number of transactions.
+    pub frequency: usize,
+    /// Sum of the values of the transactions, the `rating` of the dataset.
+    pub monetary: f32,
+    pub recency_score: u8,
+    pub frequency_score: u8,
+    pub monetary_score: u8,
+}
+
+impl RfmScore {
+    /// The three scores as the usual code, e.g. `"434"`.
+    pub fn code(&self) -> String {
+        format!(
+            "{}{}{}",
+            self.recency_score, self.frequency_score, self.monetary_score
+        )
+    }
+
+    pub fn segment(&self) -> Segment {
+        match (self.recency_score, self.frequency_score) {
+            (4, 4) => Segment::Champions,
+            (3..=4, 3..=4) => Segment::Loyal,
+            (4, _) => Segment::NewCustomers,
+            (1..=2, 3..=4) => Segment::AtRisk,
+            (1..=2, _) => Segment::Hibernating,
+            _ => Segment::NeedsAttention,
+        }
+    }
+}
+
+/// # RFM scores
+/// Scores every user of a transaction log against the quartiles of the other users.
+///
+/// ## Parameters:
+/// * `transactions`: The transactions, with the amount spent as rating. Ratings
+///   without timestamp are ignored.
+/// * `now`: The reference time of the recency, usually the end of the log.
+///
+/// ## Returns:
+/// * The scores of every user, sorted by user id.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::dataset::{Dataset, Rating};
+/// use rec_rsys::rfm::{rfm_scores, Segment};
+/// let transactions = Dataset::new(vec![
+///     Rating::new(1, 10, 50.0).timestamp(95),
+///     Rating::new(1, 11, 20.0).timestamp(98),
+///     Rating::new(1, 12, 30.0).timestamp(99),
+///     Rating::new(2, 10, 5.0).timestamp(10),
+///     Rating::new(3, 10, 8.0).timestamp(60),
+///     Rating::new(3, 11, 8.0).timestamp(62),
+///     Rating::new(4, 12, 2.0).timestamp(20),
+/// ]);
+/// let scores = rfm_scores(&transactions, 100);
+/// assert_eq!(scores[0].segment(), Segment::Champions);
+/// assert_eq!(scores[1].segment(), Segment::Hibernating);
+/// ```
+#[doc = include_str!("../docs/rfm/rfm.md")]
+pub fn rfm_scores(transactions: &Dataset, now: u64) -> Vec<RfmScore> {
+    let mut users: BTreeMap<u32, (u64, usize, f32)> = BTreeMap::new();
+    for rating in &transactions.ratings {
+        let Some(timestamp) = rating.timestamp else {
+            continue;
+        };
+        let (last, frequency, monetary) = users.entry(rating.user_id).or_default();
+        *last = (*last).max(timestamp);
+        *frequency += 1;
+        *monetary += rating.rating;
+    }
+    let recency: Vec<f32> = users
+        .values()
+        .map(|(last, _, _)| now.saturating_sub(*last) as f32)
+        .collect();
+    let frequency: Vec<f32> = users.values().map(|(_, f, _)| *f as f32).collect();
+    let monetary: Vec<f32> = users.values().map(|(_, _, m)| *m).collect();
+    let (recency_cuts, frequency_cuts, monetary_cuts) =
+        (cuts(&recency), cuts(&frequency), cuts(&monetary));
+    users
+        .iter()
+        .zip(recency.iter().zip(frequency.iter()))
+        .map(|((user_id, (_, count, spent)), (r, f))| RfmScore {
+            user_id: *user_id,
+            recency: *r as u64,
+            frequency: *count,
+            monetary: *spent,
+            // The most recent users have the lowest recency.
+            recency_score: 5 - score(*r, &recency_cuts),
+            frequency_score: score(*f, &frequency_cuts),
+            monetary_score: score(*spent, &monetary_cuts),
+        })
+        .collect()
+}
+
+/// The three quartiles of the values.
+fn cuts(values: &[f32]) -> [f32; 3] {
+    if values.is_empty() {
+        return [0.0; 3];
+    }
+    let mut sorted = values.to_vec();
+    let (q1, q3) = quartiles(&mut sorted);
+    [q1, median(&sorted), q3]
+}
+
+/// The quartile of the value, from 1 to 4.
+fn score(value: f32, cuts: &[f32; 3]) -> u8 {
+    1 + cuts.iter().filter(|cut| value > **cut).count() as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dataset::Rating;
+
+    #[test]
+    fn test_score() {
+        let cuts = [2.0, 4.0, 6.0];
+        assert_eq!(score(1.0, &cuts), 1);
+        assert_eq!(score(2.0, &cuts), 1);
+        assert_eq!(score(5.0, &cuts), 3);
+        assert_eq!(score(7.0, &cuts), 4);
+    }
+
+    #[test]
+    fn test_rfm_scores() {
+        let transactions = Dataset::new(
+            (1..=8)
+                .flat_map(|user| {
+                    (0..user).map(move |t| {
+                        Rating::new(user, t, user as f32 * 10.0)
+                            .timestamp(user as u64 * 10 + t as u64)
+                    })
+                })
+                .chain([Rating::new(9, 1, 100.0)])
+                .collect(),
+        );
+        let scores = rfm_scores(&transactions, 100);
+        assert_eq!(scores.len(), 8);
+        assert_eq!(scores[0].code(), "111");
+        assert_eq!(scores[0].recency, 90);
+        assert_eq!(scores[7].code(), "444");
+        assert_eq!(scores[7].segment(), Segment::Champions);
+        assert_eq!(scores[2].segment(), Segment::Hibernating);
+        assert_eq!(scores[5].segment(), Segment::Loyal);
+        assert_eq!(Segment::AtRisk.as_str(), "at_risk");
+        assert!(rfm_scores(&Dataset::default(), 0).is_empty());
+    }
+}
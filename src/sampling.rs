@@ -0,0 +1,354 @@
+//! # Sampling
+//! Random selection helpers: uniform samples of streams too large to hold in
+//! memory and constant time draws from weighted distributions, e.g. to pick the
+//! negative items of a user proportionally to their popularity.
+use std::collections::HashMap;
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::dataset::Dataset;
+use crate::errors::{Error, Result};
+use crate::memory::MemoryFootprint;
+use crate::sets::IdSet;
+
+/// # Reservoir sampling
+/// Draws `k` elements of an iterator uniformly without replacement, in one pass
+/// and with memory for `k` elements only (algorithm R).
+///
+/// ## Parameters:
+/// * `iter`: The elements, of any length.
+/// * `k`: The number of elements drawn.
+/// * `rng`: The random number generator.
+///
+/// ## Returns:
+/// * `k` elements, or every element if there are fewer, in no particular order.
+///
+/// ## Examples:
+/// ```
+/// use rand::rngs::StdRng;
+/// use rand::SeedableRng;
+/// use rec_rsys::sampling::reservoir;
+/// let mut rng = StdRng::seed_from_u64(42);
+/// let sample = reservoir(0..1_000_000, 10, &mut rng);
+/// assert_eq!(sample.len(), 10);
+/// assert_eq!(reservoir(0..3, 10, &mut rng).len(), 3);
+/// ```
+pub fn reservoir<T, I, R>(iter: I, k: usize, rng: &mut R) -> Vec<T>
+where
+    I: IntoIterator<Item = T>,
+    R: Rng + ?Sized,
+{
+    let mut sample = Vec::with_capacity(k);
+    if k == 0 {
+        return sample;
+    }
+    for (seen, element) in iter.into_iter().enumerate() {
+        if seen < k {
+            sample.push(element);
+        } else {
+            let position = rng.gen_range(0..=seen);
+            if position < k {
+                sample[position] = element;
+            }
+        }
+    }
+    sample
+}
+
+/// # Shuffled reservoir sampling
+/// Draws `k` elements of an iterator like [`reservoir`], in a uniformly random
+/// order: the sample of a reservoir that is not full keeps the order of the
+/// iterator.
+///
+/// ## Examples:
+/// ```
+/// use rand::rngs::StdRng;
+/// use rand::SeedableRng;
+/// use rec_rsys::sampling::shuffled_reservoir;
+/// let mut rng = StdRng::seed_from_u64(42);
+/// let mut sample = shuffled_reservoir(0..100, 100, &mut rng);
+/// assert_ne!(sample, (0..100).collect::<Vec<_>>());
+/// sample.sort_unstable();
+/// assert_eq!(sample, (0..100).collect::<Vec<_>>());
+/// ```
+pub fn shuffled_reservoir<T, I, R>(iter: I, k: usize, rng: &mut R) -> Vec<T>
+where
+    I: IntoIterator<Item = T>,
+    R: Rng + ?Sized,
+{
+    let mut sample = reservoir(iter, k, rng);
+    sample.shuffle(rng);
+    sample
+}
+
+/// # Alias table
+/// A discrete distribution over the indices of weights, drawn from in constant
+/// time with the alias method (Vose's construction): every index owns a column of
+/// the same height, topped with the excess of a heavier index.
+///
+/// ## Examples:
+/// ```
+/// use rand::rngs::StdRng;
+/// use rand::SeedableRng;
+/// use rec_rsys::sampling::AliasTable;
+/// let table = AliasTable::new(&[1.0, 0.0, 3.0]).unwrap();
+/// let mut rng = StdRng::seed_from_u64(42);
+/// let draws: Vec<usize> = (0..1000).map(|_| table.sample(&mut rng)).collect();
+/// assert!(!draws.contains(&1));
+/// assert!(draws.iter().filter(|&&i| i == 2).count() > 600);
+/// assert!(AliasTable::new(&[0.0, 0.0]).is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct AliasTable {
+    /// Probability of keeping the drawn column rather than taking its alias.
+    probabilities: Vec<f32>,
+    aliases: Vec<usize>,
+}
+
+impl AliasTable {
+    /// Builds the table of the weights, which must be finite, non-negative and
+    /// not all 0. They do not need to add up to 1.
+    pub fn new(weights: &[f32]) -> Result<Self> {
+        if weights.iter().any(|w| !w.is_finite() || *w < 0.0) {
+            return Err(Error::InvalidData(
+                "the weights must be finite and non-negative".to_string(),
+            ));
+        }
+        let total: f64 = weights.iter().map(|w| *w as f64).sum();
+        if total <= 0.0 {
+            return Err(Error::InvalidData(
+                "the weights must not all be 0".to_string(),
+            ));
+        }
+        let n = weights.len();
+        let mut scaled: Vec<f64> = weights
+            .iter()
+            .map(|w| *w as f64 * n as f64 / total)
+            .collect();
+        let (mut small, mut large): (Vec<usize>, Vec<usize>) =
+            (0..n).partition(|&i| scaled[i] < 1.0);
+        let mut probabilities = vec![1.0; n];
+        let mut aliases: Vec<usize> = (0..n).collect();
+        while let (Some(&less), Some(&more)) = (small.last(), large.last()) {
+            small.pop();
+            probabilities[less] = scaled[less] as f32;
+            aliases[less] = more;
+            scaled[more] -= 1.0 - scaled[less];
+            if scaled[more] < 1.0 {
+                large.pop();
+                small.push(more);
+            }
+        }
+        // What is left only misses 1 by rounding errors and keeps its column.
+        Ok(AliasTable {
+            probabilities,
+            aliases,
+        })
+    }
+
+    /// Draws an index, with a probability proportional to its weight.
+    pub fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> usize {
+        let column = rng.gen_range(0..self.probabilities.len());
+        match rng.gen::<f32>() < self.probabilities[column] {
+            true => column,
+            false => self.aliases[column],
+        }
+    }
+
+    /// The number of weights.
+    pub fn len(&self) -> usize {
+        self.probabilities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.probabilities.is_empty()
+    }
+}
+
+/// # Negative sampler
+/// Draws items a user did not interact with, to train or evaluate against implicit
+/// feedback. Items are drawn proportionally to their number of ratings raised to
+/// an exponent: 0 draws uniformly, 1 as often as they were rated and in between,
+/// e.g. 0.75, flattens the popularity so the tail is drawn too.
+///
+/// ## Examples:
+/// ```
+/// use rand::rngs::StdRng;
+/// use rand::SeedableRng;
+/// use rec_rsys::dataset::{Dataset, Rating};
+/// use rec_rsys::sampling::NegativeSampler;
+/// let dataset = Dataset::new(vec![
+///     Rating::new(1, 10, 1.0), Rating::new(2, 10, 1.0), Rating::new(2, 11, 1.0),
+///     Rating::new(3, 12, 1.0),
+/// ]);
+/// let sampler = NegativeSampler::from_dataset(&dataset, 0.75).unwrap();
+/// let positives = dataset.user_item_sets().remove(&2).unwrap();
+/// let negatives = sampler.sample(&positives, 3, &mut StdRng::seed_from_u64(42));
+/// assert_eq!(negatives, vec![12, 12, 12]);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct NegativeSampler {
+    item_ids: Vec<u32>,
+    table: AliasTable,
+}
+
+impl NegativeSampler {
+    /// The sampler of the items of a dataset, weighted by their popularity raised
+    /// to `exponent`.
+    pub fn from_dataset(dataset: &Dataset, exponent: f32) -> Result<Self> {
+        if !exponent.is_finite() || exponent < 0.0 {
+            return Err(Error::InvalidConfig(format!(
+                "exponent must be a non-negative number, got {}",
+                exponent
+            )));
+        }
+        let mut counts: HashMap<u32, usize> = HashMap::new();
+        dataset
+            .ratings
+            .iter()
+            .for_each(|r| *counts.entry(r.item_id).or_default() += 1);
+        let mut counts: Vec<(u32, usize)> = counts.into_iter().collect();
+        counts.sort_unstable();
+        let weights: Vec<f32> = counts
+            .iter()
+            .map(|(_, count)| (*count as f32).powf(exponent))
+            .collect();
+        Ok(NegativeSampler {
+            table: AliasTable::new(&weights)?,
+            item_ids: counts.into_iter().map(|(item_id, _)| item_id).collect(),
+        })
+    }
+
+    /// # Sample
+    /// Draws negatives of a user, with replacement.
+    ///
+    /// ## Parameters:
+    /// * `positives`: The items of the user, never drawn.
+    /// * `num_samples`: The number of negatives.
+    /// * `rng`: The random number generator.
+    ///
+    /// ## Returns:
+    /// * Up to `num_samples` items. Draws hitting a positive are retried a bounded
+    ///   number of times, so users who rated nearly every item get fewer negatives
+    ///   rather than an endless loop.
+    pub fn sample<R: Rng + ?Sized>(
+        &self,
+        positives: &IdSet,
+        num_samples: usize,
+        rng: &mut R,
+    ) -> Vec<u32> {
+        let mut negatives = Vec::with_capacity(num_samples);
+        let max_draws = num_samples.saturating_mul(10).max(100);
+        for _ in 0..max_draws {
+            if negatives.len() == num_samples {
+                break;
+            }
+            let item_id = self.item_ids[self.table.sample(rng)];
+            if !positives.contains(item_id) {
+                negatives.push(item_id);
+            }
+        }
+        negatives
+    }
+
+    /// Like [`NegativeSampler::sample`], without drawing an item twice.
+    pub fn sample_distinct<R: Rng + ?Sized>(
+        &self,
+        positives: &IdSet,
+        num_samples: usize,
+        rng: &mut R,
+    ) -> Vec<u32> {
+        let mut drawn = IdSet::new();
+        let mut negatives = Vec::with_capacity(num_samples);
+        let max_draws = num_samples.saturating_mul(10).max(100);
+        for _ in 0..max_draws {
+            if negatives.len() == num_samples {
+                break;
+            }
+            let item_id = self.item_ids[self.table.sample(rng)];
+            if !positives.contains(item_id) && drawn.insert(item_id) {
+                negatives.push(item_id);
+            }
+        }
+        negatives
+    }
+
+    /// The number of items that may be drawn.
+    pub fn num_items(&self) -> usize {
+        self.item_ids.len()
+    }
+}
+
+impl MemoryFootprint for AliasTable {
+    fn heap_size(&self) -> usize {
+        self.probabilities.heap_size() + self.aliases.heap_size()
+    }
+}
+
+impl MemoryFootprint for NegativeSampler {
+    fn heap_size(&self) -> usize {
+        self.item_ids.heap_size() + self.table.heap_size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::dataset::Rating;
+
+    #[test]
+    fn test_reservoir_is_uniform() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut counts = [0; 10];
+        for _ in 0..10_000 {
+            reservoir(0..10, 3, &mut rng)
+                .into_iter()
+                .for_each(|i| counts[i] += 1);
+        }
+        // Every element is expected 3000 times.
+        assert!(
+            counts.iter().all(|&c| (2800..3200).contains(&c)),
+            "{:?}",
+            counts
+        );
+        assert!(reservoir(0..10, 0, &mut rng).is_empty());
+    }
+
+    #[test]
+    fn test_alias_table_frequencies() {
+        let weights = [0.1, 0.2, 0.3, 0.4];
+        let table = AliasTable::new(&weights).unwrap();
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut counts = [0.0; 4];
+        for _ in 0..100_000 {
+            counts[table.sample(&mut rng)] += 1.0;
+        }
+        for (count, weight) in counts.iter().zip(weights) {
+            assert!((count / 100_000.0 - weight).abs() < 0.01);
+        }
+        assert_eq!(table.len(), 4);
+        assert!(AliasTable::new(&[]).is_err());
+        assert!(AliasTable::new(&[1.0, -1.0]).is_err());
+        assert!(AliasTable::new(&[f32::NAN]).is_err());
+    }
+
+    #[test]
+    fn test_negative_sampler() {
+        let dataset = Dataset::new(vec![Rating::new(1, 1, 1.0), Rating::new(1, 2, 1.0)]);
+        let sampler = NegativeSampler::from_dataset(&dataset, 1.0).unwrap();
+        let mut rng = StdRng::seed_from_u64(0);
+        let all: IdSet = [1, 2].into_iter().collect();
+        assert!(sampler.sample(&all, 5, &mut rng).is_empty());
+        assert_eq!(sampler.sample(&IdSet::new(), 5, &mut rng).len(), 5);
+        let mut distinct = sampler.sample_distinct(&IdSet::new(), 5, &mut rng);
+        distinct.sort_unstable();
+        assert_eq!(distinct, [1, 2]);
+        assert_eq!(sampler.num_items(), 2);
+        assert!(NegativeSampler::from_dataset(&dataset, -1.0).is_err());
+        assert!(NegativeSampler::from_dataset(&Dataset::new(Vec::new()), 1.0).is_err());
+    }
+}
@@ -0,0 +1,93 @@
+//! # Scratch buffers
+//! Buffers reused from one query to the next by the scoring loops, so scanning a
+//! pool does not allocate for every item it compares: the scores are kept by index
+//! instead of cloning the items, and the ranks of the Spearman correlation are
+//! written into the same buffers for every item.
+use crate::algorithms::knn::KNN;
+use crate::memory::MemoryFootprint;
+use crate::similarity::{spearman_from_ranks, spearman_rank_into, SimilarityAlgos};
+
+/// # Scratch
+/// The temporary vectors of a scoring loop. A scratch only grows, up to the size
+/// of the largest pool scanned with it: keep one per thread and pass it to every
+/// query, e.g. to [`KNN::search_with`].
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::{algorithms::knn::KNN, models::Item, scratch::Scratch};
+/// let pool: Vec<Item> = (0..100).map(|id| Item::new(id, vec![id as f32, 1.0], None)).collect();
+/// let mut scratch = Scratch::new();
+/// for query in 0..10 {
+///     let knn = KNN::new(Item::new(1000, vec![query as f32, 1.0], None), pool.clone())
+///         .set_num_neighbors(3);
+///     assert_eq!(knn.search_with(&mut scratch).items, knn.result());
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Scratch {
+    /// The index of every scanned item and its score.
+    pub(crate) scores: Vec<(usize, f32)>,
+    query_ranks: Vec<f32>,
+    ranks: Vec<f32>,
+    order: Vec<usize>,
+}
+
+impl Scratch {
+    pub fn new() -> Self {
+        Scratch::default()
+    }
+
+    /// Forgets the scores of the previous query and precomputes what only depends
+    /// on `query`.
+    pub(crate) fn prepare(&mut self, metric: SimilarityAlgos, query: &[f32]) {
+        self.scores.clear();
+        if metric == SimilarityAlgos::Spearman {
+            spearman_rank_into(query, &mut self.order, &mut self.query_ranks);
+        }
+    }
+
+    /// The value of the formula of `metric` between the prepared query and
+    /// `values`, as [`KNN::get_formula`] computes it.
+    pub(crate) fn compare(
+        &mut self,
+        metric: SimilarityAlgos,
+        query: &[f32],
+        values: &[f32],
+    ) -> f32 {
+        match metric {
+            SimilarityAlgos::Spearman => {
+                spearman_rank_into(values, &mut self.order, &mut self.ranks);
+                spearman_from_ranks(&self.query_ranks, &self.ranks)
+            },
+            _ => KNN::get_formula(&metric).0(query, values),
+        }
+    }
+}
+
+impl MemoryFootprint for Scratch {
+    fn heap_size(&self) -> usize {
+        self.scores.heap_size()
+            + self.query_ranks.heap_size()
+            + self.ranks.heap_size()
+            + self.order.heap_size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compare_matches_formulas() {
+        let query = [3.0, 45.0, 7.0, 2.0];
+        let pool = [[2.0, 54.0, 13.0, 15.0], [1.0, 0.0, 2.0, -3.0]];
+        let mut scratch = Scratch::new();
+        for metric in SimilarityAlgos::ALL {
+            scratch.prepare(metric, &query);
+            for values in &pool {
+                let expected = KNN::get_formula(&metric).0(&query, values);
+                assert_eq!(scratch.compare(metric, &query, values), expected);
+            }
+        }
+    }
+}
@@ -0,0 +1,361 @@
+//! # Id sets
+//! Compact sets of user or item ids.
+#[cfg(not(feature = "roaring"))]
+use std::collections::BTreeSet;
+
+use crate::memory::MemoryFootprint;
+#[cfg(feature = "roaring")]
+use roaring::RoaringBitmap;
+
+#[cfg(not(feature = "roaring"))]
+const WORD_BITS: u32 = u64::BITS;
+
+/// # Id set
+/// A set of ids. By default it holds sparse ids in a sorted tree and switches to a
+/// bitset, one bit per id up to the largest one, once the ids are dense enough for
+/// the bitset to be the smaller of the two, like the ones assigned by an index. A
+/// single large id, e.g. a hashed one, never allocates a bit for every smaller id.
+/// With the `roaring` feature it is a roaring bitmap instead, compressed for both.
+/// The bitsets make intersections and unions word operations instead of lookups.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::sets::IdSet;
+/// let mut set: IdSet = [3, 1, 200].into_iter().collect();
+/// assert!(set.contains(200));
+/// assert!(set.remove(1));
+/// assert_eq!(set.iter().collect::<Vec<u32>>(), vec![3, 200]);
+/// ```
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(from = "Vec<u32>", into = "Vec<u32>"))]
+pub struct IdSet {
+    #[cfg(not(feature = "roaring"))]
+    ids: Ids,
+    #[cfg(feature = "roaring")]
+    bitmap: RoaringBitmap,
+}
+
+/// The storage of an [`IdSet`] without the `roaring` feature.
+#[cfg(not(feature = "roaring"))]
+#[derive(Debug, Clone)]
+enum Ids {
+    Sparse(BTreeSet<u32>),
+    /// One bit per id up to the largest one, and the number of ids. Kept while the
+    /// words are at most twice as many as the ids.
+    Dense {
+        words: Vec<u64>,
+        len: usize,
+    },
+}
+
+#[cfg(not(feature = "roaring"))]
+impl Default for Ids {
+    fn default() -> Self {
+        Ids::Sparse(BTreeSet::new())
+    }
+}
+
+#[cfg(not(feature = "roaring"))]
+impl IdSet {
+    /// Adds an id, returns whether it was not already present.
+    pub fn insert(&mut self, id: u32) -> bool {
+        let (word, mask) = position(id);
+        let inserted = match &mut self.ids {
+            Ids::Sparse(ids) => ids.insert(id),
+            Ids::Dense { words, len } if word < words.len() || word < 2 * (*len + 1) => {
+                if words.len() <= word {
+                    words.resize(word + 1, 0);
+                }
+                let inserted = words[word] & mask == 0;
+                words[word] |= mask;
+                *len += inserted as usize;
+                inserted
+            },
+            Ids::Dense { .. } => {
+                self.ids = Ids::Sparse(self.iter().collect());
+                return self.insert(id);
+            },
+        };
+        self.rebalance();
+        inserted
+    }
+
+    /// Removes an id, returns whether it was present.
+    pub fn remove(&mut self, id: u32) -> bool {
+        let removed = match &mut self.ids {
+            Ids::Sparse(ids) => ids.remove(&id),
+            Ids::Dense { words, len } => {
+                let (word, mask) = position(id);
+                let removed = words.get(word).is_some_and(|w| w & mask != 0);
+                if removed {
+                    words[word] &= !mask;
+                    *len -= 1;
+                }
+                removed
+            },
+        };
+        self.rebalance();
+        removed
+    }
+
+    pub fn contains(&self, id: u32) -> bool {
+        match &self.ids {
+            Ids::Sparse(ids) => ids.contains(&id),
+            Ids::Dense { words, .. } => {
+                let (word, mask) = position(id);
+                words.get(word).is_some_and(|w| w & mask != 0)
+            },
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match &self.ids {
+            Ids::Sparse(ids) => ids.len(),
+            Ids::Dense { len, .. } => *len,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.ids = Ids::default();
+    }
+
+    /// The number of ids present in both sets.
+    pub fn intersection_len(&self, other: &IdSet) -> usize {
+        match (&self.ids, &other.ids) {
+            (Ids::Dense { words: a, .. }, Ids::Dense { words: b, .. }) => a
+                .iter()
+                .zip(b)
+                .map(|(a, b)| (a & b).count_ones() as usize)
+                .sum(),
+            _ => {
+                let (small, large) = match self.len() <= other.len() {
+                    true => (self, other),
+                    false => (other, self),
+                };
+                small.iter().filter(|id| large.contains(*id)).count()
+            },
+        }
+    }
+
+    /// The ids in increasing order.
+    pub fn iter(&self) -> impl Iterator<Item = u32> + '_ {
+        let (sparse, dense) = match &self.ids {
+            Ids::Sparse(ids) => (Some(ids.iter().copied()), None),
+            Ids::Dense { words, .. } => {
+                let ids = words.iter().enumerate().flat_map(|(index, word)| {
+                    (0..WORD_BITS)
+                        .filter(move |bit| word & (1 << bit) != 0)
+                        .map(move |bit| index as u32 * WORD_BITS + bit)
+                });
+                (None, Some(ids))
+            },
+        };
+        sparse
+            .into_iter()
+            .flatten()
+            .chain(dense.into_iter().flatten())
+    }
+
+    /// Switches to a bitset once it holds at least one id per word, and back to a
+    /// tree when the ids become too sparse for it.
+    fn rebalance(&mut self) {
+        match &self.ids {
+            Ids::Sparse(ids) => match ids.last() {
+                Some(last) if position(*last).0 < ids.len() => {
+                    let mut words = vec![0; position(*last).0 + 1];
+                    ids.iter().for_each(|id| {
+                        let (word, mask) = position(*id);
+                        words[word] |= mask;
+                    });
+                    self.ids = Ids::Dense {
+                        words,
+                        len: ids.len(),
+                    };
+                },
+                _ => {},
+            },
+            Ids::Dense { words, len } if words.len() > 2 * (*len + 1) => {
+                self.ids = Ids::Sparse(self.iter().collect());
+            },
+            Ids::Dense { .. } => {},
+        }
+    }
+}
+
+/// The word holding the bit of an id and the mask selecting it.
+#[cfg(not(feature = "roaring"))]
+fn position(id: u32) -> (usize, u64) {
+    ((id / WORD_BITS) as usize, 1 << (id % WORD_BITS))
+}
+
+#[cfg(feature = "roaring")]
+impl IdSet {
+    /// Adds an id, returns whether it was not already present.
+    pub fn insert(&mut self, id: u32) -> bool {
+        self.bitmap.insert(id)
+    }
+
+    /// Removes an id, returns whether it was present.
+    pub fn remove(&mut self, id: u32) -> bool {
+        self.bitmap.remove(id)
+    }
+
+    pub fn contains(&self, id: u32) -> bool {
+        self.bitmap.contains(id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.bitmap.len() as usize
+    }
+
+    pub fn clear(&mut self) {
+        self.bitmap.clear();
+    }
+
+    /// The number of ids present in both sets.
+    pub fn intersection_len(&self, other: &IdSet) -> usize {
+        self.bitmap.intersection_len(&other.bitmap) as usize
+    }
+
+    /// The ids in increasing order.
+    pub fn iter(&self) -> impl Iterator<Item = u32> + '_ {
+        self.bitmap.iter()
+    }
+}
+
+impl IdSet {
+    pub fn new() -> Self {
+        IdSet::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The number of ids present in at least one of the sets.
+    pub fn union_len(&self, other: &IdSet) -> usize {
+        self.len() + other.len() - self.intersection_len(other)
+    }
+}
+
+impl PartialEq for IdSet {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.intersection_len(other) == self.len()
+    }
+}
+
+impl Eq for IdSet {}
+
+impl FromIterator<u32> for IdSet {
+    fn from_iter<I: IntoIterator<Item = u32>>(iter: I) -> Self {
+        let mut set = IdSet::new();
+        set.extend(iter);
+        set
+    }
+}
+
+impl Extend<u32> for IdSet {
+    fn extend<I: IntoIterator<Item = u32>>(&mut self, iter: I) {
+        iter.into_iter().for_each(|id| {
+            self.insert(id);
+        });
+    }
+}
+
+impl IntoIterator for IdSet {
+    type Item = u32;
+    type IntoIter = std::vec::IntoIter<u32>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        Vec::from(self).into_iter()
+    }
+}
+
+impl From<Vec<u32>> for IdSet {
+    fn from(ids: Vec<u32>) -> Self {
+        ids.into_iter().collect()
+    }
+}
+
+impl From<IdSet> for Vec<u32> {
+    fn from(set: IdSet) -> Self {
+        set.iter().collect()
+    }
+}
+
+impl MemoryFootprint for IdSet {
+    #[cfg(not(feature = "roaring"))]
+    fn heap_size(&self) -> usize {
+        match &self.ids {
+            Ids::Sparse(ids) => ids.heap_size(),
+            Ids::Dense { words, .. } => words.heap_size(),
+        }
+    }
+
+    /// The serialized size of a roaring bitmap is close to its in-memory size.
+    #[cfg(feature = "roaring")]
+    fn heap_size(&self) -> usize {
+        self.bitmap.serialized_size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_insert_remove() {
+        let mut set = IdSet::new();
+        assert!(set.insert(64));
+        assert!(!set.insert(64));
+        assert!(set.insert(0));
+        assert_eq!(set.len(), 2);
+        assert!(set.contains(64) && !set.contains(63) && !set.contains(1000));
+        assert!(set.remove(64));
+        assert!(!set.remove(64));
+        assert_eq!(set.iter().collect::<Vec<u32>>(), vec![0]);
+        assert_eq!(set, [0].into_iter().collect());
+    }
+
+    #[test]
+    fn test_intersection_union() {
+        let a: IdSet = [1, 2, 3, 130].into_iter().collect();
+        let b: IdSet = [2, 3, 4].into_iter().collect();
+        assert_eq!(a.intersection_len(&b), 2);
+        assert_eq!(b.intersection_len(&a), 2);
+        assert_eq!(a.union_len(&b), 5);
+    }
+
+    /// The storage switches between a tree and a bitset without the `roaring`
+    /// feature, roaring bitmaps handle both themselves.
+    #[test]
+    #[cfg(not(feature = "roaring"))]
+    fn test_sparse_and_dense_ids() {
+        let mut set: IdSet = [5, u32::MAX, 1 << 30].into_iter().collect();
+        assert!(set.heap_size() < 1024);
+        set.extend(0..1000);
+        assert!(set.heap_size() < 16 * 1024);
+        assert_eq!(set.len(), 1002);
+        assert!(set.contains(u32::MAX) && set.contains(999) && !set.contains(1000));
+        assert!(set.remove(u32::MAX) && set.remove(1 << 30));
+        assert!(set.heap_size() < 256);
+        // A large id added to dense ids does not grow the words up to it.
+        assert!(set.insert(u32::MAX - 1));
+        assert!(set.heap_size() < 64 * 1024);
+        let dense: IdSet = (0..1000).collect();
+        assert_eq!(set.intersection_len(&dense), 1000);
+        assert_eq!(dense.intersection_len(&set), 1000);
+        assert_eq!(set.iter().last(), Some(u32::MAX - 1));
+        assert_eq!(set.iter().take(3).collect::<Vec<u32>>(), [0, 1, 2]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_as_sorted_ids() {
+        let set: IdSet = [70, 2, 5].into_iter().collect();
+        let json = serde_json::to_string(&set).unwrap();
+        assert_eq!(json, "[2,5,70]");
+        assert_eq!(serde_json::from_str::<IdSet>(&json).unwrap(), set);
+    }
+}
@@ -0,0 +1,204 @@
+//! # Fixed dimension kernels
+//! The similarities of [`super`] over arrays whose length is known at compile
+//! time, for tiny models scored on constrained devices: nothing is allocated and
+//! the loops, of a constant length, are unrolled and vectorized by the compiler.
+use super::SimilarityAlgos;
+use crate::models::FixedItem;
+
+/// Number of independent accumulators of the sums, so the additions do not wait
+/// on each other and map to SIMD lanes.
+const LANES: usize = 8;
+
+fn sum<const D: usize, F: Fn(usize) -> f32>(term: F) -> f32 {
+    let mut lanes = [0.0_f32; LANES];
+    for index in 0..D {
+        lanes[index % LANES] += term(index);
+    }
+    lanes.iter().sum()
+}
+
+pub fn dot<const D: usize>(u: &[f32; D], v: &[f32; D]) -> f32 {
+    sum::<D, _>(|i| u[i] * v[i])
+}
+
+pub fn squared_diff_sum<const D: usize>(u: &[f32; D], v: &[f32; D]) -> f32 {
+    sum::<D, _>(|i| (u[i] - v[i]) * (u[i] - v[i]))
+}
+
+pub fn euclidean_distance<const D: usize>(u: &[f32; D], v: &[f32; D]) -> f32 {
+    squared_diff_sum(u, v).sqrt()
+}
+
+/// # Cosine similarity
+/// See [`cosine_similarity`](super::cosine_similarity).
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::similarity::fixed::cosine_similarity;
+/// let similarity = cosine_similarity(&[1.0, 0.0, 1.0, 0.0], &[1.0, 0.0, 0.0, 0.0]);
+/// assert!((similarity - 0.707_106_8).abs() < 1e-6);
+/// ```
+pub fn cosine_similarity<const D: usize>(u: &[f32; D], v: &[f32; D]) -> f32 {
+    dot(u, v) / (dot(u, u).sqrt() * dot(v, v).sqrt())
+}
+
+/// See [`pearson_correlation`](super::pearson_correlation).
+pub fn pearson_correlation<const D: usize>(u: &[f32; D], v: &[f32; D]) -> f32 {
+    let (mean_u, mean_v) = (
+        sum::<D, _>(|i| u[i]) / D as f32,
+        sum::<D, _>(|i| v[i]) / D as f32,
+    );
+    let covariance = sum::<D, _>(|i| (u[i] - mean_u) * (v[i] - mean_v));
+    let variance_u = sum::<D, _>(|i| (u[i] - mean_u) * (u[i] - mean_u));
+    let variance_v = sum::<D, _>(|i| (v[i] - mean_v) * (v[i] - mean_v));
+    covariance / (variance_u.sqrt() * variance_v.sqrt())
+}
+
+/// See [`msd_similarity`](super::msd_similarity).
+pub fn msd_similarity<const D: usize>(u: &[f32; D], v: &[f32; D]) -> f32 {
+    1.0 / (squared_diff_sum(u, v) / D as f32 + 1.0)
+}
+
+/// The rank of every value, ties ranked by position.
+fn ranks<const D: usize>(x: &[f32; D]) -> [f32; D] {
+    let mut ranks = [0.0; D];
+    for (i, rank) in ranks.iter_mut().enumerate() {
+        *rank = (0..D)
+            .filter(|&j| x[j] < x[i] || (x[j] == x[i] && j < i))
+            .count() as f32;
+    }
+    ranks
+}
+
+/// See [`spearman_correlation`](super::spearman_correlation).
+pub fn spearman_correlation<const D: usize>(u: &[f32; D], v: &[f32; D]) -> f32 {
+    let n = D as f32;
+    1.0 - (6.0 * squared_diff_sum(&ranks(u), &ranks(v))) / (n * (n.powi(2) - 1.0))
+}
+
+/// # Similarity
+/// The similarity of two vectors with a metric, the larger the more similar:
+/// distances are turned into similarities as [`KNN`](crate::algorithms::knn::KNN)
+/// does.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::similarity::fixed::similarity;
+/// use rec_rsys::similarity::SimilarityAlgos;
+/// let (u, v) = ([0.0, 0.0], [3.0, 4.0]);
+/// assert_eq!(similarity(SimilarityAlgos::Euclidean, &u, &v), 1.0 / 6.0);
+/// ```
+pub fn similarity<const D: usize>(
+    metric: SimilarityAlgos,
+    u: &[f32; D],
+    v: &[f32; D],
+) -> f32 {
+    match metric {
+        SimilarityAlgos::Euclidean => 1.0 / (1.0 + euclidean_distance(u, v)),
+        SimilarityAlgos::Cosine | SimilarityAlgos::AdjustedCosine => {
+            cosine_similarity(u, v)
+        },
+        SimilarityAlgos::PearsonCorrelation => pearson_correlation(u, v),
+        SimilarityAlgos::Spearman => spearman_correlation(u, v),
+        SimilarityAlgos::MSD => msd_similarity(u, v),
+    }
+}
+
+/// # Score into
+/// Writes the similarity of the query to every item into `scores`, a buffer the
+/// caller reuses between queries.
+///
+/// ## Parameters:
+/// * `metric`: The similarity.
+/// * `query`: The vector to compare, e.g. the profile of a user.
+/// * `items`: The items to score.
+/// * `scores`: Receives the score of `items[i]` at `scores[i]`, it must be at
+///   least as long as `items`.
+pub fn score_into<const D: usize>(
+    metric: SimilarityAlgos,
+    query: &[f32; D],
+    items: &[FixedItem<D>],
+    scores: &mut [f32],
+) {
+    for (item, score) in items.iter().zip(scores.iter_mut()) {
+        *score = similarity(metric, query, &item.values);
+    }
+}
+
+/// # Nearest
+/// The item the most similar to the query, ignoring the undefined similarities.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::models::FixedItem;
+/// use rec_rsys::similarity::fixed::nearest;
+/// use rec_rsys::similarity::SimilarityAlgos;
+/// let items = [FixedItem::new(1, [1.0, 0.0]), FixedItem::new(2, [0.6, 0.8])];
+/// let (id, _) = nearest(SimilarityAlgos::Cosine, &[0.0, 1.0], &items).unwrap();
+/// assert_eq!(id, 2);
+/// ```
+pub fn nearest<const D: usize>(
+    metric: SimilarityAlgos,
+    query: &[f32; D],
+    items: &[FixedItem<D>],
+) -> Option<(u32, f32)> {
+    items
+        .iter()
+        .map(|item| (item.id, similarity(metric, query, &item.values)))
+        .filter(|(_, score)| score.is_finite())
+        .fold(None, |best, (id, score)| match best {
+            Some((_, best_score)) if best_score >= score => best,
+            _ => Some((id, score)),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithms::knn::{to_similarity, KNN};
+
+    /// Every kernel must agree with its slice counterpart.
+    #[test]
+    fn test_matches_slice_kernels() {
+        let u = [0.3, -1.2, 2.5, 0.0, 4.1, -0.7, 1.9, 3.3, -2.2, 0.4, 1.1];
+        let v = [1.7, 0.2, -0.5, 2.2, 3.9, 0.1, -1.4, 2.8, 0.6, -0.9, 2.0];
+        assert!((dot(&u, &v) - crate::utils::dot(&u, &v)).abs() < 1e-4);
+        for metric in SimilarityAlgos::ALL {
+            let (formula, _) = KNN::get_formula(&metric);
+            let expected = to_similarity(metric, formula(&u, &v));
+            assert!(
+                (similarity(metric, &u, &v) - expected).abs() < 1e-5,
+                "{:?}",
+                metric
+            );
+        }
+    }
+
+    #[test]
+    fn test_score_into_and_nearest() {
+        let items = [
+            FixedItem::new(1, [1.0, 0.0, 0.0]),
+            FixedItem::new(2, [0.0, 0.0, 0.0]),
+            FixedItem::new(3, [0.0, 1.0, 1.0]),
+        ];
+        let mut scores = [0.0; 3];
+        score_into(
+            SimilarityAlgos::Cosine,
+            &[0.0, 1.0, 0.0],
+            &items,
+            &mut scores,
+        );
+        assert_eq!(scores[0], 0.0);
+        assert!(scores[1].is_nan());
+        assert_eq!(
+            nearest(SimilarityAlgos::Cosine, &[0.0, 1.0, 0.0], &items)
+                .unwrap()
+                .0,
+            3
+        );
+        assert_eq!(
+            nearest::<3>(SimilarityAlgos::Cosine, &[1.0, 0.0, 0.0], &[]),
+            None
+        );
+    }
+}
@@ -0,0 +1,709 @@
+//! # A collection of tools to compute similarities
+//!
+use super::memory::MemoryFootprint;
+use super::sets::IdSet;
+use super::statistics::mean;
+use super::utils::{argsort, dot, euclidean_norm, squared_diff_sum};
+use std::collections::{BTreeMap, HashSet};
+
+pub mod fixed;
+pub mod text;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum SimilarityAlgos {
+    Euclidean,
+    Cosine,
+    AdjustedCosine,
+    PearsonCorrelation,
+    Spearman,
+    MSD,
+}
+
+impl SimilarityAlgos {
+    /// Every metric, in declaration order.
+    pub const ALL: [SimilarityAlgos; 6] = [
+        SimilarityAlgos::Euclidean,
+        SimilarityAlgos::Cosine,
+        SimilarityAlgos::AdjustedCosine,
+        SimilarityAlgos::PearsonCorrelation,
+        SimilarityAlgos::Spearman,
+        SimilarityAlgos::MSD,
+    ];
+}
+
+impl MemoryFootprint for SimilarityAlgos {}
+/// # Jaccard Similarity
+/// Calculated the Jaccard similarity between to sets.
+///
+/// ## Parameters:
+/// * `a`: A set of values
+/// * `b`: A set of values
+///
+/// ## Returns:
+/// *
+///
+/// ## Examples:
+///
+/// ```
+/// ```
+///
+#[doc = include_str!("../../docs/similarity/jaccard_similarity.md")]
+pub fn jaccard_similarity(a: &HashSet<&i8>, b: &HashSet<&i8>) -> f32 {
+    a.intersection(b).count() as f32 / a.union(b).count() as f32
+}
+
+/// # Jaccard Similarity of id sets
+/// Calculates the Jaccard similarity between the interactions of two users or two
+/// items, with the set operations done on the bitmaps.
+///
+/// ## Parameters:
+/// * `a`: The first set of ids.
+/// * `b`: The second set of ids.
+///
+/// ## Returns:
+/// * The size of the intersection over the size of the union, 0 when both are empty.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::similarity::jaccard_similarity_ids;
+/// use rec_rsys::sets::IdSet;
+/// let a: IdSet = [3, 45, 7, 2].into_iter().collect();
+/// let b: IdSet = [2, 54, 13, 15].into_iter().collect();
+/// assert_eq!(jaccard_similarity_ids(&a, &b), 0.142_857_15);
+/// ```
+#[doc = include_str!("../../docs/similarity/jaccard_similarity.md")]
+pub fn jaccard_similarity_ids(a: &IdSet, b: &IdSet) -> f32 {
+    match a.union_len(b) {
+        0 => 0.0,
+        union => a.intersection_len(b) as f32 / union as f32,
+    }
+}
+
+/// # Overlap Similarity
+/// Calculates the overlap coefficient between two sets of ids, which does not
+/// penalise a small set fully contained in a big one like Jaccard does.
+///
+/// ## Parameters:
+/// * `a`: The first set of ids.
+/// * `b`: The second set of ids.
+///
+/// ## Returns:
+/// * The size of the intersection over the size of the smallest set, 0 when one is
+///   empty.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::similarity::overlap_similarity;
+/// use rec_rsys::sets::IdSet;
+/// let a: IdSet = [1, 2].into_iter().collect();
+/// let b: IdSet = [1, 2, 3, 4].into_iter().collect();
+/// assert_eq!(overlap_similarity(&a, &b), 1.0);
+/// ```
+#[doc = include_str!("../../docs/similarity/overlap_similarity.md")]
+pub fn overlap_similarity(a: &IdSet, b: &IdSet) -> f32 {
+    match a.len().min(b.len()) {
+        0 => 0.0,
+        smallest => a.intersection_len(b) as f32 / smallest as f32,
+    }
+}
+
+/// Tags of an item with their weight, e.g. how many users applied each tag.
+pub type TagWeights = BTreeMap<String, f32>;
+
+/// Similarity between two [`TagWeights`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum TagSimilarity {
+    /// See [`weighted_jaccard`].
+    WeightedJaccard,
+    /// See [`tag_cosine`].
+    Cosine,
+}
+
+impl TagSimilarity {
+    pub fn compute(&self, a: &TagWeights, b: &TagWeights) -> f32 {
+        match self {
+            TagSimilarity::WeightedJaccard => weighted_jaccard(a, b),
+            TagSimilarity::Cosine => tag_cosine(a, b),
+        }
+    }
+}
+
+impl MemoryFootprint for TagSimilarity {}
+
+/// # Weighted Jaccard Similarity
+/// Generalises the Jaccard similarity to multisets: a tag applied many times to an
+/// item counts more than a tag applied once.
+///
+/// ## Parameters:
+/// * `a`: The weights of the tags of the first item.
+/// * `b`: The weights of the tags of the second item.
+///
+/// ## Returns:
+/// * The sum of the minimum weights over the sum of the maximum weights, 0 when both
+///   are empty.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::similarity::{weighted_jaccard, TagWeights};
+/// let a = TagWeights::from([("rock".to_string(), 3.0), ("pop".to_string(), 1.0)]);
+/// let b = TagWeights::from([("rock".to_string(), 1.0), ("jazz".to_string(), 1.0)]);
+/// assert_eq!(weighted_jaccard(&a, &b), 0.2);
+/// ```
+#[doc = include_str!("../../docs/similarity/weighted_jaccard.md")]
+pub fn weighted_jaccard(a: &TagWeights, b: &TagWeights) -> f32 {
+    let (mut minimums, mut maximums) = (0.0, 0.0);
+    for tag in a.keys().chain(b.keys().filter(|tag| !a.contains_key(*tag))) {
+        let (x, y) = (
+            a.get(tag).copied().unwrap_or(0.0),
+            b.get(tag).copied().unwrap_or(0.0),
+        );
+        minimums += x.min(y);
+        maximums += x.max(y);
+    }
+    match maximums > 0.0 {
+        true => minimums / maximums,
+        false => 0.0,
+    }
+}
+
+/// # Cosine Similarity of tags
+/// The cosine of the sparse vectors of tag weights.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::similarity::{tag_cosine, TagWeights};
+/// let a = TagWeights::from([("rock".to_string(), 1.0), ("pop".to_string(), 1.0)]);
+/// let b = TagWeights::from([("rock".to_string(), 2.0)]);
+/// assert!((tag_cosine(&a, &b) - 0.707_106_8).abs() < 1e-6);
+/// ```
+#[doc = include_str!("../../docs/norms/cosine_similarity.md")]
+pub fn tag_cosine(a: &TagWeights, b: &TagWeights) -> f32 {
+    let dot: f32 = a
+        .iter()
+        .filter_map(|(tag, x)| b.get(tag).map(|y| x * y))
+        .sum();
+    let norm = |tags: &TagWeights| tags.values().map(|w| w * w).sum::<f32>().sqrt();
+    match norm(a) * norm(b) {
+        norms if norms > 0.0 => dot / norms,
+        _ => 0.0,
+    }
+}
+
+/// # Tag IDF
+/// The inverse document frequency of every tag of a catalog, to weigh down the
+/// tags most items have ("rock" in a music catalog) against the rare ones that
+/// tell items apart.
+///
+/// ## Parameters:
+/// * `items`: The tags of every item.
+///
+/// ## Returns:
+/// * The smoothed IDF of every tag, at least 1.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::similarity::{tag_idf, TagWeights};
+/// let items = vec![
+///     TagWeights::from([("rock".to_string(), 1.0), ("grunge".to_string(), 1.0)]),
+///     TagWeights::from([("rock".to_string(), 1.0)]),
+/// ];
+/// let idf = tag_idf(items.iter());
+/// assert!(idf["grunge"] > idf["rock"]);
+/// ```
+#[doc = include_str!("../../docs/similarity/tag_idf.md")]
+pub fn tag_idf<'a, I: Iterator<Item = &'a TagWeights>>(
+    items: I,
+) -> BTreeMap<String, f32> {
+    let mut frequencies: BTreeMap<String, usize> = BTreeMap::new();
+    let mut count = 0;
+    for tags in items {
+        count += 1;
+        tags.keys()
+            .for_each(|tag| *frequencies.entry(tag.clone()).or_default() += 1);
+    }
+    frequencies
+        .into_iter()
+        .map(|(tag, frequency)| {
+            let idf = ((1 + count) as f32 / (1 + frequency) as f32).ln() + 1.0;
+            (tag, idf)
+        })
+        .collect()
+}
+
+/// Multiplies the weight of every tag by its IDF, see [`tag_idf`]. Tags without
+/// IDF keep their weight.
+pub fn apply_idf(tags: &TagWeights, idf: &BTreeMap<String, f32>) -> TagWeights {
+    tags.iter()
+        .map(|(tag, weight)| (tag.clone(), weight * idf.get(tag).unwrap_or(&1.0)))
+        .collect()
+}
+
+/// # Cosine Similarity
+/// Compute the cosine similarity between two vectors.
+///
+/// ## Parameters:
+/// * `u`: The first vector.
+/// * `v`: The second vector.
+///
+/// ## Returns:
+/// The cosine similarity between the two vectors.
+///
+/// ## Examples:
+///
+/// ```
+/// ```
+///
+#[doc = include_str!("../../docs/norms/cosine_similarity.md")]
+pub fn cosine_similarity(u: &[f32], v: &[f32]) -> f32 {
+    dot(u, v) / (euclidean_norm(u) * euclidean_norm(v))
+}
+
+/// # Adjusted Cosine Similarity
+/// Function to calculate the Adjusted Cosine Similarity between two items.
+///
+/// ## Parameters:
+/// * `u`: The ratings of item A by the users.
+/// * `v`: The ratings of item B by the users.
+///
+/// ## Returns:
+/// * The Adjusted Cosine Similarity between items A and B.
+///
+/// ## Examples:
+///
+/// ```
+/// ```
+///
+#[doc = include_str!("../../docs/similarity/adjusted_cosine_similarity.md")]
+pub fn adjusted_cosine_similarity(u: &[f32], v: &[f32]) -> f32 {
+    dot(u, v) / (euclidean_norm(u) * euclidean_norm(v))
+}
+
+/// # Compute the euclidean distance.
+/// Measure the similarity between two vectors according to their distance
+///
+/// ## Parameters:
+/// * `u`: The first vector.
+/// * `v`: The second vector.
+///
+/// ## Returns:
+/// The euclidean distance between the two vectors.
+///
+/// ## Examples:
+///
+/// ```
+/// ```
+///
+#[doc = include_str!("../../docs/norms/euclidean_distance.md")]
+pub fn euclidean_distance(u: &[f32], v: &[f32]) -> f32 {
+    squared_diff_sum(u, v).sqrt()
+}
+
+/// # Exponential Decay Similarity
+/// Calculates the exponential decay similarity between two values based on a decay rate.
+///
+/// ## Parameters:
+/// * `value1`: The first value for comparison.
+/// * `value2`: The second value for comparison.
+/// * `decay_rate`: The decay rate to determine the decay factor.
+///
+/// ## Returns:
+/// The calculated similarity value as a `f32` (floating-point number) between 0 and 1.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::similarity::exponential_decay_similarity;
+/// let value1 = 10.0;
+/// let value2 = 20.0;
+/// let decay_rate = 0.2;
+/// let similarity = exponential_decay_similarity(value1, value2, decay_rate);
+/// println!("Similarity: {}", similarity);
+/// ```
+///
+#[doc = include_str!("../../docs/similarity/exponential_decay_similarity.md")]
+pub fn exponential_decay_similarity(value1: f32, value2: f32, decay_rate: f32) -> f32 {
+    (-(value1 - value2).abs() / decay_rate).exp()
+}
+
+/// # Pearson correlation
+/// Function to calculate the Pearson correlation coefficient between two sets of data.
+///
+/// ## Parameters:
+/// * `u`: The first set of data.
+/// * `v`: The second set of data.
+///
+/// ## Returns:
+/// * The Pearson correlation coefficient (r) between x and y.
+///
+/// ## Examples:
+///
+/// ```
+/// ```
+///
+#[doc = include_str!("../../docs/similarity/pearson_correlation.md")]
+pub fn pearson_correlation(u: &[f32], v: &[f32]) -> f32 {
+    let mean_u = mean(u);
+    let mean_v = mean(v);
+
+    let mut covariance = 0.0;
+    let mut variance_x = 0.0;
+    let mut variance_y = 0.0;
+
+    u.iter().zip(v.iter()).for_each(|(x, y)| {
+        let deviation_x = x - mean_u;
+        let deviation_y = y - mean_v;
+
+        covariance += deviation_x * deviation_y;
+        variance_x += deviation_x * deviation_x;
+        variance_y += deviation_y * deviation_y;
+    });
+
+    covariance / (variance_x.sqrt() * variance_y.sqrt())
+}
+
+/// # Mean Squared Difference
+/// Function to calculate the Mean Squared Difference (MSD).
+///
+/// ## Parameters:
+/// * `u`: The first vector.
+/// * `v`: The second vector.
+///
+/// ## Returns:
+/// * The MSD between the vectors.
+///
+/// ## Examples:
+///
+/// ```
+/// ```
+///
+#[doc = include_str!("../../docs/similarity/msd.md")]
+pub fn msd(u: &[f32], v: &[f32]) -> f32 {
+    squared_diff_sum(u, v) / u.len() as f32
+}
+
+/// # Mean Squared Difference Similarity
+///
+/// ## Parameters:
+/// * `u`: Vector of user's ratings.
+/// * `v`: Vector of user's ratings.
+///
+/// ## Returns:
+/// * The MSD similarity between two vectors.
+///
+/// ## Examples:
+///
+/// ```
+/// use rec_rsys::similarity::msd_similarity;
+/// let user1 = vec![23.0,15.2,11.2222];
+/// let user2 = vec![23.0,7.8,87.02];
+/// let similarity = msd_similarity(&user1,&user2);
+/// println!("Similarity: {}", similarity);
+/// ```
+///
+#[doc = include_str!("../../docs/similarity/msd_similarity.md")]
+pub fn msd_similarity(u: &[f32], v: &[f32]) -> f32 {
+    1.0 / (msd(u, v) + 1.0)
+}
+
+/// # Spearman correlation
+/// Function to calculate the Spearman correlation coefficient between two vectors.
+///
+/// ## Parameters:
+/// * `u`: The first vector.
+/// * `v`: The second vector.
+///
+/// ## Returns:
+/// * The Spearman correlation coefficient between the vectors.
+///
+/// ## Examples:
+///
+/// ```
+/// ```
+///
+#[doc = include_str!("../../docs/similarity/spearman_correlation.md")]
+pub fn spearman_correlation(u: &[f32], v: &[f32]) -> f32 {
+    spearman_from_ranks(&spearman_rank(u), &spearman_rank(v))
+}
+
+/// The Spearman correlation of two vectors from their [`spearman_rank_into`].
+pub(crate) fn spearman_from_ranks(u_ranks: &[f32], v_ranks: &[f32]) -> f32 {
+    let n = u_ranks.len() as f32;
+    1.0 - (6.0 * squared_diff_sum(u_ranks, v_ranks)) / (n * (n.powi(2) - 1.0))
+}
+
+fn spearman_rank(x: &[f32]) -> Vec<f32> {
+    argsort(&argsort(x))
+}
+
+/// Writes the [`spearman_rank`] of `x` into `ranks`, reusing the buffers instead of
+/// allocating: ranking is the inverse of the permutation sorting `x`.
+pub(crate) fn spearman_rank_into(
+    x: &[f32],
+    order: &mut Vec<usize>,
+    ranks: &mut Vec<f32>,
+) {
+    order.clear();
+    order.extend(0..x.len());
+    order.sort_by(|a, b| x[*a].partial_cmp(&x[*b]).unwrap());
+    ranks.clear();
+    ranks.resize(x.len(), 0.0);
+    for (rank, index) in order.iter().enumerate() {
+        ranks[*index] = rank as f32;
+    }
+}
+
+/// # Minkowski distance
+/// Function to calculate the Minkowski distance between two vectors.
+///
+/// ## Parameters:
+/// * `u`: The first vector.
+/// * `v`: The second vector.
+/// * `p`: The order of the Minkowski distance.
+///
+/// ## Returns:
+/// * The Minkowski distance between the vectors.
+///
+/// ## Examples:
+///
+/// ```
+/// ```
+///
+#[doc = include_str!("../../docs/similarity/minkowski_distance.md")]
+pub fn minkowski_distance(u: &[f32], v: &[f32], p: f32) -> f32 {
+    u.iter()
+        .zip(v.iter())
+        .map(|(&ui, &vi)| (ui - vi).abs().powf(p))
+        .sum::<f32>()
+        .powf(1.0 / p)
+}
+
+/// # Similarity contributions
+/// Decomposes the similarity (or distance) between two vectors into the share of
+/// each dimension, to explain which features made two items similar.
+///
+/// ## Parameters:
+/// * `u`: The first vector.
+/// * `v`: The second vector.
+/// * `metric`: The similarity to decompose.
+///
+/// ## Returns:
+/// * `(dimension, contribution)` pairs sorted by decreasing absolute contribution.
+///   The contributions add up to the cosine similarities, the Pearson correlation
+///   and the MSD, to the squared euclidean distance and to the Spearman
+///   correlation minus 1.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::similarity::{contributions, SimilarityAlgos};
+/// let contributions = contributions(&[1.0, 0.0, 2.0], &[3.0, 1.0, 2.0], SimilarityAlgos::Euclidean);
+/// assert_eq!(contributions, vec![(0, 4.0), (1, 1.0), (2, 0.0)]);
+/// ```
+///
+#[doc = include_str!("../../docs/similarity/contributions.md")]
+pub fn contributions(u: &[f32], v: &[f32], metric: SimilarityAlgos) -> Vec<(usize, f32)> {
+    let n = u.len() as f32;
+    let mut contributions: Vec<(usize, f32)> = match metric {
+        SimilarityAlgos::Cosine | SimilarityAlgos::AdjustedCosine => {
+            let norms = euclidean_norm(u) * euclidean_norm(v);
+            per_dimension(u, v, |x, y| x * y / norms)
+        },
+        SimilarityAlgos::Euclidean => per_dimension(u, v, |x, y| (x - y).powi(2)),
+        SimilarityAlgos::MSD => per_dimension(u, v, |x, y| (x - y).powi(2) / n),
+        SimilarityAlgos::PearsonCorrelation => {
+            let (mean_u, mean_v) = (mean(u), mean(v));
+            let deviations = |x: &[f32], m: f32| {
+                x.iter().map(|&a| (a - m).powi(2)).sum::<f32>().sqrt()
+            };
+            let norms = deviations(u, mean_u) * deviations(v, mean_v);
+            per_dimension(u, v, |x, y| (x - mean_u) * (y - mean_v) / norms)
+        },
+        SimilarityAlgos::Spearman => {
+            let denominator = n * (n.powi(2) - 1.0);
+            per_dimension(&spearman_rank(u), &spearman_rank(v), |x, y| {
+                -6.0 * (x - y).powi(2) / denominator
+            })
+        },
+    };
+    contributions.sort_by(|(_, a), (_, b)| b.abs().total_cmp(&a.abs()));
+    contributions
+}
+
+fn per_dimension<F: Fn(f32, f32) -> f32>(
+    u: &[f32],
+    v: &[f32],
+    f: F,
+) -> Vec<(usize, f32)> {
+    u.iter()
+        .zip(v.iter())
+        .map(|(&x, &y)| f(x, y))
+        .enumerate()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_approx_eq;
+
+    fn tags(pairs: &[(&str, f32)]) -> TagWeights {
+        pairs.iter().map(|(t, w)| (t.to_string(), *w)).collect()
+    }
+
+    #[test]
+    fn test_tag_similarities() {
+        let a = tags(&[("rock", 2.0), ("pop", 1.0)]);
+        let b = tags(&[("rock", 1.0), ("pop", 1.0), ("jazz", 2.0)]);
+        assert_eq!(weighted_jaccard(&a, &b), 0.4);
+        assert_eq!(weighted_jaccard(&a, &a), 1.0);
+        assert_eq!(
+            weighted_jaccard(&TagWeights::new(), &TagWeights::new()),
+            0.0
+        );
+        assert!(
+            (tag_cosine(&a, &b) - 3.0 / (5.0_f32.sqrt() * 6.0_f32.sqrt())).abs() < 1e-6
+        );
+        assert_eq!(tag_cosine(&a, &TagWeights::new()), 0.0);
+        assert_eq!(TagSimilarity::WeightedJaccard.compute(&a, &b), 0.4);
+    }
+
+    #[test]
+    fn test_tag_idf() {
+        let items = [
+            tags(&[("rock", 1.0), ("grunge", 1.0)]),
+            tags(&[("rock", 3.0)]),
+        ];
+        let idf = tag_idf(items.iter());
+        assert_eq!(idf["rock"], 1.0);
+        assert!((idf["grunge"] - (1.5_f32.ln() + 1.0)).abs() < 1e-6);
+        let weighted = apply_idf(&items[1], &idf);
+        assert_eq!(weighted["rock"], 3.0);
+        assert_eq!(apply_idf(&tags(&[("new", 2.0)]), &idf)["new"], 2.0);
+    }
+
+    #[test]
+    fn test_jaccard_similarity() {
+        let set_a: HashSet<&i8> = [3, 45, 7, 2].iter().collect();
+        let set_b: HashSet<&i8> = [2, 54, 13, 15].iter().collect();
+        assert_approx_eq!(jaccard_similarity(&set_a, &set_b), 0.142_857_15);
+    }
+
+    #[test]
+    fn test_id_sets_similarities() {
+        let a: IdSet = [1, 2, 3].into_iter().collect();
+        let b: IdSet = [2, 3, 4, 5, 6].into_iter().collect();
+        assert_eq!(jaccard_similarity_ids(&a, &b), 2.0 / 6.0);
+        assert_eq!(overlap_similarity(&a, &b), 2.0 / 3.0);
+        assert_eq!(jaccard_similarity_ids(&IdSet::new(), &IdSet::new()), 0.0);
+        assert_eq!(overlap_similarity(&a, &IdSet::new()), 0.0);
+    }
+
+    #[test]
+    fn test_cosine_similarity() {
+        assert_approx_eq!(
+            cosine_similarity(&[3.0, 45.0, 7.0, 2.0], &[2.0, 54.0, 13.0, 15.0]),
+            0.972_284_26,
+        );
+    }
+
+    #[test]
+    fn test_euclidean_distance() {
+        assert_approx_eq!(
+            euclidean_distance(&[3.0, 45.0, 7.0, 2.0], &[2.0, 54.0, 13.0, 15.0]),
+            16.941_074,
+        );
+    }
+
+    #[test]
+    fn test_pearson_correlation() {
+        assert_approx_eq!(
+            pearson_correlation(&[3.0, 45.0, 7.0, 2.0], &[2.0, 54.0, 13.0, 15.0]),
+            0.967_521_3,
+        );
+    }
+
+    #[test]
+    fn test_exponential_decay_similarity() {
+        assert_approx_eq!(
+            exponential_decay_similarity(23.5, 44.333_332, 10.0),
+            0.12451448,
+        );
+    }
+
+    #[test]
+    fn test_msd() {
+        assert_eq!(msd(&[3.0, 45.0, 7.0, 2.0], &[2.0, 54.0, 13.0, 15.0]), 71.75,);
+    }
+
+    #[test]
+    fn test_msd_similarity() {
+        assert_approx_eq!(
+            msd_similarity(&[3.0, 45.0, 7.0, 2.0], &[2.0, 54.0, 13.0, 15.0]),
+            0.013_745_705,
+        );
+    }
+
+    #[test]
+    fn test_spearman_correlation() {
+        assert_approx_eq!(
+            spearman_correlation(&[3.0, 45.0, 7.0, 2.0], &[2.0, 54.0, 13.0, 15.0]),
+            0.4,
+        );
+    }
+
+    #[test]
+    fn test_spearman_rank() {
+        assert_eq!(
+            spearman_rank(&[3.0, 45.0, 7.0, 2.0]),
+            vec![1.0, 3.0, 2.0, 0.0],
+        );
+    }
+
+    #[test]
+    fn test_spearman_rank_into() {
+        let (mut order, mut ranks) = (Vec::new(), vec![9.0; 7]);
+        for x in [vec![3.0, 45.0, 7.0, 2.0], vec![1.0, 1.0, 0.5], vec![]] {
+            spearman_rank_into(&x, &mut order, &mut ranks);
+            assert_eq!(ranks, spearman_rank(&x));
+        }
+    }
+
+    #[test]
+    fn test_contributions_add_up_to_the_similarity() {
+        let (u, v) = ([3.0, 45.0, 7.0, 2.0], [2.0, 54.0, 13.0, 15.0]);
+        let total = |metric| -> f32 {
+            contributions(&u, &v, metric).iter().map(|(_, c)| c).sum()
+        };
+        assert_approx_eq!(total(SimilarityAlgos::Cosine), cosine_similarity(&u, &v));
+        assert_eq!(total(SimilarityAlgos::MSD), msd(&u, &v));
+        assert_approx_eq!(
+            total(SimilarityAlgos::Euclidean),
+            euclidean_distance(&u, &v).powi(2)
+        );
+        assert_approx_eq!(total(SimilarityAlgos::PearsonCorrelation), 0.967_521_25);
+        assert_approx_eq!(
+            total(SimilarityAlgos::Spearman),
+            spearman_correlation(&u, &v) - 1.0
+        );
+    }
+
+    #[test]
+    fn test_contributions_are_sorted() {
+        let result =
+            contributions(&[1.0, 5.0, 1.0], &[1.0, 5.0, 0.0], SimilarityAlgos::Cosine);
+        assert_eq!(
+            result.iter().map(|(d, _)| *d).collect::<Vec<usize>>(),
+            vec![1, 0, 2]
+        );
+    }
+
+    #[test]
+    fn test_minkowski_distance() {
+        assert_approx_eq!(
+            minkowski_distance(&[3.0, 45.0, 7.0, 2.0], &[2.0, 54.0, 13.0, 15.0], 2.1,),
+            16.566_133,
+        );
+    }
+}
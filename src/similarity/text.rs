@@ -0,0 +1,320 @@
+//! # String similarities
+//! Compares short strings such as item titles, to find the near-duplicates a
+//! catalog accumulates when it is fed from several sources ("The Matrix (1999)",
+//! "Matrix, The").
+use std::collections::{BTreeSet, HashMap};
+
+use crate::memory::MemoryFootprint;
+
+/// The lowercase letters and digits of a string, its other characters collapsed
+/// into single spaces.
+fn normalize(text: &str) -> Vec<char> {
+    let mut normalized: Vec<char> = Vec::with_capacity(text.len());
+    for c in text.chars().flat_map(char::to_lowercase) {
+        match c.is_alphanumeric() {
+            true => normalized.push(c),
+            false if normalized.last().is_some_and(|last| *last != ' ') => {
+                normalized.push(' ')
+            },
+            false => {},
+        }
+    }
+    if normalized.last() == Some(&' ') {
+        normalized.pop();
+    }
+    normalized
+}
+
+/// # Character n-grams
+/// The distinct sequences of `n` characters of a normalized string, padded with a
+/// space on each side so the first and last characters weigh as much as the
+/// others. Strings shorter than `n` give a single n-gram.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::similarity::text::char_ngrams;
+/// let ngrams = char_ngrams("Cat", 3);
+/// assert_eq!(ngrams.into_iter().collect::<Vec<_>>(), [" ca", "at ", "cat"]);
+/// ```
+pub fn char_ngrams(text: &str, n: usize) -> BTreeSet<String> {
+    let n = n.max(1);
+    let mut padded = vec![' '];
+    padded.extend(normalize(text));
+    padded.push(' ');
+    if padded.len() <= n {
+        return BTreeSet::from([padded.into_iter().collect()]);
+    }
+    padded
+        .windows(n)
+        .map(|window| window.iter().collect())
+        .collect()
+}
+
+/// # N-gram Jaccard similarity
+/// The Jaccard similarity of the [`char_ngrams`] of two strings: robust to
+/// reordered words and typos, as most n-grams survive them.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::similarity::text::ngram_jaccard;
+/// assert_eq!(ngram_jaccard("The Matrix", "the matrix!", 3), 1.0);
+/// assert!(ngram_jaccard("The Matrix", "Matrix, The", 3) > 0.5);
+/// assert_eq!(ngram_jaccard("abc", "xyz", 3), 0.0);
+/// ```
+pub fn ngram_jaccard(a: &str, b: &str, n: usize) -> f32 {
+    let (a, b) = (char_ngrams(a, n), char_ngrams(b, n));
+    let union = a.union(&b).count();
+    match union {
+        0 => 0.0,
+        _ => a.intersection(&b).count() as f32 / union as f32,
+    }
+}
+
+/// The Jaro similarity of two sequences of characters.
+fn jaro_chars(a: &[char], b: &[char]) -> f32 {
+    if a.is_empty() && b.is_empty() {
+        return 1.0;
+    }
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    let window = (a.len().max(b.len()) / 2).saturating_sub(1);
+    let mut b_matched = vec![false; b.len()];
+    let mut a_matches = Vec::new();
+    for (i, c) in a.iter().enumerate() {
+        let (start, end) = (i.saturating_sub(window), (i + window + 1).min(b.len()));
+        if let Some(j) = (start..end).find(|&j| !b_matched[j] && b[j] == *c) {
+            b_matched[j] = true;
+            a_matches.push(*c);
+        }
+    }
+    if a_matches.is_empty() {
+        return 0.0;
+    }
+    let b_matches = b
+        .iter()
+        .zip(&b_matched)
+        .filter(|(_, m)| **m)
+        .map(|(c, _)| c);
+    let transpositions = a_matches
+        .iter()
+        .zip(b_matches)
+        .filter(|(x, y)| x != y)
+        .count() as f32
+        / 2.0;
+    let m = a_matches.len() as f32;
+    (m / a.len() as f32 + m / b.len() as f32 + (m - transpositions) / m) / 3.0
+}
+
+/// # Jaro-Winkler similarity
+/// The Jaro similarity of two normalized strings, boosted by the length of their
+/// common prefix up to 4 characters. Suits short strings differing by a few typos,
+/// less reordered words.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::similarity::text::jaro_winkler;
+/// assert!((jaro_winkler("MARTHA", "MARHTA") - 0.961).abs() < 1e-3);
+/// assert_eq!(jaro_winkler("Heat", "heat"), 1.0);
+/// ```
+#[doc = include_str!("../../docs/similarity/jaro_winkler.md")]
+pub fn jaro_winkler(a: &str, b: &str) -> f32 {
+    let (a, b) = (normalize(a), normalize(b));
+    let jaro = jaro_chars(&a, &b);
+    let prefix = a.iter().zip(&b).take(4).take_while(|(x, y)| x == y).count();
+    jaro + prefix as f32 * 0.1 * (1.0 - jaro)
+}
+
+/// Similarity between two strings.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind", rename_all = "snake_case"))]
+pub enum StringSimilarity {
+    /// See [`ngram_jaccard`].
+    NgramJaccard { n: usize },
+    /// See [`jaro_winkler`].
+    JaroWinkler,
+}
+
+impl Default for StringSimilarity {
+    fn default() -> Self {
+        StringSimilarity::NgramJaccard { n: 3 }
+    }
+}
+
+impl StringSimilarity {
+    pub fn compute(&self, a: &str, b: &str) -> f32 {
+        match self {
+            StringSimilarity::NgramJaccard { n } => ngram_jaccard(a, b, *n),
+            StringSimilarity::JaroWinkler => jaro_winkler(a, b),
+        }
+    }
+}
+
+impl MemoryFootprint for StringSimilarity {}
+
+/// Two items whose titles are similar enough to be the same item.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MergeCandidate {
+    /// The smaller id of the two.
+    pub item_id: u32,
+    pub duplicate_id: u32,
+    pub similarity: f32,
+}
+
+impl MemoryFootprint for MergeCandidate {}
+
+/// The most titles a trigram may appear in to bring candidates, beyond it the
+/// trigram is as common as "the" and would pair almost every title with every
+/// other one.
+const MAX_POSTINGS: usize = 256;
+
+/// # Find duplicates
+/// The pairs of items whose titles are at least `threshold` similar, to review or
+/// merge. Only the pairs sharing a character trigram are compared, which keeps
+/// large catalogs far from the cost of comparing every pair. The trigrams found in
+/// more than 256 titles are not used, so two titles made only of very common
+/// trigrams are not compared.
+///
+/// ## Parameters:
+/// * `titles`: The id and the title of every item.
+/// * `similarity`: How the titles are compared.
+/// * `threshold`: The minimum similarity of a candidate.
+///
+/// ## Returns:
+/// * The candidates, from the most similar.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::similarity::text::{find_duplicates, StringSimilarity};
+/// let titles = [(1, "The Matrix (1999)"), (2, "Heat"), (3, "the matrix 1999"), (4, "Matrix, The")];
+/// let candidates = find_duplicates(&titles, StringSimilarity::default(), 0.5);
+/// assert_eq!(candidates.len(), 3);
+/// assert_eq!((candidates[0].item_id, candidates[0].duplicate_id), (1, 3));
+/// ```
+pub fn find_duplicates<S: AsRef<str>>(
+    titles: &[(u32, S)],
+    similarity: StringSimilarity,
+    threshold: f32,
+) -> Vec<MergeCandidate> {
+    let mut candidates: Vec<MergeCandidate> = candidate_pairs(titles, MAX_POSTINGS)
+        .into_iter()
+        .filter_map(|(a, b)| {
+            let ((a_id, a_title), (b_id, b_title)) = (&titles[a], &titles[b]);
+            let score = similarity.compute(a_title.as_ref(), b_title.as_ref());
+            (score >= threshold && a_id != b_id).then(|| MergeCandidate {
+                item_id: *a_id.min(b_id),
+                duplicate_id: *a_id.max(b_id),
+                similarity: score,
+            })
+        })
+        .collect();
+    candidates.sort_by(|a, b| {
+        b.similarity
+            .total_cmp(&a.similarity)
+            .then_with(|| (a.item_id, a.duplicate_id).cmp(&(b.item_id, b.duplicate_id)))
+    });
+    candidates
+}
+
+/// The positions of the titles sharing a trigram found in at most `max_postings`
+/// titles, the smaller position first.
+fn candidate_pairs<S: AsRef<str>>(
+    titles: &[(u32, S)],
+    max_postings: usize,
+) -> BTreeSet<(usize, usize)> {
+    let mut index: HashMap<String, Vec<usize>> = HashMap::new();
+    for (position, (_, title)) in titles.iter().enumerate() {
+        for ngram in char_ngrams(title.as_ref(), 3) {
+            index.entry(ngram).or_default().push(position);
+        }
+    }
+    let mut pairs: BTreeSet<(usize, usize)> = BTreeSet::new();
+    for positions in index.values().filter(|p| p.len() <= max_postings) {
+        for (i, a) in positions.iter().enumerate() {
+            pairs.extend(positions[i + 1..].iter().map(|b| (*a, *b)));
+        }
+    }
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ngrams() {
+        assert_eq!(
+            normalize("  The  Matrix: Reloaded! "),
+            "the matrix reloaded".chars().collect::<Vec<_>>()
+        );
+        assert_eq!(char_ngrams("a", 3), BTreeSet::from([" a ".to_string()]));
+        assert_eq!(char_ngrams("ab", 1).len(), 3);
+        assert_eq!(ngram_jaccard("", "", 3), 1.0);
+        let typo = ngram_jaccard("Pulp Fiction", "Pulp Fictoin", 3);
+        assert!(typo > 0.4 && typo < 1.0);
+    }
+
+    #[test]
+    fn test_jaro_winkler() {
+        assert!((jaro_chars(&['a', 'b', 'c'], &['a', 'b', 'c']) - 1.0).abs() < 1e-6);
+        let dixon = jaro_chars(&normalize("DIXON"), &normalize("DICKSONX"));
+        assert!((dixon - 0.767).abs() < 1e-3);
+        assert!((jaro_winkler("DIXON", "DICKSONX") - 0.813).abs() < 1e-3);
+        assert_eq!(jaro_winkler("", ""), 1.0);
+        assert_eq!(jaro_winkler("abc", ""), 0.0);
+        assert_eq!(jaro_winkler("abc", "xyz"), 0.0);
+    }
+
+    #[test]
+    fn test_find_duplicates() {
+        let titles = vec![
+            (7, "Star Wars".to_string()),
+            (3, "Star Wars!".to_string()),
+            (5, "Star Trek".to_string()),
+            (9, "Alien".to_string()),
+        ];
+        let candidates = find_duplicates(&titles, StringSimilarity::JaroWinkler, 0.9);
+        assert_eq!(
+            candidates,
+            vec![MergeCandidate {
+                item_id: 3,
+                duplicate_id: 7,
+                similarity: 1.0
+            }]
+        );
+        let loose = find_duplicates(&titles, StringSimilarity::JaroWinkler, 0.7);
+        assert_eq!(loose.len(), 3);
+        assert!(
+            find_duplicates::<&str>(&[], StringSimilarity::default(), 0.0).is_empty()
+        );
+    }
+
+    #[test]
+    fn test_common_trigrams_bring_no_candidates() {
+        // Every title starts with "the", the rest is three letters of its own.
+        let letter = |i: usize| char::from(b'a' + (i % 26) as u8);
+        let mut titles: Vec<(u32, String)> = (0..1_000)
+            .map(|i| {
+                let word: String = [letter(i), letter(i / 26), letter(i / 676)]
+                    .iter()
+                    .collect();
+                (i as u32, format!("The {}", word))
+            })
+            .collect();
+        titles.push((9_999, "The aaa!".to_string()));
+        let all_pairs = titles.len() * (titles.len() - 1) / 2;
+        assert_eq!(candidate_pairs(&titles, usize::MAX).len(), all_pairs);
+        assert!(candidate_pairs(&titles, MAX_POSTINGS).len() < all_pairs / 10);
+        let candidates = find_duplicates(&titles, StringSimilarity::default(), 0.99);
+        assert_eq!(
+            candidates,
+            vec![MergeCandidate {
+                item_id: 0,
+                duplicate_id: 9_999,
+                similarity: 1.0
+            }]
+        );
+    }
+}
@@ -0,0 +1,328 @@
+//! # Feedback loop simulation
+//! Replays a dataset in chronological order, lets a recommender serve a list to
+//! every active user at each step, simulates which recommendations are clicked and
+//! feeds the clicks back as training data. Running it for several steps shows how
+//! a model narrows what it recommends when it learns from its own exposure.
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::algorithms::config::{ensure, AlgorithmConfig};
+use crate::dataset::{Dataset, Rating};
+use crate::errors::{Error, Result};
+use crate::recommender::{Recommendation, Recommender};
+
+/// How simulated users react to the lists they are shown. An item is relevant to
+/// a user when the user interacts with it during the step in the logged data.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClickModel {
+    /// Every relevant item shown is clicked, and nothing else.
+    Relevance,
+    /// Clicks with a probability depending on the relevance of the item, divided
+    /// by `decay` at each position, since users look less at the bottom of lists.
+    PositionBiased {
+        relevant: f32,
+        irrelevant: f32,
+        decay: f32,
+    },
+}
+
+impl Default for ClickModel {
+    fn default() -> Self {
+        ClickModel::PositionBiased {
+            relevant: 0.9,
+            irrelevant: 0.05,
+            decay: 1.2,
+        }
+    }
+}
+
+impl ClickModel {
+    fn click_probability(&self, relevant: bool, position: usize) -> f32 {
+        match *self {
+            ClickModel::Relevance => f32::from(u8::from(relevant)),
+            ClickModel::PositionBiased {
+                relevant: p_relevant,
+                irrelevant,
+                decay,
+            } => {
+                let p = if relevant { p_relevant } else { irrelevant };
+                p / decay.powi(position as i32)
+            },
+        }
+    }
+}
+
+/// Parameters of a [`simulate`] run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SimulationConfig {
+    /// Number of chronological chunks of the dataset. The first one is the
+    /// initial training data, a list is served at each of the following ones.
+    pub num_steps: usize,
+    /// Length of the lists served.
+    pub num_items: usize,
+    pub click_model: ClickModel,
+    /// Rating given to the clicked items when they are fed back.
+    pub click_rating: f32,
+    /// Also train on the logged interactions of the past steps. Without them the
+    /// model only learns from the clicks on its own recommendations.
+    pub replay_log: bool,
+    pub seed: u64,
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        SimulationConfig {
+            num_steps: 10,
+            num_items: 10,
+            click_model: ClickModel::default(),
+            click_rating: 1.0,
+            replay_log: true,
+            seed: 42,
+        }
+    }
+}
+
+impl SimulationConfig {
+    pub fn set_num_steps(mut self, num_steps: usize) -> Self {
+        self.num_steps = num_steps;
+        self
+    }
+    pub fn set_num_items(mut self, num_items: usize) -> Self {
+        self.num_items = num_items;
+        self
+    }
+    pub fn set_click_model(mut self, click_model: ClickModel) -> Self {
+        self.click_model = click_model;
+        self
+    }
+    pub fn set_click_rating(mut self, click_rating: f32) -> Self {
+        self.click_rating = click_rating;
+        self
+    }
+    pub fn set_replay_log(mut self, replay_log: bool) -> Self {
+        self.replay_log = replay_log;
+        self
+    }
+    pub fn set_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+}
+
+impl AlgorithmConfig for SimulationConfig {
+    fn validate(&self) -> Result<()> {
+        ensure(self.num_steps >= 2, "num_steps", "at least 2")?;
+        ensure(self.num_items > 0, "num_items", "greater than 0")?;
+        match self.click_model {
+            ClickModel::Relevance => Ok(()),
+            ClickModel::PositionBiased {
+                relevant,
+                irrelevant,
+                decay,
+            } => {
+                let probability = 0.0..=1.0;
+                ensure(
+                    probability.contains(&relevant) && probability.contains(&irrelevant),
+                    "click_model",
+                    "probabilities between 0 and 1",
+                )?;
+                ensure(decay >= 1.0, "click_model.decay", "at least 1")
+            },
+        }
+    }
+}
+
+/// What happened during one step of the simulation.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StepReport {
+    pub step: usize,
+    /// Users served a list.
+    pub num_users: usize,
+    /// Recommended items shown, over every list.
+    pub impressions: usize,
+    pub clicks: usize,
+    /// Distinct items recommended.
+    pub distinct_items: usize,
+    /// Gini coefficient of the impressions of the recommended items, 0 when they
+    /// are shown equally, close to 1 when a few items take all the exposure.
+    pub exposure_gini: f32,
+}
+
+impl StepReport {
+    /// Fraction of the impressions that were clicked.
+    pub fn click_through_rate(&self) -> f32 {
+        match self.impressions {
+            0 => 0.0,
+            impressions => self.clicks as f32 / impressions as f32,
+        }
+    }
+}
+
+/// # Simulate
+/// Runs the feedback loop: the model is trained on what it would have known at the
+/// start of each step, recommends to the users active during the step, and the
+/// simulated clicks become training data for the following steps. Ratings without
+/// timestamp are replayed last, in their order in the dataset.
+///
+/// ## Parameters:
+/// * `model`: The recommender, trained again at every step.
+/// * `dataset`: The logged interactions.
+/// * `config`: The parameters of the run.
+///
+/// ## Returns:
+/// * One report per served step.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::algorithms::most_popular::MostPopular;
+/// use rec_rsys::dataset::{Dataset, Rating};
+/// use rec_rsys::simulation::{simulate, ClickModel, SimulationConfig};
+/// let ratings = (0..40)
+///     .map(|t| Rating::new(t % 8, t % 5, 1.0).timestamp(t as u64))
+///     .collect();
+/// let config = SimulationConfig::default()
+///     .set_num_steps(4)
+///     .set_num_items(2)
+///     .set_click_model(ClickModel::Relevance);
+/// let reports = simulate(&mut MostPopular::default(), &Dataset::new(ratings), &config).unwrap();
+/// assert_eq!(reports.len(), 3);
+/// assert!(reports.iter().all(|r| r.clicks <= r.impressions));
+/// ```
+pub fn simulate<R: Recommender + ?Sized>(
+    model: &mut R,
+    dataset: &Dataset,
+    config: &SimulationConfig,
+) -> Result<Vec<StepReport>> {
+    config.validate()?;
+    if dataset.len() < config.num_steps {
+        return Err(Error::InvalidData(format!(
+            "{} ratings cannot be split in {} steps",
+            dataset.len(),
+            config.num_steps
+        )));
+    }
+    let mut log = dataset.ratings.clone();
+    log.sort_by_key(|rating| rating.timestamp.unwrap_or(u64::MAX));
+    let chunk_len = log.len().div_ceil(config.num_steps);
+    let mut chunks = log.chunks(chunk_len);
+    let mut training: Vec<Rating> = chunks.next().unwrap_or_default().to_vec();
+    let mut rng = StdRng::seed_from_u64(config.seed);
+    let mut reports = Vec::new();
+    for (step, chunk) in chunks.enumerate() {
+        model.fit(&Dataset::new(training.clone()))?;
+        let mut relevant: HashMap<u32, HashSet<u32>> = HashMap::new();
+        for rating in chunk {
+            relevant
+                .entry(rating.user_id)
+                .or_default()
+                .insert(rating.item_id);
+        }
+        let users: BTreeSet<u32> = relevant.keys().copied().collect();
+        let mut impressions: HashMap<u32, usize> = HashMap::new();
+        let mut clicks = Vec::new();
+        for user_id in &users {
+            let shown = model.recommend(*user_id, config.num_items);
+            for (position, Recommendation { item_id, .. }) in shown.iter().enumerate() {
+                *impressions.entry(*item_id).or_default() += 1;
+                let is_relevant = relevant[user_id].contains(item_id);
+                let probability =
+                    config.click_model.click_probability(is_relevant, position);
+                if rng.gen::<f32>() < probability {
+                    clicks.push(Rating::new(*user_id, *item_id, config.click_rating));
+                }
+            }
+        }
+        reports.push(StepReport {
+            step: step + 1,
+            num_users: users.len(),
+            impressions: impressions.values().sum(),
+            clicks: clicks.len(),
+            distinct_items: impressions.len(),
+            exposure_gini: gini(impressions.into_values().collect()),
+        });
+        if config.replay_log {
+            training.extend_from_slice(chunk);
+        }
+        training.extend(clicks);
+    }
+    Ok(reports)
+}
+
+/// Gini coefficient of non-negative counts.
+fn gini(mut counts: Vec<usize>) -> f32 {
+    let total: usize = counts.iter().sum();
+    if total == 0 {
+        return 0.0;
+    }
+    counts.sort_unstable();
+    let n = counts.len() as f32;
+    let weighted: f32 = counts
+        .iter()
+        .enumerate()
+        .map(|(i, count)| (2.0 * (i as f32 + 1.0) - n - 1.0) * *count as f32)
+        .sum();
+    weighted / (n * total as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithms::most_popular::MostPopular;
+
+    fn dataset() -> Dataset {
+        Dataset::new(
+            (0..60)
+                .map(|t| Rating::new(t % 6, (t * 7) % 10, 1.0).timestamp(t as u64))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_gini() {
+        assert_eq!(gini(vec![]), 0.0);
+        assert_eq!(gini(vec![3, 3, 3]), 0.0);
+        assert_eq!(gini(vec![0, 0, 0, 4]), 0.75);
+    }
+
+    #[test]
+    fn test_simulate() {
+        let config = SimulationConfig::default()
+            .set_num_steps(5)
+            .set_num_items(3)
+            .set_click_model(ClickModel::Relevance);
+        let reports = simulate(&mut MostPopular::default(), &dataset(), &config).unwrap();
+        assert_eq!(reports.len(), 4);
+        assert_eq!(
+            reports.iter().map(|r| r.step).collect::<Vec<usize>>(),
+            vec![1, 2, 3, 4]
+        );
+        for report in &reports {
+            assert_eq!(report.num_users, 6);
+            assert!(report.impressions <= 18);
+            assert!(report.clicks <= report.impressions);
+            assert!((0.0..=1.0).contains(&report.click_through_rate()));
+        }
+        let again = simulate(&mut MostPopular::default(), &dataset(), &config).unwrap();
+        assert_eq!(reports, again);
+    }
+
+    #[test]
+    fn test_invalid_simulation() {
+        let config = SimulationConfig::default().set_num_steps(1);
+        assert!(simulate(&mut MostPopular::default(), &dataset(), &config).is_err());
+        let config = SimulationConfig::default().set_num_steps(100);
+        assert!(simulate(&mut MostPopular::default(), &dataset(), &config).is_err());
+        let config =
+            SimulationConfig::default().set_click_model(ClickModel::PositionBiased {
+                relevant: 1.5,
+                irrelevant: 0.0,
+                decay: 1.0,
+            });
+        assert!(config.validate().is_err());
+    }
+}
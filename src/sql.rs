@@ -0,0 +1,372 @@
+//! # Relational databases
+//! Streams interactions and items out of Postgres, MySQL or SQLite queries into a
+//! [`Dataset`] and an [`ItemCatalog`], for data that lives in a database rather
+//! than in files. The queries are plain SQL, so joins and filters run where the
+//! data is; only the selected columns cross the wire.
+use async_trait::async_trait;
+use futures::TryStreamExt;
+use sqlx::any::{AnyPoolOptions, AnyRow};
+use sqlx::{AnyPool, Row};
+
+use crate::catalog::{AttributeType, AttributeValue, Attributes, ItemCatalog, Schema};
+use crate::dataset::{Dataset, Rating};
+use crate::errors::{Error, Result};
+use crate::models::{AsyncItemAdapter, Item};
+
+fn database_error(context: &str, error: sqlx::Error) -> Error {
+    Error::Backend {
+        context: context.to_string(),
+        source: Box::new(error),
+    }
+}
+
+/// # SQL source
+/// A pool of connections to a database, the driver being picked from the scheme
+/// of the url: `postgres://`, `mysql://` or `sqlite:`.
+///
+/// ## Examples:
+/// ```no_run
+/// use rec_rsys::sql::SqlSource;
+/// # async fn load() -> rec_rsys::errors::Result<()> {
+/// let source = SqlSource::connect("postgres://localhost/shop").await?;
+/// let dataset = source
+///     .load_dataset("SELECT user_id, item_id, quantity AS rating FROM orders")
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct SqlSource {
+    pool: AnyPool,
+}
+
+impl SqlSource {
+    pub async fn connect(url: &str) -> Result<Self> {
+        sqlx::any::install_default_drivers();
+        let pool = AnyPoolOptions::new()
+            .connect(url)
+            .await
+            .map_err(|error| database_error("connecting", error))?;
+        Ok(SqlSource { pool })
+    }
+
+    /// Uses a pool created by the application.
+    pub fn new(pool: AnyPool) -> Self {
+        SqlSource { pool }
+    }
+
+    pub fn pool(&self) -> &AnyPool {
+        &self.pool
+    }
+
+    /// # Load dataset
+    /// Streams the rows of a query into ratings.
+    ///
+    /// ## Parameters:
+    /// * `query`: Selects the integer columns `user_id` and `item_id`, optionally a
+    ///   numeric `rating`, `1.0` when absent, and an integer `timestamp` in seconds.
+    ///
+    /// ## Returns:
+    /// * The ratings, in the order of the rows.
+    pub async fn load_dataset(&self, query: &str) -> Result<Dataset> {
+        let mut rows = sqlx::query(query).fetch(&self.pool);
+        let mut ratings = Vec::new();
+        while let Some(row) = rows
+            .try_next()
+            .await
+            .map_err(|error| database_error("loading ratings", error))?
+        {
+            ratings.push(Rating {
+                user_id: id(&row, "user_id")?,
+                item_id: id(&row, "item_id")?,
+                rating: match has_column(&row, "rating") {
+                    true => real(&row, "rating")?.unwrap_or(1.0) as f32,
+                    false => 1.0,
+                },
+                timestamp: match has_column(&row, "timestamp") {
+                    true => integer(&row, "timestamp")?
+                        .map(|timestamp| {
+                            u64::try_from(timestamp).map_err(|_| {
+                                invalid_column("timestamp", "a negative timestamp")
+                            })
+                        })
+                        .transpose()?,
+                    false => None,
+                },
+            });
+        }
+        Ok(Dataset::new(ratings))
+    }
+
+    /// # Load catalog
+    /// Streams the rows of a query into a catalog validated against `schema`.
+    ///
+    /// ## Parameters:
+    /// * `query`: Selects the integer column `item_id` and one column per
+    ///   attribute of the schema, named after it. Tags are comma separated text and
+    ///   `NULL` is a missing attribute.
+    /// * `schema`: The attributes of the items.
+    ///
+    /// ## Returns:
+    /// * The catalog, or an error on the first item not matching the schema.
+    pub async fn load_catalog(&self, query: &str, schema: Schema) -> Result<ItemCatalog> {
+        let mut rows = sqlx::query(query).fetch(&self.pool);
+        let mut catalog = ItemCatalog::new(schema);
+        while let Some(row) = rows
+            .try_next()
+            .await
+            .map_err(|error| database_error("loading items", error))?
+        {
+            let mut attributes = Attributes::new();
+            for (name, spec) in &catalog.schema().attributes {
+                if !has_column(&row, name) {
+                    continue;
+                }
+                let value = match spec.attribute_type {
+                    AttributeType::Numeric => real(&row, name.as_str())?
+                        .map(|v| AttributeValue::Numeric(v as f32)),
+                    AttributeType::Categorical => {
+                        text(&row, name)?.map(AttributeValue::Categorical)
+                    },
+                    AttributeType::Text => text(&row, name)?.map(AttributeValue::Text),
+                    AttributeType::Tags => text(&row, name)?.map(|tags| {
+                        AttributeValue::Tags(
+                            tags.split(',')
+                                .map(str::trim)
+                                .filter(|tag| !tag.is_empty())
+                                .map(str::to_string)
+                                .collect(),
+                        )
+                    }),
+                };
+                if let Some(value) = value {
+                    attributes.insert(name.clone(), value);
+                }
+            }
+            catalog.insert(id(&row, "item_id")?, attributes)?;
+        }
+        Ok(catalog)
+    }
+
+    /// # Load items
+    /// Streams the rows of a query into [`Item`]s, e.g. the references of a
+    /// content based similarity.
+    ///
+    /// ## Parameters:
+    /// * `query`: Selects the integer column `item_id` followed by the numeric
+    ///   columns of the values.
+    pub async fn load_items(&self, query: &str) -> Result<Vec<Item>> {
+        let mut rows = sqlx::query(query).fetch(&self.pool);
+        let mut items = Vec::new();
+        while let Some(row) = rows
+            .try_next()
+            .await
+            .map_err(|error| database_error("loading items", error))?
+        {
+            items.push(Item::new(id(&row, "item_id")?, values(&row, 1)?, None));
+        }
+        Ok(items)
+    }
+
+    /// # SQL item
+    /// Fetches an item and the items it is compared to, so it can be handed to the
+    /// code written against [`AsyncItemAdapter`].
+    ///
+    /// ## Parameters:
+    /// * `item_id`: The item, bound to the first parameter of `values_query`.
+    /// * `values_query`: Selects the numeric values of the item.
+    /// * `references_query`: Selects the references, see [`SqlSource::load_items`].
+    pub async fn item(
+        &self,
+        item_id: u32,
+        values_query: &str,
+        references_query: &str,
+    ) -> Result<SqlItem> {
+        let row = sqlx::query(values_query)
+            .bind(item_id as i64)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|error| database_error("loading the item", error))?
+            .ok_or_else(|| Error::InvalidData(format!("unknown item {}", item_id)))?;
+        Ok(SqlItem {
+            id: item_id,
+            values: values(&row, 0)?,
+            references: self.load_items(references_query).await?,
+        })
+    }
+}
+
+/// An item read from a database by [`SqlSource::item`]. The queries already ran,
+/// so the adapter methods only hand the rows over.
+#[derive(Debug, Clone)]
+pub struct SqlItem {
+    id: u32,
+    values: Vec<f32>,
+    references: Vec<Item>,
+}
+
+#[async_trait]
+impl AsyncItemAdapter for SqlItem {
+    async fn to_item(&self) -> Item {
+        Item::new(self.id, self.create_values().await, None)
+    }
+
+    async fn create_values(&self) -> Vec<f32> {
+        self.values.clone()
+    }
+
+    async fn get_references(&self) -> Vec<Item> {
+        self.references.clone()
+    }
+}
+
+fn invalid_column(column: &str, message: &str) -> Error {
+    Error::InvalidData(format!("column {}: {}", column, message))
+}
+
+fn has_column(row: &AnyRow, column: &str) -> bool {
+    row.try_column(column).is_ok()
+}
+
+fn integer(row: &AnyRow, column: &str) -> Result<Option<i64>> {
+    row.try_get(column)
+        .map_err(|error| invalid_column(column, &error.to_string()))
+}
+
+fn id(row: &AnyRow, column: &str) -> Result<u32> {
+    let value = integer(row, column)?.ok_or_else(|| invalid_column(column, "NULL id"))?;
+    u32::try_from(value).map_err(|_| invalid_column(column, "the id does not fit a u32"))
+}
+
+/// A number, whether the column holds floats or integers.
+fn real<I: sqlx::ColumnIndex<AnyRow> + Copy + ToString>(
+    row: &AnyRow,
+    column: I,
+) -> Result<Option<f64>> {
+    row.try_get_unchecked::<Option<f64>, _>(column)
+        .or_else(|_| {
+            row.try_get::<Option<i64>, _>(column)
+                .map(|value| value.map(|v| v as f64))
+        })
+        .map_err(|error| invalid_column(&column.to_string(), &error.to_string()))
+}
+
+fn text(row: &AnyRow, column: &str) -> Result<Option<String>> {
+    row.try_get(column)
+        .map_err(|error| invalid_column(column, &error.to_string()))
+}
+
+/// The numeric columns of a row from `start`, `NULL` being `0`.
+fn values(row: &AnyRow, start: usize) -> Result<Vec<f32>> {
+    (start..row.len())
+        .map(|index| Ok(real(row, index)?.unwrap_or(0.0) as f32))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn source() -> SqlSource {
+        sqlx::any::install_default_drivers();
+        // A single connection, every new one would open another empty database.
+        let pool = AnyPoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        let source = SqlSource::new(pool);
+        for statement in [
+            "CREATE TABLE ratings (user_id INTEGER, item_id INTEGER, stars REAL, ts INTEGER)",
+            "INSERT INTO ratings VALUES (1, 10, 4.5, 100), (1, 11, 3, NULL), (2, 10, 5.0, 200)",
+            "CREATE TABLE items (item_id INTEGER, price REAL, genres TEXT, width INTEGER)",
+            "INSERT INTO items VALUES (10, 9.5, 'rock, pop', 3), (11, NULL, 'jazz', 4)",
+        ] {
+            sqlx::query(statement).execute(source.pool()).await.unwrap();
+        }
+        source
+    }
+
+    #[tokio::test]
+    async fn test_load_dataset() {
+        let source = source().await;
+        let dataset = source
+            .load_dataset(
+                "SELECT user_id, item_id, stars AS rating, ts AS timestamp FROM ratings",
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            dataset.ratings,
+            vec![
+                Rating::new(1, 10, 4.5).timestamp(100),
+                Rating::new(1, 11, 3.0),
+                Rating::new(2, 10, 5.0).timestamp(200),
+            ]
+        );
+        let implicit = source
+            .load_dataset("SELECT user_id, item_id FROM ratings")
+            .await
+            .unwrap();
+        assert!(implicit.ratings.iter().all(|r| r.rating == 1.0));
+        assert!(source
+            .load_dataset("SELECT item_id FROM ratings")
+            .await
+            .is_err());
+        let error = source
+            .load_dataset("SELECT * FROM missing")
+            .await
+            .unwrap_err();
+        assert!(error.to_string().starts_with("loading ratings: "));
+        let cause = std::error::Error::source(&error).unwrap();
+        assert!(cause.downcast_ref::<sqlx::Error>().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_load_catalog() {
+        let source = source().await;
+        let schema = Schema::new()
+            .attribute("price", AttributeType::Numeric, false)
+            .attribute("genres", AttributeType::Tags, true);
+        let catalog = source
+            .load_catalog("SELECT item_id, price, genres FROM items", schema)
+            .await
+            .unwrap();
+        assert_eq!(catalog.len(), 2);
+        assert_eq!(
+            catalog.attribute(10, "genres"),
+            Some(&AttributeValue::Tags(vec![
+                "rock".to_string(),
+                "pop".to_string()
+            ]))
+        );
+        assert_eq!(catalog.attribute(11, "price"), None);
+        let required = Schema::new().attribute("price", AttributeType::Numeric, true);
+        assert!(source
+            .load_catalog("SELECT item_id, price FROM items", required)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_item_adapter() {
+        let source = source().await;
+        let item = source
+            .item(
+                10,
+                "SELECT price, width FROM items WHERE item_id = ?",
+                "SELECT item_id, price, width FROM items WHERE item_id != 10",
+            )
+            .await
+            .unwrap();
+        assert_eq!(item.to_item().await.values, vec![9.5, 3.0]);
+        let references = item.get_references().await;
+        assert_eq!(references.len(), 1);
+        assert_eq!(references[0].id, 11);
+        assert_eq!(references[0].values, vec![0.0, 4.0]);
+        assert!(source
+            .item(99, "SELECT price FROM items WHERE item_id = ?", "SELECT 1")
+            .await
+            .is_err());
+    }
+}
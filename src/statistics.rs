@@ -1,6 +1,8 @@
 //! # A collection of statistical functions
 //!
+
 use super::utils::local_sort;
+use crate::errors::{Error, Result};
 
 /// # Mean
 /// Function to calculate the mean (average) of a set of data.
@@ -43,7 +45,7 @@ pub fn quartiles(data: &mut [f32]) -> (f32, f32) {
 /// * The value at the specified percentile.
 ///
 #[doc = include_str!("../docs/statistics/percentile_of_sorted.md")]
-fn percentile_of_sorted(sorted_samples: &[f32], pct: f32) -> f32 {
+pub(crate) fn percentile_of_sorted(sorted_samples: &[f32], pct: f32) -> f32 {
     let sorted_len = sorted_samples.len();
     if sorted_len == 1 {
         return sorted_samples[0];
@@ -65,11 +67,16 @@ fn percentile_of_sorted(sorted_samples: &[f32], pct: f32) -> f32 {
 /// * `data`: The set of data.
 ///
 /// ## Returns:
-/// * The median value of the data.
+/// * The median value of the data, which does not need to be sorted.
 ///
 #[doc = include_str!("../docs/statistics/median.md")]
 pub fn median(data: &[f32]) -> f32 {
-    percentile_of_sorted(data, 50_f32)
+    if data.windows(2).all(|pair| pair[0] <= pair[1]) {
+        return percentile_of_sorted(data, 50_f32);
+    }
+    let mut sorted = data.to_vec();
+    local_sort(&mut sorted);
+    percentile_of_sorted(&sorted, 50_f32)
 }
 
 /// # Covariance
@@ -142,6 +149,193 @@ pub fn standard_deviation(data: &[f32]) -> f32 {
     (sum_squared_deviations / data.len() as f32).sqrt()
 }
 
+/// # Standard error
+/// The standard error of the mean: how far the mean of the data is expected to be
+/// from the mean of the distribution they are drawn from.
+///
+/// ## Parameters:
+/// * `data`: The set of data.
+///
+/// ## Returns:
+/// * The standard error, `None` with less than two values.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::statistics::standard_error;
+/// assert_eq!(standard_error(&[2.0, 4.0, 6.0, 8.0]), Some(1.2909944));
+/// assert_eq!(standard_error(&[2.0]), None);
+/// ```
+#[doc = include_str!("../docs/statistics/confidence_interval.md")]
+pub fn standard_error(data: &[f32]) -> Option<f32> {
+    if data.len() < 2 {
+        return None;
+    }
+    let n = data.len() as f32;
+    let mean = mean(data);
+    let sum_squared_deviations = data.iter().map(|&x| (x - mean).powi(2)).sum::<f32>();
+    Some((sum_squared_deviations / (n * (n - 1.0))).sqrt())
+}
+
+/// # Normal quantile
+/// The value below which the standard normal distribution falls with probability
+/// `p`, with a relative error below 1.2e-9 (Acklam's approximation).
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::statistics::normal_quantile;
+/// assert!((normal_quantile(0.975) - 1.959964).abs() < 1e-6);
+/// assert_eq!(normal_quantile(0.5), 0.0);
+/// assert!(normal_quantile(1.0).is_infinite());
+/// ```
+pub fn normal_quantile(p: f64) -> f64 {
+    const A: [f64; 6] = [
+        -3.969683028665376e1,
+        2.209460984245205e2,
+        -2.759285104469687e2,
+        1.38357751867269e2,
+        -3.066479806614716e1,
+        2.506628277459239,
+    ];
+    const B: [f64; 5] = [
+        -5.447609879822406e1,
+        1.615858368580409e2,
+        -1.556989798598866e2,
+        6.680131188771972e1,
+        -1.328068155288572e1,
+    ];
+    const C: [f64; 6] = [
+        -7.784894002430293e-3,
+        -3.223964580411365e-1,
+        -2.400758277161838,
+        -2.549732539343734,
+        4.374664141464968,
+        2.938163982698783,
+    ];
+    const D: [f64; 4] = [
+        7.784695709041462e-3,
+        3.224671290700398e-1,
+        2.445134137142996,
+        3.754408661907416,
+    ];
+    const P_LOW: f64 = 0.02425;
+    let polynomial = |coefficients: &[f64], x: f64| {
+        coefficients.iter().fold(0.0, |acc, c| acc * x + c)
+    };
+    let tail = |p: f64| {
+        let q = (-2.0 * p.ln()).sqrt();
+        polynomial(&C, q) / (polynomial(&D, q) * q + 1.0)
+    };
+    match p {
+        p if p.is_nan() || !(0.0..=1.0).contains(&p) => f64::NAN,
+        0.0 => f64::NEG_INFINITY,
+        1.0 => f64::INFINITY,
+        p if p < P_LOW => tail(p),
+        p if p > 1.0 - P_LOW => -tail(1.0 - p),
+        p => {
+            let q = p - 0.5;
+            let r = q * q;
+            polynomial(&A, r) * q / (polynomial(&B, r) * r + 1.0)
+        },
+    }
+}
+
+/// # Student's t quantile
+/// The value below which Student's t distribution with `degrees_of_freedom`
+/// falls with probability `p`. Exact for 1 and 2 degrees of freedom, otherwise a
+/// Cornish-Fisher expansion around the normal quantile, within 0.2% from 3
+/// degrees of freedom.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::statistics::student_t_quantile;
+/// assert!((student_t_quantile(0.975, 2) - 4.302653).abs() < 1e-6);
+/// assert!((student_t_quantile(0.975, 30) - 2.042272).abs() < 1e-4);
+/// ```
+pub fn student_t_quantile(p: f64, degrees_of_freedom: u64) -> f64 {
+    let nu = degrees_of_freedom as f64;
+    match degrees_of_freedom {
+        0 => f64::NAN,
+        1 => (std::f64::consts::PI * (p - 0.5)).tan(),
+        2 => (2.0 * p - 1.0) / (2.0 * p * (1.0 - p)).sqrt(),
+        _ => {
+            let z = normal_quantile(p);
+            let z2 = z * z;
+            let g1 = (z2 + 1.0) * z / 4.0;
+            let g2 = ((5.0 * z2 + 16.0) * z2 + 3.0) * z / 96.0;
+            let g3 = (((3.0 * z2 + 19.0) * z2 + 17.0) * z2 - 15.0) * z / 384.0;
+            let g4 = ((((79.0 * z2 + 776.0) * z2 + 1482.0) * z2 - 1920.0) * z2 - 945.0)
+                * z
+                / 92160.0;
+            z + g1 / nu + g2 / nu.powi(2) + g3 / nu.powi(3) + g4 / nu.powi(4)
+        },
+    }
+}
+
+/// A range containing the mean of a distribution with a given probability.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConfidenceInterval {
+    pub mean: f32,
+    pub lower: f32,
+    pub upper: f32,
+    /// The probability of the interval to contain the mean, e.g. 0.95.
+    pub level: f32,
+}
+
+impl ConfidenceInterval {
+    /// Half the width of the interval.
+    pub fn margin(&self) -> f32 {
+        (self.upper - self.lower) / 2.0
+    }
+
+    /// The margin relative to the mean, infinite if the mean is 0.
+    pub fn relative_margin(&self) -> f32 {
+        match self.mean != 0.0 {
+            true => self.margin() / self.mean.abs(),
+            false => f32::INFINITY,
+        }
+    }
+
+    pub fn overlaps(&self, other: &ConfidenceInterval) -> bool {
+        self.lower <= other.upper && other.lower <= self.upper
+    }
+}
+
+/// # Confidence interval
+/// The interval around the mean of the data containing the mean of the
+/// distribution they are drawn from with probability `level`, from Student's t
+/// distribution.
+///
+/// ## Parameters:
+/// * `data`: The set of data.
+/// * `level`: The confidence level, between 0 and 1 exclusive.
+///
+/// ## Returns:
+/// * The interval, `None` with less than two values or an invalid level.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::statistics::confidence_interval;
+/// let interval = confidence_interval(&[2.0, 4.0, 6.0, 8.0], 0.95).unwrap();
+/// assert_eq!(interval.mean, 5.0);
+/// assert!((interval.margin() - 4.108).abs() < 0.01);
+/// ```
+pub fn confidence_interval(data: &[f32], level: f32) -> Option<ConfidenceInterval> {
+    if !(level > 0.0 && level < 1.0) {
+        return None;
+    }
+    let standard_error = standard_error(data)?;
+    let t = student_t_quantile((1.0 + level as f64) / 2.0, data.len() as u64 - 1);
+    let mean = mean(data);
+    let margin = t as f32 * standard_error;
+    Some(ConfidenceInterval {
+        mean,
+        lower: mean - margin,
+        upper: mean + margin,
+        level,
+    })
+}
+
 /// TODO
 /// #[doc = include_str!("../docs/statistics/median_abs_dev.md")]
 pub fn standard_deviation_pct(data: &[f32]) -> f32 {
@@ -164,10 +358,363 @@ pub fn median_abs_dev_pct(data: &[f32]) -> f32 {
     (median_abs_dev(data) / median(data)) * 100_f32
 }
 
+/// # Find knee
+/// Finds the knee (or elbow) of a curve with the Kneedle algorithm: the point after
+/// which increasing the parameter stops paying off. Useful to pick the number of
+/// clusters, components or latent factors from the results of a grid search.
+///
+/// ## Parameters:
+/// * `points`: The `(parameter, metric)` points of the curve, in any order. The
+///   curve should be monotonic, increasing or decreasing.
+///
+/// ## Returns:
+/// * The knee point, `None` when there are fewer than 3 points or the curve is a
+///   straight line.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::statistics::find_knee;
+/// let inertia = [(1.0, 100.0), (2.0, 40.0), (3.0, 15.0), (4.0, 12.0), (5.0, 10.0), (6.0, 9.0)];
+/// assert_eq!(find_knee(&inertia), Some((3.0, 15.0)));
+/// ```
+///
+#[doc = include_str!("../docs/statistics/find_knee.md")]
+pub fn find_knee(points: &[(f32, f32)]) -> Option<(f32, f32)> {
+    if points.len() < 3 {
+        return None;
+    }
+    let mut points = points.to_vec();
+    points.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+    let (first, last) = (points[0], points[points.len() - 1]);
+    let bounds = |values: &mut dyn Iterator<Item = f32>| {
+        values.fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), v| {
+            (min.min(v), max.max(v))
+        })
+    };
+    let (x_min, x_max) = bounds(&mut points.iter().map(|(x, _)| *x));
+    let (y_min, y_max) = bounds(&mut points.iter().map(|(_, y)| *y));
+    if x_max == x_min || y_max == y_min {
+        return None;
+    }
+    let normalize = |value: f32, min: f32, max: f32| (value - min) / (max - min);
+    let increasing = last.1 >= first.1;
+    let (knee, difference) = points
+        .iter()
+        .map(|&(x, y)| {
+            let x_norm = normalize(x, x_min, x_max);
+            let chord = if increasing { x_norm } else { 1.0 - x_norm };
+            ((x, y), (normalize(y, y_min, y_max) - chord).abs())
+        })
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))?;
+    (difference > f32::EPSILON).then_some(knee)
+}
+
+/// # Histogram
+/// Counts of observations in fixed buckets, for distributions too long to keep,
+/// such as the latencies of every query served. Quantiles are estimated by linear
+/// interpolation inside the bucket they fall in.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::statistics::Histogram;
+/// let mut histogram = Histogram::new(vec![1.0, 2.0, 4.0]);
+/// [0.5, 1.5, 1.5, 3.0].iter().for_each(|v| histogram.observe(*v));
+/// assert_eq!(histogram.count(), 4);
+/// assert_eq!(histogram.cumulative_counts(), vec![1, 3, 4, 4]);
+/// assert_eq!(histogram.quantile(0.5), Some(1.5));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    bounds: Vec<f32>,
+    /// One count per bound, plus the observations above the last bound.
+    counts: Vec<u64>,
+    sum: f64,
+}
+
+impl Histogram {
+    /// Creates an empty histogram with the upper bounds of the buckets, sorted and
+    /// deduplicated.
+    pub fn new(mut bounds: Vec<f32>) -> Self {
+        bounds.retain(|bound| bound.is_finite());
+        bounds.sort_by(f32::total_cmp);
+        bounds.dedup();
+        Histogram {
+            counts: vec![0; bounds.len() + 1],
+            bounds,
+            sum: 0.0,
+        }
+    }
+
+    pub fn observe(&mut self, value: f32) {
+        if value.is_nan() {
+            return;
+        }
+        let bucket = self.bounds.partition_point(|bound| *bound < value);
+        self.counts[bucket] += 1;
+        self.sum += value as f64;
+    }
+
+    /// The upper bounds of the buckets, without the last unbounded one.
+    pub fn bounds(&self) -> &[f32] {
+        &self.bounds
+    }
+
+    /// The number of observations lower or equal to each bound, the last one
+    /// being the total.
+    pub fn cumulative_counts(&self) -> Vec<u64> {
+        self.counts
+            .iter()
+            .scan(0, |total, count| {
+                *total += count;
+                Some(*total)
+            })
+            .collect()
+    }
+
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    pub fn sum(&self) -> f64 {
+        self.sum
+    }
+
+    /// The number of observations in each bucket, the last one being unbounded.
+    pub fn counts(&self) -> &[u64] {
+        &self.counts
+    }
+
+    /// Adds the observations of a histogram with the same bounds.
+    pub fn merge(&mut self, other: &Histogram) -> Result<()> {
+        if self.bounds != other.bounds {
+            return Err(Error::InvalidData(
+                "histograms with different bounds cannot be merged".to_string(),
+            ));
+        }
+        self.counts
+            .iter_mut()
+            .zip(&other.counts)
+            .for_each(|(count, other)| *count += other);
+        self.sum += other.sum;
+        Ok(())
+    }
+
+    /// Forgets every observation, keeping the bounds.
+    pub fn clear(&mut self) {
+        self.counts.iter_mut().for_each(|count| *count = 0);
+        self.sum = 0.0;
+    }
+
+    /// The fraction of the observations in each bucket, `None` when empty.
+    fn fractions(&self) -> Option<Vec<f64>> {
+        let count = self.count();
+        (count > 0).then(|| {
+            self.counts
+                .iter()
+                .map(|c| *c as f64 / count as f64)
+                .collect()
+        })
+    }
+
+    /// # Population stability index
+    /// How far the distribution of the observations moved from a reference one.
+    ///
+    /// ## Returns:
+    /// * The index, 0 for identical distributions, or `None` if a histogram is
+    ///   empty or their bounds differ.
+    ///
+    /// ## Examples:
+    /// ```
+    /// use rec_rsys::statistics::Histogram;
+    /// let mut reference = Histogram::new(vec![0.25, 0.5, 0.75]);
+    /// let mut current = reference.clone();
+    /// (0..100).for_each(|i| reference.observe(i as f32 / 100.0));
+    /// (0..100).for_each(|i| current.observe(i as f32 / 200.0));
+    /// assert_eq!(reference.population_stability_index(&reference), Some(0.0));
+    /// assert!(current.population_stability_index(&reference).unwrap() > 0.25);
+    /// ```
+    #[doc = include_str!("../docs/statistics/drift.md")]
+    pub fn population_stability_index(&self, reference: &Histogram) -> Option<f64> {
+        if self.bounds != reference.bounds {
+            return None;
+        }
+        // Empty buckets would make the log infinite.
+        const FLOOR: f64 = 1e-4;
+        let psi = self
+            .fractions()?
+            .iter()
+            .zip(reference.fractions()?)
+            .map(|(p, q)| {
+                let (p, q) = (p.max(FLOOR), q.max(FLOOR));
+                (p - q) * (p / q).ln()
+            })
+            .sum();
+        Some(psi)
+    }
+
+    /// The Kolmogorov-Smirnov distance between the distributions of two
+    /// histograms, measured at the bounds, see
+    /// [`Histogram::population_stability_index`]. `None` if a histogram is empty
+    /// or their bounds differ.
+    pub fn ks_distance(&self, other: &Histogram) -> Option<f64> {
+        if self.bounds != other.bounds {
+            return None;
+        }
+        let (mut p, mut q) = (0.0, 0.0);
+        let distance = self
+            .fractions()?
+            .iter()
+            .zip(other.fractions()?)
+            .map(|(a, b)| {
+                p += a;
+                q += b;
+                f64::abs(p - q)
+            })
+            .fold(0.0, f64::max);
+        Some(distance)
+    }
+
+    /// Estimates the `q` quantile, `None` when nothing was observed. Quantiles in
+    /// the unbounded bucket are clamped to the last bound.
+    pub fn quantile(&self, q: f32) -> Option<f32> {
+        let count = self.count();
+        if count == 0 {
+            return None;
+        }
+        let rank = q.clamp(0.0, 1.0) as f64 * count as f64;
+        let mut below = 0;
+        for (bucket, &in_bucket) in self.counts.iter().enumerate() {
+            if in_bucket > 0 && (below + in_bucket) as f64 >= rank {
+                let Some(&upper) = self.bounds.get(bucket) else {
+                    return self.bounds.last().copied();
+                };
+                let lower = match bucket {
+                    0 => upper.min(0.0),
+                    _ => self.bounds[bucket - 1],
+                };
+                let fraction = ((rank - below as f64) / in_bucket as f64) as f32;
+                return Some(lower + (upper - lower) * fraction);
+            }
+            below += in_bucket;
+        }
+        self.bounds.last().copied()
+    }
+}
+
+/// # Running statistics
+/// Count, mean and variance of a stream of values, updated one value at a time
+/// with Welford's algorithm. The accumulators are 64 bits, so averages over
+/// billions of values neither overflow nor lose the small values to rounding, and
+/// two partial results, e.g. of two threads, can be merged.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::statistics::Running;
+/// let mut left = Running::default();
+/// [1.0, 2.0].iter().for_each(|v| left.push(*v));
+/// let mut right = Running::default();
+/// [3.0, 4.0, 5.0].iter().for_each(|v| right.push(*v));
+/// left.merge(&right);
+/// assert_eq!(left.count(), 5);
+/// assert_eq!(left.mean(), Some(3.0));
+/// assert_eq!(left.variance(), Some(2.0));
+/// ```
+#[doc = include_str!("../docs/statistics/running.md")]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Running {
+    count: u64,
+    mean: f64,
+    /// Sum of the squared differences to the mean.
+    m2: f64,
+}
+
+impl Running {
+    pub fn push(&mut self, value: f64) {
+        self.count = self.count.saturating_add(1);
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value - self.mean);
+    }
+
+    /// Adds the values summarised by `other`.
+    pub fn merge(&mut self, other: &Running) {
+        if other.count == 0 {
+            return;
+        }
+        let count = self.count.saturating_add(other.count);
+        let delta = other.mean - self.mean;
+        let weight = other.count as f64 / count as f64;
+        self.m2 += other.m2 + delta * delta * self.count as f64 * weight;
+        self.mean += delta * weight;
+        self.count = count;
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// The mean, `None` when nothing was pushed.
+    pub fn mean(&self) -> Option<f64> {
+        (self.count > 0).then_some(self.mean)
+    }
+
+    /// The population variance, `None` when nothing was pushed.
+    pub fn variance(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.m2 / self.count as f64)
+    }
+
+    pub fn sum(&self) -> f64 {
+        self.mean * self.count as f64
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_running() {
+        let values = [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0];
+        let mut running = Running::default();
+        assert_eq!(running.mean(), None);
+        values.iter().for_each(|v| running.push(*v));
+        assert_eq!(running.mean(), Some(5.0));
+        assert_eq!(running.variance(), Some(4.0));
+        assert_eq!(running.sum(), 40.0);
+        let (mut left, mut right) = (Running::default(), Running::default());
+        values[..3].iter().for_each(|v| left.push(*v));
+        values[3..].iter().for_each(|v| right.push(*v));
+        left.merge(&right);
+        left.merge(&Running::default());
+        assert_eq!(left.count(), 8);
+        assert!((left.variance().unwrap() - 4.0).abs() < 1e-12);
+        // Small values are not lost next to a large count.
+        let mut ones = Running::default();
+        (0..10_000_000).for_each(|_| ones.push(1.0));
+        ones.push(0.0);
+        assert!((ones.mean().unwrap() - (1.0 - 1e-7)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_histogram_drift() {
+        let bounds = vec![1.0, 2.0, 3.0];
+        let mut a = Histogram::new(bounds.clone());
+        let mut b = Histogram::new(bounds);
+        [0.5, 1.5, 2.5, 3.5].iter().for_each(|v| a.observe(*v));
+        [0.5, 0.5, 0.5, 0.5].iter().for_each(|v| b.observe(*v));
+        assert_eq!(a.ks_distance(&b), Some(0.75));
+        assert_eq!(a.ks_distance(&a), Some(0.0));
+        assert!(a.population_stability_index(&b).unwrap() > 1.0);
+        let other = Histogram::new(vec![1.0]);
+        assert_eq!(a.ks_distance(&other), None);
+        assert!(a.clone().merge(&other).is_err());
+        a.merge(&b).unwrap();
+        assert_eq!(a.counts(), [5, 1, 1, 1]);
+        assert_eq!(a.sum(), 10.0);
+        a.clear();
+        assert_eq!(a.population_stability_index(&b), None);
+    }
+
     #[test]
     fn test_variance() {
         assert_eq!(variance(&[1.0, 2.0, 3.0, 4.0, 5.0]), 2.0);
@@ -186,15 +733,74 @@ mod tests {
         assert_eq!(standard_deviation(&[3.0, 45.0, 7.0, 2.0]), 17.851_82,);
     }
 
+    #[test]
+    fn test_confidence_interval() {
+        // Quantiles of the t distribution at 97.5%.
+        for (degrees_of_freedom, expected) in [
+            (1, 12.706_2),
+            (3, 3.182_4),
+            (5, 2.570_6),
+            (10, 2.228_1),
+            (100, 1.984_0),
+        ] {
+            let t = student_t_quantile(0.975, degrees_of_freedom);
+            assert!((t - expected).abs() / expected < 2e-3, "{}", t);
+        }
+        assert!((normal_quantile(0.01) + 2.326_348).abs() < 1e-6);
+        assert!((normal_quantile(0.999) - 3.090_232).abs() < 1e-6);
+        assert!(normal_quantile(1.5).is_nan());
+
+        let data: Vec<f32> = (0..100).map(|i| (i % 10) as f32).collect();
+        let interval = confidence_interval(&data, 0.95).unwrap();
+        assert!(interval.lower < 4.5 && interval.upper > 4.5);
+        let wider = confidence_interval(&data, 0.99).unwrap();
+        assert!(wider.margin() > interval.margin());
+        assert!(wider.overlaps(&interval));
+        assert_eq!(confidence_interval(&data, 1.0), None);
+        assert_eq!(confidence_interval(&[1.0], 0.95), None);
+    }
+
+    #[test]
+    fn test_find_knee() {
+        let concave: Vec<(f32, f32)> =
+            (1..=10).map(|x| (x as f32, (x as f32).ln())).collect();
+        assert_eq!(find_knee(&concave).map(|(x, _)| x), Some(4.0));
+        let convex: Vec<(f32, f32)> = (0..=10)
+            .rev()
+            .map(|x| (x as f32, (x as f32).powi(2)))
+            .collect();
+        assert_eq!(find_knee(&convex).map(|(x, _)| x), Some(5.0));
+        let line: Vec<(f32, f32)> = (0..5).map(|x| (x as f32, 2.0 * x as f32)).collect();
+        assert_eq!(find_knee(&line), None);
+        assert_eq!(find_knee(&[(1.0, 1.0), (2.0, 0.5)]), None);
+    }
+
+    #[test]
+    fn test_histogram() {
+        let mut histogram = Histogram::new(vec![10.0, 1.0, 5.0, f32::INFINITY]);
+        assert_eq!(histogram.bounds(), &[1.0, 5.0, 10.0]);
+        assert_eq!(histogram.quantile(0.5), None);
+        [0.5, 1.0, 2.0, 3.0, 20.0, f32::NAN]
+            .iter()
+            .for_each(|v| histogram.observe(*v));
+        assert_eq!(histogram.cumulative_counts(), vec![2, 4, 4, 5]);
+        assert_eq!(histogram.sum(), 26.5);
+        assert_eq!(histogram.quantile(0.0), Some(0.0));
+        assert_eq!(histogram.quantile(0.5), Some(2.0));
+        assert_eq!(histogram.quantile(1.0), Some(10.0));
+    }
+
     // #[test]
     // fn test_quartiles() {
     //     assert_eq!(quartiles(&mut [3.0, 45.0, 7.0, 2.0]), (2.75, 16.5),);
     // }
 
-    // #[test]
-    // fn test_median() {
-    //     assert_eq!(median(&[3.0, 45.0, 7.0, 2.0]), 17.851820635442202,);
-    // }
+    #[test]
+    fn test_median() {
+        assert_eq!(median(&[3.0, 45.0, 7.0, 2.0]), 5.0);
+        assert_eq!(median(&[1.0, 2.0, 9.0]), 2.0);
+        assert_eq!(median_abs_dev(&[9.0, 1.0, 2.0, 2.0, 4.0]), 1.4826);
+    }
 
     // #[test]
     // fn standard_deviation_pct() {
@@ -0,0 +1,661 @@
+//! # Serving state
+//! Precomputed neighbor lists and user profiles kept in a key-value store, so the
+//! processes answering requests hold no state of their own: a batch job trains the
+//! models and writes the state, any number of servers read it with one round trip
+//! per request. [`MemoryStore`] keeps it in the process, [`FileStore`] in a
+//! directory and `RedisStore`, behind the `redis` feature, in a Redis server. Any
+//! other storage is plugged in by implementing [`KeyValueStore`].
+use std::collections::HashMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use crate::errors::{Error, Result};
+use crate::ids::{fnv1a, FNV_OFFSET};
+use crate::memory::MemoryFootprint;
+use crate::pipeline::write_atomically;
+use crate::profiles::UserProfile;
+use crate::recommender::Recommendation;
+
+/// The nearest items of an item, with their similarity.
+pub type NeighborList = Vec<(u32, f32)>;
+
+/// A key-value store read and written by batches.
+pub trait KeyValueStore {
+    /// Writes every entry, replacing the existing values.
+    fn set_many(&mut self, entries: &[(String, Vec<u8>)]) -> Result<()>;
+
+    /// Reads the values of the keys, in the same order, `None` for missing keys.
+    fn get_many(&mut self, keys: &[String]) -> Result<Vec<Option<Vec<u8>>>>;
+
+    /// Removes the keys, the missing ones are ignored.
+    fn delete_many(&mut self, keys: &[String]) -> Result<()>;
+
+    /// Reads every entry whose key starts with the prefix, sorted by key.
+    fn scan(&mut self, prefix: &str) -> Result<Vec<(String, Vec<u8>)>>;
+
+    fn get(&mut self, key: &str) -> Result<Option<Vec<u8>>> {
+        Ok(self.get_many(&[key.to_string()])?.pop().flatten())
+    }
+
+    fn set(&mut self, key: &str, value: Vec<u8>) -> Result<()> {
+        self.set_many(&[(key.to_string(), value)])
+    }
+}
+
+/// Keeps the entries in a `HashMap`, for tests and single process deployments.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStore {
+    entries: HashMap<String, Vec<u8>>,
+}
+
+impl KeyValueStore for MemoryStore {
+    fn set_many(&mut self, entries: &[(String, Vec<u8>)]) -> Result<()> {
+        self.entries.extend(entries.iter().cloned());
+        Ok(())
+    }
+
+    fn get_many(&mut self, keys: &[String]) -> Result<Vec<Option<Vec<u8>>>> {
+        Ok(keys
+            .iter()
+            .map(|key| self.entries.get(key).cloned())
+            .collect())
+    }
+
+    fn delete_many(&mut self, keys: &[String]) -> Result<()> {
+        keys.iter().for_each(|key| {
+            self.entries.remove(key);
+        });
+        Ok(())
+    }
+
+    fn scan(&mut self, prefix: &str) -> Result<Vec<(String, Vec<u8>)>> {
+        let mut entries: Vec<(String, Vec<u8>)> = self
+            .entries
+            .iter()
+            .filter(|(key, _)| key.starts_with(prefix))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        entries.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        Ok(entries)
+    }
+}
+
+/// # File store
+/// Keeps every entry in a file of a directory, named after its key, so the state
+/// survives restarts and can be shared through a network file system. A value is
+/// written next to its file then renamed over it, readers never see it partly
+/// written. Keys must not be empty.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::store::{FileStore, KeyValueStore};
+/// let dir = std::env::temp_dir().join("rec_rsys_doc_file_store");
+/// let mut store = FileStore::open(&dir).unwrap();
+/// store.set("knn:neighbors:10", b"[[11,0.9]]".to_vec()).unwrap();
+/// assert_eq!(store.get("knn:neighbors:10").unwrap().unwrap(), b"[[11,0.9]]");
+/// assert_eq!(store.scan("knn:").unwrap().len(), 1);
+/// # std::fs::remove_dir_all(dir).unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct FileStore {
+    dir: PathBuf,
+}
+
+impl FileStore {
+    /// Uses a directory, created if it does not exist.
+    pub fn open<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        fs::create_dir_all(&dir)?;
+        Ok(FileStore {
+            dir: dir.as_ref().to_path_buf(),
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn path(&self, key: &str) -> Result<PathBuf> {
+        if key.is_empty() {
+            return Err(Error::InvalidData(
+                "the keys of a file store cannot be empty".to_string(),
+            ));
+        }
+        Ok(self.dir.join(encode_key(key)))
+    }
+}
+
+/// The longest file name of an entry, so that with the `.key.tmp` suffix it stays
+/// under the 255 bytes most file systems allow.
+const MAX_NAME_LEN: usize = 240;
+
+/// A file name for any key: lower case ASCII letters, digits, `-` and `_` are kept
+/// and every other byte is written `%XX`, upper case letters included so keys
+/// differing by case do not collide on case-insensitive file systems. The names
+/// never contain a dot, the staging files end with `.tmp` and are told apart from
+/// the entries.
+///
+/// Names longer than [`MAX_NAME_LEN`] are cut and end with `~` and the hash of the
+/// key, which is then kept in a `.key` file next to the entry.
+fn encode_key(key: &str) -> String {
+    let name: String = key
+        .bytes()
+        .map(|byte| match byte {
+            b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect();
+    if name.len() <= MAX_NAME_LEN {
+        return name;
+    }
+    let hash = fnv1a(FNV_OFFSET, key.as_bytes());
+    format!("{}~{:016x}", &name[..MAX_NAME_LEN - 17], hash)
+}
+
+/// The file keeping the key of an entry whose name is hashed.
+fn key_path(path: &Path) -> Option<PathBuf> {
+    let name = path.file_name()?.to_str()?;
+    name.contains('~')
+        .then(|| path.with_file_name(format!("{}.key", name)))
+}
+
+fn decode_key(name: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(name.len());
+    let mut chars = name.bytes();
+    while let Some(byte) = chars.next() {
+        match byte {
+            b'%' => {
+                let hex = [chars.next()?, chars.next()?];
+                let hex = std::str::from_utf8(&hex).ok()?;
+                bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            },
+            b'.' | b'~' => return None,
+            byte => bytes.push(byte),
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+impl KeyValueStore for FileStore {
+    fn set_many(&mut self, entries: &[(String, Vec<u8>)]) -> Result<()> {
+        for (key, value) in entries {
+            let path = self.path(key)?;
+            if let Some(key_path) = key_path(&path) {
+                write_atomically(&key_path, key.as_bytes())?;
+            }
+            write_atomically(&path, value)?;
+        }
+        Ok(())
+    }
+
+    fn get_many(&mut self, keys: &[String]) -> Result<Vec<Option<Vec<u8>>>> {
+        keys.iter()
+            .map(|key| match fs::read(self.path(key)?) {
+                Ok(value) => Ok(Some(value)),
+                Err(error) if error.kind() == ErrorKind::NotFound => Ok(None),
+                Err(error) => Err(error.into()),
+            })
+            .collect()
+    }
+
+    fn delete_many(&mut self, keys: &[String]) -> Result<()> {
+        for key in keys {
+            let path = self.path(key)?;
+            for path in [Some(path.clone()), key_path(&path)].into_iter().flatten() {
+                match fs::remove_file(path) {
+                    Err(error) if error.kind() != ErrorKind::NotFound => {
+                        return Err(error.into())
+                    },
+                    _ => {},
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn scan(&mut self, prefix: &str) -> Result<Vec<(String, Vec<u8>)>> {
+        let mut keys = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            let key = match (decode_key(name), key_path(&path)) {
+                (Some(key), _) => key,
+                (None, Some(key_path)) if !name.contains('.') => {
+                    match fs::read_to_string(key_path) {
+                        Ok(key) => key,
+                        Err(error) if error.kind() == ErrorKind::NotFound => continue,
+                        Err(error) => return Err(error.into()),
+                    }
+                },
+                _ => continue,
+            };
+            if key.starts_with(prefix) {
+                keys.push(key);
+            }
+        }
+        keys.sort_unstable();
+        let values = self.get_many(&keys)?;
+        // A key deleted since the directory was listed is skipped.
+        Ok(keys
+            .into_iter()
+            .zip(values)
+            .filter_map(|(key, value)| Some((key, value?)))
+            .collect())
+    }
+}
+
+impl MemoryFootprint for FileStore {
+    fn heap_size(&self) -> usize {
+        self.dir.as_os_str().len()
+    }
+}
+
+impl MemoryFootprint for MemoryStore {
+    fn heap_size(&self) -> usize {
+        self.entries.heap_size()
+    }
+}
+
+/// Keeps the entries in a Redis server, reading them with `MGET` and writing them
+/// with `MSET`.
+#[cfg(feature = "redis")]
+pub struct RedisStore {
+    connection: redis::Connection,
+}
+
+#[cfg(feature = "redis")]
+impl RedisStore {
+    /// Connects to a server, e.g. `redis://127.0.0.1/`.
+    pub fn connect(url: &str) -> Result<Self> {
+        let connection = redis::Client::open(url)
+            .and_then(|client| client.get_connection())
+            .map_err(redis_error)?;
+        Ok(RedisStore { connection })
+    }
+}
+
+#[cfg(feature = "redis")]
+fn redis_error(error: redis::RedisError) -> crate::errors::Error {
+    crate::errors::Error::Backend {
+        context: "redis".to_string(),
+        source: Box::new(error),
+    }
+}
+
+#[cfg(feature = "redis")]
+impl KeyValueStore for RedisStore {
+    fn set_many(&mut self, entries: &[(String, Vec<u8>)]) -> Result<()> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+        redis::cmd("MSET")
+            .arg(entries)
+            .exec(&mut self.connection)
+            .map_err(redis_error)
+    }
+
+    fn get_many(&mut self, keys: &[String]) -> Result<Vec<Option<Vec<u8>>>> {
+        if keys.is_empty() {
+            return Ok(Vec::new());
+        }
+        redis::cmd("MGET")
+            .arg(keys)
+            .query(&mut self.connection)
+            .map_err(redis_error)
+    }
+
+    fn delete_many(&mut self, keys: &[String]) -> Result<()> {
+        if keys.is_empty() {
+            return Ok(());
+        }
+        redis::cmd("DEL")
+            .arg(keys)
+            .exec(&mut self.connection)
+            .map_err(redis_error)
+    }
+
+    /// Lists the keys with `SCAN`, the keys written meanwhile may be missed.
+    fn scan(&mut self, prefix: &str) -> Result<Vec<(String, Vec<u8>)>> {
+        // The prefix is matched literally, not as a glob pattern.
+        let mut pattern: String = prefix
+            .chars()
+            .flat_map(|c| match c {
+                '*' | '?' | '[' | ']' | '\\' => vec!['\\', c],
+                c => vec![c],
+            })
+            .collect();
+        pattern.push('*');
+        let mut keys: Vec<String> = Vec::new();
+        let mut cursor = 0_u64;
+        loop {
+            let (next, batch): (u64, Vec<String>) = redis::cmd("SCAN")
+                .arg(cursor)
+                .arg("MATCH")
+                .arg(&pattern)
+                .arg("COUNT")
+                .arg(1000)
+                .query(&mut self.connection)
+                .map_err(redis_error)?;
+            keys.extend(batch);
+            cursor = next;
+            if cursor == 0 {
+                break;
+            }
+        }
+        keys.sort_unstable();
+        keys.dedup();
+        let values = self.get_many(&keys)?;
+        Ok(keys
+            .into_iter()
+            .zip(values)
+            .filter_map(|(key, value)| Some((key, value?)))
+            .collect())
+    }
+}
+
+/// # Serving state
+/// Typed access to the neighbor lists of the items, the profiles of the users and
+/// their cached recommendations stored in a [`KeyValueStore`], under keys starting
+/// with a prefix so several models can share a store.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::profiles::{Aggregation, UserProfile};
+/// use rec_rsys::store::{MemoryStore, ServingState};
+/// let mut state = ServingState::new(MemoryStore::default(), "item_knn:v3");
+/// state.put_neighbors([(10, &[(11, 0.9), (12, 0.5)][..])]).unwrap();
+/// state.put_profiles(&[UserProfile::new(1, Aggregation::Mean)]).unwrap();
+/// let neighbors = state.get_neighbors(&[10, 99]).unwrap();
+/// assert_eq!(neighbors, vec![Some(vec![(11, 0.9), (12, 0.5)]), None]);
+/// assert_eq!(state.get_profiles(&[1]).unwrap()[0].as_ref().unwrap().user_id, 1);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ServingState<S> {
+    store: S,
+    prefix: String,
+}
+
+impl<S: KeyValueStore> ServingState<S> {
+    pub fn new(store: S, prefix: &str) -> Self {
+        ServingState {
+            store,
+            prefix: prefix.to_string(),
+        }
+    }
+
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    fn key(&self, kind: &str, id: u32) -> String {
+        format!("{}:{}:{}", self.prefix, kind, id)
+    }
+
+    fn keys(&self, kind: &str, ids: &[u32]) -> Vec<String> {
+        ids.iter().map(|id| self.key(kind, *id)).collect()
+    }
+
+    /// Every entry of a kind, with the id parsed back from its key.
+    fn scan_kind<T: serde::de::DeserializeOwned>(
+        &mut self,
+        kind: &str,
+    ) -> Result<Vec<(u32, T)>> {
+        let prefix = format!("{}:{}:", self.prefix, kind);
+        self.store
+            .scan(&prefix)?
+            .into_iter()
+            .filter_map(|(key, value)| {
+                let id = key[prefix.len()..].parse().ok()?;
+                Some(serde_json::from_slice(&value).map(|value| (id, value)))
+            })
+            .map(|entry| entry.map_err(Into::into))
+            .collect()
+    }
+
+    /// Writes the neighbor lists of the items, e.g. the ones of
+    /// [`ItemKNN::neighbor_lists`](crate::algorithms::item_knn::ItemKNN::neighbor_lists).
+    pub fn put_neighbors<'a, I>(&mut self, neighbors: I) -> Result<()>
+    where
+        I: IntoIterator<Item = (u32, &'a [(u32, f32)])>,
+    {
+        let entries = neighbors
+            .into_iter()
+            .map(|(item_id, list)| {
+                Ok((self.key("neighbors", item_id), serde_json::to_vec(list)?))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        self.store.set_many(&entries)
+    }
+
+    /// Reads the neighbor lists of the items with a single request.
+    pub fn get_neighbors(
+        &mut self,
+        item_ids: &[u32],
+    ) -> Result<Vec<Option<NeighborList>>> {
+        let keys = self.keys("neighbors", item_ids);
+        decode_all(self.store.get_many(&keys)?)
+    }
+
+    /// Reads every neighbor list of the store, sorted by item, e.g. to load them
+    /// all in memory when a server starts.
+    pub fn all_neighbors(&mut self) -> Result<Vec<(u32, NeighborList)>> {
+        let mut neighbors = self.scan_kind("neighbors")?;
+        neighbors.sort_unstable_by_key(|(item_id, _)| *item_id);
+        Ok(neighbors)
+    }
+
+    pub fn remove_neighbors(&mut self, item_ids: &[u32]) -> Result<()> {
+        let keys = self.keys("neighbors", item_ids);
+        self.store.delete_many(&keys)
+    }
+
+    pub fn put_profiles(&mut self, profiles: &[UserProfile]) -> Result<()> {
+        let entries = profiles
+            .iter()
+            .map(|profile| {
+                Ok((
+                    self.key("profile", profile.user_id),
+                    serde_json::to_vec(profile)?,
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        self.store.set_many(&entries)
+    }
+
+    /// Reads the profiles of the users with a single request.
+    pub fn get_profiles(&mut self, user_ids: &[u32]) -> Result<Vec<Option<UserProfile>>> {
+        let keys = self.keys("profile", user_ids);
+        decode_all(self.store.get_many(&keys)?)
+    }
+
+    pub fn remove_profiles(&mut self, user_ids: &[u32]) -> Result<()> {
+        let keys = self.keys("profile", user_ids);
+        self.store.delete_many(&keys)
+    }
+
+    /// Caches the recommendations computed for a user, until they are
+    /// invalidated or replaced.
+    pub fn put_recommendations(
+        &mut self,
+        user_id: u32,
+        recommendations: &[Recommendation],
+    ) -> Result<()> {
+        let value = serde_json::to_vec(recommendations)?;
+        self.store.set(&self.key("recommendations", user_id), value)
+    }
+
+    /// Reads the cached recommendations of the users with a single request.
+    pub fn get_recommendations(
+        &mut self,
+        user_ids: &[u32],
+    ) -> Result<Vec<Option<Vec<Recommendation>>>> {
+        let keys = self.keys("recommendations", user_ids);
+        decode_all(self.store.get_many(&keys)?)
+    }
+
+    /// Drops the cached recommendations of the users, e.g. after they rated an
+    /// item.
+    pub fn invalidate_recommendations(&mut self, user_ids: &[u32]) -> Result<()> {
+        let keys = self.keys("recommendations", user_ids);
+        self.store.delete_many(&keys)
+    }
+}
+
+fn decode_all<T: serde::de::DeserializeOwned>(
+    values: Vec<Option<Vec<u8>>>,
+) -> Result<Vec<Option<T>>> {
+    values
+        .into_iter()
+        .map(|value| match value {
+            Some(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+            None => Ok(None),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::algorithms::item_knn::ItemKNN;
+    use crate::algorithms::knn::KNNConfig;
+    use crate::dataset::{Dataset, Rating};
+    use crate::profiles::Aggregation;
+    use crate::recommender::Recommender;
+
+    #[test]
+    fn test_neighbors_round_trip() {
+        let dataset = Dataset::new(vec![
+            Rating::new(1, 10, 5.0),
+            Rating::new(1, 11, 4.0),
+            Rating::new(2, 10, 3.0),
+            Rating::new(2, 12, 1.0),
+        ]);
+        let mut model = ItemKNN::new(KNNConfig::default().set_num_neighbors(2));
+        model.fit(&dataset).unwrap();
+        let mut state = ServingState::new(MemoryStore::default(), "knn");
+        state.put_neighbors(model.neighbor_lists()).unwrap();
+        let neighbors = state.get_neighbors(&[12, 10, 13]).unwrap();
+        assert_eq!(neighbors[0].as_deref(), Some(model.neighbors(12)));
+        assert_eq!(neighbors[1].as_deref(), Some(model.neighbors(10)));
+        assert_eq!(neighbors[2], None);
+    }
+
+    #[test]
+    fn test_file_store() {
+        let dir = std::env::temp_dir().join("rec_rsys_test_file_store");
+        let _ = fs::remove_dir_all(&dir);
+        let mut store = FileStore::open(&dir).unwrap();
+        let entries: Vec<(String, Vec<u8>)> = ["a:1", "a:2", "a/../b", "b.1", "c:é"]
+            .iter()
+            .map(|key| (key.to_string(), key.as_bytes().to_vec()))
+            .collect();
+        store.set_many(&entries).unwrap();
+        let keys: Vec<String> =
+            store.scan("").unwrap().into_iter().map(|e| e.0).collect();
+        assert_eq!(keys, ["a/../b", "a:1", "a:2", "b.1", "c:é"]);
+        assert_eq!(store.scan("a:").unwrap().len(), 2);
+        store
+            .delete_many(&["a:1".to_string(), "missing".to_string()])
+            .unwrap();
+        assert_eq!(store.get("a:1").unwrap(), None);
+        assert_eq!(store.get("c:é").unwrap().unwrap(), "c:é".as_bytes());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 4);
+
+        let mut state = ServingState::new(FileStore::open(&dir).unwrap(), "knn");
+        state
+            .put_neighbors([(2, &[(1, 0.5)][..]), (1, &[(2, 0.5)][..])])
+            .unwrap();
+        assert_eq!(
+            state.all_neighbors().unwrap(),
+            [(1, vec![(2, 0.5)]), (2, vec![(1, 0.5)])]
+        );
+        fs::remove_dir_all(dir).unwrap();
+        assert_eq!(decode_key(&encode_key("x:1/%")).unwrap(), "x:1/%");
+    }
+
+    #[test]
+    fn test_file_store_names() {
+        let dir = std::env::temp_dir().join("rec_rsys_test_file_store_names");
+        let _ = fs::remove_dir_all(&dir);
+        let mut store = FileStore::open(&dir).unwrap();
+        let (long, longer) = ("k".repeat(1000), format!("{}2", "k".repeat(999)));
+        let keys = ["User:1".to_string(), "user:1".to_string(), long, longer];
+        let entries: Vec<(String, Vec<u8>)> = keys
+            .iter()
+            .map(|key| (key.clone(), key.as_bytes().to_vec()))
+            .collect();
+        store.set_many(&entries).unwrap();
+        let names: HashSet<String> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .inspect(|name| assert!(name.len() <= 255))
+            .map(|name| name.to_lowercase())
+            .collect();
+        assert_eq!(names.len(), 6);
+        assert_eq!(store.scan("").unwrap(), {
+            let mut sorted = entries.clone();
+            sorted.sort_unstable();
+            sorted
+        });
+        assert_eq!(store.get(&keys[3]).unwrap().unwrap(), keys[3].as_bytes());
+        store.delete_many(&keys[2..]).unwrap();
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+        assert!(matches!(store.get(""), Err(Error::InvalidData(_))));
+        assert!(store.set("", Vec::new()).is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_cached_recommendations() {
+        let mut state = ServingState::new(MemoryStore::default(), "mf");
+        let recommendations = [Recommendation {
+            item_id: 3,
+            score: 0.5,
+        }];
+        state.put_recommendations(1, &recommendations).unwrap();
+        state.put_recommendations(2, &[]).unwrap();
+        let cached = state.get_recommendations(&[1, 2, 3]).unwrap();
+        assert_eq!(cached, [Some(recommendations.to_vec()), Some(vec![]), None]);
+        state.invalidate_recommendations(&[1]).unwrap();
+        assert_eq!(state.get_recommendations(&[1]).unwrap(), [None]);
+        state
+            .put_profiles(&[UserProfile::new(4, Aggregation::Mean)])
+            .unwrap();
+        state.remove_profiles(&[4]).unwrap();
+        assert_eq!(state.get_profiles(&[4]).unwrap(), [None]);
+    }
+
+    #[test]
+    fn test_profiles_and_prefixes() {
+        let mut profile = UserProfile::new(7, Aggregation::Mean);
+        profile.update(&[1.0, 2.0], 10);
+        let mut state = ServingState::new(MemoryStore::default(), "a");
+        state.put_profiles(&[profile.clone()]).unwrap();
+        assert_eq!(state.get_profiles(&[7]).unwrap(), vec![Some(profile)]);
+        let mut other = ServingState::new(state.store().clone(), "b");
+        assert_eq!(other.get_profiles(&[7]).unwrap(), vec![None]);
+        let mut corrupted = MemoryStore::default();
+        corrupted
+            .set_many(&[("a:profile:1".to_string(), b"{".to_vec())])
+            .unwrap();
+        assert!(ServingState::new(corrupted, "a")
+            .get_profiles(&[1])
+            .is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "redis")]
+    fn test_redis_errors_keep_their_source() {
+        let error = match RedisStore::connect("not a url") {
+            Err(error) => error,
+            Ok(_) => panic!("connected to an invalid url"),
+        };
+        assert!(error.to_string().starts_with("redis: "));
+        let cause = std::error::Error::source(&error).unwrap();
+        assert!(cause.downcast_ref::<redis::RedisError>().is_some());
+    }
+}
@@ -0,0 +1,318 @@
+//! # Tenants
+//! Serves several shops or sites from one process: every tenant owns its catalog,
+//! its model and its configuration, and nothing is shared between tenants, so the
+//! item and user ids of one tenant never reach the recommendations of another.
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde_json::Value;
+
+use crate::catalog::{ItemCatalog, Schema};
+use crate::config::AlgorithmSpec;
+use crate::dataset::Dataset;
+use crate::errors::{Error, Result};
+use crate::memory::MemoryFootprint;
+use crate::recommender::{Recommendation, Recommender};
+
+/// # Tenant
+/// The state of one tenant of a [`TenantRegistry`].
+pub struct Tenant {
+    id: String,
+    catalog: ItemCatalog,
+    algorithm: AlgorithmSpec,
+    model: Option<Box<dyn Recommender + Sync>>,
+}
+
+impl Tenant {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn catalog(&self) -> &ItemCatalog {
+        &self.catalog
+    }
+
+    pub fn catalog_mut(&mut self) -> &mut ItemCatalog {
+        &mut self.catalog
+    }
+
+    /// The algorithm of the tenant, the default one with the overrides of the
+    /// tenant applied.
+    pub fn algorithm(&self) -> &AlgorithmSpec {
+        &self.algorithm
+    }
+
+    /// The model trained by [`TenantRegistry::fit`], if any.
+    pub fn model(&self) -> Option<&(dyn Recommender + Sync)> {
+        self.model.as_deref()
+    }
+
+    /// The prefix of the keys of the tenant in a store shared by every tenant, see
+    /// [`ServingState::new`](crate::store::ServingState::new).
+    pub fn store_prefix(&self, prefix: &str) -> String {
+        format!("{}:tenant:{}", prefix, self.id)
+    }
+}
+
+impl fmt::Debug for Tenant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Tenant")
+            .field("id", &self.id)
+            .field("catalog", &self.catalog)
+            .field("algorithm", &self.algorithm)
+            .field("trained", &self.model.is_some())
+            .finish()
+    }
+}
+
+/// # Tenant registry
+/// The tenants served by the process, each with its own catalog and model. The
+/// algorithm of a tenant is the default of the registry, with the fields of its
+/// overrides replaced, so tenants only spell out what they change.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::algorithms::knn::KNNConfig;
+/// use rec_rsys::catalog::Schema;
+/// use rec_rsys::config::AlgorithmSpec;
+/// use rec_rsys::dataset::{Dataset, Rating};
+/// use rec_rsys::tenant::TenantRegistry;
+/// let mut registry = TenantRegistry::new(AlgorithmSpec::ItemKnn(KNNConfig::default())).unwrap();
+/// registry.register("shop", Schema::new(), None).unwrap();
+/// let overrides = serde_json::json!({"num_neighbors": 5});
+/// registry.register("blog", Schema::new(), Some(&overrides)).unwrap();
+/// assert_eq!(
+///     registry.get("blog").unwrap().algorithm(),
+///     &AlgorithmSpec::ItemKnn(KNNConfig::default().set_num_neighbors(5))
+/// );
+/// let dataset = Dataset::new(vec![
+///     Rating::new(1, 10, 5.0), Rating::new(1, 11, 4.0), Rating::new(2, 10, 5.0),
+/// ]);
+/// registry.fit("shop", &dataset).unwrap();
+/// assert_eq!(registry.recommend("shop", 2, 1).unwrap()[0].item_id, 11);
+/// assert!(registry.recommend("blog", 2, 1).is_err());
+/// ```
+#[derive(Debug)]
+pub struct TenantRegistry {
+    default_algorithm: AlgorithmSpec,
+    tenants: BTreeMap<String, Tenant>,
+}
+
+impl TenantRegistry {
+    /// A registry without tenants, whose tenants use `default_algorithm` unless
+    /// they override it.
+    pub fn new(default_algorithm: AlgorithmSpec) -> Result<Self> {
+        default_algorithm.validate()?;
+        Ok(TenantRegistry {
+            default_algorithm,
+            tenants: BTreeMap::new(),
+        })
+    }
+
+    pub fn default_algorithm(&self) -> &AlgorithmSpec {
+        &self.default_algorithm
+    }
+
+    /// # Register
+    /// Adds a tenant with an empty catalog and no model.
+    ///
+    /// ## Parameters:
+    /// * `tenant_id`: The name of the tenant.
+    /// * `schema`: The schema of the catalog of the tenant.
+    /// * `overrides`: Fields of the default algorithm replaced for this tenant, as a
+    ///   JSON object. A `name` field switches to another algorithm, whose other
+    ///   hyperparameters keep their default values.
+    ///
+    /// ## Returns:
+    /// * The tenant, or an error if it is already registered or the overridden
+    ///   algorithm is invalid.
+    pub fn register(
+        &mut self,
+        tenant_id: &str,
+        schema: Schema,
+        overrides: Option<&Value>,
+    ) -> Result<&mut Tenant> {
+        if self.tenants.contains_key(tenant_id) {
+            return Err(Error::InvalidData(format!(
+                "tenant {} is already registered",
+                tenant_id
+            )));
+        }
+        let algorithm = match overrides {
+            Some(overrides) => self.override_algorithm(overrides)?,
+            None => self.default_algorithm.clone(),
+        };
+        let tenant = Tenant {
+            id: tenant_id.to_string(),
+            catalog: ItemCatalog::new(schema),
+            algorithm,
+            model: None,
+        };
+        Ok(self.tenants.entry(tenant_id.to_string()).or_insert(tenant))
+    }
+
+    fn override_algorithm(&self, overrides: &Value) -> Result<AlgorithmSpec> {
+        let overrides = overrides.as_object().ok_or_else(|| {
+            Error::InvalidConfig("tenant overrides must be a JSON object".to_string())
+        })?;
+        let mut fields = match serde_json::to_value(&self.default_algorithm)? {
+            Value::Object(fields) => fields,
+            _ => serde_json::Map::new(),
+        };
+        if overrides
+            .get("name")
+            .is_some_and(|name| Some(name) != fields.get("name"))
+        {
+            fields.clear();
+        }
+        fields.extend(overrides.clone());
+        let algorithm: AlgorithmSpec = serde_json::from_value(Value::Object(fields))
+            .map_err(|error| {
+                Error::InvalidConfig(format!("tenant overrides: {}", error))
+            })?;
+        algorithm.validate()?;
+        Ok(algorithm)
+    }
+
+    /// Removes a tenant with its catalog and model.
+    pub fn remove(&mut self, tenant_id: &str) -> Option<Tenant> {
+        self.tenants.remove(tenant_id)
+    }
+
+    pub fn get(&self, tenant_id: &str) -> Option<&Tenant> {
+        self.tenants.get(tenant_id)
+    }
+
+    pub fn get_mut(&mut self, tenant_id: &str) -> Option<&mut Tenant> {
+        self.tenants.get_mut(tenant_id)
+    }
+
+    /// The tenant ids, sorted.
+    pub fn tenant_ids(&self) -> impl Iterator<Item = &str> + '_ {
+        self.tenants.keys().map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.tenants.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tenants.is_empty()
+    }
+
+    fn tenant(&self, tenant_id: &str) -> Result<&Tenant> {
+        self.tenants
+            .get(tenant_id)
+            .ok_or_else(|| unknown_tenant(tenant_id))
+    }
+
+    /// Trains a new model of the tenant on its ratings, replacing the previous one
+    /// only once the training succeeded.
+    pub fn fit(&mut self, tenant_id: &str, dataset: &Dataset) -> Result<()> {
+        let tenant = self
+            .tenants
+            .get_mut(tenant_id)
+            .ok_or_else(|| unknown_tenant(tenant_id))?;
+        let mut model = tenant.algorithm.build();
+        model.fit(dataset)?;
+        tenant.model = Some(model);
+        Ok(())
+    }
+
+    /// Recommends items of a tenant to one of its users, see
+    /// [`Recommender::recommend`]. Fails if the tenant is unknown or not trained.
+    pub fn recommend(
+        &self,
+        tenant_id: &str,
+        user_id: u32,
+        num_items: usize,
+    ) -> Result<Vec<Recommendation>> {
+        let model = self.tenant(tenant_id)?.model().ok_or_else(|| {
+            Error::InvalidData(format!("tenant {} has no trained model", tenant_id))
+        })?;
+        Ok(model.recommend(user_id, num_items))
+    }
+}
+
+fn unknown_tenant(tenant_id: &str) -> Error {
+    Error::InvalidData(format!("unknown tenant {}", tenant_id))
+}
+
+/// The model is not counted, recommenders do not expose their footprint through a
+/// trait object.
+impl MemoryFootprint for Tenant {
+    fn heap_size(&self) -> usize {
+        self.id.heap_size() + self.catalog.heap_size()
+    }
+}
+
+impl MemoryFootprint for TenantRegistry {
+    fn heap_size(&self) -> usize {
+        self.tenants.heap_size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::algorithms::knn::KNNConfig;
+    use crate::algorithms::mf::MFConfig;
+    use crate::catalog::{AttributeType, AttributeValue, Attributes};
+    use crate::dataset::Rating;
+
+    fn registry() -> TenantRegistry {
+        TenantRegistry::new(AlgorithmSpec::ItemKnn(KNNConfig::default())).unwrap()
+    }
+
+    #[test]
+    fn test_overrides() {
+        let mut registry = registry();
+        let mf = json!({"name": "mf", "num_factors": 4});
+        let tenant = registry.register("a", Schema::new(), Some(&mf)).unwrap();
+        assert_eq!(
+            tenant.algorithm(),
+            &AlgorithmSpec::Mf(MFConfig {
+                num_factors: 4,
+                ..MFConfig::default()
+            })
+        );
+        assert!(registry.register("a", Schema::new(), None).is_err());
+        let invalid = json!({"num_neighbors": 0});
+        assert!(registry
+            .register("b", Schema::new(), Some(&invalid))
+            .is_err());
+        assert!(registry
+            .register("c", Schema::new(), Some(&json!(3)))
+            .is_err());
+        assert_eq!(registry.tenant_ids().collect::<Vec<_>>(), ["a"]);
+    }
+
+    #[test]
+    fn test_isolation() {
+        let mut registry = registry();
+        let schema = Schema::new().attribute("price", AttributeType::Numeric, true);
+        registry.register("shop", schema, None).unwrap();
+        registry.register("site", Schema::new(), None).unwrap();
+        let price =
+            Attributes::from([("price".to_string(), AttributeValue::Numeric(1.0))]);
+        let shop = registry.get_mut("shop").unwrap();
+        shop.catalog_mut().insert(10, price).unwrap();
+        assert!(registry.get("site").unwrap().catalog().is_empty());
+
+        registry
+            .fit("shop", &Dataset::new(vec![Rating::new(1, 10, 5.0)]))
+            .unwrap();
+        assert!(registry.get("shop").unwrap().model().is_some());
+        assert!(registry.get("site").unwrap().model().is_none());
+        assert!(registry.recommend("site", 1, 3).is_err());
+        assert!(registry.recommend("other", 1, 3).is_err());
+        assert_ne!(
+            registry.get("shop").unwrap().store_prefix("item_knn"),
+            registry.get("site").unwrap().store_prefix("item_knn")
+        );
+        assert!(registry.remove("shop").is_some());
+        assert!(registry.fit("shop", &Dataset::new(Vec::new())).is_err());
+    }
+}
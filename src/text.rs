@@ -0,0 +1,913 @@
+//! # Text
+//! Turns free text, such as the descriptions of the items, into TF-IDF vectors so
+//! items can be compared by what their text says. The [`Tokenizer`] works out of
+//! the box for English; the `languages` feature adds Unicode word segmentation,
+//! Snowball stemmers and stopword lists for the other languages.
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use serde::{Deserialize, Serialize};
+
+use crate::algorithms::config::{ensure, AlgorithmConfig};
+use crate::catalog::{AttributeValue, ItemCatalog};
+use crate::errors::{Error, Result};
+use crate::memory::MemoryFootprint;
+use crate::models::Item;
+
+/// The most common English words, removed by [`Tokenizer::english`].
+pub const ENGLISH_STOPWORDS: &[&str] = &[
+    "a",
+    "about",
+    "above",
+    "after",
+    "again",
+    "against",
+    "all",
+    "am",
+    "an",
+    "and",
+    "any",
+    "are",
+    "as",
+    "at",
+    "be",
+    "because",
+    "been",
+    "before",
+    "being",
+    "below",
+    "between",
+    "both",
+    "but",
+    "by",
+    "can",
+    "could",
+    "did",
+    "do",
+    "does",
+    "doing",
+    "down",
+    "during",
+    "each",
+    "few",
+    "for",
+    "from",
+    "further",
+    "had",
+    "has",
+    "have",
+    "having",
+    "he",
+    "her",
+    "here",
+    "hers",
+    "herself",
+    "him",
+    "himself",
+    "his",
+    "how",
+    "i",
+    "if",
+    "in",
+    "into",
+    "is",
+    "it",
+    "its",
+    "itself",
+    "just",
+    "me",
+    "more",
+    "most",
+    "my",
+    "myself",
+    "no",
+    "nor",
+    "not",
+    "now",
+    "of",
+    "off",
+    "on",
+    "once",
+    "only",
+    "or",
+    "other",
+    "our",
+    "ours",
+    "ourselves",
+    "out",
+    "over",
+    "own",
+    "same",
+    "she",
+    "should",
+    "so",
+    "some",
+    "such",
+    "than",
+    "that",
+    "the",
+    "their",
+    "theirs",
+    "them",
+    "themselves",
+    "then",
+    "there",
+    "these",
+    "they",
+    "this",
+    "those",
+    "through",
+    "to",
+    "too",
+    "under",
+    "until",
+    "up",
+    "very",
+    "was",
+    "we",
+    "were",
+    "what",
+    "when",
+    "where",
+    "which",
+    "while",
+    "who",
+    "whom",
+    "why",
+    "will",
+    "with",
+    "would",
+    "you",
+    "your",
+    "yours",
+    "yourself",
+    "yourselves",
+];
+
+/// Languages with a Snowball stemmer and a stopword list.
+#[cfg(feature = "languages")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Language {
+    Danish,
+    Dutch,
+    English,
+    Finnish,
+    French,
+    German,
+    Hungarian,
+    Italian,
+    Norwegian,
+    Portuguese,
+    Romanian,
+    Russian,
+    Spanish,
+    Swedish,
+    Turkish,
+}
+
+#[cfg(feature = "languages")]
+impl Language {
+    fn algorithm(&self) -> rust_stemmers::Algorithm {
+        use rust_stemmers::Algorithm;
+        match self {
+            Language::Danish => Algorithm::Danish,
+            Language::Dutch => Algorithm::Dutch,
+            Language::English => Algorithm::English,
+            Language::Finnish => Algorithm::Finnish,
+            Language::French => Algorithm::French,
+            Language::German => Algorithm::German,
+            Language::Hungarian => Algorithm::Hungarian,
+            Language::Italian => Algorithm::Italian,
+            Language::Norwegian => Algorithm::Norwegian,
+            Language::Portuguese => Algorithm::Portuguese,
+            Language::Romanian => Algorithm::Romanian,
+            Language::Russian => Algorithm::Russian,
+            Language::Spanish => Algorithm::Spanish,
+            Language::Swedish => Algorithm::Swedish,
+            Language::Turkish => Algorithm::Turkish,
+        }
+    }
+
+    /// The ISO 639-1 code of the language.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Language::Danish => "da",
+            Language::Dutch => "nl",
+            Language::English => "en",
+            Language::Finnish => "fi",
+            Language::French => "fr",
+            Language::German => "de",
+            Language::Hungarian => "hu",
+            Language::Italian => "it",
+            Language::Norwegian => "no",
+            Language::Portuguese => "pt",
+            Language::Romanian => "ro",
+            Language::Russian => "ru",
+            Language::Spanish => "es",
+            Language::Swedish => "sv",
+            Language::Turkish => "tr",
+        }
+    }
+}
+
+/// How the tokens are reduced to their stem, so "playing" and "played" match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stemmer {
+    /// The tokens are kept as they are.
+    #[default]
+    None,
+    /// Strips the most common English suffixes ("-ing", "-ed", "-s"...). Crude but
+    /// without any resource.
+    Suffix,
+    /// The Snowball stemmer of a language.
+    #[cfg(feature = "languages")]
+    Snowball(Language),
+}
+
+impl Stemmer {
+    pub fn stem(&self, token: &str) -> String {
+        match self {
+            Stemmer::None => token.to_string(),
+            Stemmer::Suffix => strip_suffix(token),
+            #[cfg(feature = "languages")]
+            Stemmer::Snowball(language) => {
+                rust_stemmers::Stemmer::create(language.algorithm())
+                    .stem(token)
+                    .into_owned()
+            },
+        }
+    }
+}
+
+/// Removes the first English suffix that leaves a stem of at least 3 letters. The
+/// "-es" of plurals is only removed after a sibilant ("boxes", "classes").
+fn strip_suffix(token: &str) -> String {
+    const SUFFIXES: [(&str, &str); 9] = [
+        ("ies", "y"),
+        ("ing", ""),
+        ("edly", ""),
+        ("ed", ""),
+        ("ly", ""),
+        ("es", ""),
+        ("ness", ""),
+        ("ment", ""),
+        ("s", ""),
+    ];
+    for (suffix, replacement) in SUFFIXES {
+        if let Some(stem) = token.strip_suffix(suffix) {
+            let sibilant = ["ss", "x", "z", "ch", "sh"]
+                .iter()
+                .any(|e| stem.ends_with(e));
+            let kept = match suffix {
+                "es" => !sibilant,
+                "s" => stem.ends_with('s'),
+                _ => false,
+            };
+            if stem.chars().count() >= 3 && !kept {
+                return format!("{}{}", stem, replacement);
+            }
+        }
+    }
+    token.to_string()
+}
+
+/// # Tokenizer
+/// Splits a text into words, lowercases them, drops the short ones and the
+/// stopwords and reduces the rest to their stem.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::text::Tokenizer;
+/// let tokens = Tokenizer::english().tokenize("The Players were playing in Zürich!");
+/// assert_eq!(tokens, vec!["player", "play", "zürich"]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Tokenizer {
+    pub lowercase: bool,
+    /// Tokens with fewer characters are dropped.
+    pub min_length: usize,
+    /// Words dropped, compared in lowercase.
+    pub stopwords: BTreeSet<String>,
+    pub stemmer: Stemmer,
+}
+
+impl Default for Tokenizer {
+    fn default() -> Self {
+        Tokenizer {
+            lowercase: true,
+            min_length: 2,
+            stopwords: BTreeSet::new(),
+            stemmer: Stemmer::None,
+        }
+    }
+}
+
+impl Tokenizer {
+    /// Removes the [`ENGLISH_STOPWORDS`] and strips the English suffixes.
+    pub fn english() -> Self {
+        Tokenizer::default()
+            .set_stopwords(ENGLISH_STOPWORDS.iter().copied())
+            .set_stemmer(Stemmer::Suffix)
+    }
+
+    /// Removes the stopwords of a language and stems with its Snowball stemmer.
+    #[cfg(feature = "languages")]
+    pub fn for_language(language: Language) -> Self {
+        Tokenizer::default()
+            .set_stopwords(stop_words::get(language.code()).iter().copied())
+            .set_stemmer(Stemmer::Snowball(language))
+    }
+
+    pub fn set_lowercase(mut self, lowercase: bool) -> Self {
+        self.lowercase = lowercase;
+        self
+    }
+    pub fn set_min_length(mut self, min_length: usize) -> Self {
+        self.min_length = min_length;
+        self
+    }
+    pub fn set_stopwords<'a, I: IntoIterator<Item = &'a str>>(
+        mut self,
+        stopwords: I,
+    ) -> Self {
+        self.stopwords = stopwords.into_iter().map(str::to_lowercase).collect();
+        self
+    }
+    pub fn set_stemmer(mut self, stemmer: Stemmer) -> Self {
+        self.stemmer = stemmer;
+        self
+    }
+
+    /// The tokens of a text, in order and with repetitions.
+    pub fn tokenize(&self, text: &str) -> Vec<String> {
+        words(text)
+            .filter(|word| word.chars().count() >= self.min_length)
+            .map(|word| match self.lowercase {
+                true => word.to_lowercase(),
+                false => word.to_string(),
+            })
+            .filter(|word| !self.stopwords.contains(&word.to_lowercase()))
+            .map(|word| self.stemmer.stem(&word))
+            .collect()
+    }
+}
+
+/// The words of a text following the Unicode word boundaries.
+#[cfg(feature = "languages")]
+fn words(text: &str) -> impl Iterator<Item = &str> {
+    use unicode_segmentation::UnicodeSegmentation;
+    text.unicode_words()
+}
+
+/// The runs of letters and digits of a text.
+#[cfg(not(feature = "languages"))]
+fn words(text: &str) -> impl Iterator<Item = &str> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+}
+
+impl AlgorithmConfig for Tokenizer {
+    fn validate(&self) -> Result<()> {
+        ensure(self.min_length > 0, "min_length", "greater than 0")
+    }
+}
+
+impl MemoryFootprint for Tokenizer {
+    fn heap_size(&self) -> usize {
+        self.stopwords.heap_size()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TfIdfConfig {
+    pub tokenizer: Tokenizer,
+    /// Terms found in fewer documents are left out of the vocabulary.
+    pub min_df: usize,
+    /// Keeps only the terms found in the most documents.
+    pub max_features: Option<usize>,
+    /// Uses `1 + ln(tf)` instead of the raw count of a term in a document.
+    pub sublinear_tf: bool,
+}
+
+impl Default for TfIdfConfig {
+    fn default() -> Self {
+        TfIdfConfig {
+            tokenizer: Tokenizer::english(),
+            min_df: 1,
+            max_features: None,
+            sublinear_tf: false,
+        }
+    }
+}
+
+impl TfIdfConfig {
+    pub fn set_tokenizer(mut self, tokenizer: Tokenizer) -> Self {
+        self.tokenizer = tokenizer;
+        self
+    }
+    pub fn set_min_df(mut self, min_df: usize) -> Self {
+        self.min_df = min_df;
+        self
+    }
+    pub fn set_max_features(mut self, max_features: Option<usize>) -> Self {
+        self.max_features = max_features;
+        self
+    }
+    pub fn set_sublinear_tf(mut self, sublinear_tf: bool) -> Self {
+        self.sublinear_tf = sublinear_tf;
+        self
+    }
+}
+
+impl AlgorithmConfig for TfIdfConfig {
+    fn validate(&self) -> Result<()> {
+        self.tokenizer.validate()?;
+        ensure(self.min_df > 0, "min_df", "greater than 0")?;
+        ensure(
+            self.max_features.is_none_or(|max| max > 0),
+            "max_features",
+            "greater than 0",
+        )
+    }
+}
+
+/// # TF-IDF
+/// Vectorizes texts over the vocabulary of a corpus: every term weighs its count
+/// in the text times its inverse document frequency and the vectors are L2
+/// normalized, ready for the cosine similarity.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::text::{TfIdf, TfIdfConfig};
+/// let corpus = [
+///     "A thriller about a heist in Paris",
+///     "A comedy about a family in Rome",
+///     "Heist thrillers in the Paris underground",
+/// ];
+/// let tfidf = TfIdf::fit(&corpus, &TfIdfConfig::default()).unwrap();
+/// assert!(tfidf.vocabulary().contains_key("heist"));
+/// let vector = tfidf.transform("a daring heist");
+/// assert_eq!(vector.len(), tfidf.vocabulary().len());
+/// ```
+#[doc = include_str!("../docs/text/tf_idf.md")]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TfIdf {
+    config: TfIdfConfig,
+    vocabulary: BTreeMap<String, usize>,
+    idf: Vec<f32>,
+}
+
+impl TfIdf {
+    /// Learns the vocabulary and the IDF of its terms from a corpus.
+    pub fn fit<S: AsRef<str>>(corpus: &[S], config: &TfIdfConfig) -> Result<Self> {
+        config.validate()?;
+        let mut frequencies: HashMap<String, usize> = HashMap::new();
+        for document in corpus {
+            let terms: BTreeSet<String> = config
+                .tokenizer
+                .tokenize(document.as_ref())
+                .into_iter()
+                .collect();
+            for term in terms {
+                *frequencies.entry(term).or_default() += 1;
+            }
+        }
+        let mut terms: Vec<(String, usize)> = frequencies
+            .into_iter()
+            .filter(|(_, frequency)| *frequency >= config.min_df)
+            .collect();
+        if terms.is_empty() {
+            return Err(Error::InvalidData(
+                "no term left in the vocabulary of the corpus".to_string(),
+            ));
+        }
+        if let Some(max_features) = config.max_features {
+            terms.sort_by(|(a, fa), (b, fb)| fb.cmp(fa).then_with(|| a.cmp(b)));
+            terms.truncate(max_features);
+        }
+        terms.sort();
+        let documents = corpus.len() as f32;
+        let idf = terms
+            .iter()
+            .map(|(_, frequency)| {
+                ((1.0 + documents) / (1.0 + *frequency as f32)).ln() + 1.0
+            })
+            .collect();
+        let vocabulary = terms
+            .into_iter()
+            .enumerate()
+            .map(|(index, (term, _))| (term, index))
+            .collect();
+        Ok(TfIdf {
+            config: config.clone(),
+            vocabulary,
+            idf,
+        })
+    }
+
+    /// The index of every term in the vectors.
+    pub fn vocabulary(&self) -> &BTreeMap<String, usize> {
+        &self.vocabulary
+    }
+
+    pub fn idf(&self) -> &[f32] {
+        &self.idf
+    }
+
+    /// The normalized TF-IDF vector of a text, all zeros when none of its terms is
+    /// in the vocabulary.
+    pub fn transform(&self, text: &str) -> Vec<f32> {
+        let mut vector = vec![0.0_f32; self.idf.len()];
+        for token in self.config.tokenizer.tokenize(text) {
+            if let Some(index) = self.vocabulary.get(&token) {
+                vector[*index] += 1.0;
+            }
+        }
+        for (value, idf) in vector.iter_mut().zip(&self.idf) {
+            if self.config.sublinear_tf && *value > 0.0 {
+                *value = 1.0 + value.ln();
+            }
+            *value *= idf;
+        }
+        let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+        if norm > 0.0 {
+            vector.iter_mut().for_each(|v| *v /= norm);
+        }
+        vector
+    }
+
+    /// # Catalog items
+    /// Fits the vectorizer on a text attribute of a catalog and vectorizes every
+    /// item, e.g. for the [`ContentBased`](crate::algorithms::content_based::ContentBased)
+    /// recommender. Items without the attribute get the zero vector.
+    ///
+    /// ## Parameters:
+    /// * `catalog`: The items.
+    /// * `name`: The name of a text attribute.
+    /// * `config`: The configuration of the vectorizer.
+    ///
+    /// ## Returns:
+    /// * The fitted vectorizer and the items with their vector.
+    pub fn fit_catalog(
+        catalog: &ItemCatalog,
+        name: &str,
+        config: &TfIdfConfig,
+    ) -> Result<(Self, Vec<Item>)> {
+        let texts = catalog_texts(catalog, name);
+        let corpus: Vec<&str> = texts.iter().map(|(_, text)| *text).collect();
+        let tfidf = TfIdf::fit(&corpus, config)?;
+        let items = texts
+            .into_iter()
+            .map(|(item_id, text)| Item::new(item_id, tfidf.transform(text), None))
+            .collect();
+        Ok((tfidf, items))
+    }
+}
+
+/// The text attribute of every item, empty when missing.
+fn catalog_texts<'a>(catalog: &'a ItemCatalog, name: &str) -> Vec<(u32, &'a str)> {
+    catalog
+        .iter()
+        .map(|(item_id, attributes)| {
+            let text = match attributes.get(name) {
+                Some(AttributeValue::Text(text)) => text.as_str(),
+                _ => "",
+            };
+            (item_id, text)
+        })
+        .collect()
+}
+
+impl MemoryFootprint for TfIdf {
+    fn heap_size(&self) -> usize {
+        self.config.tokenizer.heap_size()
+            + self.vocabulary.heap_size()
+            + self.idf.heap_size()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Bm25Config {
+    pub tokenizer: Tokenizer,
+    /// How quickly the score of a term saturates with its count in a document.
+    pub k1: f32,
+    /// How much the length of a document lowers its scores, from 0 (not at all)
+    /// to 1 (fully normalized).
+    pub b: f32,
+}
+
+impl Default for Bm25Config {
+    fn default() -> Self {
+        Bm25Config {
+            tokenizer: Tokenizer::english(),
+            k1: 1.2,
+            b: 0.75,
+        }
+    }
+}
+
+impl Bm25Config {
+    pub fn set_tokenizer(mut self, tokenizer: Tokenizer) -> Self {
+        self.tokenizer = tokenizer;
+        self
+    }
+    pub fn set_k1(mut self, k1: f32) -> Self {
+        self.k1 = k1;
+        self
+    }
+    pub fn set_b(mut self, b: f32) -> Self {
+        self.b = b;
+        self
+    }
+}
+
+impl AlgorithmConfig for Bm25Config {
+    fn validate(&self) -> Result<()> {
+        self.tokenizer.validate()?;
+        ensure(
+            self.k1 >= 0.0 && self.k1.is_finite(),
+            "k1",
+            "a non-negative number",
+        )?;
+        ensure((0.0..=1.0).contains(&self.b), "b", "between 0 and 1")
+    }
+}
+
+/// # BM25
+/// Scores the texts of the items against a query, the text of another item or
+/// the texts of the items a user liked. Unlike the cosine of [`TfIdf`] vectors,
+/// repeating a term has diminishing returns and long texts are not favoured for
+/// containing more terms.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::text::{Bm25, Bm25Config};
+/// let documents = [
+///     (10, "A thriller about a heist in Paris"),
+///     (11, "A comedy about a family in Rome"),
+///     (12, "Heist thrillers in the Paris underground"),
+/// ];
+/// let bm25 = Bm25::fit(&documents, &Bm25Config::default()).unwrap();
+/// let similar = bm25.similar(10, 1);
+/// assert_eq!(similar[0].0, 12);
+/// assert!(bm25.scores("a family").contains_key(&11));
+/// ```
+#[doc = include_str!("../docs/text/bm25.md")]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Bm25 {
+    config: Bm25Config,
+    ids: Vec<u32>,
+    /// The index of the first document of every id.
+    rows: HashMap<u32, usize>,
+    /// The count of every term of every document.
+    documents: Vec<BTreeMap<String, u32>>,
+    lengths: Vec<usize>,
+    average_length: f32,
+    idf: BTreeMap<String, f32>,
+}
+
+impl Bm25 {
+    /// Indexes the documents, each with the id of its item.
+    pub fn fit<S: AsRef<str>>(
+        documents: &[(u32, S)],
+        config: &Bm25Config,
+    ) -> Result<Self> {
+        config.validate()?;
+        if documents.is_empty() {
+            return Err(Error::InvalidData("no document to index".to_string()));
+        }
+        let mut counts = Vec::with_capacity(documents.len());
+        let mut lengths = Vec::with_capacity(documents.len());
+        let mut frequencies: BTreeMap<String, usize> = BTreeMap::new();
+        for (_, text) in documents {
+            let tokens = config.tokenizer.tokenize(text.as_ref());
+            lengths.push(tokens.len());
+            let mut terms: BTreeMap<String, u32> = BTreeMap::new();
+            for token in tokens {
+                *terms.entry(token).or_default() += 1;
+            }
+            for term in terms.keys() {
+                *frequencies.entry(term.clone()).or_default() += 1;
+            }
+            counts.push(terms);
+        }
+        let n = documents.len() as f32;
+        let idf = frequencies
+            .into_iter()
+            .map(|(term, frequency)| {
+                let frequency = frequency as f32;
+                (term, ((n - frequency + 0.5) / (frequency + 0.5) + 1.0).ln())
+            })
+            .collect();
+        Ok(Bm25 {
+            config: config.clone(),
+            ids: documents.iter().map(|(id, _)| *id).collect(),
+            rows: documents
+                .iter()
+                .enumerate()
+                .rev()
+                .map(|(index, (id, _))| (*id, index))
+                .collect(),
+            documents: counts,
+            average_length: lengths.iter().sum::<usize>() as f32 / n,
+            lengths,
+            idf,
+        })
+    }
+
+    /// Indexes a text attribute of a catalog, see [`Bm25::fit`].
+    pub fn fit_catalog(
+        catalog: &ItemCatalog,
+        name: &str,
+        config: &Bm25Config,
+    ) -> Result<Self> {
+        Bm25::fit(&catalog_texts(catalog, name), config)
+    }
+
+    /// The ids of the documents, in the order they were indexed.
+    pub fn ids(&self) -> &[u32] {
+        &self.ids
+    }
+
+    /// The count of every term of a document, once tokenized.
+    pub fn terms(&self, item_id: u32) -> Option<&BTreeMap<String, u32>> {
+        self.rows.get(&item_id).map(|index| &self.documents[*index])
+    }
+
+    /// The score of every document sharing a term with the query, a term weighing
+    /// as many times as it is repeated.
+    pub fn scores(&self, query: &str) -> HashMap<u32, f32> {
+        let mut terms: BTreeMap<String, f32> = BTreeMap::new();
+        for token in self.config.tokenizer.tokenize(query) {
+            *terms.entry(token).or_default() += 1.0;
+        }
+        self.score_terms(&terms)
+    }
+
+    /// The scores of the documents for query terms already tokenized, with their
+    /// weight.
+    pub fn score_terms(&self, terms: &BTreeMap<String, f32>) -> HashMap<u32, f32> {
+        let (k1, b) = (self.config.k1, self.config.b);
+        let mut scores = HashMap::new();
+        for (index, document) in self.documents.iter().enumerate() {
+            let length =
+                self.lengths[index] as f32 / self.average_length.max(f32::EPSILON);
+            let score: f32 = terms
+                .iter()
+                .filter_map(|(term, weight)| {
+                    let count = *document.get(term)? as f32;
+                    let saturation =
+                        count * (k1 + 1.0) / (count + k1 * (1.0 - b + b * length));
+                    Some(weight * self.idf[term] * saturation)
+                })
+                .sum();
+            if score > 0.0 {
+                scores.insert(self.ids[index], score);
+            }
+        }
+        scores
+    }
+
+    /// The documents scoring the highest against the text of an item, without the
+    /// item itself.
+    pub fn similar(&self, item_id: u32, num_items: usize) -> Vec<(u32, f32)> {
+        let terms = match self.terms(item_id) {
+            Some(terms) => terms.iter().map(|(t, c)| (t.clone(), *c as f32)).collect(),
+            None => return Vec::new(),
+        };
+        let mut scores: Vec<(u32, f32)> = self
+            .score_terms(&terms)
+            .into_iter()
+            .filter(|(id, _)| *id != item_id)
+            .collect();
+        scores.sort_by(|(a, x), (b, y)| y.total_cmp(x).then_with(|| a.cmp(b)));
+        scores.truncate(num_items);
+        scores
+    }
+}
+
+impl MemoryFootprint for Bm25 {
+    fn heap_size(&self) -> usize {
+        self.config.tokenizer.heap_size()
+            + self.ids.heap_size()
+            + self.rows.heap_size()
+            + self.documents.heap_size()
+            + self.lengths.heap_size()
+            + self.idf.heap_size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog::{AttributeType, Attributes, Schema};
+
+    #[test]
+    fn test_tokenize() {
+        let tokenizer = Tokenizer::default();
+        assert_eq!(
+            tokenizer.tokenize("Rock-n-roll, 1970s—a BIG hit"),
+            vec!["rock", "roll", "1970s", "big", "hit"]
+        );
+        let tokenizer = Tokenizer::english().set_lowercase(false).set_min_length(3);
+        assert_eq!(
+            tokenizer.tokenize("The Cats and the classes"),
+            ["Cat", "class"]
+        );
+        assert!(Tokenizer::default().set_min_length(0).validate().is_err());
+    }
+
+    #[test]
+    fn test_suffix_stemmer() {
+        let stem = |token| Stemmer::Suffix.stem(token);
+        assert_eq!(stem("stories"), "story");
+        assert_eq!(stem("jumping"), "jump");
+        assert_eq!(stem("glass"), "glass");
+        assert_eq!(stem("boxes"), "box");
+        assert_eq!(stem("roses"), "rose");
+        assert_eq!(stem("sing"), "sing");
+        assert_eq!(Stemmer::None.stem("jumping"), "jumping");
+    }
+
+    #[cfg(feature = "languages")]
+    #[test]
+    fn test_languages() {
+        let tokenizer = Tokenizer::for_language(Language::French);
+        assert_eq!(
+            tokenizer.tokenize("Les chanteuses chantaient"),
+            ["chanteux", "chant"]
+        );
+    }
+
+    #[test]
+    fn test_tf_idf() {
+        let corpus = ["red apples", "green apples", "red cars and red roses"];
+        let tfidf = TfIdf::fit(&corpus, &TfIdfConfig::default()).unwrap();
+        let terms: Vec<&String> = tfidf.vocabulary().keys().collect();
+        assert_eq!(terms, ["apple", "car", "green", "red", "rose"]);
+        let vector = tfidf.transform("red red apples");
+        assert!((vector.iter().map(|v| v * v).sum::<f32>() - 1.0).abs() < 1e-6);
+        assert!(vector[3] > vector[0]);
+        assert_eq!(tfidf.transform("nothing known"), vec![0.0; 5]);
+
+        let config = TfIdfConfig::default().set_min_df(2).set_sublinear_tf(true);
+        let tfidf = TfIdf::fit(&corpus, &config).unwrap();
+        assert_eq!(tfidf.vocabulary().len(), 2);
+        let config = TfIdfConfig::default().set_max_features(Some(1));
+        let tfidf = TfIdf::fit(&corpus, &config).unwrap();
+        assert_eq!(tfidf.vocabulary().keys().collect::<Vec<_>>(), ["apple"]);
+        assert!(TfIdf::fit(&["the"], &TfIdfConfig::default()).is_err());
+    }
+
+    #[test]
+    fn test_bm25() {
+        let documents = [
+            (1, "heist heist heist heist"),
+            (2, "heist"),
+            (
+                3,
+                "a heist in paris with a long long long long long description",
+            ),
+            (4, "a wedding"),
+        ];
+        let bm25 = Bm25::fit(&documents, &Bm25Config::default()).unwrap();
+        let scores = bm25.scores("heist");
+        assert_eq!(scores.len(), 3);
+        // Saturated: four times the term is far from four times the score.
+        assert!(scores[&1] > scores[&2] && scores[&1] < 2.0 * scores[&2]);
+        assert!(scores[&2] > scores[&3]);
+        let flat = Bm25::fit(&documents, &Bm25Config::default().set_b(0.0)).unwrap();
+        assert_eq!(flat.scores("heist")[&2], flat.scores("heist")[&3]);
+        assert_eq!(
+            bm25.similar(2, 5).iter().map(|s| s.0).collect::<Vec<_>>(),
+            [1, 3]
+        );
+        assert!(bm25.similar(9, 5).is_empty());
+        assert_eq!(bm25.terms(1).unwrap()["heist"], 4);
+        assert_eq!(bm25.terms(9), None);
+        assert!(Bm25::fit(&documents, &Bm25Config::default().set_b(2.0)).is_err());
+        assert!(Bm25::fit::<&str>(&[], &Bm25Config::default()).is_err());
+    }
+
+    #[test]
+    fn test_fit_catalog() {
+        let schema = Schema::new().attribute("plot", AttributeType::Text, false);
+        let mut catalog = ItemCatalog::new(schema);
+        for (item_id, plot) in [(1, "a heist in paris"), (2, "a wedding in rome")] {
+            let plot = AttributeValue::Text(plot.to_string());
+            catalog
+                .insert(item_id, Attributes::from([("plot".to_string(), plot)]))
+                .unwrap();
+        }
+        catalog.insert(3, Attributes::new()).unwrap();
+        let (tfidf, items) =
+            TfIdf::fit_catalog(&catalog, "plot", &TfIdfConfig::default()).unwrap();
+        assert_eq!(items.len(), 3);
+        assert_eq!(items[0].values.len(), tfidf.vocabulary().len());
+        assert!(items[2].values.iter().all(|v| *v == 0.0));
+    }
+}
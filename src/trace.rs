@@ -0,0 +1,517 @@
+//! # Query traces
+//! Runs a recommendation query step by step, the candidates of a recommender then
+//! the filters and re-rankings applied to them, and in debug mode records what
+//! every step changed, to answer why a user was or was not served an item.
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::catalog::ItemCatalog;
+use crate::errors::Result;
+use crate::exclusions::ExclusionStore;
+use crate::memory::MemoryFootprint;
+use crate::recommender::{Recommendation, Recommender};
+
+/// What a step of a query did to an item.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum Change {
+    /// The item was dropped from the list.
+    Removed {
+        item_id: u32,
+        score: f32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reason: Option<String>,
+    },
+    /// The item was not in the list before the step.
+    Added { item_id: u32, score: f32 },
+    /// The item got another score.
+    Rescored {
+        item_id: u32,
+        before: f32,
+        after: f32,
+    },
+}
+
+impl Change {
+    pub fn item_id(&self) -> u32 {
+        match self {
+            Change::Removed { item_id, .. }
+            | Change::Added { item_id, .. }
+            | Change::Rescored { item_id, .. } => *item_id,
+        }
+    }
+}
+
+/// A step of a query and the items it changed. Items only moved in the list are
+/// not listed, the lists of the trace show their positions.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TraceStep {
+    pub name: String,
+    /// The length of the list after the step.
+    pub num_items: usize,
+    pub changes: Vec<Change>,
+}
+
+/// # Query trace
+/// Everything a query did: the candidates the recommender scored, every step and
+/// the list served.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueryTrace {
+    pub user_id: u32,
+    pub candidates: Vec<Recommendation>,
+    pub steps: Vec<TraceStep>,
+    pub recommendations: Vec<Recommendation>,
+}
+
+impl QueryTrace {
+    /// The position of an item in the served list.
+    pub fn position(&self, item_id: u32) -> Option<usize> {
+        self.recommendations
+            .iter()
+            .position(|r| r.item_id == item_id)
+    }
+
+    /// # Explain
+    /// The history of an item through the query.
+    ///
+    /// ## Parameters:
+    /// * `item_id`: The item.
+    ///
+    /// ## Returns:
+    /// * The name of every step that changed the item and the change, empty if the
+    ///   item was served as the recommender scored it or never was a candidate.
+    pub fn explain(&self, item_id: u32) -> Vec<(&str, &Change)> {
+        self.steps
+            .iter()
+            .flat_map(|step| {
+                step.changes
+                    .iter()
+                    .filter(move |change| change.item_id() == item_id)
+                    .map(move |change| (step.name.as_str(), change))
+            })
+            .collect()
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(self)?)
+    }
+}
+
+/// The changes from one list to the next.
+fn diff(before: &[Recommendation], after: &[Recommendation]) -> Vec<Change> {
+    let scores: HashMap<u32, f32> = after.iter().map(|r| (r.item_id, r.score)).collect();
+    let previous: HashMap<u32, f32> =
+        before.iter().map(|r| (r.item_id, r.score)).collect();
+    let mut changes: Vec<Change> = before
+        .iter()
+        .filter_map(|r| match scores.get(&r.item_id) {
+            None => Some(Change::Removed {
+                item_id: r.item_id,
+                score: r.score,
+                reason: None,
+            }),
+            Some(score) if score.to_bits() != r.score.to_bits() => {
+                Some(Change::Rescored {
+                    item_id: r.item_id,
+                    before: r.score,
+                    after: *score,
+                })
+            },
+            Some(_) => None,
+        })
+        .collect();
+    changes.extend(
+        after
+            .iter()
+            .filter(|r| !previous.contains_key(&r.item_id))
+            .map(|r| Change::Added {
+                item_id: r.item_id,
+                score: r.score,
+            }),
+    );
+    changes
+}
+
+/// # Query
+/// A recommendation query applying its steps to the candidates of a recommender
+/// as they are called. With debug enabled every step is recorded in a
+/// [`QueryTrace`], otherwise the steps cost nothing more than applying them.
+///
+/// ## Examples:
+/// ```
+/// use std::collections::HashMap;
+/// use rec_rsys::algorithms::most_popular::MostPopular;
+/// use rec_rsys::dataset::{Dataset, Rating};
+/// use rec_rsys::exclusions::{ExclusionReason, ExclusionStore};
+/// use rec_rsys::recommender::Recommender;
+/// use rec_rsys::rerank::enforce_exposure_quotas;
+/// use rec_rsys::trace::{Change, Query};
+/// let mut model = MostPopular::default();
+/// model.fit(&Dataset::new(vec![
+///     Rating::new(1, 10, 1.0), Rating::new(2, 10, 1.0), Rating::new(3, 10, 1.0),
+///     Rating::new(1, 11, 1.0), Rating::new(2, 11, 1.0), Rating::new(1, 12, 1.0),
+///     Rating::new(2, 13, 1.0),
+/// ])).unwrap();
+/// let mut store = ExclusionStore::new();
+/// store.exclude(4, 11, ExclusionReason::Disliked);
+/// let groups = HashMap::from([(13, "indie".to_string())]);
+/// let quotas = HashMap::from([("indie".to_string(), 1)]);
+///
+/// let (served, trace) = Query::new(&model, 4, 10)
+///     .set_debug(true)
+///     .exclude(&store)
+///     .rerank("quotas", |list| enforce_exposure_quotas(list, &groups, &quotas, 2))
+///     .finish(2);
+/// let ids: Vec<u32> = served.iter().map(|r| r.item_id).collect();
+/// assert_eq!(ids, [10, 13]);
+///
+/// let trace = trace.unwrap();
+/// let (step, change) = trace.explain(11)[0];
+/// assert_eq!(step, "exclusions");
+/// let Change::Removed { reason, .. } = change else { panic!() };
+/// assert_eq!(reason.as_deref(), Some("disliked"));
+/// assert_eq!(trace.explain(12)[0].0, "quotas");
+/// assert!(trace.to_json().unwrap().contains("\"change\":\"removed\""));
+/// ```
+pub struct Query<'a, R: Recommender + ?Sized> {
+    recommender: &'a R,
+    user_id: u32,
+    list: Vec<Recommendation>,
+    trace: Option<QueryTrace>,
+}
+
+impl<'a, R: Recommender + ?Sized> Query<'a, R> {
+    /// Starts a query from the best `num_candidates` items of the recommender.
+    /// Filters and exclusions remove items, so the candidates should be more than
+    /// the items served.
+    pub fn new(recommender: &'a R, user_id: u32, num_candidates: usize) -> Self {
+        Query {
+            recommender,
+            user_id,
+            list: recommender.recommend(user_id, num_candidates),
+            trace: None,
+        }
+    }
+
+    /// Records the candidates and the next steps or stops recording.
+    pub fn set_debug(mut self, debug: bool) -> Self {
+        self.trace = match debug {
+            true => self.trace.or_else(|| {
+                Some(QueryTrace {
+                    user_id: self.user_id,
+                    candidates: self.list.clone(),
+                    steps: Vec::new(),
+                    recommendations: Vec::new(),
+                })
+            }),
+            false => None,
+        };
+        self
+    }
+
+    pub fn is_debug(&self) -> bool {
+        self.trace.is_some()
+    }
+
+    pub fn recommender(&self) -> &R {
+        self.recommender
+    }
+
+    /// The list as it is after the steps applied so far.
+    pub fn list(&self) -> &[Recommendation] {
+        &self.list
+    }
+
+    fn record(&mut self, name: &str, changes: Vec<Change>) {
+        if let Some(trace) = &mut self.trace {
+            trace.steps.push(TraceStep {
+                name: name.to_string(),
+                num_items: self.list.len(),
+                changes,
+            });
+        }
+    }
+
+    /// Removes the items excluded for the user, recording the reason of each.
+    pub fn exclude(mut self, exclusions: &ExclusionStore) -> Self {
+        let user_id = self.user_id;
+        let (kept, excluded): (Vec<Recommendation>, Vec<Recommendation>) = self
+            .list
+            .into_iter()
+            .partition(|r| !exclusions.is_excluded(user_id, r.item_id));
+        self.list = kept;
+        if self.is_debug() {
+            let changes = excluded
+                .iter()
+                .map(|r| Change::Removed {
+                    item_id: r.item_id,
+                    score: r.score,
+                    reason: exclusions
+                        .reason(user_id, r.item_id)
+                        .map(|reason| format!("{:?}", reason).to_lowercase()),
+                })
+                .collect();
+            self.record("exclusions", changes);
+        }
+        self
+    }
+
+    /// Removes the items the catalog does not make available at `now`, recording
+    /// whether they expired or are not valid yet. As re-rankings may add items, it
+    /// is best applied last.
+    pub fn available(mut self, catalog: &ItemCatalog, now: u64) -> Self {
+        let (kept, removed): (Vec<Recommendation>, Vec<Recommendation>) = self
+            .list
+            .into_iter()
+            .partition(|r| catalog.is_available(r.item_id, now));
+        self.list = kept;
+        if self.is_debug() {
+            let changes = removed
+                .iter()
+                .map(|r| Change::Removed {
+                    item_id: r.item_id,
+                    score: r.score,
+                    reason: catalog.validity(r.item_id).map(|validity| {
+                        match validity.has_expired(now) {
+                            true => "expired".to_string(),
+                            false => "not valid yet".to_string(),
+                        }
+                    }),
+                })
+                .collect();
+            self.record("availability", changes);
+        }
+        self
+    }
+
+    /// Keeps the items for which the predicate holds, e.g. the ones in stock.
+    pub fn filter<F: FnMut(&Recommendation) -> bool>(
+        mut self,
+        name: &str,
+        mut predicate: F,
+    ) -> Self {
+        let (kept, removed): (Vec<Recommendation>, Vec<Recommendation>) =
+            self.list.into_iter().partition(|r| predicate(r));
+        self.list = kept;
+        if self.is_debug() {
+            let changes = removed
+                .iter()
+                .map(|r| Change::Removed {
+                    item_id: r.item_id,
+                    score: r.score,
+                    reason: Some(name.to_string()),
+                })
+                .collect();
+            self.record(name, changes);
+        }
+        self
+    }
+
+    /// Replaces the list with the one the function builds from it, e.g. a
+    /// calibration, quotas or exploration. The changes are found by comparing the
+    /// two lists.
+    pub fn rerank<F: FnOnce(&[Recommendation]) -> Vec<Recommendation>>(
+        mut self,
+        name: &str,
+        rerank: F,
+    ) -> Self {
+        let reranked = rerank(&self.list);
+        let before = std::mem::replace(&mut self.list, reranked);
+        if self.is_debug() {
+            let changes = diff(&before, &self.list);
+            self.record(name, changes);
+        }
+        self
+    }
+
+    /// # Finish
+    /// Keeps the first `num_items` items of the list.
+    ///
+    /// ## Returns:
+    /// * The list served and, in debug mode, the trace of the query, whose last
+    ///   step is the truncation to `num_items`.
+    pub fn finish(
+        mut self,
+        num_items: usize,
+    ) -> (Vec<Recommendation>, Option<QueryTrace>) {
+        let cut = self.list.split_off(num_items.min(self.list.len()));
+        if self.is_debug() {
+            let changes = cut
+                .iter()
+                .map(|r| Change::Removed {
+                    item_id: r.item_id,
+                    score: r.score,
+                    reason: Some("truncated".to_string()),
+                })
+                .collect();
+            self.record("truncate", changes);
+        }
+        let trace = self.trace.map(|mut trace| {
+            trace.recommendations = self.list.clone();
+            trace
+        });
+        (self.list, trace)
+    }
+}
+
+impl MemoryFootprint for Change {
+    fn heap_size(&self) -> usize {
+        match self {
+            Change::Removed { reason, .. } => reason.heap_size(),
+            _ => 0,
+        }
+    }
+}
+
+impl MemoryFootprint for TraceStep {
+    fn heap_size(&self) -> usize {
+        self.name.heap_size() + self.changes.heap_size()
+    }
+}
+
+impl MemoryFootprint for QueryTrace {
+    fn heap_size(&self) -> usize {
+        self.candidates.heap_size()
+            + self.steps.heap_size()
+            + self.recommendations.heap_size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog::{Attributes, Schema, Validity};
+    use crate::dataset::Dataset;
+
+    /// Recommends the items 1 to 10, item 1 first.
+    struct Fixed;
+
+    impl Recommender for Fixed {
+        fn fit(&mut self, _dataset: &Dataset) -> Result<()> {
+            Ok(())
+        }
+
+        fn recommend(&self, _user_id: u32, num_items: usize) -> Vec<Recommendation> {
+            (1..=10)
+                .map(|item_id| Recommendation {
+                    item_id,
+                    score: 1.0 / item_id as f32,
+                })
+                .take(num_items)
+                .collect()
+        }
+    }
+
+    fn query(debug: bool) -> (Vec<Recommendation>, Option<QueryTrace>) {
+        Query::new(&Fixed, 1, 8)
+            .set_debug(debug)
+            .filter("even", |r| r.item_id % 2 == 0)
+            .rerank("boost", |list| {
+                list.iter()
+                    .map(|r| Recommendation {
+                        item_id: r.item_id,
+                        score: if r.item_id == 8 { 1.0 } else { r.score },
+                    })
+                    .collect()
+            })
+            .rerank("sort", |list| {
+                let mut sorted = list.to_vec();
+                sorted.sort_by(|a, b| b.score.total_cmp(&a.score));
+                sorted
+            })
+            .finish(2)
+    }
+
+    #[test]
+    fn test_trace() {
+        let (served, trace) = query(true);
+        let trace = trace.unwrap();
+        assert_eq!(trace.candidates.len(), 8);
+        assert_eq!(trace.recommendations, served);
+        assert_eq!(trace.position(8), Some(0));
+        let names: Vec<&str> = trace.steps.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["even", "boost", "sort", "truncate"]);
+        assert_eq!(trace.steps[0].num_items, 4);
+        assert_eq!(
+            trace.explain(8),
+            [(
+                "boost",
+                &Change::Rescored {
+                    item_id: 8,
+                    before: 0.125,
+                    after: 1.0
+                }
+            )]
+        );
+        assert_eq!(trace.explain(3)[0].0, "even");
+        assert_eq!(trace.explain(2), []);
+        assert_eq!(trace.explain(6)[0].0, "truncate");
+        assert!(trace.steps[2].changes.is_empty());
+        let json = trace.to_json().unwrap();
+        let parsed: QueryTrace = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, trace);
+    }
+
+    #[test]
+    fn test_without_debug() {
+        let (served, trace) = query(false);
+        assert!(trace.is_none());
+        assert_eq!(served, query(true).0);
+        let ids: Vec<u32> = served.iter().map(|r| r.item_id).collect();
+        assert_eq!(ids, [8, 2]);
+    }
+
+    #[test]
+    fn test_available() {
+        let mut catalog = ItemCatalog::new(Schema::new());
+        for item_id in 1..=3 {
+            catalog.insert(item_id, Attributes::new()).unwrap();
+        }
+        catalog.set_validity(1, Validity::with_ttl(0, 10)).unwrap();
+        catalog
+            .set_validity(3, Validity::between(Some(100), None))
+            .unwrap();
+        let (served, trace) = Query::new(&Fixed, 1, 4)
+            .set_debug(true)
+            .available(&catalog, 50)
+            .finish(2);
+        let ids: Vec<u32> = served.iter().map(|r| r.item_id).collect();
+        assert_eq!(ids, [2, 4]);
+        let trace = trace.unwrap();
+        let reasons: Vec<Option<&str>> = trace.steps[0]
+            .changes
+            .iter()
+            .map(|change| match change {
+                Change::Removed { reason, .. } => reason.as_deref(),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(reasons, [Some("expired"), Some("not valid yet")]);
+    }
+
+    #[test]
+    fn test_diff() {
+        let list = |items: &[(u32, f32)]| -> Vec<Recommendation> {
+            items
+                .iter()
+                .map(|&(item_id, score)| Recommendation { item_id, score })
+                .collect()
+        };
+        let changes = diff(&list(&[(1, 0.5), (2, 0.4)]), &list(&[(3, 0.5), (1, 0.5)]));
+        assert_eq!(
+            changes,
+            [
+                Change::Removed {
+                    item_id: 2,
+                    score: 0.4,
+                    reason: None
+                },
+                Change::Added {
+                    item_id: 3,
+                    score: 0.5
+                },
+            ]
+        );
+    }
+}
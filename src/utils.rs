@@ -1,7 +1,9 @@
 //! # A collection of tools
 //!
 
+use crate::algorithms::knn::{to_similarity, KNN};
 use crate::models::Item;
+use crate::similarity::SimilarityAlgos;
 /// # Dot product
 /// Calculates the dot product between two vectors.
 ///
@@ -27,7 +29,7 @@ pub fn euclidean_norm(x: &[f32]) -> f32 {
     x.iter().map(|&a| a * a).sum::<f32>().sqrt()
 }
 
-/// TODO
+/// Sum of the squared differences between two vectors.
 pub fn squared_diff_sum(x: &[f32], y: &[f32]) -> f32 {
     x.iter()
         .zip(y.iter())
@@ -35,11 +37,112 @@ pub fn squared_diff_sum(x: &[f32], y: &[f32]) -> f32 {
         .sum::<f32>()
 }
 
-/// TODO
+/// Sorts the values in place, in increasing order.
 pub fn local_sort(v: &mut [f32]) {
     v.sort_by(|x: &f32, y: &f32| x.total_cmp(y))
 }
 
+/// How close two floats must be to be considered equal by [`approx_eq`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tolerance {
+    /// The difference is at most the epsilon, suits values close to 0.
+    Absolute(f32),
+    /// The difference is at most the epsilon times the largest magnitude, suits
+    /// values of any scale.
+    Relative(f32),
+    /// At most that many representable floats lie between the two values, suits
+    /// results that only differ by rounding.
+    Ulps(u32),
+}
+
+/// Relative, 10 times the machine epsilon: a few rounding errors.
+impl Default for Tolerance {
+    fn default() -> Self {
+        Tolerance::Relative(10.0 * f32::EPSILON)
+    }
+}
+
+/// # Approximate equality
+/// Whether two floats are equal up to a tolerance, instead of bitwise like `==`
+/// which fails on results computed in another order, e.g. by another platform or
+/// a vectorized kernel. NaN is never equal to anything and the infinities are only
+/// equal to themselves.
+///
+/// ## Parameters:
+/// * `a`: The first value.
+/// * `b`: The second value.
+/// * `tolerance`: How close they must be.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::utils::{approx_eq, Tolerance};
+/// assert!(0.1 + 0.2 != 0.3_f32 + f32::EPSILON);
+/// assert!(approx_eq(0.1 + 0.2, 0.3, Tolerance::default()));
+/// assert!(approx_eq(1.0, 1.0 + f32::EPSILON, Tolerance::Ulps(1)));
+/// assert!(!approx_eq(1e-3, 2e-3, Tolerance::Relative(0.1)));
+/// assert!(approx_eq(1e-3, 2e-3, Tolerance::Absolute(0.01)));
+/// assert!(!approx_eq(f32::NAN, f32::NAN, Tolerance::Absolute(1.0)));
+/// ```
+pub fn approx_eq(a: f32, b: f32, tolerance: Tolerance) -> bool {
+    if a == b {
+        return true;
+    }
+    if !a.is_finite() || !b.is_finite() {
+        return false;
+    }
+    match tolerance {
+        Tolerance::Absolute(epsilon) => (a - b).abs() <= epsilon,
+        Tolerance::Relative(epsilon) => (a - b).abs() <= epsilon * a.abs().max(b.abs()),
+        Tolerance::Ulps(ulps) => {
+            // Maps the floats onto integers whose difference counts the floats
+            // between them, across 0 as well.
+            let ordered = |x: f32| {
+                let bits = x.to_bits() as i32;
+                match bits < 0 {
+                    true => i32::MIN - bits,
+                    false => bits,
+                }
+            };
+            (ordered(a) as i64 - ordered(b) as i64).unsigned_abs() <= ulps as u64
+        },
+    }
+}
+
+/// Whether two slices have the same length and [`approx_eq`] values.
+pub fn approx_eq_slice(a: &[f32], b: &[f32], tolerance: Tolerance) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(a, b)| approx_eq(*a, *b, tolerance))
+}
+
+/// # Assert approximately equal
+/// Asserts that two floats are [`approx_eq`](crate::utils::approx_eq), with the
+/// default [`Tolerance`](crate::utils::Tolerance) unless one is given, e.g. in the
+/// tests of adapters built on the crate.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::assert_approx_eq;
+/// use rec_rsys::similarity::cosine_similarity;
+/// use rec_rsys::utils::Tolerance;
+/// assert_approx_eq!(cosine_similarity(&[1.0, 1.0], &[1.0, 0.0]), 0.707_106_8);
+/// assert_approx_eq!(1.0, 1.001, Tolerance::Absolute(1e-2));
+/// ```
+#[macro_export]
+macro_rules! assert_approx_eq {
+    ($a:expr, $b:expr $(,)?) => {
+        $crate::assert_approx_eq!($a, $b, $crate::utils::Tolerance::default())
+    };
+    ($a:expr, $b:expr, $tolerance:expr $(,)?) => {{
+        let (a, b, tolerance): (f32, f32, $crate::utils::Tolerance) = ($a, $b, $tolerance);
+        assert!(
+            $crate::utils::approx_eq(a, b, tolerance),
+            "assertion failed: `left ≈ right`\n  left: {:?}\n right: {:?}\n tolerance: {:?}",
+            a,
+            b,
+            tolerance
+        );
+    }};
+}
+
 /// Function to calculate the ranks of the values in a vector.
 ///
 /// ## Parameters:
@@ -64,7 +167,7 @@ pub fn argsort(x: &[f32]) -> Vec<f32> {
 /// ## Parameters:
 /// * `v`: The vector to be sorted.
 /// * `compare_fn`: The comparison function that compares two elements and returns an `Ordering`.
-///                  It should take two references to elements of type `T` and return an `Ordering` value.
+///   It should take two references to elements of type `T` and return an `Ordering` value.
 /// * `reverse`: A flag indicating whether to sort the elements in reverse order.
 ///
 /// ## Example
@@ -89,7 +192,17 @@ where
     }
 }
 
-pub fn sort_and_trucate(
+/// # Sort and truncate
+/// Keeps the `k` items with the best result.
+///
+/// ## Parameters:
+/// * `best_matches`: The items with their result.
+/// * `reverse`: Whether a higher result is better.
+/// * `k`: The number of items to keep.
+///
+/// ## Returns:
+/// * The `k` best items, best first.
+pub fn sort_and_truncate(
     mut best_matches: Vec<Item>,
     reverse: bool,
     k: usize,
@@ -103,6 +216,152 @@ pub fn sort_and_trucate(
     best_matches
 }
 
+#[deprecated(since = "1.1.0", note = "renamed to `sort_and_truncate`")]
+pub fn sort_and_trucate(best_matches: Vec<Item>, reverse: bool, k: usize) -> Vec<Item> {
+    sort_and_truncate(best_matches, reverse, k)
+}
+
+/// # Centroid
+/// Calculates the mean of the vectors of the items.
+///
+/// ## Parameters:
+/// * `items`: The items, all with vectors of the same length.
+///
+/// ## Returns:
+/// * The centroid, empty when there are no items.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::models::Item;
+/// use rec_rsys::utils::centroid;
+/// let items = vec![Item::new(1, vec![1.0, 2.0], None), Item::new(2, vec![3.0, 4.0], None)];
+/// assert_eq!(centroid(&items), vec![2.0, 3.0]);
+/// ```
+///
+#[doc = include_str!("../docs/utils/centroid.md")]
+pub fn centroid(items: &[Item]) -> Vec<f32> {
+    weighted_centroid(items, &vec![1.0; items.len()])
+}
+
+/// # Weighted centroid
+/// Calculates the weighted mean of the vectors of the items.
+///
+/// ## Parameters:
+/// * `items`: The items, all with vectors of the same length.
+/// * `weights`: The weight of each item.
+///
+/// ## Returns:
+/// * The centroid, empty when there are no items or the weights add up to 0.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::models::Item;
+/// use rec_rsys::utils::weighted_centroid;
+/// let items = vec![Item::new(1, vec![0.0, 2.0], None), Item::new(2, vec![4.0, 6.0], None)];
+/// assert_eq!(weighted_centroid(&items, &[3.0, 1.0]), vec![1.0, 3.0]);
+/// ```
+///
+#[doc = include_str!("../docs/utils/centroid.md")]
+pub fn weighted_centroid(items: &[Item], weights: &[f32]) -> Vec<f32> {
+    let total: f32 = weights.iter().take(items.len()).sum();
+    let dimensions = items.first().map_or(0, |item| item.values.len());
+    if total == 0.0 {
+        return Vec::new();
+    }
+    items
+        .iter()
+        .zip(weights)
+        .fold(vec![0.0; dimensions], |mut sum, (item, weight)| {
+            sum.iter_mut()
+                .zip(&item.values)
+                .for_each(|(s, value)| *s += weight * value);
+            sum
+        })
+        .into_iter()
+        .map(|sum| sum / total)
+        .collect()
+}
+
+/// # Medoid
+/// Finds the item the most similar to all the others. Unlike the centroid, the
+/// medoid is one of the items and any metric can be used.
+///
+/// ## Parameters:
+/// * `items`: The items.
+/// * `metric`: The similarity between the items, distances are turned into
+///   similarities.
+///
+/// ## Returns:
+/// * The medoid, `None` when there are no items.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::models::Item;
+/// use rec_rsys::similarity::SimilarityAlgos;
+/// use rec_rsys::utils::medoid;
+/// let items = vec![
+///     Item::new(1, vec![0.0, 0.0], None),
+///     Item::new(2, vec![1.0, 1.0], None),
+///     Item::new(3, vec![3.0, 3.0], None),
+/// ];
+/// assert_eq!(medoid(&items, SimilarityAlgos::Euclidean).unwrap().id, 2);
+/// ```
+///
+#[doc = include_str!("../docs/utils/medoid.md")]
+pub fn medoid(items: &[Item], metric: SimilarityAlgos) -> Option<&Item> {
+    weighted_medoid(items, &vec![1.0; items.len()], metric)
+}
+
+/// # Weighted medoid
+/// Finds the item with the highest weighted similarity to all the others.
+///
+/// ## Parameters:
+/// * `items`: The items.
+/// * `weights`: The weight of each item, how much being close to it matters.
+/// * `metric`: The similarity between the items, distances are turned into
+///   similarities.
+///
+/// ## Returns:
+/// * The medoid, `None` when there are no items.
+///
+/// ## Examples:
+/// ```
+/// use rec_rsys::models::Item;
+/// use rec_rsys::similarity::SimilarityAlgos;
+/// use rec_rsys::utils::weighted_medoid;
+/// let items = vec![
+///     Item::new(1, vec![0.0, 0.0], None),
+///     Item::new(2, vec![1.0, 1.0], None),
+///     Item::new(3, vec![3.0, 3.0], None),
+/// ];
+/// let medoid = weighted_medoid(&items, &[1.0, 1.0, 10.0], SimilarityAlgos::Euclidean);
+/// assert_eq!(medoid.unwrap().id, 3);
+/// ```
+///
+#[doc = include_str!("../docs/utils/medoid.md")]
+pub fn weighted_medoid<'a>(
+    items: &'a [Item],
+    weights: &[f32],
+    metric: SimilarityAlgos,
+) -> Option<&'a Item> {
+    let (formula, _) = KNN::get_formula(&metric);
+    items
+        .iter()
+        .map(|item| {
+            let total: f32 = items
+                .iter()
+                .zip(weights)
+                .map(|(other, weight)| {
+                    weight * to_similarity(metric, formula(&item.values, &other.values))
+                })
+                .filter(|similarity| similarity.is_finite())
+                .sum();
+            (item, total)
+        })
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(item, _)| item)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -122,6 +381,18 @@ mod tests {
     }
 
     #[test]
+    fn test_sort_and_truncate() {
+        let item1 = Item::new(1, vec![0.1], Some(0.2));
+        let item2 = Item::new(2, vec![0.3], Some(0.7));
+        let item3 = Item::new(3, vec![0.5], Some(0.4));
+        assert_eq!(
+            sort_and_truncate(vec![item1.clone(), item2, item3.clone()], false, 2),
+            vec![item1, item3]
+        );
+    }
+
+    #[test]
+    #[allow(deprecated)]
     fn test_sort_and_trucate() {
         let item1 = Item::new(1, vec![0.9193, 0.9097, 0.4990, 0.3292, 0.8811], Some(1.0));
         let item2 =
@@ -143,7 +414,7 @@ mod tests {
 
     #[test]
     fn test_euclidean_norm() {
-        assert_eq!(euclidean_norm(&[3.0, 45.0, 7.0, 2.0]), 45.683_697,);
+        crate::assert_approx_eq!(euclidean_norm(&[3.0, 45.0, 7.0, 2.0]), 45.683_697);
     }
 
     #[test]
@@ -154,8 +425,75 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_approx_eq() {
+        assert!(approx_eq(0.0, -0.0, Tolerance::Ulps(0)));
+        assert!(approx_eq(
+            -f32::MIN_POSITIVE / 2.0,
+            f32::MIN_POSITIVE / 2.0,
+            Tolerance::Ulps(1 << 23)
+        ));
+        assert!(!approx_eq(
+            1.0,
+            1.0 + 2.0 * f32::EPSILON,
+            Tolerance::Ulps(1)
+        ));
+        assert!(approx_eq(f32::INFINITY, f32::INFINITY, Tolerance::Ulps(0)));
+        assert!(!approx_eq(
+            f32::INFINITY,
+            f32::MAX,
+            Tolerance::Relative(1.0)
+        ));
+        assert!(approx_eq(1e6, 1e6 + 0.1, Tolerance::default()));
+        assert!(!approx_eq(1e-6, 2e-6, Tolerance::default()));
+        assert!(approx_eq_slice(
+            &[1.0, 0.1 + 0.2],
+            &[1.0, 0.3],
+            Tolerance::default()
+        ));
+        assert!(!approx_eq_slice(&[1.0], &[1.0, 2.0], Tolerance::default()));
+    }
+
+    #[test]
+    #[should_panic(expected = "left ≈ right")]
+    fn test_assert_approx_eq() {
+        crate::assert_approx_eq!(1.0, 1.1);
+    }
+
     #[test]
     fn test_argsort() {
         assert_eq!(argsort(&[3.0, 45.0, 7.0, 2.0]), vec![3.0, 0.0, 2.0, 1.0],);
     }
+
+    #[test]
+    fn test_centroid() {
+        let items = vec![
+            Item::new(1, vec![1.0, 0.0, 2.0], None),
+            Item::new(2, vec![3.0, 2.0, 2.0], None),
+        ];
+        assert_eq!(centroid(&items), vec![2.0, 1.0, 2.0]);
+        assert_eq!(weighted_centroid(&items, &[1.0, 0.0]), vec![1.0, 0.0, 2.0]);
+        assert!(centroid(&[]).is_empty());
+        assert!(weighted_centroid(&items, &[0.0, 0.0]).is_empty());
+    }
+
+    #[test]
+    fn test_medoid() {
+        let items = vec![
+            Item::new(1, vec![1.0, 0.0], None),
+            Item::new(2, vec![1.0, 1.0], None),
+            Item::new(3, vec![0.0, 1.0], None),
+            Item::new(4, vec![0.9, 1.1], None),
+        ];
+        assert_eq!(medoid(&items, SimilarityAlgos::Cosine).unwrap().id, 2);
+        assert_eq!(medoid(&items, SimilarityAlgos::Euclidean).unwrap().id, 2);
+        let weights = [5.0, 1.0, 0.0, 0.0];
+        assert_eq!(
+            weighted_medoid(&items, &weights, SimilarityAlgos::Cosine)
+                .unwrap()
+                .id,
+            1
+        );
+        assert!(medoid(&[], SimilarityAlgos::Cosine).is_none());
+    }
 }
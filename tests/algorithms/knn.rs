@@ -1,4 +1,5 @@
 use rec_rsys::algorithms::knn::KNN;
+use rec_rsys::assert_approx_eq;
 use rec_rsys::models::Item;
 use rec_rsys::similarity::SimilarityAlgos;
 
@@ -25,9 +26,9 @@ fn test_cosine_knn() {
         .set_num_neighbors(3)
         .result();
     assert_eq!(result, vec![new_item, &refs[1], &refs[9]]);
-    assert_eq!(result[0].result, 1.0000001);
-    assert_eq!(result[1].result, 0.969_654_7);
-    assert_eq!(result[2].result, 0.94337976);
+    assert_approx_eq!(result[0].result, 1.0);
+    assert_approx_eq!(result[1].result, 0.969_654_7);
+    assert_approx_eq!(result[2].result, 0.94337976);
 }
 
 #[test]
@@ -40,6 +41,6 @@ fn test_euclidean_knn() {
         .result();
     assert_eq!(result, vec![new_item, &refs[1], &refs[9]]);
     assert_eq!(result[0].result, 0.0);
-    assert_eq!(result[1].result, 0.4905142);
-    assert_eq!(result[2].result, 0.5744563);
+    assert_approx_eq!(result[1].result, 0.4905142);
+    assert_approx_eq!(result[2].result, 0.5744563);
 }
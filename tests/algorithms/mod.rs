@@ -1,3 +1 @@
 pub mod knn;
-
-pub use knn::*;
@@ -0,0 +1,83 @@
+//! The item adapters generated by `#[derive(ItemAdapter)]`.
+use rec_rsys::models::{Item, ItemAdapter, OneHot};
+
+#[derive(OneHot, Clone, Copy)]
+enum Genre {
+    Drama,
+    Comedy,
+    Horror,
+}
+
+#[derive(ItemAdapter)]
+#[item(references = "sequels")]
+struct Movie {
+    #[item(id)]
+    movie_id: u16,
+    year: i32,
+    rating: f64,
+    #[item(one_hot)]
+    genre: Genre,
+    #[item(one_hot = ["en", "fr"])]
+    language: &'static str,
+    #[item(skip)]
+    title: String,
+}
+
+impl Movie {
+    fn sequels(&self) -> Vec<Item> {
+        vec![Item::new(u32::from(self.movie_id) + 1, vec![0.0], None)]
+    }
+}
+
+#[derive(ItemAdapter)]
+struct Tagged<T> {
+    id: u32,
+    weight: f32,
+    #[item(skip)]
+    tag: T,
+}
+
+#[test]
+fn test_one_hot_enum() {
+    assert_eq!(Genre::NUM_CATEGORIES, 3);
+    assert_eq!(Genre::Comedy.category(), 1);
+    assert_eq!(Genre::Horror.one_hot(), [0.0, 0.0, 1.0]);
+}
+
+#[test]
+fn test_item_adapter() {
+    let movie = Movie {
+        movie_id: 12,
+        year: 1999,
+        rating: 4.5,
+        genre: Genre::Drama,
+        language: "fr",
+        title: "Ma vie".to_string(),
+    };
+    assert_eq!(movie.title, "Ma vie");
+    let item = movie.to_item();
+    assert_eq!(item.id, 12);
+    assert_eq!(item.values, [1999.0, 4.5, 1.0, 0.0, 0.0, 0.0, 1.0]);
+    assert!(item.result.is_nan());
+    let references = movie.get_references();
+    assert_eq!(references.len(), 1);
+    assert_eq!(references[0].id, 13);
+    // An unknown language is encoded with zeros.
+    let movie = Movie {
+        language: "de",
+        ..movie
+    };
+    assert_eq!(movie.create_values()[5..], [0.0, 0.0]);
+}
+
+#[test]
+fn test_generic_item_adapter() {
+    let tagged = Tagged {
+        id: 3,
+        weight: 2.0,
+        tag: vec!["new"],
+    };
+    assert_eq!(tagged.tag, ["new"]);
+    assert_eq!(tagged.to_item().values, [2.0]);
+    assert!(tagged.get_references().is_empty());
+}
@@ -0,0 +1,52 @@
+//! Runs the examples on their bundled sample data.
+// Every example declares the module of the shared loaders.
+#![allow(clippy::duplicate_mod)]
+#[path = "../../examples/common/mod.rs"]
+mod common;
+#[allow(dead_code)]
+#[path = "../../examples/content_based.rs"]
+mod content_based;
+#[allow(dead_code)]
+#[path = "../../examples/hybrid.rs"]
+mod hybrid;
+#[allow(dead_code)]
+#[path = "../../examples/movielens_mf.rs"]
+mod movielens_mf;
+
+use std::collections::HashSet;
+
+fn rated_by(user_id: u32) -> HashSet<u32> {
+    common::load_ratings()
+        .unwrap()
+        .ratings
+        .iter()
+        .filter(|r| r.user_id == user_id)
+        .map(|r| r.item_id)
+        .collect()
+}
+
+#[test]
+fn test_content_based_example() {
+    let recommendations = content_based::run(1).unwrap();
+    assert_eq!(recommendations.len(), 5);
+    let rated = rated_by(1);
+    assert!(recommendations.iter().all(|r| !rated.contains(&r.item_id)));
+}
+
+#[test]
+fn test_movielens_mf_example() {
+    let summary = movielens_mf::run(&common::load_ratings().unwrap()).unwrap();
+    assert!(summary.rmse < 1.3, "rmse {}", summary.rmse);
+    assert!(summary.train_losses.last() < summary.train_losses.first());
+    assert!(summary.report.users > 0);
+}
+
+#[test]
+fn test_hybrid_example() {
+    let collaborative = hybrid::run(1, 1.0).unwrap();
+    let blended = hybrid::run(1, 0.5).unwrap();
+    assert_eq!(blended.len(), 5);
+    assert_ne!(collaborative, blended);
+    let rated = rated_by(1);
+    assert!(blended.iter().all(|r| !rated.contains(&r.item_id)));
+}
@@ -1,3 +1,5 @@
 pub mod algorithms;
-
-pub use algorithms::*;
+#[cfg(feature = "derive")]
+pub mod derive;
+#[cfg(feature = "serde")]
+pub mod examples;